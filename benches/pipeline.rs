@@ -0,0 +1,60 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use ::cryptotax::cryptotax::{write_tax, Options};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use tempfile::NamedTempFile;
+
+/// A Revolut account statement with `rows` exchange rows, newest first: buys of DOGE for SEK, and
+/// sales of part of the DOGE held, ten minutes apart.
+fn synthetic_file(rows: usize) -> io::Result<NamedTempFile> {
+    let mut file = NamedTempFile::new()?;
+    let mut out = io::BufWriter::new(file.as_file_mut());
+    writeln!(out, "Type,Started Date,Completed Date,Description,Amount,Fee,Currency,Original Amount,Original Currency,Settled Amount,Settled Currency,State,Balance")?;
+    let mut exchanges = vec![];
+    let mut held = 0;
+    for i in 0..rows / 2 {
+        let minutes = 10 * i as i64;
+        let date = format!("{} {:02}:{:02}:00", day(minutes / (24 * 60)), minutes / 60 % 24, minutes % 60);
+        let (amount, price) = (1 + i * 7919 % 1000, 1 + i * 104729 % 100);
+        let value = amount * price;
+        match i % 3 == 2 && held >= amount {
+            true => {
+                held -= amount;
+                exchanges.push(format!("Exchange,{d},{d},Exchanged to SEK,-{},0,DOGE,-{},DOGE,,,Completed,", amount, amount, d = date));
+                exchanges.push(format!("Exchange,{d},{d},Exchanged from DOGE,{},0,SEK,{},SEK,,,Completed,", value, value, d = date));
+            }
+            false => {
+                held += amount;
+                exchanges.push(format!("Exchange,{d},{d},Exchanged to DOGE,-{},0,SEK,-{},SEK,,,Completed,", value, value, d = date));
+                exchanges.push(format!("Exchange,{d},{d},Exchanged from SEK,{},0,DOGE,{},DOGE,,,Completed,", amount, amount, d = date));
+            }
+        }
+    }
+    exchanges.iter().rev().try_for_each(|row| writeln!(out, "{}", row))?;
+    drop(out);
+    Ok(file)
+}
+
+/// The date `days` after 2000-01-01, in a calendar of 28-day months that is good enough here.
+fn day(days: i64) -> String {
+    format!("{:04}-{:02}-{:02}", 2000 + days / 336, 1 + days / 28 % 12, 1 + days % 28)
+}
+
+/// Parses, pairs and calculates the tax report of files of growing size.
+fn tax_report(c: &mut Criterion) {
+    let mut group = c.benchmark_group("tax_report");
+    group.sample_size(10);
+    for rows in [10_000, 100_000, 1_000_000] {
+        let file = synthetic_file(rows).expect("Could not write the synthetic file");
+        let path = PathBuf::from(file.path());
+        let (currency, base, options) = ("DOGE".to_string(), "SEK".to_string(), Options::default());
+        group.throughput(Throughput::Elements(rows as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(rows), &path, |b, path| {
+            b.iter(|| write_tax(path, &currency, &base, &options, io::sink()).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, tax_report);
+criterion_main!(benches);
@@ -0,0 +1,15 @@
+#![no_main]
+
+//! Feeds arbitrary bytes to the reader of the Revolut account statements. Whatever the file, the
+//! tax report must be written or fail with an error, and never panic. Bytes that can't be read
+//! fail with a `Parse` error, as a statement in memory has no I/O to fail.
+
+use ::cryptotax::cryptotax::{write_statement_tax, CryptotaxError, Options};
+use libfuzzer_sys::fuzz_target;
+use std::io;
+
+fuzz_target!(|statement: &[u8]| {
+    let options = Options::default();
+    let result = write_statement_tax(statement, &"BTC".to_string(), &"SEK".to_string(), &options, io::sink());
+    assert!(!matches!(result, Err(CryptotaxError::Io(_))), "{:?}", result);
+});
@@ -0,0 +1,180 @@
+#![no_main]
+
+//! Feeds account statements that look like the Revolut exports to the reader: rows of the known
+//! types, descriptions and currencies, with arbitrary dates, amounts, fees, states and balances,
+//! in English or Swedish, and of the current or the legacy columns. These get past the parsing of
+//! the rows, into the pairing and the calculations, which must not panic either, with the
+//! invariants checked. Every row is read, but the one whose amount is `corrupt`, if any, which
+//! fails with a `Parse` error at its line.
+
+use ::cryptotax::cryptotax::{write_statement_tax, CryptotaxError, Options};
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use std::fmt::Write;
+use std::io;
+
+#[derive(Debug, Arbitrary)]
+struct Statement {
+    rows: Vec<Row>,
+    swedish: bool,
+    legacy: bool,
+    corrupt: Option<u16>,       // The index of the row whose amount is not a number
+}
+
+#[derive(Debug, Arbitrary)]
+struct Row {
+    r#type: Type,
+    minutes: u32,               // After 2020-01-01
+    completed: Option<i16>,     // Minutes after the started date
+    description: Description,
+    amount: i64,
+    fee: i32,
+    decimals: u8,
+    currency: Currency,
+    state: State,
+    balance: Option<i64>,
+}
+
+#[derive(Debug, Arbitrary)]
+enum Type {
+    Exchange,
+    CardPayment,
+    Transfer,
+    Topup,
+    Cashback,
+    Buy,
+    Sell,
+    Interest,
+    Other(String),
+}
+
+#[derive(Debug, Arbitrary)]
+enum Description {
+    ExchangedTo(Currency),
+    ExchangedFrom(Currency),
+    ExchangedToVault(Currency),
+    FromVault(Currency),
+    Other(String),
+}
+
+#[derive(Debug, Arbitrary, Clone, Copy)]
+enum Currency {
+    Btc,
+    Eth,
+    Doge,
+    Sek,
+    Eur,
+}
+
+#[derive(Debug, Arbitrary)]
+enum State {
+    Completed,
+    Pending,
+    Reverted,
+    Declined,
+}
+
+impl Currency {
+    fn code(&self) -> &'static str {
+        match self {
+            Currency::Btc => "BTC",
+            Currency::Eth => "ETH",
+            Currency::Doge => "DOGE",
+            Currency::Sek => "SEK",
+            Currency::Eur => "EUR",
+        }
+    }
+}
+
+/// The date `minutes` after 2020-01-01, in a calendar of 28-day months that is good enough here.
+fn date(minutes: i64) -> String {
+    let days = minutes.div_euclid(24 * 60);
+    format!("{:04}-{:02}-{:02} {:02}:{:02}:00",
+            2020 + days / 336, 1 + days / 28 % 12, 1 + days % 28, minutes.rem_euclid(24 * 60) / 60, minutes.rem_euclid(60))
+}
+
+impl Statement {
+    /// The line of the row whose amount is not a number, counting from 1 for the header.
+    fn corrupt_line(&self) -> Option<u64> {
+        self.corrupt.filter(|_| !self.rows.is_empty()).map(|i| usize::from(i) % self.rows.len() + 2).map(|line| line as u64)
+    }
+
+    fn to_csv(&self) -> String {
+        let (delimiter, decimal) = match self.swedish {
+            true => (';', ','),
+            false => (',', '.'),
+        };
+        let number = |value: i64, decimals: u8| {
+            let decimals = u32::from(decimals % 10);
+            let (sign, value) = (if value < 0 { "-" } else { "" }, value.unsigned_abs());
+            let scale = 10u64.pow(decimals);
+            match decimals {
+                0 => format!("{}{}", sign, value),
+                _ => format!("{}{}{}{:0width$}", sign, value / scale, decimal, value % scale, width = decimals as usize),
+            }
+        };
+        let header: &[&str] = match (self.swedish, self.legacy) {
+            (false, false) => &["Type", "Started Date", "Completed Date", "Description", "Amount", "Fee", "Currency",
+                                "Original Amount", "Original Currency", "Settled Amount", "Settled Currency", "State", "Balance"],
+            (true, false) => &["Typ", "Startdatum", "Slutförandedatum", "Beskrivning", "Belopp", "Avgift", "Valuta",
+                               "Ursprungligt belopp", "Ursprunglig valuta", "Avräknat belopp", "Avräknad valuta", "Status", "Saldo"],
+            (false, true) => &["Type", "Product", "Started Date", "Completed Date", "Description", "Amount", "Fee", "Currency", "State", "Balance"],
+            (true, true) => &["Typ", "Produkt", "Startdatum", "Slutförandedatum", "Beskrivning", "Belopp", "Avgift", "Valuta", "Status", "Saldo"],
+        };
+        let mut csv = header.join(&delimiter.to_string());
+        for (i, row) in self.rows.iter().enumerate() {
+            let started = date(i64::from(row.minutes % (10 * 336 * 24 * 60)));
+            let completed = row.completed.map(|c| date(i64::from(row.minutes % (10 * 336 * 24 * 60)) + i64::from(c))).unwrap_or_default();
+            let r#type = match &row.r#type {
+                Type::Exchange => "EXCHANGE".to_string(),
+                Type::CardPayment => "CARD_PAYMENT".to_string(),
+                Type::Transfer => "TRANSFER".to_string(),
+                Type::Topup => "TOPUP".to_string(),
+                Type::Cashback => "CASHBACK".to_string(),
+                Type::Buy => "BUY".to_string(),
+                Type::Sell => "SELL".to_string(),
+                Type::Interest => "INTEREST".to_string(),
+                Type::Other(name) => name.replace([',', ';', '\n', '\r', '"'], ""),
+            };
+            let description = match &row.description {
+                Description::ExchangedTo(c) => format!("Exchanged to {}", c.code()),
+                Description::ExchangedFrom(c) => format!("Exchanged from {}", c.code()),
+                Description::ExchangedToVault(c) => format!("Exchanged to {c} {c} Vault", c = c.code()),
+                Description::FromVault(c) => format!("From {} Vault", c.code()),
+                Description::Other(text) => text.replace([',', ';', '\n', '\r', '"'], ""),
+            };
+            let state = match row.state {
+                State::Completed => "COMPLETED",
+                State::Pending => "PENDING",
+                State::Reverted => "REVERTED",
+                State::Declined => "DECLINED",
+            };
+            let (mut amount, fee) = (number(row.amount, row.decimals), number(i64::from(row.fee), row.decimals));
+            if self.corrupt_line() == Some(i as u64 + 2) {
+                amount.push('x');
+            }
+            let balance = row.balance.map(|b| number(b, row.decimals)).unwrap_or_default();
+            let currency = row.currency.code();
+            let fields = match self.legacy {
+                false => vec![r#type, started, completed, description, amount.clone(), fee, currency.to_string(),
+                              amount, currency.to_string(), String::new(), String::new(), state.to_string(), balance],
+                true => vec![r#type, "Current".to_string(), started, completed, description, amount, fee, currency.to_string(),
+                             state.to_string(), balance],
+            };
+            let _ = write!(csv, "\n{}", fields.join(&delimiter.to_string()));
+        }
+        csv
+    }
+}
+
+fuzz_target!(|statement: Statement| {
+    let options = Options{ check: true, ..Default::default() };
+    let csv = statement.to_csv();
+    for currency in ["BTC", "DOGE"] {
+        let result = write_statement_tax(csv.as_bytes(), &currency.to_string(), &"SEK".to_string(), &options, io::sink());
+        match statement.corrupt_line() {
+            Some(line) => assert!(matches!(&result, Err(CryptotaxError::Parse{ line: l, .. }) if *l == Some(line)), "{:?}", result),
+            None => assert!(!matches!(result, Err(CryptotaxError::Parse{..})), "{:?}", result),
+        }
+    }
+});
@@ -0,0 +1,111 @@
+use crate::calculator::{Calculation, Holding};
+use crate::error::Result;
+use crate::transaction::{Currency, Transaction};
+use std::collections::BTreeMap;
+use tracing::{debug, instrument};
+
+/// The transactions of each account, by the file that they were read from, e.g. each csv file of
+/// a zip archive. The transactions keep their order.
+pub(crate) fn split(txns: &[Transaction]) -> BTreeMap<&str, Vec<Transaction>> {
+    let mut accounts: BTreeMap<&str, Vec<Transaction>> = BTreeMap::new();
+    txns.iter().for_each(|t| accounts.entry(t.origin.file.as_str()).or_default().push(t.clone()));
+    accounts
+}
+
+/// Calculates the accounts one at a time with `calculate`, each with a cost basis of its own, see
+/// `BasisScope::PerAccount`, and merges the calculations into one of the currency. A transfer
+/// between two accounts does not move any cost, as it is not matched with the other side.
+#[instrument(name = "account", skip_all)]
+pub(crate) fn calculate<F>(txns: &[Transaction], currency: &Currency, mut calculate: F) -> Result<Calculation>
+    where F: FnMut(&[Transaction]) -> Result<Calculation>
+{
+    let mut merged = Calculation{
+        taxables: vec![],
+        non_taxables: vec![],
+        derivatives: vec![],
+        income: vec![],
+        exempt: vec![],
+        holding: Holding{ currency: currency.clone(), ..Default::default() },
+        carryover: vec![],
+    };
+    for (account, txns) in split(txns) {
+        debug!(%account, txns = txns.len(), "Calculating account");
+        let calculation = calculate(&txns)?;
+        merged.taxables.extend(calculation.taxables);
+        merged.non_taxables.extend(calculation.non_taxables);
+        merged.derivatives.extend(calculation.derivatives);
+        merged.income.extend(calculation.income);
+        merged.exempt.extend(calculation.exempt);
+        merged.holding.amount += calculation.holding.amount;
+        merged.holding.costs.extend(calculation.holding.costs);
+        merged.holding.vault += calculation.holding.vault;
+        merged.holding.vault_costs.extend(calculation.holding.vault_costs);
+        merged.carryover.extend(calculation.carryover.into_iter().map(|mut t| {
+            t.origin.file = account.to_string();    // Stays in the account in the next calculation
+            t
+        }));
+    }
+    merged.taxables.sort_by(|a, b| a.date.cmp(&b.date));
+    merged.non_taxables.sort_by(|a, b| a.date.cmp(&b.date));
+    merged.derivatives.sort_by(|a, b| a.date.cmp(&b.date));
+    merged.income.sort_by(|a, b| a.date.cmp(&b.date));
+    merged.exempt.sort_by(|a, b| a.date.cmp(&b.date));
+    merged.carryover.sort_by(Transaction::cmp_by_date);
+    Ok(merged)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::account::*;
+    use crate::calculator;
+    use crate::cryptotax::Options;
+    use crate::transaction::TransactionType;
+    use futures::executor::block_on;
+    use rust_decimal::Decimal;
+    use rust_decimal_macros::dec;
+    use std::error::Error;
+
+    #[test]
+    fn should_calculate_each_account_at_its_own_cost() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let txn = |r#type: TransactionType, account: &str, amount: Decimal, exchanged: Decimal, date: &str| {
+            let mut txn = Transaction::new();
+            txn.r#type = r#type;
+            txn.paid_currency = "BTC".to_string();
+            txn.paid_amount = amount;
+            txn.exchanged_currency = "SEK".to_string();
+            txn.exchanged_amount = exchanged;
+            txn.date = date.to_string();
+            txn.origin.file = account.to_string();
+            txn
+        };
+        let txns = vec![
+            txn(TransactionType::Buy, "revolut.csv", dec!(1), dec!(-10000), "2022-01-01 10:00:00"),
+            txn(TransactionType::Buy, "kraken.csv", dec!(1), dec!(-30000), "2022-02-01 10:00:00"),
+            txn(TransactionType::Sell, "revolut.csv", dec!(-1), dec!(25000), "2022-03-01 10:00:00"),
+        ];
+        let options = Options::default();
+        let btc = "BTC".to_string();
+
+        /*
+         * When
+         */
+        let universal = block_on(calculator::calculate(&txns, &btc, &"SEK".to_string(), &options))?;
+        let per_account = calculate(&txns, &btc, |txns| {
+            block_on(calculator::calculate(txns, &btc, &"SEK".to_string(), &options))
+        })?;
+
+        /*
+         * Then
+         */
+        assert_eq!(split(&txns).keys().collect::<Vec<_>>(), vec![&"kraken.csv", &"revolut.csv"]);
+        assert_eq!(universal.taxables[0].net_income, Some(dec!(5000)));
+        assert_eq!(per_account.taxables.len(), 1);
+        assert_eq!(per_account.taxables[0].net_income, Some(dec!(15000)));
+        assert_eq!(per_account.holding.amount, dec!(1));
+        assert_eq!(per_account.carryover[0].origin.file, "kraken.csv");
+        Ok(())
+    }
+}
@@ -0,0 +1,196 @@
+use crate::calculator::{self, Consumed, TaxableTransaction};
+use crate::transaction::{Currency, Origin, TransactionType};
+use rust_decimal::Decimal;
+use serde::Serialize;
+
+/// A line of the audit report. Every disposal is followed by the acquisitions it consumed, with
+/// the file, the lines and the raw descriptions of the rows they were read from, so that every
+/// gain or loss can be traced back to the export files, and with what they were annotated with.
+#[derive(Debug, PartialEq, Serialize)]
+pub(crate) struct AuditRow {
+    #[serde(rename = "Date")]
+    date: String,                   // Of the disposal, also on the rows of its lots
+
+    #[serde(rename = "Row")]
+    row: Row,
+
+    #[serde(rename = "Type")]
+    r#type: TransactionType,
+
+    #[serde(rename = "Currency")]
+    currency: Currency,
+
+    #[serde(rename = "Amount")]
+    amount: Option<Decimal>,        // None on the further rows of a pooled lot
+
+    #[serde(rename = "Income")]
+    income: Option<String>,
+
+    #[serde(rename = "Cost")]
+    cost: Option<String>,
+
+    #[serde(rename = "Net Income")]
+    net_income: Option<Decimal>,
+
+    #[serde(rename = "Acquired")]
+    acquired: Option<String>,       // Of the lot, None if pooled, see `Consumed::acquired`
+
+    #[serde(rename = "File")]
+    file: String,
+
+    #[serde(rename = "Lines")]
+    lines: String,                  // e.g. "9 10" for both rows of an exchange
+
+    #[serde(rename = "Description")]
+    description: String,
+
+    #[serde(rename = "Price Deviation")]
+    deviation: Option<Decimal>,     // In percent, see `price::check_deviations`
+
+    #[serde(rename = "Note")]
+    note: Option<String>,
+
+    #[serde(rename = "Tags")]
+    tags: String,
+
+    #[serde(rename = "Counterparty")]
+    counterparty: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Serialize)]
+enum Row {
+    Disposal,
+    Lot,
+}
+
+/// Returns a row for every disposal, followed by a row for every origin of the lots it
+/// consumed. A lot of the average cost method is pooled from many buys, so its amount and cost
+/// are only on the first of its rows.
+pub(crate) fn report(taxables: &[TaxableTransaction]) -> Vec<AuditRow> {
+    let mut rows = Vec::with_capacity(taxables.len());
+    for t in taxables {
+        rows.push(AuditRow{
+            date: t.date.clone(),
+            row: Row::Disposal,
+            r#type: t.r#type.clone(),
+            currency: t.currency.clone(),
+            amount: Some(t.amount),
+            income: Some(t.income.to_string()),
+            cost: Some(calculator::costs_to_string(&t.costs)),
+            net_income: t.net_income,
+            acquired: None,
+            file: t.origin.file.clone(),
+            lines: lines_to_string(&t.origin),
+            description: t.origin.descriptions.join(" | "),
+            deviation: t.origin.deviation,
+            note: t.origin.note.clone(),
+            tags: t.origin.tags.join(" "),
+            counterparty: t.origin.counterparty.clone(),
+        });
+        t.consumed.iter().for_each(|c| push_lots(t, c, &mut rows));
+    }
+    rows
+}
+
+/// Pushes a row for every origin of the lot, without copying the origins.
+fn push_lots(disposal: &TaxableTransaction, consumed: &Consumed, rows: &mut Vec<AuditRow>) {
+    let empty = [Origin::default()];    // e.g. the costs carried over from a snapshot
+    consumed.origins.with(|origins| {
+        let origins = if origins.is_empty() { &empty[..] } else { origins };
+        rows.extend(origins.iter()
+            .enumerate()
+            .map(|(i, origin)| AuditRow{
+                date: disposal.date.clone(),
+                row: Row::Lot,
+                r#type: disposal.r#type.clone(),
+                currency: disposal.currency.clone(),
+                amount: Some(consumed.amount).filter(|_| i == 0),
+                income: None,
+                cost: Some(calculator::costs_to_string(&consumed.costs)).filter(|_| i == 0),
+                net_income: None,
+                acquired: consumed.acquired.clone().filter(|_| i == 0),
+                file: origin.file.clone(),
+                lines: lines_to_string(origin),
+                description: origin.descriptions.join(" | "),
+                deviation: origin.deviation,
+                note: origin.note.clone(),
+                tags: origin.tags.join(" "),
+                counterparty: origin.counterparty.clone(),
+            }));
+    });
+}
+
+fn lines_to_string(origin: &Origin) -> String {
+    origin.lines.iter().map(|l| l.to_string()).collect::<Vec<String>>().join(" ")
+}
+
+#[cfg(test)]
+mod test {
+    use crate::audit::*;
+    use crate::transaction::Money;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn should_trace_disposal_to_consumed_lots() {
+        /*
+         * Given
+         */
+        let origin = |lines: Vec<u64>, descriptions: Vec<&str>| Origin{
+            file: "history.csv".to_string(),
+            lines,
+            descriptions: descriptions.into_iter().map(String::from).collect(),
+            deviation: None,
+            note: None,
+            tags: vec![],
+            counterparty: None,
+        };
+        let mut taxable = TaxableTransaction{
+            r#type: TransactionType::Sell,
+            date: "2022-03-01 16:21:49".to_string(),
+            currency: "BTC".to_string(),
+            amount: dec!(-0.1),
+            income: Money::new_cash("SEK".to_string(), dec!(5000)),
+            costs: vec![Money::new_cash("SEK".to_string(), dec!(-3000))],
+            net_income: Some(dec!(2000)),
+            acquisition_fee: dec!(0),
+            disposal_fee: dec!(0),
+            origin: origin(vec![6, 7], vec!["Exchanged to SEK", "Exchanged from BTC"]),
+            consumed: vec![Consumed{
+                amount: dec!(0.1),
+                costs: vec![Money::new_cash("SEK".to_string(), dec!(-3000))],
+                origins: vec![
+                    origin(vec![2, 3], vec!["Exchanged to BTC", "Exchanged from SEK"]),
+                    origin(vec![4, 5], vec!["Exchanged to BTC", "Exchanged from SEK"]),
+                ].into(),
+                acquired: Some("2021-01-01 10:00:00".to_string()),
+            }],
+        };
+
+        taxable.origin.annotate(Some(&"Paid a supplier".to_string()), &["business".to_string()]);
+
+        /*
+         * When
+         */
+        let rows = report(&[taxable]);
+
+        /*
+         * Then
+         */
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0].row, Row::Disposal);
+        assert_eq!(rows[0].net_income, Some(dec!(2000)));
+        assert_eq!(rows[0].lines, "6 7");
+        assert_eq!(rows[0].description, "Exchanged to SEK | Exchanged from BTC");
+        assert_eq!(rows[0].note, Some("Paid a supplier".to_string()));
+        assert_eq!(rows[0].tags, "business");
+        assert_eq!(rows[1].row, Row::Lot);
+        assert_eq!(rows[1].amount, Some(dec!(0.1)));
+        assert_eq!(rows[1].cost, Some("-3000".to_string()));
+        assert_eq!(rows[1].lines, "2 3");
+        assert_eq!(rows[1].acquired, Some("2021-01-01 10:00:00".to_string()));
+        assert_eq!(rows[2].amount, None);
+        assert_eq!(rows[2].cost, None);
+        assert_eq!(rows[2].file, "history.csv");
+        assert_eq!(rows[2].lines, "4 5");
+    }
+}
@@ -0,0 +1,207 @@
+use crate::error::{self, Result};
+use crate::locale::CsvFormat;
+use crate::reader;
+use crate::transaction::{Currency, Transaction};
+use chrono::NaiveDate;
+use csv::{ReaderBuilder, StringRecord, Trim};
+use rust_decimal::prelude::*;
+use serde::Serialize;
+use std::io::Read;
+use std::path::Path;
+
+/// How many days a transfer may take between the bank and Revolut, e.g. over a weekend.
+const MAX_DAYS_APART: i64 = 5;
+
+/// The names of the columns of the statements of common banks, in lower case, e.g. of
+/// Swedbank, SEB, Handelsbanken and Nordea, which are in Swedish.
+const DATE_COLUMNS: [&str; 7] = ["date", "datum", "bokföringsdag", "bokföringsdatum", "transaktionsdag", "booking date", "completed date"];
+const AMOUNT_COLUMNS: [&str; 2] = ["amount", "belopp"];
+const DESCRIPTION_COLUMNS: [&str; 7] = ["description", "text", "beskrivning", "meddelande", "rubrik", "reference", "transaktion"];
+
+/// The formats of the dates of the statements.
+const DATE_FORMATS: [&str; 4] = ["%Y-%m-%d", "%d.%m.%Y", "%d/%m/%Y", "%Y/%m/%d"];
+
+/// A fiat transfer that has no match on the other side: a top-up or a withdrawal of Revolut
+/// without a transfer of the bank, or a transfer of the bank to or from Revolut without its
+/// top-up or withdrawal in the export, which suggests that an export is missing.
+#[derive(Debug, PartialEq, Serialize)]
+pub(crate) struct UnmatchedRow {
+    #[serde(rename = "Status")]
+    status: Status,
+
+    #[serde(rename = "Date")]
+    date: String,
+
+    #[serde(rename = "Currency")]
+    currency: Currency,
+
+    #[serde(rename = "Amount")]
+    amount: Decimal,                // Into Revolut if positive, on either side
+
+    #[serde(rename = "Description")]
+    description: String,
+
+    #[serde(rename = "File")]
+    file: String,
+
+    #[serde(rename = "Line")]
+    line: u64,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Serialize)]
+enum Status {
+    /// In the Revolut export, but not in the statement of the bank.
+    NotInBank,
+    /// In the statement of the bank and naming Revolut, but not in the export.
+    NotInExport,
+}
+
+/// A row of the statement of a bank. The amount is signed from the side of the bank account.
+#[derive(Debug, PartialEq)]
+struct BankRow {
+    date: NaiveDate,
+    amount: Decimal,
+    description: String,
+    line: u64,
+}
+
+fn date_of(value: &str) -> Option<NaiveDate> {
+    let value = value.get(..10).unwrap_or(value);
+    DATE_FORMATS.iter().find_map(|format| NaiveDate::parse_from_str(value, format).ok())
+}
+
+/// Reads the statement of a bank from path. Its columns are told by their headers, in English or
+/// Swedish, see `DATE_COLUMNS`, and its delimiter and decimal separator are detected.
+fn read_statement(path: &Path) -> Result<Vec<BankRow>> {
+    let mut content = String::new();
+    reader::open(path)?.read_to_string(&mut content)?;
+    let format = CsvFormat::detect(&content);
+    let mut rdr = ReaderBuilder::new()
+        .has_headers(true)
+        .delimiter(format.delimiter)
+        .flexible(true)
+        .trim(Trim::All)
+        .from_reader(content.as_bytes());
+
+    let headers = rdr.headers()?.clone();
+    let find = |names: &[&str], column: &str| {
+        headers.iter().position(|h| names.contains(&h.to_lowercase().as_str())).ok_or_else(|| {
+            let msg = format!("No {} column in the bank statement. Supported: {}", column, names.join(", "));
+            error::invalid(path, msg)
+        })
+    };
+    let date = find(&DATE_COLUMNS, "date")?;
+    let amount = find(&AMOUNT_COLUMNS, "amount")?;
+    let description = find(&DESCRIPTION_COLUMNS, "description").ok();
+
+    let rows = rdr.records()
+        .collect::<Result<Vec<StringRecord>, csv::Error>>()
+        .map_err(|e| error::parse(path, e))?
+        .into_iter()
+        .filter_map(|record| Some(BankRow{
+            date: date_of(record.get(date)?)?,
+            amount: Decimal::from_str(&format.normalize_decimal(record.get(amount)?)).ok()?,
+            description: description.and_then(|i| record.get(i)).unwrap_or_default().to_string(),
+            line: reader::line_of(&record),
+        }))
+        .collect();
+    Ok(rows)
+}
+
+/// Matches the fiat top-ups and withdrawals of Revolut, see `reader::read_fiat_transfers`, with
+/// the transfers of the statement of the bank at `statement`: a top-up of an amount with a
+/// payment of the same amount out of the bank, and a withdrawal with a deposit, up to
+/// `MAX_DAYS_APART` days apart, the closest first. Returns the transfers of Revolut without a
+/// match, and the transfers of the bank without a match whose description names Revolut, in
+/// order of date.
+pub(crate) fn reconcile(transfers: &[Transaction], statement: &Path, currency: &Currency) -> Result<Vec<UnmatchedRow>> {
+    let bank = read_statement(statement)?;
+    let mut matched = vec![false; bank.len()];
+    let mut rows = vec![];
+    for t in transfers {
+        let date = date_of(&t.date);
+        let closest = bank.iter().enumerate()
+            .filter(|(i, b)| !matched[*i] && b.amount == -t.paid_amount)
+            .filter_map(|(i, b)| date.map(|d| (i, (b.date - d).num_days().abs())))
+            .filter(|(_, days)| *days <= MAX_DAYS_APART)
+            .min_by_key(|(_, days)| *days);
+        match closest {
+            Some((i, _)) => matched[i] = true,
+            None => rows.push(UnmatchedRow{
+                status: Status::NotInBank,
+                date: t.date.clone(),
+                currency: currency.clone(),
+                amount: t.paid_amount,
+                description: t.origin.descriptions.join(" "),
+                file: t.origin.file.clone(),
+                line: t.origin.lines.first().copied().unwrap_or_default(),
+            }),
+        }
+    }
+    let names_revolut = |b: &BankRow| b.description.to_lowercase().contains("revolut");
+    rows.extend(bank.iter().enumerate()
+        .filter(|(i, b)| !matched[*i] && names_revolut(b))
+        .map(|(_, b)| UnmatchedRow{
+            status: Status::NotInExport,
+            date: b.date.format("%Y-%m-%d").to_string(),
+            currency: currency.clone(),
+            amount: -b.amount,
+            description: b.description.clone(),
+            file: statement.display().to_string(),
+            line: b.line,
+        }));
+    rows.sort_by(|a, b| a.date.cmp(&b.date));
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::bank::*;
+    use crate::transaction::TransactionType;
+    use rust_decimal_macros::dec;
+    use std::error::Error;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn should_reconcile_fiat_transfers_with_bank_statement() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let transfer = |date: &str, amount, description: &str| {
+            let mut txn = Transaction::new();
+            txn.r#type = TransactionType::Transfer;
+            txn.date = date.to_string();
+            txn.paid_currency = "SEK".to_string();
+            txn.paid_amount = amount;
+            txn.origin.add(2, description.to_string());
+            txn
+        };
+        let transfers = vec![
+            transfer("2022-01-03 10:00:00", dec!(1000), "Payment from John Doe"),         // Two days after the bank
+            transfer("2022-02-01 10:00:00", dec!(-500), "To John Doe"),                   // Withdrawn to the bank
+            transfer("2022-03-01 10:00:00", dec!(2000), "Top-Up by *1234"),               // Not in the bank
+        ];
+        let mut statement = NamedTempFile::new()?;
+        writeln!(statement, "Bokföringsdag;Transaktionsdag;Referens;Text;Belopp;Saldo
+                             2022-01-01;2022-01-01;;Revolut Ltd;-1 000,00;9 000,00
+                             2022-02-02;2022-02-02;;Revolut Ltd;500,00;9 500,00
+                             2022-04-01;2022-04-01;;Revolut Ltd;-3 000,00;6 500,00
+                             2022-04-02;2022-04-02;;ICA Maxi;-300,00;6 200,00")?;
+
+        /*
+         * When
+         */
+        let rows = reconcile(&transfers, statement.path(), &"SEK".to_string())?;
+
+        /*
+         * Then
+         */
+        let rows: Vec<(Status, &str, Decimal)> = rows.iter().map(|r| (r.status, r.date.as_str(), r.amount)).collect();
+        assert_eq!(rows, vec![
+            (Status::NotInBank, "2022-03-01 10:00:00", dec!(2000)),
+            (Status::NotInExport, "2022-04-01", dec!(3000)),
+        ]);
+        Ok(())
+    }
+}
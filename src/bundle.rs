@@ -0,0 +1,313 @@
+use crate::decimal;
+use crate::error::Result;
+use crate::transaction::{Currency, Transaction, TransactionType};
+use crate::warnings::Warning;
+use crate::writer;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{self, Cursor, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tracing::Level;
+use tracing::field::{Field, Visit};
+use tracing_subscriber::field::{MakeVisitor, VisitFmt, VisitOutput};
+use tracing_subscriber::fmt::format::{DefaultFields, DefaultVisitor, FmtSpan, Writer};
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+
+/// A transaction of a debug bundle, with the amounts scaled and the descriptions hashed, so that
+/// it can be attached to a bug report without telling how much was traded or with whom. The
+/// types, the currencies, the dates and the lines are kept, as most bugs depend on them.
+#[derive(Debug, PartialEq, Serialize)]
+struct BundleRow {
+    #[serde(rename = "Type")]
+    r#type: TransactionType,
+
+    #[serde(rename = "Date")]
+    date: String,
+
+    #[serde(rename = "Paid Currency")]
+    paid_currency: Currency,
+
+    #[serde(rename = "Paid Amount")]
+    paid_amount: Decimal,
+
+    #[serde(rename = "Paid Fee")]
+    paid_fee: Decimal,
+
+    #[serde(rename = "Exchanged Currency")]
+    exchanged_currency: Currency,
+
+    #[serde(rename = "Exchanged Amount")]
+    exchanged_amount: Decimal,
+
+    #[serde(rename = "Exchanged Fee")]
+    exchanged_fee: Decimal,
+
+    #[serde(rename = "Vault")]
+    is_vault: bool,
+
+    #[serde(rename = "File")]
+    file: String,                   // The name of the file, without the directories
+
+    #[serde(rename = "Lines")]
+    lines: String,
+
+    #[serde(rename = "Descriptions")]
+    descriptions: String,           // Hashed, the same description to the same hash
+}
+
+/// Scales every amount by the same factor, so that the prices and the gains keep their sign and
+/// proportions, and hashes the descriptions with a salt, so that they can't be looked up.
+pub(crate) struct Anonymizer {
+    scale: Decimal,
+    salt: String,
+}
+
+impl Anonymizer {
+    /// An anonymizer with a random scale between 0.0000001 and 10000, so that the order of
+    /// magnitude of the amounts isn't told either, and a random salt. Neither is written to the
+    /// bundle.
+    pub(crate) fn new() -> Anonymizer {
+        let random = || RandomState::new().build_hasher().finish();
+        let scale = Decimal::new(1000 + (random() % 9000) as i64, (random() % 11) as u32);
+        Anonymizer{ scale, salt: format!("{:016x}", random()) }
+    }
+
+    fn hash(&self, text: &str) -> String {
+        let digest = Sha256::digest(format!("{}{}", self.salt, text));
+        digest.iter().take(6).map(|b| format!("{:02x}", b)).collect()
+    }
+
+    fn row(&self, t: &Transaction) -> Result<BundleRow> {
+        Ok(BundleRow{
+            r#type: t.r#type.clone(),
+            date: t.date.clone(),
+            paid_currency: t.paid_currency.clone(),
+            paid_amount: decimal::mul(t.paid_amount, self.scale)?.normalize(),
+            paid_fee: decimal::mul(t.paid_fee, self.scale)?.normalize(),
+            exchanged_currency: t.exchanged_currency.clone(),
+            exchanged_amount: decimal::mul(t.exchanged_amount, self.scale)?.normalize(),
+            exchanged_fee: decimal::mul(t.exchanged_fee, self.scale)?.normalize(),
+            is_vault: t.is_vault,
+            file: file_name(&t.origin.file),
+            lines: t.origin.lines.iter().map(|l| l.to_string()).collect::<Vec<_>>().join(" "),
+            descriptions: t.origin.descriptions.iter().map(|d| self.hash(d)).collect::<Vec<_>>().join(" | "),
+        })
+    }
+
+    /// The warning without its message, which may tell an amount.
+    fn warning(&self, warning: &Warning) -> Warning {
+        Warning{ message: "".to_string(), file: warning.file.as_deref().map(file_name), ..warning.clone() }
+    }
+}
+
+fn file_name(file: &str) -> String {
+    Path::new(file).file_name().map_or("".to_string(), |f| f.to_string_lossy().to_string())
+}
+
+/// Formats the fields of the log like `DefaultFields`, with the `path` and `file` fields cut down
+/// to the names of the files.
+struct FileNames;
+
+impl<'a> MakeVisitor<Writer<'a>> for FileNames {
+    type Visitor = FileNameVisitor<'a>;
+
+    fn make_visitor(&self, target: Writer<'a>) -> Self::Visitor {
+        FileNameVisitor(DefaultFields::new().make_visitor(target))
+    }
+}
+
+struct FileNameVisitor<'a>(DefaultVisitor<'a>);
+
+impl Visit for FileNameVisitor<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        match field.name() {
+            "path" | "file" => self.0.record_str(field, &file_name(value)),
+            _ => self.0.record_str(field, value),
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        match field.name() {
+            "path" | "file" => self.0.record_debug(field, &format_args!("{}", file_name(&format!("{:?}", value)))),
+            _ => self.0.record_debug(field, value),
+        }
+    }
+}
+
+impl VisitOutput<fmt::Result> for FileNameVisitor<'_> {
+    fn finish(self) -> fmt::Result {
+        self.0.finish()
+    }
+}
+
+impl VisitFmt for FileNameVisitor<'_> {
+    fn writer(&mut self) -> &mut dyn fmt::Write {
+        self.0.writer()
+    }
+}
+
+/// Runs `f` with the phases and their timings logged to a buffer instead of the log of the run,
+/// and returns the log with the result. Only the `info` level is logged, as the details of the
+/// transactions are logged at the `debug` level, and the paths of the files are cut down to their
+/// names, like in the transactions.
+pub(crate) fn capture_log<T, F: FnOnce() -> T>(f: F) -> (T, Vec<u8>) {
+    #[derive(Clone)]
+    struct Capture(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Capture {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().map_err(|_| io::Error::other("Log poisoned"))?.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let capture = Capture(Arc::new(Mutex::new(vec![])));
+    let writer = capture.clone();
+    let subscriber = tracing_subscriber::fmt()
+        .with_ansi(false)
+        .fmt_fields(FileNames)
+        .with_max_level(Level::INFO)
+        .with_span_events(FmtSpan::CLOSE)
+        .with_writer(move || writer.clone())
+        .finish();
+    let result = tracing::subscriber::with_default(subscriber, f);
+    let log = capture.0.lock().map(|log| log.clone()).unwrap_or_default();
+    (result, log)
+}
+
+/// Writes a zip archive to `out` with the anonymized transactions in `transactions.csv`, the
+/// warnings without their messages in `warnings.json`, the `log`, and `about`, e.g. the version
+/// and the settings of the run, in `about.txt`.
+pub(crate) fn write<W: io::Write>(txns: &[Transaction], warnings: &[Warning], log: &[u8], about: &str,
+                                  anonymizer: &Anonymizer, mut out: W) -> Result<()> {
+    let mut zip = ZipWriter::new(Cursor::new(vec![]));
+    let options = SimpleFileOptions::default();
+
+    zip.start_file("transactions.csv", options)?;
+    let rows: Vec<BundleRow> = txns.iter().map(|t| anonymizer.row(t)).collect::<Result<_>>()?;
+    futures::executor::block_on(writer::write(&rows, &mut zip))?;
+
+    zip.start_file("warnings.json", options)?;
+    let warnings: Vec<Warning> = warnings.iter().map(|w| anonymizer.warning(w)).collect();
+    serde_json::to_writer_pretty(&mut zip, &warnings)?;
+
+    zip.start_file("log.txt", options)?;
+    zip.write_all(log)?;
+
+    zip.start_file("about.txt", options)?;
+    zip.write_all(about.as_bytes())?;
+
+    let bytes = zip.finish()?.into_inner();
+    out.write_all(&bytes)?;
+    Ok(out.flush()?)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::bundle::*;
+    use crate::transaction::Origin;
+    use crate::warnings::Code;
+    use rust_decimal_macros::dec;
+    use std::error::Error;
+    use std::io::Read;
+    use zip::ZipArchive;
+
+    #[test]
+    fn should_bundle_anonymized_transactions() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let txn = |amount: Decimal, exchanged: Decimal, lines: Vec<u64>, descriptions: Vec<&str>| {
+            let mut txn = Transaction::new();
+            txn.paid_currency = "BTC".to_string();
+            txn.paid_amount = amount;
+            txn.exchanged_currency = "SEK".to_string();
+            txn.exchanged_amount = exchanged;
+            txn.date = "2022-01-01 10:00:00".to_string();
+            txn.origin = Origin{
+                file: "/home/someone/transactions_history.csv".to_string(),
+                lines,
+                descriptions: descriptions.into_iter().map(String::from).collect(),
+                deviation: None,
+                note: None,
+                tags: vec![],
+                counterparty: None,
+            };
+            txn
+        };
+        let txns = vec![
+            txn(dec!(1), dec!(-1000), vec![2, 3], vec!["Exchanged to BTC", "Exchanged from SEK"]),
+            txn(dec!(-1), dec!(1500), vec![4], vec!["Exchanged to BTC"]),
+        ];
+        let warning = Warning::new(Code::UnmatchedRow, "Sold 1 BTC without its other side".to_string()).at_line(4);
+        let anonymizer = Anonymizer{ scale: dec!(2.5), salt: "salt".to_string() };
+
+        /*
+         * When
+         */
+        let mut out = vec![];
+        write(&txns, &[warning], b"parse: close", "cryptotax 0.1.0", &anonymizer, &mut out)?;
+
+        /*
+         * Then
+         */
+        let mut zip = ZipArchive::new(Cursor::new(out))?;
+        let mut read = |name: &str| -> Result<String, Box<dyn Error>> {
+            let mut content = String::new();
+            zip.by_name(name)?.read_to_string(&mut content)?;
+            Ok(content)
+        };
+        let transactions = read("transactions.csv")?;
+        let rows: Vec<&str> = transactions.lines().collect();
+        let hash = anonymizer.hash("Exchanged to BTC");
+        assert_eq!(rows[1], format!("Buy;2022-01-01 10:00:00;BTC;2.5;0;SEK;-2500;0;false;transactions_history.csv;2 3;{} | {}",
+                                    hash, anonymizer.hash("Exchanged from SEK")));
+        assert!(rows[2].ends_with(&format!(";4;{}", hash)));
+        assert!(!read("warnings.json")?.contains("Sold"));
+        assert_eq!(read("log.txt")?, "parse: close");
+        assert_eq!(read("about.txt")?, "cryptotax 0.1.0");
+        Ok(())
+    }
+
+    #[test]
+    fn should_capture_log_without_directories() -> Result<(), Box<dyn Error>> {
+        /*
+         * When
+         */
+        let ((), log) = capture_log(|| {
+            let _span = tracing::info_span!("parse", path = %"/home/someone/transactions_history.csv", currency = "BTC").entered();
+            tracing::info!(rows = 2, "Read");
+        });
+
+        /*
+         * Then
+         */
+        let log = String::from_utf8(log)?;
+        assert!(log.contains("parse{path=transactions_history.csv currency=\"BTC\"}: cryptotax::bundle::test: Read rows=2"), "{}", log);
+        assert!(!log.contains("/home/someone"), "{}", log);
+        Ok(())
+    }
+
+    #[test]
+    fn should_scale_by_orders_of_magnitude() {
+        /*
+         * When
+         */
+        let scales: Vec<Decimal> = (0..100).map(|_| Anonymizer::new().scale).collect();
+
+        /*
+         * Then
+         */
+        assert!(scales.iter().all(|s| *s >= dec!(0.0000001) && *s < dec!(10000)));
+        assert!(scales.iter().any(|s| *s < Decimal::ONE) && scales.iter().any(|s| *s >= dec!(10)));
+    }
+}
@@ -1,12 +1,22 @@
-use crate::transaction::{Currency, Transaction, TransactionType, Money};
-use log::debug;
+use crate::cryptotax::Options;
+use crate::decimal;
+use crate::error::{CryptotaxError, Result};
+use crate::fees;
+use crate::jurisdiction::FeePolicy;
+use crate::precision::{self, Precision};
+use crate::rounding::Rounding;
+use crate::term::{self, Split};
+use crate::transaction::{Currency, Money, Origin, Origins, Transaction, TransactionType};
+use crate::warnings::{Code, Warning};
 use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
 use serde::ser::SerializeStruct;
 use serde::{Serialize, Serializer};
+use std::collections::HashMap;
 use std::fmt::Debug;
-use std::io;
+use std::mem;
 use std::ops::{Neg, Sub};
+use tracing::{debug, instrument};
 
 // 1. Bought Crypto 1 from SEK      (cost in SEK),  sold to SEK      (sales in SEK)
 // 2. Bought Crypto 1 from SEK      (cost in SEK),  sold to Crypto 2 (SEK price as sales)
@@ -14,12 +24,28 @@ use std::ops::{Neg, Sub};
 // 4. Bought from Crypto 3 (SEK price as cost),     sold to SEK      (sales in SEK)
 #[derive(Debug, PartialEq)]
 pub(crate) struct TaxableTransaction {
-    date: String,
-    currency: Currency,             // Valutakod
-    amount: Decimal,                // Antal
-    income: Money,                  // Försäljningspris
-    costs: Vec<Money>,              // Omkostnadsbelopp
-    net_income: Option<Decimal>,    // Vinst/förlust
+    pub(crate) r#type: TransactionType,             // Sell, or a disposal such as a gift
+    pub(crate) date: String,
+    pub(crate) currency: Currency,             // Valutakod
+    pub(crate) amount: Decimal,                // Antal
+    pub(crate) income: Money,                  // Försäljningspris
+    pub(crate) costs: Vec<Money>,              // Omkostnadsbelopp
+    pub(crate) net_income: Option<Decimal>,    // Vinst/förlust
+    pub(crate) acquisition_fee: Decimal,       // In the base currency, in `costs` unless `FeePolicy::Separate`
+    pub(crate) disposal_fee: Decimal,          // In the base currency, in `income` if `FeePolicy::Deduct`
+    pub(crate) origin: Origin,                 // The rows of the disposal, for the audit report
+    pub(crate) consumed: Vec<Consumed>,        // The acquisitions that make up `costs`
+}
+
+/// The part of the acquisitions that a disposal consumed, with the rows they were read from. A
+/// pooled cost is made of many acquisitions, so it has many origins, and no acquisition date to
+/// tell its holding period by, see `term::split`.
+#[derive(Debug, PartialEq, Clone)]
+pub(crate) struct Consumed {
+    pub(crate) amount: Decimal,
+    pub(crate) costs: Vec<Money>,
+    pub(crate) origins: Origins,
+    pub(crate) acquired: Option<String>,
 }
 
 impl Serialize for TaxableTransaction {
@@ -28,26 +54,168 @@ impl Serialize for TaxableTransaction {
     {
         // 6 is the number of fields in the struct.
         let mut state = serializer.serialize_struct("TaxableTransaction", 6)?;
+        self.serialize_fields(&mut state)?;
+        state.end()
+    }
+}
+
+/// Serializes a `TaxableTransaction` with the optional columns of the tax report: its fees, its
+/// net income split into the short-term and the long-term part, the note and the tags it was
+/// annotated with, see `Annotation`, and who was paid, e.g. the merchant of a card payment.
+pub(crate) struct Columns<'a> {
+    taxable: &'a TaxableTransaction,
+    fees: bool,
+    terms: Option<Split>,
+    notes: bool,
+    counterparties: bool,
+}
+
+impl Columns<'_> {
+    /// The disposals with their fees if `Options::separate_fees` is set or the fees are
+    /// `FeePolicy::Separate`, with the split of the net income if the jurisdiction tells
+    /// short-term and long-term gains apart, see `term::split`, with the notes and tags if any of
+    /// them has some, and with the counterparties if any of them has one, so that every row has
+    /// the same columns.
+    pub(crate) fn of<'a>(taxables: &'a [TaxableTransaction], options: &Options) -> Result<Vec<Columns<'a>>> {
+        let fees = options.separate_fees || fees::policy(options) == FeePolicy::Separate;
+        let period = options.jurisdiction.holding_period();
+        let notes = taxables.iter().any(|t| t.origin.has_notes());
+        let counterparties = taxables.iter().any(|t| t.origin.counterparty.is_some());
+        taxables.iter()
+            .map(|taxable| {
+                let terms = period.map(|p| term::split(taxable, &p, &options.rounding)).transpose()?;
+                Ok(Columns{ taxable, fees, terms, notes, counterparties })
+            })
+            .collect()
+    }
+}
+
+impl Serialize for Columns<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where S: Serializer,
+    {
+        let mut state = serializer.serialize_struct("TaxableTransaction", 13)?;
+        self.taxable.serialize_fields(&mut state)?;
+        if self.fees {
+            state.serialize_field("Acquisition Fee", &self.taxable.acquisition_fee)?;
+            state.serialize_field("Disposal Fee", &self.taxable.disposal_fee)?;
+        }
+        if let Some(terms) = &self.terms {
+            state.serialize_field("Short-Term", &terms.short)?;
+            state.serialize_field("Long-Term", &terms.long)?;
+        }
+        if self.notes {
+            state.serialize_field("Note", &self.taxable.origin.note)?;
+            state.serialize_field("Tags", &self.taxable.origin.tags.join(" "))?;
+        }
+        if self.counterparties {
+            state.serialize_field("Counterparty", &self.taxable.origin.counterparty)?;
+        }
+        state.end()
+    }
+}
+
+/// Serializes a profit or loss of a margin, futures or short position for the derivatives table
+/// of the tax report, which has no costs, and whose profit or loss is empty if it is not valued
+/// in the base currency.
+#[derive(Serialize)]
+pub(crate) struct ProfitAndLoss<'a> {
+    #[serde(rename = "Date")]
+    date: &'a str,
+    #[serde(rename = "Type")]
+    r#type: &'a TransactionType,
+    #[serde(rename = "Currency")]
+    currency: &'a Currency,
+    #[serde(rename = "Amount")]
+    amount: Decimal,
+    #[serde(rename = "Profit and Loss")]
+    net_income: Option<Decimal>,
+    #[serde(rename = "Fee")]
+    fee: Decimal,
+}
+
+impl<'a> From<&'a TaxableTransaction> for ProfitAndLoss<'a> {
+    fn from(t: &'a TaxableTransaction) -> Self {
+        ProfitAndLoss{ date: &t.date, r#type: &t.r#type, currency: &t.currency, amount: t.amount, net_income: t.net_income, fee: t.disposal_fee }
+    }
+}
+
+impl TaxableTransaction {
+    /// Rounds the amounts in the base currency for a report. The costs are summed up before
+    /// rounding, and the net income is recalculated from the rounded amounts, so that the printed
+    /// columns add up.
+    pub(crate) fn round(&mut self, rounding: &Rounding) -> Result<()> {
+        if let Money::Cash(cash) = &mut self.income {
+            cash.amount = rounding.gain(cash.amount);
+        }
+        if let (Some(Money::Cash(first)), true) = (self.costs.first(), self.costs.iter().all(|c| c.is_cash())) {
+            let cost = decimal::sum(self.costs.iter().map(|c| c.amount()))?;
+            self.costs = vec![Money::new_cash(first.currency.clone(), rounding.gain(cost))];
+        }
+        self.net_income = self.income.to_net_income(&self.costs)?;
+        self.acquisition_fee = rounding.gain(self.acquisition_fee);
+        self.disposal_fee = rounding.gain(self.disposal_fee);
+        Ok(())
+    }
+
+    /// Whether the disposal paid for goods or services, e.g. a card payment, rather than being
+    /// traded, by whether it has a counterparty, see `Origin::counterparty`.
+    pub(crate) fn is_purchase(&self) -> bool {
+        self.r#type == TransactionType::Sell && self.origin.counterparty.is_some()
+    }
+
+    /// Sets the cost to the income, for a disposal whose gain is negligible, see
+    /// `stablecoin::is_negligible`. A disposal that is not valued in the base currency is left as
+    /// it is.
+    pub(crate) fn without_gain(&mut self) {
+        if let Money::Cash(cash) = &self.income {
+            self.costs = vec![Money::new_cash(cash.currency.clone(), -cash.amount)];
+            self.net_income = Some(dec!(0));
+        }
+    }
+
+    /// Treats the fees of the disposal by `policy`, see `fees::apply`. The engines deduct them. A
+    /// disposal without a net income keeps none.
+    pub(crate) fn with_fees(&mut self, policy: FeePolicy) -> Result<()> {
+        let cost = decimal::sum(self.costs.iter().filter(|c| c.is_cash()).map(|c| c.amount()))?;
+        let income = match &self.income { Money::Cash(cash) => cash.amount, Money::Coupon(_) => dec!(0) };
+        let (income, with_fees) = fees::apply(policy, income, cost, self.acquisition_fee, self.disposal_fee)?;
+        if let Money::Cash(cash) = &mut self.income {
+            cash.amount = income;
+        }
+        let adjustment = decimal::sub(with_fees, cost)?;
+        match self.costs.iter_mut().find_map(|c| match c { Money::Cash(cash) => Some(cash), Money::Coupon(_) => None }) {
+            Some(cash) => cash.amount = decimal::add(cash.amount, adjustment)?,
+            None if !adjustment.is_zero() => {
+                if let Money::Cash(cash) = &self.income {
+                    self.costs.push(Money::new_cash(cash.currency.clone(), adjustment));
+                }
+            }
+            None => {}
+        }
+        if self.net_income.is_some() {
+            self.net_income = self.income.to_net_income(&self.costs)?;
+        }
+        Ok(())
+    }
+
+    fn serialize_fields<S: SerializeStruct>(&self, state: &mut S) -> Result<(), S::Error> {
         state.serialize_field("Date", &self.date)?;
         state.serialize_field("Currency", &self.currency)?;
         state.serialize_field("Amount", &self.amount)?;
         state.serialize_field("Income", &format!("{}", self.income))?;
-        state.serialize_field("Cost", &self.costs_to_string())?;
+        state.serialize_field("Cost", &costs_to_string(&self.costs))?;
         state.serialize_field("Net Income", &self.net_income)?;
-        state.end()
+        Ok(())
     }
 }
 
-impl TaxableTransaction {
-    fn costs_to_string(&self) -> String {
-        if self.costs.iter().all(|c| c.is_cash()) {
-            self.costs.iter()
-                .fold(dec!(0), |acc, c| acc + c.amount())
-                .to_string()
-        } else {
-            self.costs.iter()
-                .fold("".to_string(), |acc, c| format!("{}, {}", acc, c))
-        }
+/// The costs as one amount if they are all in the base currency and their sum fits a decimal,
+/// otherwise listed one by one.
+pub(crate) fn costs_to_string(costs: &[Money]) -> String {
+    match decimal::sum(costs.iter().map(|c| c.amount())) {
+        Ok(total) if costs.iter().all(|c| c.is_cash()) => total.to_string(),
+        _ => costs.iter().fold("".to_string(), |acc, c| format!("{}, {}", acc, c)),
     }
 }
 
@@ -56,57 +224,72 @@ struct Cost {
     paid_amount: Decimal,
     exchanged: Money,
     is_vault: bool,
+    fee: Decimal,               // Acquisition fees in the base currency, included in `exchanged`
+    origins: Origins,           // The buys that were added to the cost
 }
 
 impl Cost {
     fn new(paid_amount: Decimal, exchanged: Money, is_vault: bool) -> Cost {
-        Cost{ paid_amount, exchanged, is_vault }
+        Cost{ paid_amount, exchanged, is_vault, fee: dec!(0), origins: Default::default() }
     }
 
-    fn deduct(&mut self, paid_amount: Decimal) -> Option<Cost> {
-        if self.paid_amount + paid_amount < dec!(0) {
-            None
+    /// Deducts `paid_amount` (negative) from the cost, with the exchanged amount and the fee in
+    /// proportion, or `None` if the cost is of less. Fails if the proportion overflows.
+    fn deduct(&mut self, paid_amount: Decimal) -> Result<Option<Cost>> {
+        if decimal::add(self.paid_amount, paid_amount)? < dec!(0) {
+            Ok(None)
         } else {
-            let exchanged_amount = self.exchanged.amount() / self.paid_amount * paid_amount.abs();
+            let exchanged_amount = decimal::share(self.exchanged.amount(), paid_amount.abs(), self.paid_amount)?;
+            let fee = decimal::share(self.fee, paid_amount.abs(), self.paid_amount)?;
             let deducted = self.exchanged.deduct(exchanged_amount);
-            self.paid_amount += paid_amount;
-            let deducted_cost = Cost::new(paid_amount.neg(), deducted, self.is_vault);
-            Some(deducted_cost)
+            self.paid_amount = decimal::add(self.paid_amount, paid_amount)?;
+            self.fee = decimal::sub(self.fee, fee)?;
+            let mut deducted_cost = Cost::new(paid_amount.neg(), deducted, self.is_vault);
+            deducted_cost.fee = fee;
+            deducted_cost.origins = self.origins.clone();
+            Ok(Some(deducted_cost))
         }
     }
 
-    fn add_cash(&mut self, paid_amount: Decimal, amount: Decimal) {
+    fn add_cash(&mut self, paid_amount: Decimal, amount: Decimal, fee: Decimal, origin: &Origin) -> Result<()> {
         if let Money::Cash(cash) = &mut self.exchanged {
-            cash.amount += amount;
-            self.paid_amount += paid_amount;
+            cash.amount = decimal::add(cash.amount, amount)?;
+            self.paid_amount = decimal::add(self.paid_amount, paid_amount)?;
+            self.fee = decimal::add(self.fee, fee)?;
+            self.origins.push(origin.clone());
         }
+        Ok(())
     }
 
-    fn deduct_coupon_cost(&mut self, paid_amount: Decimal) -> Option<Cost> {
+    fn to_consumed(&self) -> Consumed {
+        Consumed{ amount: self.paid_amount, costs: vec![self.exchanged.clone()], origins: self.origins.clone(), acquired: None }
+    }
+
+    fn deduct_coupon_cost(&mut self, paid_amount: Decimal) -> Result<Option<Cost>> {
         match (&self.exchanged, self.is_vault) {
             (Money::Coupon(_), false) => self.deduct(paid_amount),
-            _ => None,
+            _ => Ok(None),
         }
     }
 
-    fn deduct_cash_cost(&mut self, paid_amount: Decimal) -> Option<Cost> {
+    fn deduct_cash_cost(&mut self, paid_amount: Decimal) -> Result<Option<Cost>> {
         match (&self.exchanged, self.is_vault) {
             (Money::Cash(_), false) => self.deduct(paid_amount),
-            _ => None,
+            _ => Ok(None),
         }
     }
 
-    fn deduct_vault_coupon_cost(&mut self, paid_amount: Decimal) -> Option<Cost> {
+    fn deduct_vault_coupon_cost(&mut self, paid_amount: Decimal) -> Result<Option<Cost>> {
         match (&self.exchanged, self.is_vault) {
             (Money::Coupon(_), true) => self.deduct(paid_amount),
-            _ => None,
+            _ => Ok(None),
         }
     }
 
-    fn deduct_vault_cash_cost(&mut self, paid_amount: Decimal) -> Option<Cost> {
+    fn deduct_vault_cash_cost(&mut self, paid_amount: Decimal) -> Result<Option<Cost>> {
         match (&self.exchanged, self.is_vault) {
             (Money::Cash(_), true) => self.deduct(paid_amount),
-            _ => None,
+            _ => Ok(None),
         }
     }
 }
@@ -126,35 +309,73 @@ impl CostBook {
             costs: vec![],
         }
     }
-    fn add_buy(&mut self, transaction: &Transaction) {
+    fn add_buy(&mut self, transaction: &Transaction) -> Result<()> {
         match transaction.to_money(&self.base) {
             Money::Cash(cash) => {
-                self.find_cash_cost_mut(transaction.is_vault).map(|cost|
-                    cost.add_cash(transaction.paid_amount, cash.amount)
-                );
+                if let Some(cost) = self.find_cash_cost_mut(transaction.is_vault) {
+                    cost.add_cash(transaction.paid_amount, cash.amount, transaction.exchanged_fee, &transaction.origin)?;
+                }
             }
             income @ Money::Coupon(_) => {
-                let coupon_cost = Cost::new(transaction.paid_amount, income, transaction.is_vault);
+                let mut coupon_cost = Cost::new(transaction.paid_amount, income, transaction.is_vault);
+                coupon_cost.origins.push(transaction.origin.clone());
                 self.costs.push(coupon_cost);
             }
         }
+        Ok(())
     }
 
-    fn add_sell(&mut self, transaction: &Transaction) -> io::Result<TaxableTransaction> {
+    fn add_sell(&mut self, transaction: &Transaction) -> Result<TaxableTransaction> {
         let income = transaction.to_money(&self.base);
-        let costs =
-            self.find_and_deduct_cost(&income, transaction.paid_amount)?
-                .into_iter()
-                .map(|c| c.exchanged)
-                .collect();
-        let net_income = income.to_net_income(&costs);
+        let deducted = self.find_and_deduct_cost(&income, transaction.paid_amount)?;
+        let acquisition_fee = decimal::sum(deducted.iter().map(|c| c.fee))?;
+        let consumed = deducted.iter().map(Cost::to_consumed).collect();
+        let costs: Vec<Money> = deducted.into_iter().map(|c| c.exchanged).collect();
+        let net_income = income.to_net_income(&costs)?;
+        let disposal_fee = match income.is_cash() {
+            true => transaction.exchanged_fee,
+            false => dec!(0),
+        };
         Ok(TaxableTransaction{
+            r#type: transaction.r#type.clone(),
             date: transaction.date.clone(),
             currency: transaction.paid_currency.clone(),
             amount: transaction.paid_amount,
             income,
             costs,
-            net_income
+            net_income,
+            acquisition_fee,
+            disposal_fee,
+            origin: transaction.origin.clone(),
+            consumed
+        })
+    }
+
+    /// Deducts the costs of a disposal without income, e.g. a gift where the deducted costs are
+    /// carried over to the recipient, or a stolen currency. If `recognizes_loss`, the costs are
+    /// a loss in `net_income`.
+    fn add_without_income(&mut self, transaction: &Transaction, recognizes_loss: bool) -> Result<TaxableTransaction> {
+        let income = Money::new_cash(self.base.clone(), dec!(0));
+        let deducted = self.find_and_deduct_cost(&income, transaction.paid_amount)?;
+        let acquisition_fee = decimal::sum(deducted.iter().map(|c| c.fee))?;
+        let consumed = deducted.iter().map(Cost::to_consumed).collect();
+        let costs: Vec<Money> = deducted.into_iter().map(|c| c.exchanged).collect();
+        let net_income = match recognizes_loss {
+            true => income.to_net_income(&costs)?,
+            false => None,
+        };
+        Ok(TaxableTransaction{
+            r#type: transaction.r#type.clone(),
+            date: transaction.date.clone(),
+            currency: transaction.paid_currency.clone(),
+            amount: transaction.paid_amount,
+            income,
+            costs,
+            net_income,
+            acquisition_fee,
+            disposal_fee: dec!(0),
+            origin: transaction.origin.clone(),
+            consumed
         })
     }
 
@@ -172,32 +393,68 @@ impl CostBook {
         }
     }
 
+    /// Moves the costs of a transfer between the main balance and the vault, e.g. "To DOGE Vault"
+    /// or "From DOGE Vault": a negative amount left the main balance for the vault, a positive
+    /// amount came back from it. The transfer is internal, so nothing is disposed of, and the
+    /// costs keep their amounts and origins. Only what is held in the pool is moved.
+    fn move_between_pools(&mut self, transaction: &Transaction) -> Result<()> {
+        let to_vault = transaction.paid_amount.is_sign_negative();
+        let mut ddr = Deductor::new(&mut self.costs, transaction.paid_amount.abs().neg());
+        let moved = match to_vault {
+            true => ddr.deduct(Cost::deduct_cash_cost)?.deduct(Cost::deduct_coupon_cost)?.collect(),
+            false => ddr.deduct(Cost::deduct_vault_cash_cost)?.deduct(Cost::deduct_vault_coupon_cost)?.collect(),
+        };
+        if !ddr.remaining.is_zero() {
+            debug!(date = %transaction.date, remaining = %ddr.remaining.abs(), to_vault, "Moved more than is held in the pool");
+        }
+        for mut cost in moved {
+            cost.is_vault = to_vault;
+            match &cost.exchanged {
+                Money::Cash(cash) => {
+                    if let Some(pool) = self.find_cash_cost_mut(to_vault) {
+                        if let Money::Cash(pooled) = &mut pool.exchanged {
+                            pooled.amount = decimal::add(pooled.amount, cash.amount)?;
+                        }
+                        pool.paid_amount = decimal::add(pool.paid_amount, cost.paid_amount)?;
+                        pool.fee = decimal::add(pool.fee, cost.fee)?;
+                        pool.origins.extend(&cost.origins);
+                    }
+                }
+                Money::Coupon(_) => self.costs.push(cost),
+            }
+        }
+        Ok(())
+    }
+
     /// Find the costs for the given `income`. Then deduct them from the book.
     /// If `income` is `Money::Cash`, try deduct from the cash in the `CostBook`.
     /// Likewise, if `income` is `Money::Coupon`, try deduct from the coupons in the `CostBook`.
     /// Only start deducting from the vault if there are no non-vault costs to deduct.
     /// Returns a `Vec<Cost>` which is a list of deducted costs.
-    fn find_and_deduct_cost(&mut self, income: &Money, paid_amount: Decimal) -> io::Result<Vec<Cost>> {
+    fn find_and_deduct_cost(&mut self, income: &Money, paid_amount: Decimal) -> Result<Vec<Cost>> {
         let mut ddr = Deductor::new(&mut self.costs, paid_amount);
         let deducted =
             match income {
                 Money::Cash(_) =>
-                    ddr.deduct(Cost::deduct_cash_cost)
-                        .deduct(Cost::deduct_coupon_cost)
-                        .deduct(Cost::deduct_vault_cash_cost)
-                        .deduct(Cost::deduct_vault_coupon_cost)
+                    ddr.deduct(Cost::deduct_cash_cost)?
+                        .deduct(Cost::deduct_coupon_cost)?
+                        .deduct(Cost::deduct_vault_cash_cost)?
+                        .deduct(Cost::deduct_vault_coupon_cost)?
                         .collect(),
                 Money::Coupon(_) =>
-                    ddr.deduct(Cost::deduct_coupon_cost)
-                        .deduct(Cost::deduct_cash_cost)
-                        .deduct(Cost::deduct_vault_coupon_cost)
-                        .deduct(Cost::deduct_vault_cash_cost)
+                    ddr.deduct(Cost::deduct_coupon_cost)?
+                        .deduct(Cost::deduct_cash_cost)?
+                        .deduct(Cost::deduct_vault_coupon_cost)?
+                        .deduct(Cost::deduct_vault_cash_cost)?
                         .collect(),
             };
 
         match ddr.remaining.eq(&dec!(0)) {
             true => Ok(deducted),
-            false => Err(io::Error::from(io::ErrorKind::InvalidData)) // Not enough costs to deduct from
+            false => {
+                let msg = format!("Not enough costs of {} to dispose of {}, {} missing", self.currency, paid_amount.abs(), ddr.remaining.abs());
+                Err(CryptotaxError::Pairing(msg))
+            }
         }
     }
 
@@ -211,63 +468,276 @@ struct Deductor<'a> {
 
 impl<'a> Deductor<'a>
 {
-    fn new(costs: &mut Vec<Cost>, paid_amount: Decimal) -> Deductor {
+    fn new(costs: &mut Vec<Cost>, paid_amount: Decimal) -> Deductor<'_> {
        Deductor { costs, remaining: paid_amount, result: vec![] }
     }
 
     /// Use the given closure to deduct costs from `self.costs`
-    fn deduct<T>(&mut self, deduct: T) -> &mut Deductor<'a>
-        where T: Fn(&mut Cost, Decimal) -> Option<Cost>
+    fn deduct<T>(&mut self, deduct: T) -> Result<&mut Deductor<'a>>
+        where T: Fn(&mut Cost, Decimal) -> Result<Option<Cost>>
     {
         if !self.remaining.eq(&dec!(0)) {
-            self.costs.iter_mut().rev().fold((self.remaining, &mut self.result), |(remaining, acc), cost| {
-                if remaining.eq(&dec!(0)) { (remaining, acc) } else {
-                    let amount = remaining.max(cost.paid_amount.neg());
-
-                    match deduct(cost, amount) {
-                        None => (remaining, acc),
-                        Some(cost) => {
-                            acc.push(cost);
-                            self.remaining -= amount;
-                            (remaining.sub(amount), acc)
-                        }
-                    }
+            for cost in self.costs.iter_mut().rev() {
+                if self.remaining.eq(&dec!(0)) {
+                    break;
+                }
+                let amount = self.remaining.max(cost.paid_amount.neg());
+                if let Some(cost) = deduct(cost, amount)? {
+                    self.result.push(cost);
+                    self.remaining = self.remaining.sub(amount);
                 }
-            });
+            }
             self.costs.retain(|c| !c.paid_amount.is_zero());
         }
-        self
+        Ok(self)
+    }
+
+    fn collect(&mut self) -> Vec<Cost> {
+        mem::take(&mut self.result)
+    }
+}
+
+/// The amount of the traded currency that is still held after all transactions, and its costs.
+#[derive(Debug, PartialEq, Default)]
+pub(crate) struct Holding {
+    pub(crate) currency: Currency,
+    pub(crate) amount: Decimal,
+    pub(crate) costs: Vec<Money>,
+    pub(crate) vault: Decimal,             // The part of `amount` held in vaults
+    pub(crate) vault_costs: Vec<Money>,    // The part of `costs` of the `vault`
+}
+
+impl CostBook {
+    fn to_holding(&self) -> Result<Holding> {
+        let vault = self.costs.iter().filter(|c| c.is_vault);
+        Ok(Holding {
+            currency: self.currency.clone(),
+            amount: decimal::sum(self.costs.iter().map(|c| c.paid_amount))?,
+            costs: self.costs.iter().map(|c| c.exchanged.clone()).collect(),
+            vault: decimal::sum(vault.clone().map(|c| c.paid_amount))?,
+            vault_costs: vault.map(|c| c.exchanged.clone()).collect(),
+        })
+    }
+
+    /// The costs left in the book as buys, which open an empty book with the same costs again.
+    /// Cash costs are dated at `date`, coupons at the date they were received.
+    fn to_carryover(&self, date: &str) -> Vec<Transaction> {
+        self.costs.iter()
+            .map(|c| {
+                let (exchanged_currency, date) = match &c.exchanged {
+                    Money::Cash(cash) => (cash.currency.clone(), date.to_string()),
+                    Money::Coupon(coupon) => (coupon.currency.clone(), coupon.date.clone()),
+                };
+                Transaction{
+                    r#type: TransactionType::Buy,
+                    paid_currency: self.currency.clone(),
+                    paid_amount: c.paid_amount,
+                    exchanged_currency,
+                    exchanged_amount: c.exchanged.amount(),
+                    date,
+                    is_vault: c.is_vault,
+                    paid_fee: dec!(0),
+                    exchanged_fee: c.fee,
+                    price_source: None,
+                    unit_price: None,
+                    fiat_value: None,
+                    other_fee: None,
+                    origin: Default::default()
+                }
+            })
+            .collect()
+    }
+}
+
+/// The result of running all transactions through the `CostBook`.
+#[derive(Debug)]
+pub(crate) struct Calculation {
+    /// Disposals where a gain or loss is recognized.
+    pub(crate) taxables: Vec<TaxableTransaction>,
+    /// Disposals where no gain or loss is recognized, see `Jurisdiction::recognizes_gain`.
+    pub(crate) non_taxables: Vec<TaxableTransaction>,
+    /// Profits and losses of margin, futures and short positions, which are not in the `CostBook`.
+    pub(crate) derivatives: Vec<TaxableTransaction>,
+    /// Rewards, interest and cashback received in the traded currency, at their market value.
+    pub(crate) income: Vec<TaxableTransaction>,
+    /// Disposals whose gains are tax free, see `Jurisdiction::exempts_long_term_gains`.
+    pub(crate) exempt: Vec<TaxableTransaction>,
+    pub(crate) holding: Holding,
+    /// The costs of the `holding` as buys dated at the last transaction, to be carried over to
+    /// the next calculation, e.g. the next tax year. With `Matching::Fifo`, the lots as buys dated
+    /// at their acquisition instead, see `lots::Disposals::carryover`.
+    pub(crate) carryover: Vec<Transaction>,
+}
+
+pub(crate) async fn tax(txns: &[Transaction], currency: &Currency, base: &Currency, options: &Options) -> Result<Vec<TaxableTransaction>> {
+    let calculation = calculate(txns, currency, base, options).await?;
+    Ok(calculation.taxables)
+}
+
+/// Calculates tax like `tax`, and also returns the non-taxable disposals and what is left in the
+/// `CostBook` as a `Holding`.
+#[instrument(name = "calculate", skip_all, fields(currency = %currency, jurisdiction = %options.jurisdiction))]
+pub(crate) async fn calculate(txns: &[Transaction], currency: &Currency, base: &Currency, options: &Options) -> Result<Calculation> {
+    let jurisdiction = &options.jurisdiction;
+    let mut book = CostBook::new(currency.clone(), base.clone());
+    let (mut taxables, mut non_taxables, mut derivatives, mut income) = (vec![], vec![], vec![], vec![]);
+    for t in txns {
+        match t.r#type {
+            TransactionType::Buy => book.add_buy(t)?,
+            TransactionType::AddLiquidity | TransactionType::RemoveLiquidity if t.paid_amount.is_sign_positive() => book.add_buy(t)?,
+            TransactionType::Income => {
+                book.add_buy(t)?;
+                income.push(to_income(t, base)?);
+            },
+            TransactionType::Transfer if t.is_vault => book.move_between_pools(t)?,
+            TransactionType::Transfer | TransactionType::Borrow | TransactionType::Repay => {},
+            TransactionType::Margin | TransactionType::Futures | TransactionType::Short => derivatives.push(to_profit_and_loss(t, base)?),
+            TransactionType::Lost | TransactionType::Stolen
+                if jurisdiction.recognizes_gain(&t.r#type) || options.claim_write_offs => {
+                taxables.push(book.add_without_income(t, true)?);
+            },
+            _ if jurisdiction.recognizes_gain(&t.r#type) => taxables.push(book.add_sell(t)?),
+            _ => non_taxables.push(book.add_without_income(t, false)?),
+        }
     }
+    book.costs.iter().for_each(|c| debug!(currency = %book.currency, cost = ?c, "Remaining cost"));
+    taxables.iter().for_each(|t| debug!(taxable = ?t, "Taxable transaction"));
+    non_taxables.iter().for_each(|t| debug!(non_taxable = ?t, "Non-taxable transaction"));
+    let last_date = txns.last().map(|t| t.date.as_str()).unwrap_or_default();
+    Ok(Calculation{ taxables, non_taxables, derivatives, income, exempt: vec![], holding: book.to_holding()?, carryover: book.to_carryover(last_date) })
+}
 
-    fn collect(&self) -> Vec<Cost> {
-        self.result.clone()
+/// A disposal of more of the traded currency than is held, e.g. because earlier buys are missing
+/// from the export. The costs of the disposal can't be found then.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Shortfall {
+    pub(crate) index: usize,        // Of the disposal in the transactions
+    pub(crate) held: Decimal,
+    pub(crate) missing: Decimal,
+}
+
+impl Shortfall {
+    /// A warning at the disposal, with what can be done about it.
+    pub(crate) fn to_warning(&self, txns: &[Transaction]) -> Warning {
+        let t = &txns[self.index];
+        let msg = format!(
+            "Disposed {} {} with only {} held. Import the earlier history of {}, mark the disposal in the \
+             overrides file (--overrides), or use --assume-zero-cost to continue with a zero cost for the missing {}",
+            t.paid_amount.abs(), t.paid_currency, self.held, t.paid_currency, self.missing
+        );
+        Warning::new(Code::NegativeHolding, msg).at(&t.origin).at_date(&t.date)
     }
 }
 
-pub(crate) async fn tax(txns: &Vec<Transaction>, currency: &Currency, base: &Currency) -> io::Result<Vec<TaxableTransaction>> {
-    let book = CostBook::new(currency.clone(), base.clone());
-    let (txns, b) =
-        txns.iter().fold((vec![], book), |(mut acc, mut book), t| {
-            match t.r#type {
-                TransactionType::Buy => book.add_buy(t),
-                TransactionType::Sell => {
-                    let x = book.add_sell(t).unwrap();
-                    acc.push(x);
-                },
+/// Finds the disposals of more than is held, of each currency, e.g. of each NFT. The holding
+/// counts as empty after each of them, as if the missing amount had been bought at zero cost,
+/// see `assume_zero_cost`. What is held is compared at the precision of the currency, see
+/// `precision::normalize`, so that a remainder of a split beyond it is no shortfall.
+pub(crate) fn shortfalls(txns: &[Transaction], precisions: &[Precision]) -> Result<Vec<Shortfall>> {
+    let mut holdings: HashMap<&Currency, Decimal> = HashMap::new();
+    let mut shortfalls = vec![];
+    for (index, t) in txns.iter().enumerate() {
+        if matches!(t.r#type, TransactionType::Transfer | TransactionType::Margin | TransactionType::Futures | TransactionType::Short) {
+            continue;
+        }
+        let held = holdings.entry(&t.paid_currency).or_default();
+        match decimal::add(*held, t.paid_amount)? {
+            after if precision::normalize(after, &t.paid_currency, precisions) < dec!(0) => {
+                shortfalls.push(Shortfall{ index, held: *held, missing: after.neg() });
+                *held = dec!(0);
             }
-            (acc, book)
-        });
-    debug!("Remaining costs for {:?}:", b.currency);
-    b.costs.iter().for_each(|c| debug!("{:?}", c));
-    debug!("Taxable transactions:");
-    txns.iter().for_each(|t| debug!("{:?}", t));
-    Ok(txns)
+            after => *held = after,
+        }
+    }
+    Ok(shortfalls)
+}
+
+/// Adds a buy of the missing amount at zero cost right before every shortfall, so that the
+/// disposal has a zero cost basis for what was missing.
+pub(crate) fn assume_zero_cost(txns: &mut Vec<Transaction>, shortfalls: &[Shortfall], base: &Currency) {
+    for s in shortfalls.iter().rev() {
+        let disposal = &txns[s.index];
+        let buy = Transaction{
+            r#type: TransactionType::Buy,
+            paid_currency: disposal.paid_currency.clone(),
+            paid_amount: s.missing,
+            exchanged_currency: base.clone(),
+            exchanged_amount: dec!(0),
+            date: disposal.date.clone(),
+            is_vault: false,
+            paid_fee: dec!(0),
+            exchanged_fee: dec!(0),
+            price_source: None,
+            unit_price: None,
+            fiat_value: None,
+            other_fee: None,
+            origin: disposal.origin.clone(),
+        };
+        txns.insert(s.index, buy);
+    }
+}
+
+/// The profits and losses of the margin, futures and short transactions, in the order of the
+/// transactions, like `Calculation::derivatives` but without running the rest through the `CostBook`.
+pub(crate) fn derivatives(txns: &[Transaction], base: &Currency) -> Result<Vec<TaxableTransaction>> {
+    txns.iter()
+        .filter(|t| matches!(t.r#type, TransactionType::Margin | TransactionType::Futures | TransactionType::Short))
+        .map(|t| to_profit_and_loss(t, base))
+        .collect()
+}
+
+/// A margin, futures or short transaction is already a gain or loss, without any costs.
+fn to_profit_and_loss(transaction: &Transaction, base: &Currency) -> Result<TaxableTransaction> {
+    let income = transaction.to_money(base);
+    let net_income = income.to_net_income(&[])?;
+    Ok(TaxableTransaction{
+        r#type: transaction.r#type.clone(),
+        date: transaction.date.clone(),
+        currency: transaction.paid_currency.clone(),
+        amount: transaction.paid_amount,
+        income,
+        costs: vec![],
+        net_income,
+        acquisition_fee: dec!(0),
+        disposal_fee: transaction.exchanged_fee,
+        origin: transaction.origin.clone(),
+        consumed: vec![]
+    })
+}
+
+/// Income is valued at what it costs as an acquisition, so the income of a reward is its negated
+/// cost.
+fn to_income(transaction: &Transaction, base: &Currency) -> Result<TaxableTransaction> {
+    let income = match transaction.to_money(base) {
+        Money::Cash(cash) => Money::new_cash(cash.currency, -cash.amount),
+        Money::Coupon(coupon) => Money::new_coupon(coupon.currency, -coupon.amount, coupon.date),
+    };
+    let net_income = income.to_net_income(&[])?;
+    Ok(TaxableTransaction{
+        r#type: transaction.r#type.clone(),
+        date: transaction.date.clone(),
+        currency: transaction.paid_currency.clone(),
+        amount: transaction.paid_amount,
+        income,
+        costs: vec![],
+        net_income,
+        acquisition_fee: dec!(0),
+        disposal_fee: dec!(0),
+        origin: transaction.origin.clone(),
+        consumed: vec![]
+    })
 }
 
 #[cfg(test)]
 mod test {
-    use crate::calculator::{Cost, CostBook, TaxableTransaction};
-    use crate::transaction::{Cash, Coupon, Money, Transaction, TransactionType};
+    use crate::bundle;
+    use crate::calculator::{assume_zero_cost, calculate, shortfalls, tax, Columns, Consumed, Cost, CostBook, Shortfall, TaxableTransaction};
+    use crate::cryptotax::Options;
+    use crate::error::CryptotaxError;
+    use crate::jurisdiction::FeePolicy;
+    use crate::transaction::{Cash, Coupon, Money, Origin, Transaction, TransactionType};
+    use crate::writer;
+    use futures::executor::block_on;
     use rust_decimal_macros::dec;
     use std::error::Error;
 
@@ -288,9 +758,16 @@ mod test {
             exchanged_currency: "SEK".to_string(),
             exchanged_amount: dec!(-20),
             date: "2021-11-11 18:03:13".to_string(),
-            is_vault: true
+            is_vault: true,
+            paid_fee: dec!(0),
+            exchanged_fee: dec!(0),
+            price_source: None,
+            unit_price: None,
+            fiat_value: None,
+            other_fee: None,
+            origin: Default::default()
         };
-        book.add_buy(&txn);
+        book.add_buy(&txn)?;
 
         let txn = Transaction{
             r#type: TransactionType::Buy,
@@ -299,9 +776,16 @@ mod test {
             exchanged_currency: "SEK".to_string(),
             exchanged_amount: dec!(-5080.60),
             date: "2021-12-31 17:54:48".to_string(),
-            is_vault: false
+            is_vault: false,
+            paid_fee: dec!(0),
+            exchanged_fee: dec!(0),
+            price_source: None,
+            unit_price: None,
+            fiat_value: None,
+            other_fee: None,
+            origin: Default::default()
         };
-        book.add_buy(&txn);
+        book.add_buy(&txn)?;
 
         let txn = Transaction{
             r#type: TransactionType::Buy,
@@ -310,9 +794,16 @@ mod test {
             exchanged_currency: "EOS".to_string(),
             exchanged_amount: dec!(-500),
             date: "2022-02-03 10:30:29".to_string(),
-            is_vault: false
+            is_vault: false,
+            paid_fee: dec!(0),
+            exchanged_fee: dec!(0),
+            price_source: None,
+            unit_price: None,
+            fiat_value: None,
+            other_fee: None,
+            origin: Default::default()
         };
-        book.add_buy(&txn);
+        book.add_buy(&txn)?;
 
         let txn = Transaction{
             r#type: TransactionType::Buy,
@@ -321,9 +812,16 @@ mod test {
             exchanged_currency: "EOS".to_string(),
             exchanged_amount: dec!(-62.35),
             date: "2022-02-04 11:01:35".to_string(),
-            is_vault: false
+            is_vault: false,
+            paid_fee: dec!(0),
+            exchanged_fee: dec!(0),
+            price_source: None,
+            unit_price: None,
+            fiat_value: None,
+            other_fee: None,
+            origin: Default::default()
         };
-        book.add_buy(&txn);
+        book.add_buy(&txn)?;
 
         /*
          * Then
@@ -332,22 +830,30 @@ mod test {
         assert_eq!(iter.next(), Some(&Cost{
             paid_amount: dec!(39.94),
             exchanged: Money::new_cash("SEK".to_string(), dec!(-20)),
-            is_vault: true
+            is_vault: true,
+            fee: dec!(0),
+            origins: vec![Default::default()].into()
         }));
         assert_eq!(iter.next(), Some(&Cost{
             paid_amount: dec!(2000),
             exchanged: Money::new_cash("SEK".to_string(), dec!(-5080.6)),
-            is_vault: false
+            is_vault: false,
+            fee: dec!(0),
+            origins: vec![Default::default()].into()
         }));
         assert_eq!(iter.next(), Some(&Cost{
             paid_amount: dec!(200),
             exchanged: Money::new_coupon("EOS".to_string(), dec!(-500), "2022-02-03 10:30:29".to_string()),
-            is_vault: false
+            is_vault: false,
+            fee: dec!(0),
+            origins: vec![Default::default()].into()
         }));
         assert_eq!(iter.next(), Some(&Cost{
             paid_amount: dec!(30.3),
             exchanged: Money::new_coupon("EOS".to_string(), dec!(-62.35), "2022-02-04 11:01:35".to_string()),
-            is_vault: false
+            is_vault: false,
+            fee: dec!(0),
+            origins: vec![Default::default()].into()
         }));
         assert_eq!(iter.next(), None);
 
@@ -380,7 +886,14 @@ mod test {
             exchanged_currency: "SEK".to_string(),
             exchanged_amount: dec!(200.63),
             date: "2022-05-05 05:01:12".to_string(),
-            is_vault: false
+            is_vault: false,
+            paid_fee: dec!(0),
+            exchanged_fee: dec!(0),
+            price_source: None,
+            unit_price: None,
+            fiat_value: None,
+            other_fee: None,
+            origin: Default::default()
         };
         let x = book.add_sell(&txn)?;
 
@@ -388,12 +901,17 @@ mod test {
          * Then
          */
         assert_eq!(x, TaxableTransaction{
+            r#type: TransactionType::Sell,
             date: "2022-05-05 05:01:12".to_string(),
             currency: "DOGE".to_string(),
             amount: dec!(-50),
             income: Money::Cash(Cash{ currency: "SEK".to_string(), amount: dec!(200.63) }),
             costs: vec![Money::Cash(Cash{ currency: "SEK".to_string(), amount: dec!(-105) })],
-            net_income: Some(dec!(95.63))
+            net_income: Some(dec!(95.63)),
+            acquisition_fee: dec!(0),
+            disposal_fee: dec!(0),
+            origin: Default::default(),
+            consumed: vec![Consumed{ amount: dec!(50), costs: vec![Money::Cash(Cash{ currency: "SEK".to_string(), amount: dec!(-105) })], origins: vec![].into(), acquired: None }]
         });
 
         let txn = Transaction{
@@ -403,16 +921,28 @@ mod test {
             exchanged_currency: "BTC".to_string(),
             exchanged_amount: dec!(0.0000201),
             date: "2022-07-06 06:02:13".to_string(),
-            is_vault: false
+            is_vault: false,
+            paid_fee: dec!(0),
+            exchanged_fee: dec!(0),
+            price_source: None,
+            unit_price: None,
+            fiat_value: None,
+            other_fee: None,
+            origin: Default::default()
         };
         let x = book.add_sell(&txn)?;
         assert_eq!(x, TaxableTransaction{
+            r#type: TransactionType::Sell,
             date: "2022-07-06 06:02:13".to_string(),
             currency: "DOGE".to_string(),
             amount: dec!(-50),
             income: Money::Coupon(Coupon{ currency: "BTC".to_string(), amount: dec!(0.0000201), date: "2022-07-06 06:02:13".to_string() }),
             costs: vec![Money::Coupon(Coupon{ currency: "BTC".to_string(), amount: dec!(-0.000000505), date: "2021-03-04 11:31:30".to_string() })],
-            net_income: None
+            net_income: None,
+            acquisition_fee: dec!(0),
+            disposal_fee: dec!(0),
+            origin: Default::default(),
+            consumed: vec![Consumed{ amount: dec!(50), costs: vec![Money::Coupon(Coupon{ currency: "BTC".to_string(), amount: dec!(-0.000000505), date: "2021-03-04 11:31:30".to_string() })], origins: vec![].into(), acquired: None }]
         });
 
         let txn = Transaction{
@@ -422,10 +952,18 @@ mod test {
             exchanged_currency: "BCH".to_string(),
             exchanged_amount: dec!(325),
             date: "2022-08-07 07:03:14".to_string(),
-            is_vault: false
+            is_vault: false,
+            paid_fee: dec!(0),
+            exchanged_fee: dec!(0),
+            price_source: None,
+            unit_price: None,
+            fiat_value: None,
+            other_fee: None,
+            origin: Default::default()
         };
         let x = book.add_sell(&txn)?;
         assert_eq!(x, TaxableTransaction{
+            r#type: TransactionType::Sell,
             date: "2022-08-07 07:03:14".to_string(),
             currency: "DOGE".to_string(),
             amount: dec!(-1250),
@@ -434,7 +972,14 @@ mod test {
                        , Money::Coupon(Coupon{ currency: "EOS".to_string(), amount: dec!(-500), date: "2021-02-03 10:30:29".to_string() })
                        , Money::Cash(Cash{ currency: "SEK".to_string(), amount: dec!(-210) })
                        ],
-            net_income: None
+            net_income: None,
+            acquisition_fee: dec!(0),
+            disposal_fee: dec!(0),
+            origin: Default::default(),
+            consumed: vec![ Consumed{ amount: dec!(950), costs: vec![Money::Coupon(Coupon{ currency: "BTC".to_string(), amount: dec!(-0.000009595), date: "2021-03-04 11:31:30".to_string() })], origins: vec![].into(), acquired: None }
+                         , Consumed{ amount: dec!(200), costs: vec![Money::Coupon(Coupon{ currency: "EOS".to_string(), amount: dec!(-500), date: "2021-02-03 10:30:29".to_string() })], origins: vec![].into(), acquired: None }
+                         , Consumed{ amount: dec!(100), costs: vec![Money::Cash(Cash{ currency: "SEK".to_string(), amount: dec!(-210) })], origins: vec![].into(), acquired: None }
+                         ]
         });
 
         Ok(())
@@ -444,22 +989,598 @@ mod test {
     fn should_deduct_from_cost() -> Result<(), Box<dyn Error>> {
         let cash = Money::new_cash("SEK".to_string(), dec!(-16000));
         let mut cost = Cost::new(dec!(7500), cash, true);
-        let deducted = cost.deduct(dec!(-500));
+        let deducted = cost.deduct(dec!(-500))?;
         assert_eq!(deducted, Some(Cost{
             paid_amount: dec!(500),
             exchanged: Money::Cash(Cash{ currency: "SEK".to_string(), amount: dec!(-1066.6666666666666666666666666) }),
-            is_vault: true
+            is_vault: true,
+            fee: dec!(0),
+            origins: vec![].into()
         }));
 
         let coupon = Money::new_coupon("EOS".to_string(), dec!(-500), "2021-02-03 10:30:29".to_string());
         let mut cost = Cost::new(dec!(200), coupon, false);
-        let deducted = cost.deduct(dec!(-50));
+        let deducted = cost.deduct(dec!(-50))?;
         assert_eq!(deducted, Some(Cost{
             paid_amount: dec!(50),
             exchanged: Money::Coupon(Coupon{ currency: "EOS".to_string(), amount: dec!(-125), date: "2021-02-03 10:30:29".to_string()}),
-            is_vault: false
+            is_vault: false,
+            fee: dec!(0),
+            origins: vec![].into()
         }));
 
         Ok(())
     }
+    #[test]
+    fn should_carry_over_cost_of_gift() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let txns = vec![
+            Transaction{
+                r#type: TransactionType::Buy,
+                paid_currency: "BTC".to_string(),
+                paid_amount: dec!(0.2),
+                exchanged_currency: "SEK".to_string(),
+                exchanged_amount: dec!(-6000),
+                date: "2021-01-01 10:00:00".to_string(),
+                is_vault: false,
+                paid_fee: dec!(0),
+                exchanged_fee: dec!(0),
+                price_source: None,
+                unit_price: None,
+                fiat_value: None,
+                other_fee: None,
+                origin: Default::default()
+            },
+            Transaction{
+                r#type: TransactionType::Gift,
+                paid_currency: "BTC".to_string(),
+                paid_amount: dec!(-0.05),
+                exchanged_currency: "".to_string(),
+                exchanged_amount: dec!(0),
+                date: "2021-02-01 10:00:00".to_string(),
+                is_vault: false,
+                paid_fee: dec!(0),
+                exchanged_fee: dec!(0),
+                price_source: None,
+                unit_price: None,
+                fiat_value: None,
+                other_fee: None,
+                origin: Default::default()
+            },
+            Transaction{
+                r#type: TransactionType::Transfer,
+                paid_currency: "BTC".to_string(),
+                paid_amount: dec!(-0.05),
+                exchanged_currency: "".to_string(),
+                exchanged_amount: dec!(0),
+                date: "2021-03-01 10:00:00".to_string(),
+                is_vault: false,
+                paid_fee: dec!(0),
+                exchanged_fee: dec!(0),
+                price_source: None,
+                unit_price: None,
+                fiat_value: None,
+                other_fee: None,
+                origin: Default::default()
+            },
+        ];
+
+        /*
+         * When
+         */
+        let calculation = block_on(calculate(&txns, &"BTC".to_string(), &"SEK".to_string(), &Options::default()))?;
+
+        /*
+         * Then
+         */
+        assert_eq!(calculation.taxables, vec![]);
+        assert_eq!(calculation.non_taxables, vec![TaxableTransaction{
+            r#type: TransactionType::Gift,
+            date: "2021-02-01 10:00:00".to_string(),
+            currency: "BTC".to_string(),
+            amount: dec!(-0.05),
+            income: Money::Cash(Cash{ currency: "SEK".to_string(), amount: dec!(0) }),
+            costs: vec![Money::Cash(Cash{ currency: "SEK".to_string(), amount: dec!(-1500) })],
+            net_income: None,
+            acquisition_fee: dec!(0),
+            disposal_fee: dec!(0),
+            origin: Default::default(),
+            consumed: vec![Consumed{ amount: dec!(0.05), costs: vec![Money::Cash(Cash{ currency: "SEK".to_string(), amount: dec!(-1500) })], origins: vec![Default::default()].into(), acquired: None }]
+        }]);
+        assert_eq!(calculation.holding.amount, dec!(0.15));
+
+        Ok(())
+    }
+    #[test]
+    fn should_write_off_stolen() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let txns = vec![
+            Transaction{
+                r#type: TransactionType::Buy,
+                paid_currency: "BTC".to_string(),
+                paid_amount: dec!(0.2),
+                exchanged_currency: "SEK".to_string(),
+                exchanged_amount: dec!(-6000),
+                date: "2021-01-01 10:00:00".to_string(),
+                is_vault: false,
+                paid_fee: dec!(0),
+                exchanged_fee: dec!(0),
+                price_source: None,
+                unit_price: None,
+                fiat_value: None,
+                other_fee: None,
+                origin: Default::default()
+            },
+            Transaction{
+                r#type: TransactionType::Stolen,
+                paid_currency: "BTC".to_string(),
+                paid_amount: dec!(-0.1),
+                exchanged_currency: "".to_string(),
+                exchanged_amount: dec!(0),
+                date: "2021-02-01 10:00:00".to_string(),
+                is_vault: false,
+                paid_fee: dec!(0),
+                exchanged_fee: dec!(0),
+                price_source: None,
+                unit_price: None,
+                fiat_value: None,
+                other_fee: None,
+                origin: Default::default()
+            },
+        ];
+
+        /*
+         * When
+         */
+        let not_claimed = block_on(calculate(&txns, &"BTC".to_string(), &"SEK".to_string(), &Options::default()))?;
+        let options = Options{ claim_write_offs: true, ..Default::default() };
+        let claimed = block_on(calculate(&txns, &"BTC".to_string(), &"SEK".to_string(), &options))?;
+
+        /*
+         * Then
+         */
+        assert_eq!(not_claimed.taxables, vec![]);
+        assert_eq!(not_claimed.non_taxables.len(), 1);
+        assert_eq!(not_claimed.non_taxables[0].net_income, None);
+        assert_eq!(not_claimed.holding.amount, dec!(0.1));
+
+        assert_eq!(claimed.non_taxables, vec![]);
+        assert_eq!(claimed.taxables, vec![TaxableTransaction{
+            r#type: TransactionType::Stolen,
+            date: "2021-02-01 10:00:00".to_string(),
+            currency: "BTC".to_string(),
+            amount: dec!(-0.1),
+            income: Money::Cash(Cash{ currency: "SEK".to_string(), amount: dec!(0) }),
+            costs: vec![Money::Cash(Cash{ currency: "SEK".to_string(), amount: dec!(-3000) })],
+            net_income: Some(dec!(-3000)),
+            acquisition_fee: dec!(0),
+            disposal_fee: dec!(0),
+            origin: Default::default(),
+            consumed: vec![Consumed{ amount: dec!(0.1), costs: vec![Money::Cash(Cash{ currency: "SEK".to_string(), amount: dec!(-3000) })], origins: vec![Default::default()].into(), acquired: None }]
+        }]);
+        assert_eq!(claimed.holding.amount, dec!(0.1));
+
+        Ok(())
+    }
+    #[test]
+    fn should_track_fees() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let txns = vec![
+            Transaction{
+                r#type: TransactionType::Buy,
+                paid_currency: "BTC".to_string(),
+                paid_amount: dec!(1),
+                exchanged_currency: "SEK".to_string(),
+                exchanged_amount: dec!(-1010),
+                date: "2021-01-01 10:00:00".to_string(),
+                is_vault: false,
+                paid_fee: dec!(0),
+                exchanged_fee: dec!(-10),
+                price_source: None,
+                unit_price: None,
+                fiat_value: None,
+                other_fee: None,
+                origin: Default::default()
+            },
+            Transaction{
+                r#type: TransactionType::Buy,
+                paid_currency: "BTC".to_string(),
+                paid_amount: dec!(1),
+                exchanged_currency: "SEK".to_string(),
+                exchanged_amount: dec!(-2020),
+                date: "2021-02-01 10:00:00".to_string(),
+                is_vault: false,
+                paid_fee: dec!(0),
+                exchanged_fee: dec!(-20),
+                price_source: None,
+                unit_price: None,
+                fiat_value: None,
+                other_fee: None,
+                origin: Default::default()
+            },
+            Transaction{
+                r#type: TransactionType::Sell,
+                paid_currency: "BTC".to_string(),
+                paid_amount: dec!(-1),
+                exchanged_currency: "SEK".to_string(),
+                exchanged_amount: dec!(1495),
+                date: "2021-03-01 10:00:00".to_string(),
+                is_vault: false,
+                paid_fee: dec!(0),
+                exchanged_fee: dec!(-5),
+                price_source: None,
+                unit_price: None,
+                fiat_value: None,
+                other_fee: None,
+                origin: Default::default()
+            },
+        ];
+
+        /*
+         * When
+         */
+        let txns = block_on(tax(&txns, &"BTC".to_string(), &"SEK".to_string(), &Options::default()))?;
+
+        /*
+         * Then
+         */
+        assert_eq!(txns, vec![TaxableTransaction{
+            r#type: TransactionType::Sell,
+            date: "2021-03-01 10:00:00".to_string(),
+            currency: "BTC".to_string(),
+            amount: dec!(-1),
+            income: Money::Cash(Cash{ currency: "SEK".to_string(), amount: dec!(1495) }),
+            costs: vec![Money::Cash(Cash{ currency: "SEK".to_string(), amount: dec!(-1515) })],
+            net_income: Some(dec!(-20)),
+            acquisition_fee: dec!(-15),
+            disposal_fee: dec!(-5),
+            origin: Default::default(),
+            consumed: vec![Consumed{ amount: dec!(1), costs: vec![Money::Cash(Cash{ currency: "SEK".to_string(), amount: dec!(-1515) })], origins: vec![Default::default(), Default::default()].into(), acquired: None }]
+        }]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_treat_fees_of_disposal_by_policy() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let txns = vec![
+            Transaction::buy().currency("BTC").amount(dec!(1)).exchanged_currency("SEK").exchanged_amount(dec!(-1010)).exchanged_fee(dec!(-10)).date("2021-01-01 10:00:00").build()?,
+            Transaction::sell().currency("BTC").amount(dec!(-1)).exchanged_currency("SEK").exchanged_amount(dec!(1495)).exchanged_fee(dec!(-5)).date("2021-03-01 10:00:00").build()?,
+        ];
+
+        /*
+         * When
+         */
+        let treated = |policy| -> Result<_, Box<dyn Error>> {
+            let mut taxables = block_on(tax(&txns, &"BTC".to_string(), &"SEK".to_string(), &Options::default()))?;
+            taxables[0].with_fees(policy)?;
+            Ok((taxables[0].income.amount(), taxables[0].costs[0].amount(), taxables[0].net_income))
+        };
+
+        /*
+         * Then
+         */
+        assert_eq!(treated(FeePolicy::Deduct)?, (dec!(1495), dec!(-1010), Some(dec!(485))));
+        assert_eq!(treated(FeePolicy::Capitalize)?, (dec!(1500), dec!(-1015), Some(dec!(485))));
+        assert_eq!(treated(FeePolicy::Separate)?, (dec!(1500), dec!(-1000), Some(dec!(500))));
+        Ok(())
+    }
+
+    #[test]
+    fn should_carry_over_costs() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let txn = |r#type, paid_amount, exchanged_currency: &str, exchanged_amount, date: &str| Transaction{
+            r#type,
+            paid_currency: "BTC".to_string(),
+            paid_amount,
+            exchanged_currency: exchanged_currency.to_string(),
+            exchanged_amount,
+            date: date.to_string(),
+            is_vault: false,
+            paid_fee: dec!(0),
+            exchanged_fee: dec!(0),
+            price_source: None,
+            unit_price: None,
+            fiat_value: None,
+            other_fee: None,
+            origin: Default::default()
+        };
+        let year_2021 = || vec![
+            txn(TransactionType::Buy, dec!(1), "SEK", dec!(-30000), "2021-01-01 10:00:00"),
+            txn(TransactionType::Buy, dec!(0.5), "ETH", dec!(-10), "2021-02-01 10:00:00"),
+            txn(TransactionType::Sell, dec!(-0.5), "SEK", dec!(20000), "2021-03-01 10:00:00"),
+        ];
+        let year_2022 = || vec![
+            txn(TransactionType::Sell, dec!(-1), "SEK", dec!(50000), "2022-01-01 10:00:00"),
+        ];
+        let (btc, sek, options) = ("BTC".to_string(), "SEK".to_string(), Options::default());
+
+        /*
+         * When
+         */
+        let carryover = block_on(calculate(&year_2021(), &btc, &sek, &options))?.carryover;
+        let mut txns = year_2022();
+        txns.extend(carryover);
+        txns.sort_by(Transaction::cmp_by_date);
+        let from_carryover = block_on(calculate(&txns, &btc, &sek, &options))?;
+
+        let mut txns = year_2021();
+        txns.extend(year_2022());
+        let from_history = block_on(calculate(&txns, &btc, &sek, &options))?;
+
+        /*
+         * Then
+         */
+        assert_eq!(from_carryover.taxables.last(), from_history.taxables.last());
+        assert_eq!(from_carryover.holding, from_history.holding);
+        Ok(())
+    }
+    #[test]
+    fn should_assume_zero_cost_of_missing_amount() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let txn = |r#type, paid_amount, exchanged_amount, date: &str| Transaction{
+            r#type,
+            paid_currency: "BTC".to_string(),
+            paid_amount,
+            exchanged_currency: "SEK".to_string(),
+            exchanged_amount,
+            date: date.to_string(),
+            is_vault: false,
+            paid_fee: dec!(0),
+            exchanged_fee: dec!(0),
+            price_source: None,
+            unit_price: None,
+            fiat_value: None,
+            other_fee: None,
+            origin: Default::default()
+        };
+        let mut txns = vec![
+            txn(TransactionType::Buy, dec!(0.1), dec!(-1000), "2021-01-01 10:00:00"),
+            txn(TransactionType::Sell, dec!(-0.3), dec!(9000), "2021-02-01 10:00:00"),
+            txn(TransactionType::Buy, dec!(0.1), dec!(-2000), "2021-03-01 10:00:00"),
+        ];
+
+        /*
+         * When
+         */
+        let without = block_on(tax(&txns, &"BTC".to_string(), &"SEK".to_string(), &Options::default())).map_err(|e| e.to_string());
+        let shortfalls = shortfalls(&txns, &[])?;
+        assume_zero_cost(&mut txns, &shortfalls, &"SEK".to_string());
+        let taxables = block_on(tax(&txns, &"BTC".to_string(), &"SEK".to_string(), &Options::default()))?;
+
+        /*
+         * Then
+         */
+        assert_eq!(without.map(|_| ()), Err("Not enough costs of BTC to dispose of 0.3, 0.2 missing".to_string()));
+        assert_eq!(shortfalls, vec![Shortfall{ index: 1, held: dec!(0.1), missing: dec!(0.2) }]);
+        assert_eq!(txns.len(), 4);
+        assert_eq!(txns[1].paid_amount, dec!(0.2));
+        assert_eq!(taxables[0].costs, vec![Money::new_cash("SEK".to_string(), dec!(-1000))]);
+        assert_eq!(taxables[0].net_income, Some(dec!(8000)));
+        Ok(())
+    }
+
+    #[test]
+    fn should_acquire_income_at_market_value() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let txn = |r#type, paid_amount, exchanged_amount, date: &str| Transaction{
+            r#type,
+            paid_currency: "BTC".to_string(),
+            paid_amount,
+            exchanged_currency: "SEK".to_string(),
+            exchanged_amount,
+            date: date.to_string(),
+            is_vault: false,
+            paid_fee: dec!(0),
+            exchanged_fee: dec!(0),
+            price_source: None,
+            unit_price: None,
+            fiat_value: None,
+            other_fee: None,
+            origin: Default::default()
+        };
+        let txns = vec![
+            txn(TransactionType::Buy, dec!(0.1), dec!(-3000), "2021-01-01 10:00:00"),
+            txn(TransactionType::Income, dec!(0.1), dec!(-5000), "2021-06-01 10:00:00"),
+            txn(TransactionType::Sell, dec!(-0.2), dec!(10000), "2022-01-01 10:00:00"),
+        ];
+        let (btc, sek, options) = ("BTC".to_string(), "SEK".to_string(), Options::default());
+
+        /*
+         * When
+         */
+        let calculation = block_on(calculate(&txns, &btc, &sek, &options))?;
+
+        /*
+         * Then
+         */
+        assert_eq!(calculation.income.len(), 1);
+        assert_eq!(calculation.income[0].income, Money::new_cash("SEK".to_string(), dec!(5000)));
+        assert_eq!(calculation.income[0].net_income, Some(dec!(5000)));
+        assert_eq!(calculation.taxables[0].net_income, Some(dec!(2000)));
+        Ok(())
+    }
+
+    #[test]
+    fn should_move_costs_to_and_from_the_vault() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let txn = |r#type, paid_amount, exchanged_amount, is_vault, date: &str| Transaction{
+            r#type,
+            paid_currency: "BTC".to_string(),
+            paid_amount,
+            exchanged_currency: if exchanged_amount == dec!(0) { "".to_string() } else { "SEK".to_string() },
+            exchanged_amount,
+            date: date.to_string(),
+            is_vault,
+            paid_fee: dec!(0),
+            exchanged_fee: dec!(0),
+            price_source: None,
+            unit_price: None,
+            fiat_value: None,
+            other_fee: None,
+            origin: Default::default()
+        };
+        let txns = vec![
+            txn(TransactionType::Buy, dec!(0.1), dec!(-3000), false, "2021-01-01 10:00:00"),
+            txn(TransactionType::Buy, dec!(0.02), dec!(-800), true, "2021-02-01 10:00:00"),     // Round-ups
+            txn(TransactionType::Transfer, dec!(-0.05), dec!(0), true, "2021-03-01 10:00:00"),  // To BTC Vault
+            txn(TransactionType::Transfer, dec!(0.035), dec!(0), true, "2021-04-01 10:00:00"),  // From BTC Vault
+        ];
+        let (btc, sek, options) = ("BTC".to_string(), "SEK".to_string(), Options::default());
+
+        /*
+         * When
+         */
+        let calculation = block_on(calculate(&txns, &btc, &sek, &options))?;
+
+        /*
+         * Then
+         */
+        assert!(calculation.taxables.is_empty());
+        assert_eq!(calculation.holding.amount, dec!(0.12));
+        assert_eq!(calculation.holding.vault, dec!(0.035));
+        assert_eq!(calculation.holding.vault_costs, vec![Money::new_cash("SEK".to_string(), dec!(-1150))]);
+        assert_eq!(calculation.holding.costs.iter().fold(dec!(0), |acc, c| acc + c.amount()), dec!(-3800));
+        Ok(())
+    }
+
+    #[test]
+    fn should_fail_on_disposal_of_more_than_is_held() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let txn = |r#type, paid_amount, exchanged_amount, is_vault, date: &str| Transaction{
+            r#type,
+            paid_currency: "BTC".to_string(),
+            paid_amount,
+            exchanged_currency: if exchanged_amount == dec!(0) { "".to_string() } else { "SEK".to_string() },
+            exchanged_amount,
+            date: date.to_string(),
+            is_vault,
+            paid_fee: dec!(0),
+            exchanged_fee: dec!(0),
+            price_source: None,
+            unit_price: None,
+            fiat_value: None,
+            other_fee: None,
+            origin: Default::default()
+        };
+        let txns = vec![
+            txn(TransactionType::Buy, dec!(0.1), dec!(-3000), false, "2021-01-01 10:00:00"),
+            txn(TransactionType::Buy, dec!(0.02), dec!(-800), true, "2021-02-01 10:00:00"),     // Round-ups
+            txn(TransactionType::Transfer, dec!(-0.05), dec!(0), true, "2021-03-01 10:00:00"),  // To BTC Vault
+            txn(TransactionType::Sell, dec!(-0.2), dec!(9000), false, "2021-04-01 10:00:00"),
+        ];
+        let (btc, sek, options) = ("BTC".to_string(), "SEK".to_string(), Options::default());
+
+        /*
+         * When
+         */
+        let error = block_on(calculate(&txns, &btc, &sek, &options)).err().ok_or("The disposal was calculated")?;
+
+        /*
+         * Then
+         */
+        assert!(matches!(error, CryptotaxError::Pairing(_)));
+        assert_eq!(error.to_string(), "Not enough costs of BTC to dispose of 0.2, 0.08 missing");
+        Ok(())
+    }
+
+    #[test]
+    fn should_log_phase_with_its_fields() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let txns = vec![Transaction{
+            r#type: TransactionType::Buy,
+            paid_currency: "BTC".to_string(),
+            paid_amount: dec!(0.1),
+            exchanged_currency: "SEK".to_string(),
+            exchanged_amount: dec!(-3000),
+            date: "2021-01-01 10:00:00".to_string(),
+            is_vault: false,
+            paid_fee: dec!(0),
+            exchanged_fee: dec!(0),
+            price_source: None,
+            unit_price: None,
+            fiat_value: None,
+            other_fee: None,
+            origin: Default::default()
+        }];
+        let (btc, sek, options) = ("BTC".to_string(), "SEK".to_string(), Options::default());
+
+        /*
+         * When
+         */
+        let (calculation, log) = bundle::capture_log(|| block_on(calculate(&txns, &btc, &sek, &options)));
+        let log = String::from_utf8(log)?;
+
+        /*
+         * Then
+         */
+        calculation?;
+        assert!(log.contains("INFO calculate{currency=BTC jurisdiction=SE}: cryptotax::calculator: close time.busy="), "{}", log);
+        Ok(())
+    }
+
+    #[test]
+    fn should_write_counterparty_column() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let taxable = |date: &str, counterparty: Option<&str>| TaxableTransaction{
+            r#type: TransactionType::Sell,
+            date: date.to_string(),
+            currency: "BTC".to_string(),
+            amount: dec!(-0.001),
+            income: Money::new_cash("SEK".to_string(), dec!(350)),
+            costs: vec![Money::new_cash("SEK".to_string(), dec!(-300))],
+            net_income: Some(dec!(50)),
+            acquisition_fee: dec!(0),
+            disposal_fee: dec!(0),
+            origin: Origin{ counterparty: counterparty.map(str::to_string), ..Default::default() },
+            consumed: vec![],
+        };
+        let taxables = vec![
+            taxable("2022-11-05 10:30:00", Some("Coffee Corner")),
+            taxable("2022-12-01 10:00:00", None),
+        ];
+
+        /*
+         * When
+         */
+        let mut with = vec![];
+        block_on(writer::write(&Columns::of(&taxables, &Options::default())?, &mut with))?;
+        let mut without = vec![];
+        block_on(writer::write(&Columns::of(&taxables[1..], &Options::default())?, &mut without))?;
+
+        /*
+         * Then
+         */
+        assert_eq!(String::from_utf8(with)?, "\
+            Date;Currency;Amount;Income;Cost;Net Income;Counterparty\n\
+            2022-11-05 10:30:00;BTC;-0.001;350;-300;50;Coffee Corner\n\
+            2022-12-01 10:00:00;BTC;-0.001;350;-300;50;\n");
+        assert_eq!(String::from_utf8(without)?, "\
+            Date;Currency;Amount;Income;Cost;Net Income\n\
+            2022-12-01 10:00:00;BTC;-0.001;350;-300;50\n");
+        Ok(())
+    }
 }
@@ -0,0 +1,187 @@
+use crate::calculator::{Calculation, TaxableTransaction};
+use crate::decimal;
+use crate::error::Result;
+use crate::transaction::{Currency, Money, Transaction, TransactionType};
+use chrono::NaiveDate;
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+/// The size of the SVG chart, and the margin around the plot for the labels.
+const WIDTH: f64 = 800.0;
+const HEIGHT: f64 = 400.0;
+const MARGIN: f64 = 60.0;
+
+/// A point of the time series of a currency: the state at the end of a day with transactions.
+#[derive(Debug, PartialEq, Serialize)]
+pub(crate) struct ChartPoint {
+    #[serde(rename = "Date")]
+    date: String,
+
+    #[serde(rename = "Realized")]
+    realized: Decimal,              // The net income of the disposals until then, in the base currency
+
+    #[serde(rename = "Amount")]
+    amount: Decimal,                // Held of the traded currency
+
+    #[serde(rename = "Cost Basis")]
+    cost_basis: Decimal,            // Of the amount held, positive, in the base currency
+
+    #[serde(rename = "Value")]
+    value: Option<Decimal>,         // Of the amount held, at the unit price of the latest trade
+}
+
+/// What changed on a day.
+#[derive(Default)]
+struct Day {
+    realized: Decimal,
+    amount: Decimal,
+    cost_basis: Decimal,
+    price: Option<Decimal>,
+}
+
+/// The time series of the realized gains, the holding, its cost basis and its value, a point for
+/// every day with transactions. The cost basis is what the acquisitions in the base currency cost
+/// less the costs of the disposals, so acquisitions for other crypto currencies that are not
+/// valued are left out. The value is at the unit price of the latest trade, see
+/// `price::annotate`.
+pub(crate) fn series(txns: &[Transaction], calculation: &Calculation, base: &Currency) -> Result<Vec<ChartPoint>> {
+    let mut days: BTreeMap<String, Day> = BTreeMap::new();
+    for t in txns {
+        if matches!(t.r#type, TransactionType::Transfer | TransactionType::Margin | TransactionType::Futures | TransactionType::Short) {
+            continue;
+        }
+        let d = day(&mut days, &t.date);
+        d.amount += t.paid_amount;
+        if t.paid_amount.is_sign_positive() {
+            if let Money::Cash(cash) = t.to_money(base) {
+                d.cost_basis -= cash.amount;
+            }
+        }
+        if matches!(t.r#type, TransactionType::Buy | TransactionType::Sell) && t.unit_price.is_some() {
+            d.price = t.unit_price;
+        }
+    }
+    let disposals = calculation.taxables.iter().chain(&calculation.exempt).map(|t| (t, true))
+        .chain(calculation.non_taxables.iter().map(|t| (t, false)));
+    for (t, realizes) in disposals {
+        let d = day(&mut days, &t.date);
+        d.cost_basis += cash_costs(t);
+        if realizes {
+            d.realized += t.net_income.unwrap_or_default();
+        }
+    }
+    let (mut realized, mut amount, mut cost_basis, mut price) = (Decimal::ZERO, Decimal::ZERO, Decimal::ZERO, None);
+    days.into_iter()
+        .map(|(date, d)| {
+            realized += d.realized;
+            amount += d.amount;
+            cost_basis += d.cost_basis;
+            price = d.price.or(price);
+            let value = price.map(|p| decimal::mul(p, amount)).transpose()?;
+            Ok(ChartPoint{ date, realized, amount, cost_basis, value })
+        })
+        .collect()
+}
+
+fn day<'a>(days: &'a mut BTreeMap<String, Day>, date: &str) -> &'a mut Day {
+    days.entry(date.get(..10).unwrap_or(date).to_string()).or_default()
+}
+
+/// The costs of a disposal in the base currency, negative.
+fn cash_costs(t: &TaxableTransaction) -> Decimal {
+    t.costs.iter().filter(|c| c.is_cash()).fold(Decimal::ZERO, |acc, c| acc + c.amount())
+}
+
+/// Draws the realized gains, the cost basis and the value of the points as lines over time, with
+/// the dates and the range of the amounts as labels.
+pub(crate) fn to_svg(points: &[ChartPoint], currency: &Currency, base: &Currency) -> String {
+    let days: Vec<f64> = points.iter().map(|p| days_since(&points[0].date, &p.date)).collect();
+    let lines: [(&str, &str, Vec<Option<f64>>); 3] = [
+        ("Realized", "#2e7d32", points.iter().map(|p| p.realized.to_f64()).collect()),
+        ("Cost Basis", "#757575", points.iter().map(|p| p.cost_basis.to_f64()).collect()),
+        ("Value", "#1565c0", points.iter().map(|p| p.value.and_then(|v| v.to_f64())).collect()),
+    ];
+    let amounts = lines.iter().flat_map(|(_, _, ys)| ys.iter().flatten().copied());
+    let (low, high) = amounts.fold((0.0_f64, 0.0_f64), |(low, high), y| (low.min(y), high.max(y)));
+    let (span, range) = (days.last().copied().unwrap_or_default().max(1.0), (high - low).max(1.0));
+    let x = |day: f64| MARGIN + day / span * (WIDTH - 2.0 * MARGIN);
+    let y = |amount: f64| HEIGHT - MARGIN - (amount - low) / range * (HEIGHT - 2.0 * MARGIN);
+
+    let mut svg = String::new();
+    let _ = writeln!(svg, r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" font-family="sans-serif" font-size="12">"#, WIDTH, HEIGHT);
+    let _ = writeln!(svg, r#"<rect width="100%" height="100%" fill="white"/>"#);
+    let _ = writeln!(svg, r#"<text x="{}" y="24" font-size="16">{} in {}</text>"#, MARGIN, currency, base);
+    let _ = writeln!(svg, r##"<line x1="{0}" y1="{1:.1}" x2="{2}" y2="{1:.1}" stroke="#bdbdbd"/>"##, MARGIN, y(0.0), WIDTH - MARGIN);
+    let _ = writeln!(svg, r#"<text x="{}" y="{:.1}" text-anchor="end">{:.0}</text>"#, MARGIN - 6.0, y(high) + 4.0, high);
+    let _ = writeln!(svg, r#"<text x="{}" y="{:.1}" text-anchor="end">{:.0}</text>"#, MARGIN - 6.0, y(low) + 4.0, low);
+    if let (Some(first), Some(last)) = (points.first(), points.last()) {
+        let _ = writeln!(svg, r#"<text x="{}" y="{}">{}</text>"#, MARGIN, HEIGHT - MARGIN + 20.0, first.date);
+        let _ = writeln!(svg, r#"<text x="{}" y="{}" text-anchor="end">{}</text>"#, WIDTH - MARGIN, HEIGHT - MARGIN + 20.0, last.date);
+    }
+    for (i, (name, color, ys)) in lines.iter().enumerate() {
+        let coordinates: Vec<String> = days.iter().zip(ys)
+            .filter_map(|(day, amount)| amount.map(|amount| format!("{:.1},{:.1}", x(*day), y(amount))))
+            .collect();
+        let _ = writeln!(svg, r#"<polyline fill="none" stroke="{}" stroke-width="2" points="{}"/>"#, color, coordinates.join(" "));
+        let legend = WIDTH - MARGIN - 110.0 * (lines.len() - i) as f64;
+        let _ = writeln!(svg, r#"<text x="{}" y="24" fill="{}">{}</text>"#, legend, color, name);
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+fn days_since(from: &str, date: &str) -> f64 {
+    match (NaiveDate::parse_from_str(from, "%Y-%m-%d"), NaiveDate::parse_from_str(date, "%Y-%m-%d")) {
+        (Ok(from), Ok(date)) => (date - from).num_days() as f64,
+        _ => 0.0,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::calculator;
+    use crate::chart::*;
+    use crate::cryptotax::Options;
+    use crate::price;
+    use crate::transaction::TransactionBuilder;
+    use futures::executor::block_on;
+    use rust_decimal_macros::dec;
+    use std::error::Error;
+
+    #[test]
+    fn should_chart_gains_and_holdings_over_time() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let trade = |builder: TransactionBuilder, amount, exchanged_amount, date: &str| {
+            builder.currency("BTC").amount(amount).exchanged_currency("SEK").exchanged_amount(exchanged_amount).date(date).build()
+        };
+        let mut txns = vec![
+            trade(Transaction::buy(), dec!(1), dec!(-1000), "2022-01-01 10:00:00")?,
+            trade(Transaction::buy(), dec!(1), dec!(-3000), "2022-01-01 12:00:00")?,
+            trade(Transaction::sell(), dec!(-1), dec!(2500), "2022-02-01 10:00:00")?,
+        ];
+        price::annotate(&mut txns, &"SEK".to_string(), None);
+        let calculation = block_on(calculator::calculate(&txns, &"BTC".to_string(), &"SEK".to_string(), &Options::default()))?;
+
+        /*
+         * When
+         */
+        let points = series(&txns, &calculation, &"SEK".to_string())?;
+        let svg = to_svg(&points, &"BTC".to_string(), &"SEK".to_string());
+
+        /*
+         * Then
+         */
+        assert_eq!(points, vec![
+            ChartPoint{ date: "2022-01-01".to_string(), realized: dec!(0), amount: dec!(2), cost_basis: dec!(4000), value: Some(dec!(6000)) },
+            ChartPoint{ date: "2022-02-01".to_string(), realized: dec!(500), amount: dec!(1), cost_basis: dec!(2000), value: Some(dec!(2500)) },
+        ]);
+        assert!(svg.starts_with("<svg"));
+        assert_eq!(svg.matches("<polyline").count(), 3);
+        Ok(())
+    }
+}
@@ -0,0 +1,149 @@
+use crate::error::{CryptotaxError, Result};
+use keyring::Entry;
+use std::collections::HashMap;
+use std::io;
+use std::sync::{Mutex, OnceLock};
+use tracing::debug;
+
+/// The service that the secrets of cryptotax are stored under in the keyring of the system, e.g.
+/// the macOS Keychain, the Windows Credential Manager or the Secret Service of Linux.
+const SERVICE: &str = "cryptotax";
+
+/// The providers whose API keys can be stored, and whether they also have a secret.
+const PROVIDERS: [(&str, bool); 5] = [
+    ("binance", true),
+    ("kraken", true),
+    ("etherscan", false),
+    ("coingecko", false),
+    ("cryptocompare", false),
+];
+
+/// The optional API keys read so far, so that the keyring is only asked once per run.
+static KEYS: OnceLock<Mutex<HashMap<String, Option<String>>>> = OnceLock::new();
+
+/// A read-only API key of an exchange and its secret.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Credentials {
+    pub(crate) key: String,
+    pub(crate) secret: String,
+}
+
+/// Whether the provider has a secret besides its API key, or an error if it is not known.
+pub(crate) fn has_secret(provider: &str) -> Result<bool> {
+    match PROVIDERS.iter().find(|(name, _)| name.eq_ignore_ascii_case(provider)) {
+        Some((_, secret)) => Ok(*secret),
+        None => {
+            let names: Vec<&str> = PROVIDERS.iter().map(|(name, _)| *name).collect();
+            let msg = format!("Unknown provider `{}`. Supported: {}", provider, names.join(", "));
+            Err(CryptotaxError::Config(msg))
+        }
+    }
+}
+
+fn entry(name: &str) -> Result<Entry> {
+    Entry::new(SERVICE, name).map_err(|e| io::Error::other(format!("Could not open `{}` in the keyring: {}", name, e)).into())
+}
+
+/// Reads a secret from the keyring, stored as `name`, e.g. `binance-key`.
+fn get(name: &str) -> Result<Option<String>> {
+    match entry(name)?.get_password() {
+        Ok(secret) => Ok(Some(secret)),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(io::Error::other(format!("Could not read `{}` from the keyring: {}", name, e)).into()),
+    }
+}
+
+/// Stores the API key of the provider, e.g. `binance`, in the keyring as `binance-key`, and its
+/// secret as `binance-secret` if the provider has one.
+pub(crate) fn set(provider: &str, key: &str, secret: Option<&str>) -> Result<()> {
+    let provider = provider.to_lowercase();
+    let secrets = match (has_secret(&provider)?, secret) {
+        (true, Some(secret)) => vec![("key", key), ("secret", secret)],
+        (true, None) => {
+            let msg = format!("The API key of {} needs its secret", provider);
+            return Err(CryptotaxError::Config(msg));
+        }
+        (false, _) => vec![("key", key)],
+    };
+    for (kind, secret) in secrets {
+        let name = format!("{}-{}", provider, kind);
+        entry(&name)?.set_password(secret)
+            .map_err(|e| io::Error::other(format!("Could not store `{}` in the keyring: {}", name, e)))?;
+    }
+    Ok(())
+}
+
+/// Removes the API key of the provider and its secret from the keyring. Whether they were
+/// stored is returned.
+pub(crate) fn remove(provider: &str) -> Result<bool> {
+    let provider = provider.to_lowercase();
+    let kinds = match has_secret(&provider)? { true => vec!["key", "secret"], false => vec!["key"] };
+    let mut removed = false;
+    for kind in kinds {
+        let name = format!("{}-{}", provider, kind);
+        match entry(&name)?.delete_credential() {
+            Ok(()) => removed = true,
+            Err(keyring::Error::NoEntry) => {},
+            Err(e) => return Err(io::Error::other(format!("Could not remove `{}` from the keyring: {}", name, e)).into()),
+        }
+    }
+    Ok(removed)
+}
+
+/// Reads the API key of the exchange, e.g. `binance`, and its secret from the keyring, see
+/// `set`.
+pub(crate) fn load(exchange: &str) -> Result<Credentials> {
+    let (key, secret) = (format!("{}-key", exchange), format!("{}-secret", exchange));
+    match (get(&key)?, get(&secret)?) {
+        (Some(key), Some(secret)) => Ok(Credentials{ key, secret }),
+        _ => {
+            let msg = format!("No API key of {} in the keyring. Store a read-only key with `cryptotax auth set {}`", exchange, exchange);
+            Err(CryptotaxError::Config(msg))
+        }
+    }
+}
+
+/// The API key of a provider that also works without one, e.g. `coingecko`, if it is stored. A
+/// keyring that can't be read, e.g. without a Secret Service, counts as no key.
+pub(crate) fn api_key(provider: &str) -> Option<String> {
+    let mut keys = KEYS.get_or_init(Default::default).lock().unwrap_or_else(|e| e.into_inner());
+    keys.entry(provider.to_string())
+        .or_insert_with(|| {
+            get(&format!("{}-key", provider)).unwrap_or_else(|e| {
+                debug!(%provider, error = %e, "No API key");
+                None
+            })
+        })
+        .clone()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::credentials::*;
+    use std::error::Error;
+
+    #[test]
+    fn should_only_store_the_keys_of_known_providers() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let providers = ["Binance", "etherscan", "coinbase"];
+
+        /*
+         * When
+         */
+        let secrets: Vec<Result<bool, String>> = providers.iter().map(|p| has_secret(p).map_err(|e| e.to_string())).collect();
+        let without_secret = set("kraken", "key", None);
+
+        /*
+         * Then
+         */
+        assert_eq!(secrets, vec![
+            Ok(true),
+            Ok(false),
+            Err("Unknown provider `coinbase`. Supported: binance, kraken, etherscan, coingecko, cryptocompare".to_string()),
+        ]);
+        assert_eq!(without_secret.map_err(|e| e.to_string()), Err("The API key of kraken needs its secret".to_string()));
+        Ok(())
+    }
+}
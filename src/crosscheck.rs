@@ -0,0 +1,295 @@
+use crate::calculator::TaxableTransaction;
+use crate::error::{self, Result};
+use crate::reader;
+use crate::timestamp::Timestamp;
+use crate::transaction::Currency;
+use chrono::{NaiveDateTime, TimeDelta};
+use csv::{ReaderBuilder, Trim};
+use rust_decimal::prelude::*;
+use rust_decimal_macros::dec;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+/// Differences in the base currency up to this are taken to be rounding, since the other tools
+/// round every lot to cents.
+const TOLERANCE: Decimal = dec!(1);
+
+/// Differences in the traded currency up to this are taken to be rounding.
+const AMOUNT_TOLERANCE: Decimal = dec!(0.000001);
+
+/// How far apart the same disposal may be dated in the two reports when the time zones differ.
+const MAX_TIME_ZONE_OFFSET_HOURS: i64 = 14;
+
+/// The formats of the dates of the reports of Koinly and CoinTracking.
+const DATE_FORMATS: [&str; 6] = [
+    "%Y-%m-%d %H:%M:%S",
+    "%Y-%m-%d %H:%M",
+    "%Y-%m-%dT%H:%M:%S",
+    "%d.%m.%Y %H:%M:%S",
+    "%d.%m.%Y %H:%M",
+    "%d/%m/%Y %H:%M",
+];
+
+/// A disposal that differs between the gains report of another tool and ours, or that only one
+/// of them has, with its probable cause.
+#[derive(Debug, PartialEq, Serialize)]
+pub(crate) struct CrossCheckRow {
+    #[serde(rename = "Status")]
+    status: Status,
+
+    #[serde(rename = "Date")]
+    date: String,                       // To the minute, since the other tools leave out the seconds
+
+    #[serde(rename = "Currency")]
+    currency: Currency,
+
+    #[serde(rename = "Amount")]
+    amount: Option<Decimal>,
+
+    #[serde(rename = "Reported Amount")]
+    reported_amount: Option<Decimal>,
+
+    #[serde(rename = "Proceeds")]
+    proceeds: Option<Decimal>,
+
+    #[serde(rename = "Reported Proceeds")]
+    reported_proceeds: Option<Decimal>,
+
+    #[serde(rename = "Net Income")]
+    net_income: Option<Decimal>,
+
+    #[serde(rename = "Reported Gain")]
+    reported_gain: Option<Decimal>,
+
+    #[serde(rename = "Probable Cause")]
+    cause: String,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Serialize)]
+enum Status {
+    /// In the other report, but not disposed here.
+    Missing,
+    /// Disposed here, but not in the other report.
+    Unreported,
+    /// In both, but the amount, the proceeds or the gain differ.
+    Differs,
+    /// The same amount disposed at another time in the other report.
+    Shifted,
+}
+
+/// The disposals of one currency at one minute, summed over their lots.
+#[derive(Debug, PartialEq, Default, Clone, Copy)]
+struct Disposal {
+    amount: Decimal,
+    proceeds: Option<Decimal>,          // None if not valued in the base currency
+    gain: Option<Decimal>,
+}
+
+impl Disposal {
+    fn add(self, amount: Decimal, proceeds: Option<Decimal>, gain: Option<Decimal>) -> Disposal {
+        Disposal{
+            amount: self.amount + amount.abs(),
+            proceeds: self.proceeds.zip(proceeds).map(|(a, b)| a + b),
+            gain: self.gain.zip(gain).map(|(a, b)| a + b),
+        }
+    }
+
+    fn zero() -> Disposal {
+        Disposal{ amount: dec!(0), proceeds: Some(dec!(0)), gain: Some(dec!(0)) }
+    }
+}
+
+type Disposals = BTreeMap<(String, Currency), Disposal>;
+
+/// Reads the disposals of the currency from the capital gains report of Koinly, or the
+/// "Realized and unrealized gains" report of CoinTracking, both as csv in the base currency.
+/// Their columns are told by their headers, e.g. `Date Sold`, `Asset` or `Currency`, `Amount`,
+/// `Proceeds (SEK)` or `Proceeds in SEK`, and `Gain / loss` or `Gain/Loss in SEK`.
+fn read_report(path: &Path, currency: &Currency) -> Result<Disposals> {
+    let mut rdr = ReaderBuilder::new()
+        .has_headers(true)
+        .flexible(true)
+        .trim(Trim::All)
+        .from_reader(reader::open(path)?);
+
+    let headers = rdr.headers()?.clone();
+    let find = |names: &[&str]| headers.iter().position(|h| names.iter().any(|n| h.to_lowercase().starts_with(n)));
+    let missing = |column: &str| {
+        let msg = format!("No `{}` column in the report. Supported: the csv gains reports of Koinly and CoinTracking", column);
+        error::invalid(path, msg)
+    };
+    let date = find(&["date sold"]).ok_or_else(|| missing("Date Sold"))?;
+    let asset = find(&["asset", "currency"]).ok_or_else(|| missing("Asset"))?;
+    let amount = find(&["amount"]).ok_or_else(|| missing("Amount"))?;
+    let proceeds = find(&["proceeds"]);
+    let gain = find(&["gain"]);
+
+    let mut disposals = Disposals::new();
+    for record in rdr.records() {
+        let record = record?;
+        let field = |i: usize| record.get(i).unwrap_or_default();
+        let decimal = |i: usize| Decimal::from_str(&field(i).replace(',', "")).ok();
+        if field(asset) != currency {
+            continue;
+        }
+        let (Some(date), Some(amount)) = (date_of(field(date)), decimal(amount)) else {
+            continue;
+        };
+        let disposal = disposals.entry((date, currency.clone())).or_insert_with(Disposal::zero);
+        *disposal = disposal.add(amount, proceeds.and_then(decimal), gain.and_then(decimal));
+    }
+    Ok(disposals)
+}
+
+/// The date to the minute, e.g. `2022-01-31 10:00`.
+fn date_of(value: &str) -> Option<String> {
+    Timestamp::parse_with(value, &DATE_FORMATS)
+        .map(|t| t.to_string()[..16].to_string())
+}
+
+/// Our disposals, keyed like the ones of the report.
+fn ours(taxables: &[TaxableTransaction]) -> Disposals {
+    taxables.iter().fold(Disposals::new(), |mut acc, t| {
+        let proceeds = Some(t.income.amount()).filter(|_| t.income.is_cash());
+        let disposal = acc.entry((t.date[..16].to_string(), t.currency.clone())).or_insert_with(Disposal::zero);
+        *disposal = disposal.add(t.amount, proceeds, t.net_income);
+        acc
+    })
+}
+
+/// Compares our disposals, taxable or exempt, with the ones of the gains report of another tool
+/// at `report`, matched by their date to the minute and their currency. Only the disposals that
+/// differ, or that one side lacks, are returned, in order of date. A disposal that one side lacks
+/// is paired with one of the same amount on the other side within a time zone offset, since
+/// the tools may print the times in different time zones.
+pub(crate) fn cross_check(report: &Path, taxables: &[TaxableTransaction], currency: &Currency) -> Result<Vec<CrossCheckRow>> {
+    let theirs = read_report(report, currency)?;
+    let ours = ours(taxables);
+    let keys: BTreeSet<&(String, Currency)> = ours.keys().chain(theirs.keys()).collect();
+
+    let mut rows = vec![];
+    for key in keys {
+        let (o, t) = (ours.get(key), theirs.get(key));
+        let cause = match (o, t) {
+            (Some(_), None) => "Not in the other report: a disposal it does not count, e.g. a payment or a fee, or a row missing from its import",
+            (None, Some(_)) => "Not disposed here: a row missing from the export, or a transfer that the other tool counts as a sale",
+            (Some(o), Some(t)) if (o.amount - t.amount).abs() > AMOUNT_TOLERANCE => "The amount differs: a fee counted in the amount, or a partial fill",
+            (Some(o), Some(t)) if differs(o.proceeds, t.proceeds) => "The proceeds differ: another price, or another valuation of the fee",
+            (Some(o), Some(t)) if differs(o.gain, t.gain) => "The cost basis differs: another cost method, e.g. FIFO or average cost, or other acquisitions",
+            _ => continue,
+        };
+        rows.push(CrossCheckRow{
+            status: match (o, t) {
+                (Some(_), None) => Status::Unreported,
+                (None, Some(_)) => Status::Missing,
+                _ => Status::Differs,
+            },
+            date: key.0.clone(),
+            currency: key.1.clone(),
+            amount: o.map(|o| o.amount),
+            reported_amount: t.map(|t| t.amount),
+            proceeds: o.and_then(|o| o.proceeds),
+            reported_proceeds: t.and_then(|t| t.proceeds),
+            net_income: o.and_then(|o| o.gain),
+            reported_gain: t.and_then(|t| t.gain),
+            cause: cause.to_string(),
+        });
+    }
+    Ok(pair_shifted(rows))
+}
+
+fn differs(ours: Option<Decimal>, theirs: Option<Decimal>) -> bool {
+    ours.zip(theirs).is_some_and(|(o, t)| (o - t).abs() > TOLERANCE)
+}
+
+/// Merges every missing disposal with an unreported one of the same currency and amount within
+/// the time zone offset into one row of the two.
+fn pair_shifted(rows: Vec<CrossCheckRow>) -> Vec<CrossCheckRow> {
+    let minute = |row: &CrossCheckRow| NaiveDateTime::parse_from_str(&row.date, "%Y-%m-%d %H:%M").ok();
+    let mut rows: Vec<Option<CrossCheckRow>> = rows.into_iter().map(Some).collect();
+    for i in 0..rows.len() {
+        let Some(missing) = rows[i].as_ref().filter(|r| r.status == Status::Missing) else {
+            continue;
+        };
+        let shifted = rows.iter().position(|r| r.as_ref().is_some_and(|r| {
+            r.status == Status::Unreported
+                && r.currency == missing.currency
+                && r.amount.zip(missing.reported_amount).is_some_and(|(a, b)| (a - b).abs() <= AMOUNT_TOLERANCE)
+                && minute(r).zip(minute(missing)).is_some_and(|(a, b)| (a - b).abs() <= TimeDelta::hours(MAX_TIME_ZONE_OFFSET_HOURS))
+        }));
+        if let Some(j) = shifted {
+            let missing = rows[i].take().expect("missing");
+            let ours = rows[j].as_mut().expect("unreported");
+            ours.status = Status::Shifted;
+            ours.reported_amount = missing.reported_amount;
+            ours.reported_proceeds = missing.reported_proceeds;
+            ours.reported_gain = missing.reported_gain;
+            ours.cause = format!("Reported at {}: probably another time zone", missing.date);
+        }
+    }
+    rows.into_iter().flatten().collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::crosscheck::*;
+    use crate::transaction::{Money, Origin, TransactionType};
+    use std::error::Error;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn should_cross_check_disposals_against_a_koinly_report() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let sale = |date: &str, amount, income, cost| TaxableTransaction{
+            r#type: TransactionType::Sell,
+            date: date.to_string(),
+            currency: "BTC".to_string(),
+            amount,
+            income: Money::new_cash("SEK".to_string(), income),
+            costs: vec![Money::new_cash("SEK".to_string(), cost)],
+            net_income: Some(income + cost),
+            acquisition_fee: dec!(0),
+            disposal_fee: dec!(0),
+            origin: Origin::new(0, "".to_string()),
+            consumed: vec![],
+        };
+        let taxables = vec![
+            sale("2022-01-01 10:00:30", dec!(-1), dec!(1000), dec!(-400)),     // Matches the two lots of the report
+            sale("2022-02-01 10:00:00", dec!(-1), dec!(1000), dec!(-500)),     // Another cost
+            sale("2022-03-01 10:00:00", dec!(-0.5), dec!(600), dec!(-200)),    // An hour later in the report
+            sale("2022-04-01 10:00:00", dec!(-0.1), dec!(100), dec!(-50)),     // Not in the report
+        ];
+        let mut report = NamedTempFile::new()?;
+        writeln!(report, "Date Sold,Date Acquired,Asset,Amount,Cost (SEK),Proceeds (SEK),Gain / loss,Notes,Wallet Name,Holding period
+                          2022-01-01 10:00,2021-01-01 10:00,BTC,0.4,200.00,400.00,200.00,,Revolut,Short term
+                          2022-01-01 10:00,2021-02-01 10:00,BTC,0.6,200.00,600.00,400.00,,Revolut,Short term
+                          2022-02-01 10:00,2021-01-01 10:00,BTC,1,300.00,\"1,000.00\",700.00,,Revolut,Short term
+                          2022-03-01 11:00,2021-01-01 10:00,BTC,0.5,200.00,600.00,400.00,,Revolut,Short term
+                          2022-05-01 10:00,2021-01-01 10:00,BTC,0.2,100.00,200.00,100.00,,Revolut,Short term
+                          2022-05-01 10:00,2021-01-01 10:00,ETH,1,100.00,200.00,100.00,,Revolut,Short term")?;
+
+        /*
+         * When
+         */
+        let rows = cross_check(report.path(), &taxables, &"BTC".to_string())?;
+
+        /*
+         * Then
+         */
+        let statuses: Vec<(Status, &str, Option<Decimal>, Option<Decimal>)> = rows.iter()
+            .map(|r| (r.status, r.date.as_str(), r.net_income, r.reported_gain))
+            .collect();
+        assert_eq!(statuses, vec![
+            (Status::Differs, "2022-02-01 10:00", Some(dec!(500)), Some(dec!(700))),
+            (Status::Shifted, "2022-03-01 10:00", Some(dec!(400)), Some(dec!(400))),
+            (Status::Unreported, "2022-04-01 10:00", Some(dec!(50)), None),
+            (Status::Missing, "2022-05-01 10:00", None, Some(dec!(100))),
+        ]);
+        assert!(rows[0].cause.starts_with("The cost basis differs"));
+        Ok(())
+    }
+}
@@ -1,19 +1,449 @@
-use crate::{calculator, reader, writer};
+use crate::calculator::{Calculation, Columns, ProfitAndLoss, TaxableTransaction};
+#[cfg(feature = "native")]
+use crate::fx::FxRates;
+use crate::reader::generic;
+use crate::price::{FixedPrice, MarketPrice, PriceChain, PriceProvider};
+use crate::transaction::{self, Currency};
+#[cfg(feature = "native")]
+use crate::transaction::Annotation;
+#[cfg(feature = "native")]
+use crate::store::{Imported, Store};
+use crate::summary::TaxRate;
+use crate::jurisdiction::Matching;
+#[cfg(feature = "native")]
+use crate::diff::DiffRow;
+#[cfg(feature = "native")]
+use crate::onchain::{self, bitcoin, ethereum};
+#[cfg(feature = "native")]
+use crate::{credentials, fx, watch};
+#[cfg(feature = "rpc")]
+use crate::rpc;
+#[cfg(feature = "tui")]
+use crate::tui;
+use crate::{account, audit, bank, bundle, calculator, chart, crosscheck, diff, dust, equivalence, error, fees, fixture, form8949, invariants, journal, k4, legs, liquidity, lots, nft, overrides, precision, price, reader, rebate, rename, rules, section104, short, simulate, stablecoin, summary, timezone, warnings, writer, xlsx};
 use futures::executor::block_on;
-use log::info;
-use std::io;
-use std::path::PathBuf;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::fmt;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+#[cfg(feature = "native")]
+use std::thread;
+#[cfg(feature = "native")]
+use std::time::Duration;
+use tracing::info;
+#[cfg(feature = "native")]
+use tracing::{error, warn};
+
+pub use crate::dust::Dust;
+pub use crate::equivalence::Equivalent;
+pub use crate::error::{CryptotaxError, ExitCode, Result};
+#[cfg(feature = "native")]
+pub use crate::exchange::Exchange;
+pub use crate::fx::FxSource;
+pub use crate::journal::JournalFormat;
+pub use crate::jurisdiction::{BasisScope, FeePolicy, Jurisdiction, Liquidity, Rebates, StablecoinGains};
+pub use crate::locale::Locale;
+pub use crate::precision::Precision;
+pub use crate::price::PriceSource;
+pub use crate::report::{Gain, Report, ReportRegistry, ReportWriter};
+pub use crate::rounding::Rounding;
+pub use crate::search::Search;
+pub use crate::summary::Breakdown;
+pub use crate::transaction::{Transaction, TransactionBuilder};
+pub use crate::warnings::WarningFormat;
+pub use chrono_tz::Tz;
+pub use crate::writer::ReportFormat;
+
+/// Settings that apply to the calculations, besides the traded and the base currency.
+#[derive(Debug, Default)]
+pub struct Options {
+    /// The country whose tax rules are applied.
+    pub jurisdiction: Jurisdiction,
+    /// Path to a csv file that changes the type of some transactions, e.g. to mark them as gifts.
+    pub overrides: Option<PathBuf>,
+    /// Path to a csv file of currencies that were renamed or redenominated, whose holdings carry
+    /// over to the new currency.
+    pub renames: Option<PathBuf>,
+    /// Path to a TOML file of rules that classify, tag or name the counterparty of the matching
+    /// transactions, see `rules::read_rules`.
+    pub rules: Option<PathBuf>,
+    /// Claim lost or stolen currencies as a loss, even if the jurisdiction does not recognize it.
+    pub claim_write_offs: bool,
+    /// Print the acquisition and disposal fees of the taxable transactions as separate columns.
+    pub separate_fees: bool,
+    /// How the fees are treated in the income and the costs of the disposals, instead of the way
+    /// of the jurisdiction.
+    pub fee_policy: Option<FeePolicy>,
+    /// Calculate the gains of a fiat target currency from the exchanges between fiat currencies.
+    pub currency_gains: bool,
+    /// Path to the store that snapshots are saved to and read from, and that caches the FX rates.
+    pub store: Option<PathBuf>,
+    /// How the store is opened, e.g. with the passphrase that it is encrypted with.
+    pub store_access: StoreAccess,
+    /// Start from the costs saved in the snapshot at the end of this year, instead of the
+    /// transactions until then.
+    pub from_snapshot: Option<String>,
+    /// Convert the amounts exchanged in other fiat currencies to the base currency with the daily
+    /// reference rates of this source.
+    pub fx: Option<FxSource>,
+    /// Value the crypto-to-crypto trades at the market price from these sources, in order of
+    /// preference.
+    pub prices: Vec<PriceSource>,
+    /// Path to a csv file with prices that are used before any price source, e.g. of delisted
+    /// tokens.
+    pub price_table: Option<PathBuf>,
+    /// Don't access the network. Prices come from the price table and FX rates from the store,
+    /// and whatever is missing fails the run, see `http::send`.
+    pub offline: bool,
+    /// How far, in percent, the price that a trade implies may be from the market price of
+    /// `prices` or `price_table` before it is warned of, instead of 5%.
+    pub max_price_deviation: Option<Decimal>,
+    /// How the amounts in the base currency are rounded in the printed reports.
+    pub rounding: Rounding,
+    /// The rate in percent that the realized gains are taxed at, for the estimated tax of the
+    /// summary, instead of the rate of the jurisdiction.
+    pub tax_rate: Option<Decimal>,
+    /// The buys, sells and income of a smaller amount of the traded currency are dust, handled by
+    /// `dust`. Without it, only the transactions of a zero amount are left out.
+    pub dust_threshold: Option<Decimal>,
+    /// Whether dust is aggregated or ignored, see `dust::apply`.
+    pub dust: Dust,
+    /// Continue when more is disposed than is held, with a zero cost for the missing amount.
+    pub assume_zero_cost: bool,
+    /// Path to a TOML file that maps the columns of the csv file of an exchange that is not
+    /// supported natively, see `reader::generic::Mapping`.
+    pub mapping: Option<PathBuf>,
+    /// How adding and removing liquidity of a pool is taxed, instead of the way of the
+    /// jurisdiction.
+    pub liquidity: Option<Liquidity>,
+    /// How fee rebates and referral bonuses are taxed, instead of the way of the jurisdiction.
+    pub rebates: Option<Rebates>,
+    /// Whether each account has a cost basis of its own, instead of the way of the jurisdiction.
+    pub basis_scope: Option<BasisScope>,
+    /// Currencies that are the same asset as another for the cost basis, e.g. `WETH=ETH`, on top
+    /// of the wrapped and bridged currencies that the jurisdiction pools by default.
+    pub equivalents: Vec<Equivalent>,
+    /// Don't pool the wrapped and bridged currencies by default, only `equivalents`.
+    pub no_default_equivalents: bool,
+    /// The number of decimals of a currency, e.g. `BTC=8`, instead of the default, see
+    /// `precision::decimals_of`.
+    pub precisions: Vec<Precision>,
+    /// How the gains of stablecoins are taxed, instead of the way of the jurisdiction.
+    pub stablecoin_gains: Option<StablecoinGains>,
+    /// The stablecoins of `stablecoin_gains`, instead of USDC, USDT and EURS.
+    pub stablecoins: Vec<Currency>,
+    /// Check the invariants of the pairing and the calculations, see `invariants`, and fail at
+    /// the first that is broken.
+    pub check: bool,
+    /// Memory-map the Revolut account statements instead of reading them through a buffer, which
+    /// is faster for very large files.
+    pub mmap: bool,
+    /// Path to a TOML file of the words of the descriptions of the Revolut account statements in a
+    /// language or a version of the app that the shipped ones don't have, see
+    /// `keywords::of`.
+    pub keywords: Option<PathBuf>,
+    /// Keep the rows of a Revolut account statement that look exported twice, instead of
+    /// collapsing them, see `reader::collapse_duplicates`.
+    pub no_dedup: bool,
+    /// Path to the file that the report is written to instead of `std::io::stdout()`. The file is
+    /// only replaced once the whole report is written.
+    pub output: Option<PathBuf>,
+    /// How the reports are written, a csv file by default.
+    pub format: ReportFormat,
+    /// How the numbers and dates of the tables are written, instead of the locale of the
+    /// environment.
+    pub locale: Option<Locale>,
+    /// The timezone of the dates that the transactions are assigned to tax years and matched with
+    /// daily prices by, instead of the timezone of the jurisdiction.
+    pub timezone: Option<Tz>,
+    /// The formats of `ReportFormat::Custom`, registered by the crates that depend on this one.
+    pub reports: ReportRegistry,
+}
+
+/// How the stores are opened, see `Store::open`.
+#[derive(Clone, Default)]
+pub struct StoreAccess {
+    /// The passphrase that the stores are encrypted with, and a new store is encrypted with. The
+    /// stores are encrypted with SQLCipher, which needs the crate built with the `encryption`
+    /// feature.
+    pub passphrase: Option<String>,
+    /// The portfolio whose transactions and snapshots are kept apart from the others in the same
+    /// store, e.g. of a spouse or a business. The FX rates are shared. The empty name is the
+    /// default portfolio, which is also where the transactions of a store from before portfolios
+    /// are.
+    pub portfolio: String,
+}
+
+impl fmt::Debug for StoreAccess {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StoreAccess")
+            .field("passphrase", &self.passphrase.as_ref().map(|_| "<redacted>"))
+            .field("portfolio", &self.portfolio)
+            .finish()
+    }
+}
+
+/// Reads the transactions in the target currency, the currencies renamed to it and its
+/// equivalents from the path, and prepares them for the calculation, see `prepare_transactions`.
+/// A fiat target currency is only allowed when calculating currency exchange gains.
+fn read_transactions(path: &Path, currency: &String, base: &String, options: &Options) -> Result<Vec<Transaction>> {
+    let mapping = options.mapping.as_ref().map(generic::read_mapping).transpose()?;
+    prepare_transactions(|currency| block_on(reader::read_transactions(path, currency, mapping.as_ref(), options)), currency, base, options)
+}
+
+/// Prepares the transactions of a currency read by `read` for the calculation: applies the rules,
+/// the overrides and the renames, pools the equivalents, normalizes the amounts to the precision
+/// of their currencies, prepares the liquidity pool transactions, and leaves out the dust. The
+/// other fiat currencies are converted if `Options::fx` is set, the third-currency trades and
+/// fees valued if a price source is set, the rebates applied, and the short positions separated.
+/// Every transaction is annotated with its unit price and fiat value, and checked against the
+/// market price if there is a price source.
+fn prepare_transactions<F>(read: F, currency: &String, base: &String, options: &Options) -> Result<Vec<Transaction>>
+    where F: Fn(&Currency) -> Result<Vec<Transaction>>,
+{
+    if transaction::is_fiat(currency) && !options.currency_gains {
+        let msg = format!("`{}` is a fiat currency. Use --currency-gains to calculate currency exchange gains", currency);
+        return Err(CryptotaxError::Config(msg));
+    }
+    if options.currency_gains && !options.jurisdiction.taxes_currency_gains() {
+        let msg = format!("Currency exchange gains are not taxed in jurisdiction `{}`", options.jurisdiction);
+        return Err(CryptotaxError::Config(msg));
+    }
+    if options.rebates == Some(Rebates::Discount) && !options.jurisdiction.allows_rebate_discounts() {
+        let msg = format!("Rebates are income in jurisdiction `{}`, and can't be treated as discounts", options.jurisdiction);
+        return Err(CryptotaxError::Config(msg));
+    }
+    let equivalents = equivalence::equivalents(options);
+    if let Some(e) = equivalents.iter().find(|e| e.alias.eq(currency)) {
+        let msg = format!("`{}` is pooled with `{}`. Calculate `{}`, or use --no-default-equivalents", currency, e.currency, e.currency);
+        return Err(CryptotaxError::Config(msg));
+    }
+    let renames = match &options.renames {
+        Some(path) => block_on(rename::read_renames(path))?,
+        None => vec![],
+    };
+    let mut txns = read(currency)?;
+    let sources = rename::sources_of(currency, &renames);
+    for source in &sources {
+        txns.extend(read(source)?);
+    }
+    for alias in equivalence::aliases_of(currency, &equivalents) {
+        txns.extend(read(alias)?);
+    }
+    if let Some(rules) = &options.rules {
+        rules::apply(&mut txns, &rules::read_rules(rules)?);
+    }
+    if let Some(overrides) = &options.overrides {
+        let overrides = block_on(overrides::read_overrides(overrides))?;
+        overrides::apply(&mut txns, &overrides, base);
+    }
+    let timezone = options.timezone.unwrap_or(options.jurisdiction.timezone());
+    timezone::to_local(&mut txns, timezone);     // After the overrides, which match the dates of the export
+    rename::apply(&mut txns, &renames);
+    txns.retain(|t| !sources.contains(&&t.paid_currency));    // After the rename, e.g. a reused ticker
+    equivalence::apply(&mut txns, &equivalents);
+    precision::apply(&mut txns, &options.precisions);
+    liquidity::prepare(&mut txns, options.liquidity.unwrap_or(options.jurisdiction.liquidity()))?;
+    dust::apply(&mut txns, options.dust_threshold, options.dust);
+    if let Some(source) = options.fx {
+        convert_fx(&mut txns, base, source, options)?;
+    }
+    let chain = price_chain(options)?;
+    if let Some(chain) = &chain {
+        price::value(&mut txns, base, chain)?;
+    }
+    legs::value_fees(&mut txns, base, chain.as_ref())?;
+    rebate::apply(&mut txns, base, options.rebates.unwrap_or(options.jurisdiction.rebates()));
+    if let Some(year) = &options.from_snapshot {
+        let carryover = carryover(currency, year, options)?;
+        txns.retain(|t| year_of(t).as_str() > year.as_str());
+        txns.extend(carryover);
+    }
+    txns.sort_by(Transaction::cmp_by_date);
+    short::separate(&mut txns)?;
+    price::report_unpriced(&txns, base);
+    price::annotate(&mut txns, base, chain.as_ref());
+    if let Some(chain) = &chain {
+        price::check_deviations(&mut txns, base, chain, options.max_price_deviation.unwrap_or(price::MAX_DEVIATION))?;
+    }
+    Ok(txns)
+}
+
+/// Reads the transactions like `read_transactions`, for a calculation. Fails at the first
+/// disposal of more than is held, unless `Options::assume_zero_cost` is set.
+fn read_holdings(path: &Path, currency: &String, base: &String, options: &Options) -> Result<Vec<Transaction>> {
+    holdings(read_transactions(path, currency, base, options)?, base, options)
+}
+
+/// The transactions of `read_holdings`, once they are read. With `BasisScope::PerAccount` what is
+/// held is checked in each account.
+fn holdings(mut txns: Vec<Transaction>, base: &String, options: &Options) -> Result<Vec<Transaction>> {
+    if is_per_account(&txns, options) {
+        let mut checked = vec![];
+        for txns in account::split(&txns).into_values() {
+            checked.extend(holdings(txns, base, options)?);
+        }
+        checked.sort_by(Transaction::cmp_by_date);
+        return Ok(checked);
+    }
+    let shortfalls = calculator::shortfalls(&txns, &options.precisions)?;
+    if let (Some(first), false) = (shortfalls.first(), options.assume_zero_cost) {
+        return Err(CryptotaxError::Pairing(first.to_warning(&txns).to_string()));
+    }
+    shortfalls.iter().for_each(|s| warnings::report(s.to_warning(&txns)));
+    calculator::assume_zero_cost(&mut txns, &shortfalls, base);
+    Ok(txns)
+}
+
+/// Calculates the taxable transactions with the matching method of the jurisdiction, with the
+/// fees treated by the fee policy, see `fees::policy`. The gains of a stablecoin are left out if
+/// they are negligible, see `stablecoin::is_negligible`.
+fn taxables(txns: &[Transaction], currency: &String, base: &String, options: &Options) -> Result<Vec<TaxableTransaction>> {
+    if currency == transaction::NFT || is_per_account(txns, options) {
+        return Ok(calculate(txns, currency, base, options)?.taxables);
+    }
+    let mut taxables = match options.jurisdiction.matching() {
+        Matching::AverageCost => block_on(calculator::tax(txns, currency, base, options))?,
+        Matching::Section104 => block_on(section104::calculate(txns, base, options))?,
+        Matching::Fifo => block_on(lots::calculate(txns, base, options))?.taxables,
+    };
+    taxables.iter_mut().try_for_each(|t| t.with_fees(fees::policy(options)))?;
+    if stablecoin::is_negligible(currency, options) {
+        taxables.iter_mut().for_each(TaxableTransaction::without_gain);
+    }
+    if options.check {
+        invariants::check_gains(&taxables)?;
+    }
+    Ok(taxables)
+}
+
+/// Calculates the costs with the average cost method, and the taxable transactions with the
+/// matching method of the jurisdiction, like `taxables`. The NFTs of `NFT` are calculated one at
+/// a time, see `nft::calculate`, and so are the accounts with `BasisScope::PerAccount`, see
+/// `account::calculate`.
+fn calculate(txns: &[Transaction], currency: &String, base: &String, options: &Options) -> Result<Calculation> {
+    if currency == transaction::NFT {
+        return nft::calculate(txns, |txns, nft| calculate(txns, nft, base, options));
+    }
+    if is_per_account(txns, options) {
+        return account::calculate(txns, currency, |txns| calculate(txns, currency, base, options));
+    }
+    let mut calculation = block_on(calculator::calculate(txns, currency, base, options))?;
+    match options.jurisdiction.matching() {
+        Matching::AverageCost => {},
+        Matching::Section104 => calculation.taxables = block_on(section104::calculate(txns, base, options))?,
+        Matching::Fifo => {
+            let disposals = block_on(lots::calculate(txns, base, options))?;
+            calculation.taxables = disposals.taxables;
+            calculation.exempt = disposals.exempt;
+            calculation.carryover = disposals.carryover;
+        }
+    }
+    for t in calculation.taxables.iter_mut().chain(calculation.exempt.iter_mut()) {
+        t.with_fees(fees::policy(options))?;
+    }
+    if stablecoin::is_negligible(currency, options) {
+        calculation.taxables.iter_mut().for_each(TaxableTransaction::without_gain);
+    }
+    if options.check {
+        invariants::check_calculation(txns, &calculation)?;
+    }
+    Ok(calculation)
+}
+
+/// Whether the transactions are of more than one account, which each have a cost basis of their
+/// own.
+fn is_per_account(txns: &[Transaction], options: &Options) -> bool {
+    options.basis_scope.unwrap_or(options.jurisdiction.basis_scope()) == BasisScope::PerAccount
+        && txns.iter().any(|t| t.origin.file != txns[0].origin.file)
+}
+
+/// The market prices of `--price-table` and `--prices`, if any, at the local time of the
+/// jurisdiction or `--timezone`.
+fn price_chain(options: &Options) -> Result<Option<PriceChain>> {
+    if options.prices.is_empty() && options.price_table.is_none() {
+        return Ok(None);
+    }
+    let table = match &options.price_table {
+        Some(path) => block_on(price::read_price_table(path))?,
+        None => vec![],
+    };
+    let timezone = options.timezone.unwrap_or(options.jurisdiction.timezone());
+    Ok(Some(PriceChain::new(table, options.prices.clone()).with_timezone(timezone).offline(options.offline)))
+}
+
+#[cfg(feature = "native")]
+fn open_store(options: &Options) -> Result<Store> {
+    let path = options.store.as_ref().ok_or_else(|| {
+        CryptotaxError::Config("No store to keep the snapshots and rates in".to_string())
+    })?;
+    Store::open(path, &options.store_access)
+}
+
+/// Converts the other fiat currencies to `base` at the rates of the source, which are cached in
+/// the store, see `fx::convert`.
+#[cfg(feature = "native")]
+fn convert_fx(txns: &mut [Transaction], base: &String, source: FxSource, options: &Options) -> Result<()> {
+    let mut rates = FxRates::new(source, open_store(options)?, options.offline);
+    fx::convert(txns, base, &mut rates)
+}
+
+/// Fails, as the rates are fetched and cached in the store, which need the `native` feature.
+#[cfg(not(feature = "native"))]
+fn convert_fx(_txns: &mut [Transaction], _base: &String, source: FxSource, _options: &Options) -> Result<()> {
+    let msg = format!("The {} rates need the store, which this build has not", source);
+    Err(CryptotaxError::Config(msg))
+}
+
+/// The buys that carry over the holdings of the snapshot of the year in the store.
+#[cfg(feature = "native")]
+fn carryover(currency: &String, year: &str, options: &Options) -> Result<Vec<Transaction>> {
+    open_store(options)?.snapshot(currency, year)?.ok_or_else(|| {
+        let msg = format!("No snapshot of `{}` at the end of {}", currency, year);
+        CryptotaxError::Config(msg)
+    })
+}
+
+/// Fails, as the snapshots are saved in the store, which needs the `native` feature.
+#[cfg(not(feature = "native"))]
+fn carryover(currency: &String, year: &str, _options: &Options) -> Result<Vec<Transaction>> {
+    let msg = format!("The snapshot of `{}` at the end of {} is in the store, which this build has not", currency, year);
+    Err(CryptotaxError::Config(msg))
+}
+
+fn year_of(t: &Transaction) -> String {
+    t.date.chars().take(4).collect()
+}
+
+/// Writes the rows of a report to `Options::output`, or prints them to `std::io::stdout()`.
+fn output<S: Serialize>(rows: &[S], options: &Options) -> Result<()> {
+    match &options.output {
+        Some(path) => writer::atomically(path, |file| write_rows(rows, options, false, file)),
+        None => write_rows(rows, options, true, io::stdout().lock()),
+    }
+}
+
+/// Writes the rows of a report to `out` in `Options::format`, with colors if `styled`, see
+/// `writer::write_table`.
+fn write_rows<S: Serialize, W: io::Write>(rows: &[S], options: &Options, styled: bool, out: W) -> Result<()> {
+    match &options.format {
+        ReportFormat::Csv => block_on(writer::write(rows, out)),
+        ReportFormat::Table => block_on(writer::write_table(rows, &options.locale.unwrap_or_else(Locale::from_env), styled, out)),
+        ReportFormat::Json => writer::write_json(rows, out),
+        ReportFormat::Custom(name) => {
+            let msg = format!("The report format `{}` is only for the tax report", name);
+            Err(CryptotaxError::Report(msg))
+        }
+    }
+}
 
 /// Reads the transactions with type `Exchange` from the path and prints the results to
 /// `std::io::stdout()`.
-pub fn print_exchanges(path: &PathBuf) -> io::Result<()> {
-    let now = std::time::Instant::now();
-    let rows = block_on(reader::read_exchanges(path))?;
-    info!("Done reading csv file. Elapsed: {:.2?}", now.elapsed());
+pub fn print_exchanges(path: &Path, options: &Options) -> Result<()> {
+    let rows = block_on(reader::read_exchanges(path, options))?;
 
-    let now = std::time::Instant::now();
     block_on(writer::print(&rows))?;
-    info!("Done printing rows. Elapsed: {:.2?}", now.elapsed());
 
     Ok(())
 }
@@ -21,14 +451,37 @@ pub fn print_exchanges(path: &PathBuf) -> io::Result<()> {
 /// Reads the transactions with type `Exchange` from the path,
 /// filters for the target currency,
 /// and finally prints the results to `std::io::stdout()`.
-pub fn print_exchanges_in_currency(path: &PathBuf, currency: &String) -> io::Result<()> {
-    let now = std::time::Instant::now();
-    let rows = block_on(reader::read_exchanges_in_currency(path, currency))?;
-    info!("Done reading csv file. Elapsed: {:.2?}", now.elapsed());
+pub fn print_exchanges_in_currency(path: &Path, currency: &String, options: &Options) -> Result<()> {
+    let rows = block_on(reader::read_exchanges_in_currency(path, currency, options))?;
 
-    let now = std::time::Instant::now();
     block_on(writer::print(&rows))?;
-    info!("Done printing rows. Elapsed: {:.2?}", now.elapsed());
+
+    Ok(())
+}
+
+/// Reads the transactions with type `Exchange` from the path,
+/// filters for the target currency,
+/// converts the csv rows into transactions (or reads them directly from a crypto statement),
+/// and finally prints the results to `std::io::stdout()`.
+pub fn merge_exchanges(path: &Path, currency: &String, base: &String, options: &Options) -> Result<()> {
+    let txns = read_transactions(path, currency, base, options)?;
+
+    output(&txns, options)?;
+
+    Ok(())
+}
+
+/// Reads the transactions in each of the currencies from the path like `merge_exchanges`, and
+/// prints those that match the search, in order of date, see `Search::filter`.
+pub fn list_transactions(path: &Path, currencies: &[String], base: &String, search: &Search, options: &Options) -> Result<()> {
+    let mut txns = vec![];
+    for currency in currencies {
+        txns.extend(read_transactions(path, currency, base, options)?);
+    }
+    txns.sort_by(Transaction::cmp_by_date);
+    let txns = search.filter(txns)?;
+
+    output(&txns, options)?;
 
     Ok(())
 }
@@ -36,19 +489,176 @@ pub fn print_exchanges_in_currency(path: &PathBuf, currency: &String) -> io::Res
 /// Reads the transactions with type `Exchange` from the path,
 /// filters for the target currency,
 /// converts the csv rows into transactions,
+/// calculates tax from the transactions,
 /// and finally prints the results to `std::io::stdout()`.
-pub fn merge_exchanges(path: &PathBuf, currency: &String) -> io::Result<()> {
-    let now = std::time::Instant::now();
-    let rows = block_on(reader::read_exchanges_in_currency(path, currency))?;
-    info!("reader::read_exchanges_in_currency done. Elapsed: {:.2?}", now.elapsed());
+pub fn calculate_tax(path: &Path, currency: &String, base: &String, options: &Options) -> Result<()> {
+    if let ReportFormat::Custom(_) = options.format {
+        return match &options.output {
+            Some(output) => Ok(writer::atomically(output, |file| write_tax(path, currency, base, options, file))?),
+            None => write_tax(path, currency, base, options, io::stdout().lock()),
+        };
+    }
+    let (txns, derivatives) = tax_report(path, currency, base, options)?;
+
+    match &options.output {
+        Some(output) => writer::atomically(output, |file| write_report(&txns, &derivatives, options, false, file))?,
+        None => write_report(&txns, &derivatives, options, true, io::stdout().lock())?,
+    }
+    Ok(())
+}
+
+/// Calculates tax like `calculate_tax`, and writes the results to `out` instead.
+pub fn write_tax<W: io::Write>(path: &Path, currency: &String, base: &String, options: &Options, out: W) -> Result<()> {
+    let txns = read_holdings(path, currency, base, options)?;
+
+    write_holdings_tax(&txns, currency, base, options, out)
+}
+
+/// Calculates tax like `write_tax`, from a statement that is already in memory, e.g. uploaded to
+/// a web page, instead of a path. The statement is the csv file of any of the supported exports,
+/// or of any exchange with `Options::mapping`, but not a store or a compressed file, see
+/// `reader::read_statement`.
+pub fn write_statement_tax<W: io::Write>(statement: &[u8], currency: &String, base: &String, options: &Options, out: W) -> Result<()> {
+    let mapping = options.mapping.as_ref().map(generic::read_mapping).transpose()?;
+    let txns = prepare_transactions(|currency| block_on(reader::read_statement(statement, currency, mapping.as_ref(), options)), currency, base, options)?;
+    let txns = holdings(txns, base, options)?;
+
+    write_holdings_tax(&txns, currency, base, options, out)
+}
+
+/// Calculates tax from transactions that are already built, e.g. with `Transaction::buy()` by a
+/// crate that reads an export of its own, instead of a path. The transactions of the target
+/// currency, the currencies renamed to it and its equivalents are prepared like those that are
+/// read, see `prepare_transactions`, and the disposals and the income are returned like they are
+/// to a `ReportWriter`.
+pub fn calculate_transactions(txns: &[Transaction], currency: &String, base: &String, options: &Options) -> Result<Report> {
+    let read = |currency: &Currency| Ok(txns.iter().filter(|t| t.paid_currency.eq(currency)).cloned().collect());
+    let txns = holdings(prepare_transactions(read, currency, base, options)?, base, options)?;
+    report(&txns, currency, base, options)
+}
+
+/// Writes the tax report of the holdings in `Options::format`, unstyled.
+fn write_holdings_tax<W: io::Write>(txns: &[Transaction], currency: &String, base: &String, options: &Options, out: W) -> Result<()> {
+    if let ReportFormat::Custom(name) = &options.format {
+        return write_custom_report(txns, currency, base, name, options, out);
+    }
+    let mut taxables = taxables(txns, currency, base, options)?;
+    let mut derivatives = calculator::derivatives(txns, base)?;
+    taxables.iter_mut().chain(derivatives.iter_mut()).try_for_each(|t| t.round(&options.rounding))?;
 
-    let now = std::time::Instant::now();
-    let txns =  block_on(reader::to_transactions(&rows, currency))?;
-    info!("reader::to_transactions done. Elapsed: {:.2?}", now.elapsed());
+    write_report(&taxables, &derivatives, options, false, out)
+}
+
+/// The rounded taxable transactions and profits and losses of derivatives of the tax report.
+fn tax_report(path: &Path, currency: &String, base: &String, options: &Options) -> Result<(Vec<TaxableTransaction>, Vec<TaxableTransaction>)> {
+    let txns = read_holdings(path, currency, base, options)?;
 
-    let now = std::time::Instant::now();
-    block_on(writer::print(&txns))?;
-    info!("calculator::tax done. Elapsed: {:.2?}", now.elapsed());
+    let mut taxables = taxables(&txns, currency, base, options)?;
+    let mut derivatives = calculator::derivatives(&txns, base)?;
+    taxables.iter_mut().chain(derivatives.iter_mut()).try_for_each(|t| t.round(&options.rounding))?;
+    Ok((taxables, derivatives))
+}
+
+/// Writes the disposals and the income with the `ReportWriter` of `Options::reports` called
+/// `name`, see `report::Report`.
+fn write_custom_report<W: io::Write>(txns: &[Transaction], currency: &String, base: &String, name: &str, options: &Options, mut out: W) -> Result<()> {
+    let writer = options.reports.get(name).ok_or_else(|| {
+        let supported: Vec<&str> = ["CSV", "TABLE"].into_iter().chain(options.reports.names()).collect();
+        let msg = format!("Unknown report format `{}`. Supported: {}", name, supported.join(", "));
+        CryptotaxError::Config(msg)
+    })?;
+    writer.write(&report(txns, currency, base, options)?, &mut out)?;
+    Ok(out.flush()?)
+}
+
+/// The rounded disposals and income of the holdings, see `Report`.
+fn report(txns: &[Transaction], currency: &String, base: &String, options: &Options) -> Result<Report> {
+    let Calculation{ mut taxables, mut income, .. } = calculate(txns, currency, base, options)?;
+    taxables.iter_mut().chain(income.iter_mut()).try_for_each(|t| t.round(&options.rounding))?;
+    Ok(Report{
+        currency: currency.clone(),
+        base: base.clone(),
+        jurisdiction: options.jurisdiction,
+        disposals: taxables.iter().map(Gain::from).collect(),
+        income: income.iter().map(Gain::from).collect(),
+    })
+}
+
+/// Writes the tax report, with the fees as separate columns if `Options::separate_fees` is set,
+/// the short-term and long-term gains if the jurisdiction has a holding period, and the notes,
+/// tags and counterparties if any of the disposals has them, see `Columns::of`. The profits and
+/// losses of margin, futures and short positions follow in a table of their own, after an empty
+/// line, see `ProfitAndLoss`, or after the disposals in the same array with `ReportFormat::Json`.
+/// Either table is left out if it has no rows.
+fn write_report<W: io::Write>(txns: &[TaxableTransaction], derivatives: &[TaxableTransaction], options: &Options, styled: bool, mut out: W) -> Result<()> {
+    let disposals = Columns::of(txns, options)?;
+    let derivatives: Vec<ProfitAndLoss> = derivatives.iter().map(ProfitAndLoss::from).collect();
+    match (disposals.is_empty(), derivatives.is_empty()) {
+        (_, true) => return write_rows(&disposals, options, styled, out),
+        (true, false) if options.format != ReportFormat::Json => return write_rows(&derivatives, options, styled, out),
+        _ => {}
+    }
+    if let ReportFormat::Json = options.format {
+        let rows: Vec<serde_json::Value> = disposals.iter().map(serde_json::to_value)
+            .chain(derivatives.iter().map(serde_json::to_value))
+            .collect::<serde_json::Result<_>>()?;
+        return writer::write_json(&rows, out);
+    }
+    write_rows(&disposals, options, styled, &mut out)?;
+    writeln!(out)?;
+    write_rows(&derivatives, options, styled, out)
+}
+
+/// Reads the transactions with type `Exchange` from the path,
+/// filters for the target currency,
+/// converts the csv rows into transactions,
+/// calculates tax from the transactions,
+/// and finally prints every disposal, with the rows it was read from and the lots it consumed,
+/// to `std::io::stdout()`. The exempt disposals are included, in order of date.
+pub fn print_audit(path: &Path, currency: &String, base: &String, options: &Options) -> Result<()> {
+    let txns = read_holdings(path, currency, base, options)?;
+
+    let calculation = calculate(&txns, currency, base, options)?;
+    let mut txns: Vec<_> = calculation.taxables.into_iter().chain(calculation.exempt).collect();
+    txns.sort_by(|a, b| a.date.cmp(&b.date));
+    txns.iter_mut().try_for_each(|t| t.round(&options.rounding))?;
+    let rows = audit::report(&txns);
+
+    output(&rows, options)?;
+
+    Ok(())
+}
+
+/// Reads the transactions in the target currency from both paths, e.g. an export and the store
+/// or an export and its corrected reissue, calculates tax from each,
+/// and finally prints the added, removed and changed transactions, the disposals whose gain
+/// changed and the change of the total gain per year to `std::io::stdout()`.
+pub fn print_diff(old: &Path, new: &Path, currency: &String, base: &String, options: &Options) -> Result<()> {
+    let old_txns = read_holdings(old, currency, base, options)?;
+    let new_txns = read_holdings(new, currency, base, options)?;
+
+    let mut old_taxables = taxables(&old_txns, currency, base, options)?;
+    let mut new_taxables = taxables(&new_txns, currency, base, options)?;
+    old_taxables.iter_mut().chain(new_taxables.iter_mut()).try_for_each(|t| t.round(&options.rounding))?;
+    let rows = diff::diff(&old_txns, &old_taxables, &new_txns, &new_taxables, base);
+
+    output(&rows, options)?;
+
+    Ok(())
+}
+
+/// Reads the top-ups and transfers of the fiat currency from the Revolut account statement at the
+/// path,
+/// matches them with the transfers of the statement of the bank at `statement`,
+/// and finally prints the ones without a match on either side to `std::io::stdout()`, e.g. money
+/// moved from the bank to Revolut whose top-up is in an export that is missing.
+pub fn print_bank_reconciliation(path: &Path, statement: &Path, currency: &String, options: &Options) -> Result<()> {
+    let transfers = block_on(reader::read_fiat_transfers(path, currency, options))?;
+
+    let rows = bank::reconcile(&transfers, statement, currency)?;
+    info!(transfers = transfers.len(), unmatched = rows.len(), "Matched the fiat transfers with the bank statement");
+
+    output(&rows, options)?;
 
     Ok(())
 }
@@ -57,23 +667,622 @@ pub fn merge_exchanges(path: &PathBuf, currency: &String) -> io::Result<()> {
 /// filters for the target currency,
 /// converts the csv rows into transactions,
 /// calculates tax from the transactions,
+/// and finally prints the disposals that differ from the ones of the gains report of Koinly or
+/// CoinTracking at `report`, or that only one of them has, with their probable causes, to
+/// `std::io::stdout()`. The report must be in the base currency.
+pub fn print_cross_check(path: &Path, report: &Path, currency: &String, base: &String, options: &Options) -> Result<()> {
+    let txns = read_holdings(path, currency, base, options)?;
+
+    let calculation = calculate(&txns, currency, base, options)?;
+    let disposals: Vec<TaxableTransaction> = calculation.taxables.into_iter().chain(calculation.exempt).collect();
+    let rows = crosscheck::cross_check(report, &disposals, currency)?;
+    info!(differences = rows.len(), "Cross-checked the disposals");
+
+    output(&rows, options)?;
+
+    Ok(())
+}
+
+/// Reads the transactions with type `Exchange` from the path,
+/// filters for the target currency,
+/// converts the csv rows into transactions,
+/// calculates the realized gains per year, and per quarter or month of the `breakdown`,
+/// the estimated tax of the realized gains per year, see `tax_rate`,
+/// the non-taxable disposals per year
+/// and the unrealized gains of the remaining holdings,
 /// and finally prints the results to `std::io::stdout()`.
-pub fn calculate_tax(path: &PathBuf, currency: &String, base: &String) -> io::Result<()> {
-    let now = std::time::Instant::now();
-    let rows = block_on(reader::read_exchanges_in_currency(path, currency))?;
-    info!("Done reading csv file. Elapsed: {:.2?}", now.elapsed());
+/// The unrealized gains are valued at `price` if given, otherwise at the market price of
+/// `--price-table` and `--prices`, or else at the price of the latest trade, see `MarketPrice`.
+pub fn print_summary(path: &Path, currency: &String, base: &String, price: Option<Decimal>, breakdown: Breakdown, options: &Options) -> Result<()> {
+    let txns = read_holdings(path, currency, base, options)?;
+
+    let calculation = calculate(&txns, currency, base, options)?;
+
+    let prices: Box<dyn PriceProvider> = match price {
+        Some(price) => Box::new(FixedPrice::new(currency.clone(), base.clone(), price)),
+        None => Box::new(MarketPrice::new(price_chain(options)?, &txns)),
+    };
+    let mut rows = block_on(summary::summarize(&calculation, base, prices.as_ref(), breakdown, tax_rate(options), thresholds(options, base)))?;
+    rows.iter_mut().try_for_each(|r| r.round(&options.rounding))?;
+
+    output(&rows, options)?;
+
+    Ok(())
+}
+
+/// Reads the transactions with type `Exchange` from the path,
+/// filters for the target currency,
+/// converts the csv rows into transactions,
+/// adds a sale of `amount` of the holding at `price`, or at the market price, see `MarketPrice`, dated now,
+/// calculates tax from the transactions,
+/// and finally prints what the sale would realize and the tax it would add to its year, see
+/// `tax_rate`, to `std::io::stdout()`. Nothing is saved.
+pub fn simulate_sell(path: &Path, currency: &String, base: &String, amount: Decimal, price: Option<Decimal>, options: &Options) -> Result<()> {
+    let mut txns = read_holdings(path, currency, base, options)?;
+
+    let market = MarketPrice::new(price_chain(options)?, &txns).latest_price(currency, base).map(|(price, _)| price);
+    let price = price.or(market).ok_or_else(|| {
+        let msg = format!("No price or trade of `{}` in `{}` to price the sale at. Give the price with --price", currency, base);
+        CryptotaxError::Config(msg)
+    })?;
+    let sale = simulate::sale(&txns, currency, amount, price, base)?;
+    txns.push(sale.clone());
+    if let Some(shortfall) = calculator::shortfalls(&txns, &options.precisions)?.iter().find(|s| s.index == txns.len() - 1) {
+        return Err(CryptotaxError::Pairing(shortfall.to_warning(&txns).to_string()));
+    }
+    let calculation = calculate(&txns, currency, base, options)?;
+    let mut row = simulate::report(&sale, &calculation, tax_rate(options))?;
+    row.round(&options.rounding);
+
+    output(&[row], options)?;
+
+    Ok(())
+}
+
+/// Reads the transactions with type `Exchange` from the path,
+/// filters for the target currency,
+/// converts the csv rows into transactions,
+/// matches the disposals with the acquisitions like the jurisdiction does, first in, first out, see
+/// `lots::explore`, or by the UK share identification rules, see `section104::explore`,
+/// and finally prints the lots that are still held to `std::io::stdout()`, with `history` after
+/// every acquisition and every part of a lot that a disposal consumed. The average cost method
+/// pools every acquisition, so it has no lots to print.
+pub fn print_lots(path: &Path, currency: &String, base: &String, history: bool, options: &Options) -> Result<()> {
+    if options.jurisdiction.matching() == Matching::AverageCost {
+        let msg = format!("The average cost method of {} pools every acquisition, so there are no lots to print. Give --jurisdiction US, UK or DE", options.jurisdiction);
+        return Err(CryptotaxError::Config(msg));
+    }
+    let txns = read_holdings(path, currency, base, options)?;
+
+    let mut rows = match options.jurisdiction.matching() {
+        Matching::Section104 => section104::explore(&txns, base, history, options.assume_zero_cost)?,
+        _ => lots::explore(&txns, base, history)?,
+    };
+    rows.iter_mut().for_each(|r| r.round(&options.rounding));
+
+    output(&rows, options)?;
+
+    Ok(())
+}
+
+/// The rate that the summary estimates the tax at, `Options::tax_rate` or the rate of the
+/// jurisdiction, with the share of losses that the jurisdiction deducts. `None` if neither has a
+/// rate.
+fn tax_rate(options: &Options) -> Option<TaxRate> {
+    options.tax_rate.or(options.jurisdiction.tax_rate()).map(|rate| {
+        TaxRate{ rate, deductible_losses: options.jurisdiction.deductible_losses() }
+    })
+}
+
+/// The jurisdiction whose tax free thresholds apply to the realized gains of the summary, see
+/// `Jurisdiction::threshold`. `None` if the base currency is not the currency of the thresholds.
+fn thresholds(options: &Options, base: &Currency) -> Option<Jurisdiction> {
+    Some(options.jurisdiction).filter(|j| j.currency() == base)
+}
+
+/// Reads the transactions with type `Exchange` from the path,
+/// filters for the target currency,
+/// converts the csv rows into transactions,
+/// disposes the bought lots first in, first out,
+/// and finally prints the disposals as the rows of the US Form 8949 to `std::io::stdout()`.
+pub fn print_form8949(path: &Path, currency: &String, base: &String, options: &Options) -> Result<()> {
+    let txns = read_holdings(path, currency, base, options)?;
+
+    let accounts = match is_per_account(&txns, options) {
+        true => account::split(&txns).into_values().collect(),
+        false => vec![txns],
+    };
+    let mut rows = vec![];
+    for txns in &accounts {
+        rows.extend(block_on(form8949::report(txns, currency, base, options))?);
+    }
+    if stablecoin::is_negligible(currency, options) {
+        rows.iter_mut().for_each(|r| r.without_gain());
+    }
+    rows.iter_mut().for_each(|r| r.round(&options.rounding));
+
+    output(&rows, options)?;
+
+    Ok(())
+}
+
+/// Reads the transactions with type `Exchange` from the path,
+/// filters for the target currency,
+/// converts the csv rows into transactions,
+/// matches the disposals with the acquisitions by the UK share identification rules,
+/// and finally prints the numbers of the HMRC capital gains summary per tax year to `std::io::stdout()`.
+pub fn print_hmrc_summary(path: &Path, currency: &String, base: &String, options: &Options) -> Result<()> {
+    let txns = read_holdings(path, currency, base, options)?;
+
+    let mut taxables = block_on(section104::calculate(&txns, base, options))?;
+    taxables.iter_mut().try_for_each(|t| t.with_fees(fees::policy(options)))?;
+    if stablecoin::is_negligible(currency, options) {
+        taxables.iter_mut().for_each(TaxableTransaction::without_gain);
+    }
+    let mut rows = section104::summarize(&taxables, thresholds(options, base))?;
+    rows.iter_mut().for_each(|r| r.round(&options.rounding));
+
+    output(&rows, options)?;
+
+    Ok(())
+}
+
+/// Reads the transactions with type `Exchange` from the path,
+/// filters for the target currency,
+/// converts the csv rows into transactions,
+/// calculates tax from the transactions,
+/// and finally writes the buys, the income and the disposals as the journal entries of a business
+/// that holds the currency, booked to the ledger accounts of the TOML file at `ledger` or the BAS
+/// accounts, to `Options::output` or `std::io::stdout()`.
+pub fn write_journal(path: &Path, currency: &String, base: &String, ledger: Option<&PathBuf>, format: JournalFormat, options: &Options) -> Result<()> {
+    let txns = read_holdings(path, currency, base, options)?;
+
+    let calculation = calculate(&txns, currency, base, options)?;
+    let ledger = ledger.map(journal::read_ledger).transpose()?.unwrap_or_default();
+    let rows = journal::entries(&txns, &calculation, base, &ledger);
+
+    match &options.output {
+        Some(output) => writer::atomically(output, |file| journal::write(&rows, format, file))?,
+        None => journal::write(&rows, format, io::stdout().lock())?,
+    }
+    Ok(())
+}
+
+/// Reads the transactions with type `Exchange` from the path,
+/// filters for the target currency,
+/// converts the csv rows into transactions,
+/// calculates tax from the transactions,
+/// and finally writes the realized gains, the holding, its cost basis and its value over time, a
+/// row per day with transactions, to `Options::output` or `std::io::stdout()`. If `svg` is given,
+/// the series are also drawn as a chart to that file.
+pub fn write_chart(path: &Path, currency: &String, base: &String, svg: Option<&PathBuf>, options: &Options) -> Result<()> {
+    let txns = read_holdings(path, currency, base, options)?;
+
+    let calculation = calculate(&txns, currency, base, options)?;
+    let points = chart::series(&txns, &calculation, base)?;
+    if let Some(svg) = svg {
+        writer::atomically(svg, |file| Ok(file.write_all(chart::to_svg(&points, currency, base).as_bytes())?))?;
+    }
+
+    output(&points, options)?;
+
+    Ok(())
+}
+
+/// Reads the transactions with type `Exchange` from the path,
+/// filters for the target currency,
+/// converts the csv rows into transactions,
+/// calculates tax from the transactions,
+/// and finally writes an Excel workbook with a sheet each of the transactions, the disposals, the
+/// summary per year, the K4 numbers in whole SEK for Sweden, and the income, to
+/// `Options::output` or `std::io::stdout()`.
+pub fn write_workbook(path: &Path, currency: &String, base: &String, options: &Options) -> Result<()> {
+    let txns = read_holdings(path, currency, base, options)?;
+
+    let calculation = calculate(&txns, currency, base, options)?;
+
+    let mut summary = block_on(summary::summarize(&calculation, base, &MarketPrice::new(price_chain(options)?, &txns), Breakdown::Year, tax_rate(options), thresholds(options, base)))?;
+    summary.iter_mut().try_for_each(|r| r.round(&options.rounding))?;
+    let k4 = k4::rows(&calculation.taxables, &Rounding::Favorable);
+    let Calculation{ mut taxables, mut income, .. } = calculation;
+    taxables.iter_mut().chain(income.iter_mut()).try_for_each(|t| t.round(&options.rounding))?;
+
+    let mut workbook = xlsx::Workbook::new();
+    workbook.add_sheet("Transactions", &txns)?;
+    workbook.add_sheet("Disposals", &Columns::of(&taxables, options)?)?;
+    workbook.add_sheet("Summary", &summary)?;
+    if options.jurisdiction == Jurisdiction::Sweden {
+        workbook.add_sheet("K4", &k4)?;
+    }
+    workbook.add_sheet("Income", &income)?;
+
+    match &options.output {
+        Some(output) => writer::atomically(output, |file| workbook.write(file))?,
+        None => workbook.write(io::stdout().lock())?,
+    }
+    Ok(())
+}
+
+/// Reads the transactions with type `Exchange` from the path,
+/// filters for the target currency,
+/// converts the csv rows into transactions,
+/// calculates tax from the transactions,
+/// and finally shows the lots that are held, the latest transactions, the realized gains per
+/// year, the disposals with the lots they consumed and the warnings in an interactive dashboard
+/// in the terminal, see `tui::Dashboard`. The warnings are printed at the end of the run as well.
+#[cfg(feature = "tui")]
+pub fn show_dashboard(path: &Path, currency: &String, base: &String, options: &Options) -> Result<()> {
+    let txns = read_holdings(path, currency, base, options)?;
+
+    let calculation = calculate(&txns, currency, base, options)?;
+    let mut holdings = lots::explore(&txns, base, false)?;
+    holdings.iter_mut().for_each(|r| r.round(&options.rounding));
+    let mut gains = block_on(summary::summarize(&calculation, base, &MarketPrice::new(price_chain(options)?, &txns), Breakdown::Year, tax_rate(options), thresholds(options, base)))?;
+    gains.iter_mut().try_for_each(|r| r.round(&options.rounding))?;
+    let mut disposals: Vec<_> = calculation.taxables.into_iter().chain(calculation.exempt).collect();
+    disposals.sort_by(|a, b| a.date.cmp(&b.date));
+    disposals.iter_mut().try_for_each(|t| t.round(&options.rounding))?;
+    let warnings = warnings::take();
+    let dashboard = tui::Dashboard::new(&holdings, &txns, &gains, &disposals, &warnings)?;
+    warnings.into_iter().for_each(warnings::report);
+
+    tui::run(dashboard)?;
+
+    Ok(())
+}
+
+/// Reads the transactions with type `Exchange` from the path,
+/// filters for the target currency,
+/// converts the csv rows into transactions,
+/// calculates tax from the transactions,
+/// and finally writes a zip archive with the transactions anonymized, the warnings, the log of
+/// the phases and the version to `Options::output` or `std::io::stdout()`, to attach to a bug
+/// report, see `bundle::write`. A calculation that fails is written to the bundle as well.
+pub fn write_debug_bundle(path: &Path, currency: &String, base: &String, options: &Options) -> Result<()> {
+    let (result, log) = bundle::capture_log(|| -> Result<_> {
+        let txns = read_transactions(path, currency, base, options)?;
+        let calculation = taxables(&txns, currency, base, options);
+        Ok((txns, calculation))
+    });
+    let (txns, calculation) = result?;
+
+    let warnings = warnings::take();
+    let about = format!(
+        "cryptotax {}\nos: {} {}\ncurrency: {}\nbase: {}\njurisdiction: {}\ntransactions: {}\nwarnings: {}\ncalculation: {}\n",
+        env!("CARGO_PKG_VERSION"), std::env::consts::OS, std::env::consts::ARCH, currency, base, options.jurisdiction,
+        txns.len(), warnings.len(),
+        match &calculation {
+            Ok(taxables) => format!("{} disposals", taxables.len()),
+            Err(e) => format!("failed, {}", e.kind()),
+        },
+    );
+    let anonymizer = bundle::Anonymizer::new();
+    let written = match &options.output {
+        Some(output) => writer::atomically(output, |file| bundle::write(&txns, &warnings, &log, &about, &anonymizer, file)),
+        None => bundle::write(&txns, &warnings, &log, &about, &anonymizer, io::stdout().lock()),
+    };
+    warnings.into_iter().for_each(warnings::report);
+    written
+}
+
+/// Rewrites the Revolut account statement at the path into an anonymized statement with the
+/// same structure, to contribute as a test fixture, and writes it to `out`. The amounts are
+/// scaled and the dates shifted, by a factor and a number of days derived from `seed`, so the
+/// same statement and seed always give the same fixture. See `fixture::anonymize`.
+pub fn anonymize_statement<W: io::Write>(path: &Path, seed: u64, out: W) -> Result<()> {
+    fixture::anonymize(path, seed, out)
+}
+
+/// Reads the transactions in the target currency from the path, with the columns given by the
+/// mapping file if any,
+/// adds the ones that are not stored yet to the store at `store`, annotated by the rules file if
+/// any, see `add_to_store`,
+/// and finally prints how many were added and skipped to `std::io::stdout()`.
+/// The store can then be used in place of the export files, e.g. to calculate tax. Both the path
+/// and the store are opened with `access`, if the path is a store too.
+#[cfg(feature = "native")]
+pub fn import(path: &Path, store: &Path, currency: &String, mapping: Option<&PathBuf>, rules: Option<&PathBuf>, access: &StoreAccess) -> Result<()> {
+    let mapping = mapping.map(generic::read_mapping).transpose()?;
+    let rules = rules.map(rules::read_rules).transpose()?.unwrap_or_default();
+    let options = Options{ store_access: access.clone(), ..Default::default() };
+    let txns = block_on(reader::read_transactions(path, currency, mapping.as_ref(), &options))?;
+
+    let imported = add_to_store(&mut Store::open(store, access)?, currency, &txns, &rules)?;
+
+    block_on(writer::print(&[imported]))?;
+
+    Ok(())
+}
+
+/// Adds the transactions to the store, and annotates the ones that a rule matches in the store,
+/// see `Store::annotate`. The rules are matched on the rows that the transactions were read from,
+/// which are not stored, and their annotations are kept apart from the transactions, so that
+/// importing the same rows again still skips them.
+#[cfg(feature = "native")]
+fn add_to_store(store: &mut Store, currency: &Currency, txns: &[Transaction], rules: &[rules::Rule]) -> Result<Imported> {
+    let imported = store.add(currency, txns)?;
+    for t in txns {
+        if let Some(annotation) = rules::annotation_of(t, rules) {
+            store.annotate(&t.paid_currency, &t.date, annotation)?;
+        }
+    }
+    Ok(imported)
+}
+
+/// Fetches the history of the Ethereum address from an explorer with the Etherscan API at
+/// `api_url`, with the API key, or the key of `etherscan` in the keyring, see `set_api_key`,
+/// converts the transfers of the target currency, ETH or an ERC-20 token, and the gas paid in ETH
+/// into transactions, following the rules of the jurisdiction for the gas,
+/// adds the ones that are not stored yet to the store at `store`,
+/// and finally prints how many were added and skipped to `std::io::stdout()`.
+#[cfg(feature = "native")]
+#[allow(clippy::too_many_arguments)]
+pub fn import_chain(address: &str, api_url: &str, api_key: Option<&str>, store: &Path, currency: &String, jurisdiction: &Jurisdiction, access: &StoreAccess, offline: bool) -> Result<()> {
+    let api_key = match api_key {
+        Some(key) => key.to_string(),
+        None => credentials::api_key("etherscan").ok_or_else(|| {
+            let msg = "No API key of the explorer. Give it with --api-key, or store it with `cryptotax auth set etherscan`";
+            CryptotaxError::Config(msg.to_string())
+        })?,
+    };
+    let history = ethereum::History::fetch(address, api_url, &api_key, offline)?;
+    let txns = history.to_transactions(currency, jurisdiction);
+
+    let imported = Store::open(store, access)?.add(currency, &txns)?;
+
+    block_on(writer::print(&[imported]))?;
+
+    Ok(())
+}
 
-    let now = std::time::Instant::now();
-    let txns =  block_on(reader::to_transactions(&rows, currency))?;
-    info!("Done converting to transactions. Elapsed: {:.2?}", now.elapsed());
+/// Fetches the trade history of the account at the exchange from its API at `api_url`, or the
+/// default of the exchange, with the read-only API key from the keyring, see `credentials::load`,
+/// converts the trades of the target currency into transactions,
+/// adds the ones that are not stored yet to the store at `store`,
+/// and finally prints how many were added and skipped to `std::io::stdout()`.
+#[cfg(feature = "native")]
+pub fn sync(exchange: &Exchange, api_url: Option<&str>, store: &Path, currency: &String, access: &StoreAccess, offline: bool) -> Result<()> {
+    let credentials = credentials::load(exchange.name())?;
+    let txns = exchange.fetch(currency, api_url.unwrap_or(exchange.api_url()), &credentials, offline)?;
 
-    let now = std::time::Instant::now();
-    let txns =  block_on(calculator::tax(&txns, currency, base))?;
-    info!("Done calculating taxes. Elapsed: {:.2?}", now.elapsed());
+    let imported = Store::open(store, access)?.add(currency, &txns)?;
 
-    let now = std::time::Instant::now();
-    block_on(writer::print(&txns))?;
-    info!("Done printing rows. Elapsed: {:.2?}", now.elapsed());
+    block_on(writer::print(&[imported]))?;
 
     Ok(())
 }
+
+/// Fetches the history of a Bitcoin wallet, given by its addresses or extended public keys, from
+/// an Esplora server at `api_url`,
+/// converts what it sent and received and the fees it paid into transactions, following the rules
+/// of the jurisdiction for the fees,
+/// reports the transfers that match no withdrawal from or deposit to an exchange in the store,
+/// adds the ones that are not stored yet to the store at `store`,
+/// and finally prints how many were added and skipped to `std::io::stdout()`.
+#[cfg(feature = "native")]
+pub fn import_bitcoin(keys: &[String], api_url: &str, store: &Path, jurisdiction: &Jurisdiction, access: &StoreAccess, offline: bool) -> Result<()> {
+    let history = bitcoin::History::fetch(keys, api_url, offline)?;
+    let txns = history.to_transactions(jurisdiction);
+
+    let currency = "BTC".to_string();
+    let mut store = Store::open(store, access)?;
+    onchain::report_unmatched(&txns, &store.transactions(&currency)?);
+    let imported = store.add(&currency, &txns)?;
+
+    block_on(writer::print(&[imported]))?;
+
+    Ok(())
+}
+
+/// Reads the transactions with type `Exchange` from the path,
+/// filters for the target currency and the transactions until the end of `year`,
+/// calculates the costs left at the end of `year`,
+/// saves them as a snapshot to the store,
+/// and finally prints them as the buys that carry them over to `std::io::stdout()`.
+/// Later calculations can start from the snapshot with `Options::from_snapshot`.
+#[cfg(feature = "native")]
+pub fn create_snapshot(path: &Path, currency: &String, base: &String, year: &str, options: &Options) -> Result<()> {
+    let mut txns = read_holdings(path, currency, base, options)?;
+    txns.retain(|t| year_of(t).as_str() <= year);
+
+    let calculation = calculate(&txns, currency, base, options)?;
+
+    open_store(options)?.save_snapshot(currency, year, &calculation.carryover)?;
+
+    output(&calculation.carryover, options)?;
+
+    Ok(())
+}
+
+/// Prints the snapshots saved in the store to `std::io::stdout()`.
+#[cfg(feature = "native")]
+pub fn list_snapshots(store: &Path, access: &StoreAccess) -> Result<()> {
+    let snapshots = Store::open(store, access)?.snapshots()?;
+    block_on(writer::print(&snapshots))?;
+    Ok(())
+}
+
+/// Prints the costs saved in the snapshot of the currency at the end of `year` to
+/// `std::io::stdout()`, as the buys that carry them over.
+#[cfg(feature = "native")]
+pub fn show_snapshot(store: &Path, currency: &String, year: &str, access: &StoreAccess) -> Result<()> {
+    let carryover = Store::open(store, access)?.snapshot(currency, year)?.ok_or_else(|| {
+        let msg = format!("No snapshot of `{}` at the end of {}", currency, year);
+        CryptotaxError::Config(msg)
+    })?;
+    block_on(writer::print(&carryover))?;
+    Ok(())
+}
+
+/// Annotates the transactions in the currency at the date in the store with a note and tags,
+/// which are carried into the tax report and the audit report, e.g. to document why a transaction
+/// was reclassified or to tag the disposals of a business.
+#[cfg(feature = "native")]
+pub fn annotate_transaction(store: &Path, currency: &String, date: &str, note: Option<&String>, tags: &[String], access: &StoreAccess) -> Result<()> {
+    let annotation = Annotation{ note: note.cloned(), tags: tags.to_vec(), ..Default::default() };
+    Store::open(store, access)?.annotate(currency, date, &annotation)?;
+    Ok(())
+}
+
+/// Watches the directory for new or changed export files and imports their transactions in the
+/// target currency to the store. After every import, calculates tax from the store, writes the
+/// tax report to `report`, and prints what changed since the previous calculation to
+/// `std::io::stdout()`, see `print_diff`. The first calculation only prints the total gain per
+/// year. A file that can't be imported, or a calculation that fails, is logged and the watch goes
+/// on. The warnings are printed after every scan in `warnings`, unless it is `None`.
+///
+/// Runs until interrupted, scanning the directory every `interval`.
+#[cfg(feature = "native")]
+pub fn watch(dir: &Path, report: &Path, currency: &String, base: &String, interval: Duration,
+             warnings: Option<&WarningFormat>, options: &Options) -> Result<()> {
+    let store = options.store.clone().ok_or_else(|| {
+        CryptotaxError::Config("No store to import the transactions to".to_string())
+    })?;
+    open_store(options)?;
+    let mapping = options.mapping.as_ref().map(generic::read_mapping).transpose()?;
+    let rules = options.rules.as_ref().map(rules::read_rules).transpose()?.unwrap_or_default();
+    let mut folder = watch::Folder::new(dir, &[report, store.as_path()]);
+    let mut previous: Option<(Vec<Transaction>, Vec<TaxableTransaction>)> = None;
+    loop {
+        let mut added = 0;
+        for file in folder.ready()? {
+            let imported = block_on(reader::read_transactions(&file, currency, mapping.as_ref(), options))
+                .and_then(|txns| add_to_store(&mut open_store(options)?, currency, &txns, &rules));
+            match imported {
+                Ok(imported) => {
+                    info!(file = %file.display(), added = imported.added, skipped = imported.skipped, "Imported");
+                    added += imported.added;
+                }
+                Err(e) => error!(file = %file.display(), error = %e, "Could not import"),
+            }
+        }
+        if added > 0 || previous.is_none() {
+            match recalculate(&store, report, currency, base, previous.as_ref(), options) {
+                Ok(current) => previous = Some(current),
+                Err(e) => error!(error = %e, "Could not calculate tax"),
+            }
+        }
+        if let Some(format) = warnings {
+            print_warnings(format)?;
+        }
+        thread::sleep(interval);
+    }
+}
+
+/// Calculates tax from the store for `watch`, and returns the transactions and the taxable
+/// transactions to compare the next calculation to.
+#[cfg(feature = "native")]
+fn recalculate(store: &Path, report: &Path, currency: &String, base: &String,
+               previous: Option<&(Vec<Transaction>, Vec<TaxableTransaction>)>,
+               options: &Options) -> Result<(Vec<Transaction>, Vec<TaxableTransaction>)> {
+    let txns = read_holdings(store, currency, base, options)?;
+
+    let mut taxables = taxables(&txns, currency, base, options)?;
+    let mut derivatives = calculator::derivatives(&txns, base)?;
+    taxables.iter_mut().chain(derivatives.iter_mut()).try_for_each(|t| t.round(&options.rounding))?;
+    writer::atomically(report, |file| write_report(&taxables, &derivatives, options, false, file))?;
+    let rows = match previous {
+        Some((old_txns, old_taxables)) => diff::diff(old_txns, old_taxables, &txns, &taxables, base),
+        None => diff::diff(&[], &[], &txns, &taxables, base).into_iter().filter(DiffRow::is_total).collect(),
+    };
+
+    write_rows(&rows, options, true, io::stdout().lock())?;
+
+    Ok((txns, taxables))
+}
+
+/// Serves JSON-RPC 2.0 requests, one per line of `input`, and writes the responses and the
+/// `progress` notifications to `output`, for tools that are not written in Rust. The methods are
+/// `import` of an export file to a store, `calculate` of the taxable transactions as JSON objects,
+/// and `report` of the tax report in `format`, see `rpc::Params`. The stores of the requests are
+/// opened with `access`, and nothing is fetched if `offline`.
+#[cfg(feature = "rpc")]
+pub fn serve_rpc<R: io::BufRead, W: io::Write>(input: R, output: W, access: &StoreAccess, offline: bool) -> Result<()> {
+    rpc::serve(input, output, |method, params, progress| {
+        let params = rpc::params(params)?;
+        let options = Options{ store_access: access.clone(), offline, ..params.to_options()? };
+        let base = params.base.clone().unwrap_or("SEK".to_string());
+        match method {
+            "import" => {
+                let store = options.store.as_ref().ok_or_else(|| {
+                    CryptotaxError::Config("No store to import the transactions to".to_string())
+                })?;
+                progress("reading");
+                let mapping = options.mapping.as_ref().map(generic::read_mapping).transpose()?;
+                let txns = block_on(reader::read_transactions(&params.path, &params.currency, mapping.as_ref(), &options))?;
+                progress("importing");
+                let imported = Store::open(store, &options.store_access)?.add(&params.currency, &txns)?;
+                Ok(Some(serde_json::to_value(imported)?))
+            }
+            "calculate" => {
+                progress("reading");
+                let txns = read_holdings(&params.path, &params.currency, &base, &options)?;
+                progress("calculating");
+                let mut taxables = taxables(&txns, &params.currency, &base, &options)?;
+                taxables.iter_mut().try_for_each(|t| t.round(&options.rounding))?;
+                Ok(Some(serde_json::to_value(taxables)?))
+            }
+            "report" => {
+                progress("reading");
+                let txns = read_holdings(&params.path, &params.currency, &base, &options)?;
+                progress("calculating");
+                let mut out = vec![];
+                write_holdings_tax(&txns, &params.currency, &base, &options, &mut out)?;
+                Ok(Some(serde_json::Value::String(String::from_utf8_lossy(&out).to_string())))
+            }
+            _ => Ok(None),
+        }
+    })?;
+    Ok(())
+}
+
+/// Stores the API key of a provider, e.g. `binance` or `coingecko`, in the keyring of the system,
+/// with its secret if the provider has one, instead of a file or the command line.
+#[cfg(feature = "native")]
+pub fn set_api_key(provider: &str, key: &str, secret: Option<&str>) -> Result<()> {
+    credentials::set(provider, key, secret)
+}
+
+/// Whether the API key of the provider has a secret too, which `set_api_key` needs.
+#[cfg(feature = "native")]
+pub fn api_key_has_secret(provider: &str) -> Result<bool> {
+    credentials::has_secret(provider)
+}
+
+/// Removes the API key of a provider and its secret from the keyring, with a warning if there
+/// was none.
+#[cfg(feature = "native")]
+pub fn remove_api_key(provider: &str) -> Result<()> {
+    if !credentials::remove(provider)? {
+        warn!(%provider, "No API key in the keyring to remove");
+    }
+    Ok(())
+}
+
+/// Prints the warnings collected during the run to `std::io::stderr()`, so that they don't mix
+/// with the report.
+pub fn print_warnings(format: &WarningFormat) -> Result<()> {
+    warnings::print(&warnings::take(), format, io::stderr())
+}
+
+/// Whether a warning that rows may be missing or wrong was reported during the run, which is
+/// `ExitCode::Warnings`. Warnings of severity `info` don't count.
+pub fn has_warnings() -> bool {
+    warnings::need_attention()
+}
+
+/// Prints the exit code, the error that the run failed with, if any, and the warnings collected
+/// during the run as one JSON object to `std::io::stderr()`, instead of `print_warnings`, for
+/// tools that run cryptotax. The `message` of the error may add context to `error`.
+pub fn print_failures(exit_code: ExitCode, message: Option<String>, error: Option<&CryptotaxError>) -> Result<()> {
+    let failures = error::Failures{
+        exit_code,
+        error: message.map(|m| error::Failure::new(m, error)),
+        warnings: warnings::take(),
+    };
+    let mut out = io::stderr().lock();
+    serde_json::to_writer_pretty(&mut out, &failures).map_err(io::Error::other)?;
+    writeln!(out)?;
+    Ok(())
+}
+
@@ -0,0 +1,85 @@
+use crate::error::{CryptotaxError, Result};
+use rust_decimal::Decimal;
+
+/// The sum of `a` and `b`, e.g. of the amounts of two buys that make a holding. Fails with an
+/// `Overflow` error if it has more than the 28 integer digits of a `Decimal`, rather than
+/// panicking.
+pub(crate) fn add(a: Decimal, b: Decimal) -> Result<Decimal> {
+    a.checked_add(b).ok_or_else(|| overflow(format!("{} + {}", a, b)))
+}
+
+/// The difference of `a` and `b`, like `add`.
+pub(crate) fn sub(a: Decimal, b: Decimal) -> Result<Decimal> {
+    a.checked_sub(b).ok_or_else(|| overflow(format!("{} - {}", a, b)))
+}
+
+/// The sum of the amounts, like `add`, e.g. of the gains of a year.
+pub(crate) fn sum<I: IntoIterator<Item = Decimal>>(amounts: I) -> Result<Decimal> {
+    amounts.into_iter().try_fold(Decimal::ZERO, add)
+}
+
+/// The quotient of `a` and `b`, e.g. the price implied by a value and an amount. Fails with an
+/// `Overflow` error if it doesn't fit a `Decimal`, or if `b` is zero.
+pub(crate) fn div(a: Decimal, b: Decimal) -> Result<Decimal> {
+    a.checked_div(b).ok_or_else(|| overflow(format!("{} / {}", a, b)))
+}
+
+/// The product of `a` and `b`, e.g. the value of an amount at a price. Fails with an `Overflow`
+/// error if it has more than the 28 integer digits of a `Decimal`, e.g. 10^21 units of a token at
+/// a price of 10^8, rather than panicking. The decimals beyond the 28 digits are rounded off, e.g.
+/// of an amount with 18 decimals at a price with 8.
+pub(crate) fn mul(a: Decimal, b: Decimal) -> Result<Decimal> {
+    a.checked_mul(b).ok_or_else(|| overflow(format!("{} × {}", a, b)))
+}
+
+/// The share of `amount` of `part` of `whole`, e.g. the cost of the part of a lot that is
+/// disposed of. Divides first, and multiplies first if dividing by a `whole` of less than one
+/// overflows. Fails with an `Overflow` error if both do, or if `whole` is zero.
+pub(crate) fn share(amount: Decimal, part: Decimal, whole: Decimal) -> Result<Decimal> {
+    amount.checked_div(whole).and_then(|a| a.checked_mul(part))
+        .or_else(|| part.checked_div(whole).and_then(|p| p.checked_mul(amount)))
+        .ok_or_else(|| overflow(format!("{} × {} / {}", amount, part, whole)))
+}
+
+fn overflow(expression: String) -> CryptotaxError {
+    CryptotaxError::Overflow(format!("{} doesn't fit the 28 digits of a decimal", expression))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::decimal::*;
+    use rust_decimal_macros::dec;
+    use std::error::Error;
+    use std::str::FromStr;
+
+    #[test]
+    fn should_fail_on_overflow_of_extreme_token_amounts() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let shib = dec!(1_000_000_000_000_000_000_000);                      // 10^21 units
+        let wei = Decimal::from_str("123456789012.345678901234567890")?;   // Of 18 decimals
+        let whole = dec!(0.000000000000000001);
+
+        /*
+         * When
+         */
+        let product = mul(shib, dec!(100_000_000));
+        let value = mul(wei, dec!(0.00001234))?;
+        let cost = share(shib, whole, whole)?;
+
+        /*
+         * Then
+         */
+        let e = product.expect_err("10^29 overflows");
+        assert!(matches!(e, CryptotaxError::Overflow(_)));
+        assert_eq!(value, dec!(1523456.7764123456776412345678));    // Rounded to 28 digits
+        assert_eq!(cost, shib);
+        assert!(share(shib, dec!(1), dec!(0)).is_err());
+        assert!(matches!(sum([dec!(50_000_000_000_000_000_000_000_000_000), dec!(50_000_000_000_000_000_000_000_000_000)]), Err(CryptotaxError::Overflow(_))));
+        assert!(matches!(sub(dec!(-50_000_000_000_000_000_000_000_000_000), dec!(50_000_000_000_000_000_000_000_000_000)), Err(CryptotaxError::Overflow(_))));
+        assert!(div(shib, dec!(0)).is_err());
+        assert_eq!(add(shib, shib)?, dec!(2_000_000_000_000_000_000_000));
+        Ok(())
+    }
+}
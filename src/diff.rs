@@ -0,0 +1,270 @@
+use crate::calculator::TaxableTransaction;
+use crate::transaction::{Currency, Transaction, TransactionType};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// A line of the diff between two runs: a transaction that was added, removed or changed, a
+/// disposal whose gain changed because of other transactions, or the total gain of a year.
+#[derive(Debug, PartialEq, Serialize)]
+pub(crate) struct DiffRow {
+    #[serde(rename = "Change")]
+    change: Change,
+
+    #[serde(rename = "Date")]
+    date: String,                       // The year for `Change::Total`
+
+    #[serde(rename = "Type")]
+    r#type: Option<TransactionType>,
+
+    #[serde(rename = "Currency")]
+    currency: Currency,
+
+    #[serde(rename = "Old Amount")]
+    old_amount: Option<Decimal>,
+
+    #[serde(rename = "New Amount")]
+    new_amount: Option<Decimal>,
+
+    #[serde(rename = "Old Exchanged")]
+    old_exchanged: Option<String>,      // e.g. "900 SEK"
+
+    #[serde(rename = "New Exchanged")]
+    new_exchanged: Option<String>,
+
+    #[serde(rename = "Old Net Income")]
+    old_net_income: Option<Decimal>,
+
+    #[serde(rename = "New Net Income")]
+    new_net_income: Option<Decimal>,
+
+    #[serde(rename = "Delta")]
+    delta: Option<Decimal>,
+}
+
+impl DiffRow {
+    #[cfg(feature = "native")]
+    pub(crate) fn is_total(&self) -> bool {
+        self.change == Change::Total
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Serialize)]
+pub(crate) enum Change {
+    Added,
+    Removed,
+    Changed,
+    /// The transaction is the same, but its gain changed, e.g. because an earlier buy changed.
+    Recalculated,
+    Total,
+}
+
+/// Compares the transactions and the taxable disposals of two runs. The transactions are matched
+/// by their date and traded currency, in order if there are several at the same time. The rows
+/// of the transactions come first, in order of date, followed by the total gain in the base
+/// currency of every year that changed.
+pub(crate) fn diff(
+    old: &[Transaction],
+    old_taxables: &[TaxableTransaction],
+    new: &[Transaction],
+    new_taxables: &[TaxableTransaction],
+    base: &Currency,
+) -> Vec<DiffRow> {
+    let old_gains = gains_by_date(old_taxables);
+    let new_gains = gains_by_date(new_taxables);
+    let old_by_key = by_key(old);
+    let new_by_key = by_key(new);
+    let keys: BTreeSet<&(String, Currency)> = old_by_key.keys().chain(new_by_key.keys()).collect();
+
+    let mut rows = vec![];
+    for key in keys {
+        let empty = vec![];
+        for (o, n) in pair(old_by_key.get(key).unwrap_or(&empty), new_by_key.get(key).unwrap_or(&empty)) {
+            let (old_net_income, new_net_income) = (old_gains.get(&key.0).copied(), new_gains.get(&key.0).copied());
+            let change = match (o, n) {
+                (Some(_), None) => Change::Removed,
+                (None, Some(_)) => Change::Added,
+                (Some(o), Some(n)) if !same(o, n) => Change::Changed,
+                _ if old_net_income != new_net_income => Change::Recalculated,
+                _ => continue,
+            };
+            let any = n.or(o).expect("either side");
+            rows.push(DiffRow{
+                change,
+                date: key.0.clone(),
+                r#type: Some(any.r#type.clone()),
+                currency: key.1.clone(),
+                old_amount: o.map(|t| t.paid_amount),
+                new_amount: n.map(|t| t.paid_amount),
+                old_exchanged: o.map(exchanged),
+                new_exchanged: n.map(exchanged),
+                old_net_income,
+                new_net_income,
+                delta: delta(old_net_income, new_net_income),
+            });
+        }
+    }
+
+    let old_years = gains_by_year(old_taxables);
+    let new_years = gains_by_year(new_taxables);
+    let years: BTreeSet<&String> = old_years.keys().chain(new_years.keys()).collect();
+    for year in years {
+        let old_net_income = old_years.get(year).copied().unwrap_or(dec!(0));
+        let new_net_income = new_years.get(year).copied().unwrap_or(dec!(0));
+        if old_net_income == new_net_income {
+            continue;
+        }
+        rows.push(DiffRow{
+            change: Change::Total,
+            date: year.clone(),
+            r#type: None,
+            currency: base.clone(),
+            old_amount: None,
+            new_amount: None,
+            old_exchanged: None,
+            new_exchanged: None,
+            old_net_income: Some(old_net_income),
+            new_net_income: Some(new_net_income),
+            delta: Some(new_net_income - old_net_income),
+        });
+    }
+    rows
+}
+
+fn by_key(txns: &[Transaction]) -> BTreeMap<(String, Currency), Vec<&Transaction>> {
+    txns.iter().fold(BTreeMap::new(), |mut acc, t| {
+        acc.entry((t.date.clone(), t.paid_currency.clone())).or_insert_with(Vec::new).push(t);
+        acc
+    })
+}
+
+/// Pairs the transactions at the same time. Identical transactions are paired first, so that an
+/// unchanged transaction is not paired with a changed or an added one, then the rest in order.
+fn pair<'a>(olds: &[&'a Transaction], news: &[&'a Transaction]) -> Vec<(Option<&'a Transaction>, Option<&'a Transaction>)> {
+    let mut news: Vec<Option<&Transaction>> = news.iter().copied().map(Some).collect();
+    let (mut pairs, olds): (Vec<_>, Vec<_>) = olds.iter()
+        .map(|o| match news.iter().position(|n| n.is_some_and(|n| same(o, n))) {
+            Some(i) => (Some(*o), news[i].take()),
+            None => (Some(*o), None),
+        })
+        .partition(|(_, n)| n.is_some());
+    let mut news = news.into_iter().flatten();
+    let mut olds = olds.into_iter().flat_map(|(o, _)| o);
+    loop {
+        match (olds.next(), news.next()) {
+            (None, None) => return pairs,
+            (o, n) => pairs.push((o, n)),
+        }
+    }
+}
+
+fn same(a: &Transaction, b: &Transaction) -> bool {
+    a.r#type == b.r#type
+        && a.paid_amount == b.paid_amount
+        && a.exchanged_currency == b.exchanged_currency
+        && a.exchanged_amount == b.exchanged_amount
+        && a.paid_fee == b.paid_fee
+        && a.exchanged_fee == b.exchanged_fee
+        && a.is_vault == b.is_vault
+}
+
+fn exchanged(t: &Transaction) -> String {
+    format!("{} {}", t.exchanged_amount, t.exchanged_currency).trim().to_string()
+}
+
+fn delta(old: Option<Decimal>, new: Option<Decimal>) -> Option<Decimal> {
+    match (old, new) {
+        (None, None) => None,
+        (old, new) => Some(new.unwrap_or(dec!(0)) - old.unwrap_or(dec!(0))),
+    }
+}
+
+/// The net income of the disposals at every date. A disposal without a net income, e.g. one sold
+/// to another crypto currency, counts as zero.
+fn gains_by_date(taxables: &[TaxableTransaction]) -> BTreeMap<String, Decimal> {
+    taxables.iter().fold(BTreeMap::new(), |mut acc, t| {
+        *acc.entry(t.date.clone()).or_insert(dec!(0)) += t.net_income.unwrap_or(dec!(0));
+        acc
+    })
+}
+
+fn gains_by_year(taxables: &[TaxableTransaction]) -> BTreeMap<String, Decimal> {
+    taxables.iter().fold(BTreeMap::new(), |mut acc, t| {
+        let year = t.date.chars().take(4).collect();
+        *acc.entry(year).or_insert(dec!(0)) += t.net_income.unwrap_or(dec!(0));
+        acc
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::calculator::tax;
+    use crate::cryptotax::Options;
+    use crate::diff::*;
+    use futures::executor::block_on;
+    use std::error::Error;
+
+    #[test]
+    fn should_diff_corrected_export() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let txn = |r#type, paid_amount, exchanged_amount, date: &str| Transaction{
+            r#type,
+            paid_currency: "BTC".to_string(),
+            paid_amount,
+            exchanged_currency: "SEK".to_string(),
+            exchanged_amount,
+            date: date.to_string(),
+            is_vault: false,
+            paid_fee: dec!(0),
+            exchanged_fee: dec!(0),
+            price_source: None,
+            unit_price: None,
+            fiat_value: None,
+            other_fee: None,
+            origin: Default::default()
+        };
+        let old = vec![
+            txn(TransactionType::Buy, dec!(1), dec!(-10000), "2021-01-01 10:00:00"),
+            txn(TransactionType::Sell, dec!(-0.5), dec!(8000), "2021-06-01 10:00:00"),
+            txn(TransactionType::Sell, dec!(-0.5), dec!(9000), "2022-01-01 10:00:00"),
+        ];
+        let new = vec![
+            txn(TransactionType::Buy, dec!(1), dec!(-12000), "2021-01-01 10:00:00"),     // Corrected
+            txn(TransactionType::Sell, dec!(-0.5), dec!(8000), "2021-06-01 10:00:00"),
+            txn(TransactionType::Buy, dec!(0.5), dec!(-6000), "2021-09-01 10:00:00"),     // Added
+            txn(TransactionType::Sell, dec!(-0.5), dec!(9000), "2022-01-01 10:00:00"),
+        ];
+        let (btc, sek, options) = ("BTC".to_string(), "SEK".to_string(), Options::default());
+        let old_taxables = block_on(tax(&old, &btc, &sek, &options))?;
+        let new_taxables = block_on(tax(&new, &btc, &sek, &options))?;
+
+        /*
+         * When
+         */
+        let rows = diff(&old, &old_taxables, &new, &new_taxables, &sek);
+
+        /*
+         * Then
+         */
+        let changes: Vec<(Change, &str)> = rows.iter().map(|r| (r.change, r.date.as_str())).collect();
+        assert_eq!(changes, vec![
+            (Change::Changed, "2021-01-01 10:00:00"),
+            (Change::Recalculated, "2021-06-01 10:00:00"),
+            (Change::Added, "2021-09-01 10:00:00"),
+            (Change::Recalculated, "2022-01-01 10:00:00"),
+            (Change::Total, "2021"),
+            (Change::Total, "2022"),
+        ]);
+        assert_eq!(rows[0].old_exchanged, Some("-10000 SEK".to_string()));
+        assert_eq!(rows[0].new_exchanged, Some("-12000 SEK".to_string()));
+        assert_eq!(rows[1].delta, Some(dec!(-1000)));
+        assert_eq!(rows[4].currency, "SEK".to_string());
+        assert_eq!(rows[4].delta, Some(dec!(-1000)));
+        assert_eq!(rows[5].old_net_income, Some(dec!(4000)));
+        assert_eq!(rows[5].new_net_income, Some(dec!(3000)));
+        Ok(())
+    }
+}
@@ -0,0 +1,160 @@
+use crate::transaction::{Transaction, TransactionType};
+use crate::warnings::{self, Code, Warning};
+use rust_decimal::Decimal;
+use std::fmt;
+use std::str::FromStr;
+
+/// What is done with the buys, sells and income of an amount below the dust threshold, e.g. the
+/// tiny amounts of a sweep of small balances into another currency, which would otherwise each be
+/// a lot or a disposal of their own.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum Dust {
+    /// Summed into one transaction per day, type and exchanged currency.
+    #[default]
+    Aggregate,
+    /// Left out.
+    Ignore,
+}
+
+impl FromStr for Dust {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_uppercase().as_str() {
+            "AGGREGATE" => Ok(Dust::Aggregate),
+            "IGNORE" => Ok(Dust::Ignore),
+            _ => Err(format!("Unknown dust handling `{}`. Supported: AGGREGATE, IGNORE", s)),
+        }
+    }
+}
+
+impl fmt::Display for Dust {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Dust::Aggregate => write!(f, "AGGREGATE"),
+            Dust::Ignore => write!(f, "IGNORE"),
+        }
+    }
+}
+
+fn is_trade(t: &Transaction) -> bool {
+    matches!(t.r#type, TransactionType::Buy | TransactionType::Sell | TransactionType::Income)
+}
+
+/// Leaves out the transactions of a zero amount, which would be a lot without an amount, or a
+/// disposal without a cost. With a `threshold`, the buys, sells and income of a smaller amount
+/// are aggregated or ignored by `dust`. An aggregate is dated at the first of its transactions if
+/// it is acquired, and at the last if it is disposed of, so that it is never disposed of before it
+/// is held. Every transaction left out, and every aggregate, is reported as a `Code::Dust`
+/// warning.
+pub(crate) fn apply(txns: &mut Vec<Transaction>, threshold: Option<Decimal>, dust: Dust) {
+    txns.retain(|t| {
+        if !t.paid_amount.is_zero() {
+            return true;
+        }
+        let msg = format!("{:?} of zero {} for {} {} left out", t.r#type, t.paid_currency, t.exchanged_amount, t.exchanged_currency);
+        warnings::report(Warning::new(Code::Dust, msg).at(&t.origin).at_date(&t.date));
+        false
+    });
+    let Some(threshold) = threshold else {
+        return;
+    };
+    let is_dust = |t: &Transaction| is_trade(t) && t.paid_amount.abs() < threshold;
+    let (dusts, mut kept): (Vec<Transaction>, Vec<Transaction>) = std::mem::take(txns).into_iter().partition(is_dust);
+    if dusts.is_empty() {
+        *txns = kept;
+        return;
+    }
+    if dust == Dust::Ignore {
+        for t in &dusts {
+            let msg = format!("{:?} of dust {} {} ignored", t.r#type, t.paid_amount, t.paid_currency);
+            warnings::report(Warning::new(Code::Dust, msg).at(&t.origin).at_date(&t.date));
+        }
+        *txns = kept;
+        return;
+    }
+    let mut aggregates: Vec<(Transaction, usize)> = vec![];
+    for t in dusts {
+        let same = |a: &Transaction| {
+            a.r#type == t.r#type
+                && a.paid_currency == t.paid_currency
+                && a.exchanged_currency == t.exchanged_currency
+                && a.is_vault == t.is_vault
+                && a.date.get(..10) == t.date.get(..10)
+        };
+        match aggregates.iter_mut().find(|(a, _)| same(a)) {
+            Some((a, count)) => {
+                if a.paid_amount.is_sign_negative() && t.date > a.date || a.paid_amount.is_sign_positive() && t.date < a.date {
+                    a.date = t.date.clone();
+                }
+                a.paid_amount += t.paid_amount;
+                a.paid_fee += t.paid_fee;
+                a.exchanged_amount += t.exchanged_amount;
+                a.exchanged_fee += t.exchanged_fee;
+                t.origin.lines.iter().zip(&t.origin.descriptions).for_each(|(l, d)| a.origin.add(*l, d.clone()));
+                *count += 1;
+            }
+            None => aggregates.push((t, 1)),
+        }
+    }
+    for (a, count) in aggregates {
+        let msg = format!("{} {:?} rows of dust aggregated into {} {}", count, a.r#type, a.paid_amount, a.paid_currency);
+        warnings::report(Warning::new(Code::Dust, msg).at(&a.origin).at_date(&a.date));
+        kept.push(a);
+    }
+    kept.sort_by(Transaction::cmp_by_date);
+    *txns = kept;
+}
+
+#[cfg(test)]
+mod test {
+    use crate::dust::*;
+    use rust_decimal_macros::dec;
+    use std::error::Error;
+
+    #[test]
+    fn should_leave_out_zero_amounts_and_aggregate_dust() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let buy = |amount, cost, date: &str| {
+            Transaction::buy().currency("BNB").amount(amount).exchanged_currency("SEK").exchanged_amount(cost).date(date).build()
+        };
+        let zero = Transaction{ paid_amount: dec!(0), ..buy(dec!(1), dec!(-5), "2022-01-02 10:00:00")? };
+        let txns = vec![
+            buy(dec!(1), dec!(-3000), "2022-01-01 10:00:00")?,
+            zero,
+            buy(dec!(0.0002), dec!(-0.6), "2022-01-03 10:00:00")?,
+            buy(dec!(0.0003), dec!(-0.9), "2022-01-03 10:00:01")?,
+            buy(dec!(0.0004), dec!(-1.2), "2022-01-04 10:00:00")?,
+        ];
+        warnings::take();
+
+        /*
+         * When
+         */
+        let mut aggregated = txns.clone();
+        apply(&mut aggregated, Some(dec!(0.001)), Dust::Aggregate);
+        let mut ignored = txns.clone();
+        apply(&mut ignored, Some(dec!(0.001)), Dust::Ignore);
+        let warnings = warnings::take();
+
+        /*
+         * Then
+         */
+        let rows = |txns: &[Transaction]| -> Vec<(String, Decimal, Decimal)> {
+            txns.iter().map(|t| (t.date.clone(), t.paid_amount, t.exchanged_amount)).collect()
+        };
+        assert_eq!(rows(&aggregated), vec![
+            ("2022-01-01 10:00:00".to_string(), dec!(1), dec!(-3000)),
+            ("2022-01-03 10:00:00".to_string(), dec!(0.0005), dec!(-1.5)),
+            ("2022-01-04 10:00:00".to_string(), dec!(0.0004), dec!(-1.2)),
+        ]);
+        assert_eq!(rows(&ignored), vec![
+            ("2022-01-01 10:00:00".to_string(), dec!(1), dec!(-3000)),
+        ]);
+        assert!(warnings.iter().all(|w| w.code == Code::Dust));
+        assert_eq!(warnings.len(), 1 + 2 + 3);     // The zero amount is reported once
+        Ok(())
+    }
+}
@@ -0,0 +1,46 @@
+use crate::transaction::{Origin, TransactionType};
+
+/// Words of the rows of interest of savings and earn accounts, in lower case, e.g. the Binance
+/// `Simple Earn Flexible Interest` and `Staking Rewards`, the Crypto.com
+/// `crypto_earn_interest_paid`, the Nexo `Fixed Term Interest`, the Kraken `staking` and `earn`
+/// of subtype `reward`, and the Revolut `Interest`.
+const INTEREST_WORDS: [&str; 6] = ["interest", "savings", "simple earn", "staking", "stake_reward", "earn,reward"];
+
+/// Whether the income is interest of a savings or an earn account, by the rows it was read from,
+/// rather than e.g. cashback or a rebate. It is summed up on its own line of the summary.
+pub(crate) fn is_interest(r#type: &TransactionType, origin: &Origin) -> bool {
+    *r#type == TransactionType::Income
+        && origin.descriptions.iter().any(|d| {
+            let d = d.to_lowercase();
+            INTEREST_WORDS.iter().any(|w| d.contains(w))
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::earn::*;
+
+    #[test]
+    fn should_tell_interest_of_earn_accounts() {
+        /*
+         * Given
+         */
+        let origin = |description: &str| Origin::new(2, description.to_string());
+
+        /*
+         * When
+         */
+        let is = |r#type: TransactionType, description: &str| is_interest(&r#type, &origin(description));
+
+        /*
+         * Then
+         */
+        assert!(is(TransactionType::Income, "1,2022-01-01 10:00:00,Earn,Simple Earn Flexible Interest,BNB,0.01,"));
+        assert!(is(TransactionType::Income, "2022-02-15 08:00:00,Crypto Earn,BTC,0.0001,,,EUR,4.00,4.50,crypto_earn_interest_paid,"));
+        assert!(is(TransactionType::Income, "L1,,2022-01-01 10:00:00,staking,,currency,DOT.S,0.05,0,1.05"));
+        assert!(is(TransactionType::Income, "L2,,2022-01-02 10:00:00,earn,reward,currency,EUR.M,0.05,0,100.05"));
+        assert!(!is(TransactionType::Income, "2022-02-15 08:00:00,Card Cashback,BTC,0.0001,,,EUR,4.00,4.50,referral_card_cashback,"));
+        assert!(!is(TransactionType::Income, "1,2022-01-01 10:00:00,Spot,Commission Rebate,BNB,0.01,"));
+        assert!(!is(TransactionType::Transfer, "1,2022-01-01 10:00:00,Earn,Simple Earn Flexible Subscription,BNB,-1,"));
+    }
+}
@@ -0,0 +1,155 @@
+use crate::cryptotax::Options;
+use crate::transaction::{Currency, Transaction, TransactionType};
+use crate::warnings::{self, Code, Warning};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// The wrapped and bridged currencies that are pooled with the currency they stand for, unless
+/// the jurisdiction treats them as other assets, see `Jurisdiction::pools_equivalent_currencies`.
+/// Wrapped BTC is left out, as it is a claim on a custodian rather than on a contract.
+pub(crate) const DEFAULTS: [(&str, &str); 6] = [
+    ("WETH", "ETH"),
+    ("WETH.e", "ETH"),      // Bridged to Avalanche
+    ("BTC.b", "BTC"),
+    ("USDC.e", "USDC"),
+    ("USDbC", "USDC"),      // Bridged to Base
+    ("USDT.e", "USDT"),
+];
+
+/// A currency that is the same asset as another for the cost basis, e.g. `WETH=ETH`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Equivalent {
+    pub(crate) alias: Currency,
+    pub(crate) currency: Currency,
+}
+
+impl Equivalent {
+    pub(crate) fn new(alias: &str, currency: &str) -> Equivalent {
+        Equivalent{ alias: alias.to_string(), currency: currency.to_string() }
+    }
+}
+
+impl FromStr for Equivalent {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('=') {
+            Some((alias, currency)) if !alias.trim().is_empty() && !currency.trim().is_empty() => {
+                Ok(Equivalent::new(alias.trim(), currency.trim()))
+            }
+            _ => Err(format!("Unknown equivalent `{}`. Expected e.g. WETH=ETH", s)),
+        }
+    }
+}
+
+impl fmt::Display for Equivalent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}={}", self.alias, self.currency)
+    }
+}
+
+/// The equivalents of `Options::equivalents`, and the defaults if the jurisdiction pools them
+/// and `Options::no_default_equivalents` is not set.
+pub(crate) fn equivalents(options: &Options) -> Vec<Equivalent> {
+    let mut equivalents = options.equivalents.clone();
+    if options.jurisdiction.pools_equivalent_currencies() && !options.no_default_equivalents {
+        let defaults = DEFAULTS.iter().filter(|(alias, _)| !equivalents.iter().any(|e| e.alias == *alias));
+        equivalents.extend(defaults.map(|(alias, currency)| Equivalent::new(alias, currency)).collect::<Vec<_>>());
+    }
+    equivalents
+}
+
+/// The currencies that are pooled with `currency`.
+pub(crate) fn aliases_of<'a>(currency: &Currency, equivalents: &'a [Equivalent]) -> Vec<&'a Currency> {
+    equivalents.iter().filter(|e| e.currency.eq(currency)).map(|e| &e.alias).collect()
+}
+
+/// Renames the aliases among the paid and exchanged currencies to the currency they are
+/// equivalent to, and reports every equivalent that was applied. A trade of a currency for its
+/// equivalent, e.g. wrapping ETH on a DEX, is a transfer then, as nothing is disposed of.
+pub(crate) fn apply(txns: &mut [Transaction], equivalents: &[Equivalent]) {
+    let canonical = |c: &Currency| equivalents.iter().find(|e| e.alias.eq(c)).map(|e| &e.currency);
+    let mut applied: BTreeMap<String, usize> = BTreeMap::new();
+    for t in txns.iter_mut() {
+        let (paid, exchanged) = (canonical(&t.paid_currency), canonical(&t.exchanged_currency));
+        if paid.is_none() && exchanged.is_none() {
+            continue;
+        }
+        let mut names = vec![];
+        paid.iter().for_each(|c| names.push(format!("{}={}", t.paid_currency, c)));
+        exchanged.iter().for_each(|c| names.push(format!("{}={}", t.exchanged_currency, c)));
+        names.dedup();
+        names.into_iter().for_each(|name| *applied.entry(name).or_default() += 1);
+        let same = paid.unwrap_or(&t.paid_currency) == exchanged.unwrap_or(&t.exchanged_currency);
+        if same && t.paid_currency != t.exchanged_currency && matches!(t.r#type, TransactionType::Buy | TransactionType::Sell) {
+            t.r#type = TransactionType::Transfer;
+            t.exchanged_currency = "".to_string();
+            t.exchanged_amount = Default::default();
+            t.exchanged_fee = Default::default();
+        }
+        if let Some(paid) = paid {
+            t.paid_currency = paid.clone();
+        }
+        if let Some(exchanged) = exchanged.filter(|_| !t.exchanged_currency.is_empty()) {
+            t.exchanged_currency = exchanged.clone();
+        }
+    }
+    for (equivalent, count) in applied {
+        let msg = format!("Pooled {} transactions with the equivalent currencies {}", count, equivalent);
+        warnings::report(Warning::new(Code::EquivalentCurrency, msg));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::equivalence::*;
+    use rust_decimal::Decimal;
+    use rust_decimal_macros::dec;
+    use std::error::Error;
+
+    #[test]
+    fn should_pool_equivalent_currencies() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let txn = |r#type: TransactionType, paid: &str, amount: Decimal, exchanged: &str, exchanged_amount: Decimal| {
+            let mut txn = Transaction::new();
+            txn.r#type = r#type;
+            txn.paid_currency = paid.to_string();
+            txn.paid_amount = amount;
+            txn.exchanged_currency = exchanged.to_string();
+            txn.exchanged_amount = exchanged_amount;
+            txn
+        };
+        let mut txns = vec![
+            txn(TransactionType::Buy, "ETH", dec!(1), "SEK", dec!(-30000)),
+            txn(TransactionType::Sell, "ETH", dec!(-1), "WETH", dec!(1)),         // Wrapped on a DEX
+            txn(TransactionType::Sell, "WETH", dec!(-0.5), "USDC", dec!(1500)),
+            txn(TransactionType::Sell, "WETH", dec!(-0.01), "WETH", dec!(0.01)),  // A network fee
+        ];
+        let equivalents: Vec<Equivalent> = vec!["WETH=ETH".parse()?];
+
+        /*
+         * When
+         */
+        warnings::take();
+        apply(&mut txns, &equivalents);
+
+        /*
+         * Then
+         */
+        let rows: Vec<_> = txns.iter().map(|t| (t.r#type.clone(), t.paid_currency.as_str(), t.exchanged_currency.as_str())).collect();
+        assert_eq!(rows, vec![
+            (TransactionType::Buy, "ETH", "SEK"),
+            (TransactionType::Transfer, "ETH", ""),
+            (TransactionType::Sell, "ETH", "USDC"),
+            (TransactionType::Sell, "ETH", "ETH"),
+        ]);
+        let warnings = warnings::take();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].code, Code::EquivalentCurrency);
+        assert!("WETH".parse::<Equivalent>().is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,241 @@
+use crate::warnings::Warning;
+use serde::Serialize;
+use std::io;
+use std::path::Path;
+use thiserror::Error;
+
+/// What went wrong, so that the errors can be told apart by the library users and the CLI, and
+/// presented with what can be done about them.
+#[derive(Debug, Error)]
+pub enum CryptotaxError {
+    /// A file, or a row of it, that can't be read, at the line and column if known. The file is
+    /// empty for a statement that is read from memory.
+    #[error("{}: {message}", location(.file, .line, .column))]
+    Parse {
+        file: String,
+        line: Option<u64>,
+        column: Option<usize>,
+        message: String,
+    },
+    /// Transactions that don't add up, e.g. a disposal of more than is held.
+    #[error("{0}")]
+    Pairing(String),
+    /// Prices or exchange rates that are missing to value the transactions.
+    #[error("{0}")]
+    Pricing(String),
+    /// An amount that doesn't fit a decimal, e.g. of a token of 18 decimals at a high price.
+    #[error("{0}")]
+    Overflow(String),
+    /// A report that can't be written with what it was given.
+    #[error("{0}")]
+    Report(String),
+    /// Options or arguments that can't be used, or don't go together.
+    #[error("{0}")]
+    Config(String),
+    #[error(transparent)]
+    Io(io::Error),
+}
+
+pub type Result<T, E = CryptotaxError> = std::result::Result<T, E>;
+
+/// The exit codes of the CLI.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(into = "i32")]
+pub enum ExitCode {
+    Ok = 0,
+    /// The report is written, but warnings that rows may be missing or wrong were reported.
+    Warnings = 1,
+    /// The input can't be read or doesn't add up: `Parse`, `Pairing`, `Pricing`, `Overflow`
+    /// and `Io` errors.
+    DataError = 2,
+    /// The options or arguments can't be used: `Config` and `Report` errors.
+    ConfigError = 3,
+}
+
+impl From<ExitCode> for i32 {
+    fn from(code: ExitCode) -> i32 {
+        code as i32
+    }
+}
+
+/// The summary of a run that `--json-errors` prints: its exit code, the error it failed with, and
+/// the warnings that were reported.
+#[derive(Debug, Serialize)]
+pub(crate) struct Failures {
+    pub(crate) exit_code: ExitCode,
+    pub(crate) error: Option<Failure>,
+    pub(crate) warnings: Vec<Warning>,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct Failure {
+    kind: &'static str,
+    message: String,
+    file: Option<String>,
+    line: Option<u64>,
+    column: Option<usize>,
+}
+
+impl Failure {
+    /// The failure of `error`, with `message`, which may add context to it. An error that is not a
+    /// `CryptotaxError` is of the kind `io`.
+    pub(crate) fn new(message: String, error: Option<&CryptotaxError>) -> Failure {
+        let (file, line, column) = match error {
+            Some(CryptotaxError::Parse{ file, line, column, .. }) => (Some(file.clone()), *line, *column),
+            _ => (None, None, None),
+        };
+        Failure{ kind: error.map_or("io", CryptotaxError::kind), message, file, line, column }
+    }
+}
+
+fn location(file: &str, line: &Option<u64>, column: &Option<usize>) -> String {
+    match (file.is_empty(), line, column) {
+        (false, Some(line), Some(column)) => format!("{}:{}:{}", file, line, column),
+        (false, Some(line), None) => format!("{}:{}", file, line),
+        (false, None, _) => file.to_string(),
+        (true, Some(line), Some(column)) => format!("line {}, column {}", line, column),
+        (true, Some(line), None) => format!("line {}", line),
+        (true, None, _) => "statement".to_string(),
+    }
+}
+
+impl CryptotaxError {
+    /// The name of the variant, in lower case.
+    pub(crate) fn kind(&self) -> &'static str {
+        match self {
+            CryptotaxError::Parse{..} => "parse",
+            CryptotaxError::Pairing(_) => "pairing",
+            CryptotaxError::Pricing(_) => "pricing",
+            CryptotaxError::Overflow(_) => "overflow",
+            CryptotaxError::Report(_) => "report",
+            CryptotaxError::Config(_) => "config",
+            CryptotaxError::Io(_) => "io",
+        }
+    }
+
+    /// Locates a `Parse` error of a row, see `row`, in `file`. Other errors are returned as is.
+    pub(crate) fn in_file(self, file: &str) -> CryptotaxError {
+        match self {
+            CryptotaxError::Parse{ file: f, line, column, message } if f.is_empty() =>
+                CryptotaxError::Parse{ file: file.to_string(), line, column, message },
+            e => e,
+        }
+    }
+
+    pub fn exit_code(&self) -> ExitCode {
+        match self {
+            CryptotaxError::Config(_) | CryptotaxError::Report(_) => ExitCode::ConfigError,
+            _ => ExitCode::DataError,
+        }
+    }
+}
+
+impl From<io::Error> for CryptotaxError {
+    fn from(e: io::Error) -> Self {
+        CryptotaxError::Io(e)
+    }
+}
+
+impl From<csv::Error> for CryptotaxError {
+    /// An error of reading or writing a csv file is an `Io` error, and of its contents a `Parse`
+    /// error, see `row`.
+    fn from(e: csv::Error) -> Self {
+        match e.kind() {
+            csv::ErrorKind::Io(_) => CryptotaxError::Io(e.into()),
+            _ => row(e),
+        }
+    }
+}
+
+impl From<serde_json::Error> for CryptotaxError {
+    /// An error of reading or writing a json file is an `Io` error, and of its contents a `Parse`
+    /// error at the line and column of the error, located in the file by `in_file`.
+    fn from(e: serde_json::Error) -> Self {
+        if e.is_io() {
+            return CryptotaxError::Io(e.into());
+        }
+        let (line, column) = match e.line() {
+            0 => (None, None),
+            line => (Some(line as u64), Some(e.column())),
+        };
+        let suffix = format!(" at line {} column {}", e.line(), e.column());
+        let message = e.to_string();
+        let message = message.strip_suffix(&suffix).unwrap_or(&message).to_string();
+        CryptotaxError::Parse{ file: "".to_string(), line, column, message }
+    }
+}
+
+impl From<zip::result::ZipError> for CryptotaxError {
+    fn from(e: zip::result::ZipError) -> Self {
+        CryptotaxError::Io(e.into())
+    }
+}
+
+/// A `CryptotaxError::Parse` of a csv file, at the line and column of the error if known.
+pub(crate) fn parse(file: &Path, e: csv::Error) -> CryptotaxError {
+    let line = e.position().map(|p| p.line());
+    let column = match e.kind() {
+        csv::ErrorKind::Deserialize{ err, .. } => err.field().map(|f| f as usize + 1),
+        _ => None,
+    };
+    let message = match e.kind() {
+        csv::ErrorKind::Deserialize{ err, .. } => err.kind().to_string(),
+        _ => e.to_string(),
+    };
+    CryptotaxError::Parse{ file: file.display().to_string(), line, column, message }
+}
+
+/// A `CryptotaxError::Parse` of a row that can't be read, of a reader that doesn't know its file.
+/// It is located in the file by `CryptotaxError::in_file`.
+pub(crate) fn row(e: csv::Error) -> CryptotaxError {
+    parse(Path::new(""), e)
+}
+
+/// A `CryptotaxError::Parse` of a file that is not a csv file, e.g. a toml file, or of a csv file
+/// with values that can't be used.
+pub(crate) fn invalid<E: std::fmt::Display>(file: &Path, e: E) -> CryptotaxError {
+    CryptotaxError::Parse{ file: file.display().to_string(), line: None, column: None, message: e.to_string() }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::error::*;
+    use csv::ReaderBuilder;
+    use serde::Deserialize;
+    use std::path::PathBuf;
+
+    #[test]
+    fn should_tell_errors_apart() {
+        /*
+         * Given
+         */
+        #[derive(Debug, Deserialize)]
+        #[allow(dead_code)]
+        struct Row { date: String, amount: u32 }
+        let csv = "date,amount\n2022-01-01,1\n2022-01-02,one\n";
+        let e = ReaderBuilder::new().from_reader(csv.as_bytes())
+            .deserialize::<Row>()
+            .collect::<Result<Vec<Row>, csv::Error>>()
+            .unwrap_err();
+
+        /*
+         * When
+         */
+        let parse = parse(&PathBuf::from("prices.csv"), e);
+        let invalid = invalid(&PathBuf::from("mapping.toml"), "missing field `date`");
+        let config = CryptotaxError::Config("Unknown".to_string());
+        let other = CryptotaxError::from(io::Error::from(io::ErrorKind::NotFound));
+        let json = CryptotaxError::from(serde_json::from_str::<Vec<u32>>("[1,\n2,}").unwrap_err())
+            .in_file("prices.json");
+
+        /*
+         * Then
+         */
+        assert!(matches!(parse, CryptotaxError::Parse{ line: Some(3), column: Some(2), .. }));
+        assert!(parse.to_string().starts_with("prices.csv:3:2: "));
+        assert_eq!(invalid.to_string(), "mapping.toml: missing field `date`");
+        assert!(matches!(other, CryptotaxError::Io(_)));
+        assert_eq!(json.to_string(), "prices.json:2:3: expected value");
+        assert_eq!((parse.exit_code(), other.exit_code(), config.exit_code()), (ExitCode::DataError, ExitCode::DataError, ExitCode::ConfigError));
+    }
+}
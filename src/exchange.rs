@@ -0,0 +1,166 @@
+use crate::credentials::Credentials;
+use crate::error::Result;
+use crate::timestamp::Timestamp;
+use crate::transaction::{Currency, Origin, Transaction, TransactionType};
+use rust_decimal::Decimal;
+use std::fmt;
+use std::str::FromStr;
+
+pub(crate) mod binance;
+pub(crate) mod kraken;
+
+/// The date of a unix timestamp of a trade, in UTC.
+fn date_of(time: i64) -> Option<String> {
+    Timestamp::from_unix(time).map(|t| t.to_string())
+}
+
+/// An exchange whose trade history is fetched from its REST API with a read-only API key, see
+/// `credentials::load`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exchange {
+    Binance,
+    Kraken,
+}
+
+impl Exchange {
+    /// The name of the exchange in the keyring and in the origin of its transactions.
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            Exchange::Binance => "binance",
+            Exchange::Kraken => "kraken",
+        }
+    }
+
+    /// The URL of the API, unless another is given, e.g. of Binance US.
+    pub(crate) fn api_url(&self) -> &'static str {
+        match self {
+            Exchange::Binance => "https://api.binance.com",
+            Exchange::Kraken => "https://api.kraken.com",
+        }
+    }
+
+    /// Fetches the trades of the account in the target currency, against any other currency.
+    /// The transactions are sorted by date, see `Transaction::cmp_by_date`. Nothing is fetched if
+    /// `offline`, see `http::send`.
+    pub(crate) fn fetch(&self, currency: &Currency, api_url: &str, credentials: &Credentials, offline: bool) -> Result<Vec<Transaction>> {
+        let trades = match self {
+            Exchange::Binance => binance::fetch(currency, api_url, credentials, offline)?,
+            Exchange::Kraken => kraken::fetch(currency, api_url, credentials, offline)?,
+        };
+        let mut txns: Vec<Transaction> = trades.iter().filter_map(|t| t.to_transaction(currency, self.name())).collect();
+        txns.sort_by(Transaction::cmp_by_date);
+        Ok(txns)
+    }
+}
+
+/// A trade of a pair of an exchange, whichever API it was fetched from.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Trade {
+    pub(crate) id: String,
+    pub(crate) date: String,
+    pub(crate) base: Currency,
+    pub(crate) quote: Currency,
+    pub(crate) is_buy: bool,
+    pub(crate) size: Decimal,      // In the base currency
+    pub(crate) funds: Decimal,     // In the quote currency, without the fee
+    pub(crate) fee: Decimal,
+    pub(crate) fee_currency: Currency,
+}
+
+impl Trade {
+    /// Converts the trade from the side of the target currency, which can be the base or the
+    /// quote currency of the pair: selling BTC/USDT is buying USDT. A fee in another currency,
+    /// e.g. BNB, is left out.
+    fn to_transaction(&self, currency: &Currency, exchange: &str) -> Option<Transaction> {
+        let (size, funds, fee) = (self.size.abs(), self.funds.abs(), self.fee.abs());
+        // Signed from the side of the base currency: bought size for funds, or sold size for funds.
+        let (base_amount, quote_amount) = match self.is_buy {
+            true => (size, -funds),
+            false => (-size, funds),
+        };
+        let (amount, other, other_amount) = match currency {
+            c if c.eq(&self.base) => (base_amount, self.quote.clone(), quote_amount),
+            c if c.eq(&self.quote) => (quote_amount, self.base.clone(), base_amount),
+            _ => return None,
+        };
+        let mut txn = Transaction::new();
+        txn.r#type = if amount.is_sign_positive() { TransactionType::Buy } else { TransactionType::Sell };
+        txn.date = self.date.clone();
+        txn.paid_currency = currency.clone();
+        txn.paid_amount = amount;
+        txn.exchanged_currency = other;
+        txn.exchanged_amount = other_amount;
+        if self.fee_currency.eq(currency) {
+            txn.paid_amount -= fee;
+            txn.paid_fee = -fee;
+        } else if self.fee_currency.eq(&txn.exchanged_currency) {
+            txn.exchanged_amount -= fee;
+            txn.exchanged_fee = -fee;
+        }
+        txn.origin = Origin{ file: exchange.to_string(), lines: vec![], descriptions: vec![self.id.clone()], deviation: None, note: None, tags: vec![], counterparty: None };
+        Some(txn)
+    }
+}
+
+impl FromStr for Exchange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_uppercase().as_str() {
+            "BINANCE" => Ok(Exchange::Binance),
+            "KRAKEN" => Ok(Exchange::Kraken),
+            _ => Err(format!("Unknown exchange `{}`. Supported: BINANCE, KRAKEN", s)),
+        }
+    }
+}
+
+impl fmt::Display for Exchange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Exchange::Binance => write!(f, "BINANCE"),
+            Exchange::Kraken => write!(f, "KRAKEN"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::exchange::*;
+    use rust_decimal_macros::dec;
+    use std::error::Error;
+
+    #[test]
+    fn should_convert_a_trade_from_either_side() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let trade = Trade{
+            id: "28457".to_string(),
+            date: "2022-01-02 10:00:00".to_string(),
+            base: "BTC".to_string(),
+            quote: "USDT".to_string(),
+            is_buy: true,
+            size: dec!(0.01),
+            funds: dec!(400),
+            fee: dec!(0.4),
+            fee_currency: "USDT".to_string(),
+        };
+
+        /*
+         * When
+         */
+        let btc = trade.to_transaction(&"BTC".to_string(), "binance").ok_or("No BTC transaction")?;
+        let usdt = trade.to_transaction(&"USDT".to_string(), "binance").ok_or("No USDT transaction")?;
+        let eth = trade.to_transaction(&"ETH".to_string(), "binance");
+
+        /*
+         * Then
+         */
+        assert_eq!((btc.r#type, btc.paid_amount, btc.exchanged_amount, btc.exchanged_fee), (TransactionType::Buy, dec!(0.01), dec!(-400.4), dec!(-0.4)));
+        assert_eq!((usdt.r#type, usdt.paid_amount, usdt.exchanged_amount, usdt.paid_fee), (TransactionType::Sell, dec!(-400.4), dec!(0.01), dec!(-0.4)));
+        assert_eq!(btc.origin.file, "binance");
+        assert_eq!(btc.origin.descriptions, vec!["28457"]);
+        assert_eq!(eth, None);
+        Ok(())
+    }
+}
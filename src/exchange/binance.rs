@@ -0,0 +1,148 @@
+use crate::credentials::Credentials;
+use crate::error::Result;
+use crate::exchange::{self, Trade};
+use crate::http;
+use crate::transaction::Currency;
+use hmac::{Hmac, Mac};
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use sha2::Sha256;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{debug, instrument};
+
+/// The most trades that `myTrades` returns at a time.
+const PAGE_SIZE: usize = 1000;
+
+/// How long a signed request is valid, in milliseconds.
+const RECV_WINDOW: u64 = 60_000;
+
+#[derive(Debug, Deserialize)]
+struct ExchangeInfo {
+    symbols: Vec<Symbol>,
+}
+
+/// A pair of the exchange, e.g. `BTCUSDT` of `BTC` and `USDT`.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Symbol {
+    symbol: String,
+    base_asset: Currency,
+    quote_asset: Currency,
+}
+
+/// A trade of the account, from `myTrades`, e.g.
+/// `{"symbol":"BTCUSDT","id":28457,"price":"40000","qty":"0.01","quoteQty":"400","commission":"0.4","commissionAsset":"USDT","time":1641117600000,"isBuyer":true}`.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MyTrade {
+    id: u64,
+    qty: String,
+    quote_qty: String,
+    commission: String,
+    commission_asset: Currency,
+    time: i64,      // Milliseconds
+    is_buyer: bool,
+}
+
+impl MyTrade {
+    fn to_trade(&self, symbol: &Symbol) -> Option<Trade> {
+        Some(Trade{
+            id: format!("{}:{}", symbol.symbol, self.id),
+            date: exchange::date_of(self.time / 1000)?,
+            base: symbol.base_asset.clone(),
+            quote: symbol.quote_asset.clone(),
+            is_buy: self.is_buyer,
+            size: Decimal::from_str(&self.qty).ok()?,
+            funds: Decimal::from_str(&self.quote_qty).ok()?,
+            fee: Decimal::from_str(&self.commission).unwrap_or_default(),
+            fee_currency: self.commission_asset.clone(),
+        })
+    }
+}
+
+/// The signature of a query of a signed endpoint, the HMAC-SHA256 of the query with the secret.
+fn sign(query: &str, secret: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes a key of any length");
+    mac.update(query.as_bytes());
+    mac.finalize().into_bytes().iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or_default()
+}
+
+/// Fetches a page of the trades of the symbol from the trade id `from_id` on.
+fn fetch_page(api_url: &str, credentials: &Credentials, symbol: &str, from_id: u64, offline: bool) -> Result<Vec<MyTrade>> {
+    let query = format!(
+        "symbol={}&fromId={}&limit={}&recvWindow={}&timestamp={}",
+        symbol, from_id, PAGE_SIZE, RECV_WINDOW, now_millis()
+    );
+    let url = format!("{}/api/v3/myTrades?{}&signature={}", api_url, query, sign(&query, &credentials.secret));
+    debug!(%symbol, from_id, "Fetching trades");
+    let body = http::send("binance", offline, || (http::get(&url).set("X-MBX-APIKEY", &credentials.key), None))?;
+    Ok(serde_json::from_str(&body)?)
+}
+
+/// Fetches the trades of every symbol of the target currency, as the base or the quote currency,
+/// from the API at `api_url`, e.g. `https://api.binance.com`.
+#[instrument(name = "sync", skip_all, fields(exchange = "binance", %currency))]
+pub(crate) fn fetch(currency: &Currency, api_url: &str, credentials: &Credentials, offline: bool) -> Result<Vec<Trade>> {
+    let body = http::fetch("binance", offline, http::get(&format!("{}/api/v3/exchangeInfo", api_url)))?;
+    let info: ExchangeInfo = serde_json::from_str(&body)?;
+    let mut trades = vec![];
+    for symbol in info.symbols.iter().filter(|s| s.base_asset.eq(currency) || s.quote_asset.eq(currency)) {
+        let mut from_id = 0;
+        loop {
+            let page = fetch_page(api_url, credentials, &symbol.symbol, from_id, offline)?;
+            trades.extend(page.iter().filter_map(|t| t.to_trade(symbol)));
+            match page.last() {
+                Some(last) if page.len() == PAGE_SIZE => from_id = last.id + 1,
+                _ => break,
+            }
+        }
+    }
+    Ok(trades)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::exchange::binance::*;
+    use rust_decimal_macros::dec;
+    use std::error::Error;
+
+    #[test]
+    fn should_sign_and_read_trades() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let secret = "NhqPtmdSJYdKjVHjA7PZj4Mge3R5YNiP1e3UZjInClVN65XAbvqqM6A7H5fATj0j";
+        let query = "symbol=LTCBTC&side=BUY&type=LIMIT&timeInForce=GTC&quantity=1&price=0.1&recvWindow=5000&timestamp=1499827319559";
+        let body = r#"[{"symbol":"BTCUSDT","id":28457,"orderId":100234,"price":"40000","qty":"0.01","quoteQty":"400","commission":"0.0005","commissionAsset":"BNB","time":1641117600000,"isBuyer":false,"isMaker":false,"isBestMatch":true}]"#;
+        let symbol = Symbol{ symbol: "BTCUSDT".to_string(), base_asset: "BTC".to_string(), quote_asset: "USDT".to_string() };
+
+        /*
+         * When
+         */
+        let signature = sign(query, secret);
+        let trades: Vec<MyTrade> = serde_json::from_str(body)?;
+        let trade = trades[0].to_trade(&symbol).ok_or("No trade")?;
+
+        /*
+         * Then
+         */
+        assert_eq!(signature, "c8db56825ae71d6d79447849e617115f4a920fa2acdcab2b053c4b2838bd6b71");
+        assert_eq!(trade, Trade{
+            id: "BTCUSDT:28457".to_string(),
+            date: "2022-01-02 10:00:00".to_string(),
+            base: "BTC".to_string(),
+            quote: "USDT".to_string(),
+            is_buy: false,
+            size: dec!(0.01),
+            funds: dec!(400),
+            fee: dec!(0.0005),
+            fee_currency: "BNB".to_string(),
+        });
+        Ok(())
+    }
+}
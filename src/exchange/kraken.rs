@@ -0,0 +1,175 @@
+use crate::credentials::Credentials;
+use crate::error::{CryptotaxError, Result};
+use crate::exchange::{self, Trade};
+use crate::http;
+use crate::reader::kraken::normalize_asset;
+use crate::transaction::Currency;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use rust_decimal::Decimal;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use sha2::{Digest, Sha256, Sha512};
+use std::collections::HashMap;
+use std::io;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{debug, instrument};
+
+const TRADES_HISTORY: &str = "/0/private/TradesHistory";
+
+/// A response of the API, e.g. `{"error":[],"result":{...}}`.
+#[derive(Debug, Deserialize)]
+struct Response<T> {
+    error: Vec<String>,
+    result: Option<T>,
+}
+
+/// A pair of the exchange from `AssetPairs`, e.g. `XXBTZEUR` of `XXBT` and `ZEUR`.
+#[derive(Debug, Deserialize)]
+struct AssetPair {
+    base: String,
+    quote: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct TradesHistory {
+    trades: HashMap<String, KrakenTrade>,
+    count: usize,
+}
+
+/// A trade of the account, from `TradesHistory`, e.g.
+/// `{"pair":"XXBTZEUR","time":1641117600.1234,"type":"buy","price":"40000.0","cost":"400.0","fee":"0.64","vol":"0.01"}`.
+#[derive(Debug, Deserialize)]
+struct KrakenTrade {
+    pair: String,
+    time: f64,
+    #[serde(rename = "type")]
+    r#type: String,
+    cost: String,       // In the quote currency, without the fee
+    fee: String,        // In the quote currency
+    vol: String,
+}
+
+impl KrakenTrade {
+    fn to_trade(&self, txid: &str, pairs: &HashMap<String, AssetPair>) -> Option<Trade> {
+        let pair = pairs.get(&self.pair)?;
+        let quote = normalize_asset(&pair.quote);
+        Some(Trade{
+            id: txid.to_string(),
+            date: exchange::date_of(self.time as i64)?,
+            base: normalize_asset(&pair.base),
+            quote: quote.clone(),
+            is_buy: self.r#type == "buy",
+            size: Decimal::from_str(&self.vol).ok()?,
+            funds: Decimal::from_str(&self.cost).ok()?,
+            fee: Decimal::from_str(&self.fee).unwrap_or_default(),
+            fee_currency: quote,
+        })
+    }
+}
+
+fn parse<T: DeserializeOwned>(body: &str) -> Result<T> {
+    let response: Response<T> = serde_json::from_str(body)?;
+    match (response.result, response.error.is_empty()) {
+        (Some(result), true) => Ok(result),
+        _ => Err(io::Error::other(response.error.join(", ")).into()),
+    }
+}
+
+/// The key of the signatures, the secret decoded from base64.
+fn signing_key(secret: &str) -> Result<Vec<u8>> {
+    STANDARD.decode(secret).map_err(|e| CryptotaxError::Config(format!("Invalid Kraken API secret: {}", e)))
+}
+
+/// The signature of a request of a private endpoint: the HMAC-SHA512 of the path and the SHA256
+/// of the nonce and the body, see `signing_key`.
+fn sign(path: &str, nonce: u64, body: &str, key: &[u8]) -> String {
+    let digest = Sha256::digest(format!("{}{}", nonce, body));
+    let mut mac = Hmac::<Sha512>::new_from_slice(key).expect("HMAC takes a key of any length");
+    mac.update(path.as_bytes());
+    mac.update(&digest);
+    STANDARD.encode(mac.finalize().into_bytes())
+}
+
+/// Fetches a page of the trades of the account, from the offset `ofs` on, newest first. A retry
+/// is signed with a new nonce, as Kraken refuses a nonce that is not larger than the last one.
+fn fetch_page(api_url: &str, credentials: &Credentials, ofs: usize, offline: bool) -> Result<TradesHistory> {
+    let key = signing_key(&credentials.secret)?;
+    debug!(ofs, "Fetching trades");
+    let response = http::send("kraken", offline, || {
+        let nonce = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or_default();
+        let body = format!("nonce={}&ofs={}", nonce, ofs);
+        let request = http::post(&format!("{}{}", api_url, TRADES_HISTORY))
+            .set("API-Key", &credentials.key)
+            .set("API-Sign", &sign(TRADES_HISTORY, nonce, &body, &key))
+            .set("Content-Type", "application/x-www-form-urlencoded");
+        (request, Some(body))
+    })?;
+    parse(&response)
+}
+
+/// Fetches the trades of the account from the API at `api_url`, e.g. `https://api.kraken.com`,
+/// and keeps the ones of the target currency, as the base or the quote currency.
+#[instrument(name = "sync", skip_all, fields(exchange = "kraken", %currency))]
+pub(crate) fn fetch(currency: &Currency, api_url: &str, credentials: &Credentials, offline: bool) -> Result<Vec<Trade>> {
+    let body = http::fetch("kraken", offline, http::get(&format!("{}/0/public/AssetPairs", api_url)))?;
+    let pairs: HashMap<String, AssetPair> = parse(&body)?;
+    let mut trades = vec![];
+    let mut ofs = 0;
+    loop {
+        let page = fetch_page(api_url, credentials, ofs, offline)?;
+        ofs += page.trades.len();
+        trades.extend(page.trades.iter().filter_map(|(txid, t)| t.to_trade(txid, &pairs)));
+        if page.trades.is_empty() || ofs >= page.count {
+            break;
+        }
+    }
+    Ok(trades.into_iter().filter(|t| t.base.eq(currency) || t.quote.eq(currency)).collect())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::exchange::kraken::*;
+    use rust_decimal_macros::dec;
+    use std::error::Error;
+
+    #[test]
+    fn should_sign_and_read_trades() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let secret = "kQH5HW/8p1uGOVjbgWA7FunAmGO8lsSUXNsu3eow76sz84Q18fWxnyRzBHCd3pd5nE9qa99HAZtuZuj6F1huXg==";
+        let body = "nonce=1616492376594&ordertype=limit&pair=XBTUSD&price=37500&type=buy&volume=1.25";
+        let pairs = r#"{"error":[],"result":{"XXBTZEUR":{"altname":"XBTEUR","base":"XXBT","quote":"ZEUR"}}}"#;
+        let history = r#"{"error":[],"result":{"count":1,"trades":{"TX1":{"ordertxid":"O1","pair":"XXBTZEUR","time":1641117600.1234,"type":"sell","ordertype":"limit","price":"40000.0","cost":"400.0","fee":"0.64","vol":"0.01","margin":"0.0","misc":""}}}}"#;
+
+        /*
+         * When
+         */
+        let signature = sign("/0/private/AddOrder", 1616492376594, body, &signing_key(secret)?);
+        let pairs: HashMap<String, AssetPair> = parse(pairs)?;
+        let history: TradesHistory = parse(history)?;
+        let trade = history.trades["TX1"].to_trade("TX1", &pairs).ok_or("No trade")?;
+        let error = parse::<TradesHistory>(r#"{"error":["EAPI:Invalid key"]}"#);
+
+        /*
+         * Then
+         */
+        assert_eq!(signature, "4/dpxb3iT4tp/ZCVEwSnEsLxx0bqyhLpdfOpc6fn7OR8+UClSV5n9E6aSS8MPtnRfp32bAb0nmbRn6H8ndwLUQ==");
+        assert_eq!(trade, Trade{
+            id: "TX1".to_string(),
+            date: "2022-01-02 10:00:00".to_string(),
+            base: "BTC".to_string(),
+            quote: "EUR".to_string(),
+            is_buy: false,
+            size: dec!(0.01),
+            funds: dec!(400),
+            fee: dec!(0.64),
+            fee_currency: "EUR".to_string(),
+        });
+        assert_eq!(error.map_err(|e| e.to_string()).err(), Some("EAPI:Invalid key".to_string()));
+        Ok(())
+    }
+}
@@ -0,0 +1,55 @@
+use crate::cryptotax::Options;
+use crate::decimal;
+use crate::error::Result;
+use crate::jurisdiction::FeePolicy;
+use rust_decimal::Decimal;
+
+/// How the fees are treated, with `Options::fee_policy` or in the jurisdiction.
+pub(crate) fn policy(options: &Options) -> FeePolicy {
+    options.fee_policy.unwrap_or(options.jurisdiction.fee_policy())
+}
+
+/// The income and the cost (negative) of a disposal with its fees treated by `policy`, from the
+/// ones of the engines, which deduct them: the `income` is net of the `disposal_fee`, and the
+/// `cost` includes the `acquisition_fee`. The fees are negative. Fails if a sum overflows.
+pub(crate) fn apply(policy: FeePolicy, income: Decimal, cost: Decimal, acquisition_fee: Decimal, disposal_fee: Decimal) -> Result<(Decimal, Decimal)> {
+    match policy {
+        FeePolicy::Capitalize => Ok((decimal::sub(income, disposal_fee)?, decimal::add(cost, disposal_fee)?)),
+        FeePolicy::Deduct => Ok((income, cost)),
+        FeePolicy::Separate => Ok((decimal::sub(income, disposal_fee)?, decimal::sub(cost, acquisition_fee)?)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::fees::*;
+    use crate::jurisdiction::Jurisdiction;
+    use rust_decimal_macros::dec;
+    use std::error::Error;
+
+    #[test]
+    fn should_treat_fees_by_policy() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let (income, cost, acquisition_fee, disposal_fee) = (dec!(990), dec!(-505), dec!(-5), dec!(-10));
+        let uk = Options{ jurisdiction: Jurisdiction::UnitedKingdom, ..Default::default() };
+        let separate = Options{ jurisdiction: Jurisdiction::UnitedKingdom, fee_policy: Some(FeePolicy::Separate), ..Default::default() };
+
+        /*
+         * When
+         */
+        let treated = |policy| apply(policy, income, cost, acquisition_fee, disposal_fee);
+
+        /*
+         * Then
+         */
+        assert_eq!(treated(FeePolicy::Capitalize)?, (dec!(1000), dec!(-515)));
+        assert_eq!(treated(FeePolicy::Deduct)?, (dec!(990), dec!(-505)));
+        assert_eq!(treated(FeePolicy::Separate)?, (dec!(1000), dec!(-500)));
+        assert_eq!(policy(&Options::default()), FeePolicy::Deduct);
+        assert_eq!(policy(&uk), FeePolicy::Capitalize);
+        assert_eq!(policy(&separate), FeePolicy::Separate);
+        Ok(())
+    }
+}
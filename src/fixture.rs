@@ -0,0 +1,148 @@
+use crate::error::{CryptotaxError, Result};
+use crate::keywords::Keywords;
+use crate::locale::{self, CsvFormat};
+use crate::reader::DECIMAL_COLUMNS;
+use chrono::{Duration, NaiveDateTime};
+use csv::{ReaderBuilder, StringRecord, Trim, WriterBuilder};
+use rust_decimal::Decimal;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+const DATE_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Rewrites a Revolut account statement so that it can be added to the repository as a test
+/// fixture. Every amount is scaled by the same factor and every date is shifted by the same
+/// number of days, both derived from the seed, so that the same file and seed always give the
+/// same fixture. The descriptions of the exchanges are kept, as the pairing depends on them, and
+/// the others, e.g. the merchants of card payments, are replaced by a hash. The columns, the
+/// delimiter and the decimal separator are kept, so the rows are read and paired as before.
+pub(crate) fn anonymize<W: io::Write>(path: &Path, seed: u64, out: W) -> Result<()> {
+    let content = fs::read_to_string(path)?;
+    let format = CsvFormat::detect(&content);
+    let mut rdr = ReaderBuilder::new()
+        .has_headers(true)
+        .delimiter(format.delimiter)
+        .trim(Trim::All)
+        .from_reader(content.as_bytes());
+    let headers = rdr.headers()?.clone();
+    let columns: Vec<&str> = headers.iter().map(locale::canonical_header).collect();
+    if !["Started Date", "Description", "Amount", "Currency"].iter().all(|c| columns.contains(c)) {
+        let msg = "Only Revolut account statements can be anonymized";
+        return Err(CryptotaxError::Config(msg.to_string()));
+    }
+
+    let keywords = Keywords::shipped();
+    let scale = Decimal::new(1000 + (derive(seed, "scale") % 9000) as i64, 3);
+    let shift = Duration::days(1 + (derive(seed, "shift") % 365) as i64);
+    let mut wtr = WriterBuilder::new().delimiter(format.delimiter).from_writer(out);
+    wtr.write_record(&headers)?;
+    for record in rdr.records() {
+        let record: StringRecord =
+            record?.iter()
+                .zip(columns.iter())
+                .map(|(field, column)| match *column {
+                    c if DECIMAL_COLUMNS.contains(&c) => scaled(field, scale, &format),
+                    "Started Date" | "Completed Date" => shifted(field, shift),
+                    "Description" if locale::is_exchanged_from(field, &keywords) || locale::is_exchanged_to(field, &keywords) => field.to_string(),
+                    "Description" if !field.is_empty() => format!("Anonymized {}", hash(seed, field)),
+                    _ => field.to_string(),
+                })
+                .collect();
+        wtr.write_record(&record)?;
+    }
+    Ok(wtr.flush()?)
+}
+
+/// A number derived from the seed for the given use.
+fn derive(seed: u64, what: &str) -> u64 {
+    let digest = Sha256::digest(format!("{}{}", seed, what));
+    digest.iter().take(8).fold(0, |acc, b| acc << 8 | *b as u64)
+}
+
+fn hash(seed: u64, text: &str) -> String {
+    let digest = Sha256::digest(format!("{}{}", seed, text));
+    digest.iter().take(4).map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Scales a localized decimal, written back with the decimal separator of the file. A field that
+/// is not a number is left as it is.
+fn scaled(field: &str, scale: Decimal, format: &CsvFormat) -> String {
+    match Decimal::from_str(&format.normalize_decimal(field)) {
+        Ok(amount) => (amount * scale).normalize().to_string().replace('.', &format.decimal_separator.to_string()),
+        Err(_) => field.to_string(),
+    }
+}
+
+/// Shifts a date such as `2022-01-31 10:00:00`. A field that is not a date is left as it is.
+fn shifted(field: &str, shift: Duration) -> String {
+    match NaiveDateTime::parse_from_str(field, DATE_FORMAT) {
+        Ok(date) => (date + shift).format(DATE_FORMAT).to_string(),
+        Err(_) => field.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cryptotax::Options;
+    use crate::fixture::*;
+    use crate::reader;
+    use futures::executor::block_on;
+    use std::error::Error;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn should_anonymize_a_statement_into_the_same_transactions() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let mut file = NamedTempFile::new()?;
+        writeln!(file, "Type;Started Date;Completed Date;Description;Amount;Fee;Currency;Original Amount;Original Currency;Settled Amount;Settled Currency;State;Balance
+                        Exchange;2022-03-01 16:21:49;2022-03-01 16:21:49;Exchanged to SEK;-1 000,5;0;BTC;-1 000,5;BTC;;;Completed;0
+                        Card Payment;2022-03-01 16:22:00;2022-03-02 10:00:00;Klarna;-0,5;0;BTC;-500;SEK;500;SEK;Completed;0
+                        Exchange;2022-03-01 16:22:49;2022-03-01 16:22:49;Exchanged from BTC;900;0;SEK;900;SEK;;;Completed;900
+                        Exchange;2021-12-31 17:54:48;2021-12-31 17:54:48;Växlat till BTC;-5000,45;-80,15;SEK;-5000,45;SEK;;;Completed;700,27
+                        Exchange;2021-12-31 17:54:48;2021-12-31 17:54:48;Växlat från SEK;1001;0;BTC;1001;BTC;;;Completed;1001")?;
+        let path = file.path().to_path_buf();
+
+        /*
+         * When
+         */
+        let mut fixture = NamedTempFile::new()?;
+        anonymize(&path, 42, &mut fixture)?;
+        let mut again = vec![];
+        anonymize(&path, 42, &mut again)?;
+
+        /*
+         * Then
+         */
+        let content = fs::read_to_string(fixture.path())?;
+        assert_eq!(content.as_bytes(), again.as_slice());
+        assert!(!content.contains("Klarna"));
+        assert!(content.contains(";Växlat till BTC;"));
+
+        let original = block_on(reader::read_transactions(&path, &"BTC".to_string(), None, &Options::default()))?;
+        let anonymized = block_on(reader::read_transactions(fixture.path(), &"BTC".to_string(), None, &Options::default()))?;
+        let scale = anonymized[0].paid_amount / original[0].paid_amount;
+        let days = |a: &str, b: &str| -> Result<i64, Box<dyn Error>> {
+            let (a, b) = (NaiveDateTime::parse_from_str(a, DATE_FORMAT)?, NaiveDateTime::parse_from_str(b, DATE_FORMAT)?);
+            Ok((b - a).num_days())
+        };
+        let shift = days(&original[0].date, &anonymized[0].date)?;
+        assert_eq!(original.len(), 3);
+        assert_eq!(anonymized.len(), original.len());
+        assert!(shift > 0);
+        for (o, a) in original.iter().zip(anonymized.iter()) {
+            assert_eq!(a.r#type, o.r#type);
+            assert_eq!(a.exchanged_currency, o.exchanged_currency);
+            assert_eq!(a.paid_amount, o.paid_amount * scale);
+            assert_eq!(a.exchanged_amount, o.exchanged_amount * scale);
+            assert_eq!(days(&o.date, &a.date)?, shift);
+            assert_eq!(a.origin.lines, o.origin.lines);
+        }
+        Ok(())
+    }
+}
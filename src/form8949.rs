@@ -0,0 +1,213 @@
+use crate::cryptotax::Options;
+use crate::decimal;
+use crate::error::Result;
+use crate::fees;
+use crate::jurisdiction::FeePolicy;
+use crate::lots::{Lot, LotBook};
+use crate::rounding::Rounding;
+use crate::term::{self, Term};
+use crate::transaction::{Currency, Money, Transaction, TransactionType};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde::Serialize;
+use tracing::instrument;
+
+/// A line of the IRS Form 8949, one per disposed lot. The columns follow the form, so that the
+/// csv can be imported into common tax software.
+#[derive(Debug, PartialEq, Serialize)]
+pub(crate) struct Form8949Row {
+    #[serde(rename = "Description")]
+    description: String,            // (a) e.g. "0.5 BTC"
+
+    #[serde(rename = "Date Acquired")]
+    date_acquired: String,          // (b) MM/DD/YYYY
+
+    #[serde(rename = "Date Sold")]
+    date_sold: String,              // (c)
+
+    #[serde(rename = "Proceeds")]
+    proceeds: Option<Decimal>,      // (d) None if not sold to the base currency
+
+    #[serde(rename = "Cost Basis")]
+    cost_basis: Option<Decimal>,    // (e) None if not bought with the base currency
+
+    #[serde(rename = "Gain or Loss")]
+    gain: Option<Decimal>,          // (h)
+
+    #[serde(rename = "Term")]
+    term: Term,                     // Part I (short-term) or Part II (long-term) of the form
+}
+
+impl Form8949Row {
+    /// Rounds the proceeds and the cost basis for a report, recalculating the gain or loss from
+    /// the rounded amounts.
+    pub(crate) fn round(&mut self, rounding: &Rounding) {
+        self.proceeds = self.proceeds.map(|p| rounding.gain(p));
+        self.cost_basis = self.cost_basis.map(|c| rounding.cost(c));
+        self.gain = self.proceeds.zip(self.cost_basis).map(|(p, c)| p - c);
+    }
+
+    /// Sets the cost basis to the proceeds, for a disposal whose gain is negligible, see
+    /// `stablecoin::is_negligible`.
+    pub(crate) fn without_gain(&mut self) {
+        if let Some(proceeds) = self.proceeds {
+            self.cost_basis = Some(proceeds);
+            self.gain = Some(dec!(0));
+        }
+    }
+}
+
+/// Runs the transactions through a `LotBook`, first in first out, and returns a row for every
+/// lot of a taxable disposal. The short-term rows come before the long-term rows.
+#[instrument(name = "calculate", skip_all, fields(matching = "fifo", currency = %currency))]
+pub(crate) async fn report(
+    txns: &[Transaction],
+    currency: &Currency,
+    base: &Currency,
+    options: &Options,
+) -> Result<Vec<Form8949Row>> {
+    let jurisdiction = &options.jurisdiction;
+    let policy = fees::policy(options);
+    let mut book = LotBook::new(base.clone());
+    let mut rows = vec![];
+    for t in txns {
+        match t.r#type {
+            TransactionType::Buy | TransactionType::Income => book.add_buy(t),
+            TransactionType::AddLiquidity | TransactionType::RemoveLiquidity if t.paid_amount.is_sign_positive() => book.add_buy(t),
+            TransactionType::Transfer | TransactionType::Margin | TransactionType::Futures | TransactionType::Short => {},
+            TransactionType::Lost | TransactionType::Stolen
+                if jurisdiction.recognizes_gain(&t.r#type) || options.claim_write_offs => {
+                let disposed = book.dispose(t.paid_amount)?;
+                rows.extend(to_rows(&disposed, Some(dec!(0)), dec!(0), currency, &t.date, policy)?);
+            },
+            _ if jurisdiction.recognizes_gain(&t.r#type) => {
+                let disposed = book.dispose(t.paid_amount)?;
+                let (proceeds, fee) = match t.to_money(base) {
+                    Money::Cash(cash) => (Some(cash.amount), t.exchanged_fee),
+                    Money::Coupon(_) => (None, dec!(0)),
+                };
+                rows.extend(to_rows(&disposed, proceeds, fee, currency, &t.date, policy)?);
+            },
+            _ => { book.dispose(t.paid_amount)?; },
+        }
+    }
+    rows.sort_by_key(|r| r.term);
+    Ok(rows)
+}
+
+/// Splits the proceeds and the fee (negative) of a disposal over the disposed lots in proportion
+/// to their amounts, with the fees treated by `policy`, see `fees::apply`.
+fn to_rows(disposed: &[Lot], proceeds: Option<Decimal>, fee: Decimal, currency: &Currency, date: &str, policy: FeePolicy) -> Result<Vec<Form8949Row>> {
+    let total = disposed.iter().fold(dec!(0), |acc, l| acc + l.amount);
+    let (mut remaining, mut remaining_fee) = (proceeds, fee);
+    disposed.iter()
+        .enumerate()
+        .map(|(i, lot)| {
+            let (share, fee_share) = match i == disposed.len() - 1 {
+                true => (remaining, remaining_fee),
+                false => (
+                    proceeds.map(|p| decimal::share(p, lot.amount, total)).transpose()?,
+                    decimal::share(fee, lot.amount, total)?,
+                ),
+            };
+            remaining = remaining.zip(share).map(|(r, s)| r - s);
+            remaining_fee -= fee_share;
+            let cost = match &lot.cost {
+                Money::Cash(cash) => Some(cash.amount),
+                Money::Coupon(_) => None,
+            };
+            let (share, cost_basis) = match (share, cost) {
+                (Some(p), Some(c)) => {
+                    let (p, c) = fees::apply(policy, p, c, lot.fee, fee_share)?;
+                    (Some(p), Some(-c))
+                }
+                (p, c) => (p, c.map(|c| -c)),
+            };
+            Ok(Form8949Row{
+                description: format!("{} {}", lot.amount, currency),
+                date_acquired: to_us_date(&lot.date),
+                date_sold: to_us_date(date),
+                proceeds: share,
+                cost_basis,
+                gain: share.zip(cost_basis).map(|(p, c)| p - c),
+                term: term::ONE_YEAR.term_of(&lot.date, date),
+            })
+        })
+        .collect()
+}
+
+/// Converts `YYYY-MM-DD ...` to `MM/DD/YYYY`.
+fn to_us_date(date: &str) -> String {
+    match (date.get(..4), date.get(5..7), date.get(8..10)) {
+        (Some(year), Some(month), Some(day)) => format!("{}/{}/{}", month, day, year),
+        _ => date.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cryptotax::{Jurisdiction, Options};
+    use crate::form8949::*;
+    use futures::executor::block_on;
+    use rust_decimal_macros::dec;
+    use std::error::Error;
+
+    #[test]
+    fn should_split_short_and_long_term() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let txn = |r#type, paid_amount, exchanged_amount, date: &str| Transaction{
+            r#type,
+            paid_currency: "BTC".to_string(),
+            paid_amount,
+            exchanged_currency: "USD".to_string(),
+            exchanged_amount,
+            date: date.to_string(),
+            is_vault: false,
+            paid_fee: dec!(0),
+            exchanged_fee: dec!(0),
+            price_source: None,
+            unit_price: None,
+            fiat_value: None,
+            other_fee: None,
+            origin: Default::default()
+        };
+        let txns = vec![
+            txn(TransactionType::Buy, dec!(1), dec!(-10000), "2020-01-15 10:00:00"),
+            txn(TransactionType::Buy, dec!(1), dec!(-30000), "2021-01-15 10:00:00"),
+            txn(TransactionType::Sell, dec!(-1.5), dec!(60000), "2021-06-01 10:00:00"),
+        ];
+        let options = Options{ jurisdiction: Jurisdiction::UnitedStates, ..Default::default() };
+
+        /*
+         * When
+         */
+        let rows = block_on(report(&txns, &"BTC".to_string(), &"USD".to_string(), &options))?;
+
+        /*
+         * Then
+         */
+        assert_eq!(rows, vec![
+            Form8949Row{
+                description: "0.5 BTC".to_string(),
+                date_acquired: "01/15/2021".to_string(),
+                date_sold: "06/01/2021".to_string(),
+                proceeds: Some(dec!(20000)),
+                cost_basis: Some(dec!(15000)),
+                gain: Some(dec!(5000)),
+                term: Term::Short,
+            },
+            Form8949Row{
+                description: "1 BTC".to_string(),
+                date_acquired: "01/15/2020".to_string(),
+                date_sold: "06/01/2021".to_string(),
+                proceeds: Some(dec!(40000)),
+                cost_basis: Some(dec!(10000)),
+                gain: Some(dec!(30000)),
+                term: Term::Long,
+            },
+        ]);
+        Ok(())
+    }
+}
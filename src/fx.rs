@@ -0,0 +1,297 @@
+#[cfg(feature = "native")]
+use crate::decimal;
+#[cfg(feature = "native")]
+use crate::error::{CryptotaxError, Result};
+#[cfg(feature = "native")]
+use crate::http;
+#[cfg(feature = "native")]
+use crate::store::Store;
+#[cfg(feature = "native")]
+use crate::transaction::{self, Currency, Transaction};
+#[cfg(feature = "native")]
+use chrono::{Duration, NaiveDate};
+#[cfg(feature = "native")]
+use rust_decimal::Decimal;
+#[cfg(feature = "native")]
+use rust_decimal_macros::dec;
+#[cfg(feature = "native")]
+use serde::Deserialize;
+#[cfg(feature = "native")]
+use std::collections::HashSet;
+use std::fmt;
+use std::str::FromStr;
+#[cfg(feature = "native")]
+use std::time::{SystemTime, UNIX_EPOCH};
+#[cfg(feature = "native")]
+use tracing::{debug, instrument};
+
+/// A publisher of official daily reference rates of fiat currencies. The rates are only fetched
+/// and converted with the `native` feature, which has the store to cache them in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FxSource {
+    /// The euro foreign exchange reference rates of the European Central Bank, as units of the
+    /// currency per EUR.
+    Ecb,
+    /// The daily rates of Sveriges Riksbank, as SEK per unit of the currency.
+    Riksbank,
+}
+
+/// Currencies that the Riksbank quotes per 100 units.
+#[cfg(feature = "native")]
+const PER_HUNDRED: [&str; 4] = ["HUF", "IDR", "JPY", "KRW"];
+
+#[cfg(feature = "native")]
+impl FxSource {
+    /// The currency that the rates are published against.
+    fn quote(&self) -> &str {
+        match self {
+            FxSource::Ecb => "EUR",
+            FxSource::Riksbank => "SEK",
+        }
+    }
+
+    fn url(&self, currency: &str, from: &str, to: &str) -> String {
+        match self {
+            FxSource::Ecb => format!(
+                "https://data-api.ecb.europa.eu/service/data/EXR/D.{}.EUR.SP00.A?startPeriod={}&endPeriod={}&format=csvdata",
+                currency, from, to
+            ),
+            FxSource::Riksbank => format!(
+                "https://api.riksbank.se/swea/v1/Observations/SEK{}PMI/{}/{}",
+                currency, from, to
+            ),
+        }
+    }
+
+    /// Parses a response from `url` into the rates per date, per one unit of the currency.
+    fn parse(&self, currency: &str, body: &str) -> Result<Vec<(String, Decimal)>> {
+        match self {
+            FxSource::Ecb => {
+                let mut rdr = csv::Reader::from_reader(body.as_bytes());
+                let mut rates = vec![];
+                for result in rdr.deserialize() {
+                    let observation: EcbObservation = result?;
+                    if let Some(value) = observation.value {
+                        rates.push((observation.date, value));
+                    }
+                }
+                Ok(rates)
+            }
+            FxSource::Riksbank => {
+                let observations: Vec<RiksbankObservation> = serde_json::from_str(body)?;
+                let unit = match PER_HUNDRED.contains(&currency) { true => dec!(100), false => dec!(1) };
+                Ok(observations.into_iter().map(|o| (o.date, o.value / unit)).collect())
+            }
+        }
+    }
+}
+
+impl FromStr for FxSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_uppercase().as_str() {
+            "ECB" => Ok(FxSource::Ecb),
+            "RIKSBANK" | "RIKSBANKEN" => Ok(FxSource::Riksbank),
+            _ => Err(format!("Unknown FX rate source `{}`. Supported: ECB, RIKSBANK", s)),
+        }
+    }
+}
+
+impl fmt::Display for FxSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FxSource::Ecb => write!(f, "ECB"),
+            FxSource::Riksbank => write!(f, "RIKSBANK"),
+        }
+    }
+}
+
+#[cfg(feature = "native")]
+#[derive(Debug, Deserialize)]
+struct EcbObservation {
+    #[serde(rename = "TIME_PERIOD")]
+    date: String,
+
+    #[serde(rename = "OBS_VALUE")]
+    value: Option<Decimal>,
+}
+
+#[cfg(feature = "native")]
+#[derive(Debug, Deserialize)]
+struct RiksbankObservation {
+    date: String,
+    value: Decimal,
+}
+
+/// The daily reference rates of a source. Fetched rates are cached in the store, so that every
+/// year of a currency is fetched once.
+#[cfg(feature = "native")]
+pub(crate) struct FxRates {
+    source: FxSource,
+    store: Store,
+    fetched: HashSet<(Currency, String)>,   // The currencies and years fetched by this instance
+    offline: bool,                          // Only the cached rates, nothing is fetched
+}
+
+#[cfg(feature = "native")]
+impl FxRates {
+    pub(crate) fn new(source: FxSource, store: Store, offline: bool) -> FxRates {
+        FxRates{ source, store, fetched: HashSet::new(), offline }
+    }
+
+    /// Returns the price of one unit of `currency` in `base` on the date, from the latest rates
+    /// published on or before it.
+    pub(crate) fn price(&mut self, currency: &Currency, base: &Currency, date: &str) -> Result<Decimal> {
+        let rate = self.rate(currency, date)?;
+        let base_rate = self.rate(base, date)?;
+        match self.source {
+            FxSource::Ecb => Ok(base_rate / rate),
+            FxSource::Riksbank => Ok(rate / base_rate),
+        }
+    }
+
+    fn rate(&mut self, currency: &Currency, date: &str) -> Result<Decimal> {
+        if currency == self.source.quote() {
+            return Ok(dec!(1));
+        }
+        let source = self.source.to_string();
+        let day = date.get(..10).unwrap_or_default();
+        // A later rate in the cache tells that there is no gap in the cached rates around the date.
+        // Offline, the latest cached rate is the best there is.
+        if let Some(rate) = self.store.fx_rate(&source, currency, day)? {
+            if self.offline || self.store.has_fx_rate_after(&source, currency, day)? {
+                return Ok(rate);
+            }
+        }
+        let year = day.get(..4).unwrap_or_default().to_string();
+        if !self.offline && self.fetched.insert((currency.clone(), year.clone())) {
+            let rates = self.fetch(currency, &year)?;
+            self.store.save_fx_rates(&source, currency, &rates)?;
+        }
+        self.store.fx_rate(&source, currency, day)?.ok_or_else(|| {
+            let msg = format!("No {} rate of `{}` on {}", source, currency, day);
+            CryptotaxError::Pricing(msg)
+        })
+    }
+
+    /// Fetches the rates of the year, and of the last days of the year before, in case the year
+    /// starts on a holiday.
+    fn fetch(&self, currency: &str, year: &str) -> Result<Vec<(String, Decimal)>> {
+        let start: i32 = year.parse().map_err(|e| CryptotaxError::Pricing(format!("No {} rates of the year `{}`: {}", self.source, year, e)))?;
+        let from = format!("{}-12-20", start - 1);
+        let to = format!("{}-12-31", year).min(today());
+        let url = self.source.url(currency, &from, &to);
+        debug!(%url, "Fetching rates");
+        let body = http::fetch(&self.source.to_string(), self.offline, http::get(&url))?;
+        self.source.parse(currency, &body)
+    }
+}
+
+#[cfg(feature = "native")]
+fn today() -> String {
+    let days = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() / 86400).unwrap_or_default();
+    let epoch = NaiveDate::from_ymd_opt(1970, 1, 1).unwrap_or_default();
+    (epoch + Duration::days(days as i64)).format("%Y-%m-%d").to_string()
+}
+
+/// Converts the exchanged amounts in other fiat currencies than `base` to `base`, at the price on
+/// the date of each transaction. Fails with every missing rate, rather than the first, so that
+/// they can all be fetched or cached at once.
+#[cfg(feature = "native")]
+#[instrument(name = "price", skip_all, fields(source = %rates.source))]
+pub(crate) fn convert(txns: &mut [Transaction], base: &Currency, rates: &mut FxRates) -> Result<()> {
+    let mut missing: Vec<String> = vec![];
+    for t in txns.iter_mut().filter(|t| !t.exchanged_currency.eq(base) && transaction::is_fiat(&t.exchanged_currency)) {
+        let price = match rates.price(&t.exchanged_currency, base, &t.date) {
+            Ok(price) => price,
+            Err(CryptotaxError::Pricing(msg)) => {
+                if !missing.contains(&msg) {
+                    missing.push(msg);
+                }
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        debug!(date = %t.date, amount = %t.exchanged_amount, currency = %t.exchanged_currency, %base, %price, "Converted");
+        t.exchanged_amount = decimal::mul(t.exchanged_amount, price)?;
+        t.exchanged_fee = decimal::mul(t.exchanged_fee, price)?;
+        t.exchanged_currency = base.clone();
+    }
+    if !missing.is_empty() {
+        let hint = match rates.offline {
+            true => "Fetch the rates once without --offline, which caches them in the store",
+            false => "The source has no rates of these dates",
+        };
+        let msg = format!("{}\n{}", missing.join("\n"), hint);
+        return Err(CryptotaxError::Pricing(msg));
+    }
+    Ok(())
+}
+
+#[cfg(all(test, feature = "native"))]
+mod test {
+    use crate::cryptotax::StoreAccess;
+    use crate::fx::*;
+    use crate::transaction::TransactionType;
+    use std::error::Error;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn should_parse_rates() -> Result<(), Box<dyn Error>> {
+        let ecb = "KEY,FREQ,CURRENCY,CURRENCY_DENOM,EXR_TYPE,EXR_SUFFIX,TIME_PERIOD,OBS_VALUE,OBS_STATUS\n\
+                   EXR.D.SEK.EUR.SP00.A,D,SEK,EUR,SP00,A,2022-01-03,10.2335,A\n\
+                   EXR.D.SEK.EUR.SP00.A,D,SEK,EUR,SP00,A,2022-01-04,,M\n";
+        assert_eq!(FxSource::Ecb.parse("SEK", ecb)?, vec![("2022-01-03".to_string(), dec!(10.2335))]);
+
+        let riksbank = r#"[{"date":"2022-01-03","value":7.8435},{"date":"2022-01-04","value":7.8156}]"#;
+        assert_eq!(FxSource::Riksbank.parse("JPY", riksbank)?, vec![
+            ("2022-01-03".to_string(), dec!(0.078435)),
+            ("2022-01-04".to_string(), dec!(0.078156)),
+        ]);
+        Ok(())
+    }
+
+    #[test]
+    fn should_convert_with_cached_rates() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let file = NamedTempFile::new()?;
+        let mut store = Store::open(file.path(), &StoreAccess::default())?;
+        store.save_fx_rates("ECB", &"SEK".to_string(), &[
+            ("2021-12-31".to_string(), dec!(10.25)),
+            ("2022-01-03".to_string(), dec!(10.2)),
+        ])?;
+        let mut rates = FxRates::new(FxSource::Ecb, store, false);
+        let mut txns = vec![Transaction{
+            r#type: TransactionType::Buy,
+            paid_currency: "BTC".to_string(),
+            paid_amount: dec!(0.1),
+            exchanged_currency: "EUR".to_string(),
+            exchanged_amount: dec!(-4000),
+            date: "2022-01-01 10:00:00".to_string(),  // Holiday, the rate of the day before applies
+            is_vault: false,
+            paid_fee: dec!(0),
+            exchanged_fee: dec!(-20),
+            price_source: None,
+            unit_price: None,
+            fiat_value: None,
+            other_fee: None,
+            origin: Default::default()
+        }];
+
+        /*
+         * When
+         */
+        convert(&mut txns, &"SEK".to_string(), &mut rates)?;
+
+        /*
+         * Then
+         */
+        assert_eq!(txns[0].exchanged_currency, "SEK");
+        assert_eq!(txns[0].exchanged_amount, dec!(-41000));
+        assert_eq!(txns[0].exchanged_fee, dec!(-205));
+        Ok(())
+    }
+}
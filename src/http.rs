@@ -0,0 +1,221 @@
+use crate::error::{CryptotaxError, Result};
+use std::collections::HashMap;
+use std::io;
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::{debug, warn};
+
+/// How often a request that failed on the way or with a status that may pass is tried again.
+const RETRIES: u32 = 4;
+
+/// The wait before the first retry, doubled for every retry after it.
+const BACKOFF: Duration = Duration::from_secs(1);
+
+/// The longest wait before a retry, also if the server asks for a longer one with `Retry-After`.
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// The shortest time between two requests to a provider, within the free limits of its API.
+/// Requests to other hosts, e.g. an explorer given with `--api-url`, are not limited.
+const LIMITS: [(&str, Duration); 8] = [
+    ("binance", Duration::from_millis(1000)),   // 1200 weight a minute, 20 of it per `myTrades`
+    ("coingecko", Duration::from_millis(2000)), // 30 calls a minute with a demo key
+    ("cryptocompare", Duration::from_millis(200)),
+    ("ecb", Duration::from_millis(500)),
+    ("esplora", Duration::from_millis(200)),
+    ("etherscan", Duration::from_millis(250)),  // 5 calls a second
+    ("kraken", Duration::from_millis(1000)),
+    ("riksbank", Duration::from_millis(1000)),
+];
+
+/// When the next request to each provider may be sent.
+static SLOTS: OnceLock<Mutex<HashMap<String, Instant>>> = OnceLock::new();
+
+/// The bodies of the GET requests of this run, by URL.
+static CACHE: OnceLock<Mutex<HashMap<String, String>>> = OnceLock::new();
+
+/// The agent that every request is sent with, so that connections are kept alive and a server
+/// that stops answering fails the request rather than hanging the run.
+fn agent() -> &'static ureq::Agent {
+    static AGENT: OnceLock<ureq::Agent> = OnceLock::new();
+    AGENT.get_or_init(|| {
+        ureq::AgentBuilder::new()
+            .timeout_connect(Duration::from_secs(10))
+            .timeout_read(Duration::from_secs(60))
+            .build()
+    })
+}
+
+/// A GET request with the shared agent, to send with `fetch` or `send`.
+pub(crate) fn get(url: &str) -> ureq::Request {
+    agent().get(url)
+}
+
+/// A POST request with the shared agent, to send with `send`.
+pub(crate) fn post(url: &str) -> ureq::Request {
+    agent().post(url)
+}
+
+/// Sends a GET request to the API of a provider, e.g. `coingecko`, and returns the body. The
+/// same URL is only fetched once per run. See `send`.
+pub(crate) fn fetch(provider: &str, offline: bool, request: ureq::Request) -> Result<String> {
+    let url = request.url().to_string();
+    if let Some(body) = CACHE.get_or_init(Default::default).lock().unwrap_or_else(|e| e.into_inner()).get(&url) {
+        debug!(%provider, %url, "Cached");
+        return Ok(body.clone());
+    }
+    let body = send(provider, offline, || (request.clone(), None))?;
+    CACHE.get_or_init(Default::default).lock().unwrap_or_else(|e| e.into_inner()).insert(url, body.clone());
+    Ok(body)
+}
+
+/// Sends a request to the API of a provider within its rate limit, see `LIMITS`, and returns the
+/// body. The request is sent again with exponential backoff if it fails on the way, is rate
+/// limited or fails on the server. `request` builds the request and its body, if any, for every
+/// attempt, so that a signed request can be signed anew. If `offline`, the request fails with
+/// `CryptotaxError::Config` instead of being sent, so that nothing about the transactions, e.g.
+/// their dates, is sent to another party.
+pub(crate) fn send<F>(provider: &str, offline: bool, mut request: F) -> Result<String>
+    where F: FnMut() -> (ureq::Request, Option<String>)
+{
+    let mut retry = 0;
+    loop {
+        let (request, body) = request();
+        if offline {
+            let msg = format!("Not fetching `{}` from {}, as --offline forbids network access", request.url(), provider);
+            return Err(CryptotaxError::Config(msg));
+        }
+        wait_for_slot(provider);
+        let response = match (request, body) {
+            (request, Some(body)) => request.send_string(&body),
+            (request, None) => request.call(),
+        };
+        match response {
+            Ok(response) => return Ok(response.into_string()?),
+            Err(e) if retry < RETRIES && is_transient(&e) => {
+                let retry_after = match &e {
+                    ureq::Error::Status(_, response) => response.header("Retry-After").map(str::to_string),
+                    ureq::Error::Transport(_) => None,
+                };
+                let wait = backoff(retry, retry_after.as_deref());
+                retry += 1;
+                warn!(%provider, error = %e, retry, wait = ?wait, "Request failed, trying again");
+                thread::sleep(wait);
+            }
+            Err(e) => return Err(io::Error::other(e).into()),
+        }
+    }
+}
+
+/// Whether a failed request may pass if it is sent again: a request that failed on the way, e.g.
+/// with a timeout, or that was rate limited or failed on the server.
+fn is_transient(e: &ureq::Error) -> bool {
+    match e {
+        ureq::Error::Status(status, _) => *status == 429 || *status >= 500,
+        ureq::Error::Transport(_) => true,
+    }
+}
+
+/// The wait before a retry: the seconds of `Retry-After` if the server gives them, or else
+/// `BACKOFF` doubled for every retry before it.
+fn backoff(retry: u32, retry_after: Option<&str>) -> Duration {
+    retry_after
+        .and_then(|s| s.trim().parse::<u64>().ok())
+        .map(Duration::from_secs)
+        .unwrap_or_else(|| BACKOFF.saturating_mul(2u32.saturating_pow(retry)))
+        .min(MAX_BACKOFF)
+}
+
+/// Takes the next slot of the provider, and sleeps until it comes.
+fn wait_for_slot(provider: &str) {
+    let Some((_, interval)) = LIMITS.iter().find(|(name, _)| name.eq_ignore_ascii_case(provider)) else {
+        return;
+    };
+    let now = Instant::now();
+    let slot = {
+        let mut slots = SLOTS.get_or_init(Default::default).lock().unwrap_or_else(|e| e.into_inner());
+        let slot = next_slot(slots.get(provider).copied(), now);
+        slots.insert(provider.to_string(), slot + *interval);
+        slot
+    };
+    if slot > now {
+        thread::sleep(slot - now);
+    }
+}
+
+/// The slot of the next request: now, unless the slot of the provider is still to come.
+fn next_slot(slot: Option<Instant>, now: Instant) -> Instant {
+    slot.map_or(now, |slot| slot.max(now))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::http::*;
+    use std::error::Error;
+
+    #[test]
+    fn should_back_off_within_the_limits() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let now = Instant::now();
+        let later = now + Duration::from_millis(500);
+
+        /*
+         * When
+         */
+        let waits: Vec<Duration> = (0..4).map(|retry| backoff(retry, None)).collect();
+
+        /*
+         * Then
+         */
+        assert_eq!(waits, vec![Duration::from_secs(1), Duration::from_secs(2), Duration::from_secs(4), Duration::from_secs(8)]);
+        assert_eq!(backoff(20, None), MAX_BACKOFF);
+        assert_eq!(backoff(0, Some("5")), Duration::from_secs(5));
+        assert_eq!(backoff(0, Some("3600")), MAX_BACKOFF);
+        assert_eq!(backoff(1, Some("Wed, 21 Oct 2015 07:28:00 GMT")), Duration::from_secs(2));
+        assert_eq!(next_slot(None, now), now);
+        assert_eq!(next_slot(Some(later), now), later);
+        assert_eq!(next_slot(Some(now), later), later);
+        Ok(())
+    }
+
+    #[test]
+    fn should_fetch_a_url_once() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let url = "http://127.0.0.1:9/cached";
+        CACHE.get_or_init(Default::default).lock().unwrap().insert(url.to_string(), "{}".to_string());
+
+        /*
+         * When
+         */
+        let body = fetch("test", false, get(url))?;
+
+        /*
+         * Then
+         */
+        assert_eq!(body, "{}");
+        Ok(())
+    }
+
+    #[test]
+    fn should_not_fetch_offline() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let url = "http://127.0.0.1:9/offline";
+
+        /*
+         * When
+         */
+        let result = fetch("test", true, get(url));
+
+        /*
+         * Then
+         */
+        assert!(result.is_err_and(|e| e.to_string().contains("as --offline forbids network access")));
+        Ok(())
+    }
+}
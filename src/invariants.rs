@@ -0,0 +1,109 @@
+use crate::calculator::{Calculation, TaxableTransaction};
+use crate::error::{CryptotaxError, Result};
+use crate::transaction::{Money, Transaction, TransactionType};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+
+fn violated(msg: String) -> CryptotaxError {
+    CryptotaxError::Pairing(format!("Invariant violated: {}", msg))
+}
+
+/// Checks that the pairs of exchange rows and the unmatched rows, see `reader::pair_exchanges`,
+/// use every one of the `rows` exactly once.
+pub(crate) fn check_pairing(rows: usize, pairs: &[(usize, usize)], unmatched: &[usize]) -> Result<()> {
+    let mut uses = vec![0; rows];
+    for i in pairs.iter().flat_map(|(a, b)| [*a, *b]).chain(unmatched.iter().copied()) {
+        match uses.get_mut(i) {
+            Some(count) => *count += 1,
+            None => return Err(violated(format!("exchange row {} of {} was paired", i, rows))),
+        }
+    }
+    match uses.iter().position(|count| *count != 1) {
+        Some(i) => Err(violated(format!("exchange row {} was paired {} times", i, uses[i]))),
+        None => Ok(()),
+    }
+}
+
+/// Checks that the net income of every disposal valued in the base currency is its income less
+/// its costs.
+pub(crate) fn check_gains(taxables: &[TaxableTransaction]) -> Result<()> {
+    for t in taxables {
+        let expected = t.income.to_net_income(&t.costs)?;
+        if t.net_income.is_some() && t.net_income != expected {
+            let msg = format!("net income {:?} of {} at {} is not the income less the costs, {:?}", t.net_income, t.currency, t.date, expected);
+            return Err(violated(msg));
+        }
+    }
+    Ok(())
+}
+
+/// Checks the gains of the calculation, see `check_gains`, and that the costs left in the book
+/// add up to the amount held after `txns`, none of them with a negative cost.
+pub(crate) fn check_calculation(txns: &[Transaction], calculation: &Calculation) -> Result<()> {
+    check_gains(&calculation.taxables)?;
+    check_gains(&calculation.exempt)?;
+    let held: Decimal =
+        txns.iter()
+            .filter(|t| !matches!(t.r#type, TransactionType::Transfer | TransactionType::Margin | TransactionType::Futures | TransactionType::Short))
+            .map(|t| t.paid_amount)
+            .sum();
+    let holding = &calculation.holding;
+    if holding.amount != held {
+        let msg = format!("the costs of {} add up to {}, but {} is held", holding.currency, holding.amount, held);
+        return Err(violated(msg));
+    }
+    if let Some(cost) = holding.costs.iter().find(|c| c.amount() > dec!(0)) {
+        let currency = match cost { Money::Cash(cash) => &cash.currency, Money::Coupon(coupon) => &coupon.currency };
+        let msg = format!("{} is held at a negative cost of {} {}", holding.currency, -cost.amount(), currency);
+        return Err(violated(msg));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::calculator;
+    use crate::cryptotax::Options;
+    use crate::invariants::*;
+    use futures::executor::block_on;
+    use std::error::Error;
+
+    #[test]
+    fn should_find_broken_invariants() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let txn = |r#type: TransactionType, amount: Decimal, exchanged: Decimal| {
+            let mut txn = Transaction::new();
+            txn.r#type = r#type;
+            txn.paid_currency = "BTC".to_string();
+            txn.paid_amount = amount;
+            txn.exchanged_currency = "SEK".to_string();
+            txn.exchanged_amount = exchanged;
+            txn.date = "2022-01-01 10:00:00".to_string();
+            txn
+        };
+        let txns = vec![
+            txn(TransactionType::Buy, dec!(2), dec!(-1000)),
+            txn(TransactionType::Sell, dec!(-1), dec!(700)),
+        ];
+        let calculation = block_on(calculator::calculate(&txns, &"BTC".to_string(), &"SEK".to_string(), &Options::default()))?;
+
+        /*
+         * When
+         */
+        let mut broken = block_on(calculator::calculate(&txns, &"BTC".to_string(), &"SEK".to_string(), &Options::default()))?;
+        broken.taxables[0].net_income = Some(dec!(300));
+
+        /*
+         * Then
+         */
+        assert!(check_calculation(&txns, &calculation).is_ok());
+        assert!(check_calculation(&txns[..1], &calculation).is_err());     // Another holding
+        assert!(check_calculation(&txns, &broken).is_err());
+        assert!(check_pairing(4, &[(3, 1), (2, 0)], &[]).is_ok());
+        assert!(check_pairing(4, &[(3, 1), (2, 1)], &[0]).is_err());
+        assert!(check_pairing(3, &[(3, 1)], &[0, 2]).is_err());
+        Ok(())
+    }
+}
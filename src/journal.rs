@@ -0,0 +1,255 @@
+use crate::calculator::Calculation;
+use crate::error::{self, Result};
+use crate::transaction::{Currency, Transaction, TransactionType};
+use csv::WriterBuilder;
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// The ledger accounts that the journal entries are booked to, accounts of the Swedish BAS chart
+/// by default. A TOML file gives other accounts, e.g. of another chart, with any of the names:
+///
+/// ```toml
+/// cash = "1920"
+/// holdings = "1820"
+/// ```
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct Ledger {
+    /// The bank account that pays for the buys and receives the proceeds.
+    cash: String,
+    /// The crypto currencies held, at their cost.
+    holdings: String,
+    gains: String,
+    losses: String,
+    /// Staking rewards, interest and other income received in the crypto currency.
+    income: String,
+}
+
+impl Default for Ledger {
+    fn default() -> Ledger {
+        Ledger{
+            cash: "1930".to_string(),       // Företagskonto
+            holdings: "1880".to_string(),   // Andra kortfristiga placeringar
+            gains: "8350".to_string(),      // Resultat vid försäljning av kortfristiga placeringar
+            losses: "8350".to_string(),
+            income: "8390".to_string(),     // Övriga finansiella intäkter
+        }
+    }
+}
+
+/// Reads the ledger accounts from path, see `Ledger`.
+pub(crate) fn read_ledger(path: &PathBuf) -> Result<Ledger> {
+    toml::from_str(&fs::read_to_string(path)?)
+        .map_err(|e| error::invalid(path, format!("Invalid ledger file: {}", e)))
+}
+
+/// The columns of the journal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum JournalFormat {
+    /// Double-entry rows with English headers and the amounts as they are.
+    #[default]
+    Generic,
+    /// The rows of a verification import of Fortnox or Visma, with Swedish headers and a decimal
+    /// comma.
+    Fortnox,
+}
+
+/// A line of a journal entry, a debit or a credit of an account. The lines of an entry share
+/// its number and balance.
+#[derive(Debug, PartialEq, Serialize)]
+pub(crate) struct JournalRow {
+    #[serde(rename = "Entry")]
+    entry: usize,
+
+    #[serde(rename = "Date")]
+    date: String,
+
+    #[serde(rename = "Account")]
+    account: String,
+
+    #[serde(rename = "Description")]
+    description: String,
+
+    #[serde(rename = "Debit")]
+    debit: Option<Decimal>,
+
+    #[serde(rename = "Credit")]
+    credit: Option<Decimal>,
+}
+
+/// A `JournalRow` of `JournalFormat::Fortnox`.
+#[derive(Serialize)]
+struct FortnoxRow<'a> {
+    #[serde(rename = "Verifikationsnummer")]
+    entry: usize,
+
+    #[serde(rename = "Datum")]
+    date: &'a str,
+
+    #[serde(rename = "Konto")]
+    account: &'a str,
+
+    #[serde(rename = "Text")]
+    description: &'a str,
+
+    #[serde(rename = "Debet")]
+    debit: String,
+
+    #[serde(rename = "Kredit")]
+    credit: String,
+}
+
+impl<'a> From<&'a JournalRow> for FortnoxRow<'a> {
+    fn from(row: &'a JournalRow) -> FortnoxRow<'a> {
+        let amount = |a: Option<Decimal>| a.map(|a| a.to_string().replace('.', ",")).unwrap_or_default();
+        FortnoxRow{
+            entry: row.entry,
+            date: &row.date,
+            account: &row.account,
+            description: &row.description,
+            debit: amount(row.debit),
+            credit: amount(row.credit),
+        }
+    }
+}
+
+/// A journal entry before it is numbered, the amounts booked to each account as debits if
+/// positive and credits if negative.
+struct Entry<'a> {
+    date: &'a str,
+    description: String,
+    lines: Vec<(&'a str, Decimal)>,
+}
+
+/// Books the buys, the income and the taxable disposals of the calculation as journal entries in
+/// order of date, with the amounts rounded to two decimals. Only what is valued in the base
+/// currency can be booked, so the trades for other currencies are left out unless they are valued
+/// at their market price.
+pub(crate) fn entries(txns: &[Transaction], calculation: &Calculation, base: &Currency, ledger: &Ledger) -> Vec<JournalRow> {
+    let amount = |a: Decimal| a.abs().round_dp(2);
+    let mut entries: Vec<Entry> = vec![];
+    for t in txns.iter().filter(|t| t.r#type == TransactionType::Buy && t.exchanged_currency.eq(base)) {
+        let cost = amount(t.exchanged_amount);
+        let description = format!("Buy {} {}", t.paid_amount, t.paid_currency);
+        entries.push(Entry{ date: &t.date, description, lines: vec![(ledger.holdings.as_str(), cost), (ledger.cash.as_str(), -cost)] });
+    }
+    for t in calculation.income.iter().filter(|t| t.income.is_cash()) {
+        let value = amount(t.income.amount());
+        let description = format!("{:?} {} {}", t.r#type, t.amount, t.currency);
+        entries.push(Entry{ date: &t.date, description, lines: vec![(ledger.holdings.as_str(), value), (ledger.income.as_str(), -value)] });
+    }
+    for t in calculation.taxables.iter().filter(|t| t.net_income.is_some()) {
+        let proceeds = amount(t.income.amount());
+        let cost = amount(t.costs.iter().map(|c| c.amount()).sum());
+        let gain = proceeds - cost;
+        let account = match gain >= dec!(0) { true => &ledger.gains, false => &ledger.losses };
+        let description = format!("{:?} {} {}", t.r#type, -t.amount, t.currency);
+        entries.push(Entry{ date: &t.date, description, lines: vec![(ledger.cash.as_str(), proceeds), (ledger.holdings.as_str(), -cost), (account.as_str(), -gain)] });
+    }
+    entries.sort_by(|a, b| a.date.cmp(b.date));
+    entries.into_iter()
+        .enumerate()
+        .flat_map(|(i, Entry{ date, description, lines })| {
+            lines.into_iter()
+                .filter(|(_, amount)| !amount.is_zero())
+                .map(move |(account, amount)| JournalRow{
+                    entry: i + 1,
+                    date: date.chars().take(10).collect(),
+                    account: account.to_string(),
+                    description: description.clone(),
+                    debit: Some(amount).filter(|a| a.is_sign_positive()),
+                    credit: Some(-amount).filter(|a| a.is_sign_positive()),
+                })
+        })
+        .collect()
+}
+
+/// Writes the journal to `out` in the format.
+pub(crate) fn write<W: io::Write>(rows: &[JournalRow], format: JournalFormat, out: W) -> Result<()> {
+    let mut wtr = WriterBuilder::new().delimiter(b';').from_writer(out);
+    for row in rows {
+        match format {
+            JournalFormat::Generic => wtr.serialize(row)?,
+            JournalFormat::Fortnox => wtr.serialize(FortnoxRow::from(row))?,
+        }
+    }
+    Ok(wtr.flush()?)
+}
+
+impl FromStr for JournalFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_uppercase().as_str() {
+            "GENERIC" => Ok(JournalFormat::Generic),
+            "FORTNOX" | "VISMA" => Ok(JournalFormat::Fortnox),
+            _ => Err(format!("Unknown journal format `{}`. Supported: GENERIC, FORTNOX, VISMA", s)),
+        }
+    }
+}
+
+impl fmt::Display for JournalFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JournalFormat::Generic => write!(f, "GENERIC"),
+            JournalFormat::Fortnox => write!(f, "FORTNOX"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::calculator;
+    use crate::cryptotax::Options;
+    use crate::journal::*;
+    use futures::executor::block_on;
+    use std::error::Error;
+
+    #[test]
+    fn should_book_balanced_journal_entries() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let txns = vec![
+            Transaction::buy().currency("BTC").amount(dec!(1)).exchanged_currency("SEK").exchanged_amount(dec!(-1000.004)).date("2021-12-31 17:54:48").build()?,
+            Transaction::sell().currency("BTC").amount(dec!(-0.5)).exchanged_currency("SEK").exchanged_amount(dec!(400)).date("2022-03-01 16:21:49").build()?,
+            Transaction::sell().currency("BTC").amount(dec!(-0.5)).exchanged_currency("ETH").exchanged_amount(dec!(0.2)).date("2022-04-01 10:00:00").build()?,
+        ];
+        let base = "SEK".to_string();
+        let calculation = block_on(calculator::calculate(&txns, &"BTC".to_string(), &base, &Options::default()))?;
+        let ledger: Ledger = toml::from_str("cash = \"1920\"")?;
+
+        /*
+         * When
+         */
+        let rows = entries(&txns, &calculation, &base, &ledger);
+        let mut out = vec![];
+        write(&rows, JournalFormat::Fortnox, &mut out)?;
+
+        /*
+         * Then
+         */
+        let line = |entry: usize, date: &str, account: &str, description: &str, debit: Option<Decimal>, credit: Option<Decimal>| JournalRow{
+            entry, date: date.to_string(), account: account.to_string(), description: description.to_string(), debit, credit,
+        };
+        assert_eq!(rows, vec![
+            line(1, "2021-12-31", "1880", "Buy 1 BTC", Some(dec!(1000.00)), None),
+            line(1, "2021-12-31", "1920", "Buy 1 BTC", None, Some(dec!(1000.00))),
+            line(2, "2022-03-01", "1920", "Sell 0.5 BTC", Some(dec!(400)), None),
+            line(2, "2022-03-01", "1880", "Sell 0.5 BTC", None, Some(dec!(500.00))),
+            line(2, "2022-03-01", "8350", "Sell 0.5 BTC", Some(dec!(100.00)), None),
+        ]);
+        assert_eq!(
+            String::from_utf8(out)?.lines().take(2).collect::<Vec<_>>(),
+            vec!["Verifikationsnummer;Datum;Konto;Text;Debet;Kredit", "1;2021-12-31;1880;Buy 1 BTC;1000,00;"]
+        );
+        assert!(read_ledger(&PathBuf::from("missing.toml")).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,472 @@
+use crate::term::{self, HoldingPeriod};
+use crate::transaction::TransactionType;
+use chrono_tz::Tz;
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use std::fmt;
+use std::str::FromStr;
+
+/// The country whose tax rules are applied.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum Jurisdiction {
+    #[default]
+    Sweden,
+    UnitedStates,
+    UnitedKingdom,
+    Germany,
+}
+
+/// How the cost of a disposal is found among the acquisitions.
+#[derive(Debug, PartialEq)]
+pub(crate) enum Matching {
+    /// The average cost of all acquisitions ("genomsnittsmetoden"), see `CostBook`.
+    AverageCost,
+    /// The UK share identification rules, see `section104::calculate`.
+    Section104,
+    /// First in, first out, see `lots::calculate`.
+    Fifo,
+}
+
+/// How adding liquidity to a pool and removing it is taxed, see `liquidity::prepare`.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Liquidity {
+    /// Adding liquidity disposes of the currencies for the LP tokens, and removing it disposes of
+    /// the LP tokens for the currencies, both at their market value. The gain or loss of the
+    /// position, including the impermanent loss, is realized on the LP tokens.
+    Disposal,
+    /// The currencies are still owned while in the pool, and the LP tokens are receipts without a
+    /// cost. Removing liquidity realizes the difference between what is withdrawn and what was
+    /// deposited, the impermanent loss, as a buy or sell of the difference at its market value.
+    Deposit,
+}
+
+/// How the gains of disposing of a stablecoin are taxed, see `stablecoin::is_negligible`.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum StablecoinGains {
+    /// Like any other currency, so that the small gains and losses from the price moving around
+    /// its peg, and from the exchange rate of the pegged fiat currency to the base currency, are
+    /// calculated precisely.
+    Precise,
+    /// The gains are negligible, so a disposal is reported at a cost equal to its income.
+    Negligible,
+}
+
+/// How the fees of trading are treated in the income and the costs of a disposal, see
+/// `fees::apply`. The fees paid in the traded currency are in its amounts whatever the policy, as
+/// they change what is held.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum FeePolicy {
+    /// The fees of both the acquisition and the disposal are capitalized into the costs, and the
+    /// income is gross, e.g. the allowable costs of the HMRC.
+    Capitalize,
+    /// The fees of the acquisition are capitalized into the costs, and the fee of the disposal is
+    /// deducted from the income.
+    Deduct,
+    /// The fees are left out of the income and the costs, and so of the gains, and are reported in
+    /// columns of their own, e.g. to be deducted as expenses.
+    Separate,
+}
+
+/// Whether the acquisitions of all accounts are one pool of cost basis, or each account has its
+/// own, see `account::calculate`.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum BasisScope {
+    /// One pool for all accounts, wherever the currency is held.
+    Universal,
+    /// A pool for each account, the file that its transactions are read from, so that a disposal
+    /// only gets the costs of what was acquired in the same account.
+    PerAccount,
+}
+
+/// How a fee rebate or a referral bonus paid in a currency is taxed, see `rebate::apply`.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Rebates {
+    /// Income at its market value when received, which is also its cost.
+    Income,
+    /// A discount on the fees, so it is no income, and acquired at no cost.
+    Discount,
+}
+
+/// A threshold of the net gains of a tax year, in the currency of the jurisdiction, see
+/// `Jurisdiction::threshold`.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub(crate) enum Threshold {
+    /// The gains are tax free if they are less than the limit, and taxed in full otherwise, e.g.
+    /// the German "Freigrenze" of private sales.
+    Limit(Decimal),
+    /// The allowance is deducted from the gains, e.g. the UK annual exempt amount.
+    Allowance(Decimal),
+}
+
+impl Threshold {
+    pub(crate) fn amount(&self) -> Decimal {
+        match self {
+            Threshold::Limit(amount) | Threshold::Allowance(amount) => *amount,
+        }
+    }
+
+    /// The part of the net gain of a tax year that is taxed. A net loss is left as it is.
+    pub(crate) fn taxable(&self, net_income: Decimal) -> Decimal {
+        match self {
+            _ if net_income <= dec!(0) => net_income,
+            Threshold::Limit(limit) if net_income < *limit => dec!(0),
+            Threshold::Limit(_) => net_income,
+            Threshold::Allowance(allowance) => (net_income - allowance).max(dec!(0)),
+        }
+    }
+}
+
+impl Jurisdiction {
+    /// Whether a gain or loss is recognized when disposing of a currency with a transaction of
+    /// the given type. If not, the cost basis is carried over to the recipient, e.g. a gift in
+    /// Sweden ("gåva"), or just removed from the holdings, e.g. a lost or stolen currency. Such
+    /// disposals are reported separately from the taxable transactions.
+    pub(crate) fn recognizes_gain(&self, r#type: &TransactionType) -> bool {
+        match (self, r#type) {
+            (Jurisdiction::Sweden, TransactionType::Gift) => false,
+            (Jurisdiction::Sweden, TransactionType::Donation) => false,
+            (Jurisdiction::Sweden, TransactionType::Lost) => false,     // Not a disposal ("avyttring")
+            (Jurisdiction::Sweden, TransactionType::Stolen) => false,
+            (Jurisdiction::Sweden, _) => true,
+            (Jurisdiction::UnitedStates, TransactionType::Gift) => false,     // The donee takes over the basis
+            (Jurisdiction::UnitedStates, TransactionType::Donation) => false,
+            (Jurisdiction::UnitedStates, TransactionType::Lost) => false,     // No personal casualty loss since 2018
+            (Jurisdiction::UnitedStates, TransactionType::Stolen) => false,
+            (Jurisdiction::UnitedStates, _) => true,
+            (Jurisdiction::UnitedKingdom, TransactionType::Donation) => false, // No gain, no loss for charities
+            (Jurisdiction::UnitedKingdom, TransactionType::Lost) => false,     // Unless a negligible value claim is made
+            (Jurisdiction::UnitedKingdom, TransactionType::Stolen) => false,
+            (Jurisdiction::UnitedKingdom, _) => true,                          // Including gifts, at market value
+            (Jurisdiction::Germany, TransactionType::Gift) => false,           // Not a sale ("Veräußerung")
+            (Jurisdiction::Germany, TransactionType::Donation) => false,
+            (Jurisdiction::Germany, TransactionType::Lost) => false,
+            (Jurisdiction::Germany, TransactionType::Stolen) => false,
+            (Jurisdiction::Germany, _) => true,
+        }
+    }
+
+    /// How long a lot is held before its gains are long-term, if the jurisdiction tells them
+    /// apart, see `term::split`.
+    pub(crate) fn holding_period(&self) -> Option<HoldingPeriod> {
+        match self {
+            Jurisdiction::Sweden => None,
+            Jurisdiction::UnitedStates => Some(term::ONE_YEAR),     // Taxed at the long-term capital gains rates
+            Jurisdiction::UnitedKingdom => None,
+            Jurisdiction::Germany => Some(term::ONE_YEAR),          // "Spekulationsfrist"
+        }
+    }
+
+    /// Whether the gains of lots held for more than a year are tax free, e.g. in Germany
+    /// ("Spekulationsfrist").
+    pub(crate) fn exempts_long_term_gains(&self) -> bool {
+        matches!(self, Jurisdiction::Germany)
+    }
+
+    /// Whether gains from exchanging a foreign fiat currency are taxed, e.g. "valutavinst" in
+    /// Sweden.
+    pub(crate) fn taxes_currency_gains(&self) -> bool {
+        match self {
+            Jurisdiction::Sweden => true,
+            Jurisdiction::UnitedStates => true,
+            Jurisdiction::UnitedKingdom => false,  // Foreign currency for personal use is exempt
+            Jurisdiction::Germany => true,
+        }
+    }
+
+    /// Whether paying a network fee ("gas") with a crypto currency disposes of it, as spending it
+    /// on anything else would. If not, the fee is a cost of the transaction that it paid for.
+    #[cfg(feature = "native")]
+    pub(crate) fn disposes_network_fees(&self) -> bool {
+        match self {
+            Jurisdiction::Sweden => true,
+            Jurisdiction::UnitedStates => true,
+            Jurisdiction::UnitedKingdom => true,
+            Jurisdiction::Germany => false,    // Not an exchange ("Tausch") of its own
+        }
+    }
+
+    /// Whether a wrapped or bridged currency is the same asset as the currency it stands for, so
+    /// that wrapping or bridging it is not a disposal, see `equivalence::DEFAULTS`.
+    pub(crate) fn pools_equivalent_currencies(&self) -> bool {
+        match self {
+            Jurisdiction::Sweden => true,
+            Jurisdiction::UnitedStates => true,
+            Jurisdiction::UnitedKingdom => false,  // A token with other rights is another asset
+            Jurisdiction::Germany => true,
+        }
+    }
+
+    /// How the gains of stablecoins are taxed, unless `Options::stablecoin_gains` is set. No
+    /// jurisdiction has an exemption for them, however small the gains are.
+    pub(crate) fn stablecoin_gains(&self) -> StablecoinGains {
+        match self {
+            Jurisdiction::Sweden => StablecoinGains::Precise,
+            Jurisdiction::UnitedStates => StablecoinGains::Precise,
+            Jurisdiction::UnitedKingdom => StablecoinGains::Precise,
+            Jurisdiction::Germany => StablecoinGains::Precise,
+        }
+    }
+
+    /// How the fees of trading are treated, unless `Options::fee_policy` is set.
+    pub(crate) fn fee_policy(&self) -> FeePolicy {
+        match self {
+            Jurisdiction::Sweden => FeePolicy::Deduct,          // The sale price after the selling expenses on K4
+            Jurisdiction::UnitedStates => FeePolicy::Deduct,    // The proceeds less the selling expenses on Form 8949
+            Jurisdiction::UnitedKingdom => FeePolicy::Capitalize,
+            Jurisdiction::Germany => FeePolicy::Deduct,
+        }
+    }
+
+    /// How liquidity pools are taxed, unless `Options::liquidity` is set.
+    pub(crate) fn liquidity(&self) -> Liquidity {
+        match self {
+            Jurisdiction::Sweden => Liquidity::Disposal,      // An exchange ("byte") for the LP tokens
+            Jurisdiction::UnitedStates => Liquidity::Disposal,
+            Jurisdiction::UnitedKingdom => Liquidity::Disposal,
+            Jurisdiction::Germany => Liquidity::Deposit,
+        }
+    }
+
+    /// Whether the cost basis is pooled across accounts, unless `Options::basis_scope` is set.
+    pub(crate) fn basis_scope(&self) -> BasisScope {
+        match self {
+            Jurisdiction::Sweden => BasisScope::Universal,
+            Jurisdiction::UnitedStates => BasisScope::Universal,    // Per wallet from 2025, which needs the earlier years in that scope too
+            Jurisdiction::UnitedKingdom => BasisScope::Universal,   // A single section 104 pool of each asset
+            Jurisdiction::Germany => BasisScope::Universal,
+        }
+    }
+
+    /// The rate in percent that the realized gains are taxed at, for the estimated tax of the
+    /// summary, unless `Options::tax_rate` is set. `None` where the rate depends on the income of
+    /// the taxpayer.
+    pub(crate) fn tax_rate(&self) -> Option<Decimal> {
+        match self {
+            Jurisdiction::Sweden => Some(dec!(30)),     // Tax on capital income ("inkomst av kapital")
+            Jurisdiction::UnitedStates => None,         // 0, 15 or 20% long-term, the marginal rate short-term
+            Jurisdiction::UnitedKingdom => None,        // By the band of the income
+            Jurisdiction::Germany => None,              // The personal income tax rate
+        }
+    }
+
+    /// The share in percent of a net loss of a year that is deducted, for the estimated tax.
+    pub(crate) fn deductible_losses(&self) -> Decimal {
+        match self {
+            Jurisdiction::Sweden => dec!(70),           // Of other assets than listed shares ("övriga tillgångar")
+            Jurisdiction::UnitedStates => dec!(100),
+            Jurisdiction::UnitedKingdom => dec!(100),
+            Jurisdiction::Germany => dec!(100),
+        }
+    }
+
+    /// How fee rebates and referral bonuses are taxed, unless `Options::rebates` is set.
+    pub(crate) fn rebates(&self) -> Rebates {
+        match self {
+            Jurisdiction::Sweden => Rebates::Income,
+            Jurisdiction::UnitedStates => Rebates::Income,
+            Jurisdiction::UnitedKingdom => Rebates::Income,
+            Jurisdiction::Germany => Rebates::Income,   // Other income ("sonstige Einkünfte")
+        }
+    }
+
+    /// Whether a fee rebate may be treated as a discount on the fees, see `Rebates::Discount`.
+    pub(crate) fn allows_rebate_discounts(&self) -> bool {
+        match self {
+            Jurisdiction::Sweden => false,
+            Jurisdiction::UnitedStates => true,     // A rebate reduces the price paid, like card rewards
+            Jurisdiction::UnitedKingdom => true,
+            Jurisdiction::Germany => false,
+        }
+    }
+
+    /// The currency of the thresholds, see `threshold`.
+    pub(crate) fn currency(&self) -> &'static str {
+        match self {
+            Jurisdiction::Sweden => "SEK",
+            Jurisdiction::UnitedStates => "USD",
+            Jurisdiction::UnitedKingdom => "GBP",
+            Jurisdiction::Germany => "EUR",
+        }
+    }
+
+    /// The timezone that the dates are converted to before they are assigned to tax years and
+    /// matched with daily prices, unless `Options::timezone` is given, see `timezone::to_local`.
+    pub(crate) fn timezone(&self) -> Tz {
+        match self {
+            Jurisdiction::Sweden => chrono_tz::Europe::Stockholm,
+            Jurisdiction::UnitedStates => chrono_tz::America::New_York,    // One of several, see `--timezone`
+            Jurisdiction::UnitedKingdom => chrono_tz::Europe::London,
+            Jurisdiction::Germany => chrono_tz::Europe::Berlin,
+        }
+    }
+
+    /// The threshold below which the net gains of the tax year are tax free, if any. The tax year
+    /// is a calendar year such as `2022`, or a UK tax year such as `2022/23`, see
+    /// `section104::summarize`, and only the thresholds of the tax years of the jurisdiction are
+    /// known.
+    pub(crate) fn threshold(&self, tax_year: &str) -> Option<Threshold> {
+        match self {
+            Jurisdiction::Sweden => None,
+            Jurisdiction::UnitedStates => None,
+            Jurisdiction::UnitedKingdom => {
+                let (start, _) = tax_year.split_once('/')?;
+                let allowance = match start.parse::<i32>().ok()? {
+                    2015 | 2016 => dec!(11100),
+                    2017 => dec!(11300),
+                    2018 => dec!(11700),
+                    2019 => dec!(12000),
+                    2020..=2022 => dec!(12300),
+                    2023 => dec!(6000),
+                    2024.. => dec!(3000),
+                    _ => return None,
+                };
+                Some(Threshold::Allowance(allowance))
+            }
+            Jurisdiction::Germany => match tax_year.parse::<i32>().ok()? {
+                ..=2023 => Some(Threshold::Limit(dec!(600))),
+                _ => Some(Threshold::Limit(dec!(1000))),   // Raised by the "Wachstumschancengesetz"
+            },
+        }
+    }
+
+    pub(crate) fn matching(&self) -> Matching {
+        match self {
+            Jurisdiction::Sweden => Matching::AverageCost,
+            Jurisdiction::UnitedStates => Matching::Fifo,       // The IRS default without specific identification, as on Form 8949
+            Jurisdiction::UnitedKingdom => Matching::Section104,
+            Jurisdiction::Germany => Matching::Fifo,
+        }
+    }
+}
+
+impl FromStr for Jurisdiction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_uppercase().as_str() {
+            "SE" | "SWEDEN" => Ok(Jurisdiction::Sweden),
+            "US" | "USA" => Ok(Jurisdiction::UnitedStates),
+            "UK" | "GB" => Ok(Jurisdiction::UnitedKingdom),
+            "DE" | "GERMANY" => Ok(Jurisdiction::Germany),
+            _ => Err(format!("Unknown jurisdiction `{}`. Supported: SE, US, UK, DE", s)),
+        }
+    }
+}
+
+impl fmt::Display for Jurisdiction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Jurisdiction::Sweden => write!(f, "SE"),
+            Jurisdiction::UnitedStates => write!(f, "US"),
+            Jurisdiction::UnitedKingdom => write!(f, "UK"),
+            Jurisdiction::Germany => write!(f, "DE"),
+        }
+    }
+}
+
+impl FromStr for Liquidity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_uppercase().as_str() {
+            "DISPOSAL" => Ok(Liquidity::Disposal),
+            "DEPOSIT" => Ok(Liquidity::Deposit),
+            _ => Err(format!("Unknown liquidity treatment `{}`. Supported: DISPOSAL, DEPOSIT", s)),
+        }
+    }
+}
+
+impl fmt::Display for Liquidity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Liquidity::Disposal => write!(f, "DISPOSAL"),
+            Liquidity::Deposit => write!(f, "DEPOSIT"),
+        }
+    }
+}
+
+impl FromStr for StablecoinGains {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_uppercase().as_str() {
+            "PRECISE" => Ok(StablecoinGains::Precise),
+            "NEGLIGIBLE" => Ok(StablecoinGains::Negligible),
+            _ => Err(format!("Unknown stablecoin gains `{}`. Supported: PRECISE, NEGLIGIBLE", s)),
+        }
+    }
+}
+
+impl fmt::Display for StablecoinGains {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StablecoinGains::Precise => write!(f, "PRECISE"),
+            StablecoinGains::Negligible => write!(f, "NEGLIGIBLE"),
+        }
+    }
+}
+
+impl FromStr for FeePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_uppercase().as_str() {
+            "CAPITALIZE" => Ok(FeePolicy::Capitalize),
+            "DEDUCT" => Ok(FeePolicy::Deduct),
+            "SEPARATE" => Ok(FeePolicy::Separate),
+            _ => Err(format!("Unknown fee policy `{}`. Supported: CAPITALIZE, DEDUCT, SEPARATE", s)),
+        }
+    }
+}
+
+impl fmt::Display for FeePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FeePolicy::Capitalize => write!(f, "CAPITALIZE"),
+            FeePolicy::Deduct => write!(f, "DEDUCT"),
+            FeePolicy::Separate => write!(f, "SEPARATE"),
+        }
+    }
+}
+
+impl FromStr for BasisScope {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_uppercase().replace('_', "-").as_str() {
+            "UNIVERSAL" => Ok(BasisScope::Universal),
+            "PER-ACCOUNT" => Ok(BasisScope::PerAccount),
+            _ => Err(format!("Unknown basis scope `{}`. Supported: UNIVERSAL, PER-ACCOUNT", s)),
+        }
+    }
+}
+
+impl fmt::Display for BasisScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BasisScope::Universal => write!(f, "UNIVERSAL"),
+            BasisScope::PerAccount => write!(f, "PER-ACCOUNT"),
+        }
+    }
+}
+
+impl FromStr for Rebates {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_uppercase().as_str() {
+            "INCOME" => Ok(Rebates::Income),
+            "DISCOUNT" => Ok(Rebates::Discount),
+            _ => Err(format!("Unknown rebates treatment `{}`. Supported: INCOME, DISCOUNT", s)),
+        }
+    }
+}
+
+impl fmt::Display for Rebates {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Rebates::Income => write!(f, "INCOME"),
+            Rebates::Discount => write!(f, "DISCOUNT"),
+        }
+    }
+}
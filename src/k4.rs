@@ -0,0 +1,121 @@
+use crate::calculator::TaxableTransaction;
+use crate::rounding::Rounding;
+use crate::transaction::{self, Currency, NFT};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// A line of section D of the Swedish K4 form, the disposals of a currency in a year. Only the
+/// disposals valued in the base currency are included.
+#[derive(Debug, PartialEq, Serialize)]
+pub(crate) struct K4Row {
+    #[serde(rename = "Year")]
+    year: String,
+
+    #[serde(rename = "Amount")]
+    amount: Decimal,                // Antal, the amount sold as a positive number
+
+    #[serde(rename = "Currency")]
+    currency: Currency,             // Beteckning
+
+    #[serde(rename = "Sales Price")]
+    sales_price: Decimal,           // Försäljningspris
+
+    #[serde(rename = "Cost Basis")]
+    cost_basis: Decimal,            // Omkostnadsbelopp
+
+    #[serde(rename = "Gain")]
+    gain: Decimal,                  // Vinst
+
+    #[serde(rename = "Loss")]
+    loss: Decimal,                  // Förlust
+}
+
+/// Sums up the taxable disposals per year like the K4 form. The sales price and the cost basis
+/// are rounded after they are summed up, whole SEK in the taxpayer's favor with
+/// `Rounding::Favorable`, and the gain or the loss is the difference of the rounded amounts.
+pub(crate) fn rows(taxables: &[TaxableTransaction], rounding: &Rounding) -> Vec<K4Row> {
+    let mut rows: Vec<K4Row> =
+        taxables.iter()
+            .filter(|t| t.net_income.is_some())
+            .fold(BTreeMap::new(), |mut years, t| {
+                let currency = match transaction::is_nft(&t.currency) {
+                    true => NFT.to_string(),
+                    false => t.currency.clone(),
+                };
+                let year: String = t.date.chars().take(4).collect();
+                let row = years.entry((year.clone(), currency.clone())).or_insert_with(|| K4Row{
+                    year,
+                    amount: dec!(0),
+                    currency,
+                    sales_price: dec!(0),
+                    cost_basis: dec!(0),
+                    gain: dec!(0),
+                    loss: dec!(0),
+                });
+                row.amount -= t.amount;
+                row.sales_price += t.income.amount();
+                row.cost_basis -= t.costs.iter().map(|c| c.amount()).sum::<Decimal>();
+                years
+            })
+            .into_values()
+            .collect();
+    for row in rows.iter_mut() {
+        row.sales_price = rounding.gain(row.sales_price);
+        row.cost_basis = rounding.cost(row.cost_basis);
+        let net = row.sales_price - row.cost_basis;
+        row.gain = net.max(dec!(0));
+        row.loss = (-net).max(dec!(0));
+    }
+    rows
+}
+
+#[cfg(test)]
+mod test {
+    use crate::k4::*;
+    use crate::transaction::{Money, TransactionType};
+    use std::error::Error;
+
+    #[test]
+    fn should_sum_up_disposals_per_year_in_whole_kronor() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let taxable = |date: &str, amount: Decimal, income: Decimal, cost: Decimal| TaxableTransaction{
+            r#type: TransactionType::Sell,
+            date: date.to_string(),
+            currency: "BTC".to_string(),
+            amount,
+            income: Money::new_cash("SEK".to_string(), income),
+            costs: vec![Money::new_cash("SEK".to_string(), cost)],
+            net_income: Some(income + cost),
+            acquisition_fee: dec!(0),
+            disposal_fee: dec!(0),
+            origin: Default::default(),
+            consumed: vec![],
+        };
+        let mut unpriced = taxable("2021-03-01 10:00:00", dec!(-1), dec!(0), dec!(0));
+        unpriced.net_income = None;
+        let taxables = vec![
+            taxable("2021-01-01 10:00:00", dec!(-0.5), dec!(1000.9), dec!(-400.1)),
+            taxable("2021-02-01 10:00:00", dec!(-0.5), dec!(1000.9), dec!(-400.1)),
+            unpriced,
+            taxable("2022-01-01 10:00:00", dec!(-1), dec!(500), dec!(-800)),
+        ];
+
+        /*
+         * When
+         */
+        let rows = rows(&taxables, &Rounding::Favorable);
+
+        /*
+         * Then
+         */
+        assert_eq!(rows, vec![
+            K4Row{ year: "2021".to_string(), amount: dec!(1), currency: "BTC".to_string(), sales_price: dec!(2001), cost_basis: dec!(801), gain: dec!(1200), loss: dec!(0) },
+            K4Row{ year: "2022".to_string(), amount: dec!(1), currency: "BTC".to_string(), sales_price: dec!(500), cost_basis: dec!(800), gain: dec!(0), loss: dec!(300) },
+        ]);
+        Ok(())
+    }
+}
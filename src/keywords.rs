@@ -0,0 +1,155 @@
+use crate::cryptotax::Options;
+use crate::error::{self, Result};
+use serde::Deserialize;
+use std::fs;
+use std::path::PathBuf;
+use tracing::debug;
+
+/// The keywords shipped with the crate, see `keywords.toml`.
+const SHIPPED: &str = include_str!("keywords.toml");
+
+/// The version of the format of the keywords files that is read. A file of a newer version is
+/// rejected, rather than read with keywords left out.
+const VERSION: u32 = 1;
+
+/// A keywords file, the shipped one or one given with `--keywords`:
+///
+/// ```toml
+/// version = 1
+///
+/// [[set]]
+/// language = "en"
+/// since = "2024"                          # Optional: the version of the app, for the record
+/// exchanged_from = ["Exchanged from"]
+/// exchanged_to = ["Exchanged to", "Converted to"]
+/// vault = ["Vault"]
+/// savings = ["Savings"]
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct KeywordsFile {
+    version: u32,
+    #[serde(default)]
+    set: Vec<KeywordSet>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct KeywordSet {
+    language: String,
+    since: Option<String>,
+    #[serde(default)]
+    exchanged_from: Vec<String>,
+    #[serde(default)]
+    exchanged_to: Vec<String>,
+    #[serde(default)]
+    vault: Vec<String>,
+    #[serde(default)]
+    savings: Vec<String>,
+}
+
+/// The words of the descriptions of the account statements in all the languages and versions of
+/// the app that are known, see `locale::parse_exchange`.
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct Keywords {
+    /// The prefixes of the description of an `Exchange` row that received a currency.
+    pub(crate) exchanged_from: Vec<String>,
+    /// The prefixes of the description of an `Exchange` row that paid with a currency.
+    pub(crate) exchanged_to: Vec<String>,
+    /// The words after the counter currency that tell that an exchange was to or from a vault.
+    pub(crate) vault: Vec<String>,
+    /// The words after the counter currency that tell that an exchange was to or from a savings
+    /// account.
+    pub(crate) savings: Vec<String>,
+}
+
+impl Keywords {
+    fn parse(toml: &str) -> Result<Keywords, String> {
+        let file: KeywordsFile = toml::from_str(toml).map_err(|e| e.to_string())?;
+        if file.version > VERSION {
+            return Err(format!("Version {} is newer than the supported version {}", file.version, VERSION));
+        }
+        Ok(file.set.into_iter().fold(Keywords::default(), |acc, set| {
+            debug!(language = %set.language, since = ?set.since, "Keyword set");
+            acc.extend(Keywords{
+                exchanged_from: set.exchanged_from,
+                exchanged_to: set.exchanged_to,
+                vault: set.vault,
+                savings: set.savings,
+            })
+        }))
+    }
+
+    /// The keywords shipped with the crate.
+    pub(crate) fn shipped() -> Keywords {
+        Keywords::parse(SHIPPED).expect("The shipped keywords are valid")
+    }
+
+    /// The keywords of both, the ones of `self` first.
+    fn extend(mut self, other: Keywords) -> Keywords {
+        self.exchanged_from.extend(other.exchanged_from);
+        self.exchanged_to.extend(other.exchanged_to);
+        self.vault.extend(other.vault);
+        self.savings.extend(other.savings);
+        self
+    }
+}
+
+/// Reads a keywords file from path, with the phrasings of a language or a version of the app that
+/// the shipped keywords don't have yet.
+pub(crate) fn read_keywords(path: &PathBuf) -> Result<Keywords> {
+    Keywords::parse(&fs::read_to_string(path)?)
+        .map_err(|e| error::invalid(path, format!("Invalid keywords file: {}", e)))
+}
+
+/// The keywords that the descriptions are read with: the shipped ones, and the ones of the file
+/// of `Options::keywords` if any.
+pub(crate) fn of(options: &Options) -> Result<Keywords> {
+    match &options.keywords {
+        Some(path) => Ok(Keywords::shipped().extend(read_keywords(path)?)),
+        None => Ok(Keywords::shipped()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::keywords::*;
+    use crate::locale::{self, Direction};
+    use std::error::Error;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn should_read_descriptions_with_keywords_of_file() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let mut file = NamedTempFile::new()?;
+        writeln!(file, r#"
+            version = 1
+
+            [[set]]
+            language = "en"
+            since = "2024"
+            exchanged_to = ["Converted to"]
+        "#)?;
+        let options = Options{ keywords: Some(file.path().to_path_buf()), ..Default::default() };
+        let mut newer = NamedTempFile::new()?;
+        writeln!(newer, "version = 2")?;
+
+        /*
+         * When
+         */
+        let shipped = Keywords::shipped();
+        let extended = of(&options)?;
+
+        /*
+         * Then
+         */
+        assert_eq!(locale::parse_exchange("Converted to BTC", &shipped), None);
+        assert_eq!(locale::parse_exchange("Converted to BTC", &extended).map(|d| d.direction), Some(Direction::To));
+        assert_eq!(locale::parse_exchange("Växlat från SEK", &extended).map(|d| d.direction), Some(Direction::From));
+        assert!(read_keywords(&newer.path().to_path_buf()).is_err());
+        Ok(())
+    }
+}
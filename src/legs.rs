@@ -0,0 +1,186 @@
+use crate::decimal;
+use crate::error::Result;
+use crate::price::PriceChain;
+use crate::transaction::{Currency, Origin, Transaction, TransactionType};
+use crate::warnings::{self, Code, Warning};
+use rust_decimal::Decimal;
+use std::collections::BTreeMap;
+use tracing::debug;
+
+/// One row of an event that an exchange writes as many rows, e.g. the `Transaction Buy`,
+/// `Transaction Spend` and `Transaction Fee` rows of one trade in the Binance transaction history,
+/// with the fee in a third currency.
+#[derive(Debug, PartialEq, Clone)]
+pub(crate) struct Leg {
+    pub(crate) date: String,
+    pub(crate) currency: Currency,
+    pub(crate) amount: Decimal,     // Negative if paid
+    pub(crate) is_fee: bool,
+    pub(crate) origin: Origin,
+}
+
+/// Groups the legs into events by their key, e.g. the id of the trade, or its date if the export
+/// has no id. The events are in the order of their keys, and the legs of each in the order they
+/// were read.
+pub(crate) fn group<K: Ord>(legs: impl IntoIterator<Item = (K, Leg)>) -> Vec<Vec<Leg>> {
+    legs.into_iter()
+        .fold(BTreeMap::new(), |mut events: BTreeMap<K, Vec<Leg>>, (key, leg)| {
+            events.entry(key).or_default().push(leg);
+            events
+        })
+        .into_values()
+        .collect()
+}
+
+/// Converts an event from the side of the target currency, at the date of its first leg: a trade
+/// of the two currencies that were paid and received, whose fees are in the paid amount, the
+/// exchanged amount, or else in `Transaction::other_fee`. A fee paid in the target currency for a
+/// trade of two others is a disposal of it, valued at the market price with `--prices`. `None` if
+/// the target currency is not in the event, or if the event is not a trade of two currencies,
+/// which is reported.
+pub(crate) fn to_transaction(event: &[Leg], currency: &Currency) -> Option<Transaction> {
+    let sum = |is_fee: bool| event.iter()
+        .filter(|l| l.is_fee == is_fee)
+        .fold(BTreeMap::new(), |mut sums: BTreeMap<&Currency, Decimal>, l| {
+            *sums.entry(&l.currency).or_default() += l.amount;
+            sums
+        });
+    let (traded, fees) = (sum(false), sum(true));
+    if !traded.contains_key(currency) && !fees.contains_key(currency) {
+        return None;
+    }
+    let date = event[0].date.as_str();
+    let mut txn = Transaction::new();
+    txn.date = date.to_string();
+    txn.paid_currency = currency.clone();
+    txn.origin = origin_of(event);
+    let Some(amount) = traded.get(currency).copied() else {
+        let fee = fees[currency];
+        debug!(%date, %fee, %currency, "Fee of a trade of other currencies");
+        txn.r#type = TransactionType::Sell;
+        txn.paid_amount = fee;
+        txn.exchanged_currency = currency.clone();
+        txn.exchanged_amount = -fee;
+        return Some(txn);
+    };
+    let others: Vec<(&Currency, Decimal)> = traded.iter()
+        .filter(|(c, a)| **c != currency && a.is_sign_positive() != amount.is_sign_positive())
+        .map(|(c, a)| (*c, *a))
+        .collect();
+    let [(other, other_amount)] = others[..] else {
+        let msg = format!("{} rows at {} are not a trade of {} for one other currency", event.len(), date, currency);
+        warnings::report(Warning::new(Code::UnmatchedRow, msg).at(&txn.origin).at_date(date));
+        return None;
+    };
+    txn.r#type = if amount.is_sign_positive() { TransactionType::Buy } else { TransactionType::Sell };
+    txn.exchanged_currency = other.clone();
+    txn.paid_amount = amount;
+    txn.exchanged_amount = other_amount;
+    for (fee_currency, fee) in fees {
+        match fee_currency {
+            c if c.eq(currency) => {
+                txn.paid_amount += fee;
+                txn.paid_fee += fee;
+            }
+            c if c.eq(other) => {
+                txn.exchanged_amount += fee;
+                txn.exchanged_fee += fee;
+            }
+            c => txn.other_fee = Some((c.clone(), fee)),
+        }
+    }
+    Some(txn)
+}
+
+/// The origin of all the legs, with their lines in the order of the file.
+fn origin_of(event: &[Leg]) -> Origin {
+    let mut origin = Origin{ file: event[0].origin.file.clone(), ..Default::default() };
+    for leg in event {
+        for (line, description) in leg.origin.lines.iter().zip(&leg.origin.descriptions) {
+            origin.add(*line, description.clone());
+        }
+    }
+    origin
+}
+
+/// Values the fees of `Transaction::other_fee` at the market price of their currency in `base`,
+/// and adds them to the exchanged amount and fee of the trades that are valued in `base`, so that
+/// they are treated like any other fee, by the fee policy, see `fees::apply`. This runs after
+/// `price::value`. A fee that can't be valued is reported and left out.
+pub(crate) fn value_fees(txns: &mut [Transaction], base: &Currency, chain: Option<&PriceChain>) -> Result<()> {
+    for t in txns.iter_mut().filter(|t| t.other_fee.is_some()) {
+        let Some((currency, fee)) = t.other_fee.take() else { continue };
+        let price = match chain {
+            Some(chain) if t.exchanged_currency.eq(base) => chain.price_at(&currency, base, &t.date)?,
+            _ => None,
+        };
+        match price {
+            Some((price, source)) => {
+                let value = decimal::mul(fee, price)?;
+                debug!(date = %t.date, %fee, %currency, %price, %base, %source, "Fee valued");
+                t.exchanged_amount += value;
+                t.exchanged_fee += value;
+            }
+            None => {
+                let msg = format!("The fee of {} {} is not valued in {}, and is left out. Value it with --prices or --price-table", fee.abs(), currency, base);
+                warnings::report(Warning::new(Code::MissingPrice, msg).at(&t.origin).at_date(&t.date));
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::legs::*;
+    use crate::price;
+    use futures::executor::block_on;
+    use rust_decimal_macros::dec;
+    use std::error::Error;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn should_convert_trades_of_three_legs() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let leg = |key: &str, line, currency: &str, amount, is_fee| {
+            (key.to_string(), Leg{ date: key.to_string(), currency: currency.to_string(), amount, is_fee, origin: Origin::new(line, format!("{} {}", amount, currency)) })
+        };
+        let events = group(vec![
+            leg("2022-01-02 10:00:00", 4, "BTC", dec!(-0.01), false),
+            leg("2022-01-01 10:00:00", 1, "BTC", dec!(0.01), false),
+            leg("2022-01-01 10:00:00", 2, "EUR", dec!(-400), false),
+            leg("2022-01-01 10:00:00", 3, "BNB", dec!(-0.01), true),
+            leg("2022-01-02 10:00:00", 5, "EUR", dec!(420), false),
+            leg("2022-01-02 10:00:00", 6, "EUR", dec!(-0.42), true),
+        ]);
+        let mut table = NamedTempFile::new()?;
+        writeln!(table, "currency,date,price,base\nBNB,2022-01-01,300,EUR")?;
+        let chain = PriceChain::new(block_on(price::read_price_table(&table.path().to_path_buf()))?, vec![]);
+
+        /*
+         * When
+         */
+        let convert = |currency: &str| -> Vec<Transaction> {
+            events.iter().filter_map(|event| to_transaction(event, &currency.to_string())).collect()
+        };
+        let mut btc = convert("BTC");
+        value_fees(&mut btc, &"EUR".to_string(), Some(&chain))?;
+        let bnb = convert("BNB");
+
+        /*
+         * Then
+         */
+        let rows: Vec<_> = btc.iter().map(|t| (t.r#type.clone(), t.paid_amount, t.exchanged_amount, t.exchanged_fee, t.origin.lines.clone())).collect();
+        assert_eq!(rows, vec![
+            (TransactionType::Buy, dec!(0.01), dec!(-403), dec!(-3), vec![1, 2, 3]),
+            (TransactionType::Sell, dec!(-0.01), dec!(419.58), dec!(-0.42), vec![4, 5, 6]),
+        ]);
+        assert!(btc.iter().all(|t| t.other_fee.is_none()));
+        let fees: Vec<_> = bnb.iter().map(|t| (t.r#type.clone(), t.paid_amount, t.exchanged_currency.as_str(), t.exchanged_amount)).collect();
+        assert_eq!(fees, vec![(TransactionType::Sell, dec!(-0.01), "BNB", dec!(0.01))]);
+        Ok(())
+    }
+}
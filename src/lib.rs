@@ -1,5 +1,66 @@
 pub mod cryptotax;
+mod account;
+mod audit;
+mod bank;
+mod bundle;
 mod calculator;
+mod chart;
+#[cfg(feature = "native")]
+mod credentials;
+mod crosscheck;
+mod decimal;
+mod diff;
+mod dust;
+mod earn;
+mod equivalence;
+mod error;
+#[cfg(feature = "native")]
+mod exchange;
+mod fees;
+mod fixture;
+mod form8949;
+mod fx;
+#[cfg(feature = "native")]
+mod http;
+mod invariants;
+mod journal;
+mod jurisdiction;
+mod k4;
+mod keywords;
+mod legs;
+mod liquidity;
+mod locale;
+mod lots;
+mod nft;
+#[cfg(feature = "native")]
+mod onchain;
+mod overrides;
+mod precision;
+mod price;
+mod rebate;
 mod reader;
+mod rename;
+#[cfg(feature = "rpc")]
+mod rpc;
+mod report;
+mod rounding;
+mod rules;
+mod search;
+mod section104;
+mod short;
+mod simulate;
+mod stablecoin;
+#[cfg(feature = "native")]
+mod store;
+mod summary;
+mod term;
+mod timestamp;
+mod timezone;
 mod transaction;
-mod writer;
\ No newline at end of file
+#[cfg(feature = "tui")]
+mod tui;
+mod warnings;
+#[cfg(feature = "native")]
+mod watch;
+mod writer;
+mod xlsx;
\ No newline at end of file
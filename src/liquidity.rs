@@ -0,0 +1,139 @@
+use crate::decimal;
+use crate::error::Result;
+use crate::jurisdiction::Liquidity;
+use crate::transaction::{Currency, Transaction, TransactionType};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use std::collections::HashMap;
+use tracing::debug;
+
+/// What is still deposited in a pool, for the LP tokens received for it.
+#[derive(Debug, Default)]
+struct Position {
+    deposited: Decimal,     // Of the traded currency
+    shares: Decimal,        // Of the LP tokens
+}
+
+/// Whether the transaction is a leg of a pool currency, rather than of its LP tokens: the
+/// currency is paid into the pool when adding liquidity and received when removing it.
+fn is_pool_currency(t: &Transaction) -> bool {
+    match t.r#type {
+        TransactionType::AddLiquidity => t.paid_amount.is_sign_negative(),
+        TransactionType::RemoveLiquidity => t.paid_amount.is_sign_positive(),
+        _ => false,
+    }
+}
+
+/// Values the leg at the market price of its own amount, see `price::value`, as the LP tokens it
+/// is exchanged for have no price of their own.
+fn self_valued(t: Transaction) -> Transaction {
+    Transaction{ exchanged_currency: t.paid_currency.clone(), exchanged_amount: -t.paid_amount, ..t }
+}
+
+/// Prepares the liquidity pool transactions for the calculations, which treat a leg received
+/// from or paid to a pool as an acquisition or a disposal of the traded currency.
+///
+/// Each currency of a pool is a leg of its own, against its share of the LP tokens: adding 1 ETH
+/// and 3000 USDC for 10 LP tokens is `-1 ETH` for `5 UNI-V2`, and `+5 UNI-V2` for `-1 ETH`. The
+/// same goes for `-3000 USDC`.
+///
+/// With `Liquidity::Disposal` the legs of the pool currencies are valued at their own market
+/// price, and the LP tokens get the cost of what was paid for them. With `Liquidity::Deposit`
+/// the LP tokens are left out, and so is adding liquidity. Removing it is replaced by the
+/// difference between what is withdrawn and the share of the deposit for the LP tokens burned:
+/// more than deposited is acquired at its market value, and less is disposed of.
+pub(crate) fn prepare(txns: &mut Vec<Transaction>, model: Liquidity) -> Result<()> {
+    if model == Liquidity::Disposal {
+        *txns = std::mem::take(txns).into_iter()
+            .map(|t| if is_pool_currency(&t) { self_valued(t) } else { t })
+            .collect();
+        return Ok(());
+    }
+    let mut positions: HashMap<Currency, Position> = HashMap::new();
+    let mut prepared = Vec::with_capacity(txns.len());
+    for t in std::mem::take(txns) {
+        match t.r#type {
+            TransactionType::AddLiquidity if is_pool_currency(&t) => {
+                let position = positions.entry(t.exchanged_currency.clone()).or_default();
+                position.deposited -= t.paid_amount;
+                position.shares += t.exchanged_amount.abs();
+            }
+            TransactionType::RemoveLiquidity if is_pool_currency(&t) => {
+                let position = positions.entry(t.exchanged_currency.clone()).or_default();
+                let burned = t.exchanged_amount.abs().min(position.shares);
+                let returned = match position.shares.is_zero() {
+                    true => dec!(0),
+                    false => decimal::share(position.deposited, burned, position.shares)?,
+                };
+                position.deposited -= returned;
+                position.shares -= burned;
+                let difference = t.paid_amount - returned;
+                debug!(date = %t.date, pool = %t.exchanged_currency, withdrawn = %t.paid_amount, %returned, %difference, "Removed liquidity");
+                if !difference.is_zero() {
+                    prepared.push(self_valued(Transaction{ paid_amount: difference, paid_fee: dec!(0), ..t }));
+                }
+            }
+            TransactionType::AddLiquidity | TransactionType::RemoveLiquidity => {}
+            _ => prepared.push(t),
+        }
+    }
+    *txns = prepared;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::liquidity::*;
+    use std::error::Error;
+
+    #[test]
+    fn should_prepare_liquidity_as_disposals_or_deposits() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let txns = || {
+            let txn = |r#type: TransactionType, amount: Decimal, currency: &str, exchanged: Decimal, date: &str| {
+                let mut txn = Transaction::new();
+                txn.r#type = r#type;
+                txn.paid_currency = "ETH".to_string();
+                txn.paid_amount = amount;
+                txn.exchanged_currency = currency.to_string();
+                txn.exchanged_amount = exchanged;
+                txn.date = date.to_string();
+                txn
+            };
+            vec![
+                txn(TransactionType::Buy, dec!(2), "USD", dec!(-4000), "2022-01-01 10:00:00"),
+                txn(TransactionType::AddLiquidity, dec!(-2), "UNI-V2", dec!(10), "2022-02-01 10:00:00"),
+                txn(TransactionType::RemoveLiquidity, dec!(1.5), "UNI-V2", dec!(-5), "2022-03-01 10:00:00"),
+                txn(TransactionType::RemoveLiquidity, dec!(1), "UNI-V2", dec!(-5), "2022-04-01 10:00:00"),
+            ]
+        };
+
+        /*
+         * When
+         */
+        let mut disposals = txns();
+        prepare(&mut disposals, Liquidity::Disposal)?;
+        let mut deposits = txns();
+        prepare(&mut deposits, Liquidity::Deposit)?;
+
+        /*
+         * Then
+         */
+        let rows = |txns: &[Transaction]| -> Vec<(TransactionType, Decimal, Currency, Decimal)> {
+            txns.iter().map(|t| (t.r#type.clone(), t.paid_amount, t.exchanged_currency.clone(), t.exchanged_amount)).collect()
+        };
+        assert_eq!(rows(&disposals), vec![
+            (TransactionType::Buy, dec!(2), "USD".to_string(), dec!(-4000)),
+            (TransactionType::AddLiquidity, dec!(-2), "ETH".to_string(), dec!(2)),
+            (TransactionType::RemoveLiquidity, dec!(1.5), "ETH".to_string(), dec!(-1.5)),
+            (TransactionType::RemoveLiquidity, dec!(1), "ETH".to_string(), dec!(-1)),
+        ]);
+        assert_eq!(rows(&deposits), vec![
+            (TransactionType::Buy, dec!(2), "USD".to_string(), dec!(-4000)),
+            (TransactionType::RemoveLiquidity, dec!(0.5), "ETH".to_string(), dec!(-0.5)),   // The impermanent gain
+        ]);
+        Ok(())
+    }
+}
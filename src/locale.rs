@@ -0,0 +1,499 @@
+use crate::keywords::Keywords;
+use csv::StringRecord;
+use std::str::FromStr;
+
+/// The delimiter and the decimal separator of a csv file. Both differ by the locale of the
+/// Revolut app the file was exported from, e.g. `;` and `1 234,56` in Swedish exports.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub(crate) struct CsvFormat {
+    pub(crate) delimiter: u8,
+    pub(crate) decimal_separator: char,
+}
+
+impl Default for CsvFormat {
+    fn default() -> Self {
+        CsvFormat { delimiter: b',', decimal_separator: '.' }
+    }
+}
+
+const DELIMITERS: [u8; 3] = [b',', b';', b'\t'];
+
+/// Characters used to group thousands, besides `.` and `,`.
+const GROUP_SEPARATORS: [char; 4] = [' ', '\u{a0}', '\u{202f}', '\''];
+
+impl CsvFormat {
+    /// Detects the format from the first lines of a csv file. The delimiter is the most common
+    /// candidate in the header line. The decimal separator is the one that most numbers end with.
+    pub(crate) fn detect(sample: &str) -> CsvFormat {
+        let mut lines = sample.lines();
+        let header = lines.next().unwrap_or_default();
+        let delimiter =
+            DELIMITERS.iter()
+                .map(|d| (*d, header.matches(*d as char).count()))
+                .max_by_key(|(_, count)| *count)
+                .filter(|(_, count)| *count > 0)
+                .map_or(b',', |(d, _)| d);
+
+        let (commas, dots) =
+            lines
+                .flat_map(|line| split(line, delimiter))
+                .filter_map(|field| decimal_separator_of(&field))
+                .fold((0, 0), |(commas, dots), separator| match separator {
+                    ',' => (commas + 1, dots),
+                    _ => (commas, dots + 1),
+                });
+        let decimal_separator = if commas > dots { ',' } else { '.' };
+
+        CsvFormat { delimiter, decimal_separator }
+    }
+
+    /// Rewrites a localized number such as `1 234,56` into `1234.56`.
+    pub(crate) fn normalize_decimal(&self, field: &str) -> String {
+        field.chars()
+            .filter(|c| !GROUP_SEPARATORS.contains(c))
+            .filter_map(|c| match c {
+                '.' | ',' if c == self.decimal_separator => Some('.'),
+                '.' | ',' => None,
+                '\u{2212}' => Some('-'), // Unicode minus sign
+                c => Some(c),
+            })
+            .collect()
+    }
+
+    /// Trims the fields and normalizes the ones at the given `columns` with `normalize_decimal`
+    /// into `normalized`, which is cleared first and takes the position of `record`, so that one
+    /// record is reused for all the rows of a file. A number that is already plain, e.g. `-1234.56` in a file with decimal points, is
+    /// copied as it is, and a record whose fields are all plain is returned as it is.
+    pub(crate) fn normalize_record<'a>(&self, record: &'a StringRecord, columns: &[usize], normalized: &'a mut StringRecord) -> &'a StringRecord {
+        let is_plain = |field: &str| field.bytes().all(|b| b.is_ascii_digit() || b == b'-' || (b == b'.' && self.decimal_separator == '.'));
+        let is_normal = |(i, field): (usize, &str)| field.trim().len() == field.len() && (!columns.contains(&i) || is_plain(field));
+        if record.iter().enumerate().all(is_normal) {
+            return record;
+        }
+        normalized.clear();
+        normalized.set_position(record.position().cloned());
+        for (i, field) in record.iter().map(str::trim).enumerate() {
+            match columns.contains(&i) && !is_plain(field) {
+                true => normalized.push_field(&self.normalize_decimal(field)),
+                false => normalized.push_field(field),
+            }
+        }
+        normalized
+    }
+}
+
+/// Localized headers of Revolut exports, and the English header they correspond to.
+const HEADER_ALIASES: [(&str, &str); 38] = [
+    // Swedish
+    ("Typ", "Type"),
+    ("Startdatum", "Started Date"),
+    ("Slutförandedatum", "Completed Date"),
+    ("Beskrivning", "Description"),
+    ("Belopp", "Amount"),
+    ("Avgift", "Fee"),
+    ("Valuta", "Currency"),
+    ("Ursprungligt belopp", "Original Amount"),
+    ("Ursprunglig valuta", "Original Currency"),
+    ("Avräknat belopp", "Settled Amount"),
+    ("Avräknad valuta", "Settled Currency"),
+    ("Status", "State"),
+    ("Saldo", "Balance"),
+    // German
+    ("Art", "Type"),
+    ("Datum des Beginns", "Started Date"),
+    ("Datum des Abschlusses", "Completed Date"),
+    ("Beschreibung", "Description"),
+    ("Betrag", "Amount"),
+    ("Gebühr", "Fee"),
+    ("Währung", "Currency"),
+    ("Ursprünglicher Betrag", "Original Amount"),
+    ("Ursprüngliche Währung", "Original Currency"),
+    ("Abgerechneter Betrag", "Settled Amount"),
+    ("Abgerechnete Währung", "Settled Currency"),
+    ("Zustand", "State"),
+    // French
+    ("Date de début", "Started Date"),
+    ("Date de fin", "Completed Date"),
+    ("Date de démarrage", "Started Date"),
+    ("Date d'achèvement", "Completed Date"),
+    ("Montant", "Amount"),
+    ("Frais", "Fee"),
+    ("Devise", "Currency"),
+    ("Montant d'origine", "Original Amount"),
+    ("Devise d'origine", "Original Currency"),
+    ("Montant réglé", "Settled Amount"),
+    ("Devise de règlement", "Settled Currency"),
+    ("État", "State"),
+    ("Solde", "Balance"),
+];
+
+/// Whether an `Exchange` row received or paid with its currency.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub(crate) enum Direction {
+    /// "Exchanged from SEK": the currency of the row was received for SEK.
+    From,
+    /// "Exchanged to BTC": the currency of the row paid for BTC.
+    To,
+}
+
+/// Where the counter currency of an exchange was kept, besides the main account.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub(crate) enum Pocket {
+    Vault,
+    Savings,
+}
+
+/// The description of an `Exchange` row split into its tokens, e.g. "Exchanged to DOGE DOGE
+/// Vault" into `To`, `DOGE` and `Vault`.
+#[derive(Debug, PartialEq)]
+pub(crate) struct ExchangeDescription<'a> {
+    pub(crate) direction: Direction,
+    /// The other currency of the exchange, the first token after the prefix.
+    pub(crate) currency: &'a str,
+    pub(crate) pocket: Option<Pocket>,
+}
+
+/// Tokenizes the description of an `Exchange` row in any of the known languages, see
+/// `keywords::of`, or `None` if it is not one. The counter currency is the whole first token after "Exchanged to" or "Exchanged
+/// from", so that e.g. `ETHW` is not taken for `ETH`. The tokens after it only tell the pocket.
+pub(crate) fn parse_exchange<'a>(description: &'a str, keywords: &Keywords) -> Option<ExchangeDescription<'a>> {
+    let prefixes = keywords.exchanged_from.iter().map(|p| (Direction::From, p)).chain(keywords.exchanged_to.iter().map(|p| (Direction::To, p)));
+    let (direction, rest) = prefixes
+        .filter(|(_, prefix)| prefix.chars().next().is_some_and(|c| description.contains(c)))   // Mostly of other languages
+        .filter_map(|(direction, prefix)| {
+            let rest = &description[description.find(prefix)? + prefix.len()..];
+            rest.starts_with(char::is_whitespace).then_some((direction, rest))
+        })
+        .next()?;
+    let mut tokens = tokens(rest);
+    let currency = tokens.next().filter(|t| !t.is_empty())?;
+    let pocket = pocket_in(tokens, keywords);
+    Some(ExchangeDescription{ direction, currency, pocket })
+}
+
+/// The pocket that the description of any row names, e.g. "To DOGE Vault" of a transfer to a
+/// vault, or `None` if it names none.
+pub(crate) fn pocket_of(description: &str, keywords: &Keywords) -> Option<Pocket> {
+    pocket_in(tokens(description), keywords)
+}
+
+fn tokens(s: &str) -> impl Iterator<Item = &str> {
+    s.split_whitespace().map(|t| t.trim_matches(|c: char| !c.is_alphanumeric()))
+}
+
+fn pocket_in<'a>(mut tokens: impl Iterator<Item = &'a str>, keywords: &Keywords) -> Option<Pocket> {
+    let is = |words: &[String], token: &str| words.iter().any(|w| w.eq_ignore_ascii_case(token));
+    tokens.find_map(|t| match t {
+        t if is(&keywords.vault, t) => Some(Pocket::Vault),
+        t if is(&keywords.savings, t) => Some(Pocket::Savings),
+        _ => None,
+    })
+}
+
+/// Returns the English header for a localized header, or the header itself if it is not known.
+pub(crate) fn canonical_header(header: &str) -> &str {
+    HEADER_ALIASES.iter()
+        .find(|(alias, _)| alias.eq_ignore_ascii_case(header.trim()))
+        .map_or(header, |(_, canonical)| canonical)
+}
+
+/// Whether the description is e.g. "Exchanged from SEK", in any of the known languages.
+pub(crate) fn is_exchanged_from(description: &str, keywords: &Keywords) -> bool {
+    keywords.exchanged_from.iter().any(|k| description.contains(k.as_str()))
+}
+
+/// Whether the description is e.g. "Exchanged to BTC", in any of the known languages.
+pub(crate) fn is_exchanged_to(description: &str, keywords: &Keywords) -> bool {
+    keywords.exchanged_to.iter().any(|k| description.contains(k.as_str()))
+}
+
+/// How numbers and dates are written in the reports for people, e.g. `1 234,56` in Swedish and
+/// `1,234.56` and `12/31/2022` in American English. The csv and JSON reports are never
+/// localized, so that tools can read them.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Locale {
+    group_separator: char,
+    decimal_separator: char,
+    dates: DateOrder,
+}
+
+/// The order of the day, month and year of a date, and the separator between them.
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum DateOrder {
+    Iso,                    // 2022-12-31
+    DayMonthYear(char),     // 31.12.2022
+    MonthDayYear,           // 12/31/2022
+}
+
+impl Default for Locale {
+    fn default() -> Self {
+        Locale { group_separator: ',', decimal_separator: '.', dates: DateOrder::Iso }
+    }
+}
+
+/// Languages that group thousands with a space and write decimals after a comma.
+const SPACE_GROUPED: [&str; 14] = ["sv", "fi", "nb", "nn", "no", "fr", "pl", "cs", "sk", "ru", "uk", "et", "lt", "hu"];
+
+/// Languages that group thousands with a dot and write decimals after a comma.
+const DOT_GROUPED: [&str; 12] = ["de", "da", "nl", "it", "es", "pt", "id", "tr", "el", "ro", "sl", "hr"];
+
+/// Languages that write the day first with dots, e.g. 31.12.2022.
+const DOTTED_DATES: [&str; 14] = ["de", "da", "fi", "nb", "nn", "no", "pl", "cs", "sk", "ru", "uk", "et", "tr", "ro"];
+
+/// Languages that write the day first with slashes, e.g. 31/12/2022.
+const SLASHED_DATES: [&str; 8] = ["fr", "it", "es", "pt", "el", "id", "en", "nl"];
+
+impl Locale {
+    /// The locale of a language tag such as `sv-SE`, or a POSIX locale such as `sv_SE.UTF-8`.
+    /// The unknown languages, e.g. `C`, use the default: `1,234.56` and ISO dates.
+    fn of(language: &str, region: &str) -> Locale {
+        let (group_separator, decimal_separator) = match language {
+            l if SPACE_GROUPED.contains(&l) => ('\u{a0}', ','),
+            l if DOT_GROUPED.contains(&l) => ('.', ','),
+            _ => (',', '.'),
+        };
+        let dates = match (language, region) {
+            ("en", "US") => DateOrder::MonthDayYear,
+            ("nl", _) => DateOrder::DayMonthYear('-'),
+            (l, _) if DOTTED_DATES.contains(&l) => DateOrder::DayMonthYear('.'),
+            (l, _) if SLASHED_DATES.contains(&l) => DateOrder::DayMonthYear('/'),
+            _ => DateOrder::Iso,
+        };
+        Locale { group_separator, decimal_separator, dates }
+    }
+
+    /// The locale of the environment, from `LC_ALL`, `LC_NUMERIC` or `LANG`.
+    pub(crate) fn from_env() -> Locale {
+        ["LC_ALL", "LC_NUMERIC", "LANG"].iter()
+            .filter_map(|name| std::env::var(name).ok())
+            .find(|locale| !locale.is_empty())
+            .and_then(|locale| locale.parse().ok())
+            .unwrap_or_default()
+    }
+
+    /// Rewrites a number such as `-1234.56` with the separators of the locale, e.g. `-1 234,56`.
+    pub(crate) fn number(&self, number: &str) -> String {
+        let (sign, digits) = match number.strip_prefix('-') {
+            Some(digits) => ("-", digits),
+            None => ("", number),
+        };
+        let (integer, decimals) = digits.split_once('.').map_or((digits, None), |(i, d)| (i, Some(d)));
+        let mut localized = sign.to_string();
+        for (i, c) in integer.chars().enumerate() {
+            if i > 0 && (integer.len() - i) % 3 == 0 {
+                localized.push(self.group_separator);
+            }
+            localized.push(c);
+        }
+        if let Some(decimals) = decimals {
+            localized.push(self.decimal_separator);
+            localized.push_str(decimals);
+        }
+        localized
+    }
+
+    /// Rewrites a date such as `2022-12-31 10:00:00` in the order of the locale, e.g.
+    /// `31.12.2022 10:00:00`. Anything that doesn't start with an ISO date is left as it is.
+    pub(crate) fn date(&self, date: &str) -> String {
+        let is_iso =
+            date.len() >= 10
+                && date.char_indices().take(10).all(|(i, c)| match i {
+                    4 | 7 => c == '-',
+                    _ => c.is_ascii_digit(),
+                });
+        if !is_iso {
+            return date.to_string();
+        }
+        let (year, month, day, time) = (&date[0..4], &date[5..7], &date[8..10], &date[10..]);
+        match self.dates {
+            DateOrder::Iso => date.to_string(),
+            DateOrder::DayMonthYear(separator) => format!("{d}{s}{m}{s}{y}{t}", d = day, m = month, y = year, s = separator, t = time),
+            DateOrder::MonthDayYear => format!("{}/{}/{}{}", month, day, year, time),
+        }
+    }
+}
+
+impl FromStr for Locale {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(['.', '@']).next().unwrap_or_default().split(['-', '_']);
+        let language = parts.next().unwrap_or_default().to_lowercase();
+        let region = parts.next().unwrap_or_default().to_uppercase();
+        match language.as_str() {
+            "c" | "posix" => Ok(Locale::default()),
+            l if (2..=3).contains(&l.len()) && l.chars().all(|c| c.is_ascii_alphabetic()) => Ok(Locale::of(l, &region)),
+            _ => Err(format!("Unknown locale `{}`. Expected a language and region such as 'sv-SE' or 'en-US'", s)),
+        }
+    }
+}
+
+/// Splits a line on the delimiter, ignoring delimiters within double quotes.
+fn split(line: &str, delimiter: u8) -> Vec<String> {
+    let (mut fields, last, _) =
+        line.chars().fold((vec![], String::new(), false), |(mut fields, mut field, quoted), c| {
+            match c {
+                '"' => (fields, field, !quoted),
+                c if c == delimiter as char && !quoted => {
+                    fields.push(field);
+                    (fields, String::new(), quoted)
+                }
+                c => {
+                    field.push(c);
+                    (fields, field, quoted)
+                }
+            }
+        });
+    fields.push(last);
+    fields
+}
+
+/// Returns the decimal separator of a field if it looks like a number with decimals, i.e. it has
+/// a `.` or `,` that is not followed by exactly three digits, or it has both `.` and `,`.
+fn decimal_separator_of(field: &str) -> Option<char> {
+    let field = field.trim();
+    let is_number =
+        !field.is_empty()
+            && field.chars().any(|c| c.is_ascii_digit())
+            && field.chars().all(|c| {
+                c.is_ascii_digit() || c == '.' || c == ',' || c == '-' || c == '+' || c == '\u{2212}'
+                    || GROUP_SEPARATORS.contains(&c)
+            });
+    if !is_number {
+        return None;
+    }
+    let (position, separator) = field.char_indices().rev().find(|(_, c)| *c == '.' || *c == ',')?;
+    let has_both = field.contains('.') && field.contains(',');
+    let decimals = field[position + 1..].chars().filter(|c| c.is_ascii_digit()).count();
+    match has_both || decimals != 3 {
+        true => Some(separator),
+        false => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::locale::*;
+
+    #[test]
+    fn should_detect_format() {
+        let sample = "Type,Started Date,Description,Amount,Fee,Currency\n\
+                      Exchange,2022-05-02 08:00:00,\"Exchanged to BTC\",-100.00,-1.00,SEK\n\
+                      Exchange,2022-05-02 08:00:00,Exchanged from SEK,0.00010000,0,BTC";
+        assert_eq!(CsvFormat::detect(sample), CsvFormat{ delimiter: b',', decimal_separator: '.' });
+
+        let sample = "Type;Started Date;Description;Amount;Fee;Currency\n\
+                      Exchange;2022-05-02 08:00:00;Exchanged to BTC;-1 234,56;-1,00;SEK\n\
+                      Exchange;2022-05-02 08:00:00;Exchanged from SEK;0,00010000;0;BTC";
+        assert_eq!(CsvFormat::detect(sample), CsvFormat{ delimiter: b';', decimal_separator: ',' });
+
+        let sample = "Type,Started Date,Description,Amount,Fee,Currency\n\
+                      Exchange,2022-05-02 08:00:00,Exchanged to BTC,\"-1.234,56\",\"-1,00\",SEK";
+        assert_eq!(CsvFormat::detect(sample), CsvFormat{ delimiter: b',', decimal_separator: ',' });
+    }
+
+    #[test]
+    fn should_tokenize_exchange_descriptions() {
+        // Descriptions as they are written in exports, and the tokens they are expected to give
+        let keywords = Keywords::shipped();
+        let corpus = [
+            ("Exchanged to BTC", Some((Direction::To, "BTC", None))),
+            ("Exchanged from SEK", Some((Direction::From, "SEK", None))),
+            ("Exchanged to DOGE DOGE Vault", Some((Direction::To, "DOGE", Some(Pocket::Vault)))),
+            ("Exchanged from DOGE DOGE Vault", Some((Direction::From, "DOGE", Some(Pocket::Vault)))),
+            ("Exchanged from EUR Savings", Some((Direction::From, "EUR", Some(Pocket::Savings)))),
+            ("Exchanged to ETHW", Some((Direction::To, "ETHW", None))),
+            ("Exchanged to 1INCH", Some((Direction::To, "1INCH", None))),
+            ("Exchanged to  BTC ", Some((Direction::To, "BTC", None))),
+            ("Exchanged to BTC (Vault)", Some((Direction::To, "BTC", Some(Pocket::Vault)))),
+            ("Växlat till BTC", Some((Direction::To, "BTC", None))),
+            ("Växlat från SEK", Some((Direction::From, "SEK", None))),
+            ("Umgetauscht in ETH", Some((Direction::To, "ETH", None))),
+            ("Umgetauscht von EUR", Some((Direction::From, "EUR", None))),
+            ("Échangé en BTC", Some((Direction::To, "BTC", None))),
+            ("Échangé depuis EUR", Some((Direction::From, "EUR", None))),
+            ("Exchanged to", None),
+            ("Exchanged tothemoon", None),
+            ("Top-Up by *1234", None),
+            ("Klarna", None),
+            ("", None),
+        ];
+        for (description, expected) in corpus {
+            let tokens = parse_exchange(description, &keywords).map(|d| (d.direction, d.currency, d.pocket));
+            assert_eq!(tokens, expected, "{}", description);
+        }
+    }
+
+    #[test]
+    fn should_normalize_decimal() {
+        let format = CsvFormat{ delimiter: b';', decimal_separator: ',' };
+        assert_eq!(format.normalize_decimal("1 234,56"), "1234.56");
+        assert_eq!(format.normalize_decimal("-1.234.567,8"), "-1234567.8");
+        assert_eq!(format.normalize_decimal("\u{2212}12\u{a0}000,5"), "-12000.5");
+
+        let format = CsvFormat::default();
+        assert_eq!(format.normalize_decimal("1,234.56"), "1234.56");
+        assert_eq!(format.normalize_decimal("-0.00010000"), "-0.00010000");
+        assert_eq!(format.normalize_decimal(""), "");
+    }
+
+    #[test]
+    fn should_translate_headers_and_descriptions() {
+        assert_eq!(canonical_header("Startdatum"), "Started Date");
+        assert_eq!(canonical_header("Ursprünglicher Betrag"), "Original Amount");
+        assert_eq!(canonical_header("Montant réglé"), "Settled Amount");
+        assert_eq!(canonical_header("Balance"), "Balance");
+
+        let keywords = Keywords::shipped();
+        assert!(is_exchanged_to("Växlat till BTC", &keywords));
+        assert!(is_exchanged_from("Échangé depuis EUR", &keywords));
+        assert!(!is_exchanged_from("Exchanged to BTC", &keywords));
+    }
+
+    #[test]
+    fn should_localize_numbers_and_dates() {
+        let swedish: Locale = "sv_SE.UTF-8".parse().unwrap();
+        assert_eq!(swedish.number("-1234567.891"), "-1\u{a0}234\u{a0}567,891");
+        assert_eq!(swedish.number("123"), "123");
+        assert_eq!(swedish.date("2022-12-31 10:00:00"), "2022-12-31 10:00:00");
+
+        let german: Locale = "de-DE".parse().unwrap();
+        assert_eq!(german.number("1234.5"), "1.234,5");
+        assert_eq!(german.date("2022-12-31 10:00:00"), "31.12.2022 10:00:00");
+
+        let american: Locale = "en-US".parse().unwrap();
+        assert_eq!(american.number("-123456"), "-123,456");
+        assert_eq!(american.date("2022-12-31"), "12/31/2022");
+        assert_eq!(american.date("2022"), "2022");
+
+        assert_eq!("C.UTF-8".parse(), Ok(Locale::default()));
+        assert!("12-34".parse::<Locale>().is_err());
+    }
+
+    #[test]
+    fn should_write_numbers_and_dates_by_language_and_region() -> Result<(), String> {
+        /*
+         * Given
+         */
+        let tags = ["en-GB", "en-US", "nl_NL.UTF-8", "fr_FR@euro"];
+        let (number, date) = ("-1234.5", "2022-12-31 23:30:00");
+
+        /*
+         * When
+         */
+        let written = tags.iter()
+            .map(|tag| tag.parse::<Locale>().map(|locale| (locale.number(number), locale.date(date))))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        /*
+         * Then
+         */
+        assert_eq!(written, vec![
+            ("-1,234.5".to_string(), "31/12/2022 23:30:00".to_string()),
+            ("-1,234.5".to_string(), "12/31/2022 23:30:00".to_string()),
+            ("-1.234,5".to_string(), "31-12-2022 23:30:00".to_string()),
+            ("-1\u{a0}234,5".to_string(), "31/12/2022 23:30:00".to_string()),
+        ]);
+        Ok(())
+    }
+}
@@ -0,0 +1,501 @@
+use crate::calculator::{self, Consumed, TaxableTransaction};
+use crate::cryptotax::Options;
+use crate::decimal;
+use crate::error::{CryptotaxError, Result};
+use crate::rounding::Rounding;
+use crate::term::{self, Term};
+use crate::transaction::{Currency, Money, Origin, Transaction, TransactionType};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde::Serialize;
+use std::collections::VecDeque;
+use tracing::instrument;
+
+/// An amount of the traded currency bought at `date` for `cost`. Unlike the pooled `Cost`s of
+/// the average cost method, every lot keeps its acquisition date, so that the holding period of
+/// each disposal can be told.
+#[derive(Debug, PartialEq, Clone)]
+pub(crate) struct Lot {
+    pub(crate) date: String,
+    pub(crate) amount: Decimal,
+    pub(crate) cost: Money,     // Negative, like the exchanged amount of a buy
+    pub(crate) fee: Decimal,    // Acquisition fees in the base currency, included in `cost`
+    pub(crate) origin: Origin,
+}
+
+/// The lots of the traded currency that are still held, disposed first in, first out.
+#[derive(Debug)]
+pub(crate) struct LotBook {
+    base: Currency,
+    lots: VecDeque<Lot>,
+}
+
+impl LotBook {
+    pub(crate) fn new(base: Currency) -> LotBook {
+        LotBook{ base, lots: VecDeque::new() }
+    }
+
+    pub(crate) fn add_buy(&mut self, transaction: &Transaction) {
+        let cost = transaction.to_money(&self.base);
+        let fee = match cost.is_cash() { true => transaction.exchanged_fee, false => dec!(0) };
+        let origin = transaction.origin.clone();
+        self.lots.push_back(Lot{ date: transaction.date.clone(), amount: transaction.paid_amount, cost, fee, origin });
+    }
+
+    /// Removes `paid_amount` (negative) from the oldest lots. Returns the removed parts of the
+    /// lots, with their costs in proportion to the removed amounts.
+    pub(crate) fn dispose(&mut self, paid_amount: Decimal) -> Result<Vec<Lot>> {
+        let mut remaining = paid_amount.abs();
+        let mut disposed = vec![];
+        while remaining > dec!(0) {
+            let lot = self.lots.front_mut()
+                .ok_or_else(|| CryptotaxError::Pairing("Not enough lots to dispose of".to_string()))?;
+            let amount = remaining.min(lot.amount);
+            let cost_amount = decimal::share(lot.cost.amount(), amount, lot.amount)?;
+            let cost = lot.cost.deduct(cost_amount);
+            let fee = decimal::share(lot.fee, amount, lot.amount)?;
+            lot.amount -= amount;
+            lot.fee -= fee;
+            remaining -= amount;
+            disposed.push(Lot{ date: lot.date.clone(), amount, cost, fee, origin: lot.origin.clone() });
+            if lot.amount.is_zero() {
+                self.lots.pop_front();
+            }
+        }
+        Ok(disposed)
+    }
+
+    /// The lots that are still held as buys dated at their acquisition, which open an empty book
+    /// with the same lots again, so that a later disposal of them has the same holding period.
+    fn to_carryover(&self, currency: &Currency) -> Vec<Transaction> {
+        self.lots.iter()
+            .map(|l| {
+                let exchanged_currency = match &l.cost {
+                    Money::Cash(cash) => cash.currency.clone(),
+                    Money::Coupon(coupon) => coupon.currency.clone(),
+                };
+                Transaction{
+                    r#type: TransactionType::Buy,
+                    paid_currency: currency.clone(),
+                    paid_amount: l.amount,
+                    exchanged_currency,
+                    exchanged_amount: l.cost.amount(),
+                    date: l.date.clone(),
+                    is_vault: false,
+                    paid_fee: dec!(0),
+                    exchanged_fee: l.fee,
+                    price_source: None,
+                    unit_price: None,
+                    fiat_value: None,
+                    other_fee: None,
+                    origin: Default::default()
+                }
+            })
+            .collect()
+    }
+}
+
+/// A lot that is still held, or with `explore` and its history, a lot that was acquired or the
+/// part of it that a disposal consumed.
+#[derive(Debug, PartialEq, Serialize)]
+pub(crate) struct LotRow {
+    #[serde(rename = "Date")]
+    date: String,                   // Of the acquisition or the disposal
+
+    #[serde(rename = "Event")]
+    event: Event,
+
+    #[serde(rename = "Acquired")]
+    acquired: String,
+
+    #[serde(rename = "Amount")]
+    amount: Decimal,                // Positive, also if disposed
+
+    #[serde(rename = "Unit Cost")]
+    unit_cost: Option<Decimal>,     // None if not valued in the base currency
+
+    #[serde(rename = "Cost")]
+    cost: String,
+
+    #[serde(rename = "File")]
+    file: String,
+
+    #[serde(rename = "Lines")]
+    lines: String,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Serialize)]
+pub(crate) enum Event {
+    Acquired,
+    Disposed,
+    Open,           // Still held after the last transaction
+}
+
+impl LotRow {
+    fn new(date: &str, event: Event, lot: &Lot) -> Result<LotRow> {
+        Ok(LotRow{
+            date: date.to_string(),
+            event,
+            acquired: lot.date.clone(),
+            amount: lot.amount,
+            unit_cost: match (&lot.cost, lot.amount.is_zero()) {
+                (Money::Cash(cash), false) => Some(decimal::div(-cash.amount, lot.amount)?),
+                _ => None,
+            },
+            cost: calculator::costs_to_string(std::slice::from_ref(&lot.cost)),
+            file: lot.origin.file.clone(),
+            lines: lot.origin.lines.iter().map(|l| l.to_string()).collect::<Vec<String>>().join(" "),
+        })
+    }
+
+    /// The row of an acquisition, or the part of it that a disposal consumed, which may have been
+    /// pooled with others, see `section104::explore`. Pooled parts have no acquisition date.
+    pub(crate) fn of(date: &str, event: Event, consumed: &Consumed) -> Result<LotRow> {
+        let (files, lines) = consumed.origins.with(|origins| {
+            let mut files: Vec<&str> = origins.iter().map(|o| o.file.as_str()).collect();
+            files.dedup();
+            (files.join(" "), origins.iter().flat_map(|o| &o.lines).map(|l| l.to_string()).collect::<Vec<String>>().join(" "))
+        });
+        Ok(LotRow{
+            date: date.to_string(),
+            event,
+            acquired: consumed.acquired.clone().unwrap_or_default(),
+            amount: consumed.amount,
+            unit_cost: match (consumed.costs.iter().all(Money::is_cash), consumed.amount.is_zero()) {
+                (true, false) => Some(decimal::div(-decimal::sum(consumed.costs.iter().map(|c| c.amount()))?, consumed.amount)?),
+                _ => None,
+            },
+            cost: calculator::costs_to_string(&consumed.costs),
+            file: files,
+            lines,
+        })
+    }
+
+    /// Rounds the costs in the base currency for a report.
+    pub(crate) fn round(&mut self, rounding: &Rounding) {
+        self.unit_cost = self.unit_cost.map(|c| rounding.gain(c));
+    }
+}
+
+/// Runs the transactions through a `LotBook`, first in, first out, and returns the lots that are
+/// still held, oldest first. With `history`, they come after a row for every lot that was
+/// acquired and for every part of a lot that a disposal consumed, in the order they happened.
+pub(crate) fn explore(txns: &[Transaction], base: &Currency, history: bool) -> Result<Vec<LotRow>> {
+    let mut book = LotBook::new(base.clone());
+    let mut rows = vec![];
+    for t in txns {
+        match t.r#type {
+            TransactionType::Buy | TransactionType::Income => {
+                book.add_buy(t);
+                rows.extend(book.lots.back().map(|l| LotRow::new(&t.date, Event::Acquired, l)).transpose()?);
+            }
+            TransactionType::AddLiquidity | TransactionType::RemoveLiquidity if t.paid_amount.is_sign_positive() => {
+                book.add_buy(t);
+                rows.extend(book.lots.back().map(|l| LotRow::new(&t.date, Event::Acquired, l)).transpose()?);
+            }
+            TransactionType::Transfer | TransactionType::Margin | TransactionType::Futures | TransactionType::Short => {},
+            _ => for l in book.dispose(t.paid_amount)? {
+                rows.push(LotRow::new(&t.date, Event::Disposed, &l)?);
+            },
+        }
+    }
+    if !history {
+        rows.clear();
+    }
+    for l in &book.lots {
+        rows.push(LotRow::new(&l.date, Event::Open, l)?);
+    }
+    Ok(rows)
+}
+
+/// The disposals of `calculate`, split by whether the gains are taxed.
+#[derive(Debug, Default)]
+pub(crate) struct Disposals {
+    pub(crate) taxables: Vec<TaxableTransaction>,
+    /// Disposals of lots held for more than a year, if the jurisdiction exempts them.
+    pub(crate) exempt: Vec<TaxableTransaction>,
+    /// The lots that are still held, to be carried over to the next calculation, see
+    /// `Calculation::carryover`.
+    pub(crate) carryover: Vec<Transaction>,
+}
+
+/// Disposes the lots first in, first out. A disposal of lots with different holding periods is
+/// split in two, with the income in proportion to the amounts.
+#[instrument(name = "calculate", skip_all, fields(matching = "fifo", jurisdiction = %options.jurisdiction))]
+pub(crate) async fn calculate(txns: &[Transaction], base: &Currency, options: &Options) -> Result<Disposals> {
+    let jurisdiction = &options.jurisdiction;
+    let mut book = LotBook::new(base.clone());
+    let mut disposals = Disposals::default();
+    for t in txns {
+        match t.r#type {
+            TransactionType::Buy | TransactionType::Income => book.add_buy(t),
+            TransactionType::AddLiquidity | TransactionType::RemoveLiquidity if t.paid_amount.is_sign_positive() => book.add_buy(t),
+            TransactionType::Transfer | TransactionType::Margin | TransactionType::Futures | TransactionType::Short => {},
+            _ => {
+                let disposed = book.dispose(t.paid_amount)?;
+                let write_off = matches!(t.r#type, TransactionType::Lost | TransactionType::Stolen)
+                    && options.claim_write_offs;
+                if !jurisdiction.recognizes_gain(&t.r#type) && !write_off {
+                    continue;
+                }
+                let (exempt, taxable): (Vec<Lot>, Vec<Lot>) =
+                    disposed.into_iter()
+                        .partition(|l| jurisdiction.exempts_long_term_gains() && term::ONE_YEAR.term_of(&l.date, &t.date) == Term::Long);
+                let mut income = match write_off {
+                    true => Money::new_cash(base.clone(), dec!(0)),
+                    false => t.to_money(base),
+                };
+                let mut disposal_fee = match income.is_cash() && !write_off {
+                    true => t.exchanged_fee,
+                    false => dec!(0),
+                };
+                let (part, total) = (sum(&taxable)?, t.paid_amount.abs());
+                if !taxable.is_empty() {
+                    let taxable_income = income.deduct(decimal::div(decimal::mul(income.amount(), part)?, total)?);
+                    let taxable_fee = decimal::div(decimal::mul(disposal_fee, part)?, total)?;
+                    disposal_fee = decimal::sub(disposal_fee, taxable_fee)?;
+                    disposals.taxables.push(to_taxable(t, &taxable, taxable_income, taxable_fee)?);
+                }
+                if !exempt.is_empty() {
+                    disposals.exempt.push(to_taxable(t, &exempt, income, disposal_fee)?);
+                }
+            }
+        }
+    }
+    disposals.carryover = book.to_carryover(&txns.first().map(|t| t.paid_currency.clone()).unwrap_or_default());
+    Ok(disposals)
+}
+
+fn to_taxable(transaction: &Transaction, lots: &[Lot], income: Money, disposal_fee: Decimal) -> Result<TaxableTransaction> {
+    let costs: Vec<Money> = lots.iter().map(|l| l.cost.clone()).collect();
+    let net_income = income.to_net_income(&costs)?;
+    Ok(TaxableTransaction{
+        r#type: transaction.r#type.clone(),
+        date: transaction.date.clone(),
+        currency: transaction.paid_currency.clone(),
+        amount: -sum(lots)?,
+        income,
+        costs,
+        net_income,
+        acquisition_fee: decimal::sum(lots.iter().map(|l| l.fee))?,
+        disposal_fee,
+        origin: transaction.origin.clone(),
+        consumed: lots.iter()
+            .map(|l| Consumed{ amount: l.amount, costs: vec![l.cost.clone()], origins: vec![l.origin.clone()].into(), acquired: Some(l.date.clone()) })
+            .collect()
+    })
+}
+
+fn sum(lots: &[Lot]) -> Result<Decimal> {
+    decimal::sum(lots.iter().map(|l| l.amount))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cryptotax::Jurisdiction;
+    use crate::lots::*;
+    use crate::transaction::Cash;
+    use futures::executor::block_on;
+    use std::error::Error;
+
+    #[test]
+    fn should_dispose_first_in_first_out() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let mut book = LotBook::new("USD".to_string());
+        let buy = |amount, cost, date: &str| Transaction{
+            r#type: TransactionType::Buy,
+            paid_currency: "BTC".to_string(),
+            paid_amount: amount,
+            exchanged_currency: "USD".to_string(),
+            exchanged_amount: cost,
+            date: date.to_string(),
+            is_vault: false,
+            paid_fee: dec!(0),
+            exchanged_fee: dec!(0),
+            price_source: None,
+            unit_price: None,
+            fiat_value: None,
+            other_fee: None,
+            origin: Default::default()
+        };
+        book.add_buy(&buy(dec!(1), dec!(-10000), "2020-01-01 10:00:00"));
+        book.add_buy(&buy(dec!(1), dec!(-30000), "2021-01-01 10:00:00"));
+
+        /*
+         * When
+         */
+        let disposed = book.dispose(dec!(-1.5))?;
+
+        /*
+         * Then
+         */
+        assert_eq!(disposed, vec![
+            Lot{
+                date: "2020-01-01 10:00:00".to_string(),
+                amount: dec!(1),
+                cost: Money::Cash(Cash{ currency: "USD".to_string(), amount: dec!(-10000) }),
+                fee: dec!(0),
+                origin: Default::default()
+            },
+            Lot{
+                date: "2021-01-01 10:00:00".to_string(),
+                amount: dec!(0.5),
+                cost: Money::Cash(Cash{ currency: "USD".to_string(), amount: dec!(-15000) }),
+                fee: dec!(0),
+                origin: Default::default()
+            },
+        ]);
+        assert_eq!(book.dispose(dec!(-0.5))?.len(), 1);
+        assert!(book.dispose(dec!(-0.1)).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn should_explore_open_lots_and_their_history() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let trade = |builder: crate::transaction::TransactionBuilder, amount, exchanged_amount, date: &str| {
+            builder.currency("BTC").amount(amount).exchanged_currency("USD").exchanged_amount(exchanged_amount).date(date).build()
+        };
+        let txns = vec![
+            trade(Transaction::buy(), dec!(1), dec!(-10000), "2020-01-01 10:00:00")?,
+            trade(Transaction::buy(), dec!(2), dec!(-60000), "2021-01-01 10:00:00")?,
+            trade(Transaction::sell(), dec!(-1.5), dec!(60000), "2021-06-01 10:00:00")?,
+        ];
+
+        /*
+         * When
+         */
+        let open = explore(&txns, &"USD".to_string(), false)?;
+        let history = explore(&txns, &"USD".to_string(), true)?;
+
+        /*
+         * Then
+         */
+        let summary = |rows: &[LotRow]| -> Vec<(Event, String, Decimal, Option<Decimal>)> {
+            rows.iter().map(|r| (r.event, r.acquired.clone(), r.amount, r.unit_cost)).collect()
+        };
+        assert_eq!(summary(&open), vec![
+            (Event::Open, "2021-01-01 10:00:00".to_string(), dec!(1.5), Some(dec!(30000))),
+        ]);
+        assert_eq!(summary(&history), vec![
+            (Event::Acquired, "2020-01-01 10:00:00".to_string(), dec!(1), Some(dec!(10000))),
+            (Event::Acquired, "2021-01-01 10:00:00".to_string(), dec!(2), Some(dec!(30000))),
+            (Event::Disposed, "2020-01-01 10:00:00".to_string(), dec!(1), Some(dec!(10000))),
+            (Event::Disposed, "2021-01-01 10:00:00".to_string(), dec!(0.5), Some(dec!(30000))),
+            (Event::Open, "2021-01-01 10:00:00".to_string(), dec!(1.5), Some(dec!(30000))),
+        ]);
+        Ok(())
+    }
+
+    #[test]
+    fn should_exempt_lots_held_over_a_year() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let txn = |r#type, paid_amount, exchanged_amount, date: &str| Transaction{
+            r#type,
+            paid_currency: "BTC".to_string(),
+            paid_amount,
+            exchanged_currency: "EUR".to_string(),
+            exchanged_amount,
+            date: date.to_string(),
+            is_vault: false,
+            paid_fee: dec!(0),
+            exchanged_fee: dec!(0),
+            price_source: None,
+            unit_price: None,
+            fiat_value: None,
+            other_fee: None,
+            origin: Default::default()
+        };
+        let txns = vec![
+            txn(TransactionType::Buy, dec!(1), dec!(-10000), "2020-01-15 10:00:00"),
+            txn(TransactionType::Buy, dec!(1), dec!(-30000), "2021-01-15 10:00:00"),
+            txn(TransactionType::Sell, dec!(-1.5), dec!(60000), "2021-06-01 10:00:00"),
+        ];
+        let options = Options{ jurisdiction: Jurisdiction::Germany, ..Default::default() };
+
+        /*
+         * When
+         */
+        let disposals = block_on(calculate(&txns, &"EUR".to_string(), &options))?;
+
+        /*
+         * Then
+         */
+        assert_eq!(disposals.taxables.len(), 1);
+        assert_eq!(disposals.taxables[0].amount, dec!(-0.5));
+        assert_eq!(disposals.taxables[0].income, Money::new_cash("EUR".to_string(), dec!(20000)));
+        assert_eq!(disposals.taxables[0].net_income, Some(dec!(5000)));
+        assert_eq!(disposals.exempt.len(), 1);
+        assert_eq!(disposals.exempt[0].amount, dec!(-1));
+        assert_eq!(disposals.exempt[0].net_income, Some(dec!(30000)));
+        Ok(())
+    }
+
+    #[test]
+    fn should_carry_over_lots_with_their_acquisition_dates() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let txn = |r#type, paid_amount, exchanged_amount, date: &str| Transaction{
+            r#type,
+            paid_currency: "BTC".to_string(),
+            paid_amount,
+            exchanged_currency: "EUR".to_string(),
+            exchanged_amount,
+            date: date.to_string(),
+            is_vault: false,
+            paid_fee: dec!(0),
+            exchanged_fee: dec!(0),
+            price_source: None,
+            unit_price: None,
+            fiat_value: None,
+            other_fee: None,
+            origin: Default::default()
+        };
+        let year_2021 = || vec![
+            txn(TransactionType::Buy, dec!(1), dec!(-10000), "2021-03-01 10:00:00"),
+            txn(TransactionType::Buy, dec!(1), dec!(-30000), "2021-11-01 10:00:00"),
+            txn(TransactionType::Sell, dec!(-0.5), dec!(25000), "2021-12-01 10:00:00"),
+        ];
+        let year_2022 = || vec![
+            txn(TransactionType::Sell, dec!(-1), dec!(20000), "2022-06-01 10:00:00"),   // 0.5 held over a year, 0.5 not
+        ];
+        let eur = "EUR".to_string();
+
+        /*
+         * When
+         */
+        let from_carryover_and_history = |jurisdiction| -> Result<_, Box<dyn Error>> {
+            let options = Options{ jurisdiction, ..Default::default() };
+            let carryover = block_on(calculate(&year_2021(), &eur, &options))?.carryover;
+            let mut txns = year_2022();
+            txns.extend(carryover.clone());
+            txns.sort_by(Transaction::cmp_by_date);
+            let from_carryover = block_on(calculate(&txns, &eur, &options))?;
+            let from_history = block_on(calculate(&[year_2021(), year_2022()].concat(), &eur, &options))?;
+            Ok((carryover, from_carryover, from_history))
+        };
+        let (carryover, us, us_history) = from_carryover_and_history(Jurisdiction::UnitedStates)?;
+        let (_, de, de_history) = from_carryover_and_history(Jurisdiction::Germany)?;
+
+        /*
+         * Then
+         */
+        let lots: Vec<_> = carryover.iter().map(|t| (t.date.as_str(), t.paid_amount, t.exchanged_amount)).collect();
+        assert_eq!(lots, vec![("2021-03-01 10:00:00", dec!(0.5), dec!(-5000)), ("2021-11-01 10:00:00", dec!(1), dec!(-30000))]);
+        assert_eq!(us.taxables, us_history.taxables[1..]);
+        let acquired: Vec<_> = us.taxables[0].consumed.iter().map(|c| c.acquired.as_deref()).collect();
+        assert_eq!(acquired, vec![Some("2021-03-01 10:00:00"), Some("2021-11-01 10:00:00")]);
+        assert_eq!(de.taxables, de_history.taxables[1..]);
+        assert_eq!(de.exempt, de_history.exempt);
+        assert_eq!(de.exempt.len(), 1);
+        assert_eq!(de.exempt[0].amount, dec!(-0.5));
+        Ok(())
+    }
+}
@@ -1,12 +1,26 @@
 use anyhow::Context;
 use cryptotax::cryptotax;
-use clap::Parser;
+use ::cryptotax::cryptotax::{BasisScope, Breakdown, CryptotaxError, Dust, Equivalent, Exchange, ExitCode, FeePolicy, FxSource, JournalFormat, Jurisdiction, Liquidity, Locale, Options, Precision, PriceSource, Rebates, ReportFormat, ReportRegistry, Rounding, Search, StablecoinGains, StoreAccess, Tz, WarningFormat};
+use clap::{Args, Parser, Subcommand};
+use rust_decimal::Decimal;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+use tracing_subscriber::fmt::format::FmtSpan;
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::{fmt, EnvFilter};
 
 /// Search for currency exchanges in a Revolut csv file and output a new csv containing the tax information.
 #[derive(Parser)]
+#[clap(subcommand_negates_reqs = true)]
 struct Cli {
-    #[clap(parse(from_os_str), help = "Path to the Revolut transactions_history.csv file that contains transactions.")]
-    path: std::path::PathBuf,
+    #[clap(subcommand)]
+    command: Option<Command>,
+
+    #[clap(parse(from_os_str), required = true, help = "Path to the Revolut transactions_history.csv file that contains transactions, or - to read it from stdin.")]
+    path: Option<std::path::PathBuf>,
 
     #[clap(short, long, help = "The traded currency for which you report the tax. 'ALL' for all currencies when --exchanges is used")]
     currency: Option<String>,
@@ -20,30 +34,978 @@ struct Cli {
     #[clap(short, long, help = "Merge both sides of a currency 'Exchange' into a single line. Print to stdout a new csv file")]
     transactions: bool,
 
+    #[clap(flatten)]
+    options: OptionArgs,
+
+    #[clap(flatten)]
+    logging: LoggingArgs,
+
+    #[clap(flatten)]
+    store: StoreArgs,
+
+    #[clap(long, global = true, help = "Forbid any network access. Prices come only from --price-table and FX rates only from the store, and the run fails with the list of what is missing")]
+    offline: bool,
+}
+
+/// Settings of the log and the warnings, which are written to stderr. `RUST_LOG` overrides the
+/// level of the log.
+#[derive(Args)]
+struct LoggingArgs {
+    #[clap(short, long, global = true, parse(from_occurrences), help = "Log the phases and their timings with -v, the details of every transaction with -vv")]
+    verbose: u64,
+
+    #[clap(short, long, global = true, conflicts_with = "verbose", help = "Only log errors")]
+    quiet: bool,
+
+    #[clap(long, global = true, parse(from_os_str), help = "Also write the log to a file, with the details of every transaction, e.g. to attach to a bug report")]
+    log_file: Option<PathBuf>,
+
+    #[clap(long, global = true, default_value = "text", help = "How the warnings are printed at the end of the run: 'TEXT', or 'JSON' for tools. Text warnings are left out with --quiet")]
+    warnings: WarningFormat,
+
+    #[clap(long, global = true, help = "Print the exit code, the error and the warnings of the run as one JSON object to stderr, for automation. Exit codes: 0 ok, 1 warnings, 2 data errors, 3 config errors")]
+    json_errors: bool,
+}
+
+/// Settings of the store that apply to every command.
+#[derive(Args)]
+struct StoreArgs {
+    #[clap(long, global = true, parse(from_os_str), conflicts_with = "store-passphrase", help = "Path to a file with the passphrase that the store is encrypted with. Needs cryptotax built with --features encryption")]
+    store_key_file: Option<PathBuf>,
+
+    #[clap(long, global = true, help = "Ask for the passphrase that the store is encrypted with. A new store is encrypted with it. Needs cryptotax built with --features encryption")]
+    store_passphrase: bool,
+
+    #[clap(long, global = true, help = "The portfolio of the store to import to and calculate from, e.g. of a spouse or a business, whose lots are kept apart from the others. Default: the default portfolio")]
+    portfolio: Option<String>,
+}
+
+impl StoreArgs {
+    /// How the stores of the run are opened, with the passphrase read from the key file or
+    /// prompted for.
+    fn access(&self) -> Result<StoreAccess, CryptotaxError> {
+        let portfolio = self.portfolio.clone().unwrap_or_default();
+        let passphrase = match (&self.store_key_file, self.store_passphrase) {
+            (Some(path), _) => std::fs::read_to_string(path)
+                .map_err(|e| CryptotaxError::Config(format!("Could not read the passphrase from file `{:?}`: {}", path, e)))?
+                .trim_end_matches(['\r', '\n'])
+                .to_string(),
+            (None, true) => rpassword::prompt_password("Passphrase of the store: ")
+                .map_err(|e| CryptotaxError::Config(format!("Could not read the passphrase of the store: {}", e)))?,
+            (None, false) => return Ok(StoreAccess{ passphrase: None, portfolio }),
+        };
+        if passphrase.is_empty() {
+            return Err(CryptotaxError::Config("The passphrase of the store is empty".to_string()));
+        }
+        Ok(StoreAccess{ passphrase: Some(passphrase), portfolio })
+    }
+}
+
+impl LoggingArgs {
+    fn level(&self) -> &str {
+        match (self.quiet, self.verbose) {
+            (true, _) => "error",
+            (false, 0) => "warn",
+            (false, 1) => "info",
+            (false, 2) => "debug",
+            (false, _) => "trace",
+        }
+    }
+
+    /// Logs the closing of the spans of the phases, e.g. `parse` and `calculate`, with their timings.
+    fn init(&self) -> Result<(), CryptotaxError> {
+        let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(self.level()));
+        let stderr = fmt::layer()
+            .with_writer(std::io::stderr)
+            .with_span_events(FmtSpan::CLOSE)
+            .with_filter(filter);
+        let file = match &self.log_file {
+            Some(path) => {
+                let file = File::create(path)
+                    .map_err(|e| CryptotaxError::Config(format!("Could not create log file `{:?}`: {}", path, e)))?;
+                let level = match self.verbose { 0..=2 => "debug", _ => "trace" };
+                let layer = fmt::layer()
+                    .with_ansi(false)
+                    .with_writer(Mutex::new(file))
+                    .with_span_events(FmtSpan::CLOSE)
+                    .with_filter(EnvFilter::new(level));
+                Some(layer)
+            }
+            None => None,
+        };
+        tracing_subscriber::registry().with(stderr).with(file).try_init()
+            .map_err(|e| CryptotaxError::Config(format!("Could not set up logging: {}", e)))
+    }
+}
+
+/// Settings that apply to the calculations, see `cryptotax::Options`.
+#[derive(Args)]
+struct OptionArgs {
+    #[clap(short, long, help = "The country whose tax rules are applied: 'SE', 'US', 'UK' or 'DE'. Default: 'SE'")]
+    jurisdiction: Option<Jurisdiction>,
+
+    #[clap(long, parse(from_os_str), help = "Path to a csv file with the columns 'Date', 'Currency', 'Type' and 'Value' that changes the type of the matching transactions, e.g. to 'Gift', 'Donation', 'Lost' or 'Stolen'")]
+    overrides: Option<std::path::PathBuf>,
+
+    #[clap(long, parse(from_os_str), help = "Path to a csv file with the columns 'Date', 'From', 'To' and 'Ratio' of currencies that were renamed or redenominated, whose holdings carry over to the new currency")]
+    renames: Option<std::path::PathBuf>,
+
+    #[clap(long, parse(from_os_str), help = "Path to a TOML file of rules that classify, tag or name the counterparty of the transactions that match a description regex, type, currency or amount range")]
+    rules: Option<std::path::PathBuf>,
+
+    #[clap(long, help = "Claim currencies marked as 'Lost' or 'Stolen' in the overrides file as a loss, even if the jurisdiction does not recognize it")]
+    claim_write_offs: bool,
+
+    #[clap(long, help = "Print the acquisition and disposal fees in the base currency as separate columns. The fees are still treated by --fee-policy")]
+    separate_fees: bool,
+
+    #[clap(long, help = "How the fees in the base currency are treated: 'CAPITALIZE' into the costs, with the income gross, 'DEDUCT' the fee of a disposal from its income, or 'SEPARATE' from the income and the costs, in columns of their own. Default: 'CAPITALIZE' in the UK, otherwise 'DEDUCT'")]
+    fee_policy: Option<FeePolicy>,
+
+    #[clap(long, help = "Allow a fiat currency such as 'EUR' as the traded currency, to calculate the currency exchange gains of the exchanges between fiat currencies")]
+    currency_gains: bool,
+
+    #[clap(short, long, parse(from_os_str), default_value = "cryptotax.db", help = "Path to the store that snapshots are saved to and read from, and that caches the FX rates")]
+    store: std::path::PathBuf,
+
+    #[clap(long, help = "Start from the costs saved with 'snapshot create' at the end of the given year, and skip the transactions until then")]
+    from_snapshot: Option<String>,
+
+    #[clap(long, help = "Convert amounts exchanged in other fiat currencies to the base currency with the daily reference rates of 'ECB' or 'RIKSBANK'")]
+    fx: Option<FxSource>,
+
+    #[clap(long, use_value_delimiter = true, help = "Value crypto-to-crypto trades at the market price from 'COINGECKO', 'CRYPTOCOMPARE' or 'KRAKEN', e.g. 'KRAKEN,COINGECKO' to fall back to CoinGecko")]
+    prices: Vec<PriceSource>,
+
+    #[clap(long, parse(from_os_str), help = "Path to a csv file with the columns 'currency', 'date', 'price' and 'base' with prices of e.g. delisted tokens, used before the price sources")]
+    price_table: Option<std::path::PathBuf>,
+
+    #[clap(long, help = "Warn of the trades whose price, implied by their rows, is more than this many percent from the market price of --prices or --price-table, e.g. because rows were paired wrongly. Default: 5")]
+    max_price_deviation: Option<Decimal>,
+
+    #[clap(long, help = "How the amounts in the base currency are rounded in the report: 'FULL', a number of decimals, or 'FAVORABLE' (alias 'K4') for whole units in the taxpayer's favor. Default: 'FULL', '2' for form8949, 'FAVORABLE' for hmrc")]
+    rounding: Option<Rounding>,
+
+    #[clap(long, help = "The rate in percent that the realized gains are taxed at, for the estimated tax per year of the summary and the workbook, e.g. '30'. Losses are deducted at the share of the jurisdiction, 70% for SE. Default: '30' for SE, none otherwise")]
+    tax_rate: Option<Decimal>,
+
+    #[clap(long, help = "Continue when more is disposed than is held, e.g. because earlier buys are missing from the export, with a zero cost for the missing amount")]
+    assume_zero_cost: bool,
+
+    #[clap(long, parse(from_os_str), help = "Path to a TOML file that tells which columns of the csv file of another exchange are the date, type, currency, amount, quote currency, quote amount, fee and fee currency")]
+    mapping: Option<std::path::PathBuf>,
+
+    #[clap(long, help = "How adding and removing liquidity of a pool is taxed: 'DISPOSAL' of the currencies for the LP tokens and back, or 'DEPOSIT' that only realizes the impermanent loss. Default: 'DEPOSIT' for DE, 'DISPOSAL' otherwise")]
+    liquidity: Option<Liquidity>,
+
+    #[clap(long, help = "Whether the cost basis is one pool for all accounts, 'UNIVERSAL', or a pool for each account, 'PER-ACCOUNT', e.g. for the wallet by wallet basis of the US from 2025. An account is a file, e.g. each csv file of a zip archive. Default: 'UNIVERSAL'")]
+    basis_scope: Option<BasisScope>,
+
+    #[clap(long, use_value_delimiter = true, help = "Pool a currency with another that is the same asset for the cost basis, e.g. 'WETH=ETH'. Added to the defaults: WETH, WETH.e, BTC.b, USDC.e, USDbC and USDT.e, except for UK")]
+    equivalent: Vec<Equivalent>,
+
+    #[clap(long, use_value_delimiter = true, help = "The number of decimals of a currency, e.g. 'BTC=8', that its amounts are rounded to when read and compared at. Defaults: 8 for BTC, 18 for ETH, 6 for USDT and USDC, 2 for fiat, 0 for NFTs and 18 for other tokens")]
+    precision: Vec<Precision>,
+
+    #[clap(long, help = "How fee rebates and referral bonuses are taxed: 'INCOME' at their market value, or 'DISCOUNT' on the fees, at no cost, where the jurisdiction allows it. Default: 'INCOME'")]
+    rebates: Option<Rebates>,
+
+    #[clap(long, help = "Don't pool the wrapped and bridged currencies by default, only the ones given with --equivalent")]
+    no_default_equivalents: bool,
+
+    #[clap(long, help = "How the gains of stablecoins are taxed: 'PRECISE' like any other currency, or 'NEGLIGIBLE' at a cost equal to the income. Default: 'PRECISE'")]
+    stablecoin_gains: Option<StablecoinGains>,
+
+    #[clap(long, use_value_delimiter = true, help = "The stablecoins of --stablecoin-gains. Default: 'USDC,USDT,EURS'")]
+    stablecoins: Vec<String>,
+
+    #[clap(long, help = "Buys, sells and income of a smaller amount of the traded currency are dust, e.g. of a sweep of small balances. Without it, only zero amounts are left out")]
+    dust_threshold: Option<Decimal>,
+
+    #[clap(long, default_value = "aggregate", help = "What is done with dust below --dust-threshold: 'AGGREGATE' into one transaction per day, type and exchanged currency, or 'IGNORE'")]
+    dust: Dust,
+
+    #[clap(long, help = "Check that every exchange row is paired once, that the gains are the proceeds less the costs, and that the costs add up to the holding, and stop at the first that doesn't")]
+    check: bool,
+
+    #[clap(long, help = "Memory-map the Revolut account statements instead of reading them, for very large files. Falls back to reading a file that can't be mapped or isn't a regular file. The files must not change during the run: a file truncated while mapped kills the run with SIGBUS")]
+    mmap: bool,
+
+    #[clap(long, parse(from_os_str), help = "Path to a TOML file of the words of the descriptions of the Revolut account statements, e.g. 'Exchanged to', in a language or a version of the app that is not supported yet. Used on top of the shipped words")]
+    keywords: Option<PathBuf>,
+
+    #[clap(long, help = "Keep the rows of a Revolut account statement that look exported twice, with the same type, time, amount, currency and balance, instead of collapsing them")]
+    no_dedup: bool,
+
+    #[clap(long, parse(from_os_str), help = "Path to the file that the report is written to instead of stdout. The file is only replaced once the whole report is written")]
+    output: Option<std::path::PathBuf>,
+
+    #[clap(long, default_value = "csv", help = "How the report is written: 'CSV', 'TABLE' with aligned columns, the thousands separators of the locale and colored gains and losses, to read in a terminal, or 'JSON' for tools. Binaries built on the library can register more formats of the tax report")]
+    format: ReportFormat,
+
+    #[clap(long, help = "How the numbers and dates of --format table are written, e.g. 'sv-SE' for 1 234,56 or 'en-US' for 12/31/2022. The csv reports are never localized. Default: the locale of the environment")]
+    locale: Option<Locale>,
+
+    #[clap(long, help = "The timezone of the dates that the transactions are assigned to tax years and matched with daily prices by, e.g. 'Europe/Stockholm' or 'UTC'. A trade at 23:30 on Dec 31 UTC is in the next year in Stockholm. Default: the timezone of the jurisdiction, 'America/New_York' in the US")]
+    timezone: Option<Tz>,
+}
+
+impl OptionArgs {
+    fn into_options(self, access: &StoreAccess, offline: bool) -> Options {
+        Options{
+            jurisdiction: self.jurisdiction.unwrap_or_default(),
+            overrides: self.overrides,
+            renames: self.renames,
+            rules: self.rules,
+            claim_write_offs: self.claim_write_offs,
+            separate_fees: self.separate_fees,
+            fee_policy: self.fee_policy,
+            currency_gains: self.currency_gains,
+            store: Some(self.store),
+            store_access: access.clone(),
+            from_snapshot: self.from_snapshot,
+            fx: self.fx,
+            prices: self.prices,
+            price_table: self.price_table,
+            offline,
+            max_price_deviation: self.max_price_deviation,
+            rounding: self.rounding.unwrap_or_default(),
+            tax_rate: self.tax_rate,
+            assume_zero_cost: self.assume_zero_cost,
+            mapping: self.mapping,
+            liquidity: self.liquidity,
+            rebates: self.rebates,
+            precisions: self.precision,
+            basis_scope: self.basis_scope,
+            equivalents: self.equivalent,
+            no_default_equivalents: self.no_default_equivalents,
+            stablecoin_gains: self.stablecoin_gains,
+            stablecoins: self.stablecoins,
+            dust_threshold: self.dust_threshold,
+            dust: self.dust,
+            check: self.check,
+            mmap: self.mmap,
+            keywords: self.keywords,
+            no_dedup: self.no_dedup,
+            output: self.output,
+            format: self.format,
+            locale: self.locale,
+            timezone: self.timezone,
+            reports: ReportRegistry::new(),
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Print the realized gains per year and the unrealized gains of the current holdings.
+    Summary {
+        #[clap(parse(from_os_str), help = "Path to the Revolut transactions_history.csv file that contains transactions, or - to read it from stdin.")]
+        path: std::path::PathBuf,
+
+        #[clap(short, long, help = "The traded currency for which you report the tax.")]
+        currency: String,
+
+        #[clap(short, long, help = "Base currency. The currency in which you report the tax. Default: 'SEK'")]
+        base: Option<String>,
+
+        #[clap(short, long, help = "Latest market price of the traded currency in the base currency. Default: the price of the latest trade")]
+        price: Option<Decimal>,
+
+        #[clap(long, default_value = "year", help = "Also break the realized gains of each year down by 'QUARTER' or 'MONTH', e.g. for preliminary tax payments, or only by 'YEAR'")]
+        breakdown: Breakdown,
+
+        #[clap(flatten)]
+        options: OptionArgs,
+    },
+    /// Print the realized gains, the holding, its cost basis and its value over time, a row per day with
+    /// transactions, to plot with --format json or a spreadsheet, and optionally draw them as an SVG chart.
+    Chart {
+        #[clap(parse(from_os_str), help = "Path to the export file or the store that contains transactions.")]
+        path: std::path::PathBuf,
+
+        #[clap(short, long, help = "The traded currency for which you report the tax.")]
+        currency: String,
+
+        #[clap(short, long, help = "Base currency. The currency in which you report the tax. Default: 'SEK'")]
+        base: Option<String>,
+
+        #[clap(long, parse(from_os_str), help = "Also draw the realized gains, the cost basis and the value as lines of an SVG chart to this file")]
+        svg: Option<std::path::PathBuf>,
+
+        #[clap(flatten)]
+        options: OptionArgs,
+    },
+    /// Compare the transactions and the taxable gains of two export files, or of an export file and the store,
+    /// e.g. after a corrected export was issued.
+    Diff {
+        #[clap(parse(from_os_str), help = "Path to the old export file or the store.")]
+        old: std::path::PathBuf,
+
+        #[clap(parse(from_os_str), help = "Path to the new export file or the store.")]
+        new: std::path::PathBuf,
+
+        #[clap(short, long, help = "The traded currency for which you report the tax.")]
+        currency: String,
+
+        #[clap(short, long, help = "Base currency. The currency in which you report the tax. Default: 'SEK'")]
+        base: Option<String>,
+
+        #[clap(flatten)]
+        options: OptionArgs,
+    },
+    /// Match the fiat top-ups and withdrawals of a Revolut account statement with the transfers of a bank statement, and
+    /// print the ones without a match, which suggest a missing export.
+    MatchBank {
+        #[clap(parse(from_os_str), help = "Path to the Revolut account statement.")]
+        path: std::path::PathBuf,
+
+        #[clap(parse(from_os_str), help = "Path to the csv statement of the bank account.")]
+        statement: std::path::PathBuf,
+
+        #[clap(short, long, help = "The fiat currency of the bank account. Default: 'SEK'")]
+        currency: Option<String>,
+
+        #[clap(flatten)]
+        options: OptionArgs,
+    },
+    /// Compare the disposals with the capital gains report of Koinly or CoinTracking, e.g. after migrating, and print
+    /// the ones that differ with their probable causes.
+    CrossCheck {
+        #[clap(parse(from_os_str), help = "Path to the export file or the store that contains transactions.")]
+        path: std::path::PathBuf,
+
+        #[clap(parse(from_os_str), help = "Path to the csv gains report of Koinly or CoinTracking, in the base currency.")]
+        report: std::path::PathBuf,
+
+        #[clap(short, long, help = "The traded currency for which you report the tax.")]
+        currency: String,
+
+        #[clap(short, long, help = "Base currency. The currency in which you report the tax. Default: 'SEK'")]
+        base: Option<String>,
+
+        #[clap(flatten)]
+        options: OptionArgs,
+    },
+    /// Print every disposal with the file, lines and descriptions of the rows it was read from, followed by the
+    /// acquisitions it consumed and the rows they were read from.
+    Audit {
+        #[clap(parse(from_os_str), help = "Path to the export file or the store that contains transactions.")]
+        path: std::path::PathBuf,
+
+        #[clap(short, long, help = "The traded currency for which you report the tax.")]
+        currency: String,
+
+        #[clap(short, long, help = "Base currency. The currency in which you report the tax. Default: 'SEK'")]
+        base: Option<String>,
+
+        #[clap(flatten)]
+        options: OptionArgs,
+    },
+    /// Print the disposals as the rows of the US Form 8949, split into short-term and long-term.
+    Form8949 {
+        #[clap(parse(from_os_str), help = "Path to the export file or the store that contains transactions.")]
+        path: std::path::PathBuf,
+
+        #[clap(short, long, help = "The traded currency for which you report the tax.")]
+        currency: String,
+
+        #[clap(short, long, help = "Base currency. The currency in which you report the tax. Default: 'USD'")]
+        base: Option<String>,
+
+        #[clap(flatten)]
+        options: OptionArgs,
+    },
+    /// Print the numbers of the HMRC capital gains summary per UK tax year, using Section 104 pooling.
+    Hmrc {
+        #[clap(parse(from_os_str), help = "Path to the export file or the store that contains transactions.")]
+        path: std::path::PathBuf,
+
+        #[clap(short, long, help = "The traded currency for which you report the tax.")]
+        currency: String,
+
+        #[clap(short, long, help = "Base currency. The currency in which you report the tax. Default: 'GBP'")]
+        base: Option<String>,
+
+        #[clap(flatten)]
+        options: OptionArgs,
+    },
+    /// Write the transactions, the disposals, the summary per year, the K4 numbers and the income as the sheets of an
+    /// Excel workbook, e.g. to annotate in a spreadsheet. Use --output to write it to a file.
+    Xlsx {
+        #[clap(parse(from_os_str), help = "Path to the export file or the store that contains transactions.")]
+        path: std::path::PathBuf,
+
+        #[clap(short, long, help = "The traded currency for which you report the tax.")]
+        currency: String,
+
+        #[clap(short, long, help = "Base currency. The currency in which you report the tax. Default: 'SEK'")]
+        base: Option<String>,
+
+        #[clap(flatten)]
+        options: OptionArgs,
+    },
+    /// Write the buys, the income and the disposals as the journal entries of a business that holds the currency, to
+    /// import into the bookkeeping. The accounts are of the Swedish BAS chart unless --ledger gives others.
+    Journal {
+        #[clap(parse(from_os_str), help = "Path to the export file or the store that contains transactions.")]
+        path: std::path::PathBuf,
+
+        #[clap(short, long, help = "The traded currency for which you report the tax.")]
+        currency: String,
+
+        #[clap(short, long, help = "Base currency. The currency in which you report the tax. Default: 'SEK'")]
+        base: Option<String>,
+
+        #[clap(long, parse(from_os_str), help = "Path to a TOML file with the ledger accounts of 'cash', 'holdings', 'gains', 'losses' and 'income'. Default: 1930, 1880, 8350, 8350 and 8390")]
+        ledger: Option<std::path::PathBuf>,
+
+        #[clap(long, default_value = "generic", help = "The columns of the journal: 'GENERIC' double-entry rows, or 'FORTNOX' (alias 'VISMA') for a verification import with Swedish headers")]
+        journal_format: JournalFormat,
+
+        #[clap(flatten)]
+        options: OptionArgs,
+    },
+    /// Write a zip archive with the transactions, the amounts scaled and the descriptions hashed, the warnings, the
+    /// log and the version, to attach to a bug report without telling your finances. Use --output to write it to a file.
+    DebugBundle {
+        #[clap(parse(from_os_str), help = "Path to the export file or the store that contains transactions.")]
+        path: std::path::PathBuf,
+
+        #[clap(short, long, help = "The traded currency for which you report the tax.")]
+        currency: String,
+
+        #[clap(short, long, help = "Base currency. The currency in which you report the tax. Default: 'SEK'")]
+        base: Option<String>,
+
+        #[clap(flatten)]
+        options: OptionArgs,
+    },
+    /// Add the transactions of an export file to a local store, skipping the ones already stored. The store can then
+    /// be used in place of the export files.
+    Import {
+        #[clap(parse(from_os_str), help = "Path to the export file that contains transactions.")]
+        path: std::path::PathBuf,
+
+        #[clap(short, long, help = "The traded currency whose transactions are imported.")]
+        currency: String,
+
+        #[clap(short, long, parse(from_os_str), default_value = "cryptotax.db", help = "Path to the store. Created if it doesn't exist")]
+        store: std::path::PathBuf,
+
+        #[clap(long, parse(from_os_str), help = "Path to a TOML file that maps the columns of the csv file of another exchange")]
+        mapping: Option<std::path::PathBuf>,
+
+        #[clap(long, parse(from_os_str), help = "Path to a TOML file of rules whose classifications, tags and counterparties are saved in the store with the transactions they match")]
+        rules: Option<std::path::PathBuf>,
+    },
+    /// Add the ETH or ERC-20 transfers and the gas fees of an Ethereum address to a local store, fetched from Etherscan
+    /// or another explorer with the same API.
+    ImportChain {
+        #[clap(help = "The Ethereum address whose transactions are imported.")]
+        address: String,
+
+        #[clap(short, long, help = "'ETH', the symbol of the ERC-20 token whose transfers are imported, or 'NFT' for the NFTs.")]
+        currency: String,
+
+        #[clap(long, help = "The API key of the explorer. Default: the key stored with `auth set etherscan`")]
+        api_key: Option<String>,
+
+        #[clap(long, default_value = "https://api.etherscan.io/api", help = "The URL of an explorer with the Etherscan API")]
+        api_url: String,
+
+        #[clap(short, long, parse(from_os_str), default_value = "cryptotax.db", help = "Path to the store. Created if it doesn't exist")]
+        store: std::path::PathBuf,
+
+        #[clap(short, long, help = "The country whose rules tell whether paying gas is a disposal: 'SE', 'US', 'UK' or 'DE'. Default: 'SE'")]
+        jurisdiction: Option<Jurisdiction>,
+    },
+    /// Add the BTC sent and received and the fees paid by a wallet to a local store, fetched from an Esplora server.
+    ImportBitcoin {
+        #[clap(required = true, help = "The addresses or the extended public keys (xpub, ypub or zpub) of the wallet.")]
+        keys: Vec<String>,
+
+        #[clap(long, default_value = "https://blockstream.info/api", help = "The URL of an Esplora server")]
+        api_url: String,
+
+        #[clap(short, long, parse(from_os_str), default_value = "cryptotax.db", help = "Path to the store. Created if it doesn't exist")]
+        store: std::path::PathBuf,
+
+        #[clap(short, long, help = "The country whose rules tell whether paying a fee is a disposal: 'SE', 'US', 'UK' or 'DE'. Default: 'SE'")]
+        jurisdiction: Option<Jurisdiction>,
+    },
+    /// Add the trades of an account at Binance or Kraken to a local store, fetched from the API of the exchange with a
+    /// read-only API key from the keyring of the system. Run it on a schedule, e.g. with cron, to keep the store current.
+    Sync {
+        #[clap(help = "The exchange: 'BINANCE' or 'KRAKEN'")]
+        exchange: Exchange,
+
+        #[clap(short, long, help = "The traded currency whose trades are added.")]
+        currency: String,
+
+        #[clap(long, help = "The URL of the API, e.g. 'https://api.binance.us'. Default: the API of the exchange")]
+        api_url: Option<String>,
+
+        #[clap(short, long, parse(from_os_str), default_value = "cryptotax.db", help = "Path to the store. Created if it doesn't exist")]
+        store: std::path::PathBuf,
+    },
+    /// Watch a directory for new export files, import them to the store, and recalculate the tax report after
+    /// every import, printing what changed.
+    Watch {
+        #[clap(parse(from_os_str), help = "Path to the directory that export files are dropped into.")]
+        dir: std::path::PathBuf,
+
+        #[clap(short, long, help = "The traded currency for which you report the tax.")]
+        currency: String,
+
+        #[clap(short, long, help = "Base currency. The currency in which you report the tax. Default: 'SEK'")]
+        base: Option<String>,
+
+        #[clap(short, long, parse(from_os_str), default_value = "report.csv", help = "Path to the tax report, rewritten after every import")]
+        report: std::path::PathBuf,
+
+        #[clap(long, default_value = "2", help = "Seconds between the scans of the directory. A file is imported once it is unchanged for a scan")]
+        interval: u64,
+
+        #[clap(flatten)]
+        options: OptionArgs,
+    },
+    /// Serve JSON-RPC 2.0 requests to import, calculate and report, one per line of stdin, with the responses and the
+    /// progress on stdout, for tools that are not written in Rust.
+    #[cfg(feature = "rpc")]
+    Rpc,
+    /// Show the holdings, the latest transactions, the realized gains per year, the disposals with the lots they
+    /// consumed, and the warnings in an interactive dashboard in the terminal.
+    #[cfg(feature = "tui")]
+    Tui {
+        #[clap(short, long, help = "The traded currency.")]
+        currency: String,
+
+        #[clap(parse(from_os_str), help = "Path to the export file or the store that contains transactions.")]
+        path: std::path::PathBuf,
+
+        #[clap(short, long, help = "Base currency. The currency in which you report the tax. Default: 'SEK'")]
+        base: Option<String>,
+
+        #[clap(flatten)]
+        options: OptionArgs,
+    },
+    /// Print the lots of a currency that are still held, matched like the jurisdiction does, and optionally their history.
+    Lots {
+        #[clap(help = "The currency of the lots.")]
+        currency: String,
+
+        #[clap(parse(from_os_str), help = "Path to the export file or the store that contains transactions.")]
+        path: std::path::PathBuf,
+
+        #[clap(short, long, help = "Base currency. The currency in which you report the tax. Default: 'SEK'")]
+        base: Option<String>,
+
+        #[clap(long, help = "Also print every acquisition and every part of a lot that a disposal consumed.")]
+        history: bool,
+
+        #[clap(flatten)]
+        options: OptionArgs,
+    },
+    /// Calculate what a trade would realize now, and the tax it would add to its year, without saving anything.
+    Simulate {
+        #[clap(subcommand)]
+        command: SimulateCommand,
+    },
+    /// Save the costs at the end of a year, so that the next year can be calculated without the full history.
+    Snapshot {
+        #[clap(subcommand)]
+        command: SnapshotCommand,
+    },
+    /// List the transactions of an export file or the store, or annotate the stored ones.
+    Tx {
+        #[clap(subcommand)]
+        command: TxCommand,
+    },
+    /// Store the API keys of the exchanges and the price and block explorer providers in the keyring of the system.
+    Auth {
+        #[clap(subcommand)]
+        command: AuthCommand,
+    },
+}
+
+#[derive(Subcommand)]
+enum AuthCommand {
+    /// Store the API key of a provider, and its secret for an exchange, prompted for so that they are not in the history
+    /// of the shell.
+    Set {
+        #[clap(help = "The provider: 'binance', 'kraken', 'etherscan', 'coingecko' or 'cryptocompare'")]
+        provider: String,
+    },
+    /// Remove the API key of a provider and its secret.
+    Remove {
+        #[clap(help = "The provider: 'binance', 'kraken', 'etherscan', 'coingecko' or 'cryptocompare'")]
+        provider: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum SimulateCommand {
+    /// Sell an amount of the holding for the base currency, at the given price or the price of the latest trade.
+    Sell {
+        #[clap(help = "The amount to sell.")]
+        amount: Decimal,
+
+        #[clap(help = "The currency to sell.")]
+        currency: String,
+
+        #[clap(parse(from_os_str), help = "Path to the export file or the store that contains transactions.")]
+        path: std::path::PathBuf,
+
+        #[clap(short, long, help = "Base currency. The currency in which you report the tax. Default: 'SEK'")]
+        base: Option<String>,
+
+        #[clap(short, long, help = "Price of one unit of the currency in the base currency. Default: the price of the latest trade")]
+        price: Option<Decimal>,
+
+        #[clap(flatten)]
+        options: OptionArgs,
+    },
+}
+
+#[derive(Subcommand)]
+#[allow(clippy::large_enum_variant)]     // Parsed once
+enum TxCommand {
+    /// List the transactions that match the filters, as a table, csv or json by --format.
+    List {
+        #[clap(parse(from_os_str), help = "Path to the export file or the store that contains transactions.")]
+        path: std::path::PathBuf,
+
+        #[clap(short, long, required = true, use_value_delimiter = true, help = "The traded currencies whose transactions are listed, e.g. BTC,ETH")]
+        currency: Vec<String>,
+
+        #[clap(short, long, help = "Base currency. The currency in which you report the tax. Default: 'SEK'")]
+        base: Option<String>,
+
+        #[clap(short = 't', long = "type", use_value_delimiter = true, help = "Only the transactions of these types, e.g. buy,sell,income")]
+        types: Vec<String>,
+
+        #[clap(long, help = "Only the transactions on or after this day, e.g. 2022-01-01")]
+        from: Option<String>,
+
+        #[clap(long, help = "Only the transactions on or before this day, e.g. 2022-12-31")]
+        to: Option<String>,
+
+        #[clap(long, help = "Only the transactions of at least this amount of the traded currency, bought or sold")]
+        min_amount: Option<Decimal>,
+
+        #[clap(flatten)]
+        options: OptionArgs,
+    },
+    /// Annotate the stored transactions at a date with a note and tags, which are carried into the tax and audit reports.
+    Annotate {
+        #[clap(short, long, help = "The currency of the transactions, e.g. BTC")]
+        currency: String,
+
+        #[clap(short, long, help = "The date of the transactions, as in the export file, e.g. '2022-05-02 08:00:00'")]
+        date: String,
+
+        #[clap(short, long, help = "A note, e.g. why the transaction was reclassified. Replaces an earlier note")]
+        note: Option<String>,
+
+        #[clap(short, long = "tag", help = "A tag, e.g. business. Repeat for more tags")]
+        tags: Vec<String>,
+
+        #[clap(short, long, parse(from_os_str), default_value = "cryptotax.db", help = "Path to the store.")]
+        store: std::path::PathBuf,
+    },
+}
+
+#[derive(Subcommand)]
+#[allow(clippy::large_enum_variant)]     // Parsed once
+enum SnapshotCommand {
+    /// Calculate the costs of the traded currency at the end of a year and save them to the store.
+    Create {
+        #[clap(parse(from_os_str), help = "Path to the export file or the store that contains transactions.")]
+        path: std::path::PathBuf,
+
+        #[clap(short, long, help = "The traded currency whose costs are saved.")]
+        currency: String,
+
+        #[clap(short, long, help = "Base currency. The currency in which you report the tax. Default: 'SEK'")]
+        base: Option<String>,
+
+        #[clap(short, long, help = "The year at the end of which the costs are saved.")]
+        year: String,
+
+        #[clap(flatten)]
+        options: OptionArgs,
+    },
+    /// List the snapshots saved in the store.
+    List {
+        #[clap(short, long, parse(from_os_str), default_value = "cryptotax.db", help = "Path to the store.")]
+        store: std::path::PathBuf,
+    },
+    /// Print the costs saved in a snapshot.
+    Show {
+        #[clap(short, long, help = "The traded currency of the snapshot.")]
+        currency: String,
+
+        #[clap(short, long, help = "The year of the snapshot.")]
+        year: String,
+
+        #[clap(short, long, parse(from_os_str), default_value = "cryptotax.db", help = "Path to the store.")]
+        store: std::path::PathBuf,
+    },
 }
 
 fn main() {
-    env_logger::init();
     let args = Cli::parse();
+    let (format, quiet, json_errors) = (args.logging.warnings, args.logging.quiet, args.logging.json_errors);
+    let result = run(args);
+    let error = result.as_ref().err().and_then(|e| e.downcast_ref::<CryptotaxError>());
+    let exit_code = match (&result, error) {
+        (Ok(()), _) if cryptotax::has_warnings() => ExitCode::Warnings,
+        (Ok(()), _) => ExitCode::Ok,
+        (Err(_), Some(e)) => e.exit_code(),
+        (Err(_), None) => ExitCode::DataError,
+    };
+    // A run that succeeded fails if its warnings can't be reported, e.g. to a closed pipe.
+    let exit_code = match report(&result, exit_code, error, &format, quiet, json_errors) {
+        Err(e) if matches!(exit_code, ExitCode::Ok | ExitCode::Warnings) => e.exit_code(),
+        _ => exit_code,
+    };
+    std::process::exit(exit_code.into());
+}
+
+/// Writes the warnings and the error of the run to stderr, or the summary of the run if
+/// `json_errors`, see `cryptotax::print_failures`.
+fn report(result: &anyhow::Result<()>, exit_code: ExitCode, error: Option<&CryptotaxError>, format: &WarningFormat, quiet: bool, json_errors: bool) -> Result<(), CryptotaxError> {
+    if json_errors {
+        let message = result.as_ref().err().map(|e| format!("{:#}", e));
+        return cryptotax::print_failures(exit_code, message, error);
+    }
+    if !(quiet && *format == WarningFormat::Text) {
+        cryptotax::print_warnings(format)?;
+    }
+    if let Err(e) = result {
+        writeln!(io::stderr(), "Error: {:?}", e)?;
+    }
+    Ok(())
+}
+
+fn run(args: Cli) -> anyhow::Result<()> {
+    args.logging.init()?;
+    let access = args.store.access()?;
+    let offline = args.offline;
+    match args.command {
+        Some(Command::Summary { path, currency, base, price, breakdown, options }) => {
+            let base: String = base.unwrap_or("SEK".to_string());
+            let options = options.into_options(&access, offline);
+            cryptotax::print_summary(&path, &currency, &base, price, breakdown, &options)
+                .with_context(|| format!("Could not summarize gains from file `{:?}`", &path))?;
+            return Ok(());
+        }
+        Some(Command::Chart { path, currency, base, svg, options }) => {
+            let base: String = base.unwrap_or("SEK".to_string());
+            let options = options.into_options(&access, offline);
+            cryptotax::write_chart(&path, &currency, &base, svg.as_ref(), &options)
+                .with_context(|| format!("Could not chart gains from file `{:?}`", &path))?;
+            return Ok(());
+        }
+        Some(Command::Audit { path, currency, base, options }) => {
+            let base: String = base.unwrap_or("SEK".to_string());
+            let options = options.into_options(&access, offline);
+            cryptotax::print_audit(&path, &currency, &base, &options)
+                .with_context(|| format!("Could not create audit report from file `{:?}`", &path))?;
+            return Ok(());
+        }
+        Some(Command::Diff { old, new, currency, base, options }) => {
+            let base: String = base.unwrap_or("SEK".to_string());
+            let options = options.into_options(&access, offline);
+            cryptotax::print_diff(&old, &new, &currency, &base, &options)
+                .with_context(|| format!("Could not compare file `{:?}` to `{:?}`", &old, &new))?;
+            return Ok(());
+        }
+        Some(Command::MatchBank { path, statement, currency, options }) => {
+            let currency: String = currency.unwrap_or("SEK".to_string());
+            let options = options.into_options(&access, offline);
+            cryptotax::print_bank_reconciliation(&path, &statement, &currency, &options)
+                .with_context(|| format!("Could not match file `{:?}` with the bank statement `{:?}`", &path, &statement))?;
+            return Ok(());
+        }
+        Some(Command::CrossCheck { path, report, currency, base, options }) => {
+            let base: String = base.unwrap_or("SEK".to_string());
+            let options = options.into_options(&access, offline);
+            cryptotax::print_cross_check(&path, &report, &currency, &base, &options)
+                .with_context(|| format!("Could not cross-check file `{:?}` against `{:?}`", &path, &report))?;
+            return Ok(());
+        }
+        Some(Command::Form8949 { path, currency, base, mut options }) => {
+            let base: String = base.unwrap_or("USD".to_string());
+            options.jurisdiction.get_or_insert(Jurisdiction::UnitedStates);
+            options.rounding.get_or_insert(Rounding::Decimals(2));
+            let options = options.into_options(&access, offline);
+            cryptotax::print_form8949(&path, &currency, &base, &options)
+                .with_context(|| format!("Could not create Form 8949 from file `{:?}`", &path))?;
+            return Ok(());
+        }
+        Some(Command::Hmrc { path, currency, base, mut options }) => {
+            let base: String = base.unwrap_or("GBP".to_string());
+            options.jurisdiction.get_or_insert(Jurisdiction::UnitedKingdom);
+            options.rounding.get_or_insert(Rounding::Favorable);
+            let options = options.into_options(&access, offline);
+            cryptotax::print_hmrc_summary(&path, &currency, &base, &options)
+                .with_context(|| format!("Could not create HMRC summary from file `{:?}`", &path))?;
+            return Ok(());
+        }
+        Some(Command::Xlsx { path, currency, base, options }) => {
+            let base: String = base.unwrap_or("SEK".to_string());
+            let options = options.into_options(&access, offline);
+            cryptotax::write_workbook(&path, &currency, &base, &options)
+                .with_context(|| format!("Could not create workbook from file `{:?}`", &path))?;
+            return Ok(());
+        }
+        Some(Command::Journal { path, currency, base, ledger, journal_format, options }) => {
+            let base: String = base.unwrap_or("SEK".to_string());
+            let options = options.into_options(&access, offline);
+            cryptotax::write_journal(&path, &currency, &base, ledger.as_ref(), journal_format, &options)
+                .with_context(|| format!("Could not create journal from file `{:?}`", &path))?;
+            return Ok(());
+        }
+        Some(Command::DebugBundle { path, currency, base, options }) => {
+            let base: String = base.unwrap_or("SEK".to_string());
+            let options = options.into_options(&access, offline);
+            cryptotax::write_debug_bundle(&path, &currency, &base, &options)
+                .with_context(|| format!("Could not create debug bundle from file `{:?}`", &path))?;
+            return Ok(());
+        }
+        Some(Command::Import { path, currency, store, mapping, rules }) => {
+            cryptotax::import(&path, &store, &currency, mapping.as_ref(), rules.as_ref(), &access)
+                .with_context(|| format!("Could not import transactions from file `{:?}` to `{:?}`", &path, &store))?;
+            return Ok(());
+        }
+        Some(Command::ImportChain { address, currency, api_key, api_url, store, jurisdiction }) => {
+            cryptotax::import_chain(&address, &api_url, api_key.as_deref(), &store, &currency, &jurisdiction.unwrap_or_default(), &access, offline)
+                .with_context(|| format!("Could not import transactions of address `{}` to `{:?}`", &address, &store))?;
+            return Ok(());
+        }
+        Some(Command::ImportBitcoin { keys, api_url, store, jurisdiction }) => {
+            cryptotax::import_bitcoin(&keys, &api_url, &store, &jurisdiction.unwrap_or_default(), &access, offline)
+                .with_context(|| format!("Could not import the transactions of the wallet to `{:?}`", &store))?;
+            return Ok(());
+        }
+        Some(Command::Sync { exchange, currency, api_url, store }) => {
+            cryptotax::sync(&exchange, api_url.as_deref(), &store, &currency, &access, offline)
+                .with_context(|| format!("Could not sync the trades of {} to `{:?}`", exchange, &store))?;
+            return Ok(());
+        }
+        Some(Command::Watch { dir, currency, base, report, interval, options }) => {
+            let base: String = base.unwrap_or("SEK".to_string());
+            let options = options.into_options(&access, offline);
+            let (format, quiet) = (args.logging.warnings, args.logging.quiet);
+            let warnings = Some(format).filter(|f| !(quiet && *f == WarningFormat::Text));
+            cryptotax::watch(&dir, &report, &currency, &base, Duration::from_secs(interval), warnings.as_ref(), &options)
+                .with_context(|| format!("Could not watch directory `{:?}`", &dir))?;
+            return Ok(());
+        }
+        #[cfg(feature = "rpc")]
+        Some(Command::Rpc) => {
+            cryptotax::serve_rpc(std::io::stdin().lock(), std::io::stdout().lock(), &access, offline)
+                .context("Could not serve JSON-RPC requests")?;
+            return Ok(());
+        }
+        #[cfg(feature = "tui")]
+        Some(Command::Tui { currency, path, base, options }) => {
+            let base: String = base.unwrap_or("SEK".to_string());
+            let options = options.into_options(&access, offline);
+            cryptotax::show_dashboard(&path, &currency, &base, &options)
+                .with_context(|| format!("Could not show the dashboard of file `{:?}`", &path))?;
+            return Ok(());
+        }
+        Some(Command::Auth { command: AuthCommand::Set { provider } }) => {
+            let prompt = |what: &str| -> Result<String, CryptotaxError> {
+                let secret = rpassword::prompt_password(format!("{} of {}: ", what, provider))
+                    .map_err(|e| CryptotaxError::Config(format!("Could not read the {} of {}: {}", what.to_lowercase(), provider, e)))?;
+                if secret.trim().is_empty() {
+                    return Err(CryptotaxError::Config(format!("The {} of {} is empty", what.to_lowercase(), provider)));
+                }
+                Ok(secret.trim().to_string())
+            };
+            let stored = cryptotax::api_key_has_secret(&provider).map_err(anyhow::Error::from).and_then(|has_secret| {
+                let key = prompt("API key")?;
+                let secret = if has_secret { Some(prompt("API secret")?) } else { None };
+                Ok(cryptotax::set_api_key(&provider, &key, secret.as_deref())?)
+            });
+            stored.with_context(|| format!("Could not store the API key of `{}`", &provider))?;
+            return Ok(());
+        }
+        Some(Command::Auth { command: AuthCommand::Remove { provider } }) => {
+            cryptotax::remove_api_key(&provider)
+                .with_context(|| format!("Could not remove the API key of `{}`", &provider))?;
+            return Ok(());
+        }
+        Some(Command::Lots { currency, path, base, history, options }) => {
+            let base: String = base.unwrap_or("SEK".to_string());
+            let options = options.into_options(&access, offline);
+            cryptotax::print_lots(&path, &currency, &base, history, &options)
+                .with_context(|| format!("Could not print the lots from file `{:?}`", &path))?;
+            return Ok(());
+        }
+        Some(Command::Simulate { command: SimulateCommand::Sell { amount, currency, path, base, price, options } }) => {
+            let base: String = base.unwrap_or("SEK".to_string());
+            let options = options.into_options(&access, offline);
+            cryptotax::simulate_sell(&path, &currency, &base, amount, price, &options)
+                .with_context(|| format!("Could not simulate the sale from file `{:?}`", &path))?;
+            return Ok(());
+        }
+        Some(Command::Snapshot { command: SnapshotCommand::Create { path, currency, base, year, options } }) => {
+            let base: String = base.unwrap_or("SEK".to_string());
+            let options = options.into_options(&access, offline);
+            cryptotax::create_snapshot(&path, &currency, &base, &year, &options)
+                .with_context(|| format!("Could not create snapshot from file `{:?}`", &path))?;
+            return Ok(());
+        }
+        Some(Command::Snapshot { command: SnapshotCommand::List { store } }) => {
+            cryptotax::list_snapshots(&store, &access)
+                .with_context(|| format!("Could not list snapshots in `{:?}`", &store))?;
+            return Ok(());
+        }
+        Some(Command::Snapshot { command: SnapshotCommand::Show { currency, year, store } }) => {
+            cryptotax::show_snapshot(&store, &currency, &year, &access)
+                .with_context(|| format!("Could not show snapshot in `{:?}`", &store))?;
+            return Ok(());
+        }
+        Some(Command::Tx { command: TxCommand::List { path, currency, base, types, from, to, min_amount, options } }) => {
+            let base: String = base.unwrap_or("SEK".to_string());
+            let options = options.into_options(&access, offline);
+            let search = Search{ types, from, to, min_amount };
+            cryptotax::list_transactions(&path, &currency, &base, &search, &options)
+                .with_context(|| format!("Could not list transactions from file `{:?}`", &path))?;
+            return Ok(());
+        }
+        Some(Command::Tx { command: TxCommand::Annotate { currency, date, note, tags, store } }) => {
+            cryptotax::annotate_transaction(&store, &currency, &date, note.as_ref(), &tags, &access)
+                .with_context(|| format!("Could not annotate transactions in `{:?}`", &store))?;
+            return Ok(());
+        }
+        None => {}
+    }
+
+    let path = args.path
+        .ok_or_else(|| CryptotaxError::Config("Give the PATH of a statement or a store, or a subcommand".to_string()))?;
     let currency: String = args.currency.unwrap_or("ALL".to_string());
     let base: String = args.base.unwrap_or("SEK".to_string());
+    let options = args.options.into_options(&access, offline);
 
     if args.exchanges {
         match currency.as_str() {
-            "ALL" => cryptotax::print_exchanges(&args.path)
-                .with_context(|| format!("Could not read transactions from file `{:?}`", &args.path))
-                .unwrap(),
-            _ => cryptotax::print_exchanges_in_currency(&args.path, &currency)
-                .with_context(|| format!("Could not read transactions from file `{:?}`", &args.path))
-                .unwrap(),
+            "ALL" => cryptotax::print_exchanges(&path, &options)
+                .with_context(|| format!("Could not read transactions from file `{:?}`", &path))?,
+            _ => cryptotax::print_exchanges_in_currency(&path, &currency, &options)
+                .with_context(|| format!("Could not read transactions from file `{:?}`", &path))?,
         }
     } else if args.transactions {
-        cryptotax::merge_exchanges(&args.path, &currency)
-            .with_context(|| format!("Could not merge exchanges from file `{:?}`", &args.path))
-            .unwrap();
+        cryptotax::merge_exchanges(&path, &currency, &base, &options)
+            .with_context(|| format!("Could not merge exchanges from file `{:?}`", &path))?;
     } else {
-        cryptotax::calculate_tax(&args.path, &currency, &base)
-            .with_context(|| format!("Could not calculate tax from file `{:?}`", &args.path))
-            .unwrap();
+        cryptotax::calculate_tax(&path, &currency, &base, &options)
+            .with_context(|| format!("Could not calculate tax from file `{:?}`", &path))?;
     }
+    Ok(())
 }
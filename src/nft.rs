@@ -0,0 +1,98 @@
+use crate::calculator::{Calculation, Holding};
+use crate::error::Result;
+use crate::transaction::{Currency, Transaction, NFT};
+use std::collections::BTreeMap;
+use tracing::{debug, instrument};
+
+/// Calculates the NFTs one at a time with `calculate`, as each is a currency of its own whose
+/// cost is not pooled with the others, and merges the calculations into one of `NFT`. The holding
+/// is the number of NFTs held, with their costs.
+#[instrument(name = "nft", skip_all)]
+pub(crate) fn calculate<F>(txns: &[Transaction], mut calculate: F) -> Result<Calculation>
+    where F: FnMut(&[Transaction], &Currency) -> Result<Calculation>
+{
+    let mut nfts: BTreeMap<&Currency, Vec<Transaction>> = BTreeMap::new();
+    txns.iter().for_each(|t| nfts.entry(&t.paid_currency).or_default().push(t.clone()));
+    let mut merged = Calculation{
+        taxables: vec![],
+        non_taxables: vec![],
+        derivatives: vec![],
+        income: vec![],
+        exempt: vec![],
+        holding: Holding{ currency: NFT.to_string(), ..Default::default() },
+        carryover: vec![],
+    };
+    for (nft, txns) in nfts {
+        debug!(%nft, txns = txns.len(), "Calculating NFT");
+        let calculation = calculate(&txns, nft)?;
+        merged.taxables.extend(calculation.taxables);
+        merged.non_taxables.extend(calculation.non_taxables);
+        merged.derivatives.extend(calculation.derivatives);
+        merged.income.extend(calculation.income);
+        merged.exempt.extend(calculation.exempt);
+        merged.holding.amount += calculation.holding.amount;
+        merged.holding.costs.extend(calculation.holding.costs);
+        merged.holding.vault += calculation.holding.vault;
+        merged.holding.vault_costs.extend(calculation.holding.vault_costs);
+        merged.carryover.extend(calculation.carryover);
+    }
+    merged.taxables.sort_by(|a, b| a.date.cmp(&b.date));
+    merged.non_taxables.sort_by(|a, b| a.date.cmp(&b.date));
+    merged.income.sort_by(|a, b| a.date.cmp(&b.date));
+    merged.exempt.sort_by(|a, b| a.date.cmp(&b.date));
+    merged.carryover.sort_by(Transaction::cmp_by_date);
+    Ok(merged)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::calculator;
+    use crate::cryptotax::Options;
+    use crate::nft::*;
+    use crate::transaction::TransactionType;
+    use futures::executor::block_on;
+    use rust_decimal::Decimal;
+    use rust_decimal_macros::dec;
+    use std::error::Error;
+
+    #[test]
+    fn should_calculate_each_nft_at_its_own_cost() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let txn = |r#type: TransactionType, nft: &str, amount: Decimal, exchanged: Decimal, date: &str| {
+            let mut txn = Transaction::new();
+            txn.r#type = r#type;
+            txn.paid_currency = nft.to_string();
+            txn.paid_amount = amount;
+            txn.exchanged_currency = "SEK".to_string();
+            txn.exchanged_amount = exchanged;
+            txn.date = date.to_string();
+            txn
+        };
+        let (ape, punk) = ("0xbc4c:1", "0xb47e:2");
+        let txns = vec![
+            txn(TransactionType::Buy, ape, dec!(1), dec!(-10000), "2022-01-01 10:00:00"),
+            txn(TransactionType::Buy, punk, dec!(1), dec!(-50000), "2022-02-01 10:00:00"),
+            txn(TransactionType::Sell, ape, dec!(-1), dec!(30000), "2022-03-01 10:00:00"),
+        ];
+        let options = Options::default();
+
+        /*
+         * When
+         */
+        let calculation = calculate(&txns, |txns, nft| {
+            block_on(calculator::calculate(txns, nft, &"SEK".to_string(), &options))
+        })?;
+
+        /*
+         * Then
+         */
+        assert_eq!(calculation.taxables.len(), 1);
+        assert_eq!(calculation.taxables[0].currency, ape);
+        assert_eq!(calculation.taxables[0].net_income, Some(dec!(20000)));
+        assert_eq!(calculation.holding.currency, NFT);
+        assert_eq!(calculation.holding.amount, dec!(1));
+        Ok(())
+    }
+}
@@ -0,0 +1,115 @@
+use crate::jurisdiction::Jurisdiction;
+use crate::timestamp::Timestamp;
+use crate::transaction::{Transaction, TransactionType};
+use crate::warnings::{self, Code, Warning};
+use chrono::{Duration, NaiveDateTime};
+use rust_decimal_macros::dec;
+
+pub(crate) mod bitcoin;
+pub(crate) mod ethereum;
+
+/// The date of a unix timestamp of a block, in UTC.
+fn date_of(time: i64) -> Option<String> {
+    Timestamp::from_unix(time).map(|t| t.to_string())
+}
+
+/// Turns a transfer of the fee that a transaction sent by the wallet paid to the network, e.g. gas
+/// in ETH, into what the jurisdiction makes of it: a disposal of the fee, which is a sell that is
+/// valued at its market price with `--prices`, or just a fee of a transfer.
+fn network_fee(transfer: Transaction, jurisdiction: &Jurisdiction) -> Transaction {
+    let fee = -transfer.paid_amount;
+    match jurisdiction.disposes_network_fees() {
+        true => Transaction{
+            r#type: TransactionType::Sell,
+            exchanged_currency: transfer.paid_currency.clone(),
+            exchanged_amount: fee,
+            ..transfer
+        },
+        false => Transaction{ paid_fee: -fee, ..transfer },
+    }
+}
+
+/// Whether a transfer of the wallet is the other side of a transfer of an account, e.g. a receive
+/// of a withdrawal from an exchange: in the other direction, within two days, and of at least 98%
+/// of the amount, as the withdrawal fees of an exchange are deducted from the amount sent.
+fn is_other_side(transfer: &Transaction, other: &Transaction) -> bool {
+    let time = |t: &Transaction| NaiveDateTime::parse_from_str(t.date.get(..19)?, "%Y-%m-%d %H:%M:%S").ok();
+    let (amount, other_amount) = (transfer.paid_amount.abs(), other.paid_amount.abs());
+    other.r#type == TransactionType::Transfer
+        && transfer.paid_amount.is_sign_negative() != other.paid_amount.is_sign_negative()
+        && amount.min(other_amount) >= amount.max(other_amount) * dec!(0.98)
+        && time(transfer).zip(time(other)).is_some_and(|(a, b)| (a - b).abs() <= Duration::days(2))
+}
+
+/// Reports the transfers of the wallet that are not the other side of a transfer of the `known`
+/// transactions, e.g. of the exchange exports in the store. Such a transfer may be a payment or a
+/// purchase from someone else, rather than a move between own accounts. Every known transfer is
+/// matched once.
+pub(crate) fn report_unmatched(txns: &[Transaction], known: &[Transaction]) {
+    let mut candidates: Vec<&Transaction> = known.iter().collect();
+    let transfers = txns.iter().filter(|t| t.r#type == TransactionType::Transfer && t.paid_amount != t.paid_fee);
+    for t in transfers {
+        match candidates.iter().position(|other| is_other_side(t, other)) {
+            Some(i) => { candidates.remove(i); }
+            None => {
+                let msg = match t.paid_amount.is_sign_positive() {
+                    true => format!(
+                        "Received {} {} on-chain, matching no withdrawal from an exchange. If it was bought or earned, override it with its value",
+                        t.paid_amount, t.paid_currency
+                    ),
+                    false => format!(
+                        "Sent {} {} on-chain, matching no deposit to an exchange. If it was a payment, override it as a Sell with its value",
+                        -t.paid_amount, t.paid_currency
+                    ),
+                };
+                warnings::report(Warning::new(Code::UnmatchedTransfer, msg).at(&t.origin).at_date(&t.date));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::onchain::*;
+    use rust_decimal::Decimal;
+    use std::error::Error;
+
+    #[test]
+    fn should_report_transfers_without_other_side() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let transfer = |date: &str, amount: Decimal| {
+            let mut txn = Transaction::new();
+            txn.r#type = TransactionType::Transfer;
+            txn.paid_currency = "BTC".to_string();
+            txn.paid_amount = amount;
+            txn.date = date.to_string();
+            txn
+        };
+        let known = vec![
+            transfer("2022-01-02 10:00:00", dec!(-0.1)),       // Withdrawn from an exchange
+            transfer("2022-03-01 12:00:00", dec!(0.05)),       // Deposited to an exchange
+        ];
+        let onchain = vec![
+            transfer("2022-01-02 10:40:00", dec!(0.0995)),     // After the withdrawal fee
+            transfer("2022-02-01 09:00:00", dec!(-0.02)),      // A payment
+            transfer("2022-03-01 11:00:00", dec!(-0.05)),
+        ];
+
+        /*
+         * When
+         */
+        warnings::take();
+        report_unmatched(&onchain, &known);
+
+        /*
+         * Then
+         */
+        let warnings = warnings::take();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].code, Code::UnmatchedTransfer);
+        assert_eq!(warnings[0].date, Some("2022-02-01 09:00:00".to_string()));
+        Ok(())
+    }
+}
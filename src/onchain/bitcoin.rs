@@ -0,0 +1,246 @@
+use crate::error::{CryptotaxError, Result};
+use crate::http;
+use crate::jurisdiction::Jurisdiction;
+use crate::onchain;
+use crate::transaction::{Origin, Transaction, TransactionType};
+use ::bitcoin::bip32::{ChildNumber, Xpub};
+use ::bitcoin::secp256k1::Secp256k1;
+use ::bitcoin::{base58, Address, Network};
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::str::FromStr;
+use tracing::{debug, instrument};
+
+/// How many unused addresses in a row end the scan of a chain of an extended public key, as in
+/// most wallets.
+const GAP_LIMIT: u32 = 20;
+
+/// The number of confirmed transactions in a page of the Esplora API.
+const PAGE_SIZE: usize = 25;
+
+/// A transaction of the Esplora API, e.g. of `GET /address/:address/txs`. Amounts in satoshis.
+#[derive(Debug, Deserialize)]
+struct Tx {
+    txid: String,
+    vin: Vec<Input>,
+    vout: Vec<Output>,
+    fee: u64,
+    status: Status,
+}
+
+#[derive(Debug, Deserialize)]
+struct Input {
+    prevout: Option<Output>,    // The output that is spent, none for a coinbase
+}
+
+#[derive(Debug, Deserialize)]
+struct Output {
+    scriptpubkey_address: Option<String>,
+    value: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct Status {
+    confirmed: bool,
+    block_time: Option<i64>,
+}
+
+/// The addresses that the wallet of an extended public key uses.
+#[derive(Debug, PartialEq)]
+enum Script {
+    P2pkh,          // xpub, legacy addresses starting with 1
+    P2shP2wpkh,     // ypub, nested SegWit addresses starting with 3
+    P2wpkh,         // zpub, native SegWit addresses starting with bc1q
+}
+
+/// Parses an extended public key of the mainnet, `xpub`, `ypub` or `zpub`.
+fn parse_key(key: &str) -> Option<(Xpub, Script)> {
+    let script = match key.get(..4)? {
+        "xpub" => Script::P2pkh,
+        "ypub" => Script::P2shP2wpkh,
+        "zpub" => Script::P2wpkh,
+        _ => return None,
+    };
+    let mut data = base58::decode_check(key).ok()?;
+    data.get_mut(..4)?.copy_from_slice(&[0x04, 0x88, 0xb2, 0x1e]);  // The version of an xpub
+    Some((Xpub::decode(&data).ok()?, script))
+}
+
+/// Derives the address at `index` of a chain of the key, 0 for receiving and 1 for change.
+fn derive(xpub: &Xpub, script: &Script, chain: u32, index: u32) -> Result<String> {
+    let invalid = |e: ::bitcoin::bip32::Error| CryptotaxError::Config(e.to_string());
+    let path = [
+        ChildNumber::from_normal_idx(chain).map_err(invalid)?,
+        ChildNumber::from_normal_idx(index).map_err(invalid)?,
+    ];
+    let key = xpub.derive_pub(&Secp256k1::verification_only(), &path).map_err(invalid)?.to_pub();
+    let address = match script {
+        Script::P2pkh => Address::p2pkh(key, Network::Bitcoin),
+        Script::P2shP2wpkh => Address::p2shwpkh(&key, Network::Bitcoin),
+        Script::P2wpkh => Address::p2wpkh(&key, Network::Bitcoin),
+    };
+    Ok(address.to_string())
+}
+
+/// Fetches the confirmed transactions of the address from an Esplora server at `api_url`, e.g.
+/// `https://blockstream.info/api`, a page at a time, unless `offline`, see `http::send`.
+fn fetch(api_url: &str, address: &str, offline: bool) -> Result<Vec<Tx>> {
+    let mut txs = vec![];
+    let mut url = format!("{}/address/{}/txs", api_url, address);
+    loop {
+        debug!(%url, "Fetching transactions");
+        let body = http::fetch("esplora", offline, http::get(&url))?;
+        let page: Vec<Tx> = serde_json::from_str(&body)?;
+        let confirmed: Vec<Tx> = page.into_iter().filter(|t| t.status.confirmed).collect();
+        let last = confirmed.last().filter(|_| confirmed.len() == PAGE_SIZE).map(|t| t.txid.clone());
+        txs.extend(confirmed);
+        match last {
+            Some(txid) => url = format!("{}/address/{}/txs/chain/{}", api_url, address, txid),
+            None => return Ok(txs),
+        }
+    }
+}
+
+fn to_btc(satoshis: i64) -> Decimal {
+    Decimal::new(satoshis, 8).normalize()
+}
+
+/// The transactions of a wallet, given by its addresses or extended public keys.
+#[derive(Debug)]
+pub(crate) struct History {
+    addresses: HashSet<String>,
+    txs: Vec<Tx>,
+}
+
+impl History {
+    /// Fetches the transactions of the addresses, and of the receiving and change addresses of
+    /// the extended public keys, until `GAP_LIMIT` unused addresses in a row.
+    #[instrument(name = "onchain", skip_all)]
+    pub(crate) fn fetch(keys: &[String], api_url: &str, offline: bool) -> Result<History> {
+        let mut history = History{ addresses: HashSet::new(), txs: vec![] };
+        for key in keys {
+            match parse_key(key) {
+                Some((xpub, script)) => {
+                    for chain in [0, 1] {
+                        let (mut index, mut unused) = (0, 0);
+                        while unused < GAP_LIMIT {
+                            let address = derive(&xpub, &script, chain, index)?;
+                            let txs = fetch(api_url, &address, offline)?;
+                            unused = if txs.is_empty() { unused + 1 } else { 0 };
+                            history.add(address, txs);
+                            index += 1;
+                        }
+                    }
+                }
+                None => {
+                    let address = Address::from_str(key).ok().filter(|a| a.is_valid_for_network(Network::Bitcoin));
+                    let address = address.ok_or_else(|| {
+                        let msg = format!("`{}` is not a Bitcoin address or an extended public key (xpub, ypub or zpub)", key);
+                        CryptotaxError::Config(msg)
+                    })?;
+                    let txs = fetch(api_url, key, offline)?;
+                    history.add(address.assume_checked().to_string(), txs);
+                }
+            }
+        }
+        debug!(addresses = history.addresses.len(), txs = history.txs.len(), "Fetched wallet");
+        Ok(history)
+    }
+
+    /// Adds the address and its transactions, of which some may already be known from another
+    /// address of the wallet.
+    fn add(&mut self, address: String, txs: Vec<Tx>) {
+        self.addresses.insert(address);
+        for t in txs {
+            if !self.txs.iter().any(|known| known.txid == t.txid) {
+                self.txs.push(t);
+            }
+        }
+    }
+
+    fn is_own(&self, output: &Output) -> bool {
+        output.scriptpubkey_address.as_ref().is_some_and(|a| self.addresses.contains(a))
+    }
+
+    /// Converts the transactions into transfers of BTC: what the wallet received, or what it sent
+    /// to others without the fee. Moves between its own addresses are left out. The fee of a
+    /// transaction that spent coins of the wallet is a network fee, see `onchain::network_fee`.
+    /// The transactions are sorted by date, see `Transaction::cmp_by_date`.
+    pub(crate) fn to_transactions(&self, jurisdiction: &Jurisdiction) -> Vec<Transaction> {
+        let mut txns = vec![];
+        for t in &self.txs {
+            let Some(date) = t.status.block_time.and_then(onchain::date_of) else { continue };
+            let spent: u64 = t.vin.iter().filter_map(|i| i.prevout.as_ref()).filter(|o| self.is_own(o)).map(|o| o.value).sum();
+            let received: u64 = t.vout.iter().filter(|o| self.is_own(o)).map(|o| o.value).sum();
+            let fee = if spent > 0 { t.fee } else { 0 };
+            let transfer = |satoshis: i64| {
+                let mut txn = Transaction::new();
+                txn.r#type = TransactionType::Transfer;
+                txn.date = date.clone();
+                txn.paid_currency = "BTC".to_string();
+                txn.paid_amount = to_btc(satoshis);
+                txn.origin = Origin{ file: "".to_string(), lines: vec![], descriptions: vec![t.txid.clone()], deviation: None, note: None, tags: vec![], counterparty: None };
+                txn
+            };
+            let amount = received as i64 - spent as i64 + fee as i64;
+            if amount != 0 {
+                txns.push(transfer(amount));
+            }
+            if fee > 0 {
+                txns.push(onchain::network_fee(transfer(-(fee as i64)), jurisdiction));
+            }
+        }
+        txns.sort_by(Transaction::cmp_by_date);
+        txns
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::onchain::bitcoin::*;
+    use rust_decimal_macros::dec;
+    use std::error::Error;
+
+    #[test]
+    fn should_derive_addresses_and_classify_transfers() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        // The account of the test vectors of BIP 84
+        let key = "zpub6rFR7y4Q2AijBEqTUquhVz398htDFrtymD9xYYfG1m4wAcvPhXNfE3EfH1r1ADqtfSdVCToUG868RvUUkgDKf31mGDtKsAYz2oz2AGutZYs";
+        let (xpub, script) = parse_key(key).ok_or("Not a key")?;
+        let (me, change) = (derive(&xpub, &script, 0, 0)?, derive(&xpub, &script, 1, 0)?);
+        let other = "bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq";
+        let txs: Vec<Tx> = serde_json::from_str(&format!(r#"[
+            {{"txid":"aa","fee":1000,"status":{{"confirmed":true,"block_time":1641117600}},
+              "vin":[{{"prevout":{{"scriptpubkey_address":"{other}","value":10001000}}}}],
+              "vout":[{{"scriptpubkey_address":"{me}","value":10000000}}]}},
+            {{"txid":"bb","fee":2000,"status":{{"confirmed":true,"block_time":1643709600}},
+              "vin":[{{"prevout":{{"scriptpubkey_address":"{me}","value":10000000}}}}],
+              "vout":[{{"scriptpubkey_address":"{other}","value":3000000}},{{"scriptpubkey_address":"{change}","value":6998000}}]}},
+            {{"txid":"cc","fee":500,"status":{{"confirmed":true,"block_time":1646128800}},
+              "vin":[{{"prevout":{{"scriptpubkey_address":"{change}","value":6998000}}}}],
+              "vout":[{{"scriptpubkey_address":"{me}","value":6997500}}]}}
+        ]"#))?;
+        let history = History{ addresses: HashSet::from([me.clone(), change.clone()]), txs };
+
+        /*
+         * When
+         */
+        let txns = history.to_transactions(&Jurisdiction::Germany);
+
+        /*
+         * Then
+         */
+        assert_eq!(me, "bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu");
+        let rows: Vec<_> = txns.iter().map(|t| (t.r#type.clone(), t.date.as_str(), t.paid_amount, t.paid_fee)).collect();
+        assert_eq!(rows, vec![
+            (TransactionType::Transfer, "2022-01-02 10:00:00", dec!(0.1), dec!(0)),
+            (TransactionType::Transfer, "2022-02-01 10:00:00", dec!(-0.03), dec!(0)),
+            (TransactionType::Transfer, "2022-02-01 10:00:00", dec!(-0.00002), dec!(-0.00002)),
+            (TransactionType::Transfer, "2022-03-01 10:00:00", dec!(-0.000005), dec!(-0.000005)),
+        ]);
+        Ok(())
+    }
+}
@@ -0,0 +1,674 @@
+use crate::error::Result;
+use crate::http;
+use crate::jurisdiction::Jurisdiction;
+use crate::onchain;
+use crate::transaction::{self, Currency, Origin, Transaction, TransactionType, NFT};
+use rust_decimal::Decimal;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::io;
+use std::str::FromStr;
+use tracing::{debug, instrument};
+
+/// The decimals of ETH, i.e. wei per ETH.
+const ETH_DECIMALS: u32 = 18;
+
+/// A response of the `account` module of the Etherscan API, e.g.
+/// `{"status":"1","message":"OK","result":[...]}`. The result is an error message if the status is
+/// not `1`.
+#[derive(Debug, Deserialize)]
+struct Response {
+    status: String,
+    message: String,
+    result: serde_json::Value,
+}
+
+/// A transaction sent or received by the address, from the `txlist` action. Amounts in wei.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct NormalTx {
+    hash: String,
+    time_stamp: String,
+    from: String,
+    to: String,
+    value: String,
+    gas_used: String,
+    gas_price: String,
+    is_error: String,
+
+    #[serde(default)]
+    function_name: String,      // e.g. "swapExactETHForTokens(uint256 amountOutMin, ...)"
+}
+
+/// ETH moved by a contract, e.g. paid out by a swap, from the `txlistinternal` action. The gas is
+/// paid by the transaction that called the contract.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct InternalTx {
+    hash: String,
+    time_stamp: String,
+    from: String,
+    to: String,
+    value: String,
+    is_error: String,
+}
+
+/// An ERC-20 transfer, from the `tokentx` action. The value is in the smallest unit of the token.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TokenTx {
+    hash: String,
+    time_stamp: String,
+    from: String,
+    to: String,
+    value: String,
+    token_symbol: Currency,
+    token_decimal: String,
+}
+
+/// An ERC-721 transfer of an NFT, from the `tokennfttx` action.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct NftTx {
+    hash: String,
+    time_stamp: String,
+    from: String,
+    to: String,
+    contract_address: String,
+    #[serde(rename = "tokenID")]
+    token_id: String,
+
+    #[serde(skip)]
+    currency: Currency,         // See `transaction::nft_currency`
+}
+
+impl NftTx {
+    fn with_currency(self) -> NftTx {
+        NftTx{ currency: transaction::nft_currency(&self.contract_address, &self.token_id), ..self }
+    }
+}
+
+/// The Seaport contracts of OpenSea on Ethereum, which pay out the sales of NFTs and their
+/// royalties in ETH.
+const MARKETPLACES: [&str; 4] = [
+    "0x00000000006c3852cbef3e08e8df289169ede581",  // Seaport 1.1
+    "0x00000000000001ad428e4906ae43d8f9852d0dd6",  // Seaport 1.4
+    "0x00000000000000adc04c56bf30ac9d3c0aaf14dc",  // Seaport 1.5
+    "0x0000000000000068f116a894984e2db1123eb395",  // Seaport 1.6
+];
+
+/// The routers of Uniswap and SushiSwap on Ethereum. Their transactions are swaps also if the
+/// explorer names the function otherwise, e.g. `multicall` or `execute`.
+const ROUTERS: [&str; 5] = [
+    "0x7a250d5630b4cf539739df2c5dacb4c659f2488d",  // Uniswap V2 Router 02
+    "0xe592427a0aece92de3edee1cf18e0157c05861564",  // Uniswap V3 SwapRouter
+    "0x68b3465833fb72a70ecdf485e0e4c7bd8665fc45",  // Uniswap V3 SwapRouter02
+    "0x3fc91a3afd70395cd496c647d5a6cc9d4b2b7fad",  // Uniswap Universal Router
+    "0xd9e1ce17f2641f24ae83637ab66a2cca9c378b9f",  // SushiSwap Router
+];
+
+/// An amount of ETH or a token sent or received by the address in a transaction, negative if sent.
+#[derive(Debug)]
+struct Movement<'a> {
+    hash: &'a str,
+    time_stamp: &'a str,
+    currency: &'a str,
+    amount: Decimal,
+}
+
+/// A swap of one currency for another, with the amount sold negative and the amount bought
+/// positive, and the gas paid for it in ETH. A purchase or sale of an NFT is a swap, too.
+#[derive(Debug)]
+struct Swap<'a> {
+    hash: &'a str,
+    time_stamp: &'a str,
+    sold: (&'a str, Decimal),
+    bought: (&'a str, Decimal),
+    gas: Decimal,
+}
+
+/// Adding liquidity to a pool, with the currencies paid into it negative and the LP tokens
+/// received positive, or removing it, with the signs the other way around.
+#[derive(Debug)]
+struct Pool<'a> {
+    t: &'a NormalTx,
+    currencies: Vec<(&'a str, Decimal)>,
+    lp: (&'a str, Decimal),
+}
+
+/// The transactions of an Ethereum address, as listed by a block explorer.
+#[derive(Debug)]
+pub(crate) struct History {
+    address: String,
+    normal: Vec<NormalTx>,
+    internal: Vec<InternalTx>,
+    tokens: Vec<TokenTx>,
+    nfts: Vec<NftTx>,
+}
+
+/// Parses a response of the Etherscan API. An address without transactions is not an error.
+fn parse<T: DeserializeOwned>(body: &str) -> Result<Vec<T>> {
+    let response: Response = serde_json::from_str(body)?;
+    match response.status.as_str() {
+        "1" => Ok(serde_json::from_value(response.result)?),
+        _ if response.message.starts_with("No transactions found") => Ok(vec![]),
+        _ => {
+            let msg = format!("{}: {}", response.message, response.result.as_str().unwrap_or_default());
+            Err(io::Error::other(msg).into())
+        }
+    }
+}
+
+/// Fetches the list of an action of the `account` module, e.g. `txlist`, from an explorer with
+/// the Etherscan API at `api_url`, unless `offline`, see `http::send`.
+fn fetch<T: DeserializeOwned>(api_url: &str, api_key: &str, action: &str, address: &str, offline: bool) -> Result<Vec<T>> {
+    let url = format!(
+        "{}?module=account&action={}&address={}&startblock=0&endblock=99999999&sort=asc",
+        api_url, action, address
+    );
+    debug!(%url, "Fetching transactions");
+    let body = http::fetch("etherscan", offline, http::get(&url).query("apikey", api_key))?;
+    parse(&body)
+}
+
+/// Converts an integer amount in the smallest unit, e.g. wei, into a decimal with the decimals of
+/// the currency, without overflowing on amounts of more than 28 digits: the decimals beyond the 28
+/// digits of a `Decimal` are rounded off. An amount of more than 28 integer digits, e.g. of a spam
+/// token, is `None`.
+fn units(value: &str, decimals: u32) -> Option<Decimal> {
+    let width = decimals as usize + 1;
+    let digits = format!("{:0>width$}", value.trim());
+    let (int, frac) = digits.split_at(digits.len() - decimals as usize);
+    let number = match frac.is_empty() { true => int.to_string(), false => format!("{}.{}", int, frac) };
+    Decimal::from_str(&number)
+        .map_err(|e| debug!(%value, decimals, error = %e, "Left out an amount that doesn't fit a decimal"))
+        .ok()
+        .map(|d| d.normalize())
+}
+
+/// Whether the transaction adds or removes liquidity, e.g. `addLiquidityETH` of Uniswap V2 or
+/// `remove_liquidity` of Curve.
+fn is_liquidity(t: &NormalTx) -> bool {
+    let function = t.function_name.replace('_', "").to_lowercase();
+    function.starts_with("addliquidity") || function.starts_with("removeliquidity")
+}
+
+/// The gas paid by a transaction, in ETH.
+fn gas_of(t: &NormalTx) -> Option<Decimal> {
+    let wei = t.gas_used.parse::<u128>().ok()?.checked_mul(t.gas_price.parse::<u128>().ok()?)?;
+    units(&wei.to_string(), ETH_DECIMALS)
+}
+
+impl History {
+    /// Fetches the normal and internal transactions and the ERC-20 transfers of the address.
+    #[instrument(name = "onchain", skip_all, fields(%address))]
+    pub(crate) fn fetch(address: &str, api_url: &str, api_key: &str, offline: bool) -> Result<History> {
+        Ok(History{
+            address: address.to_lowercase(),
+            normal: fetch(api_url, api_key, "txlist", address, offline)?,
+            internal: fetch(api_url, api_key, "txlistinternal", address, offline)?,
+            tokens: fetch(api_url, api_key, "tokentx", address, offline)?,
+            nfts: fetch(api_url, api_key, "tokennfttx", address, offline)?.into_iter().map(NftTx::with_currency).collect(),
+        })
+    }
+
+    /// A transfer of `amount` of the currency to or from the address, negative if sent.
+    fn transfer(&self, hash: &str, time_stamp: &str, currency: &Currency, amount: Decimal) -> Option<Transaction> {
+        let mut txn = Transaction::new();
+        txn.r#type = TransactionType::Transfer;
+        txn.date = onchain::date_of(time_stamp.parse().ok()?)?;
+        txn.paid_currency = currency.clone();
+        txn.paid_amount = amount;
+        txn.origin = Origin{ file: self.address.clone(), lines: vec![], descriptions: vec![hash.to_string()], deviation: None, note: None, tags: vec![], counterparty: None };
+        Some(txn)
+    }
+
+    fn is_own(&self, address: &str) -> bool {
+        address.eq_ignore_ascii_case(&self.address)
+    }
+
+    /// The amount sent or received by the address, negative if sent, or `None` if it sent it to
+    /// itself or wasn't involved.
+    fn signed(&self, from: &str, to: &str, amount: Decimal) -> Option<Decimal> {
+        match (self.is_own(from), self.is_own(to)) {
+            (true, false) => Some(-amount),
+            (false, true) => Some(amount),
+            _ => None,
+        }
+    }
+
+    /// The ETH, the ERC-20 tokens and the NFTs sent and received by the address, in the order
+    /// listed.
+    fn movements(&self) -> Vec<Movement<'_>> {
+        let normal = self.normal.iter()
+            .filter(|t| t.is_error != "1")
+            .map(|t| (&t.hash, &t.time_stamp, "ETH", units(&t.value, ETH_DECIMALS), &t.from, &t.to));
+        let internal = self.internal.iter()
+            .filter(|t| t.is_error != "1")
+            .map(|t| (&t.hash, &t.time_stamp, "ETH", units(&t.value, ETH_DECIMALS), &t.from, &t.to));
+        let tokens = self.tokens.iter()
+            .map(|t| {
+                let value = t.token_decimal.parse().ok().and_then(|decimals| units(&t.value, decimals));
+                (&t.hash, &t.time_stamp, t.token_symbol.as_str(), value, &t.from, &t.to)
+            });
+        let nfts = self.nfts.iter()
+            .map(|t| (&t.hash, &t.time_stamp, t.currency.as_str(), Some(Decimal::ONE), &t.from, &t.to));
+        normal.chain(internal).chain(tokens).chain(nfts)
+            .filter_map(|(hash, time_stamp, currency, value, from, to)| {
+                let amount = self.signed(from, to, value.filter(|v| !v.is_zero())?)?;
+                Some(Movement{ hash, time_stamp, currency, amount })
+            })
+            .collect()
+    }
+
+    /// What a transaction of the address sent and received, after netting the legs of every
+    /// currency, e.g. the ETH that a router refunded.
+    fn net_of<'a>(hash: &str, movements: &[Movement<'a>]) -> Vec<(&'a str, Decimal)> {
+        let mut net: Vec<(&str, Decimal)> = vec![];
+        for m in movements.iter().filter(|m| m.hash == hash) {
+            match net.iter_mut().find(|(currency, _)| *currency == m.currency) {
+                Some((_, amount)) => *amount += m.amount,
+                None => net.push((m.currency, m.amount)),
+            }
+        }
+        net.retain(|(_, amount)| !amount.is_zero());
+        net
+    }
+
+    /// A swap on a DEX by a transaction of the address. A transaction that moves more than two
+    /// currencies, or that adds or removes liquidity, is not a swap.
+    fn swap_of<'a>(&self, t: &'a NormalTx, movements: &[Movement<'a>]) -> Option<Swap<'a>> {
+        let is_swap = t.function_name.starts_with("swap") || ROUTERS.contains(&t.to.to_lowercase().as_str());
+        if !is_swap || is_liquidity(t) || t.is_error == "1" || !self.is_own(&t.from) {
+            return None;
+        }
+        Self::swap(&t.hash, &t.time_stamp, &Self::net_of(&t.hash, movements), gas_of(t).unwrap_or_default())
+    }
+
+    fn swap<'a>(hash: &'a str, time_stamp: &'a str, net: &[(&'a str, Decimal)], gas: Decimal) -> Option<Swap<'a>> {
+        match net {
+            [a, b] if a.1.is_sign_negative() && b.1.is_sign_positive() => Some(Swap{ hash, time_stamp, sold: *a, bought: *b, gas }),
+            [a, b] if b.1.is_sign_negative() && a.1.is_sign_positive() => Some(Swap{ hash, time_stamp, sold: *b, bought: *a, gas }),
+            _ => None,
+        }
+    }
+
+    /// The purchases and sales of single NFTs for another currency, also by transactions that
+    /// the address didn't send, e.g. the sale of a listing that the buyer sent. A mint that was
+    /// only paid with gas is a purchase for the gas. The gas of a purchase is part of its cost.
+    fn nft_trades<'a>(&'a self, movements: &[Movement<'a>]) -> Vec<Swap<'a>> {
+        let mut hashes: Vec<(&str, &str)> = vec![];
+        for m in movements.iter().filter(|m| transaction::is_nft(m.currency)) {
+            if !hashes.iter().any(|(hash, _)| *hash == m.hash) {
+                hashes.push((m.hash, m.time_stamp));
+            }
+        }
+        hashes.into_iter()
+            .filter_map(|(hash, time_stamp)| {
+                let sent = self.normal.iter().find(|t| t.hash == hash && self.is_own(&t.from) && t.is_error != "1");
+                let gas = sent.and_then(gas_of).unwrap_or_default();
+                let net = Self::net_of(hash, movements);
+                match (sent, net.as_slice()) {
+                    (Some(_), [(nft, amount)]) if transaction::is_nft(nft) && amount.is_sign_positive() => {
+                        Some(Swap{ hash, time_stamp, sold: ("ETH", Decimal::ZERO), bought: (nft, *amount), gas })
+                    }
+                    _ => Self::swap(hash, time_stamp, &net, gas),
+                }
+            })
+            .collect()
+    }
+
+    /// The royalties of NFTs created by the address, paid out in ETH by a marketplace in a sale
+    /// that it took no part in.
+    fn royalties(&self, movements: &[Movement]) -> Vec<&InternalTx> {
+        self.internal.iter()
+            .filter(|t| t.is_error != "1" && self.is_own(&t.to) && MARKETPLACES.contains(&t.from.to_lowercase().as_str()))
+            .filter(|t| !movements.iter().any(|m| m.hash == t.hash && transaction::is_nft(m.currency)))
+            .collect()
+    }
+
+    /// Adding or removing liquidity by a transaction of the address: the LP tokens are the one
+    /// currency that moves in the other direction than the currencies of the pool.
+    fn pool_of<'a>(&self, t: &'a NormalTx, movements: &[Movement<'a>]) -> Option<Pool<'a>> {
+        if !is_liquidity(t) || t.is_error == "1" || !self.is_own(&t.from) {
+            return None;
+        }
+        let net = Self::net_of(&t.hash, movements);
+        let (lp, currencies): (Vec<_>, Vec<_>) = match net.iter().filter(|(_, a)| a.is_sign_positive()).count() {
+            1 => net.into_iter().partition(|(_, a)| a.is_sign_positive()),
+            n if n + 1 == net.len() => net.into_iter().partition(|(_, a)| a.is_sign_negative()),
+            _ => return None,
+        };
+        match (lp.as_slice(), currencies.is_empty()) {
+            ([lp], false) => Some(Pool{ t, currencies, lp: *lp }),
+            _ => None,
+        }
+    }
+
+    /// The legs of adding or removing liquidity in the target currency, see `liquidity::prepare`:
+    /// the currency of the pool against its share of the LP tokens, or the LP tokens against each
+    /// currency of the pool.
+    fn legs_of(&self, pool: &Pool, currency: &Currency) -> Vec<Transaction> {
+        let r#type = match pool.lp.1.is_sign_positive() {
+            true => TransactionType::AddLiquidity,
+            false => TransactionType::RemoveLiquidity,
+        };
+        let share = pool.lp.1 / Decimal::from(pool.currencies.len());
+        pool.currencies.iter()
+            .filter_map(|(other, amount)| {
+                let (paid, exchanged, exchanged_amount) = match (pool.lp.0 == currency, *other == currency) {
+                    (true, _) => (share, other.to_string(), *amount),
+                    (_, true) => (*amount, pool.lp.0.to_string(), share),
+                    _ => return None,
+                };
+                let txn = self.transfer(&pool.t.hash, &pool.t.time_stamp, currency, paid)?;
+                Some(Transaction{ r#type: r#type.clone(), exchanged_currency: exchanged, exchanged_amount, ..txn })
+            })
+            .collect()
+    }
+
+    /// Converts the history into transactions of the target currency: ETH, the symbol of an
+    /// ERC-20 token, or `NFT` for every NFT. Swaps on Uniswap and SushiSwap and trades of NFTs
+    /// are buys and sells for the other currency, which is valued with `--prices`. An NFT has no
+    /// market price, so a trade for one is valued at the currency paid or received for it instead.
+    /// Adding and removing liquidity of a pool with LP tokens, e.g. of Uniswap V2, SushiSwap or
+    /// Curve, are legs per currency of the pool, see `legs_of`. Royalties are income, see
+    /// `royalties`. The other movements are transfers.
+    ///
+    /// Every transaction sent by the address pays gas in ETH, also if it failed. Gas paid for
+    /// selling ETH in a swap is a fee of the sell, and any other gas is a network fee, see
+    /// `onchain::network_fee`.
+    /// The transactions are sorted by date, see `Transaction::cmp_by_date`.
+    pub(crate) fn to_transactions(&self, currency: &Currency, jurisdiction: &Jurisdiction) -> Vec<Transaction> {
+        let is_target = |c: &str| c == currency || (currency == NFT && transaction::is_nft(c));
+        let movements = self.movements();
+        let mut swaps: Vec<Swap> = self.normal.iter().filter_map(|t| self.swap_of(t, &movements)).collect();
+        for trade in self.nft_trades(&movements) {
+            if !swaps.iter().any(|s| s.hash == trade.hash) {
+                swaps.push(trade);
+            }
+        }
+        let pools: Vec<Pool> = self.normal.iter().filter_map(|t| self.pool_of(t, &movements)).collect();
+        let royalties = self.royalties(&movements);
+        let is_traded = |hash: &str| {
+            swaps.iter().any(|s| s.hash == hash) || pools.iter().any(|p| p.t.hash == hash) || royalties.iter().any(|t| t.hash == hash)
+        };
+        let mut txns: Vec<Transaction> = movements.iter()
+            .filter(|m| is_target(m.currency) && !is_traded(m.hash))
+            .filter_map(|m| self.transfer(m.hash, m.time_stamp, &m.currency.to_string(), m.amount))
+            .collect();
+        txns.extend(pools.iter().flat_map(|p| self.legs_of(p, currency)));
+        let mut fees_paid: Vec<&str> = vec![];
+        for s in &swaps {
+            let (r#type, (own, amount), (other, mut other_amount)) = match (is_target(s.sold.0), is_target(s.bought.0)) {
+                (true, _) => (TransactionType::Sell, s.sold, s.bought),
+                (_, true) => (TransactionType::Buy, s.bought, s.sold),
+                _ => continue,
+            };
+            if amount.is_zero() {
+                continue;   // The ETH of a mint that was only paid with gas
+            }
+            if transaction::is_nft(own) && other == "ETH" && r#type == TransactionType::Buy {
+                other_amount -= s.gas;
+            }
+            let Some(txn) = self.transfer(s.hash, s.time_stamp, &own.to_string(), amount) else { continue };
+            let mut txn = Transaction{ r#type, exchanged_currency: other.to_string(), exchanged_amount: other_amount, ..txn };
+            if txn.r#type == TransactionType::Sell && own == "ETH" {
+                txn.paid_amount -= s.gas;
+                txn.paid_fee = -s.gas;
+                fees_paid.push(s.hash);
+            }
+            if transaction::is_nft(other) {
+                txn.exchanged_currency = own.to_string();
+                txn.exchanged_amount = -txn.paid_amount;
+            }
+            txns.push(txn);
+        }
+        if currency == "ETH" {
+            for t in &royalties {
+                let Some(txn) = units(&t.value, ETH_DECIMALS).and_then(|amount| self.transfer(&t.hash, &t.time_stamp, currency, amount)) else { continue };
+                txns.push(Transaction{ r#type: TransactionType::Income, exchanged_currency: currency.clone(), exchanged_amount: -txn.paid_amount, ..txn });
+            }
+        }
+        if currency == "ETH" {
+            for t in self.normal.iter().filter(|t| self.is_own(&t.from) && !fees_paid.contains(&t.hash.as_str())) {
+                let Some(gas) = gas_of(t).filter(|g| !g.is_zero()) else { continue };
+                let fee = self.transfer(&t.hash, &t.time_stamp, currency, -gas);
+                txns.extend(fee.map(|txn| onchain::network_fee(txn, jurisdiction)));
+            }
+        }
+        txns.sort_by(Transaction::cmp_by_date);
+        txns
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::onchain::ethereum::*;
+    use rust_decimal_macros::dec;
+    use std::error::Error;
+
+    #[test]
+    fn should_convert_transfers_and_gas() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let address = "0xAbC0000000000000000000000000000000000001";
+        let other = "0xdef0000000000000000000000000000000000002";
+        let normal = format!(r#"{{"status":"1","message":"OK","result":[
+            {{"hash":"0x01","timeStamp":"1641117600","from":"{other}","to":"0xabc0000000000000000000000000000000000001","value":"1500000000000000000","gasUsed":"21000","gasPrice":"100000000000","isError":"0"}},
+            {{"hash":"0x02","timeStamp":"1643709600","from":"0xabc0000000000000000000000000000000000001","to":"{other}","value":"500000000000000000","gasUsed":"21000","gasPrice":"50000000000","isError":"0"}},
+            {{"hash":"0x03","timeStamp":"1646128800","from":"0xabc0000000000000000000000000000000000001","to":"{other}","value":"0","gasUsed":"50000","gasPrice":"40000000000","isError":"0"}}
+        ]}}"#);
+        let tokens = format!(r#"{{"status":"1","message":"OK","result":[
+            {{"hash":"0x03","timeStamp":"1646128800","from":"0xabc0000000000000000000000000000000000001","to":"{other}","value":"250000000","tokenSymbol":"USDC","tokenDecimal":"6"}}
+        ]}}"#);
+        let history = History{
+            address: address.to_lowercase(),
+            normal: parse(&normal)?,
+            internal: parse(r#"{"status":"0","message":"No transactions found","result":[]}"#)?,
+            tokens: parse(&tokens)?,
+            nfts: vec![],
+        };
+
+        /*
+         * When
+         */
+        let eth = history.to_transactions(&"ETH".to_string(), &Jurisdiction::Sweden);
+        let eth_de = history.to_transactions(&"ETH".to_string(), &Jurisdiction::Germany);
+        let usdc = history.to_transactions(&"USDC".to_string(), &Jurisdiction::Sweden);
+
+        /*
+         * Then
+         */
+        let rows = |txns: &[Transaction]| txns.iter()
+            .map(|t| (t.r#type.clone(), t.date.clone(), t.paid_amount, t.paid_fee, t.exchanged_currency.clone(), t.exchanged_amount))
+            .collect::<Vec<_>>();
+        let eth_sell = |date: &str, gas: Decimal| (TransactionType::Sell, date.to_string(), -gas, dec!(0), "ETH".to_string(), gas);
+        let eth_fee = |date: &str, gas: Decimal| (TransactionType::Transfer, date.to_string(), -gas, -gas, "".to_string(), dec!(0));
+        let transfer = |date: &str, amount: Decimal| (TransactionType::Transfer, date.to_string(), amount, dec!(0), "".to_string(), dec!(0));
+        assert_eq!(rows(&eth), vec![
+            transfer("2022-01-02 10:00:00", dec!(1.5)),
+            eth_sell("2022-02-01 10:00:00", dec!(0.00105)),
+            transfer("2022-02-01 10:00:00", dec!(-0.5)),
+            eth_sell("2022-03-01 10:00:00", dec!(0.002)),
+        ]);
+        assert_eq!(rows(&eth_de), vec![
+            transfer("2022-01-02 10:00:00", dec!(1.5)),
+            transfer("2022-02-01 10:00:00", dec!(-0.5)),
+            eth_fee("2022-02-01 10:00:00", dec!(0.00105)),
+            eth_fee("2022-03-01 10:00:00", dec!(0.002)),
+        ]);
+        assert_eq!(rows(&usdc), vec![transfer("2022-03-01 10:00:00", dec!(-250))]);
+        assert!(parse::<NormalTx>(r#"{"status":"0","message":"NOTOK","result":"Invalid API Key"}"#).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn should_decode_swaps() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let me = "0xabc0000000000000000000000000000000000001";
+        let router = "0x7a250d5630b4cf539739df2c5dacb4c659f2488d";
+        let pair = "0xb4e16d0168e52d35cacd2c6185b44281ec28c9dc";
+        let history = History{
+            address: me.to_string(),
+            normal: parse(&format!(r#"{{"status":"1","message":"OK","result":[
+                {{"hash":"0x01","timeStamp":"1641117600","from":"{me}","to":"{router}","value":"1000000000000000000","gasUsed":"150000","gasPrice":"100000000000","isError":"0","functionName":"swapExactETHForTokens(uint256 amountOutMin, address[] path, address to, uint256 deadline)"}},
+                {{"hash":"0x02","timeStamp":"1643709600","from":"{me}","to":"{router}","value":"0","gasUsed":"100000","gasPrice":"50000000000","isError":"0","functionName":"swapExactTokensForETH(uint256 amountIn, uint256 amountOutMin, address[] path, address to, uint256 deadline)"}}
+            ]}}"#))?,
+            internal: parse(&format!(r#"{{"status":"1","message":"OK","result":[
+                {{"hash":"0x02","timeStamp":"1643709600","from":"{router}","to":"{me}","value":"500000000000000000","isError":"0"}}
+            ]}}"#))?,
+            tokens: parse(&format!(r#"{{"status":"1","message":"OK","result":[
+                {{"hash":"0x01","timeStamp":"1641117600","from":"{pair}","to":"{me}","value":"3000000000","tokenSymbol":"USDC","tokenDecimal":"6"}},
+                {{"hash":"0x02","timeStamp":"1643709600","from":"{me}","to":"{pair}","value":"1500000000","tokenSymbol":"USDC","tokenDecimal":"6"}}
+            ]}}"#))?,
+            nfts: vec![],
+        };
+
+        /*
+         * When
+         */
+        let eth = history.to_transactions(&"ETH".to_string(), &Jurisdiction::Sweden);
+        let usdc = history.to_transactions(&"USDC".to_string(), &Jurisdiction::Sweden);
+
+        /*
+         * Then
+         */
+        let rows = |txns: &[Transaction]| txns.iter()
+            .map(|t| (t.r#type.clone(), t.paid_amount, t.paid_fee, t.exchanged_currency.clone(), t.exchanged_amount))
+            .collect::<Vec<_>>();
+        assert_eq!(rows(&eth), vec![
+            (TransactionType::Sell, dec!(-1.015), dec!(-0.015), "USDC".to_string(), dec!(3000)),
+            (TransactionType::Buy, dec!(0.5), dec!(0), "USDC".to_string(), dec!(-1500)),
+            (TransactionType::Sell, dec!(-0.005), dec!(0), "ETH".to_string(), dec!(0.005)),
+        ]);
+        assert_eq!(rows(&usdc), vec![
+            (TransactionType::Buy, dec!(3000), dec!(0), "ETH".to_string(), dec!(-1)),
+            (TransactionType::Sell, dec!(-1500), dec!(0), "ETH".to_string(), dec!(0.5)),
+        ]);
+        Ok(())
+    }
+
+    #[test]
+    fn should_decode_liquidity() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let me = "0xabc0000000000000000000000000000000000001";
+        let router = "0x7a250d5630b4cf539739df2c5dacb4c659f2488d";
+        let pair = "0xb4e16d0168e52d35cacd2c6185b44281ec28c9dc";
+        let zero = "0x0000000000000000000000000000000000000000";
+        let history = History{
+            address: me.to_string(),
+            normal: parse(&format!(r#"{{"status":"1","message":"OK","result":[
+                {{"hash":"0x01","timeStamp":"1641117600","from":"{me}","to":"{router}","value":"1000000000000000000","gasUsed":"200000","gasPrice":"50000000000","isError":"0","functionName":"addLiquidityETH(address token, uint256 amountTokenDesired, uint256 amountTokenMin, uint256 amountETHMin, address to, uint256 deadline)"}},
+                {{"hash":"0x02","timeStamp":"1643709600","from":"{me}","to":"{router}","value":"0","gasUsed":"200000","gasPrice":"50000000000","isError":"0","functionName":"removeLiquidityETH(address token, uint256 liquidity, uint256 amountTokenMin, uint256 amountETHMin, address to, uint256 deadline)"}}
+            ]}}"#))?,
+            internal: parse(&format!(r#"{{"status":"1","message":"OK","result":[
+                {{"hash":"0x02","timeStamp":"1643709600","from":"{router}","to":"{me}","value":"800000000000000000","isError":"0"}}
+            ]}}"#))?,
+            tokens: parse(&format!(r#"{{"status":"1","message":"OK","result":[
+                {{"hash":"0x01","timeStamp":"1641117600","from":"{me}","to":"{pair}","value":"3000000000","tokenSymbol":"USDC","tokenDecimal":"6"}},
+                {{"hash":"0x01","timeStamp":"1641117600","from":"{zero}","to":"{me}","value":"10000000000000000000","tokenSymbol":"UNI-V2","tokenDecimal":"18"}},
+                {{"hash":"0x02","timeStamp":"1643709600","from":"{me}","to":"{pair}","value":"10000000000000000000","tokenSymbol":"UNI-V2","tokenDecimal":"18"}},
+                {{"hash":"0x02","timeStamp":"1643709600","from":"{router}","to":"{me}","value":"3600000000","tokenSymbol":"USDC","tokenDecimal":"6"}}
+            ]}}"#))?,
+            nfts: vec![],
+        };
+
+        /*
+         * When
+         */
+        let eth = history.to_transactions(&"ETH".to_string(), &Jurisdiction::Germany);
+        let lp = history.to_transactions(&"UNI-V2".to_string(), &Jurisdiction::Germany);
+
+        /*
+         * Then
+         */
+        let rows = |txns: &[Transaction]| txns.iter()
+            .map(|t| (t.r#type.clone(), t.paid_amount, t.exchanged_currency.clone(), t.exchanged_amount))
+            .collect::<Vec<_>>();
+        assert_eq!(rows(&eth), vec![
+            (TransactionType::Transfer, dec!(-0.01), "".to_string(), dec!(0)),     // Gas
+            (TransactionType::AddLiquidity, dec!(-1), "UNI-V2".to_string(), dec!(5)),
+            (TransactionType::Transfer, dec!(-0.01), "".to_string(), dec!(0)),
+            (TransactionType::RemoveLiquidity, dec!(0.8), "UNI-V2".to_string(), dec!(-5)),
+        ]);
+        assert_eq!(rows(&lp), vec![
+            (TransactionType::AddLiquidity, dec!(5), "ETH".to_string(), dec!(-1)),
+            (TransactionType::AddLiquidity, dec!(5), "USDC".to_string(), dec!(-3000)),
+            (TransactionType::RemoveLiquidity, dec!(-5), "ETH".to_string(), dec!(0.8)),
+            (TransactionType::RemoveLiquidity, dec!(-5), "USDC".to_string(), dec!(3600)),
+        ]);
+        Ok(())
+    }
+
+    #[test]
+    fn should_decode_nft_trades_and_royalties() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let me = "0xabc0000000000000000000000000000000000001";
+        let seaport = "0x00000000000000adc04c56bf30ac9d3c0aaf14dc";
+        let apes = "0xBC4CA0EdA7647A8aB7C2061c2E118A18a936f13D";
+        let other = "0xdef0000000000000000000000000000000000002";
+        let zero = "0x0000000000000000000000000000000000000000";
+        let history = History{
+            address: me.to_string(),
+            normal: parse(&format!(r#"{{"status":"1","message":"OK","result":[
+                {{"hash":"0x01","timeStamp":"1641117600","from":"{me}","to":"{seaport}","value":"1000000000000000000","gasUsed":"100000","gasPrice":"50000000000","isError":"0","functionName":"fulfillBasicOrder(tuple parameters)"}},
+                {{"hash":"0x02","timeStamp":"1643709600","from":"{me}","to":"{apes}","value":"0","gasUsed":"200000","gasPrice":"50000000000","isError":"0","functionName":"mint(uint256 amount)"}}
+            ]}}"#))?,
+            internal: parse(&format!(r#"{{"status":"1","message":"OK","result":[
+                {{"hash":"0x03","timeStamp":"1646128800","from":"{seaport}","to":"{me}","value":"2000000000000000000","isError":"0"}},
+                {{"hash":"0x04","timeStamp":"1648807200","from":"{seaport}","to":"{me}","value":"100000000000000000","isError":"0"}}
+            ]}}"#))?,
+            tokens: vec![],
+            nfts: parse::<NftTx>(&format!(r#"{{"status":"1","message":"OK","result":[
+                {{"hash":"0x01","timeStamp":"1641117600","from":"{other}","to":"{me}","contractAddress":"{apes}","tokenID":"1"}},
+                {{"hash":"0x02","timeStamp":"1643709600","from":"{zero}","to":"{me}","contractAddress":"{apes}","tokenID":"2"}},
+                {{"hash":"0x03","timeStamp":"1646128800","from":"{me}","to":"{other}","contractAddress":"{apes}","tokenID":"1"}}
+            ]}}"#))?.into_iter().map(NftTx::with_currency).collect(),
+        };
+
+        /*
+         * When
+         */
+        let eth = history.to_transactions(&"ETH".to_string(), &Jurisdiction::Sweden);
+        let nfts = history.to_transactions(&NFT.to_string(), &Jurisdiction::Sweden);
+
+        /*
+         * Then
+         */
+        let rows = |txns: &[Transaction]| txns.iter()
+            .map(|t| (t.r#type.clone(), t.paid_currency.clone(), t.paid_amount, t.exchanged_currency.clone(), t.exchanged_amount))
+            .collect::<Vec<_>>();
+        let (eth_, ape1, ape2) = ("ETH".to_string(), format!("{}:1", apes.to_lowercase()), format!("{}:2", apes.to_lowercase()));
+        assert_eq!(rows(&eth), vec![
+            (TransactionType::Sell, eth_.clone(), dec!(-1.005), eth_.clone(), dec!(1.005)),     // Paid for the NFT
+            (TransactionType::Sell, eth_.clone(), dec!(-0.01), eth_.clone(), dec!(0.01)),       // Gas of the mint
+            (TransactionType::Buy, eth_.clone(), dec!(2), eth_.clone(), dec!(-2)),
+            (TransactionType::Income, eth_.clone(), dec!(0.1), eth_.clone(), dec!(-0.1)),       // Royalty
+        ]);
+        assert_eq!(rows(&nfts), vec![
+            (TransactionType::Buy, ape1.clone(), dec!(1), eth_.clone(), dec!(-1.005)),
+            (TransactionType::Buy, ape2, dec!(1), eth_.clone(), dec!(-0.01)),
+            (TransactionType::Sell, ape1, dec!(-1), eth_, dec!(2)),
+        ]);
+        Ok(())
+    }
+
+    #[test]
+    fn should_convert_extreme_token_amounts() {
+        assert_eq!(units("1000000000000000000000000000000000000000", 18), Some(dec!(1_000_000_000_000_000_000_000)));
+        assert_eq!(units("1", 18), Some(dec!(0.000000000000000001)));
+        // Rounded to the 28 digits of a decimal
+        assert_eq!(units("123456789012345678901234567890", 18), Some(dec!(123456789012.34567890123456789)));
+        assert_eq!(units("1", 30), Some(dec!(0)));
+        // A spam token of 10^60 units
+        assert_eq!(units(&format!("1{}", "0".repeat(60)), 18), None);
+    }
+}
@@ -0,0 +1,167 @@
+use crate::error::{self, Result};
+use crate::transaction::{Currency, Transaction, TransactionType};
+use csv::{ReaderBuilder, Trim};
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use std::path::PathBuf;
+use tracing::debug;
+
+/// A line in the overrides file, which changes the type of the transactions in `currency`
+/// at `date`, e.g. to mark a transfer as a gift:
+///
+/// ```csv
+/// Date,Currency,Type,Value
+/// 2022-05-02 08:00:00,BTC,Gift,
+/// ```
+///
+/// The optional `Value` is the market value in the base currency at the time of the transaction.
+/// The optional `Note` and `Tags`, separated by spaces, are carried into the reports, e.g. to
+/// document why a transaction was reclassified. A line with an empty `Type` only annotates.
+#[derive(Debug, Deserialize, PartialEq)]
+pub(crate) struct Override {
+    #[serde(rename = "Date")]
+    date: String,
+
+    #[serde(rename = "Currency")]
+    currency: Currency,
+
+    #[serde(rename = "Type")]
+    r#type: Option<TransactionType>,
+
+    #[serde(rename = "Value")]
+    value: Option<Decimal>,
+
+    #[serde(rename = "Note")]
+    note: Option<String>,
+
+    #[serde(rename = "Tags")]
+    tags: Option<String>,
+}
+
+/// Reads the overrides file from path into a `Vec<Override>`.
+pub(crate) async fn read_overrides(path: &PathBuf) -> Result<Vec<Override>> {
+    let mut rdr = ReaderBuilder::new()
+        .has_headers(true)
+        .trim(Trim::All)
+        .from_path(path)?;
+    let overrides =
+        rdr.deserialize::<Override>()
+            .collect::<Result<Vec<Override>, csv::Error>>()
+            .map_err(|e| error::parse(path, e))?;
+    Ok(overrides)
+}
+
+/// Applies the overrides to the transactions with the same date and currency.
+pub(crate) fn apply(txns: &mut [Transaction], overrides: &[Override], base: &Currency) {
+    txns.iter_mut().for_each(|t| {
+        let found = overrides.iter().find(|o| o.date.eq(&t.date) && o.currency.eq(&t.paid_currency));
+        if let Some(o) = found {
+            if let Some(r#type) = &o.r#type {
+                debug!(date = %t.date, currency = %t.paid_currency, from = ?t.r#type, to = ?r#type, "Overriding");
+                t.r#type = r#type.clone();
+            }
+            if let Some(value) = o.value {
+                t.exchanged_currency = base.clone();
+                t.exchanged_amount = match t.r#type {
+                    TransactionType::Income => -value,  // The cost of what was received
+                    _ => value,
+                };
+            }
+            let tags: Vec<String> = o.tags.iter().flat_map(|tags| tags.split_whitespace()).map(String::from).collect();
+            t.origin.annotate(o.note.as_ref(), &tags);
+        }
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use crate::overrides::*;
+    use futures::executor::block_on;
+    use rust_decimal_macros::dec;
+    use std::error::Error;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn should_apply_overrides() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let mut file = NamedTempFile::new()?;
+        writeln!(file, "Date,Currency,Type,Value,Note,Tags
+                        2022-05-02 08:00:00,BTC,Gift,,Birthday of Anna,family
+                        2022-06-01 10:00:00,BTC,Donation,1500,,
+                        2022-07-01 10:00:00,BTC,,,,business travel")?;
+        let overrides = block_on(read_overrides(&file.path().to_path_buf()))?;
+        let mut txns = vec![
+            Transaction{
+                r#type: TransactionType::Transfer,
+                paid_currency: "BTC".to_string(),
+                paid_amount: dec!(-0.01),
+                exchanged_currency: "".to_string(),
+                exchanged_amount: dec!(0),
+                date: "2022-05-02 08:00:00".to_string(),
+                is_vault: false,
+                paid_fee: dec!(0),
+                exchanged_fee: dec!(0),
+                price_source: None,
+                unit_price: None,
+                fiat_value: None,
+                other_fee: None,
+                origin: Default::default()
+            },
+            Transaction{
+                r#type: TransactionType::Transfer,
+                paid_currency: "BTC".to_string(),
+                paid_amount: dec!(-0.005),
+                exchanged_currency: "".to_string(),
+                exchanged_amount: dec!(0),
+                date: "2022-06-01 10:00:00".to_string(),
+                is_vault: false,
+                paid_fee: dec!(0),
+                exchanged_fee: dec!(0),
+                price_source: None,
+                unit_price: None,
+                fiat_value: None,
+                other_fee: None,
+                origin: Default::default()
+            },
+            Transaction{
+                r#type: TransactionType::Sell,
+                paid_currency: "BTC".to_string(),
+                paid_amount: dec!(-0.001),
+                exchanged_currency: "SEK".to_string(),
+                exchanged_amount: dec!(500),
+                date: "2022-07-01 10:00:00".to_string(),
+                is_vault: false,
+                paid_fee: dec!(0),
+                exchanged_fee: dec!(0),
+                price_source: None,
+                unit_price: None,
+                fiat_value: None,
+                other_fee: None,
+                origin: Default::default()
+            },
+        ];
+
+        /*
+         * When
+         */
+        apply(&mut txns, &overrides, &"SEK".to_string());
+
+        /*
+         * Then
+         */
+        assert_eq!(txns[0].r#type, TransactionType::Gift);
+        assert_eq!(txns[0].exchanged_currency, "".to_string());
+        assert_eq!(txns[1].r#type, TransactionType::Donation);
+        assert_eq!(txns[1].exchanged_currency, "SEK".to_string());
+        assert_eq!(txns[1].exchanged_amount, dec!(1500));
+        assert_eq!(txns[0].origin.note, Some("Birthday of Anna".to_string()));
+        assert_eq!(txns[0].origin.tags, vec!["family".to_string()]);
+        assert!(!txns[1].origin.has_notes());
+        assert_eq!(txns[2].r#type, TransactionType::Sell);
+        assert_eq!(txns[2].origin.tags, vec!["business".to_string(), "travel".to_string()]);
+        Ok(())
+    }
+}
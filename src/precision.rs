@@ -0,0 +1,97 @@
+use crate::transaction::{is_fiat, is_nft, Currency, Transaction};
+use rust_decimal::{Decimal, RoundingStrategy};
+use std::fmt;
+use std::str::FromStr;
+
+/// The number of decimals that the amounts of a currency are kept to, as the exchanges and the
+/// chains count them, e.g. satoshis for BTC and wei for ETH.
+pub(crate) const DEFAULTS: [(&str, u32); 20] = [
+    ("BTC", 8), ("BCH", 8), ("LTC", 8), ("DOGE", 8), ("BNB", 8),
+    ("ETH", 18), ("ETC", 18), ("MATIC", 18), ("AVAX", 18), ("DAI", 18),
+    ("USDT", 6), ("USDC", 6), ("ADA", 6), ("XRP", 6), ("ATOM", 6), ("ALGO", 6), ("TRX", 6),
+    ("SOL", 9), ("DOT", 10), ("XLM", 7),
+];
+
+/// Fiat currencies without minor units.
+const WHOLE_FIAT: [&str; 2] = ["JPY", "ISK"];
+
+const FIAT_DECIMALS: u32 = 2;
+
+/// Of currencies that are not known, e.g. most ERC-20 tokens.
+const OTHER_DECIMALS: u32 = 18;
+
+/// The number of decimals of a currency, e.g. `BTC=8`, instead of the default.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Precision {
+    pub(crate) currency: Currency,
+    pub(crate) decimals: u32,
+}
+
+impl FromStr for Precision {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('=').map(|(c, d)| (c.trim(), d.trim().parse())) {
+            Some((currency, Ok(decimals))) if !currency.is_empty() && decimals <= 28 => {
+                Ok(Precision{ currency: currency.to_string(), decimals })
+            }
+            _ => Err(format!("Unknown precision `{}`. Expected e.g. BTC=8", s)),
+        }
+    }
+}
+
+impl fmt::Display for Precision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}={}", self.currency, self.decimals)
+    }
+}
+
+/// The number of decimals of `currency`: of `precisions` if given there, otherwise of `DEFAULTS`,
+/// 2 for fiat, 0 for NFTs, and 18 for any other currency.
+pub(crate) fn decimals_of(currency: &str, precisions: &[Precision]) -> u32 {
+    if let Some(p) = precisions.iter().find(|p| p.currency == currency) {
+        return p.decimals;
+    }
+    match DEFAULTS.iter().find(|(c, _)| *c == currency) {
+        Some((_, decimals)) => *decimals,
+        None if WHOLE_FIAT.contains(&currency) => 0,
+        None if is_fiat(currency) => FIAT_DECIMALS,
+        None if is_nft(currency) => 0,
+        None => OTHER_DECIMALS,
+    }
+}
+
+/// Rounds an amount to the decimals of its currency, half away from zero, without trailing
+/// zeros, e.g. `0.100000000000000000001` ETH to `0.1`.
+pub(crate) fn normalize(amount: Decimal, currency: &str, precisions: &[Precision]) -> Decimal {
+    amount.round_dp_with_strategy(decimals_of(currency, precisions), RoundingStrategy::MidpointAwayFromZero).normalize()
+}
+
+/// Normalizes the amounts and fees of the transactions as they are read, see `normalize`, so that
+/// the digits beyond the precision of a currency don't add up to a balance that is not there.
+pub(crate) fn apply(txns: &mut [Transaction], precisions: &[Precision]) {
+    for t in txns.iter_mut() {
+        t.paid_amount = normalize(t.paid_amount, &t.paid_currency, precisions);
+        t.paid_fee = normalize(t.paid_fee, &t.paid_currency, precisions);
+        t.exchanged_amount = normalize(t.exchanged_amount, &t.exchanged_currency, precisions);
+        t.exchanged_fee = normalize(t.exchanged_fee, &t.exchanged_currency, precisions);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::precision::*;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn should_normalize_amounts_to_precision_of_currency() {
+        let precisions = vec!["DOGE=2".parse::<Precision>().unwrap()];
+        assert_eq!(normalize(dec!(0.123456789), "BTC", &precisions), dec!(0.12345679));
+        assert_eq!(normalize(dec!(0.100000000000000000001), "ETH", &precisions), dec!(0.1));
+        assert_eq!(normalize(dec!(1234.565), "SEK", &precisions), dec!(1234.57));
+        assert_eq!(normalize(dec!(1234.5), "JPY", &precisions), dec!(1235));
+        assert_eq!(normalize(dec!(1.005), "DOGE", &precisions), dec!(1.01));
+        assert_eq!(normalize(dec!(1.50), "PEPE", &precisions), dec!(1.5));
+        assert!("BTC".parse::<Precision>().is_err());
+    }
+}
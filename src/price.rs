@@ -0,0 +1,635 @@
+use crate::decimal;
+use crate::error::{self, CryptotaxError, Result};
+use crate::timezone;
+use crate::transaction::{self, Currency, Transaction, TransactionType};
+use crate::warnings::{self, Code, Warning};
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use chrono_tz::Tz;
+use csv::{ReaderBuilder, Trim};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde::{Deserialize, Serialize, Serializer};
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{debug, instrument};
+
+#[cfg(feature = "native")]
+mod coingecko;
+#[cfg(feature = "native")]
+mod cryptocompare;
+#[cfg(feature = "native")]
+mod kraken;
+
+/// Provides market prices of currencies.
+pub(crate) trait PriceProvider {
+    /// Returns the latest known price of one unit of `currency` in `base`, with the name of the
+    /// source that priced it.
+    fn latest_price(&self, currency: &Currency, base: &Currency) -> Option<(Decimal, String)>;
+}
+
+/// A price given by the user, e.g. with `--price`.
+pub(crate) struct FixedPrice {
+    currency: Currency,
+    base: Currency,
+    price: Decimal,
+}
+
+impl FixedPrice {
+    pub(crate) fn new(currency: Currency, base: Currency, price: Decimal) -> FixedPrice {
+        FixedPrice { currency, base, price }
+    }
+}
+
+impl PriceProvider for FixedPrice {
+    fn latest_price(&self, currency: &Currency, base: &Currency) -> Option<(Decimal, String)> {
+        match currency.eq(&self.currency) && base.eq(&self.base) {
+            true => Some((self.price, "FIXED".to_string())),
+            false => None,
+        }
+    }
+}
+
+/// Uses the price of the latest transaction that was exchanged directly to or from the base
+/// currency as the market price. It is the user's own price, not the market's, so it is only a
+/// fallback, see `MarketPrice`, labelled `LAST TRADE`.
+pub(crate) struct LastTradePrice<'a> {
+    txns: &'a [Transaction],
+}
+
+impl<'a> LastTradePrice<'a> {
+    /// `txns` are expected to be sorted by date, see `Transaction::cmp_by_date`.
+    pub(crate) fn new(txns: &'a [Transaction]) -> LastTradePrice<'a> {
+        LastTradePrice { txns }
+    }
+}
+
+impl PriceProvider for LastTradePrice<'_> {
+    fn latest_price(&self, currency: &Currency, base: &Currency) -> Option<(Decimal, String)> {
+        self.txns.iter().rev()
+            .find(|t| {
+                t.paid_currency.eq(currency)
+                    && t.exchanged_currency.eq(base)
+                    && !t.paid_amount.is_zero()
+            })
+            .map(|t| ((t.exchanged_amount / t.paid_amount).abs(), "LAST TRADE".to_string()))
+    }
+}
+
+/// Values the holdings at the market price of the `PriceChain` at the time of the run, e.g. of
+/// `--prices` or `--price-table`, or else at the price of the latest trade, see `LastTradePrice`.
+pub(crate) struct MarketPrice<'a> {
+    chain: Option<PriceChain>,
+    last_trade: LastTradePrice<'a>,
+}
+
+impl<'a> MarketPrice<'a> {
+    /// `txns` are expected to be sorted by date, see `Transaction::cmp_by_date`.
+    pub(crate) fn new(chain: Option<PriceChain>, txns: &'a [Transaction]) -> MarketPrice<'a> {
+        MarketPrice { chain, last_trade: LastTradePrice::new(txns) }
+    }
+}
+
+impl PriceProvider for MarketPrice<'_> {
+    fn latest_price(&self, currency: &Currency, base: &Currency) -> Option<(Decimal, String)> {
+        self.chain.as_ref()
+            .and_then(|chain| chain.latest_price(currency, base))
+            .or_else(|| self.last_trade.latest_price(currency, base))
+    }
+}
+
+/// A market data API with historical prices of crypto currencies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PriceSource {
+    CoinGecko,
+    CryptoCompare,
+    /// The OHLC candles of the Kraken exchange.
+    Kraken,
+}
+
+impl PriceSource {
+    /// Fetches the candles around the time, a unix timestamp, unless `offline`.
+    #[cfg(feature = "native")]
+    fn fetch(&self, currency: &Currency, base: &Currency, time: i64, offline: bool) -> Result<Vec<Candle>> {
+        match self {
+            PriceSource::CoinGecko => coingecko::fetch(currency, base, time, offline),
+            PriceSource::CryptoCompare => cryptocompare::fetch(currency, base, time, offline),
+            PriceSource::Kraken => kraken::fetch(currency, base, time, offline),
+        }
+    }
+
+    /// Fails, as there is no network access without the `native` feature.
+    #[cfg(not(feature = "native"))]
+    fn fetch(&self, _currency: &Currency, _base: &Currency, _time: i64, _offline: bool) -> Result<Vec<Candle>> {
+        let msg = format!("Not fetching prices from {}, as this build has no network access", self);
+        Err(CryptotaxError::Config(msg))
+    }
+}
+
+impl FromStr for PriceSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_uppercase().as_str() {
+            "COINGECKO" => Ok(PriceSource::CoinGecko),
+            "CRYPTOCOMPARE" => Ok(PriceSource::CryptoCompare),
+            "KRAKEN" => Ok(PriceSource::Kraken),
+            _ => Err(format!("Unknown price source `{}`. Supported: COINGECKO, CRYPTOCOMPARE, KRAKEN", s)),
+        }
+    }
+}
+
+impl fmt::Display for PriceSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PriceSource::CoinGecko => write!(f, "COINGECKO"),
+            PriceSource::CryptoCompare => write!(f, "CRYPTOCOMPARE"),
+            PriceSource::Kraken => write!(f, "KRAKEN"),
+        }
+    }
+}
+
+#[cfg(feature = "native")]
+const DAY: i64 = 24 * 60 * 60;
+
+/// The decimals of the unit price of a transaction in a report, see `annotate`.
+const UNIT_PRICE_DECIMALS: u32 = 8;
+
+/// How far, in percent, the price that a trade implies may be from the market price before it is
+/// warned of, unless another limit is given, see `check_deviations`. Above the spread of Revolut
+/// and the movement of a price within a day.
+pub(crate) const MAX_DEVIATION: Decimal = dec!(5);
+
+/// The price at the start and at the end of a period, e.g. the open and the close of a day.
+#[derive(Debug, PartialEq)]
+struct Candle {
+    start: i64,     // Unix timestamps
+    end: i64,
+    open: Decimal,
+    close: Decimal,
+}
+
+/// The unix timestamp of the start of the day of the time, in UTC. The candles around a time are
+/// fetched for its whole day, so that the trades of a day share one request, see `http::fetch`.
+#[cfg(feature = "native")]
+fn day_of(time: i64) -> i64 {
+    time - time.rem_euclid(DAY)
+}
+
+/// Finds the candle that the time falls into, and interpolates linearly between its open and
+/// close. Daily candles are thereby turned into a price at the time of day.
+fn interpolate(candles: &[Candle], time: i64) -> Option<Decimal> {
+    candles.iter()
+        .find(|c| c.start <= time && time <= c.end)
+        .map(|c| match c.end > c.start {
+            true => c.open + (c.close - c.open) * Decimal::from(time - c.start) / Decimal::from(c.end - c.start),
+            false => c.open,
+        })
+}
+
+/// The unix timestamp of a transaction date, `YYYY-MM-DD HH:MM:SS` or `YYYY-MM-DD`, in the local
+/// time of `timezone`, see `timezone::to_local`.
+fn timestamp_of(date: &str, timezone: Tz) -> Result<i64> {
+    let datetime = match date.get(..19) {
+        Some(datetime) => NaiveDateTime::parse_from_str(datetime, "%Y-%m-%d %H:%M:%S"),
+        None => NaiveDate::parse_from_str(date, "%Y-%m-%d").map(|d| d.and_hms_opt(0, 0, 0).unwrap_or_default()),
+    };
+    datetime
+        .map(|d| timezone::to_utc(&d, timezone).and_utc().timestamp())
+        .map_err(|e| CryptotaxError::Pricing(format!("Can't price on `{}`: {}", date, e)))
+}
+
+/// A line in the price table, with the price of one unit of `currency` in `base`, e.g. of a
+/// delisted token:
+///
+/// ```csv
+/// currency,date,price,base
+/// LUNA,2022-05-10,0.52,USD
+/// ```
+///
+/// The date is a day, or a date and time for a price at a specific time of the day.
+#[derive(Debug, Deserialize, PartialEq)]
+pub(crate) struct PriceRow {
+    #[serde(rename = "currency")]
+    currency: Currency,
+
+    #[serde(rename = "date")]
+    date: String,
+
+    #[serde(rename = "price")]
+    price: Decimal,
+
+    #[serde(rename = "base")]
+    base: Currency,
+}
+
+/// Reads the price table from path into a `Vec<PriceRow>`.
+pub(crate) async fn read_price_table(path: &PathBuf) -> Result<Vec<PriceRow>> {
+    let mut rdr = ReaderBuilder::new()
+        .has_headers(true)
+        .trim(Trim::All)
+        .from_path(path)?;
+    let rows =
+        rdr.deserialize::<PriceRow>()
+            .collect::<Result<Vec<PriceRow>, csv::Error>>()
+            .map_err(|e| error::parse(path, e))?;
+    Ok(rows)
+}
+
+/// Asks the price table first, and then the price sources in order of preference, falling back
+/// to the next source if one fails or has no price.
+pub(crate) struct PriceChain {
+    table: Vec<PriceRow>,
+    sources: Vec<PriceSource>,
+    timezone: Tz,
+    offline: bool,
+}
+
+impl PriceChain {
+    pub(crate) fn new(table: Vec<PriceRow>, sources: Vec<PriceSource>) -> PriceChain {
+        PriceChain { table, sources, timezone: Tz::UTC, offline: false }
+    }
+
+    /// Takes the dates of the transactions as the local time of `timezone`, instead of UTC, when
+    /// fetching prices. The price table is matched by the local day.
+    pub(crate) fn with_timezone(self, timezone: Tz) -> PriceChain {
+        PriceChain { timezone, ..self }
+    }
+
+    /// Leaves out the price sources if `offline`, so that only the price table prices.
+    pub(crate) fn offline(self, offline: bool) -> PriceChain {
+        PriceChain { offline, ..self }
+    }
+
+    /// Returns the price of one unit of `currency` in `base` at the date, with the name of the
+    /// source that priced it, or `None` if no source has a price.
+    pub(crate) fn price_at(&self, currency: &Currency, base: &Currency, date: &str) -> Result<Option<(Decimal, String)>> {
+        if let Some(price) = self.table_price(currency, base, date) {
+            return Ok(Some((price, "TABLE".to_string())));
+        }
+        let time = timestamp_of(date, self.timezone)?;
+        for source in &self.sources {
+            match source.fetch(currency, base, time, self.offline).map(|candles| interpolate(&candles, time)) {
+                Ok(Some(price)) => return Ok(Some((price, source.to_string()))),
+                Ok(None) => debug!(%source, %currency, %base, %date, "No price"),
+                Err(e) => debug!(%source, %currency, %base, %date, error = %e, "Failed to price"),
+            }
+        }
+        Ok(None)
+    }
+
+    /// The date and time of the run, in the timezone of the chain.
+    fn now(&self) -> String {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or_default();
+        DateTime::from_timestamp(now, 0)
+            .map(|d| d.with_timezone(&self.timezone).format("%Y-%m-%d %H:%M:%S").to_string())
+            .unwrap_or_default()
+    }
+
+    /// The price in the table on the day of the date. Of the prices with a time, the latest one at
+    /// or before the date applies.
+    fn table_price(&self, currency: &Currency, base: &Currency, date: &str) -> Option<Decimal> {
+        let day = date.get(..10).unwrap_or_default();
+        self.table.iter()
+            .rev()
+            .filter(|r| r.currency.eq(currency) && r.base.eq(base) && r.date.get(..10) == Some(day))
+            .find(|r| r.date.len() <= 10 || r.date.as_str() <= date)
+            .map(|r| r.price)
+    }
+}
+
+impl PriceProvider for PriceChain {
+    fn latest_price(&self, currency: &Currency, base: &Currency) -> Option<(Decimal, String)> {
+        match self.price_at(currency, base, &self.now()) {
+            Ok(price) => price,
+            Err(e) => {
+                debug!(%currency, %base, error = %e, "Failed to price the holding");
+                None
+            }
+        }
+    }
+}
+
+/// Values the crypto-to-crypto trades at the market price of the exchanged currency in `base`,
+/// and records the source of every price. Fails with every trade that can't be priced, rather
+/// than leaving them without a value.
+#[instrument(name = "price", skip_all)]
+pub(crate) fn value(txns: &mut [Transaction], base: &Currency, chain: &PriceChain) -> Result<()> {
+    let mut missing = vec![];
+    for t in txns.iter_mut().filter(|t| !t.exchanged_currency.eq(base) && !transaction::is_fiat(&t.exchanged_currency)) {
+        match chain.price_at(&t.exchanged_currency, base, &t.date)? {
+            Some((price, source)) => {
+                debug!(date = %t.date, amount = %t.exchanged_amount, currency = %t.exchanged_currency, %price, %base, %source, "Priced");
+                t.exchanged_amount = decimal::mul(t.exchanged_amount, price)?;
+                t.exchanged_fee = decimal::mul(t.exchanged_fee, price)?;
+                t.exchanged_currency = base.clone();
+                t.price_source = Some(source);
+            }
+            None => missing.push(format!("No price of `{}` in `{}` at {}", t.exchanged_currency, base, t.date)),
+        }
+    }
+    if !missing.is_empty() {
+        let hint = match is_offline(chain) {
+            true => "Add them to the price table (--price-table), as --offline leaves out the price sources",
+            false => "Add them to the price table (--price-table)",
+        };
+        let msg = format!("{}\n{}", missing.join("\n"), hint);
+        return Err(CryptotaxError::Pricing(msg));
+    }
+    Ok(())
+}
+
+/// Whether the price sources are left out, see `PriceChain::offline`.
+#[cfg(feature = "native")]
+fn is_offline(chain: &PriceChain) -> bool {
+    chain.offline
+}
+
+/// Whether the price sources are left out, as they always are without the `native` feature.
+#[cfg(not(feature = "native"))]
+fn is_offline(_chain: &PriceChain) -> bool {
+    true
+}
+
+/// Annotates every transaction with the price of one unit of its currency in `base` and the value
+/// of its amount, so that the valuations can be checked: the price implied by a trade that is
+/// valued in `base`, or else the price of `chain`, if any. The price keeps full precision, and is
+/// only rounded to `UNIT_PRICE_DECIMALS` in a report, see `serialize_unit_price`. A transaction
+/// that can't be priced is left without them, and one whose price or value overflows without it.
+pub(crate) fn annotate(txns: &mut [Transaction], base: &Currency, chain: Option<&PriceChain>) {
+    for t in txns.iter_mut().filter(|t| !t.paid_amount.is_zero()) {
+        let amount = t.paid_amount.abs();
+        let (unit_price, fiat_value) = match chain {
+            _ if t.exchanged_currency.eq(base) && !t.exchanged_amount.is_zero() => {
+                (decimal::div(t.exchanged_amount.abs(), amount).ok(), Some(t.exchanged_amount.abs()))
+            }
+            Some(chain) => match chain.price_at(&t.paid_currency, base, &t.date) {
+                Ok(Some((price, _))) => (Some(price), decimal::mul(price, amount).ok()),
+                Ok(None) | Err(_) => (None, None),
+            },
+            None => (None, None),
+        };
+        t.unit_price = unit_price;
+        t.fiat_value = fiat_value;
+    }
+}
+
+/// Writes the unit price of a transaction rounded to `UNIT_PRICE_DECIMALS`, see `annotate`.
+pub(crate) fn serialize_unit_price<S: Serializer>(price: &Option<Decimal>, serializer: S) -> Result<S::Ok, S::Error> {
+    price.map(round_unit_price).serialize(serializer)
+}
+
+fn round_unit_price(price: Decimal) -> Decimal {
+    price.round_dp(UNIT_PRICE_DECIMALS).normalize()
+}
+
+/// Compares the price that every trade implies, see `annotate`, with the market price of `chain`
+/// at its date, and keeps the deviation in percent in its origin for the audit report. A deviation
+/// of more than `max_deviation` percent is warned of, as the rows may have been misparsed or
+/// paired wrongly. The unrounded prices are compared, and only the deviation that is kept is
+/// rounded. The trades that `value` priced at the market are left out.
+pub(crate) fn check_deviations(txns: &mut [Transaction], base: &Currency, chain: &PriceChain, max_deviation: Decimal) -> Result<()> {
+    let trades = txns.iter_mut()
+        .filter(|t| matches!(t.r#type, TransactionType::Buy | TransactionType::Sell))
+        .filter(|t| t.price_source.is_none() && t.exchanged_currency.eq(base));
+    for t in trades {
+        let Some(implied) = t.unit_price else { continue };
+        let market = match chain.price_at(&t.paid_currency, base, &t.date) {
+            Ok(Some((market, _))) if !market.is_zero() => market,
+            _ => continue,
+        };
+        let deviation = decimal::mul(decimal::div(decimal::sub(implied, market)?, market)?, dec!(100))?;
+        t.origin.deviation = Some(deviation.round_dp(2));
+        if deviation.abs() > max_deviation {
+            let msg = format!(
+                "The price of {} that the trade implies, {} {}, is {}% from the market price of {} {}",
+                t.paid_currency, round_unit_price(implied), base, deviation.round_dp(2), round_unit_price(market), base
+            );
+            warnings::report(Warning::new(Code::PriceDeviation, msg).at(&t.origin).at_date(&t.date));
+        }
+    }
+    Ok(())
+}
+
+/// Reports the buys, income, sells, liquidity pool legs and derivatives that are not valued in `base`, e.g. a trade of one crypto currency
+/// for another without a price source, or a futures profit settled in USDT. Their gains are left out of the sums.
+pub(crate) fn report_unpriced(txns: &[Transaction], base: &Currency) {
+    txns.iter()
+        .filter(|t| matches!(
+            t.r#type,
+            TransactionType::Buy | TransactionType::Income | TransactionType::Sell | TransactionType::AddLiquidity | TransactionType::RemoveLiquidity
+                | TransactionType::Margin | TransactionType::Futures | TransactionType::Short
+        ))
+        .filter(|t| !t.exchanged_currency.is_empty() && !t.exchanged_currency.eq(base))
+        .for_each(|t| {
+            let hint = match transaction::is_fiat(&t.exchanged_currency) {
+                true => "Convert it with --fx",
+                false => "Value it with --prices or --price-table",
+            };
+            let msg = format!("{} {} is not valued in {}. {}", t.exchanged_amount.abs(), t.exchanged_currency, base, hint);
+            warnings::report(Warning::new(Code::MissingPrice, msg).at(&t.origin).at_date(&t.date));
+        });
+}
+
+#[cfg(test)]
+mod test {
+    use crate::price::*;
+    use crate::transaction::TransactionType;
+    use futures::executor::block_on;
+    use rust_decimal_macros::dec;
+    use std::error::Error;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    #[cfg(feature = "native")]
+    fn should_interpolate_daily_candles() -> Result<(), Box<dyn Error>> {
+        let day = timestamp_of("2022-01-01", Tz::UTC)?;
+        let candles = vec![
+            Candle{ start: day, end: day + DAY, open: dec!(40000), close: dec!(44000) },
+            Candle{ start: day + DAY, end: day + 2 * DAY, open: dec!(44000), close: dec!(43000) },
+        ];
+        assert_eq!(interpolate(&candles, timestamp_of("2022-01-01 06:00:00", Tz::UTC)?), Some(dec!(41000)));
+        assert_eq!(interpolate(&candles, timestamp_of("2022-01-02 12:00:00", Tz::UTC)?), Some(dec!(43500)));
+        assert_eq!(interpolate(&candles, timestamp_of("2022-01-04 12:00:00", Tz::UTC)?), None);
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "native")]
+    fn should_price_from_table() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let mut file = NamedTempFile::new()?;
+        writeln!(file, "currency,date,price,base
+                        LUNA,2022-05-10,0.52,USD
+                        LUNA,2022-05-11 12:00:00,0.02,USD")?;
+        let chain = PriceChain::new(block_on(read_price_table(&file.path().to_path_buf()))?, vec![]);
+        let txn = |date: &str| Transaction{
+            r#type: TransactionType::Buy,
+            paid_currency: "BTC".to_string(),
+            paid_amount: dec!(0.001),
+            exchanged_currency: "LUNA".to_string(),
+            exchanged_amount: dec!(-100),
+            date: date.to_string(),
+            is_vault: false,
+            paid_fee: dec!(0),
+            exchanged_fee: dec!(0),
+            price_source: None,
+            unit_price: None,
+            fiat_value: None,
+            other_fee: None,
+            origin: Default::default()
+        };
+        let mut txns = vec![txn("2022-05-10 08:00:00"), txn("2022-05-11 13:00:00")];
+
+        /*
+         * When
+         */
+        value(&mut txns, &"USD".to_string(), &chain)?;
+
+        /*
+         * Then
+         */
+        assert_eq!(txns[0].exchanged_currency, "USD");
+        assert_eq!(txns[0].exchanged_amount, dec!(-52));
+        assert_eq!(txns[0].price_source, Some("TABLE".to_string()));
+        assert_eq!(txns[1].exchanged_amount, dec!(-2));
+        let missing = value(&mut [txn("2022-05-12 08:00:00"), txn("2022-05-13 08:00:00")], &"USD".to_string(), &chain);
+        assert_eq!(missing.map_err(|e| e.to_string()), Err([
+            "No price of `LUNA` in `USD` at 2022-05-12 08:00:00",
+            "No price of `LUNA` in `USD` at 2022-05-13 08:00:00",
+            "Add them to the price table (--price-table)",
+        ].join("\n")));
+        Ok(())
+    }
+
+    #[test]
+    fn should_value_holdings_at_market_price_before_last_trade() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let today = PriceChain::new(vec![], vec![]).now()[..10].to_string();
+        let mut file = NamedTempFile::new()?;
+        writeln!(file, "currency,date,price,base\nBTC,{},400000,SEK", today)?;
+        let chain = PriceChain::new(block_on(read_price_table(&file.path().to_path_buf()))?, vec![]);
+        let buy = |currency: &str| Transaction::buy().currency(currency).amount(dec!(0.1))
+            .exchanged_currency("SEK").exchanged_amount(dec!(-3000)).date("2022-05-01 06:00:00").build();
+        let txns = vec![buy("BTC")?, buy("ETH")?];
+        let (btc, eth, doge, sek) = ("BTC".to_string(), "ETH".to_string(), "DOGE".to_string(), "SEK".to_string());
+
+        /*
+         * When
+         */
+        let with_chain = MarketPrice::new(Some(chain), &txns);
+        let without_chain = MarketPrice::new(None, &txns);
+
+        /*
+         * Then
+         */
+        assert_eq!(with_chain.latest_price(&btc, &sek), Some((dec!(400000), "TABLE".to_string())));
+        assert_eq!(with_chain.latest_price(&eth, &sek), Some((dec!(30000), "LAST TRADE".to_string())));
+        assert_eq!(with_chain.latest_price(&doge, &sek), None);
+        assert_eq!(without_chain.latest_price(&btc, &sek), Some((dec!(30000), "LAST TRADE".to_string())));
+        Ok(())
+    }
+
+    #[test]
+    fn should_annotate_unit_prices() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let mut file = NamedTempFile::new()?;
+        writeln!(file, "currency,date,price,base
+                        BTC,2022-05-10,300000,SEK")?;
+        let chain = PriceChain::new(block_on(read_price_table(&file.path().to_path_buf()))?, vec![]);
+        let buy = Transaction::buy().currency("BTC").amount(dec!(0.00003))
+            .exchanged_currency("SEK").exchanged_amount(dec!(-2)).date("2022-05-01 06:00:00").build()?;
+        let transfer = |date: &str| Transaction::transfer().currency("BTC").amount(dec!(-0.001)).date(date).build();
+        let mut txns = vec![buy, transfer("2022-05-10 08:00:00")?, transfer("2022-05-11 08:00:00")?];
+        let mut without_chain = vec![transfer("2022-05-10 08:00:00")?];
+
+        /*
+         * When
+         */
+        annotate(&mut txns, &"SEK".to_string(), Some(&chain));
+        annotate(&mut without_chain, &"SEK".to_string(), None);
+
+        /*
+         * Then
+         */
+        assert_eq!((txns[0].unit_price, txns[0].fiat_value), (Some(dec!(66666.666666666666666666666667)), Some(dec!(2))));
+        assert_eq!(txns[0].unit_price.map(round_unit_price), Some(dec!(66666.66666667)));
+        assert_eq!((txns[1].unit_price, txns[1].fiat_value), (Some(dec!(300000)), Some(dec!(300))));
+        assert_eq!((txns[2].unit_price, txns[2].fiat_value), (None, None));
+        assert_eq!((without_chain[0].unit_price, without_chain[0].fiat_value), (None, None));
+        Ok(())
+    }
+
+    #[test]
+    fn should_warn_of_prices_far_from_the_market() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let mut file = NamedTempFile::new()?;
+        writeln!(file, "currency,date,price,base
+                        BTC,2022-05-10,300000,SEK")?;
+        let chain = PriceChain::new(block_on(read_price_table(&file.path().to_path_buf()))?, vec![]);
+        let buy = |exchanged_amount| Transaction::buy().currency("BTC").amount(dec!(0.001))
+            .exchanged_currency("SEK").exchanged_amount(exchanged_amount).date("2022-05-10 08:00:00").build();
+        let mut txns = vec![buy(dec!(-306))?, buy(dec!(-3000))?, buy(dec!(-315.01))?];
+        warnings::take();
+
+        /*
+         * When
+         */
+        annotate(&mut txns, &"SEK".to_string(), Some(&chain));
+        check_deviations(&mut txns, &"SEK".to_string(), &chain, MAX_DEVIATION)?;
+
+        /*
+         * Then
+         */
+        assert_eq!(txns[0].origin.deviation, Some(dec!(2)));
+        assert_eq!(txns[1].origin.deviation, Some(dec!(900)));
+        assert_eq!(txns[2].origin.deviation, Some(dec!(5)));  // 5.0033%, above the limit unrounded
+        let warnings = warnings::take();
+        assert_eq!(warnings.len(), 2);
+        assert_eq!(warnings[0].code, Code::PriceDeviation);
+        assert_eq!(warnings[0].message, "The price of BTC that the trade implies, 3000000 SEK, is 900% from the market price of 300000 SEK");
+        assert_eq!(warnings[1].message, "The price of BTC that the trade implies, 315010 SEK, is 5.00% from the market price of 300000 SEK");
+        Ok(())
+    }
+
+    #[test]
+    fn should_warn_of_derivatives_not_valued_in_base() {
+        /*
+         * Given
+         */
+        let futures = |currency: &str, amount| Transaction{
+            r#type: TransactionType::Futures,
+            date: "2022-01-02 12:00:00".to_string(),
+            paid_currency: "USDT".to_string(),
+            paid_amount: amount,
+            exchanged_currency: currency.to_string(),
+            exchanged_amount: amount,
+            ..Transaction::new()
+        };
+        let txns = vec![futures("USDT", dec!(12.5)), futures("SEK", dec!(113.13))];
+        warnings::take();
+
+        /*
+         * When
+         */
+        report_unpriced(&txns, &"SEK".to_string());
+
+        /*
+         * Then
+         */
+        let warnings = warnings::take();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].code, Code::MissingPrice);
+        assert_eq!(warnings[0].message, "12.5 USDT is not valued in SEK. Value it with --prices or --price-table");
+    }
+}
@@ -0,0 +1,80 @@
+use crate::credentials;
+use crate::error::Result;
+use crate::http;
+use crate::price::{day_of, Candle, DAY};
+use crate::transaction::Currency;
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use tracing::debug;
+
+/// CoinGecko's ids of common currencies. Other currencies are looked up by their lowercase symbol.
+const IDS: [(&str, &str); 12] = [
+    ("ADA", "cardano"),
+    ("BCH", "bitcoin-cash"),
+    ("BTC", "bitcoin"),
+    ("DOGE", "dogecoin"),
+    ("DOT", "polkadot"),
+    ("EOS", "eos"),
+    ("ETH", "ethereum"),
+    ("LINK", "chainlink"),
+    ("LTC", "litecoin"),
+    ("SOL", "solana"),
+    ("XLM", "stellar"),
+    ("XRP", "ripple"),
+];
+
+/// A response of the `market_chart/range` endpoint, e.g.
+/// `{"prices":[[1640995200000,46319.65],[1641081600000,47816.08]],"market_caps":[...],"total_volumes":[...]}`.
+#[derive(Debug, Deserialize)]
+struct Response {
+    prices: Vec<(i64, Decimal)>,    // Milliseconds and price
+}
+
+/// Fetches the prices from the day before to the day after the day of the time. CoinGecko chooses the
+/// granularity, hourly for recent dates and daily for older ones. A demo API key in the keyring
+/// raises the rate limit.
+pub(super) fn fetch(currency: &Currency, base: &Currency, time: i64, offline: bool) -> Result<Vec<Candle>> {
+    let id = IDS.iter()
+        .find(|(symbol, _)| symbol == currency)
+        .map(|(_, id)| id.to_string())
+        .unwrap_or_else(|| currency.to_lowercase());
+    let url = format!(
+        "https://api.coingecko.com/api/v3/coins/{}/market_chart/range?vs_currency={}&from={}&to={}",
+        id, base.to_lowercase(), day_of(time) - DAY, day_of(time) + 2 * DAY
+    );
+    debug!(%url, "Fetching prices");
+    let mut request = http::get(&url);
+    if let Some(key) = credentials::api_key("coingecko") {
+        request = request.set("x-cg-demo-api-key", &key);
+    }
+    let body = http::fetch("coingecko", offline, request)?;
+    parse(&body)
+}
+
+/// Turns every two consecutive prices into a candle.
+fn parse(body: &str) -> Result<Vec<Candle>> {
+    let response: Response = serde_json::from_str(body)?;
+    let candles = response.prices
+        .windows(2)
+        .map(|w| Candle{ start: w[0].0 / 1000, end: w[1].0 / 1000, open: w[0].1, close: w[1].1 })
+        .collect();
+    Ok(candles)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::price::coingecko::*;
+    use rust_decimal_macros::dec;
+    use std::error::Error;
+
+    #[test]
+    fn should_parse_prices_into_candles() -> Result<(), Box<dyn Error>> {
+        let body = r#"{"prices":[[1640995200000,46319.65],[1641081600000,47816.08],[1641168000000,47387.21]],
+            "market_caps":[],"total_volumes":[]}"#;
+        assert_eq!(parse(body)?, vec![
+            Candle{ start: 1640995200, end: 1641081600, open: dec!(46319.65), close: dec!(47816.08) },
+            Candle{ start: 1641081600, end: 1641168000, open: dec!(47816.08), close: dec!(47387.21) },
+        ]);
+        Ok(())
+    }
+}
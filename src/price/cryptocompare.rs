@@ -0,0 +1,84 @@
+use crate::credentials;
+use crate::error::Result;
+use crate::http;
+use crate::price::{day_of, Candle, DAY};
+use crate::transaction::Currency;
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use std::io;
+use tracing::debug;
+
+/// A response of the `histoday` endpoint, e.g.
+/// `{"Response":"Success","Data":{"Data":[{"time":1640995200,"open":46216.93,"close":47722.66}]}}`.
+#[derive(Debug, Deserialize)]
+struct Response {
+    #[serde(rename = "Response")]
+    response: String,
+
+    #[serde(rename = "Message", default)]
+    message: String,
+
+    #[serde(rename = "Data", default)]
+    data: Data,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Data {
+    #[serde(rename = "Data", default)]
+    data: Vec<Ohlc>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Ohlc {
+    time: i64,
+    open: Decimal,
+    close: Decimal,
+}
+
+/// Fetches the daily candles of the day of the time and the day before, with the API key in the
+/// keyring if there is one.
+pub(super) fn fetch(currency: &Currency, base: &Currency, time: i64, offline: bool) -> Result<Vec<Candle>> {
+    let url = format!(
+        "https://min-api.cryptocompare.com/data/v2/histoday?fsym={}&tsym={}&limit=1&toTs={}",
+        currency, base, day_of(time) + DAY - 1
+    );
+    debug!(%url, "Fetching prices");
+    let mut request = http::get(&url);
+    if let Some(key) = credentials::api_key("cryptocompare") {
+        request = request.set("authorization", &format!("Apikey {}", key));
+    }
+    let body = http::fetch("cryptocompare", offline, request)?;
+    parse(&body)
+}
+
+fn parse(body: &str) -> Result<Vec<Candle>> {
+    let response: Response = serde_json::from_str(body)?;
+    if response.response != "Success" {
+        return Err(io::Error::other(response.message).into());
+    }
+    let candles = response.data.data.into_iter()
+        .filter(|o| !o.open.is_zero())  // Days before the pair was traded
+        .map(|o| Candle{ start: o.time, end: o.time + DAY, open: o.open, close: o.close })
+        .collect();
+    Ok(candles)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::price::cryptocompare::*;
+    use rust_decimal_macros::dec;
+    use std::error::Error;
+
+    #[test]
+    fn should_parse_daily_candles() -> Result<(), Box<dyn Error>> {
+        let body = r#"{"Response":"Success","Message":"","Data":{"Aggregated":false,"TimeFrom":1640908800,"TimeTo":1640995200,"Data":[
+            {"time":1640908800,"high":0,"low":0,"open":0,"volumefrom":0,"volumeto":0,"close":0},
+            {"time":1640995200,"high":47827.31,"low":46288.49,"open":46216.93,"volumefrom":14185.47,"volumeto":669013449.4,"close":47722.66}
+        ]}}"#;
+        assert_eq!(parse(body)?, vec![
+            Candle{ start: 1640995200, end: 1641081600, open: dec!(46216.93), close: dec!(47722.66) },
+        ]);
+        assert!(parse(r#"{"Response":"Error","Message":"fsym param is invalid.","Data":{}}"#).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,77 @@
+use crate::error::Result;
+use crate::http;
+use crate::price::{day_of, Candle, DAY};
+use crate::transaction::Currency;
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::io;
+use tracing::debug;
+
+/// A response of the `OHLC` endpoint, e.g.
+/// `{"error":[],"result":{"XXBTZEUR":[[1640995200,"40712.3","41900.0","40500.1","41838.2","41300.9","150.1",1000]],"last":1640995200}}`.
+#[derive(Debug, Deserialize)]
+struct Response {
+    error: Vec<String>,
+
+    #[serde(default)]
+    result: HashMap<String, serde_json::Value>,  // The candles by pair, and the `last` timestamp
+}
+
+/// Time, open, high, low, close, volume weighted average price, volume and count.
+type Ohlc = (i64, Decimal, Decimal, Decimal, Decimal, Decimal, Decimal, i64);
+
+/// Kraken's names of the currencies whose names differ, e.g. `XBT` for BTC.
+fn to_asset(currency: &str) -> &str {
+    match currency {
+        "BTC" => "XBT",
+        "DOGE" => "XDG",
+        currency => currency,
+    }
+}
+
+/// Fetches the daily candles from the day before the time. Kraken only serves the latest 720
+/// candles of an interval, so older trades are left to the other sources.
+pub(super) fn fetch(currency: &Currency, base: &Currency, time: i64, offline: bool) -> Result<Vec<Candle>> {
+    let url = format!(
+        "https://api.kraken.com/0/public/OHLC?pair={}{}&interval=1440&since={}",
+        to_asset(currency), to_asset(base), day_of(time) - DAY
+    );
+    debug!(%url, "Fetching prices");
+    let body = http::fetch("kraken", offline, http::get(&url))?;
+    parse(&body)
+}
+
+fn parse(body: &str) -> Result<Vec<Candle>> {
+    let response: Response = serde_json::from_str(body)?;
+    if !response.error.is_empty() {
+        return Err(io::Error::other(response.error.join(", ")).into());
+    }
+    let mut candles = vec![];
+    for (_, value) in response.result.into_iter().filter(|(key, _)| key != "last") {
+        let rows: Vec<Ohlc> = serde_json::from_value(value)?;
+        candles.extend(rows.into_iter().map(|(time, open, _, _, close, ..)| {
+            Candle{ start: time, end: time + DAY, open, close }
+        }));
+    }
+    Ok(candles)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::price::kraken::*;
+    use rust_decimal_macros::dec;
+    use std::error::Error;
+
+    #[test]
+    fn should_parse_daily_candles() -> Result<(), Box<dyn Error>> {
+        let body = r#"{"error":[],"result":{"XXBTZEUR":[
+            [1640995200,"40712.3","41900.0","40500.1","41838.2","41300.9","150.12345678",1000]
+        ],"last":1640995200}}"#;
+        assert_eq!(parse(body)?, vec![
+            Candle{ start: 1640995200, end: 1641081600, open: dec!(40712.3), close: dec!(41838.2) },
+        ]);
+        assert!(parse(r#"{"error":["EQuery:Unknown asset pair"]}"#).is_err());
+        Ok(())
+    }
+}
@@ -1,11 +1,45 @@
-use crate::transaction::{Currency, Transaction, TransactionType};
-use csv::{ReaderBuilder, Trim};
-use log::{debug, info};
+use crate::cryptotax::{Options, StoreAccess};
+use crate::decimal;
+use crate::error::{self, CryptotaxError, Result};
+use crate::invariants;
+use crate::keywords::{self, Keywords};
+use crate::locale::{self, CsvFormat, Direction, ExchangeDescription, Pocket};
+use crate::reader::generic::Mapping;
+#[cfg(feature = "native")]
+use crate::store::{self, Store};
+use crate::timestamp::Timestamp;
+use crate::transaction::{is_fiat, Currency, Origin, Transaction, TransactionType};
+use crate::warnings::{self, Code, Warning};
+use chrono::DateTime;
+use csv::{ReaderBuilder, StringRecord};
+#[cfg(feature = "native")]
+use memmap2::Mmap;
 use rust_decimal::prelude::*;
-use serde::{Deserialize, Serialize};
-use std::io::{self};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::{self, Read};
+use std::iter;
 use std::ops::Neg;
-use std::path::PathBuf;
+use std::path::Path;
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{debug, instrument};
+
+mod archive;
+mod binance;
+mod bitfinex;
+mod bitstamp;
+mod celsius;
+mod crypto;
+mod cryptocom;
+pub(crate) mod generic;
+pub(crate) mod kraken;
+mod kucoin;
+mod nexo;
+mod universal;
 
 #[derive(Debug, Deserialize, Serialize, PartialEq)]
 pub(crate) struct Row {
@@ -21,22 +55,22 @@ pub(crate) struct Row {
     #[serde(rename = "Description")]
     description: String,
 
-    #[serde(rename = "Amount")]
+    #[serde(rename = "Amount", deserialize_with = "amount")]
     amount: Decimal,
 
-    #[serde(rename = "Fee")]
+    #[serde(rename = "Fee", deserialize_with = "amount")]
     fee: Decimal,
 
     #[serde(rename = "Currency")]
     currency: Currency,
 
-    #[serde(rename = "Original Amount")]
+    #[serde(rename = "Original Amount", deserialize_with = "amount")]
     original_amount: Decimal,
 
     #[serde(rename = "Original Currency")]
     original_currency: Currency,
 
-    #[serde(rename = "Settled Amount")]
+    #[serde(rename = "Settled Amount", default, deserialize_with = "optional_amount")]
     settled_amount: Option<Decimal>,
 
     #[serde(rename = "Settled Currency")]
@@ -45,53 +79,522 @@ pub(crate) struct Row {
     #[serde(rename = "State")]
     state: State,
 
-    #[serde(rename = "Balance")]
+    #[serde(rename = "Balance", default, deserialize_with = "optional_amount")]
     balance: Option<Decimal>,
+
+    #[serde(skip)]
+    line: u64,                      // In the file, for the audit report
 }
 
-#[derive(Debug, Deserialize, Serialize, PartialEq)]
-#[serde(rename_all = "PascalCase")]
+/// A row of the account statements of 2018 to 2020, see `StatementVersion::Legacy`. The columns
+/// that not every one of them has are optional, whether they are blank or left out.
+#[derive(Debug, Deserialize)]
+struct LegacyRow {
+    #[serde(rename = "Type")]
+    r#type: Type,
+
+    #[serde(rename = "Started Date")]
+    started_date: String,
+
+    #[serde(rename = "Completed Date", default)]
+    completed_date: Option<String>,
+
+    #[serde(rename = "Description")]
+    description: String,
+
+    #[serde(rename = "Amount", deserialize_with = "amount")]
+    amount: Decimal,
+
+    #[serde(rename = "Fee", default, deserialize_with = "optional_amount")]
+    fee: Option<Decimal>,
+
+    #[serde(rename = "Currency")]
+    currency: Currency,
+
+    #[serde(rename = "State")]
+    state: State,
+
+    #[serde(rename = "Balance", default, deserialize_with = "optional_amount")]
+    balance: Option<Decimal>,
+}
+
+impl From<LegacyRow> for Row {
+    /// A card payment without the original amount is taken as paid in the currency itself, which
+    /// is then valued at its market price with `--prices`.
+    fn from(row: LegacyRow) -> Self {
+        Row{
+            r#type: row.r#type,
+            started_date: row.started_date,
+            completed_date: row.completed_date,
+            description: row.description,
+            amount: row.amount,
+            fee: row.fee.unwrap_or_default(),
+            original_amount: row.amount,
+            original_currency: row.currency.clone(),
+            currency: row.currency,
+            settled_amount: None,
+            settled_currency: None,
+            state: row.state,
+            balance: row.balance,
+            line: 0,
+        }
+    }
+}
+
+/// Reads an amount from the text of its field. `Decimal` on its own reads the field as a float,
+/// which rounds it to 17 digits, or as an integer, which it rejects above `u64`, e.g. 10^21 units
+/// of a token.
+fn amount<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Decimal, D::Error> {
+    let field = String::deserialize(deserializer)?;
+    Decimal::from_str(&field)
+        .or_else(|_| Decimal::from_scientific(&field))
+        .map_err(|_| serde::de::Error::custom(format!("invalid amount `{}`", field)))
+}
+
+/// Reads an amount like `amount`, or `None` if the field is blank.
+fn optional_amount<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Decimal>, D::Error> {
+    #[derive(Deserialize)]
+    struct Amount(#[serde(deserialize_with = "amount")] Decimal);
+
+    Ok(Option::<Amount>::deserialize(deserializer)?.map(|Amount(amount)| amount))
+}
+
+/// The versions of the columns of the account statement, detected by its header. The rows of
+/// every version are read into a `Row`.
+#[derive(Debug, PartialEq)]
+enum StatementVersion {
+    /// The statements of 2018 to 2020, without the original and the settled amounts of a card
+    /// payment, and some also without the fee or the balance, see `LegacyRow`.
+    Legacy,
+    /// The statements since, with the original amount of a card payment in another currency.
+    Settled,
+}
+
+/// The columns that the account statements of every `StatementVersion` have.
+const REQUIRED_COLUMNS: [&str; 6] = ["Type", "Started Date", "Description", "Amount", "Currency", "State"];
+
+impl StatementVersion {
+    fn detect(headers: &StringRecord) -> StatementVersion {
+        match headers.iter().any(|h| h == "Original Amount") {
+            true => StatementVersion::Settled,
+            false => StatementVersion::Legacy,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq, Clone)]
+#[serde(from = "String", into = "String")]
 enum Type {
     Exchange,
     Transfer,
     Cashback,
     Topup,
-
-    #[serde(rename = "Card Payment")]
     CardPayment,
+    /// A buy of a currency with a fiat currency in one row, see `Row::trade_to_transaction`,
+    /// rather than the two rows of an `Exchange`.
+    Buy,
+    /// A sell of a currency for a fiat currency in one row, like `Buy`.
+    Sell,
+    /// Interest paid on a savings account or on staked crypto, see
+    /// `Row::interest_to_transaction`.
+    Interest,
+    /// A known type of row that neither exchanges nor moves a currency, see `OTHER_TYPES`.
+    Other(String),
+    /// A type that is not in the registry, which is reported with samples of its rows, rather
+    /// than dropping them unseen.
+    Unknown(String),
+}
+
+/// The types of the rows of the account statement that are read, by their names in the export.
+/// Newer exports write them in capitals with underscores, e.g. `CARD_PAYMENT`.
+const TYPES: [(&str, Type); 8] = [
+    ("Exchange", Type::Exchange),
+    ("Transfer", Type::Transfer),
+    ("Cashback", Type::Cashback),
+    ("Topup", Type::Topup),
+    ("Card Payment", Type::CardPayment),
+    ("Buy", Type::Buy),
+    ("Sell", Type::Sell),
+    ("Interest", Type::Interest),
+];
+
+/// The other known types, of rows that are left out as they don't affect the holdings of a
+/// currency, e.g. a card refund is already in the balance of the card payment it refunds.
+const OTHER_TYPES: [&str; 5] = ["ATM", "Card Refund", "Charge", "Fee", "Refund"];
+
+/// How many rows of an unknown type are shown in its warning.
+const UNKNOWN_TYPE_SAMPLES: usize = 3;
+
+impl From<String> for Type {
+    fn from(name: String) -> Self {
+        let trimmed = name.trim();
+        let is = |known: &str| known.len() == trimmed.len()
+            && known.bytes().zip(trimmed.bytes()).all(|(k, n)| k.eq_ignore_ascii_case(&n) || (k == b' ' && n == b'_'));
+        match TYPES.iter().find(|(known, _)| is(known)) {
+            Some((_, r#type)) => r#type.clone(),
+            None if OTHER_TYPES.iter().any(|known| is(known)) => Type::Other(name),
+            None => Type::Unknown(name),
+        }
+    }
 }
 
+impl From<Type> for String {
+    fn from(r#type: Type) -> Self {
+        match r#type {
+            Type::Other(name) | Type::Unknown(name) => name,
+            r#type => TYPES.iter().find(|(_, t)| *t == r#type).map(|(name, _)| name.to_string()).unwrap_or_default(),
+        }
+    }
+}
+
+/// The state of a row, in title case, or in upper case in the later statements. Only the
+/// `Completed` rows are read into transactions.
 #[derive(Debug, Deserialize, Serialize, PartialEq)]
 #[serde(rename_all = "PascalCase")]
 enum State {
+    #[serde(alias = "COMPLETED")]
     Completed,
+    #[serde(alias = "DECLINED")]
     Declined,
+    #[serde(alias = "PENDING")]
+    Pending,
+    #[serde(alias = "REVERTED")]
+    Reverted,
+}
+
+/// The columns that hold decimals, which are normalized according to the detected `CsvFormat`.
+pub(crate) const DECIMAL_COLUMNS: [&str; 5] = ["Amount", "Fee", "Original Amount", "Settled Amount", "Balance"];
+
+/// The kinds of csv files that can be read, detected by their columns.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ExportFormat {
+    /// The Revolut account statement, where an exchange is two `Exchange` rows.
+    Account,
+    /// The Revolut crypto statement, where a trade is a single `Buy` or `Sell` row.
+    Crypto,
+    /// The Binance futures transaction history.
+    BinanceFutures,
+    /// The Kraken ledger.
+    KrakenLedger,
+    /// The Bitstamp "Transactions" export.
+    BitstampTransactions,
+    /// The Bitfinex ledger.
+    BitfinexLedger,
+    /// The KuCoin trade history.
+    KucoinTrades,
+    /// The Crypto.com App "crypto_transactions_record" export.
+    CryptoComApp,
+    /// The Nexo transactions export.
+    NexoTransactions,
+    /// The Celsius transactions export.
+    CelsiusTransactions,
+    /// The Koinly universal csv, e.g. of a user migrating from Koinly.
+    KoinlyUniversal,
+    /// The CoinTracking csv import format.
+    CoinTrackingUniversal,
+    /// The local store of imported transactions, see `Store`.
+    #[cfg(feature = "native")]
+    Store,
+}
+
+impl ExportFormat {
+    fn detect(sample: &str, format: &CsvFormat, access: &StoreAccess) -> ExportFormat {
+        #[cfg(feature = "native")]
+        if store::is_store(sample, access) {
+            return ExportFormat::Store;
+        }
+        #[cfg(not(feature = "native"))]
+        let _ = access;
+        let header = sample.lines().next().unwrap_or_default();
+        let columns: Vec<&str> =
+            header.split(format.delimiter as char)
+                .map(|h| locale::canonical_header(h.trim().trim_matches('"')))
+                .collect();
+        let has = |names: &[&str]| names.iter().all(|n| columns.contains(n));
+        if has(&["Symbol", "Quantity"]) {
+            ExportFormat::Crypto
+        } else if has(&["UTC_Time", "Operation", "Coin", "Change"]) {
+            ExportFormat::BinanceFutures
+        } else if has(&["txid", "refid", "asset", "amount"]) {
+            ExportFormat::KrakenLedger
+        } else if has(&["Datetime", "Account", "Sub Type"]) {
+            ExportFormat::BitstampTransactions
+        } else if has(&["DESCRIPTION", "CURRENCY", "AMOUNT", "WALLET"]) {
+            ExportFormat::BitfinexLedger
+        } else if has(&["tradeCreatedAt", "symbol", "side", "funds"]) {
+            ExportFormat::KucoinTrades
+        } else if has(&["Timestamp (UTC)", "Transaction Kind", "Native Amount"]) {
+            ExportFormat::CryptoComApp
+        } else if has(&["Input Currency", "Output Currency", "USD Equivalent"]) {
+            ExportFormat::NexoTransactions
+        } else if has(&["Transaction type", "Coin type", "Coin amount", "USD Value"]) {
+            ExportFormat::CelsiusTransactions
+        } else if has(&["Sent Amount", "Sent Currency", "Received Amount", "Received Currency"]) {
+            ExportFormat::KoinlyUniversal
+        } else if has(&["Buy Amount", "Buy Currency", "Sell Amount", "Sell Currency"]) {
+            ExportFormat::CoinTrackingUniversal
+        } else {
+            ExportFormat::Account
+        }
+    }
+}
+
+/// The path that reads the file from `std::io::stdin()`, e.g. `cryptotax -`.
+pub(crate) const STDIN: &str = "-";
+
+static STDIN_CONTENT: OnceLock<Vec<u8>> = OnceLock::new();
+
+/// Opens the file, or `std::io::stdin()` if the path is `STDIN`, and decompresses it if it is
+/// gzipped or zipped, see `archive::decompress`.
+pub(crate) fn open(path: &Path) -> Result<Box<dyn Read>> {
+    archive::decompress(open_raw(path)?)
+}
+
+/// Opens the file as it is. Stdin is read to the end the first time, and kept, as the file is
+/// read once for every currency.
+fn open_raw(path: &Path) -> Result<Box<dyn Read>> {
+    if path != Path::new(STDIN) {
+        return Ok(Box::new(File::open(path)?));
+    }
+    if let Some(content) = STDIN_CONTENT.get() {
+        return Ok(Box::new(content.as_slice()));
+    }
+    let mut content = vec![];
+    io::stdin().lock().read_to_end(&mut content)?;
+    Ok(Box::new(STDIN_CONTENT.get_or_init(|| content).as_slice()))
+}
+
+/// Reads the first lines of the file.
+fn read_sample(path: &Path) -> Result<String> {
+    let mut sample = vec![];
+    open(path)?.take(64 * 1024).read_to_end(&mut sample)?;
+    Ok(sample_of(&sample))
+}
+
+/// Reads the first lines of the file and detects its `CsvFormat`.
+fn detect_format(path: &Path) -> Result<CsvFormat> {
+    let format = CsvFormat::detect(&read_sample(path)?);
+    debug!(?format, "Detected csv format");
+    Ok(format)
 }
 
-/// Reads the file from path into a `Vec<Row>`.
-async fn deserialize_from(path: &PathBuf) -> io::Result<Vec<Row>> {
-    let now = std::time::Instant::now();
-    let mut rdr = ReaderBuilder::new()
+/// Reads the file from path into a `Vec<Row>`. The file is memory-mapped if `Options::mmap` is
+/// set, which saves copying a large file into buffers while parsing it, or read through a buffer
+/// if it can't be mapped, e.g. if it is empty or a pipe. Without the `native` feature the file is
+/// always read through a buffer. The rows are collapsed like `deserialize_from_reader`, unless
+/// `Options::no_dedup` is set.
+async fn deserialize_from(path: &Path, options: &Options) -> Result<Vec<Row>> {
+    let format = detect_format(path)?;
+    let dedup = !options.no_dedup;
+    #[cfg(feature = "native")]
+    if options.mmap {
+        match map(path) {
+            Ok(map) if !archive::is_compressed(&map) => {
+                return deserialize_from_reader(&map[..], &format, dedup).map_err(|e| e.in_file(&path.display().to_string()));
+            }
+            Ok(_) => debug!("The file is compressed, reading it instead"),
+            Err(e) => debug!(error = %e, "Could not map the file, reading it instead"),
+        }
+    }
+    deserialize_from_reader(open(path)?, &format, dedup).map_err(|e| e.in_file(&path.display().to_string()))
+}
+
+/// Reads an account statement in `format` from any reader into a `Vec<Row>`. The fields are
+/// trimmed while they are normalized, see `CsvFormat::normalize_record`, rather than by the csv
+/// reader, which copies every record to trim it. The rows that look exported twice are collapsed
+/// if `dedup` is set, see `collapse_duplicates`.
+fn deserialize_from_reader<R: Read>(rdr: R, format: &CsvFormat, dedup: bool) -> Result<Vec<Row>> {
+    let rdr = ReaderBuilder::new()
         .has_headers(true)
-        // .delimiter(b';')
-        .delimiter(b',')
-        .trim(Trim::All)
-        .from_path(path)?;
-    info!("ReaderBuilder::from_path done. Elapsed: {:.2?}", now.elapsed());
-
-    let now = std::time::Instant::now();
-    let txns: Vec<Row> =
-        rdr.deserialize::<Row>()
-            .filter_map(|record| record.ok())
+        .delimiter(format.delimiter)
+        .from_reader(rdr);
+    deserialize_rows(rdr, format, dedup)
+}
+
+/// Maps the file into memory, if it is a regular file. A pipe, a socket or a device is read
+/// instead, as it can't be mapped or can change under the map.
+#[cfg(feature = "native")]
+fn map(path: &Path) -> Result<Mmap> {
+    let file = File::open(path)?;
+    if !file.metadata()?.is_file() {
+        return Err(io::Error::new(io::ErrorKind::Unsupported, "not a regular file").into());
+    }
+    // SAFETY: The map is only read. If another process truncates the file while it is mapped, a
+    // read past the new end isn't an error but a SIGBUS that kills the run, and if it rewrites the
+    // file the rows may be garbled. So the file must not change during a run with `--mmap`, which
+    // is why mapping is opt-in.
+    Ok(unsafe { Mmap::map(&file) }?)
+}
+
+fn deserialize_rows<R: Read>(mut rdr: csv::Reader<R>, format: &CsvFormat, dedup: bool) -> Result<Vec<Row>> {
+    let headers: StringRecord = rdr.headers()?.iter().map(|h| locale::canonical_header(h.trim())).collect();
+    let decimal_columns: Vec<usize> =
+        headers.iter()
+            .enumerate()
+            .filter(|(_, h)| DECIMAL_COLUMNS.contains(h))
+            .map(|(i, _)| i)
             .collect();
-    info!("reader::deserialize done. Elapsed: {:.2?}", now.elapsed());
+    if let Some(column) = REQUIRED_COLUMNS.iter().find(|c| !headers.iter().any(|h| h == **c)) {
+        let message = format!("No `{}` column. Not an export of a supported exchange, see the README, nor a store", column);
+        return Err(CryptotaxError::Parse{ file: "".to_string(), line: Some(1), column: None, message });
+    }
+    let version = StatementVersion::detect(&headers);
+    debug!(?version, "Detected statement version");
+    let mut txns = vec![];
+    let mut unknown: BTreeMap<String, Vec<(u64, String)>> = BTreeMap::new();
+    let (mut record, mut normalized) = (StringRecord::new(), StringRecord::new());
+    loop {
+        match rdr.read_record(&mut record) {
+            Ok(false) => break,
+            Ok(true) => {
+                let normalized = format.normalize_record(&record, &decimal_columns, &mut normalized);
+                let row = match version {
+                    StatementVersion::Settled => normalized.deserialize::<Row>(Some(&headers)),
+                    StatementVersion::Legacy => normalized.deserialize::<LegacyRow>(Some(&headers)).map(Row::from),
+                };
+                let row = row.map_err(error::row)?;
+                if let Type::Unknown(name) = &row.r#type {
+                    let raw = record.iter().map(str::trim).collect::<Vec<&str>>().join(&(format.delimiter as char).to_string());
+                    unknown.entry(name.clone()).or_default().push((line_of(&record), raw));
+                }
+                let row = Row{
+                    started_date: normalize_date(row.started_date),
+                    completed_date: row.completed_date.map(normalize_date),
+                    line: line_of(&record),
+                    ..row
+                };
+                check_completed_date(&row);
+                txns.push(row);
+            }
+            Err(e) => return Err(error::row(e)),
+        }
+    }
+    report_unknown_types(&unknown);
+    match dedup {
+        true => Ok(collapse_duplicates(txns)),
+        false => Ok(txns),
+    }
+}
 
-    Ok(txns)
+/// A date of the account statement in the format of the transactions, see `Timestamp::parse`,
+/// or as it is if it can't be parsed.
+fn normalize_date(date: String) -> String {
+    if Timestamp::is_plain(&date) {
+        return date;
+    }
+    Timestamp::parse(&date).map_or(date, |t| t.to_string())
+}
+
+/// Reports a row that was completed before it was started as a `Code::CompletedBeforeStarted`
+/// warning. The started date is the date of its transaction either way.
+fn check_completed_date(row: &Row) {
+    if row.completed_date.as_ref().is_none_or(|completed| *completed == row.started_date) {
+        return;
+    }
+    let completed = row.completed_date.as_deref().and_then(seconds_of);
+    if let (Some(started), Some(completed)) = (row.timestamp(), completed) {
+        if completed < started {
+            let msg = format!("{} of {} {} completed at {}, before it was started", String::from(row.r#type.clone()), row.amount, row.currency,
+                              row.completed_date.as_deref().unwrap_or_default());
+            warnings::report(Warning::new(Code::CompletedBeforeStarted, msg).at_line(row.line).at_date(&row.started_date));
+        }
+    }
+}
+
+/// How far after the time of the run a transaction can be dated, in seconds, before it is
+/// reported. The dates are checked before they are converted to local time, see
+/// `timezone::to_local`, in UTC like the time of the run, so this only allows for a clock that is
+/// off.
+const FUTURE_SLACK: i64 = 24 * 60 * 60;
+
+/// Reports every transaction that is dated after the time of the run as a `Code::FutureDate`
+/// warning. The transaction is kept. Not checked on wasm32-unknown-unknown, e.g. in a web page,
+/// which has no clock to tell the time of the run.
+fn check_future_dates(txns: &[Transaction]) {
+    if cfg!(all(target_arch = "wasm32", target_os = "unknown")) {
+        return;
+    }
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or_default();
+    let Some(latest) = DateTime::from_timestamp(now + FUTURE_SLACK, 0).map(|d| d.format("%Y-%m-%d %H:%M:%S").to_string()) else {
+        return;
+    };
+    for t in txns.iter().filter(|t| t.date > latest) {
+        let msg = format!("{:?} of {} {} is dated in the future", t.r#type, t.paid_amount, t.paid_currency);
+        warnings::report(Warning::new(Code::FutureDate, msg).at(&t.origin).at_date(&t.date));
+    }
+}
+
+/// Reports every unknown type once, with how many rows it has and the first of them.
+fn report_unknown_types(unknown: &BTreeMap<String, Vec<(u64, String)>>) {
+    for (name, rows) in unknown {
+        let samples = rows.iter().take(UNKNOWN_TYPE_SAMPLES).map(|(_, raw)| format!("`{}`", raw)).collect::<Vec<String>>();
+        let msg = format!(
+            "Unknown type `{}` of {} row(s), which are left out. Check whether they change the holdings, e.g. {}",
+            name, rows.len(), samples.join(", ")
+        );
+        let warning = rows.iter().take(UNKNOWN_TYPE_SAMPLES).fold(Warning::new(Code::UnknownType, msg), |w, (line, _)| w.at_line(*line));
+        warnings::report(warning);
+    }
+}
+
+/// Leaves out the rows that are the same as an earlier row of the statement in every column but
+/// the completed date: the type, the time, the description, the amount, the fee, the currency and
+/// the state. A row that was traded twice moves the balance twice, so only a row with the same
+/// balance as the other, or without a balance, is taken to be exported twice. Every row left out
+/// is reported as a `Code::DuplicateRow` warning, with the line of the row it duplicates.
+fn collapse_duplicates(mut rows: Vec<Row>) -> Vec<Row> {
+    let mut duplicate = vec![false; rows.len()];
+    // The last row kept at each time, and for every row the one kept before it at the same time
+    let mut last: HashMap<&str, usize> = HashMap::with_capacity(rows.len());
+    let mut earlier: Vec<Option<usize>> = vec![None; rows.len()];
+    for (i, row) in rows.iter().enumerate() {
+        let same_time = iter::successors(last.get(row.started_date.as_str()).copied(), |k| earlier[*k]);
+        let same = same_time.map(|k| &rows[k]).find(|k| {
+            k.r#type == row.r#type
+                && k.description == row.description
+                && k.amount == row.amount
+                && k.fee == row.fee
+                && k.currency == row.currency
+                && k.original_amount == row.original_amount
+                && k.state == row.state
+                && k.balance == row.balance
+        });
+        if let Some(same) = same {
+            let msg = format!("{} of {} {} collapsed as a duplicate of line {}", String::from(row.r#type.clone()), row.amount, row.currency, same.line);
+            warnings::report(Warning::new(Code::DuplicateRow, msg).at_line(row.line).at_date(&row.started_date));
+            duplicate[i] = true;
+            continue;
+        }
+        earlier[i] = last.insert(&row.started_date, i);
+    }
+    let mut duplicate = duplicate.into_iter();
+    rows.retain(|_| duplicate.next() == Some(false));
+    rows
+}
+
+/// The line of the record in the file, counting from 1 for the header.
+pub(crate) fn line_of(record: &StringRecord) -> u64 {
+    record.position().map(|p| p.line()).unwrap_or_default()
+}
+
+/// Deserializes the records into `T`, each with its `Origin`: the line and the raw record as the
+/// description. Fails with a `Parse` error at the first record that can't be read or deserialized.
+fn read_records<T: DeserializeOwned, R: Read>(rdr: &mut csv::Reader<R>) -> Result<Vec<(T, Origin)>> {
+    let headers = rdr.headers()?.clone();
+    rdr.records()
+        .map(|record| {
+            let record = record.map_err(error::row)?;
+            let row = record.deserialize::<T>(Some(&headers)).map_err(error::row)?;
+            let raw = record.iter().collect::<Vec<&str>>().join(",");
+            Ok((row, Origin::new(line_of(&record), raw)))
+        })
+        .collect()
 }
 
 /// Reads the file from path into a `Vec<Row>`, returns only rows with type `Exchange`.
-pub(crate) async fn read_exchanges(path: &PathBuf) -> io::Result<Vec<Row>> {
-    let txns = deserialize_from(path).await?
+#[instrument(name = "parse", skip_all, fields(path = %path.display()))]
+pub(crate) async fn read_exchanges(path: &Path, options: &Options) -> Result<Vec<Row>> {
+    let txns = deserialize_from(path, options).await?
         .into_iter()
         .filter(|t| t.r#type == Type::Exchange)
         .collect();
@@ -99,183 +602,888 @@ pub(crate) async fn read_exchanges(path: &PathBuf) -> io::Result<Vec<Row>> {
 }
 
 /// Reads the file from path into a `Vec<Row>`, returns only rows with type `Exchange` in the
-/// target currency, or  with type `Card Payment` but in the target currency.
-pub(crate) async fn read_exchanges_in_currency(path: &PathBuf, currency: &Currency) -> io::Result<Vec<Row>> {
-    let txns = deserialize_from(path).await?
-        .into_iter()
+/// target currency, or  with type `Card Payment`, `Buy` or `Sell` but in the target currency. Exchanges between
+/// two fiat currencies are left out unless the target currency is a fiat currency.
+#[instrument(name = "parse", skip_all, fields(path = %path.display(), currency = %currency))]
+pub(crate) async fn read_exchanges_in_currency(path: &Path, currency: &Currency, options: &Options) -> Result<Vec<Row>> {
+    Ok(in_currency(deserialize_from(path, options).await?, currency, false, &keywords::of(options)?))
+}
+
+/// Like `read_exchanges_in_currency`, but also returns rows with type `Transfer` in the target
+/// currency, which can be marked as e.g. gifts in the overrides file.
+async fn read_exchanges_and_transfers_in_currency(path: &Path, currency: &Currency, keywords: &Keywords, options: &Options) -> Result<Vec<Row>> {
+    Ok(in_currency(deserialize_from(path, options).await?, currency, true, keywords))
+}
+
+/// The completed rows of the target currency, see `read_exchanges_in_currency`, with the rows of
+/// type `Transfer` if `transfers` is set.
+fn in_currency(rows: Vec<Row>, currency: &Currency, transfers: bool, keywords: &Keywords) -> Vec<Row> {
+    let is_fiat = is_fiat(currency);
+    rows.into_iter()
         .filter(|t| {
             t.r#type == Type::Exchange
-            || (t.r#type == Type::CardPayment && t.currency.eq(currency))
+            || (matches!(t.r#type, Type::CardPayment | Type::Buy | Type::Sell | Type::Interest) && t.currency.eq(currency))
+            || (transfers && t.r#type == Type::Transfer && t.currency.eq(currency))
         })
         .filter(|t| t.state == State::Completed)
-        .filter(|t| t.currency.eq(currency) || t.description.contains(currency))// "Exchanged to ETH"
-        .collect();
+        .filter(|t| t.currency.eq(currency) || t.names(currency, keywords))  // "Exchanged to ETH"
+        .filter(|t| is_fiat || !t.is_fiat_exchange(keywords))
+        .collect()
+}
+
+/// Reads the Revolut account statement from path into a `Vec<Transaction>` with the completed
+/// top-ups and transfers of the fiat currency, as transfers: the money moved in from or out to a
+/// bank, e.g. to match them against the statement of the bank, see `bank::reconcile`.
+#[instrument(name = "parse", skip_all, fields(path = %path.display(), currency = %currency))]
+pub(crate) async fn read_fiat_transfers(path: &Path, currency: &Currency, options: &Options) -> Result<Vec<Transaction>> {
+    let keywords = keywords::of(options)?;
+    let txns = deserialize_from(path, options).await?
+        .iter()
+        .filter(|t| t.r#type == Type::Topup || t.r#type == Type::Transfer)
+        .filter(|t| t.state == State::Completed && t.currency.eq(currency))
+        .filter(|t| locale::pocket_of(&t.description, &keywords).is_none())     // Not to or from a vault or savings
+        .map(|t| {
+            let mut txn = Transaction::new();
+            t.transfer_to_transaction(&mut txn, currency, &keywords)?;
+            txn.origin.add(t.line, t.description.clone());
+            txn.origin.file = path.display().to_string();
+            Ok(txn)
+        })
+        .collect::<Result<_>>()?;
+    Ok(txns)
+}
+
+/// Reads the file from path into a `Vec<Transaction>` in the target currency. The file can be
+/// any of the supported `ExportFormat`s, including a store that the transactions were imported to,
+/// or the csv file of any exchange if its columns are given by a mapping, see `generic::Mapping`.
+/// A gzipped file is decompressed, and every csv file of a zip archive is read, with the name of
+/// the archive and of the csv file as the file of the transactions. The rows of an account
+/// statement that look exported twice are collapsed, unless `Options::no_dedup` is set.
+#[instrument(name = "parse", skip_all, fields(path = %path.display(), currency = %currency))]
+pub(crate) async fn read_transactions(path: &Path, currency: &Currency, mapping: Option<&Mapping>, options: &Options) -> Result<Vec<Transaction>> {
+    let mut head = vec![];
+    open_raw(path)?.take(4).read_to_end(&mut head)?;
+    if !archive::is_zip(&head) {
+        return read_file(path, currency, mapping, options).await;
+    }
+    let mut bytes = vec![];
+    open_raw(path)?.read_to_end(&mut bytes)?;
+    let mut txns = vec![];
+    for (name, content) in archive::csv_files(&bytes)? {
+        let name = path.join(&name).display().to_string();
+        let _file = warnings::in_file(&name);
+        let mut csv = read_statement(&content, currency, mapping, options).await.map_err(|e| e.in_file(&name))?;
+        csv.iter_mut().for_each(|t| t.origin.file.clone_from(&name));
+        txns.extend(csv);
+    }
+    Ok(txns)
+}
+
+/// Reads a single file like `read_transactions`. A store and an account statement are read from
+/// the path, so that the store can be opened and the statement mapped, see `deserialize_from`.
+async fn read_file(path: &Path, currency: &Currency, mapping: Option<&Mapping>, options: &Options) -> Result<Vec<Transaction>> {
+    let file = path.display().to_string();
+    let _file = warnings::in_file(&file);
+    let (format, export_format) = formats_of(&read_sample(path)?, &options.store_access);
+    let mut txns = match (export_format, mapping) {
+        #[cfg(feature = "native")]
+        (ExportFormat::Store, _) if path == Path::new(STDIN) => {
+            let msg = "A store can't be read from stdin, give the path of the store instead, e.g. `cryptotax cryptotax.db`";
+            return Err(CryptotaxError::Config(msg.to_string()));
+        }
+        #[cfg(feature = "native")]
+        (ExportFormat::Store, _) => Store::open(path, &options.store_access)?.transactions(currency)?,
+        (ExportFormat::Account, None) => {
+            let keywords = keywords::of(options)?;
+            let rows = read_exchanges_and_transfers_in_currency(path, currency, &keywords, options).await?;
+            to_transactions(&rows, currency, &keywords, options.check).await?
+        }
+        _ => read_csv(open(path)?, &format, export_format, currency, mapping, options).await.map_err(|e| e.in_file(&file))?,
+    };
+    check_future_dates(&txns);
+    txns.iter_mut().for_each(|t| t.origin.file.clone_from(&file));
+    Ok(txns)
+}
+
+/// Reads a statement that is already in memory into a `Vec<Transaction>` in the target currency,
+/// like `read_transactions`, without touching the file system, e.g. a statement uploaded to a web
+/// page. The statement can be the csv file of any of the supported `ExportFormat`s, or of any
+/// exchange with a mapping, but not a store, nor a gzipped file or a zip archive.
+#[instrument(name = "parse", skip_all, fields(bytes = statement.len(), currency = %currency))]
+pub(crate) async fn read_statement(statement: &[u8], currency: &Currency, mapping: Option<&Mapping>, options: &Options) -> Result<Vec<Transaction>> {
+    if archive::is_compressed(statement) {
+        let msg = "A compressed statement can't be read from memory, decompress it first";
+        return Err(CryptotaxError::Config(msg.to_string()));
+    }
+    let (format, export_format) = formats_of(&sample_of(statement), &options.store_access);
+    let txns = read_csv(statement, &format, export_format, currency, mapping, options).await?;
+    check_future_dates(&txns);
+    Ok(txns)
+}
+
+/// The first lines of a file that is already in memory, see `read_sample`.
+fn sample_of(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(&bytes[..bytes.len().min(64 * 1024)]).to_string()
+}
+
+/// Detects the `CsvFormat` and the `ExportFormat` of a file by its first lines.
+fn formats_of(sample: &str, access: &StoreAccess) -> (CsvFormat, ExportFormat) {
+    let format = CsvFormat::detect(sample);
+    let export_format = ExportFormat::detect(sample, &format, access);
+    debug!(?export_format, "Detected export format");
+    (format, export_format)
+}
+
+/// Reads the csv file of any `ExportFormat` but the store from the reader.
+async fn read_csv<R: Read>(rdr: R, format: &CsvFormat, export_format: ExportFormat, currency: &Currency, mapping: Option<&Mapping>, options: &Options) -> Result<Vec<Transaction>> {
+    let txns = match (export_format, mapping) {
+        #[cfg(feature = "native")]
+        (ExportFormat::Store, _) => {
+            let msg = "A store can only be read from its path, e.g. `cryptotax cryptotax.db`";
+            return Err(CryptotaxError::Config(msg.to_string()));
+        }
+        (_, Some(mapping)) => generic::read_transactions(rdr, format, mapping, currency).await?,
+        (ExportFormat::Account, None) => {
+            let keywords = keywords::of(options)?;
+            let rows = in_currency(deserialize_from_reader(rdr, format, !options.no_dedup)?, currency, true, &keywords);
+            to_transactions(&rows, currency, &keywords, options.check).await?
+        }
+        (ExportFormat::Crypto, None) => crypto::read_transactions(rdr, format, currency).await?,
+        (ExportFormat::BinanceFutures, None) => binance::read_transactions(rdr, format, currency).await?,
+        (ExportFormat::KrakenLedger, None) => kraken::read_transactions(rdr, format, currency).await?,
+        (ExportFormat::BitstampTransactions, None) => bitstamp::read_transactions(rdr, format, currency).await?,
+        (ExportFormat::BitfinexLedger, None) => bitfinex::read_transactions(rdr, format, currency).await?,
+        (ExportFormat::KucoinTrades, None) => kucoin::read_transactions(rdr, format, currency).await?,
+        (ExportFormat::CryptoComApp, None) => cryptocom::read_transactions(rdr, format, currency).await?,
+        (ExportFormat::NexoTransactions, None) => nexo::read_transactions(rdr, format, currency).await?,
+        (ExportFormat::CelsiusTransactions, None) => celsius::read_transactions(rdr, format, currency).await?,
+        (ExportFormat::KoinlyUniversal, None) => universal::read_koinly(rdr, format, currency).await?,
+        (ExportFormat::CoinTrackingUniversal, None) => universal::read_cointracking(rdr, format, currency).await?,
+    };
     Ok(txns)
 }
 
+/// How far apart in time the two sides of an exchange can be, in seconds.
+const PAIRING_WINDOW: i64 = 5 * 60;
+
+/// An exchange row with its time and its description, which are parsed once for the pairing and
+/// the conversion of the row.
+struct Exchange<'a> {
+    row: &'a Row,
+    time: Option<i64>,
+    description: Option<ExchangeDescription<'a>>,
+}
+
+impl<'a> Exchange<'a> {
+    fn new(row: &'a Row, keywords: &Keywords) -> Exchange<'a> {
+        Exchange{ row, time: row.timestamp(), description: locale::parse_exchange(&row.description, keywords) }
+    }
+}
+
 /// Converts `Vec<Row>` into `Vec<Transaction>`, given a target currency.
-pub(crate) async fn to_transactions(rows: &Vec<Row>, currency: &Currency) -> io::Result<Vec<Transaction>> {
-    let (txns, _): (Vec<Transaction>, Option<&Row>) =
-        rows.iter().rev()
-            .fold((vec![], None), |(mut acc, prev), row| {
-                match row.r#type {
-                    Type::Exchange => {
-                        match prev {
-                            None => (acc, Some(row)),
-                            Some(prev) => {
-                                let txn = prev.to_transaction(None, currency);
-                                let txn = row.to_transaction(Some(txn), currency);
-                                acc.push(txn);
-                                (acc, None)
-                            }
-                        }
-                    }
-                    Type::CardPayment => {
-                        let txn = row.to_transaction(None, currency);
-                        acc.push(txn);
-                        (acc, prev)
-                    }
-                    _ => (acc, prev)
-                }
-            });
+/// The transactions are sorted by date, see `Transaction::cmp_by_date`. The two sides of an
+/// exchange need not be adjacent, nor the rows in order, see `pair_exchanges`. An exchange row with an unknown
+/// description or without its other side is reported as a warning. If `check` is set, the pairs
+/// are checked to use every exchange row once, see `invariants::check_pairing`.
+#[instrument(name = "pair", skip_all, fields(rows = rows.len()))]
+pub(crate) async fn to_transactions(rows: &[Row], currency: &Currency, keywords: &Keywords, check: bool) -> Result<Vec<Transaction>> {
+    let mut txns = vec![];
+    for row in rows.iter().rev() {
+        if let Type::CardPayment | Type::Transfer | Type::Buy | Type::Sell | Type::Interest = row.r#type {
+            txns.push(row.to_transaction(None, None, currency, keywords)?);
+        }
+    }
+    let mut exchanges: Vec<Exchange> = rows.iter().filter(|r| r.r#type == Type::Exchange).map(|r| Exchange::new(r, keywords)).collect();
+    for row in exchanges.iter().rev().filter(|e| e.description.is_none()).map(|e| e.row) {
+        let msg = format!("Unknown description `{}` of an exchange in {}", row.description, row.currency);
+        warnings::report(Warning::new(Code::UnknownDescription, msg).at_line(row.line).at_date(&row.started_date));
+    }
+    sort_for_pairing(&mut exchanges);
+    let (pairs, unmatched) = pair_exchanges(&exchanges);
+    if check {
+        invariants::check_pairing(exchanges.len(), &pairs, &unmatched)?;
+    }
+    txns.reserve(pairs.len());
+    for (id, (first, second)) in pairs.into_iter().enumerate() {
+        let (first, second) = (&exchanges[first], &exchanges[second]);
+        debug!(exchange = id, lines = ?[first.row.line, second.row.line], date = %first.row.started_date, "Paired");
+        if first.row.started_date != second.row.started_date {
+            let msg = format!("The sides of an exchange are at {} and {}", first.row.started_date, second.row.started_date);
+            let warning = Warning::new(Code::UnmatchedRow, msg).at_line(second.row.line).at_line(first.row.line);
+            warnings::report(warning.at_date(&first.row.started_date));
+        }
+        let txn = first.row.to_transaction(None, first.description.as_ref(), currency, keywords)?;
+        txns.push(second.row.to_transaction(Some(txn), second.description.as_ref(), currency, keywords)?);
+    }
+    for row in unmatched.into_iter().map(|i| exchanges[i].row) {
+        let msg = format!("Exchange of {} {} without its other side", row.amount, row.currency);
+        warnings::report(Warning::new(Code::UnmatchedRow, msg).at_line(row.line).at_date(&row.started_date));
+    }
+    txns.sort_by(Transaction::cmp_by_date);
     Ok(txns)
 }
 
+/// Sorts the exchange rows newest first, as in the statement, by their time and then by their
+/// line, so that the pairs don't depend on the order of the rows in the file. Rows whose date
+/// can't be parsed go last. A statement in neither date order, e.g. of statements that were
+/// concatenated, is reported as a `Code::OutOfOrder` warning at the first row out of order.
+fn sort_for_pairing(exchanges: &mut [Exchange]) {
+    let timed: Vec<(i64, &Row)> = exchanges.iter().filter_map(|e| Some((e.time?, e.row))).collect();
+    let newest_first = timed.windows(2).find(|w| w[0].0 != w[1].0).is_none_or(|w| w[0].0 > w[1].0);
+    let out_of_order = timed.windows(2).find(|w| match newest_first {
+        true => w[0].0 < w[1].0,
+        false => w[0].0 > w[1].0,
+    });
+    if let Some(w) = out_of_order {
+        let msg = format!("Exchange rows out of date order, e.g. {} after {}, sorted by date before pairing", w[1].1.started_date, w[0].1.started_date);
+        warnings::report(Warning::new(Code::OutOfOrder, msg).at_line(w[1].1.line).at_date(&w[1].1.started_date));
+    }
+    exchanges.sort_by_key(|e| (Reverse(e.time), e.row.line));
+}
+
+/// Pairs the exchange rows, newest first, see `sort_for_pairing`, into the two sides of each exchange, by
+/// their indices, and returns the pairs and the rows without another side. Starting from the
+/// oldest row, the other side is the closest row in time within `PAIRING_WINDOW`, then in the
+/// file, that is in another currency, moves the amount the other way, and whose description
+/// doesn't name another currency. The sides are often not adjacent when several exchanges are
+/// made at once.
+fn pair_exchanges(exchanges: &[Exchange]) -> (Vec<(usize, usize)>, Vec<usize>) {
+    let (rows, times): (Vec<&Row>, Vec<Option<i64>>) = exchanges.iter().map(|e| (e.row, e.time)).unzip();
+    // Whether the rows can be the two sides of an exchange: in other currencies, one paid and one
+    // received, and each description, if known, naming the other currency.
+    let names = |i: usize, currency: &Currency| exchanges[i].description.as_ref().is_none_or(|d| d.currency == currency);
+    let is_side = |i: usize, j: usize| {
+        rows[i].currency != rows[j].currency
+            && rows[i].amount.is_sign_negative() != rows[j].amount.is_sign_negative()
+            && names(i, &rows[j].currency)
+            && names(j, &rows[i].currency)
+    };
+    let mut by_time: Vec<usize> = (0..rows.len()).filter(|i| times[*i].is_some()).collect();
+    by_time.sort_by_key(|i| times[*i]);
+    let untimed: Vec<usize> = (0..rows.len()).filter(|i| times[*i].is_none()).collect();
+    let mut paired = vec![false; rows.len()];
+    let (mut pairs, mut unmatched) = (vec![], vec![]);
+    for i in (0..rows.len()).rev() {
+        if paired[i] {
+            continue;
+        }
+        let window = match times[i] {
+            Some(time) => {
+                let from = by_time.partition_point(|j| times[*j] < Some(time - PAIRING_WINDOW));
+                let to = by_time.partition_point(|j| times[*j] <= Some(time + PAIRING_WINDOW));
+                &by_time[from..to]
+            }
+            None => &untimed[..],   // Only at the same date
+        };
+        let other =
+            window.iter()
+                .copied()
+                .filter(|j| *j != i && !paired[*j] && is_side(i, *j))
+                .filter(|j| times[i].is_some() || rows[i].started_date == rows[*j].started_date)
+                .map(|j| (j, times[i].zip(times[j]).map(|(a, b)| (a - b).abs()).unwrap_or_default()))
+                .min_by_key(|(j, seconds)| (*seconds, j.abs_diff(i), Reverse(*j)));
+        match other {
+            Some((j, _)) => {
+                paired[i] = true;
+                paired[j] = true;
+                pairs.push((i, j));
+            }
+            None => unmatched.push(i),
+        }
+    }
+    (pairs, unmatched)
+}
+
+/// A date of the account statement in seconds, if it can be parsed.
+fn seconds_of(date: &str) -> Option<i64> {
+    Timestamp::parse(date).map(|t| t.seconds())
+}
+
 // 1. Bought Crypto 1 from SEK      (cost in SEK),  sold to SEK      (sales in SEK)
 // 2. Bought Crypto 1 from SEK      (cost in SEK),  sold to Crypto 2 (SEK price as sales)
 // 3. Bought from Crypto 2 (SEK price as cost),     sold to Crypto 3 (SEK price as sales)
 // 4. Bought from Crypto 3 (SEK price as cost),     sold to SEK      (sales in SEK)
 impl Row {
-    fn to_transaction(&self, txn: Option<Transaction>, currency: &Currency) -> Transaction {
-        let mut txn = txn.unwrap_or(Transaction::new());
+    /// Converts the row into `txn`, or a new transaction. The description of an `Exchange` row is
+    /// given parsed, see `Exchange`.
+    fn to_transaction(&self, txn: Option<Transaction>, description: Option<&ExchangeDescription>, currency: &Currency, keywords: &Keywords) -> Result<Transaction> {
+        let mut txn = txn.unwrap_or_else(Transaction::new);
+        txn.origin.add(self.line, self.description.clone());
 
         match self.r#type {
-            Type::Exchange => self.exchange_to_transaction(&mut txn, currency),
-            Type::CardPayment => self.card_payment_to_transaction(&mut txn, currency),
+            Type::Exchange => self.exchange_to_transaction(&mut txn, description, currency)?,
+            Type::CardPayment => self.card_payment_to_transaction(&mut txn, currency)?,
+            Type::Buy | Type::Sell => self.trade_to_transaction(&mut txn, currency)?,
+            Type::Transfer => self.transfer_to_transaction(&mut txn, currency, keywords)?,
+            Type::Interest => self.interest_to_transaction(&mut txn, currency)?,
             _ => {}
         }
 
-        txn
+        Ok(txn)
+    }
+
+    /// Whether the description names the currency: as the counter currency of an exchange, see
+    /// `locale::parse_exchange`, or else as any of its words.
+    fn names(&self, currency: &Currency, keywords: &Keywords) -> bool {
+        match locale::parse_exchange(&self.description, keywords) {
+            Some(d) => d.currency == currency,
+            None => self.description.split_whitespace().any(|word| word == currency),
+        }
+    }
+
+    /// The start of the row in seconds, if the date can be parsed.
+    fn timestamp(&self) -> Option<i64> {
+        seconds_of(&self.started_date)
+    }
+
+    /// Whether the row is one side of an exchange between two fiat currencies, e.g. "Exchanged
+    /// to EUR" in SEK.
+    fn is_fiat_exchange(&self, keywords: &Keywords) -> bool {
+        let names_fiat = || match locale::parse_exchange(&self.description, keywords) {
+            Some(d) => is_fiat(d.currency),
+            None => self.description.split_whitespace().any(is_fiat),
+        };
+        self.r#type == Type::Exchange && is_fiat(&self.currency) && names_fiat()
+    }
+
+    fn exchange_to_transaction(&self, txn: &mut Transaction, description: Option<&ExchangeDescription>, currency: &Currency) -> Result<()> {
+        let amount = decimal::add(self.amount, self.fee)?;
+        let counter = |direction: Direction| description.is_some_and(|d| d.direction == direction && d.currency == currency);
+        // target currency: "BCH", currency: "BCH", description: "Exchanged from SEK"
+        // if self.currency.eq(currency) && self.description.contains("Exchanged from") {
+        if self.currency.eq(currency) && self.amount.is_sign_positive() {
+            debug!(date = %self.started_date, amount = %amount, currency = %self.currency, description = %self.description, fee = %self.fee, "Bought");
+            txn.r#type = TransactionType::Buy;
+            txn.paid_amount = amount;
+            txn.paid_fee = self.fee;
+            txn.paid_currency = currency.clone();
+            txn.date = self.started_date.clone();
+
+        }
+        // target currency: "BCH", currency: "BCH", description: "Exchanged to SEK"
+        // if self.currency.eq(currency) && self.description.contains("Exchanged to") {
+        if self.currency.eq(currency) && self.amount.is_sign_negative() {
+            debug!(date = %self.started_date, amount = %amount, currency = %self.currency, description = %self.description, fee = %self.fee, "Sold");
+            txn.r#type = TransactionType::Sell;
+            txn.paid_amount = amount;
+            txn.paid_fee = self.fee;
+            txn.paid_currency = currency.clone();
+            txn.date = self.started_date.clone();
+        }
+        // target currency: "BCH", currency: "SEK", description: "Exchanged from BCH"
+        if counter(Direction::From) {
+            debug!(date = %self.started_date, amount = %amount, currency = %self.currency, description = %self.description, fee = %self.fee, "Income of selling");
+            txn.r#type = TransactionType::Sell;
+            txn.exchanged_amount = amount;
+            txn.exchanged_fee = self.fee;
+            txn.exchanged_currency = self.currency.clone();
+        }
+        // target currency: "BCH", currency: "SEK", description: "Exchanged to BCH"
+        if counter(Direction::To) {
+            debug!(date = %self.started_date, amount = %amount, currency = %self.currency, description = %self.description, fee = %self.fee, "Cost of buying");
+            txn.r#type = TransactionType::Buy;
+            txn.exchanged_amount = amount;
+            txn.exchanged_fee = self.fee;
+            txn.exchanged_currency = self.currency.clone();
+        }
+        match description.and_then(|d| d.pocket) {
+            Some(Pocket::Vault) => txn.is_vault = true,
+            Some(Pocket::Savings) => debug!(date = %self.started_date, description = %self.description, "From or to savings"),
+            None => {}
+        }
+        Ok(())
+    }
+
+    fn card_payment_to_transaction(&self, txn: &mut Transaction, currency: &Currency) -> Result<()> {
+        // amount: -0.00123456, fee: 0.00000000, currency: "BTC", original_amount: -543.21, original_currency: "SEK",
+        // settled_amount: Some(543.21), settled_currency: Some("SEK"), state: Completed, balance: Some(0.00000000) }
+        txn.r#type = TransactionType::Sell;
+        txn.paid_amount = decimal::add(self.amount, self.fee)?;
+        txn.paid_fee = self.fee;
+        txn.paid_currency = currency.clone();
+        txn.exchanged_amount = self.original_amount.neg();
+        txn.exchanged_currency = self.original_currency.clone();
+        txn.date = self.started_date.clone();
+        txn.is_vault = false;
+        txn.origin.counterparty = merchant_of(&self.description);
+        Ok(())
+    }
+
+    /// A `Buy` or a `Sell` row of the newer statements, which has both sides of the trade: the
+    /// amount of the currency, and the fiat currency it was traded for as the original amount,
+    /// e.g. `Buy,...,Bought BTC,0.01,0,BTC,500,SEK,...`. The type tells which way the amounts
+    /// went, whatever their signs.
+    fn trade_to_transaction(&self, txn: &mut Transaction, currency: &Currency) -> Result<()> {
+        let (r#type, amount, exchanged_amount) = match self.r#type {
+            Type::Buy => (TransactionType::Buy, self.amount.abs(), self.original_amount.abs().neg()),
+            _ => (TransactionType::Sell, self.amount.abs().neg(), self.original_amount.abs()),
+        };
+        let amount = decimal::add(amount, self.fee)?;
+        debug!(date = %self.started_date, amount = %amount, currency = %self.currency, exchanged_amount = %exchanged_amount, exchanged_currency = %self.original_currency, fee = %self.fee, "Traded");
+        txn.r#type = r#type;
+        txn.paid_amount = amount;
+        txn.paid_fee = self.fee;
+        txn.paid_currency = currency.clone();
+        txn.exchanged_amount = exchanged_amount;
+        txn.exchanged_currency = self.original_currency.clone();
+        txn.date = self.started_date.clone();
+        Ok(())
+    }
+
+    /// An `Interest` row, e.g. `Interest,...,Interest earned,0.0001,0,BTC,2.50,EUR,...`, as
+    /// income. It is valued at the original amount if that is in another currency, and otherwise
+    /// at the market price with `--prices`. The moves into and out of the savings account are
+    /// transfers, see `transfer_to_transaction`.
+    fn interest_to_transaction(&self, txn: &mut Transaction, currency: &Currency) -> Result<()> {
+        let amount = decimal::add(self.amount.abs(), self.fee)?;
+        debug!(date = %self.started_date, amount = %amount, currency = %self.currency, description = %self.description, fee = %self.fee, "Interest");
+        txn.r#type = TransactionType::Income;
+        txn.paid_amount = amount;
+        txn.paid_fee = self.fee;
+        txn.paid_currency = currency.clone();
+        txn.date = self.started_date.clone();
+        if !self.original_currency.is_empty() && self.original_currency.ne(currency) {
+            txn.exchanged_amount = self.original_amount.abs().neg();
+            txn.exchanged_currency = self.original_currency.clone();
+        }
+        Ok(())
+    }
+
+    fn transfer_to_transaction(&self, txn: &mut Transaction, currency: &Currency, keywords: &Keywords) -> Result<()> {
+        txn.r#type = TransactionType::Transfer;
+        txn.paid_amount = decimal::add(self.amount, self.fee)?;
+        txn.paid_fee = self.fee;
+        txn.paid_currency = currency.clone();
+        txn.date = self.started_date.clone();
+        // A transfer to or from a vault, e.g. "To DOGE Vault", moves the currency and its costs
+        // between the main balance and the vault, see `calculator::CostBook::move_between_pools`.
+        txn.is_vault = locale::pocket_of(&self.description, keywords) == Some(Pocket::Vault);
+        Ok(())
+    }
+}
+
+/// The merchant that a card payment was made to, which is its description, e.g. `Klarna`, without
+/// the `To` that some versions of the app put before it.
+fn merchant_of(description: &str) -> Option<String> {
+    let merchant = description.trim();
+    let merchant = merchant.strip_prefix("To ").unwrap_or(merchant).trim();
+    Some(merchant.to_string()).filter(|m| !m.is_empty())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::calculator;
+    use crate::cryptotax::Options;
+    use crate::reader::*;
+    use chrono::NaiveDateTime;
+    use futures::executor::block_on;
+    use proptest::prelude::*;
+    use rust_decimal_macros::dec;
+    use std::error::Error;
+    use std::io::Write;
+    use std::path::PathBuf;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn should_deserialize_from_path() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let mut file = NamedTempFile::new()?;
+        writeln!(file, "Type,Started Date,Completed Date,Description,Amount,Fee,Currency,Original Amount,Original Currency,Settled Amount,Settled Currency,State,Balance
+                        Exchange,2022-03-01 16:21:49,2022-03-01 16:21:49,Exchanged to EOS,-900.90603463,-20.36495977,DOGE,-900.90603463,DOGE,,,Completed,1078.7290056
+                        Exchange,2022-03-01 16:21:49,2022-03-01 16:21:49,Exchanged from DOGE,50,0,EOS,50,EOS,,,Completed,50
+                        Exchange,2021-12-31 17:54:48,2021-12-31 17:54:48,Exchanged to DOGE,-5000.45,-80.15,SEK,-5000.45,SEK,,,Completed,700.27
+                        Exchange,2021-12-31 17:54:48,2021-12-31 17:54:48,Exchanged from SEK,2000,0,DOGE,2000,DOGE,,,Completed,2000")?;
+        let path = file.path().to_str().unwrap();
+
+        /*
+         * When
+         */
+        let rows = block_on(deserialize_from(&PathBuf::from(path), &Options::default()))?;
+
+        /*
+         * Then
+         */
+        let mut iter = rows.into_iter();
+        assert_eq!(iter.next(), Some(Row{
+            r#type: Type::Exchange,
+            started_date: "2022-03-01 16:21:49".to_string(),
+            completed_date: Some("2022-03-01 16:21:49".to_string()),
+            description: "Exchanged to EOS".to_string(),
+            amount: dec!(-900.90603463),
+            fee: dec!(-20.36495977),
+            currency: "DOGE".to_string(),
+            original_amount: dec!(-900.90603463),
+            original_currency: "DOGE".to_string(),
+            settled_amount: None,
+            settled_currency: None,
+            state: State::Completed,
+            balance: Some(dec!(1078.7290056)),
+            line: 2
+        }));
+        assert_eq!(iter.next(), Some(Row{
+            r#type: Type::Exchange,
+            started_date: "2022-03-01 16:21:49".to_string(),
+            completed_date: Some("2022-03-01 16:21:49".to_string()),
+            description: "Exchanged from DOGE".to_string(),
+            amount: dec!(50),
+            fee: dec!(0),
+            currency: "EOS".to_string(),
+            original_amount: dec!(50),
+            original_currency: "EOS".to_string(),
+            settled_amount: None,
+            settled_currency: None,
+            state: State::Completed,
+            balance: Some(dec!(50)),
+            line: 3
+        }));
+        assert_eq!(iter.next(), Some(Row{
+            r#type: Type::Exchange,
+            started_date: "2021-12-31 17:54:48".to_string(),
+            completed_date: Some("2021-12-31 17:54:48".to_string()),
+            description: "Exchanged to DOGE".to_string(),
+            amount: dec!(-5000.45),
+            fee: dec!(-80.15),
+            currency: "SEK".to_string(),
+            original_amount: dec!(-5000.45),
+            original_currency: "SEK".to_string(),
+            settled_amount: None,
+            settled_currency: None,
+            state: State::Completed,
+            balance: Some(dec!(700.27)),
+            line: 4
+        }));
+        assert_eq!(iter.next(), Some(Row{
+            r#type: Type::Exchange,
+            started_date: "2021-12-31 17:54:48".to_string(),
+            completed_date: Some("2021-12-31 17:54:48".to_string()),
+            description: "Exchanged from SEK".to_string(),
+            amount: dec!(2000),
+            fee: dec!(0),
+            currency: "DOGE".to_string(),
+            original_amount: dec!(2000),
+            original_currency: "DOGE".to_string(),
+            settled_amount: None,
+            settled_currency: None,
+            state: State::Completed,
+            balance: Some(dec!(2000)),
+            line: 5
+        }));
+        assert_eq!(iter.next(), None);
+        Ok(())
+    }
+
+    #[test]
+    fn should_read_the_same_rows_from_a_mapped_file() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let mut file = NamedTempFile::new()?;
+        writeln!(file, "Type,Started Date,Completed Date,Description,Amount,Fee,Currency,Original Amount,Original Currency,Settled Amount,Settled Currency,State,Balance
+                        Exchange,2021-12-31 17:54:48,2021-12-31 17:54:48,Exchanged to DOGE,-5000.45,-80.15,SEK,-5000.45,SEK,,,Completed,700.27
+                        Exchange,2021-12-31 17:54:48,2021-12-31 17:54:48,Exchanged from SEK,2000,0,DOGE,2000,DOGE,,,Completed,2000")?;
+        let empty = NamedTempFile::new()?;
+
+        /*
+         * When
+         */
+        let mmap = Options{ mmap: true, ..Default::default() };
+        let read = block_on(deserialize_from(file.path(), &Options::default()))?;
+        let mapped = block_on(deserialize_from(file.path(), &mmap))?;
+
+        /*
+         * Then
+         */
+        assert_eq!(mapped.len(), 2);
+        assert_eq!(mapped, read);
+        let empty = block_on(deserialize_from(empty.path(), &mmap));   // Can't be mapped, so it is read instead
+        assert!(matches!(empty, Err(CryptotaxError::Parse{ line: Some(1), .. })), "{:?}", empty);
+        Ok(())
+    }
+
+    #[test]
+    fn should_read_the_same_transactions_from_a_statement_in_memory() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let statement = "Type;Started Date;Completed Date;Description;Amount;Fee;Currency;Original Amount;Original Currency;Settled Amount;Settled Currency;State;Balance
+                         Exchange;2022-03-01 16:21:49;2022-03-01 16:21:49;Exchanged to SEK;-1 000,5;0;DOGE;-1 000,5;DOGE;;;Completed;999,5
+                         Exchange;2022-03-01 16:21:49;2022-03-01 16:21:49;Exchanged from DOGE;900;0;SEK;900;SEK;;;Completed;900
+                         Exchange;2021-12-31 17:54:48;2021-12-31 17:54:48;Exchanged to DOGE;-5000,45;-80,15;SEK;-5000,45;SEK;;;Completed;700,27
+                         Exchange;2021-12-31 17:54:48;2021-12-31 17:54:48;Exchanged from SEK;2000;0;DOGE;2000;DOGE;;;Completed;2000";
+        let crypto = "Symbol,Type,Quantity,Price,Value,Fees,Date\nBTC,Buy,0.001,SEK 300000.00,SEK 300.00,SEK 3.00,2023-01-02 10:00:00";
+        let store = "SQLite format 3\0";
+        let read = |content: &str, currency: &str| -> Result<Vec<Transaction>, Box<dyn Error>> {
+            let mut file = NamedTempFile::new()?;
+            write!(file, "{}", content)?;
+            let txns = block_on(read_transactions(file.path(), &currency.to_string(), None, &Options::default()))?;
+            Ok(txns.into_iter().map(|t| Transaction{ origin: Origin{ file: "".to_string(), ..t.origin.clone() }, ..t }).collect())
+        };
+
+        /*
+         * When
+         */
+        let account = block_on(read_statement(statement.as_bytes(), &"DOGE".to_string(), None, &Options::default()))?;
+        let trades = block_on(read_statement(crypto.as_bytes(), &"BTC".to_string(), None, &Options::default()))?;
+
+        /*
+         * Then
+         */
+        assert_eq!(account.len(), 2);
+        assert_eq!(account, read(statement, "DOGE")?);
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades, read(crypto, "BTC")?);
+        assert!(block_on(read_statement(store.as_bytes(), &"BTC".to_string(), None, &Options::default())).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn should_keep_and_report_rows_of_unknown_types() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let statement = "Type,Started Date,Completed Date,Description,Amount,Fee,Currency,Original Amount,Original Currency,Settled Amount,Settled Currency,State,Balance
+                         EXCHANGE,2022-03-01 16:21:49,2022-03-01 16:21:49,Exchanged to SEK,-1,0,BTC,-1,BTC,,,Completed,0
+                         CARD_REFUND,2022-03-02 10:00:00,2022-03-02 10:00:00,Refund,10,0,SEK,10,SEK,,,Completed,910
+                         Stake,2022-03-03 10:00:00,2022-03-03 10:00:00,Staked ETH,-2,0,ETH,-2,ETH,,,Completed,0
+                         Stake,2022-03-04 10:00:00,2022-03-04 10:00:00,Staked ETH,-1,0,ETH,-1,ETH,,,Completed,0";
+        warnings::take();
+
+        /*
+         * When
+         */
+        let rows = deserialize_from_reader(statement.as_bytes(), &CsvFormat::default(), true)?;
+        let rows_again = deserialize_from_reader(statement.as_bytes(), &CsvFormat::default(), true)?;
+        let warnings = warnings::take();
+
+        /*
+         * Then
+         */
+        let types: Vec<Type> = rows.iter().map(|r| r.r#type.clone()).collect();
+        assert_eq!(types, vec![
+            Type::Exchange,
+            Type::Other("CARD_REFUND".to_string()),
+            Type::Unknown("Stake".to_string()),
+            Type::Unknown("Stake".to_string()),
+        ]);
+        assert_eq!(rows, rows_again);
+        assert_eq!(String::from(Type::CardPayment), "Card Payment");
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].code, Code::UnknownType);
+        assert_eq!(warnings[0].lines, vec![4, 5]);
+        assert!(warnings[0].message.starts_with("Unknown type `Stake` of 2 row(s)"));
+        assert!(warnings[0].message.contains("`Stake,2022-03-04 10:00:00,2022-03-04 10:00:00,Staked ETH,-1,0,ETH,-1,ETH,,,Completed,0`"));
+        Ok(())
+    }
+
+    #[test]
+    fn should_collapse_rows_exported_twice() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let statement = "Type,Started Date,Completed Date,Description,Amount,Fee,Currency,Original Amount,Original Currency,Settled Amount,Settled Currency,State,Balance
+                         EXCHANGE,2022-03-01 16:21:49,2022-03-01 16:21:49,Exchanged to BTC,0.1,0,BTC,0.1,BTC,,,Completed,0.1
+                         EXCHANGE,2022-03-01 16:21:49,2022-03-01 16:21:49,Exchanged to BTC,0.1,0,BTC,0.1,BTC,,,Completed,0.1
+                         EXCHANGE,2022-03-01 16:21:49,2022-03-01 16:21:49,Exchanged to BTC,0.1,0,BTC,0.1,BTC,,,Completed,0.2
+                         EXCHANGE,2022-03-01 16:21:49,2022-03-01 16:21:49,Exchanged to BTC,-400,0,SEK,-400,SEK,,,Completed,600";
+        warnings::take();
+
+        /*
+         * When
+         */
+        let rows = deserialize_from_reader(statement.as_bytes(), &CsvFormat::default(), true)?;
+        let warnings = warnings::take();
+        let all_rows = deserialize_from_reader(statement.as_bytes(), &CsvFormat::default(), false)?;
+
+        /*
+         * Then
+         */
+        let lines: Vec<u64> = rows.iter().map(|r| r.line).collect();
+        assert_eq!(lines, vec![2, 4, 5]);   // The second buy moved the balance
+        assert_eq!(all_rows.len(), 4);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!((warnings[0].code, warnings[0].lines.clone()), (Code::DuplicateRow, vec![3]));
+        assert_eq!(warnings[0].message, "Exchange of 0.1 BTC collapsed as a duplicate of line 2");
+        Ok(())
+    }
+
+    #[test]
+    fn should_fail_at_the_row_that_cant_be_read() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let statement = "Type,Started Date,Completed Date,Description,Amount,Fee,Currency,Original Amount,Original Currency,Settled Amount,Settled Currency,State,Balance
+                         EXCHANGE,2022-03-01 16:21:49,2022-03-01 16:21:49,Exchanged to BTC,0.1,0,BTC,0.1,BTC,,,Completed,0.1
+                         EXCHANGE,2022-03-01 16:21:49,2022-03-01 16:21:49,Exchanged to BTC,-400000000000000000000000000000,0,SEK,-400,SEK,,,Completed,600";
+
+        /*
+         * When
+         */
+        let error = deserialize_from_reader(statement.as_bytes(), &CsvFormat::default(), true).err().ok_or("The row was read")?;
+        let located = error.in_file("account-statement.csv");
+
+        /*
+         * Then
+         */
+        assert!(matches!(located, CryptotaxError::Parse{ line: Some(3), .. }));
+        assert!(located.to_string().starts_with("account-statement.csv:3: "));
+        Ok(())
+    }
+
+    #[test]
+    fn should_read_legacy_statements_without_optional_columns() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let statement = "Type,Product,Started Date,Completed Date,Description,Amount,Currency,State
+                         Exchange,Current,2019-03-01 16:21:49,2019-03-01 16:21:49,Exchanged to SEK,-1,BTC,Completed
+                         Exchange,Current,2019-03-01 16:21:49,2019-03-01 16:21:49,Exchanged from BTC,35000,SEK,Completed
+                         Card Payment,Current,2019-03-02 10:00:00,,Coffee,-0.0001,BTC,Completed";
+
+        /*
+         * When
+         */
+        let rows = deserialize_from_reader(statement.as_bytes(), &CsvFormat::default(), true)?;
+        let txns = block_on(to_transactions(&rows, &"BTC".to_string(), &Keywords::shipped(), false))?;
+
+        /*
+         * Then
+         */
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[2], Row{
+            r#type: Type::CardPayment,
+            started_date: "2019-03-02 10:00:00".to_string(),
+            completed_date: None,
+            description: "Coffee".to_string(),
+            amount: dec!(-0.0001),
+            fee: dec!(0),
+            currency: "BTC".to_string(),
+            original_amount: dec!(-0.0001),
+            original_currency: "BTC".to_string(),
+            settled_amount: None,
+            settled_currency: None,
+            state: State::Completed,
+            balance: None,
+            line: 4,
+        });
+        assert_eq!(txns.len(), 2);
+        assert_eq!((txns[0].paid_amount, txns[0].exchanged_amount, &txns[0].exchanged_currency), (dec!(-1), dec!(35000), &"SEK".to_string()));
+        assert_eq!((txns[1].paid_amount, txns[1].exchanged_amount, &txns[1].exchanged_currency), (dec!(-0.0001), dec!(0.0001), &"BTC".to_string()));
+        assert_eq!(txns[1].origin.counterparty, Some("Coffee".to_string()));
+        Ok(())
+    }
+
+    #[test]
+    fn should_read_buy_and_sell_rows_beside_exchanges() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let statement = "Type,Started Date,Completed Date,Description,Amount,Fee,Currency,Original Amount,Original Currency,Settled Amount,Settled Currency,State,Balance
+                         SELL,2022-03-01 10:00:00,2022-03-01 10:00:00,Sold BTC,-0.005,-0.00001,BTC,-300,SEK,,,Completed,0.015
+                         Buy,2022-02-01 10:00:00,2022-02-01 10:00:00,Bought ETH,1,0,ETH,2000,SEK,,,Completed,1
+                         Buy,2022-01-01 10:00:00,2022-01-01 10:00:00,Bought BTC,0.01,0,BTC,500,SEK,,,Completed,0.02
+                         Exchange,2021-12-01 10:00:00,2021-12-01 10:00:00,Exchanged to BTC,-400,0,SEK,-400,SEK,,,Completed,0
+                         Exchange,2021-12-01 10:00:00,2021-12-01 10:00:00,Exchanged from SEK,0.01,0,BTC,0.01,BTC,,,Completed,0.01";
+
+        /*
+         * When
+         */
+        let rows = in_currency(deserialize_from_reader(statement.as_bytes(), &CsvFormat::default(), true)?, &"BTC".to_string(), false, &Keywords::shipped());
+        let txns = block_on(to_transactions(&rows, &"BTC".to_string(), &Keywords::shipped(), false))?;
+
+        /*
+         * Then
+         */
+        let summary: Vec<_> = txns.iter().map(|t| (t.r#type.clone(), t.paid_amount, t.paid_fee, t.exchanged_amount, t.exchanged_currency.as_str())).collect();
+        assert_eq!(summary, vec![
+            (TransactionType::Buy, dec!(0.01), dec!(0), dec!(-400), "SEK"),
+            (TransactionType::Buy, dec!(0.01), dec!(0), dec!(-500), "SEK"),
+            (TransactionType::Sell, dec!(-0.00501), dec!(-0.00001), dec!(300), "SEK"),
+        ]);
+        assert_eq!(String::from(Type::from("SELL".to_string())), "Sell");
+        Ok(())
     }
 
-    fn exchange_to_transaction(&self, txn: &mut Transaction, currency: &Currency) {
-        if self.started_date.contains("2021-11-17 15:26:31") {
-            debug!("hello: {:?}", self);
-        }
+    #[test]
+    fn should_read_interest_and_transfers_of_savings() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let statement = "Type,Started Date,Completed Date,Description,Amount,Fee,Currency,Original Amount,Original Currency,Settled Amount,Settled Currency,State,Balance
+                         Transfer,2022-03-01 10:00:00,2022-03-01 10:00:00,From BTC Savings,0.0101,0,BTC,0.0101,BTC,,,Completed,0.0201
+                         INTEREST,2022-02-01 10:00:00,2022-02-01 10:00:00,Interest earned,0.0001,0,BTC,5,SEK,,,Completed,0.0101
+                         Interest,2022-01-15 10:00:00,2022-01-15 10:00:00,Interest earned,0.00005,0,BTC,0.00005,BTC,,,Completed,0.01005
+                         Transfer,2022-01-01 10:00:00,2022-01-01 10:00:00,To BTC Savings,-0.01,0,BTC,-0.01,BTC,,,Completed,0.01";
 
-        // target currency: "BCH", currency: "BCH", description: "Exchanged from SEK"
-        // if self.currency.eq(currency) && self.description.contains("Exchanged from") {
-        if self.currency.eq(currency) && self.amount.is_sign_positive() {
-            debug!("{:?}: Bought {:?} of {:?} ({:?}), incl. fee {:?}", self.started_date, self.amount+self.fee, self.currency, self.description, self.fee);
-            txn.r#type = TransactionType::Buy;
-            txn.paid_amount = self.amount + self.fee;
-            txn.paid_currency = currency.clone();
-            txn.date = self.started_date.clone();
+        /*
+         * When
+         */
+        let rows = in_currency(deserialize_from_reader(statement.as_bytes(), &CsvFormat::default(), true)?, &"BTC".to_string(), true, &Keywords::shipped());
+        let txns = block_on(to_transactions(&rows, &"BTC".to_string(), &Keywords::shipped(), false))?;
 
-        }
-        // target currency: "BCH", currency: "BCH", description: "Exchanged to SEK"
-        // if self.currency.eq(currency) && self.description.contains("Exchanged to") {
-        if self.currency.eq(currency) && self.amount.is_sign_negative() {
-            debug!("{:?}: Sold {:?} of {:?} ({:?}), incl. fee {:?}", self.started_date, self.amount+self.fee, self.currency, self.description, self.fee);
-            txn.r#type = TransactionType::Sell;
-            txn.paid_amount = self.amount + self.fee;
-            txn.paid_currency = currency.clone();
-            txn.date = self.started_date.clone();
-        }
-        // target currency: "BCH", currency: "SEK", description: "Exchanged from BCH"
-        if self.description.contains("Exchanged from") && self.description.contains(currency) {
-            debug!("{:?}: Income of selling is the price of {:?} of {:?} in SEK ({:?}), incl. fee {:?}", self.started_date, self.amount+self.fee, self.currency, self.description, self.fee);
-            txn.r#type = TransactionType::Sell;
-            txn.exchanged_amount = self.amount + self.fee;
-            txn.exchanged_currency = self.currency.clone();
-        }
-        // target currency: "BCH", currency: "SEK", description: "Exchanged to BCH"
-        if self.description.contains("Exchanged to") && self.description.contains(currency) {
-            debug!("{:?}: Cost of buying is the price of {:?} of {:?} in SEK ({:?}), incl. fee {:?}", self.started_date, self.amount+self.fee, self.currency, self.description, self.fee);
-            txn.r#type = TransactionType::Buy;
-            txn.exchanged_amount = self.amount + self.fee;
-            txn.exchanged_currency = self.currency.clone();
-        }
-        if self.description.contains("Vault") {
-            txn.is_vault = true;
-        }
+        /*
+         * Then
+         */
+        let summary: Vec<_> = txns.iter().map(|t| (t.r#type.clone(), t.paid_amount, t.exchanged_amount, t.exchanged_currency.as_str())).collect();
+        assert_eq!(summary, vec![
+            (TransactionType::Transfer, dec!(-0.01), dec!(0), ""),
+            (TransactionType::Income, dec!(0.00005), dec!(0), ""),
+            (TransactionType::Income, dec!(0.0001), dec!(-5), "SEK"),
+            (TransactionType::Transfer, dec!(0.0101), dec!(0), ""),
+        ]);
+        assert!(txns.iter().all(|t| !t.is_vault));
+        Ok(())
     }
 
-    fn card_payment_to_transaction(&self, txn: &mut Transaction, currency: &Currency) {
-        // amount: -0.00123456, fee: 0.00000000, currency: "BTC", original_amount: -543.21, original_currency: "SEK",
-        // settled_amount: Some(543.21), settled_currency: Some("SEK"), state: Completed, balance: Some(0.00000000) }
-        txn.r#type = TransactionType::Sell;
-        txn.paid_amount = self.amount + self.fee;
-        txn.paid_currency = currency.clone();
-        txn.exchanged_amount = self.original_amount.neg();
-        txn.exchanged_currency = self.original_currency.clone();
-        txn.date = self.started_date.clone();
-        txn.is_vault = false;
-    }
-}
+    #[test]
+    fn should_exclude_fiat_exchanges() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let mut file = NamedTempFile::new()?;
+        writeln!(file, "Type,Started Date,Completed Date,Description,Amount,Fee,Currency,Original Amount,Original Currency,Settled Amount,Settled Currency,State,Balance
+                        Exchange,2022-02-01 10:00:00,2022-02-01 10:00:00,Exchanged to BTC,-100,-1,EUR,-100,EUR,,,Completed,0
+                        Exchange,2022-02-01 10:00:00,2022-02-01 10:00:00,Exchanged from EUR,0.003,0,BTC,0.003,BTC,,,Completed,0.003
+                        Exchange,2022-01-01 10:00:00,2022-01-01 10:00:00,Exchanged to EUR,-1050,0,SEK,-1050,SEK,,,Completed,0
+                        Exchange,2022-01-01 10:00:00,2022-01-01 10:00:00,Exchanged from SEK,100,0,EUR,100,EUR,,,Completed,100")?;
+        let path = file.path().to_path_buf();
 
-#[cfg(test)]
-mod test {
-    use crate::reader::*;
-    use futures::executor::block_on;
-    use rust_decimal_macros::dec;
-    use std::error::Error;
-    use std::io::Write;
-    use std::path::PathBuf;
-    use tempfile::NamedTempFile;
+        /*
+         * When
+         */
+        let btc = block_on(read_exchanges_in_currency(&path, &"BTC".to_string(), &Options::default()))?;
+        let eur = block_on(read_exchanges_in_currency(&path, &"EUR".to_string(), &Options::default()))?;
+
+        /*
+         * Then
+         */
+        assert_eq!(btc.len(), 2);
+        assert!(btc.iter().all(|r| !r.is_fiat_exchange(&Keywords::shipped())));
+        assert_eq!(eur.len(), 4);
+        assert_eq!(eur.iter().filter(|r| r.is_fiat_exchange(&Keywords::shipped())).count(), 2);
+        Ok(())
+    }
 
     #[test]
-    fn should_deserialize_from_path() -> Result<(), Box<dyn Error>> {
+    fn should_deserialize_localized_file() -> Result<(), Box<dyn Error>> {
         /*
          * Given
          */
         let mut file = NamedTempFile::new()?;
-        writeln!(file, "Type,Started Date,Completed Date,Description,Amount,Fee,Currency,Original Amount,Original Currency,Settled Amount,Settled Currency,State,Balance
-                        Exchange,2022-03-01 16:21:49,2022-03-01 16:21:49,Exchanged to EOS,-900.90603463,-20.36495977,DOGE,-900.90603463,DOGE,,,Completed,1078.7290056
-                        Exchange,2022-03-01 16:21:49,2022-03-01 16:21:49,Exchanged from DOGE,50,0,EOS,50,EOS,,,Completed,50
-                        Exchange,2021-12-31 17:54:48,2021-12-31 17:54:48,Exchanged to DOGE,-5000.45,-80.15,SEK,-5000.45,SEK,,,Completed,700.27
-                        Exchange,2021-12-31 17:54:48,2021-12-31 17:54:48,Exchanged from SEK,2000,0,DOGE,2000,DOGE,,,Completed,2000")?;
+        writeln!(file, "Type;Started Date;Completed Date;Description;Amount;Fee;Currency;Original Amount;Original Currency;Settled Amount;Settled Currency;State;Balance
+                        Exchange;2021-12-31 17:54:48;2021-12-31 17:54:48;Exchanged to DOGE;-5 000,45;-80,15;SEK;-5 000,45;SEK;;;Completed;1 700,27")?;
         let path = file.path().to_str().unwrap();
 
         /*
          * When
          */
-        let rows = block_on(deserialize_from(&PathBuf::from(path)))?;
+        let rows = block_on(deserialize_from(&PathBuf::from(path), &Options::default()))?;
 
         /*
          * Then
          */
         let mut iter = rows.into_iter();
-        assert_eq!(iter.next(), Some(Row{
-            r#type: Type::Exchange,
-            started_date: "2022-03-01 16:21:49".to_string(),
-            completed_date: Some("2022-03-01 16:21:49".to_string()),
-            description: "Exchanged to EOS".to_string(),
-            amount: dec!(-900.90603463),
-            fee: dec!(-20.36495977),
-            currency: "DOGE".to_string(),
-            original_amount: dec!(-900.90603463),
-            original_currency: "DOGE".to_string(),
-            settled_amount: None,
-            settled_currency: None,
-            state: State::Completed,
-            balance: Some(dec!(1078.7290056))
-        }));
-        assert_eq!(iter.next(), Some(Row{
-            r#type: Type::Exchange,
-            started_date: "2022-03-01 16:21:49".to_string(),
-            completed_date: Some("2022-03-01 16:21:49".to_string()),
-            description: "Exchanged from DOGE".to_string(),
-            amount: dec!(50),
-            fee: dec!(0),
-            currency: "EOS".to_string(),
-            original_amount: dec!(50),
-            original_currency: "EOS".to_string(),
-            settled_amount: None,
-            settled_currency: None,
-            state: State::Completed,
-            balance: Some(dec!(50))
-        }));
         assert_eq!(iter.next(), Some(Row{
             r#type: Type::Exchange,
             started_date: "2021-12-31 17:54:48".to_string(),
@@ -289,22 +1497,8 @@ mod test {
             settled_amount: None,
             settled_currency: None,
             state: State::Completed,
-            balance: Some(dec!(700.27))
-        }));
-        assert_eq!(iter.next(), Some(Row{
-            r#type: Type::Exchange,
-            started_date: "2021-12-31 17:54:48".to_string(),
-            completed_date: Some("2021-12-31 17:54:48".to_string()),
-            description: "Exchanged from SEK".to_string(),
-            amount: dec!(2000),
-            fee: dec!(0),
-            currency: "DOGE".to_string(),
-            original_amount: dec!(2000),
-            original_currency: "DOGE".to_string(),
-            settled_amount: None,
-            settled_currency: None,
-            state: State::Completed,
-            balance: Some(dec!(2000))
+            balance: Some(dec!(1700.27)),
+            line: 2
         }));
         assert_eq!(iter.next(), None);
         Ok(())
@@ -329,7 +1523,8 @@ mod test {
                 settled_amount: Some(dec!(321.23456789)),
                 settled_currency: Some("SEK".to_string()),
                 state: State::Completed,
-                balance: Some(dec!(9876.123345))
+                balance: Some(dec!(9876.123345)),
+                line: 2
             },
             Row{
                 r#type: Type::Exchange,
@@ -344,7 +1539,8 @@ mod test {
                 settled_amount: None,
                 settled_currency: None,
                 state: State::Completed,
-                balance: Some(dec!(1078.7290056))
+                balance: Some(dec!(1078.7290056)),
+                line: 3
             },
             Row{
                 r#type: Type::Exchange,
@@ -359,7 +1555,8 @@ mod test {
                 settled_amount: None,
                 settled_currency: None,
                 state: State::Completed,
-                balance: Some(dec!(50))
+                balance: Some(dec!(50)),
+                line: 4
             },
             Row{
                 r#type: Type::Exchange,
@@ -374,7 +1571,8 @@ mod test {
                 settled_amount: None,
                 settled_currency: None,
                 state: State::Completed,
-                balance: Some(dec!(700.27))
+                balance: Some(dec!(700.27)),
+                line: 5
             },
             Row{
                 r#type: Type::Exchange,
@@ -389,7 +1587,8 @@ mod test {
                 settled_amount: None,
                 settled_currency: None,
                 state: State::Completed,
-                balance: Some(dec!(2000))
+                balance: Some(dec!(2000)),
+                line: 6
             },
             Row{
                 r#type: Type::Exchange,
@@ -404,7 +1603,8 @@ mod test {
                 settled_amount: None,
                 settled_currency: None,
                 state: State::Completed,
-                balance: Some(dec!(500))
+                balance: Some(dec!(500)),
+                line: 7
             },
             Row{
                 r#type: Type::Exchange,
@@ -419,7 +1619,8 @@ mod test {
                 settled_amount: None,
                 settled_currency: None,
                 state: State::Completed,
-                balance: Some(dec!(139.94))
+                balance: Some(dec!(139.94)),
+                line: 8
             },
             Row{
                 r#type: Type::Exchange,
@@ -434,7 +1635,8 @@ mod test {
                 settled_amount: None,
                 settled_currency: None,
                 state: State::Completed,
-                balance: Some(dec!(0))
+                balance: Some(dec!(0)),
+                line: 9
             },
             Row{
                 r#type: Type::Exchange,
@@ -449,13 +1651,14 @@ mod test {
                 settled_amount: None,
                 settled_currency: None,
                 state: State::Completed,
-                balance: Some(dec!(200))
+                balance: Some(dec!(200)),
+                line: 10
             }
         ];
         /*
          * When
          */
-        let txns = block_on(to_transactions(&rows, &"DOGE".to_string()))?;
+        let txns = block_on(to_transactions(&rows, &"DOGE".to_string(), &Keywords::shipped(), false))?;
 
         /*
         * Then
@@ -468,7 +1671,14 @@ mod test {
             exchanged_currency: "SEK".to_string(),
             exchanged_amount: dec!(-300),
             date: "2021-11-10 17:03:13".to_string(),
-            is_vault: true
+            is_vault: true,
+            paid_fee: dec!(-0.06),
+            exchanged_fee: dec!(0),
+            price_source: None,
+            unit_price: None,
+            fiat_value: None,
+            other_fee: None,
+            origin: Origin{ file: "".to_string(), lines: vec![9, 10], descriptions: vec!["Exchanged to DOGE DOGE Vault".to_string(), "".to_string()], deviation: None, note: None, tags: vec![], counterparty: None }
         }));
         assert_eq!(iter.next(), Some(Transaction{
             r#type: TransactionType::Buy,
@@ -477,7 +1687,14 @@ mod test {
             exchanged_currency: "SEK".to_string(),
             exchanged_amount: dec!(-20),
             date: "2021-11-11 18:03:13".to_string(),
-            is_vault: true
+            is_vault: true,
+            paid_fee: dec!(-0.06),
+            exchanged_fee: dec!(0),
+            price_source: None,
+            unit_price: None,
+            fiat_value: None,
+            other_fee: None,
+            origin: Origin{ file: "".to_string(), lines: vec![7, 8], descriptions: vec!["Exchanged to DOGE DOGE Vault".to_string(), "Exchanged from SEK".to_string()], deviation: None, note: None, tags: vec![], counterparty: None }
         }));
         assert_eq!(iter.next(), Some(Transaction{
             r#type: TransactionType::Buy,
@@ -486,7 +1703,14 @@ mod test {
             exchanged_currency: "SEK".to_string(),
             exchanged_amount: dec!(-5080.60),
             date: "2021-12-31 17:54:48".to_string(),
-            is_vault: false
+            is_vault: false,
+            paid_fee: dec!(0),
+            exchanged_fee: dec!(-80.15),
+            price_source: None,
+            unit_price: None,
+            fiat_value: None,
+            other_fee: None,
+            origin: Origin{ file: "".to_string(), lines: vec![5, 6], descriptions: vec!["Exchanged to DOGE".to_string(), "Exchanged from SEK".to_string()], deviation: None, note: None, tags: vec![], counterparty: None }
         }));
         assert_eq!(iter.next(), Some(Transaction{
             r#type: TransactionType::Sell,
@@ -495,7 +1719,14 @@ mod test {
             exchanged_currency: "EOS".to_string(),
             exchanged_amount: dec!(50),
             date: "2022-03-01 16:21:49".to_string(),
-            is_vault: false
+            is_vault: false,
+            paid_fee: dec!(-20.36495977),
+            exchanged_fee: dec!(0),
+            price_source: None,
+            unit_price: None,
+            fiat_value: None,
+            other_fee: None,
+            origin: Origin{ file: "".to_string(), lines: vec![3, 4], descriptions: vec!["Exchanged to EOS".to_string(), "Exchanged from DOGE".to_string()], deviation: None, note: None, tags: vec![], counterparty: None }
         }));
         assert_eq!(iter.next(), Some(Transaction{
             r#type: TransactionType::Sell,
@@ -504,10 +1735,309 @@ mod test {
             exchanged_currency: "SEK".to_string(),
             exchanged_amount: dec!(321.23456789),
             date: "2022-04-02 17:22:50".to_string(),
-            is_vault: false
+            is_vault: false,
+            paid_fee: dec!(0),
+            exchanged_fee: dec!(0),
+            price_source: None,
+            unit_price: None,
+            fiat_value: None,
+            other_fee: None,
+            origin: Origin{ file: "".to_string(), lines: vec![2], descriptions: vec!["Klarna".to_string()], deviation: None, note: None, tags: vec![], counterparty: Some("Klarna".to_string()) }
         }));
         assert_eq!(iter.next(), None);
 
         Ok(())
     }
-}
\ No newline at end of file
+    #[test]
+    fn should_sort_transactions_by_date() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let rows = vec![
+            Row{
+                r#type: Type::CardPayment,
+                started_date: "2021-01-02 10:00:00".to_string(),
+                completed_date: Some("2021-01-02 10:00:00".to_string()),
+                description: "Klarna".to_string(),
+                amount: dec!(-10),
+                fee: dec!(0),
+                currency: "DOGE".to_string(),
+                original_amount: dec!(-5),
+                original_currency: "SEK".to_string(),
+                settled_amount: Some(dec!(5)),
+                settled_currency: Some("SEK".to_string()),
+                state: State::Completed,
+                balance: Some(dec!(90)),
+                line: 2
+            },
+            Row{
+                r#type: Type::Exchange,
+                started_date: "2021-01-03 10:00:00".to_string(),
+                completed_date: Some("2021-01-03 10:00:00".to_string()),
+                description: "Exchanged to DOGE".to_string(),
+                amount: dec!(-50),
+                fee: dec!(0),
+                currency: "SEK".to_string(),
+                original_amount: dec!(-50),
+                original_currency: "SEK".to_string(),
+                settled_amount: None,
+                settled_currency: None,
+                state: State::Completed,
+                balance: Some(dec!(0)),
+                line: 3
+            },
+            Row{
+                r#type: Type::Exchange,
+                started_date: "2021-01-03 10:00:00".to_string(),
+                completed_date: Some("2021-01-03 10:00:00".to_string()),
+                description: "Exchanged from SEK".to_string(),
+                amount: dec!(100),
+                fee: dec!(0),
+                currency: "DOGE".to_string(),
+                original_amount: dec!(100),
+                original_currency: "DOGE".to_string(),
+                settled_amount: None,
+                settled_currency: None,
+                state: State::Completed,
+                balance: Some(dec!(190)),
+                line: 4
+            },
+            Row{
+                r#type: Type::CardPayment,
+                started_date: "2021-01-03 10:00:00".to_string(),
+                completed_date: Some("2021-01-03 10:00:00".to_string()),
+                description: "Klarna".to_string(),
+                amount: dec!(-20),
+                fee: dec!(0),
+                currency: "DOGE".to_string(),
+                original_amount: dec!(-10),
+                original_currency: "SEK".to_string(),
+                settled_amount: Some(dec!(10)),
+                settled_currency: Some("SEK".to_string()),
+                state: State::Completed,
+                balance: Some(dec!(170)),
+                line: 5
+            },
+        ];
+
+        /*
+         * When
+         */
+        let txns = block_on(to_transactions(&rows, &"DOGE".to_string(), &Keywords::shipped(), false))?;
+
+        /*
+         * Then
+         */
+        let dates_and_types: Vec<(&str, &TransactionType)> =
+            txns.iter().map(|t| (t.date.as_str(), &t.r#type)).collect();
+        assert_eq!(dates_and_types, vec![
+            ("2021-01-02 10:00:00", &TransactionType::Sell),
+            ("2021-01-03 10:00:00", &TransactionType::Buy),
+            ("2021-01-03 10:00:00", &TransactionType::Sell),
+        ]);
+
+        Ok(())
+    }
+    #[test]
+    fn should_warn_of_exchange_without_other_side() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let mut file = NamedTempFile::new()?;
+        writeln!(file, "Type,Started Date,Completed Date,Description,Amount,Fee,Currency,Original Amount,Original Currency,Settled Amount,Settled Currency,State,Balance
+                        Exchange,2022-03-02 16:21:49,2022-03-02 16:21:49,Exchanged to EOS,-300,0,DOGE,-300,DOGE,,,Completed,1700
+                        Exchange,2021-12-31 17:54:48,2021-12-31 17:54:48,Exchanged to DOGE,-5000.45,-80.15,SEK,-5000.45,SEK,,,Completed,700.27
+                        Exchange,2021-12-31 17:54:48,2021-12-31 17:54:48,Exchanged from SEK,2000,0,DOGE,2000,DOGE,,,Completed,2000")?;
+        let path = file.path().to_path_buf();
+
+        /*
+         * When
+         */
+        let txns = block_on(read_transactions(&path, &"DOGE".to_string(), None, &Options::default()))?;
+
+        /*
+         * Then
+         */
+        assert_eq!(txns.len(), 1);
+        let warnings = warnings::take();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].code, Code::UnmatchedRow);
+        assert_eq!(warnings[0].file, Some(path.display().to_string()));
+        assert_eq!(warnings[0].lines, vec![2]);
+        Ok(())
+    }
+
+    #[test]
+    fn should_pair_sides_of_exchanges_that_are_not_adjacent() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let mut file = NamedTempFile::new()?;
+        writeln!(file, "Type,Started Date,Completed Date,Description,Amount,Fee,Currency,Original Amount,Original Currency,Settled Amount,Settled Currency,State,Balance
+                        Exchange,2022-03-01 16:22:10,2022-03-01 16:22:10,Exchanged from DOGE,50,0,EOS,50,EOS,,,Completed,50
+                        Exchange,2022-03-01 16:21:49,2022-03-01 16:21:49,Exchanged from SEK,2000,0,DOGE,2000,DOGE,,,Completed,2000
+                        Exchange,2022-03-01 16:21:49,2022-03-01 16:21:49,Exchanged to EOS,-300,0,DOGE,-300,DOGE,,,Completed,1700
+                        Exchange,2022-03-01 16:21:49,2022-03-01 16:21:49,Exchanged to DOGE,-5000,0,SEK,-5000,SEK,,,Completed,700")?;
+        let path = file.path().to_path_buf();
+
+        /*
+         * When
+         */
+        warnings::take();
+        let txns = block_on(read_transactions(&path, &"DOGE".to_string(), None, &Options::default()))?;
+
+        /*
+         * Then
+         */
+        let rows: Vec<_> = txns.iter().map(|t| (t.r#type.clone(), t.exchanged_currency.as_str(), t.origin.lines.clone())).collect();
+        assert_eq!(rows, vec![
+            (TransactionType::Buy, "SEK", vec![3, 5]),
+            (TransactionType::Sell, "EOS", vec![2, 4]),
+        ]);
+        let warnings = warnings::take();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].code, Code::UnmatchedRow);
+        assert_eq!(warnings[0].lines, vec![2, 4]);
+        Ok(())
+    }
+
+    #[test]
+    fn should_warn_of_invalid_dates_and_pair_rows_out_of_order() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let mut file = NamedTempFile::new()?;
+        writeln!(file, "Type,Started Date,Completed Date,Description,Amount,Fee,Currency,Original Amount,Original Currency,Settled Amount,Settled Currency,State,Balance
+                        Exchange,2022-03-02 10:00:00,2022-03-02 10:00:00,Exchanged to SEK,-300,0,DOGE,-300,DOGE,,,Completed,1700
+                        Exchange,2022-03-02 10:00:00,2022-03-01 10:00:00,Exchanged from DOGE,3000,0,SEK,3000,SEK,,,Completed,3000
+                        Exchange,2022-03-01 10:00:00,2022-03-01 10:00:00,Exchanged to DOGE,-5000,0,SEK,-5000,SEK,,,Completed,0
+                        Exchange,2022-03-01 10:00:00,2022-03-01 10:00:00,Exchanged from SEK,2000,0,DOGE,2000,DOGE,,,Completed,2000
+                        Exchange,2022-03-03 10:00:00,2022-03-03 10:00:00,Exchanged from SEK,100,0,DOGE,100,DOGE,,,Completed,1800
+                        Exchange,2022-03-03 10:00:00,2022-03-03 10:00:00,Exchanged to DOGE,-1000,0,SEK,-1000,SEK,,,Completed,2000
+                        Transfer,2099-01-01 10:00:00,2099-01-01 10:00:00,To John,-10,0,DOGE,-10,DOGE,,,Completed,1790")?;
+        let path = file.path().to_path_buf();
+        warnings::take();
+
+        /*
+         * When
+         */
+        let txns = block_on(read_transactions(&path, &"DOGE".to_string(), None, &Options::default()))?;
+
+        /*
+         * Then
+         */
+        let rows: Vec<_> = txns.iter().map(|t| (t.date.as_str(), t.r#type.clone(), t.origin.lines.clone())).collect();
+        assert_eq!(rows, vec![
+            ("2022-03-01 10:00:00", TransactionType::Buy, vec![4, 5]),
+            ("2022-03-02 10:00:00", TransactionType::Sell, vec![2, 3]),
+            ("2022-03-03 10:00:00", TransactionType::Buy, vec![6, 7]),
+            ("2099-01-01 10:00:00", TransactionType::Transfer, vec![8]),
+        ]);
+        let warnings: Vec<_> = warnings::take().into_iter().map(|w| (w.code, w.lines)).collect();
+        assert_eq!(warnings, vec![
+            (Code::CompletedBeforeStarted, vec![3]),
+            (Code::OutOfOrder, vec![6]),
+            (Code::FutureDate, vec![8]),
+        ]);
+        Ok(())
+    }
+
+    /// Buys of DOGE for SEK and sales of part of the DOGE held, ten minutes apart, as the two
+    /// exchange rows of each in the file, newest first, and shuffled.
+    fn exchange_rows() -> impl Strategy<Value = Vec<Row>> {
+        prop::collection::vec((any::<bool>(), 1..1000u32, 1..100u32), 1..20)
+            .prop_map(|trades| {
+                let start = NaiveDateTime::parse_from_str("2022-01-01 10:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+                let mut held = dec!(0);
+                let mut rows = vec![];
+                for (i, (is_buy, amount, price)) in trades.into_iter().enumerate() {
+                    let date = (start + chrono::Duration::minutes(10 * i as i64)).format("%Y-%m-%d %H:%M:%S").to_string();
+                    let amount = match is_buy { true => Decimal::from(amount), false => -Decimal::from(amount).min(held) };
+                    if amount.is_zero() {
+                        continue;
+                    }
+                    held += amount;
+                    let row = |description: &str, amount: Decimal, currency: &str| Row{
+                        r#type: Type::Exchange,
+                        started_date: date.clone(),
+                        completed_date: Some(date.clone()),
+                        description: description.to_string(),
+                        amount,
+                        fee: dec!(0),
+                        currency: currency.to_string(),
+                        original_amount: amount,
+                        original_currency: currency.to_string(),
+                        settled_amount: None,
+                        settled_currency: None,
+                        state: State::Completed,
+                        balance: None,
+                        line: 0,
+                    };
+                    let value = -amount * Decimal::from(price);
+                    match is_buy {
+                        true => rows.extend([row("Exchanged to DOGE", value, "SEK"), row("Exchanged from SEK", amount, "DOGE")]),
+                        false => rows.extend([row("Exchanged to SEK", amount, "DOGE"), row("Exchanged from DOGE", value, "SEK")]),
+                    }
+                }
+                rows.reverse();
+                rows
+            })
+            .prop_shuffle()
+    }
+
+    proptest! {
+        #[test]
+        fn should_pair_and_calculate_any_exchanges(mut rows in exchange_rows()) {
+            /*
+             * Given
+             */
+            rows.iter_mut().enumerate().for_each(|(i, row)| row.line = i as u64 + 2);
+            let (currency, base) = ("DOGE".to_string(), "SEK".to_string());
+
+            /*
+             * When
+             */
+            let txns = block_on(to_transactions(&rows, &currency, &Keywords::shipped(), true)).unwrap();
+            let calculation = block_on(calculator::calculate(&txns, &currency, &base, &Options::default())).unwrap();
+
+            /*
+             * Then
+             */
+            prop_assert_eq!(txns.len() * 2, rows.len());
+            prop_assert!(txns.iter().all(|t| t.origin.lines.len() == 2));
+            prop_assert!(invariants::check_calculation(&txns, &calculation).is_ok());
+            prop_assert!(warnings::take().iter().all(|w| w.code == Code::OutOfOrder));
+        }
+    }
+
+    #[test]
+    fn should_detect_export_formats() {
+        /*
+         * Given
+         */
+        let headers = [
+            ("Type,Started Date,Completed Date,Description,Amount,Fee,Currency,Original Amount,Original Currency,Settled Amount,Settled Currency,State,Balance", ExportFormat::Account),
+            ("Type,Datetime,Account,Amount,Value,Rate,Fee,Sub Type", ExportFormat::BitstampTransactions),
+            ("#,DESCRIPTION,CURRENCY,AMOUNT,BALANCE,DATE,WALLET", ExportFormat::BitfinexLedger),
+            ("tradeCreatedAt,orderId,symbol,side,price,size,funds,fee,liquidity,feeCurrency,orderType", ExportFormat::KucoinTrades),
+            ("Timestamp (UTC),Transaction Description,Currency,Amount,To Currency,To Amount,Native Currency,Native Amount,Native Amount (in USD),Transaction Kind,Transaction Hash", ExportFormat::CryptoComApp),
+            ("Transaction,Type,Input Currency,Input Amount,Output Currency,Output Amount,USD Equivalent,Details,Date / Time (UTC)", ExportFormat::NexoTransactions),
+            ("Internal id, Date and time, Transaction type, Coin type, Coin amount, USD Value, Original Reward Coin, Reward Amount In Original Coin, Confirmed", ExportFormat::CelsiusTransactions),
+            ("Date,Sent Amount,Sent Currency,Received Amount,Received Currency,Fee Amount,Fee Currency,Net Worth Amount,Net Worth Currency,Label,Description,TxHash", ExportFormat::KoinlyUniversal),
+            ("\"Type\",\"Buy Amount\",\"Buy Currency\",\"Sell Amount\",\"Sell Currency\",\"Fee\",\"Fee Currency\",\"Exchange\",\"Trade-Group\",\"Comment\",\"Date\"", ExportFormat::CoinTrackingUniversal),
+        ];
+
+        /*
+         * When
+         */
+        let detected: Vec<ExportFormat> = headers.iter()
+            .map(|(header, _)| ExportFormat::detect(header, &CsvFormat::detect(header), &StoreAccess::default()))
+            .collect();
+
+        /*
+         * Then
+         */
+        let expected: Vec<ExportFormat> = headers.into_iter().map(|(_, format)| format).collect();
+        assert_eq!(detected, expected);
+    }
+}
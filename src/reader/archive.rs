@@ -0,0 +1,113 @@
+use crate::error::{CryptotaxError, Result};
+use flate2::read::MultiGzDecoder;
+use std::io::{self, BufRead, BufReader, Cursor, Read};
+use std::path::Path;
+use zip::ZipArchive;
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+
+/// Whether the bytes start like a gzip file or a zip archive, which can't be parsed as they are.
+pub(crate) fn is_compressed(bytes: &[u8]) -> bool {
+    bytes.starts_with(GZIP_MAGIC) || bytes.starts_with(ZIP_MAGIC)
+}
+
+pub(crate) fn is_zip(bytes: &[u8]) -> bool {
+    bytes.starts_with(ZIP_MAGIC)
+}
+
+/// Decompresses a gzip file, or the only csv file of a zip archive, while it is read. Anything
+/// else is read as it is. The format is detected by the first bytes, not the extension, so that
+/// stdin can be compressed too.
+pub(crate) fn decompress<R: Read + 'static>(rdr: R) -> Result<Box<dyn Read>> {
+    let mut rdr = BufReader::new(rdr);
+    let head = rdr.fill_buf()?;
+    if head.starts_with(GZIP_MAGIC) {
+        return Ok(Box::new(MultiGzDecoder::new(rdr)));
+    }
+    if head.starts_with(ZIP_MAGIC) {
+        let mut bytes = vec![];
+        rdr.read_to_end(&mut bytes)?;
+        let mut files = csv_files(&bytes)?;
+        return match files.len() {
+            1 => Ok(Box::new(Cursor::new(files.remove(0).1))),
+            n => {
+                let msg = format!("The zip archive has {} csv files. Only one can be read here", n);
+                Err(CryptotaxError::Config(msg))
+            }
+        };
+    }
+    Ok(Box::new(rdr))
+}
+
+/// The csv files of a zip archive, by their names without the directories, decompressed. Other
+/// files, e.g. a PDF of the statement, are skipped.
+pub(crate) fn csv_files(bytes: &[u8]) -> Result<Vec<(String, Vec<u8>)>> {
+    let mut zip = ZipArchive::new(Cursor::new(bytes)).map_err(io::Error::other)?;
+    let mut files = vec![];
+    for i in 0..zip.len() {
+        let mut file = zip.by_index(i).map_err(io::Error::other)?;
+        let name = file.enclosed_name()
+            .and_then(|p| p.file_name().map(|n| n.to_string_lossy().to_string()))
+            .unwrap_or_default();
+        let is_csv = Path::new(&name).extension().and_then(|e| e.to_str()).is_some_and(|e| e.eq_ignore_ascii_case("csv"));
+        if file.is_file() && is_csv && !name.starts_with('.') {
+            let mut content = vec![];
+            file.read_to_end(&mut content)?;
+            files.push((name, content));
+        }
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::reader::archive::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::error::Error;
+    use std::io::Write;
+    use zip::write::SimpleFileOptions;
+    use zip::ZipWriter;
+
+    #[test]
+    fn should_decompress_gzip_and_zip() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let csv = b"Type,Amount\nExchange,1\n";
+        let mut gz = GzEncoder::new(vec![], Compression::default());
+        gz.write_all(csv)?;
+        let gz = gz.finish()?;
+        let zipped = |names: &[&str]| -> Result<Vec<u8>, Box<dyn Error>> {
+            let mut zip = ZipWriter::new(Cursor::new(vec![]));
+            for name in names {
+                zip.start_file(*name, SimpleFileOptions::default())?;
+                zip.write_all(csv)?;
+            }
+            Ok(zip.finish()?.into_inner())
+        };
+        let zip = zipped(&["statement/transactions_history.csv", "statement.pdf"])?;
+        let zips = zipped(&["2021.csv", "2022.CSV"])?;
+
+        /*
+         * When
+         */
+        let read = |bytes: Vec<u8>| -> Result<Vec<u8>> {
+            let mut content = vec![];
+            decompress(Cursor::new(bytes))?.read_to_end(&mut content)?;
+            Ok(content)
+        };
+
+        /*
+         * Then
+         */
+        assert_eq!(read(gz)?, csv);
+        assert_eq!(read(zip.clone())?, csv);
+        assert_eq!(read(csv.to_vec())?, csv);
+        assert!(read(zips.clone()).is_err());
+        assert_eq!(csv_files(&zip)?, vec![("transactions_history.csv".to_string(), csv.to_vec())]);
+        assert_eq!(csv_files(&zips)?.into_iter().map(|(name, _)| name).collect::<Vec<_>>(), vec!["2021.csv", "2022.CSV"]);
+        Ok(())
+    }
+}
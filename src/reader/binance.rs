@@ -0,0 +1,221 @@
+use crate::error::Result;
+use crate::legs::{self, Leg};
+use crate::locale::CsvFormat;
+use crate::reader;
+use crate::timestamp::Timestamp;
+use crate::transaction::{Currency, Origin, Transaction, TransactionType};
+use csv::{ReaderBuilder, Trim};
+use rust_decimal::prelude::*;
+use serde::Deserialize;
+use std::io::Read;
+
+/// A row in the Binance futures transaction history, e.g.
+/// `2022-01-01 10:00:00,USDT-Futures,Realized Profit and Loss,USDT,12.5,`.
+#[derive(Debug, Deserialize, PartialEq)]
+struct FuturesRow {
+    #[serde(rename = "UTC_Time")]
+    time: String,
+
+    #[serde(rename = "Operation")]
+    operation: String,
+
+    #[serde(rename = "Coin")]
+    coin: Currency,
+
+    #[serde(rename = "Change")]
+    change: String,
+}
+
+/// Operations that are gains or losses of the futures positions. Transfers between the spot and
+/// the futures wallets are not.
+const PNL_OPERATIONS: [&str; 8] = [
+    "Realized Profit and Loss", "REALIZED_PNL",
+    "Funding Fee", "FUNDING_FEE",
+    "Fee", "COMMISSION",
+    "Insurance Fund Compensation", "INSURANCE_CLEAR",
+];
+
+/// Operations that pay back part of the fees, or a bonus for referring other users, in the
+/// currency itself. They are income, valued at the market price with `--prices`, see
+/// `rebate::apply`.
+const REBATE_OPERATIONS: [&str; 5] = [
+    "Commission Rebate", "Referral Kickback", "Referral Commission", "Commission Fee Shared With You", "Referral Incentive",
+];
+
+/// Operations that pay interest or rewards of the Simple Earn, savings and staking products.
+/// They are income, summed up as interest, see `earn::is_interest`.
+const EARN_OPERATIONS: [&str; 6] = [
+    "Simple Earn Flexible Interest", "Simple Earn Locked Rewards", "Savings Interest", "Staking Rewards",
+    "ETH 2.0 Staking Rewards", "Launchpool Interest",
+];
+
+/// Operations that move the currency into or out of the Simple Earn, savings and staking
+/// products. The currency stays with the user, so they are transfers, not disposals.
+const EARN_TRANSFER_OPERATIONS: [&str; 8] = [
+    "Simple Earn Flexible Subscription", "Simple Earn Flexible Redemption",
+    "Simple Earn Locked Subscription", "Simple Earn Locked Redemption",
+    "Savings purchase", "Savings Principal redemption",
+    "Staking Purchase", "Staking Redemption",
+];
+
+/// Operations of the legs of a spot trade, which the history writes as a row of each currency
+/// at the same time: the one bought and the one paid, and the fee, often in BNB, see `legs`.
+const TRADE_OPERATIONS: [&str; 4] = ["Transaction Buy", "Transaction Spend", "Transaction Sold", "Transaction Revenue"];
+
+/// Operations of the fee leg of a spot trade.
+const TRADE_FEE_OPERATIONS: [&str; 1] = ["Transaction Fee"];
+
+impl FuturesRow {
+    fn is_leg(&self) -> bool {
+        TRADE_OPERATIONS.contains(&self.operation.as_str()) || TRADE_FEE_OPERATIONS.contains(&self.operation.as_str())
+    }
+
+    fn to_leg(&self, format: &CsvFormat, origin: Origin) -> Option<(String, Leg)> {
+        let date = Timestamp::parse(&self.time)?.to_string();
+        let leg = Leg{
+            date: date.clone(),
+            currency: self.coin.clone(),
+            amount: Decimal::from_str(&format.normalize_decimal(&self.change)).ok()?,
+            is_fee: TRADE_FEE_OPERATIONS.contains(&self.operation.as_str()),
+            origin,
+        };
+        Some((date, leg))
+    }
+
+    fn to_transaction(&self, format: &CsvFormat) -> Option<Transaction> {
+        let change = Decimal::from_str(&format.normalize_decimal(&self.change)).ok()?;
+        let mut txn = Transaction::new();
+        txn.paid_currency = self.coin.clone();
+        txn.paid_amount = change;
+        txn.date = Timestamp::parse(&self.time)?.to_string();
+        let operation = self.operation.as_str();
+        if REBATE_OPERATIONS.contains(&operation) || EARN_OPERATIONS.contains(&operation) {
+            txn.r#type = TransactionType::Income;
+            return Some(txn);
+        }
+        if EARN_TRANSFER_OPERATIONS.contains(&operation) {
+            txn.r#type = TransactionType::Transfer;
+            return Some(txn);
+        }
+        txn.r#type = TransactionType::Futures;
+        txn.exchanged_currency = self.coin.clone();
+        txn.exchanged_amount = change;
+        Some(txn)
+    }
+}
+
+/// Whether the rows of the operation are read, rather than left out like the transfers between
+/// the spot and the futures wallets.
+fn is_read(operation: &str) -> bool {
+    [&PNL_OPERATIONS[..], &REBATE_OPERATIONS, &EARN_OPERATIONS, &EARN_TRANSFER_OPERATIONS].iter().any(|ops| ops.contains(&operation))
+}
+
+/// Reads the Binance futures transaction history from the reader into a `Vec<Transaction>` with the
+/// realized profits and losses in the target currency, e.g. `USDT`, the fee rebates and
+/// referral bonuses, the interest and the transfers of the earn products, and the spot trades of
+/// the legs of the same time, see `legs::to_transaction`.
+/// The transactions are sorted by date, see `Transaction::cmp_by_date`.
+pub(crate) async fn read_transactions<R: Read>(rdr: R, format: &CsvFormat, currency: &Currency) -> Result<Vec<Transaction>> {
+    let mut rdr = ReaderBuilder::new()
+        .has_headers(true)
+        .delimiter(format.delimiter)
+        .trim(Trim::All)
+        .from_reader(rdr);
+
+    let (legs, rows): (Vec<_>, Vec<_>) =
+        reader::read_records::<FuturesRow, _>(&mut rdr)?
+            .into_iter()
+            .partition(|(row, _)| row.is_leg());
+    let events = legs::group(legs.into_iter().filter_map(|(row, origin)| row.to_leg(format, origin)));
+    let mut txns: Vec<Transaction> =
+        rows.into_iter()
+            .filter(|(row, _)| row.coin.eq(currency))
+            .filter(|(row, _)| is_read(&row.operation))
+            .filter_map(|(row, origin)| row.to_transaction(format).map(|txn| Transaction{ origin, ..txn }))
+            .chain(events.iter().filter_map(|event| legs::to_transaction(event, currency)))
+            .collect();
+    txns.sort_by(Transaction::cmp_by_date);
+
+    Ok(txns)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::reader::binance::*;
+    use futures::executor::block_on;
+    use rust_decimal_macros::dec;
+    use std::error::Error;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn should_read_futures_pnl() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let mut file = NamedTempFile::new()?;
+        writeln!(file, "User_ID,UTC_Time,Account,Operation,Coin,Change,Remark
+                        1,2022-01-03 00:00:00,Earn,Simple Earn Flexible Redemption,USDT,50,
+                        1,2022-01-02 12:00:00,Earn,Simple Earn Flexible Interest,USDT,0.01,
+                        1,2022-01-02 11:00:00,Earn,Simple Earn Flexible Subscription,USDT,-50,
+                        1,2022-01-02 10:00:00,USDT-Futures,Funding Fee,USDT,-0.35,
+                        1,2022-01-02 09:00:00,USDT-Futures,Realized Profit and Loss,USDT,12.5,
+                        1,2022-01-01 11:00:00,USDT-Futures,Referral Kickback,USDT,0.2,
+                        1,2022-01-01 10:00:00,USDT-Futures,Transfer,USDT,100,")?;
+
+        /*
+         * When
+         */
+        let txns = block_on(read_transactions(reader::open(file.path())?, &CsvFormat::default(), &"USDT".to_string()))?;
+
+        /*
+         * Then
+         */
+        let amounts: Vec<(TransactionType, Decimal)> = txns.into_iter().map(|t| (t.r#type, t.paid_amount)).collect();
+        assert_eq!(amounts, vec![
+            (TransactionType::Income, dec!(0.2)),
+            (TransactionType::Futures, dec!(12.5)),
+            (TransactionType::Futures, dec!(-0.35)),
+            (TransactionType::Transfer, dec!(-50)),
+            (TransactionType::Income, dec!(0.01)),
+            (TransactionType::Transfer, dec!(50)),
+        ]);
+        Ok(())
+    }
+
+    #[test]
+    fn should_read_spot_trades_of_three_rows() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let mut file = NamedTempFile::new()?;
+        writeln!(file, "User_ID,UTC_Time,Account,Operation,Coin,Change,Remark
+                        1,2022-02-01 10:00:00,Spot,Transaction Fee,USDT,-0.3,
+                        1,2022-02-01 10:00:00,Spot,Transaction Revenue,USDT,300,
+                        1,2022-02-01 10:00:00,Spot,Transaction Sold,BTC,-0.005,
+                        1,2022-01-01 10:00:00,Spot,Transaction Fee,BNB,-0.001,
+                        1,2022-01-01 10:00:00,Spot,Transaction Spend,USDT,-400,
+                        1,2022-01-01 10:00:00,Spot,Transaction Buy,BTC,0.01,")?;
+
+        /*
+         * When
+         */
+        let btc = block_on(read_transactions(reader::open(file.path())?, &CsvFormat::default(), &"BTC".to_string()))?;
+        let bnb = block_on(read_transactions(reader::open(file.path())?, &CsvFormat::default(), &"BNB".to_string()))?;
+
+        /*
+         * Then
+         */
+        let rows: Vec<_> = btc.iter()
+            .map(|t| (t.r#type.clone(), t.paid_amount, t.exchanged_currency.as_str(), t.exchanged_amount, t.exchanged_fee, t.other_fee.clone()))
+            .collect();
+        assert_eq!(rows, vec![
+            (TransactionType::Buy, dec!(0.01), "USDT", dec!(-400), dec!(0), Some(("BNB".to_string(), dec!(-0.001)))),
+            (TransactionType::Sell, dec!(-0.005), "USDT", dec!(299.7), dec!(-0.3), None),
+        ]);
+        assert_eq!(btc[0].origin.lines, vec![5, 6, 7]);
+        let fees: Vec<_> = bnb.iter().map(|t| (t.r#type.clone(), t.paid_amount, t.exchanged_currency.as_str())).collect();
+        assert_eq!(fees, vec![(TransactionType::Sell, dec!(-0.001), "BNB")]);
+        Ok(())
+    }
+}
@@ -0,0 +1,179 @@
+use crate::decimal;
+use crate::error::Result;
+use crate::locale::CsvFormat;
+use crate::reader;
+use crate::timestamp::Timestamp;
+use crate::transaction::{Currency, Origin, Transaction, TransactionType};
+use csv::{ReaderBuilder, Trim};
+use rust_decimal::prelude::*;
+use serde::Deserialize;
+use std::io::Read;
+
+/// A row in the Bitfinex ledger, where a trade is a row in each of the two currencies and a row
+/// for the fee, e.g.
+/// `1,Exchange 0.01 BTC for USD @ 40000 on wallet exchange,BTC,0.01,0.01,22-01-02 10:00:00,exchange`.
+#[derive(Debug, Deserialize, PartialEq)]
+struct LedgerRow {
+    #[serde(rename = "DESCRIPTION")]
+    description: String,
+
+    #[serde(rename = "CURRENCY")]
+    currency: Currency,
+
+    #[serde(rename = "AMOUNT")]
+    amount: String,
+
+    #[serde(rename = "DATE")]
+    date: String,
+}
+
+/// Parses a date such as `22-01-02 10:00:00`, or `2022-01-02 10:00:00` of newer ledgers.
+fn parse_date(field: &str) -> Option<String> {
+    Timestamp::parse_with(field, &["%y-%m-%d %H:%M:%S"])
+        .or_else(|| Timestamp::parse(field))
+        .map(|t| t.to_string())
+}
+
+/// Parses the description of a trade, e.g. `Exchange 0.01 BTC for USD @ 40000 on wallet exchange`,
+/// into the currencies of the pair and the price.
+fn parse_exchange(description: &str, format: &CsvFormat) -> Option<(Currency, Currency, Decimal)> {
+    let words: Vec<&str> = description.split_whitespace().collect();
+    match words[..] {
+        ["Exchange", _, base, "for", quote, "@", price, ..] => {
+            let price = Decimal::from_str(&format.normalize_decimal(price)).ok()?;
+            Some((base.to_string(), quote.to_string(), price))
+        }
+        _ => None,
+    }
+}
+
+impl LedgerRow {
+    fn amount(&self, format: &CsvFormat) -> Option<Decimal> {
+        Decimal::from_str(&format.normalize_decimal(&self.amount)).ok()
+    }
+
+    fn is_fee(&self) -> bool {
+        self.description.starts_with("Trading fees")
+    }
+
+    /// Converts a trade or a deposit or withdrawal in the target currency. The trades are valued
+    /// at the price in their description.
+    fn to_transaction(&self, format: &CsvFormat) -> Result<Option<Transaction>> {
+        let Some(amount) = self.amount(format) else {
+            return Ok(None);
+        };
+        let mut txn = Transaction::new();
+        txn.paid_currency = self.currency.clone();
+        txn.paid_amount = amount;
+        let Some(date) = parse_date(&self.date) else {
+            return Ok(None);
+        };
+        txn.date = date;
+        txn.r#type = if amount.is_sign_positive() { TransactionType::Buy } else { TransactionType::Sell };
+        match parse_exchange(&self.description, format) {
+            Some((base, quote, price)) if base == self.currency => {
+                txn.exchanged_currency = quote;
+                txn.exchanged_amount = decimal::mul(-amount, price)?;
+            }
+            Some((base, quote, price)) if quote == self.currency && !price.is_zero() => {
+                txn.exchanged_currency = base;
+                txn.exchanged_amount = decimal::share(-amount, Decimal::ONE, price)?;
+            }
+            Some(_) => return Ok(None),
+            None if self.description.contains("Deposit") || self.description.contains("Withdrawal") => {
+                txn.r#type = TransactionType::Transfer;
+            }
+            None => return Ok(None),
+        }
+        Ok(Some(txn))
+    }
+}
+
+/// Includes the trading fees in the trades at the same time, in the traded or in the exchanged
+/// currency.
+fn add_fees(txns: &mut [Transaction], fees: &[(Currency, String, Decimal)]) {
+    for (currency, date, fee) in fees {
+        let txn = txns.iter_mut().find(|t| t.date.eq(date) && t.r#type != TransactionType::Transfer);
+        match txn {
+            Some(t) if t.paid_currency.eq(currency) => {
+                t.paid_amount += fee;
+                t.paid_fee += fee;
+            }
+            Some(t) if t.exchanged_currency.eq(currency) => {
+                t.exchanged_amount += fee;
+                t.exchanged_fee += fee;
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Reads the Bitfinex ledger from the reader into a `Vec<Transaction>` in the target currency: the
+/// trades with their fees, and the deposits and withdrawals as transfers.
+/// The transactions are sorted by date, see `Transaction::cmp_by_date`.
+pub(crate) async fn read_transactions<R: Read>(rdr: R, format: &CsvFormat, currency: &Currency) -> Result<Vec<Transaction>> {
+    let mut rdr = ReaderBuilder::new()
+        .has_headers(true)
+        .delimiter(format.delimiter)
+        .trim(Trim::All)
+        .from_reader(rdr);
+
+    let rows: Vec<(LedgerRow, Origin)> = reader::read_records::<LedgerRow, _>(&mut rdr)?;
+    let fees: Vec<(Currency, String, Decimal)> =
+        rows.iter()
+            .filter(|(row, _)| row.is_fee())
+            .filter_map(|(row, _)| Some((row.currency.clone(), parse_date(&row.date)?, row.amount(format)?)))
+            .collect();
+    let mut txns: Vec<Transaction> =
+        rows.into_iter()
+            .filter(|(row, _)| row.currency.eq(currency) && !row.is_fee())
+            .filter_map(|(row, origin)| row.to_transaction(format).map(|txn| txn.map(|txn| Transaction{ origin, ..txn })).transpose())
+            .collect::<Result<_>>()?;
+    add_fees(&mut txns, &fees);
+    txns.sort_by(Transaction::cmp_by_date);
+
+    Ok(txns)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::reader::bitfinex::*;
+    use futures::executor::block_on;
+    use rust_decimal_macros::dec;
+    use std::error::Error;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn should_read_bitfinex_ledger() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let mut file = NamedTempFile::new()?;
+        writeln!(file, "#,DESCRIPTION,CURRENCY,AMOUNT,BALANCE,DATE,WALLET
+                        6,Bitcoin Withdrawal #12345 on wallet exchange,BTC,-0.002,0.0029,22-03-01 12:00:00,exchange
+                        5,Trading fees for -0.005 BTC (BTCUSD) @ 45000 on BFX (0.2%) on wallet exchange,USD,-0.45,174.55,22-02-01 10:00:00,exchange
+                        4,Exchange -0.005 BTC for USD @ 45000 on wallet exchange,USD,225,175,22-02-01 10:00:00,exchange
+                        3,Exchange -0.005 BTC for USD @ 45000 on wallet exchange,BTC,-0.005,0.0049,22-02-01 10:00:00,exchange
+                        2,Trading fees for 0.01 BTC (BTCUSD) @ 40000 on BFX (0.1%) on wallet exchange,BTC,-0.00001,0.00999,22-01-02 10:00:00,exchange
+                        1,Exchange 0.01 BTC for USD @ 40000 on wallet exchange,BTC,0.01,0.01,22-01-02 10:00:00,exchange")?;
+
+        /*
+         * When
+         */
+        let txns = block_on(read_transactions(reader::open(file.path())?, &CsvFormat::default(), &"BTC".to_string()))?;
+
+        /*
+         * Then
+         */
+        let rows: Vec<_> = txns.iter()
+            .map(|t| (t.r#type.clone(), t.date.as_str(), t.paid_amount, t.paid_fee, t.exchanged_currency.as_str(), t.exchanged_amount, t.exchanged_fee))
+            .collect();
+        assert_eq!(rows, vec![
+            (TransactionType::Buy, "2022-01-02 10:00:00", dec!(0.00999), dec!(-0.00001), "USD", dec!(-400), dec!(0)),
+            (TransactionType::Sell, "2022-02-01 10:00:00", dec!(-0.005), dec!(0), "USD", dec!(224.55), dec!(-0.45)),
+            (TransactionType::Transfer, "2022-03-01 12:00:00", dec!(-0.002), dec!(0), "", dec!(0), dec!(0)),
+        ]);
+        Ok(())
+    }
+}
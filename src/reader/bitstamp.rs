@@ -0,0 +1,149 @@
+use crate::error::Result;
+use crate::locale::CsvFormat;
+use crate::reader;
+use crate::timestamp::Timestamp;
+use crate::transaction::{Currency, Transaction, TransactionType};
+use csv::{ReaderBuilder, Trim};
+use rust_decimal::prelude::*;
+use serde::Deserialize;
+use std::io::Read;
+
+/// A row in the Bitstamp "Transactions" export, where a trade is a single `Market` row, e.g.
+/// `Market,"Jan. 02, 2022, 10:00 AM",Main Account,0.01000000 BTC,400.00 EUR,40000.00 EUR,1.00 EUR,Buy`.
+#[derive(Debug, Deserialize, PartialEq)]
+struct TransactionRow {
+    #[serde(rename = "Type")]
+    r#type: String,
+
+    #[serde(rename = "Datetime")]
+    datetime: String,
+
+    #[serde(rename = "Amount")]
+    amount: String,             // e.g. "0.01000000 BTC"
+
+    #[serde(rename = "Value")]
+    value: String,
+
+    #[serde(rename = "Fee")]
+    fee: String,
+
+    #[serde(rename = "Sub Type")]
+    sub_type: String,           // "Buy" or "Sell" of a `Market` row
+}
+
+/// Parses an amount such as `0.01000000 BTC` into its amount and currency.
+fn parse_amount(field: &str, format: &CsvFormat) -> Option<(Decimal, Currency)> {
+    let (amount, currency) = field.trim().split_once(' ')?;
+    let amount = Decimal::from_str(&format.normalize_decimal(amount)).ok()?;
+    Some((amount, currency.trim().to_string()))
+}
+
+/// Parses a date such as `Jan. 02, 2022, 10:00 AM`, or `Sept. 02, 2022, 10:00 AM`, into
+/// `2022-01-02 10:00:00`.
+fn parse_date(field: &str) -> Option<String> {
+    let field = field.replace('.', "").replace("Sept ", "Sep ");
+    Timestamp::parse(&field).map(|t| t.to_string())
+}
+
+impl TransactionRow {
+    fn to_transaction(&self, format: &CsvFormat) -> Option<Transaction> {
+        let (amount, currency) = parse_amount(&self.amount, format)?;
+        let mut txn = Transaction::new();
+        txn.paid_currency = currency;
+        txn.date = parse_date(&self.datetime)?;
+        match (self.r#type.as_str(), self.sub_type.as_str()) {
+            ("Market", side) => {
+                let (value, quote) = parse_amount(&self.value, format)?;
+                let fee = parse_amount(&self.fee, format).map_or(Decimal::ZERO, |(fee, _)| fee.abs());
+                txn.exchanged_currency = quote;
+                txn.exchanged_fee = -fee;
+                match side {
+                    "Buy" => {
+                        txn.r#type = TransactionType::Buy;
+                        txn.paid_amount = amount.abs();
+                        txn.exchanged_amount = -(value.abs() + fee);
+                    }
+                    "Sell" => {
+                        txn.r#type = TransactionType::Sell;
+                        txn.paid_amount = -amount.abs();
+                        txn.exchanged_amount = value.abs() - fee;
+                    }
+                    _ => return None,
+                }
+            }
+            ("Deposit", _) => {
+                txn.r#type = TransactionType::Transfer;
+                txn.paid_amount = amount.abs();
+            }
+            ("Withdrawal", _) => {
+                txn.r#type = TransactionType::Transfer;
+                txn.paid_amount = -amount.abs();
+            }
+            _ => return None,
+        }
+        Some(txn)
+    }
+}
+
+/// Reads the Bitstamp "Transactions" export from the reader into a `Vec<Transaction>` in the target
+/// currency: the market buys and sells, and the deposits and withdrawals as transfers.
+/// The transactions are sorted by date, see `Transaction::cmp_by_date`.
+pub(crate) async fn read_transactions<R: Read>(rdr: R, format: &CsvFormat, currency: &Currency) -> Result<Vec<Transaction>> {
+    let mut rdr = ReaderBuilder::new()
+        .has_headers(true)
+        .delimiter(format.delimiter)
+        .trim(Trim::All)
+        .from_reader(rdr);
+
+    let mut txns: Vec<Transaction> =
+        reader::read_records::<TransactionRow, _>(&mut rdr)?
+            .into_iter()
+            .filter_map(|(row, origin)| row.to_transaction(format).map(|txn| Transaction{ origin, ..txn }))
+            .filter(|t| t.paid_currency.eq(currency))
+            .collect();
+    txns.sort_by(Transaction::cmp_by_date);
+
+    Ok(txns)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::reader::bitstamp::*;
+    use futures::executor::block_on;
+    use rust_decimal_macros::dec;
+    use std::error::Error;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn should_read_bitstamp_transactions() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let mut file = NamedTempFile::new()?;
+        writeln!(file, "Type,Datetime,Account,Amount,Value,Rate,Fee,Sub Type
+                        Deposit,\"Jan. 01, 2022, 09:00 AM\",Main Account,1000.00 EUR,,,,
+                        Market,\"Jan. 02, 2022, 10:00 AM\",Main Account,0.01000000 BTC,400.00 EUR,40000.00 EUR,1.00 EUR,Buy
+                        Market,\"Sept. 05, 2022, 03:30 PM\",Main Account,0.00500000 BTC,100.00 EUR,20000.00 EUR,0.25 EUR,Sell
+                        Withdrawal,\"Dec. 01, 2022, 12:00 PM\",Main Account,0.00100000 BTC,,,,")?;
+
+        /*
+         * When
+         */
+        let txns = block_on(read_transactions(reader::open(file.path())?, &CsvFormat::default(), &"BTC".to_string()))?;
+
+        /*
+         * Then
+         */
+        let rows: Vec<_> = txns.iter()
+            .map(|t| (t.r#type.clone(), t.date.as_str(), t.paid_amount, t.exchanged_currency.as_str(), t.exchanged_amount, t.exchanged_fee))
+            .collect();
+        assert_eq!(rows, vec![
+            (TransactionType::Buy, "2022-01-02 10:00:00", dec!(0.01), "EUR", dec!(-401.00), dec!(-1.00)),
+            (TransactionType::Sell, "2022-09-05 15:30:00", dec!(-0.005), "EUR", dec!(99.75), dec!(-0.25)),
+            (TransactionType::Transfer, "2022-12-01 12:00:00", dec!(-0.001), "", dec!(0), dec!(0)),
+        ]);
+        assert_eq!(txns[0].origin.lines, vec![3]);
+        Ok(())
+    }
+}
@@ -0,0 +1,132 @@
+use crate::error::Result;
+use crate::locale::CsvFormat;
+use crate::reader;
+use crate::timestamp::Timestamp;
+use crate::transaction::{Currency, Transaction, TransactionType};
+use csv::{ReaderBuilder, Trim};
+use rust_decimal::prelude::*;
+use serde::Deserialize;
+use std::io::Read;
+
+/// A row in the Celsius transactions export, e.g.
+/// `a1b2c3,"January 2, 2022 10:00 AM",Reward,BTC,0.0001,4.00,,,Yes`.
+#[derive(Debug, Deserialize, PartialEq)]
+struct CelsiusRow {
+    #[serde(rename = "Date and time")]
+    date: String,
+
+    #[serde(rename = "Transaction type")]
+    r#type: String,
+
+    #[serde(rename = "Coin type")]
+    coin: Currency,
+
+    #[serde(rename = "Coin amount")]
+    amount: String,
+
+    #[serde(rename = "USD Value")]
+    usd_value: String,
+
+    #[serde(rename = "Confirmed")]
+    confirmed: String,
+}
+
+/// Parses a date such as `January 2, 2022 10:00 AM` into `2022-01-02 10:00:00`.
+fn parse_date(field: &str) -> Option<String> {
+    Timestamp::parse(field).map(|t| t.to_string())
+}
+
+impl CelsiusRow {
+    /// Converts the row, valued at its USD value. The interest and rewards are income, and a
+    /// liquidation of collateral or a swap out is a disposal. Collateral that is only locked for
+    /// a loan stays in the holdings, and is skipped.
+    fn to_transaction(&self, format: &CsvFormat) -> Option<Transaction> {
+        let decimal = |field: &str| Decimal::from_str(&format.normalize_decimal(field.trim_start_matches('$'))).ok();
+        let amount = decimal(&self.amount)?;
+        let value = decimal(&self.usd_value).unwrap_or_default().abs();
+        let mut txn = Transaction::new();
+        txn.date = parse_date(&self.date)?;
+        txn.paid_currency = self.coin.clone();
+        txn.paid_amount = amount;
+        let r#type = self.r#type.to_lowercase();
+        txn.r#type = match r#type.as_str() {
+            "reward" | "interest" | "promo code reward" | "referred award" | "referrer award" | "bonus token" => TransactionType::Income,
+            "transfer" | "withdrawal" | "inbound transfer" | "outbound transfer" => {
+                return Some(Transaction{ r#type: TransactionType::Transfer, ..txn });
+            }
+            t if t.contains("liquidation") || t == "swap out" => TransactionType::Sell,
+            "swap in" => TransactionType::Buy,
+            _ => return None,
+        };
+        txn.paid_amount = match txn.r#type {
+            TransactionType::Sell => -amount.abs(),
+            _ => amount.abs(),
+        };
+        txn.exchanged_currency = "USD".to_string();
+        txn.exchanged_amount = if txn.paid_amount.is_sign_positive() { -value } else { value };
+        Some(txn)
+    }
+}
+
+/// Reads the Celsius transactions export from the reader into a `Vec<Transaction>` with the confirmed
+/// transactions in the target currency, valued in USD.
+/// The transactions are sorted by date, see `Transaction::cmp_by_date`.
+pub(crate) async fn read_transactions<R: Read>(rdr: R, format: &CsvFormat, currency: &Currency) -> Result<Vec<Transaction>> {
+    let mut rdr = ReaderBuilder::new()
+        .has_headers(true)
+        .delimiter(format.delimiter)
+        .trim(Trim::All)
+        .from_reader(rdr);
+
+    let mut txns: Vec<Transaction> =
+        reader::read_records::<CelsiusRow, _>(&mut rdr)?
+            .into_iter()
+            .filter(|(row, _)| row.coin.eq(currency) && row.confirmed.eq_ignore_ascii_case("Yes"))
+            .filter_map(|(row, origin)| row.to_transaction(format).map(|txn| Transaction{ origin, ..txn }))
+            .collect();
+    txns.sort_by(Transaction::cmp_by_date);
+
+    Ok(txns)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::reader::celsius::*;
+    use futures::executor::block_on;
+    use rust_decimal_macros::dec;
+    use std::error::Error;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn should_read_celsius_transactions() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let mut file = NamedTempFile::new()?;
+        writeln!(file, "Internal id, Date and time, Transaction type, Coin type, Coin amount, USD Value, Original Reward Coin, Reward Amount In Original Coin, Confirmed
+                        d4,\"June 13, 2022 2:00 AM\",Loan Principal Liquidation,BTC,-0.005,150.00,,,Yes
+                        c3,\"March 1, 2022 10:00 AM\",Collateral,BTC,-0.01,300.00,,,Yes
+                        b2,\"February 1, 2022 6:00 AM\",Reward,BTC,0.0001,4.00,BTC,0.0001,Yes
+                        a1,\"January 1, 2022 10:00 AM\",Transfer,BTC,0.012,450.00,,,Yes
+                        a0,\"January 1, 2022 9:00 AM\",Transfer,BTC,0.5,20000.00,,,No")?;
+
+        /*
+         * When
+         */
+        let txns = block_on(read_transactions(reader::open(file.path())?, &CsvFormat::default(), &"BTC".to_string()))?;
+
+        /*
+         * Then
+         */
+        let rows: Vec<_> = txns.iter()
+            .map(|t| (t.r#type.clone(), t.date.as_str(), t.paid_amount, t.exchanged_currency.as_str(), t.exchanged_amount))
+            .collect();
+        assert_eq!(rows, vec![
+            (TransactionType::Transfer, "2022-01-01 10:00:00", dec!(0.012), "", dec!(0)),
+            (TransactionType::Income, "2022-02-01 06:00:00", dec!(0.0001), "USD", dec!(-4.00)),
+            (TransactionType::Sell, "2022-06-13 02:00:00", dec!(-0.005), "USD", dec!(150.00)),
+        ]);
+        Ok(())
+    }
+}
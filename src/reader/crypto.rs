@@ -0,0 +1,252 @@
+use crate::error::Result;
+use crate::locale::CsvFormat;
+use crate::reader;
+use crate::timestamp::Timestamp;
+use crate::transaction::{Currency, Origin, Transaction, TransactionType};
+use crate::warnings::{self, Code, Warning};
+use csv::{ReaderBuilder, Trim};
+use rust_decimal::prelude::*;
+use serde::Deserialize;
+use std::io::Read;
+
+/// A row in the crypto statement, where a trade is a single `Buy` or `Sell` row, e.g.
+/// `BTC,Buy,0.001,SEK 300000.00,SEK 300.00,SEK 3.00,2023-01-02 10:00:00`.
+#[derive(Debug, Deserialize, PartialEq)]
+pub(crate) struct CryptoRow {
+    #[serde(rename = "Symbol")]
+    symbol: Currency,
+
+    #[serde(rename = "Type")]
+    r#type: CryptoType,
+
+    #[serde(rename = "Quantity")]
+    quantity: String,
+
+    #[serde(rename = "Price")]
+    price: String,
+
+    #[serde(rename = "Value")]
+    value: String,
+
+    #[serde(rename = "Fees")]
+    fees: String,
+
+    #[serde(rename = "Date")]
+    date: String,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+enum CryptoType {
+    Buy,
+    Sell,
+    Send,
+    Receive,
+
+    #[serde(other)]
+    Other,
+}
+
+/// Currency symbols that are used instead of currency codes in the crypto statement.
+const SYMBOLS: [(&str, &str); 4] = [("€", "EUR"), ("$", "USD"), ("£", "GBP"), ("kr", "SEK")];
+
+/// Parses a value such as `SEK 1 234,56`, `€1,234.56` or `1234.56` into its currency and amount.
+fn parse_money(field: &str, format: &CsvFormat) -> Option<(Option<Currency>, Decimal)> {
+    let number: String = field.chars().filter(|c| !c.is_alphabetic() && !"€$£".contains(*c)).collect();
+    let amount = Decimal::from_str(format.normalize_decimal(number.trim()).as_str()).ok()?;
+    let code: String = field.chars().filter(|c| c.is_ascii_uppercase()).collect();
+    let currency =
+        SYMBOLS.iter()
+            .find(|(symbol, _)| field.contains(symbol))
+            .map(|(_, code)| code.to_string())
+            .or(if code.len() == 3 { Some(code) } else { None });
+    Some((currency, amount))
+}
+
+impl CryptoRow {
+    /// Converts the row into a transaction. A `Buy` or `Sell` without the currency of its
+    /// `Value`, e.g. `BTC,Buy,0.001,300000.00,300.00,3.00,2023-01-02 10:00:00`, can't be valued,
+    /// so it is left out and reported.
+    fn to_transaction(&self, format: &CsvFormat, origin: &Origin) -> Option<Transaction> {
+        let (_, quantity) = parse_money(&self.quantity, format)?;
+        let value = parse_money(&self.value, format);
+        let fees = parse_money(&self.fees, format).map_or(Decimal::ZERO, |(_, fees)| fees.abs());
+        let mut txn = Transaction::new();
+        txn.paid_currency = self.symbol.clone();
+        txn.date = Timestamp::parse(&self.date)?.to_string();
+        match (&self.r#type, value) {
+            (CryptoType::Buy, Some((Some(currency), value))) => {
+                txn.r#type = TransactionType::Buy;
+                txn.paid_amount = quantity.abs();
+                txn.exchanged_currency = currency;
+                txn.exchanged_amount = -(value.abs() + fees);
+                txn.exchanged_fee = -fees;
+            }
+            (CryptoType::Sell, Some((Some(currency), value))) => {
+                txn.r#type = TransactionType::Sell;
+                txn.paid_amount = -quantity.abs();
+                txn.exchanged_currency = currency;
+                txn.exchanged_amount = value.abs() - fees;
+                txn.exchanged_fee = -fees;
+            }
+            (CryptoType::Send, _) => {
+                txn.r#type = TransactionType::Transfer;
+                txn.paid_amount = -quantity.abs();
+            }
+            (CryptoType::Receive, _) => {
+                txn.r#type = TransactionType::Transfer;
+                txn.paid_amount = quantity.abs();
+            }
+            (CryptoType::Buy | CryptoType::Sell, _) => {
+                let msg = format!("{:?} of {} {} without the currency of its Value {:?} is left out", self.r#type, quantity.abs(), self.symbol, self.value);
+                warnings::report(Warning::new(Code::MissingPrice, msg).at(origin).at_date(&txn.date));
+                return None;
+            }
+            _ => return None,
+        }
+        Some(txn)
+    }
+}
+
+/// Reads the crypto statement from the reader into a `Vec<Transaction>` in the target currency.
+/// The transactions are sorted by date, see `Transaction::cmp_by_date`.
+pub(crate) async fn read_transactions<R: Read>(rdr: R, format: &CsvFormat, currency: &Currency) -> Result<Vec<Transaction>> {
+    let mut rdr = ReaderBuilder::new()
+        .has_headers(true)
+        .delimiter(format.delimiter)
+        .trim(Trim::All)
+        .from_reader(rdr);
+
+    let mut txns: Vec<Transaction> =
+        reader::read_records::<CryptoRow, _>(&mut rdr)?
+            .into_iter()
+            .filter(|(row, _)| row.symbol.eq(currency))
+            .filter_map(|(row, origin)| row.to_transaction(format, &origin).map(|txn| Transaction{ origin, ..txn }))
+            .collect();
+    txns.sort_by(Transaction::cmp_by_date);
+
+    Ok(txns)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::reader::crypto::*;
+    use crate::transaction::Origin;
+    use futures::executor::block_on;
+    use rust_decimal_macros::dec;
+    use std::error::Error;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn should_read_crypto_statement() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let mut file = NamedTempFile::new()?;
+        writeln!(file, "Symbol,Type,Quantity,Price,Value,Fees,Date
+                        BTC,Sell,0.0005,SEK 400000.00,SEK 200.00,SEK 2.00,2023-02-01 10:00:00
+                        ETH,Buy,0.1,€1500.00,€150.00,€1.50,2023-01-15 10:00:00
+                        BTC,Send,0.0001,,,,2023-01-10 10:00:00
+                        BTC,Buy,0.001,SEK 300000.00,SEK 300.00,SEK 3.00,2023-01-02 10:00:00")?;
+        let format = CsvFormat::default();
+
+        /*
+         * When
+         */
+        let txns = block_on(read_transactions(reader::open(file.path())?, &format, &"BTC".to_string()))?;
+
+        /*
+         * Then
+         */
+        let mut iter = txns.into_iter();
+        assert_eq!(iter.next(), Some(Transaction{
+            r#type: TransactionType::Buy,
+            paid_currency: "BTC".to_string(),
+            paid_amount: dec!(0.001),
+            exchanged_currency: "SEK".to_string(),
+            exchanged_amount: dec!(-303.00),
+            date: "2023-01-02 10:00:00".to_string(),
+            is_vault: false,
+            paid_fee: dec!(0),
+            exchanged_fee: dec!(-3.00),
+            price_source: None,
+            unit_price: None,
+            fiat_value: None,
+            other_fee: None,
+            origin: Origin::new(5, "BTC,Buy,0.001,SEK 300000.00,SEK 300.00,SEK 3.00,2023-01-02 10:00:00".to_string())
+        }));
+        assert_eq!(iter.next(), Some(Transaction{
+            r#type: TransactionType::Transfer,
+            paid_currency: "BTC".to_string(),
+            paid_amount: dec!(-0.0001),
+            exchanged_currency: "".to_string(),
+            exchanged_amount: dec!(0),
+            date: "2023-01-10 10:00:00".to_string(),
+            is_vault: false,
+            paid_fee: dec!(0),
+            exchanged_fee: dec!(0),
+            price_source: None,
+            unit_price: None,
+            fiat_value: None,
+            other_fee: None,
+            origin: Origin::new(4, "BTC,Send,0.0001,,,,2023-01-10 10:00:00".to_string())
+        }));
+        assert_eq!(iter.next(), Some(Transaction{
+            r#type: TransactionType::Sell,
+            paid_currency: "BTC".to_string(),
+            paid_amount: dec!(-0.0005),
+            exchanged_currency: "SEK".to_string(),
+            exchanged_amount: dec!(198.00),
+            date: "2023-02-01 10:00:00".to_string(),
+            is_vault: false,
+            paid_fee: dec!(0),
+            exchanged_fee: dec!(-2.00),
+            price_source: None,
+            unit_price: None,
+            fiat_value: None,
+            other_fee: None,
+            origin: Origin::new(2, "BTC,Sell,0.0005,SEK 400000.00,SEK 200.00,SEK 2.00,2023-02-01 10:00:00".to_string())
+        }));
+        assert_eq!(iter.next(), None);
+        Ok(())
+    }
+
+    #[test]
+    fn should_report_trades_without_currency_of_value() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let mut file = NamedTempFile::new()?;
+        writeln!(file, "Symbol,Type,Quantity,Price,Value,Fees,Date
+                        BTC,Buy,0.001,300000.00,300.00,3.00,2023-01-02 10:00:00")?;
+        let format = CsvFormat::default();
+        warnings::take();
+
+        /*
+         * When
+         */
+        let txns = block_on(read_transactions(reader::open(file.path())?, &format, &"BTC".to_string()))?;
+        let warnings = warnings::take();
+
+        /*
+         * Then
+         */
+        assert!(txns.is_empty());
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].code, Code::MissingPrice);
+        assert_eq!(warnings[0].lines, vec![2]);
+        assert_eq!(warnings[0].message, "Buy of 0.001 BTC without the currency of its Value \"300.00\" is left out");
+        Ok(())
+    }
+
+    #[test]
+    fn should_parse_money() {
+        let format = CsvFormat{ delimiter: b';', decimal_separator: ',' };
+        assert_eq!(parse_money("SEK 1 234,56", &format), Some((Some("SEK".to_string()), dec!(1234.56))));
+        assert_eq!(parse_money("1 234,56 kr", &format), Some((Some("SEK".to_string()), dec!(1234.56))));
+        let format = CsvFormat::default();
+        assert_eq!(parse_money("€1,234.56", &format), Some((Some("EUR".to_string()), dec!(1234.56))));
+        assert_eq!(parse_money("-0.5", &format), Some((None, dec!(-0.5))));
+        assert_eq!(parse_money("", &format), None);
+    }
+}
@@ -0,0 +1,175 @@
+use crate::error::Result;
+use crate::locale::CsvFormat;
+use crate::reader;
+use crate::timestamp::Timestamp;
+use crate::transaction::{is_fiat, Currency, Transaction, TransactionType};
+use csv::{ReaderBuilder, Trim};
+use rust_decimal::prelude::*;
+use serde::Deserialize;
+use std::io::Read;
+
+/// A row in the Crypto.com App export "crypto_transactions_record", e.g.
+/// `2022-01-02 10:00:00,EUR -> BTC,EUR,-400.00,BTC,0.01,EUR,400.00,452.00,viban_purchase,`.
+#[derive(Debug, Deserialize, PartialEq)]
+struct RecordRow {
+    #[serde(rename = "Timestamp (UTC)")]
+    timestamp: String,
+
+    #[serde(rename = "Currency")]
+    currency: Currency,
+
+    #[serde(rename = "Amount")]
+    amount: String,
+
+    #[serde(rename = "To Currency")]
+    to_currency: Option<Currency>,
+
+    #[serde(rename = "To Amount")]
+    to_amount: Option<String>,
+
+    #[serde(rename = "Native Currency")]
+    native_currency: Currency,
+
+    #[serde(rename = "Native Amount")]
+    native_amount: String,      // The market value of the transaction, always positive
+
+    #[serde(rename = "Transaction Kind")]
+    kind: String,
+}
+
+/// How the kinds of transactions in the export map onto transactions.
+#[derive(Debug, PartialEq)]
+enum Kind {
+    /// An exchange from `Currency` to `To Currency`.
+    Exchange,
+    /// A buy or a sell of `Currency` for fiat currency outside the app, e.g. with a card, or a
+    /// sell of small amounts into CRO.
+    Trade,
+    /// A reward, interest or cashback received in `Currency`.
+    Income,
+    /// A deposit or withdrawal of `Currency`, a transfer between the app and the exchange, or
+    /// into or out of Crypto Earn.
+    Transfer,
+    /// Movements within the app such as locking CRO for a card, and fiat top-ups.
+    Ignored,
+}
+
+fn kind_of(kind: &str) -> Kind {
+    match kind {
+        "viban_purchase" | "crypto_viban_exchange" | "crypto_exchange" => Kind::Exchange,
+        "crypto_purchase" | "dust_conversion_debited" | "dust_conversion_credited" | "card_cashback_reverted" => Kind::Trade,
+        "referral_card_cashback" | "reimbursement" | "crypto_earn_interest_paid" | "crypto_earn_extra_interest_paid" | "mco_stake_reward"
+            | "referral_bonus" | "referral_gift" | "rewards_platform_deposit_credited"
+            | "supercharger_reward_to_app_credited" | "admin_wallet_credited" => Kind::Income,
+        "crypto_deposit" | "crypto_withdrawal" | "crypto_to_exchange_transfer" | "exchange_to_crypto_transfer"
+            | "crypto_earn_program_created" | "crypto_earn_program_withdrawn" => Kind::Transfer,
+        _ => Kind::Ignored,
+    }
+}
+
+impl RecordRow {
+    /// Converts the row from the side of the target currency. The other side of an exchange is
+    /// used if it is fiat, and otherwise the market value in the native currency, so that
+    /// crypto-to-crypto trades are valued without a price source. The export includes no fees.
+    fn to_transaction(&self, format: &CsvFormat, currency: &Currency) -> Option<Transaction> {
+        let decimal = |field: &str| Decimal::from_str(&format.normalize_decimal(field)).ok();
+        let native = (self.native_currency.clone(), decimal(&self.native_amount)?.abs());
+        let to = self.to_currency.clone().zip(self.to_amount.as_deref().and_then(decimal));
+        let from = (self.currency.clone(), decimal(&self.amount)?);
+        let (amount, other) = match (kind_of(&self.kind), to) {
+            (Kind::Exchange, Some(to)) if from.0.eq(currency) => (from.1, to),
+            (Kind::Exchange, Some(to)) if to.0.eq(currency) => (to.1, from),
+            (Kind::Trade | Kind::Income | Kind::Transfer, _) if from.0.eq(currency) => (from.1, native.clone()),
+            _ => return None,
+        };
+        let (other_currency, other_amount) = match is_fiat(&other.0) {
+            true => (other.0, other.1.abs()),
+            false => native,
+        };
+        let mut txn = Transaction::new();
+        txn.date = Timestamp::parse(&self.timestamp)?.to_string();
+        txn.paid_currency = currency.clone();
+        txn.paid_amount = amount;
+        txn.r#type = match kind_of(&self.kind) {
+            Kind::Transfer => return Some(Transaction{ r#type: TransactionType::Transfer, ..txn }),
+            Kind::Income if amount.is_sign_positive() => TransactionType::Income,
+            _ if amount.is_sign_positive() => TransactionType::Buy,
+            _ => TransactionType::Sell,
+        };
+        txn.exchanged_currency = other_currency;
+        txn.exchanged_amount = if amount.is_sign_positive() { -other_amount } else { other_amount };
+        Some(txn)
+    }
+}
+
+/// Reads the Crypto.com App export from the reader into a `Vec<Transaction>` in the target currency:
+/// the exchanges and trades as buys and sells, the rewards, interest and cashback as income, and
+/// the deposits, withdrawals and moves into and out of Crypto Earn as transfers.
+/// The transactions are sorted by date, see `Transaction::cmp_by_date`.
+pub(crate) async fn read_transactions<R: Read>(rdr: R, format: &CsvFormat, currency: &Currency) -> Result<Vec<Transaction>> {
+    let mut rdr = ReaderBuilder::new()
+        .has_headers(true)
+        .delimiter(format.delimiter)
+        .trim(Trim::All)
+        .from_reader(rdr);
+
+    let mut txns: Vec<Transaction> =
+        reader::read_records::<RecordRow, _>(&mut rdr)?
+            .into_iter()
+            .filter_map(|(row, origin)| row.to_transaction(format, currency).map(|txn| Transaction{ origin, ..txn }))
+            .collect();
+    txns.sort_by(Transaction::cmp_by_date);
+
+    Ok(txns)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::reader::cryptocom::*;
+    use futures::executor::block_on;
+    use rust_decimal_macros::dec;
+    use std::error::Error;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn should_read_crypto_com_app_export() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let mut file = NamedTempFile::new()?;
+        writeln!(file, "Timestamp (UTC),Transaction Description,Currency,Amount,To Currency,To Amount,Native Currency,Native Amount,Native Amount (in USD),Transaction Kind,Transaction Hash
+                        2022-04-15 09:00:00,Crypto Earn Withdrawal,BTC,0.005,,,EUR,180.00,200.00,crypto_earn_program_withdrawn,
+                        2022-04-08 09:00:00,Crypto Earn,BTC,0.00002,,,EUR,0.80,0.90,crypto_earn_interest_paid,
+                        2022-04-01 10:00:00,Crypto Earn Deposit,BTC,-0.005,,,EUR,180.00,200.00,crypto_earn_program_created,
+                        2022-04-01 09:00:00,Withdraw BTC,BTC,-0.001,,,EUR,45.00,50.00,crypto_withdrawal,0xabc
+                        2022-03-01 12:00:00,CRO Lockup,CRO,-5000,,,EUR,2000.00,2200.00,lockup_lock,
+                        2022-02-15 08:00:00,Card Cashback,BTC,0.0001,,,EUR,4.00,4.50,referral_card_cashback,
+                        2022-02-01 10:00:00,BTC -> ETH,BTC,-0.005,ETH,0.07,EUR,200.00,225.00,crypto_exchange,
+                        2022-01-15 10:00:00,BTC -> EUR,BTC,-0.001,EUR,39.50,EUR,39.50,44.50,crypto_viban_exchange,
+                        2022-01-02 10:00:00,EUR -> BTC,EUR,-400.00,BTC,0.01,EUR,400.00,452.00,viban_purchase,")?;
+
+        /*
+         * When
+         */
+        let txns = block_on(read_transactions(reader::open(file.path())?, &CsvFormat::default(), &"BTC".to_string()))?;
+
+        /*
+         * Then
+         */
+        let rows: Vec<_> = txns.iter()
+            .map(|t| (t.r#type.clone(), t.date.as_str(), t.paid_amount, t.exchanged_currency.as_str(), t.exchanged_amount))
+            .collect();
+        assert_eq!(rows, vec![
+            (TransactionType::Buy, "2022-01-02 10:00:00", dec!(0.01), "EUR", dec!(-400.00)),
+            (TransactionType::Sell, "2022-01-15 10:00:00", dec!(-0.001), "EUR", dec!(39.50)),
+            (TransactionType::Sell, "2022-02-01 10:00:00", dec!(-0.005), "EUR", dec!(200.00)),
+            (TransactionType::Income, "2022-02-15 08:00:00", dec!(0.0001), "EUR", dec!(-4.00)),
+            (TransactionType::Transfer, "2022-04-01 09:00:00", dec!(-0.001), "", dec!(0)),
+            (TransactionType::Transfer, "2022-04-01 10:00:00", dec!(-0.005), "", dec!(0)),
+            (TransactionType::Income, "2022-04-08 09:00:00", dec!(0.00002), "EUR", dec!(-0.80)),
+            (TransactionType::Transfer, "2022-04-15 09:00:00", dec!(0.005), "", dec!(0)),
+        ]);
+        Ok(())
+    }
+}
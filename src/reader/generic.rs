@@ -0,0 +1,259 @@
+use crate::error::{self, CryptotaxError, Result};
+use crate::locale::CsvFormat;
+use crate::reader;
+use crate::timestamp::Timestamp;
+use crate::transaction::{Currency, Origin, Transaction, TransactionType};
+use csv::{ReaderBuilder, StringRecord, Trim};
+use rust_decimal::prelude::*;
+use serde::de::{value, IntoDeserializer};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::io::Read;
+use std::path::PathBuf;
+use tracing::debug;
+
+/// Tells which columns of the csv export of an exchange that is not supported natively hold what,
+/// e.g.
+///
+/// ```toml
+/// date_format = "%d/%m/%Y %H:%M"
+///
+/// [columns]
+/// date = "Time"
+/// type = "Side"
+/// currency = "Coin"
+/// amount = "Amount"
+/// quote_currency = "Quote"
+/// quote_amount = "Total"
+/// fee = "Fee"
+/// fee_currency = "Fee Coin"
+///
+/// [types]
+/// BUY = "Buy"
+/// SELL = "Sell"
+/// DEPOSIT = "Transfer"
+/// WITHDRAW = "Transfer"
+/// ```
+///
+/// Without `date_format`, the dates must start with `2022-01-31 10:00:00` or
+/// `2022-01-31T10:00:00`.
+#[derive(Debug, Deserialize, PartialEq)]
+pub(crate) struct Mapping {
+    columns: Columns,
+
+    date_format: Option<String>,
+
+    /// The values of the type column, and the type of their transactions. Values that are not
+    /// listed are read as the type of the same name, e.g. `Buy`, and otherwise skipped.
+    #[serde(default)]
+    types: HashMap<String, TransactionType>,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+struct Columns {
+    date: String,
+
+    #[serde(rename = "type")]
+    r#type: String,
+
+    /// The traded currency.
+    currency: String,
+
+    amount: String,
+
+    /// The currency that the traded currency was bought with or sold for.
+    quote_currency: Option<String>,
+
+    quote_amount: Option<String>,
+
+    fee: Option<String>,
+
+    /// Without this column, the fee is in the quote currency.
+    fee_currency: Option<String>,
+}
+
+/// Reads the mapping file from path.
+pub(crate) fn read_mapping(path: &PathBuf) -> Result<Mapping> {
+    toml::from_str(&fs::read_to_string(path)?)
+        .map_err(|e| error::invalid(path, format!("Invalid mapping file: {}", e)))
+}
+
+/// The positions of the mapped columns in the header of a file.
+struct Indices {
+    date: usize,
+    r#type: usize,
+    currency: usize,
+    amount: usize,
+    quote_currency: Option<usize>,
+    quote_amount: Option<usize>,
+    fee: Option<usize>,
+    fee_currency: Option<usize>,
+}
+
+impl Mapping {
+    fn indices(&self, headers: &StringRecord) -> Result<Indices> {
+        let find = |column: &String| {
+            headers.iter().position(|h| h.eq(column)).ok_or_else(|| {
+                let message = format!("Mapped column `{}` is not in the file", column);
+                CryptotaxError::Parse{ file: "".to_string(), line: Some(1), column: None, message }
+            })
+        };
+        let find_optional = |column: &Option<String>| column.as_ref().map(find).transpose();
+        Ok(Indices{
+            date: find(&self.columns.date)?,
+            r#type: find(&self.columns.r#type)?,
+            currency: find(&self.columns.currency)?,
+            amount: find(&self.columns.amount)?,
+            quote_currency: find_optional(&self.columns.quote_currency)?,
+            quote_amount: find_optional(&self.columns.quote_amount)?,
+            fee: find_optional(&self.columns.fee)?,
+            fee_currency: find_optional(&self.columns.fee_currency)?,
+        })
+    }
+
+    fn type_of(&self, value: &str) -> Option<TransactionType> {
+        self.types.get(value).cloned()
+            .or_else(|| TransactionType::deserialize(value.into_deserializer()).map_err(|_: value::Error| ()).ok())
+    }
+
+    fn date_of(&self, value: &str) -> Option<String> {
+        match &self.date_format {
+            Some(format) => Timestamp::parse_with(value, &[format.as_str()]),
+            None => Timestamp::parse(value),
+        }.map(|t| t.to_string())
+    }
+
+    fn to_transaction(&self, record: &StringRecord, i: &Indices, format: &CsvFormat) -> Option<Transaction> {
+        let field = |i: usize| record.get(i).unwrap_or_default();
+        let decimal = |i: usize| Decimal::from_str(&format.normalize_decimal(field(i))).ok();
+        let amount = decimal(i.amount)?;
+        let quote = i.quote_amount.and_then(decimal).unwrap_or_default().abs();
+        let fee = i.fee.and_then(decimal).unwrap_or_default().abs();
+        let mut txn = Transaction::new();
+        txn.r#type = self.type_of(field(i.r#type))?;
+        txn.paid_currency = field(i.currency).to_string();
+        txn.date = self.date_of(field(i.date))?;
+        if let Some(quote_currency) = i.quote_currency.map(field).filter(|c| !c.is_empty()) {
+            txn.exchanged_currency = quote_currency.to_string();
+        }
+        let fee_currency = i.fee_currency.map(field).unwrap_or(&txn.exchanged_currency).to_string();
+        let (paid_fee, exchanged_fee) = match fee_currency {
+            c if c == txn.paid_currency => (fee, Decimal::ZERO),
+            c if c == txn.exchanged_currency => (Decimal::ZERO, fee),
+            c => {
+                debug!(date = %txn.date, currency = %c, %fee, "Leaving out fee in another currency");
+                (Decimal::ZERO, Decimal::ZERO)
+            }
+        };
+        match txn.r#type {
+            TransactionType::Buy => {
+                txn.paid_amount = amount.abs() - paid_fee;
+                txn.exchanged_amount = -(quote + exchanged_fee);
+            }
+            TransactionType::Sell => {
+                txn.paid_amount = -(amount.abs() + paid_fee);
+                txn.exchanged_amount = quote - exchanged_fee;
+            }
+            _ => {
+                txn.paid_amount = amount - paid_fee;
+                txn.exchanged_currency = "".to_string();
+            }
+        }
+        txn.paid_fee = -paid_fee;
+        txn.exchanged_fee = -exchanged_fee;
+        Some(txn)
+    }
+}
+
+/// Reads the csv file from the reader into a `Vec<Transaction>` in the target currency, with the
+/// columns given by the mapping. Buys and sells are read by the absolute values of their amounts,
+/// other types by their signed amounts. Rows of an unknown type are skipped, and a row that can't
+/// be read fails with a `Parse` error.
+/// The transactions are sorted by date, see `Transaction::cmp_by_date`.
+pub(crate) async fn read_transactions<R: Read>(rdr: R, format: &CsvFormat, mapping: &Mapping, currency: &Currency) -> Result<Vec<Transaction>> {
+    let mut rdr = ReaderBuilder::new()
+        .has_headers(true)
+        .delimiter(format.delimiter)
+        .trim(Trim::All)
+        .from_reader(rdr);
+
+    let indices = mapping.indices(rdr.headers()?)?;
+    let records = rdr.records().collect::<Result<Vec<StringRecord>, csv::Error>>().map_err(error::row)?;
+    let mut txns: Vec<Transaction> =
+        records.into_iter()
+            .filter(|record| record.get(indices.currency) == Some(currency.as_str()))
+            .filter_map(|record| {
+                let txn = mapping.to_transaction(&record, &indices, format);
+                if txn.is_none() {
+                    debug!(line = reader::line_of(&record), "Skipping unmapped row");
+                }
+                let raw = record.iter().collect::<Vec<&str>>().join(",");
+                txn.map(|txn| Transaction{ origin: Origin::new(reader::line_of(&record), raw), ..txn })
+            })
+            .collect();
+    txns.sort_by(Transaction::cmp_by_date);
+
+    Ok(txns)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::reader::generic::*;
+    use futures::executor::block_on;
+    use rust_decimal_macros::dec;
+    use std::error::Error;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn should_read_mapped_columns() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let mapping: Mapping = toml::from_str(r#"
+            date_format = "%d/%m/%Y %H:%M"
+
+            [columns]
+            date = "Time"
+            type = "Side"
+            currency = "Coin"
+            amount = "Amount"
+            quote_currency = "Quote"
+            quote_amount = "Total"
+            fee = "Fee"
+            fee_currency = "Fee Coin"
+
+            [types]
+            BUY = "Buy"
+            SELL = "Sell"
+            WITHDRAW = "Transfer"
+        "#)?;
+        let mut file = NamedTempFile::new()?;
+        writeln!(file, "Time,Side,Coin,Amount,Quote,Total,Fee,Fee Coin
+                        01/02/2022 10:00,BUY,BTC,0.01,EUR,400,1,EUR
+                        01/03/2022 11:30,SELL,BTC,0.005,EUR,250,0.0001,BTC
+                        01/04/2022 12:00,WITHDRAW,BTC,-0.002,,,,
+                        01/04/2022 12:00,BUY,ETH,1,EUR,3000,3,EUR
+                        01/05/2022 12:00,STAKE,BTC,0.001,,,,")?;
+
+        /*
+         * When
+         */
+        let txns = block_on(read_transactions(reader::open(file.path())?, &CsvFormat::default(), &mapping, &"BTC".to_string()))?;
+
+        /*
+         * Then
+         */
+        let rows: Vec<_> = txns.iter()
+            .map(|t| (t.r#type.clone(), t.date.as_str(), t.paid_amount, t.paid_fee, t.exchanged_currency.as_str(), t.exchanged_amount, t.exchanged_fee))
+            .collect();
+        assert_eq!(rows, vec![
+            (TransactionType::Buy, "2022-02-01 10:00:00", dec!(0.01), dec!(0), "EUR", dec!(-401), dec!(-1)),
+            (TransactionType::Sell, "2022-03-01 11:30:00", dec!(-0.0051), dec!(-0.0001), "EUR", dec!(250), dec!(0)),
+            (TransactionType::Transfer, "2022-04-01 12:00:00", dec!(-0.002), dec!(0), "", dec!(0), dec!(0)),
+        ]);
+        assert_eq!(txns[0].origin.lines, vec![2]);
+        Ok(())
+    }
+}
@@ -0,0 +1,202 @@
+use crate::error::Result;
+use crate::legs::{self, Leg};
+use crate::locale::CsvFormat;
+use crate::reader;
+use crate::timestamp::Timestamp;
+use crate::transaction::{Currency, Origin, Transaction, TransactionType};
+use csv::{ReaderBuilder, Trim};
+use rust_decimal::prelude::*;
+use serde::Deserialize;
+use std::io::Read;
+
+/// A row in the Kraken ledger, e.g.
+/// `"L1","T1","2022-01-01 10:00:00","margin","","currency","ZEUR",12.5000,0.0200,1012.48`.
+#[derive(Debug, Deserialize, PartialEq)]
+struct LedgerRow {
+    #[serde(rename = "refid", default)]
+    refid: String,
+
+    #[serde(rename = "time")]
+    time: String,
+
+    #[serde(rename = "type")]
+    r#type: String,
+
+    #[serde(rename = "subtype", default)]
+    subtype: String,
+
+    #[serde(rename = "asset")]
+    asset: String,
+
+    #[serde(rename = "amount")]
+    amount: String,
+
+    #[serde(rename = "fee")]
+    fee: String,
+}
+
+/// Ledger types that are gains or losses of margin positions: the profit or loss when a position
+/// is closed or settled, and the rollover fees while it is open.
+const MARGIN_TYPES: [&str; 3] = ["margin", "rollover", "settled"];
+
+/// Ledger types of the legs of a spot trade, a row of each currency with the same `refid`, each
+/// with its fee in its own currency, see `legs`.
+const TRADE_TYPES: [&str; 3] = ["trade", "spend", "receive"];
+
+/// Ledger types of the rewards of staking and Kraken Earn, which are income. The other rows of
+/// Kraken Earn, e.g. of subtype `allocation` or `deallocation`, and the transfers between the
+/// spot and the staking wallets only move the currency within the account, so they are left out.
+const EARN_TYPES: [(&str, &str); 2] = [("staking", ""), ("earn", "reward")];
+
+/// Kraken's own asset codes, and the usual codes for them.
+const ASSETS: [(&str, &str); 4] = [("XXBT", "BTC"), ("XBT", "BTC"), ("XXDG", "DOGE"), ("XDG", "DOGE")];
+
+/// Converts a Kraken asset code such as `ZEUR` or `XXBT` into `EUR` or `BTC`. The suffix of a
+/// staked or an opted-in asset, e.g. `DOT.S` or `XBT.M`, is dropped, as it is the same currency.
+pub(crate) fn normalize_asset(asset: &str) -> Currency {
+    let asset = asset.split_once('.').map_or(asset, |(asset, _)| asset);
+    match ASSETS.iter().find(|(kraken, _)| kraken.eq(&asset)) {
+        Some((_, code)) => code.to_string(),
+        None if asset.len() == 4 && (asset.starts_with('X') || asset.starts_with('Z')) => asset[1..].to_string(),
+        None => asset.to_string(),
+    }
+}
+
+impl LedgerRow {
+    fn is_earn(&self) -> bool {
+        EARN_TYPES.iter().any(|(r#type, subtype)| self.r#type.eq(r#type) && (subtype.is_empty() || self.subtype.eq(subtype)))
+    }
+
+    /// The leg of a trade, and the leg of its fee if it has one, keyed by the `refid` of the trade.
+    fn to_legs(&self, format: &CsvFormat, origin: Origin) -> Vec<(String, Leg)> {
+        let decimal = |field: &str| Decimal::from_str(&format.normalize_decimal(field)).ok();
+        let (Some(date), Some(amount)) = (Timestamp::parse(&self.time), decimal(&self.amount)) else { return vec![] };
+        let leg = |amount, is_fee| (self.refid.clone(), Leg{ date: date.to_string(), currency: normalize_asset(&self.asset), amount, is_fee, origin: origin.clone() });
+        match decimal(&self.fee).filter(|fee| !fee.is_zero()) {
+            Some(fee) => vec![leg(amount, false), leg(-fee, true)],
+            None => vec![leg(amount, false)],
+        }
+    }
+
+    /// Converts a margin row, or a reward of staking or Kraken Earn as income, which is valued at
+    /// the market price with `--prices`.
+    fn to_transaction(&self, format: &CsvFormat) -> Option<Transaction> {
+        let amount = Decimal::from_str(&format.normalize_decimal(&self.amount)).ok()?;
+        let fee = Decimal::from_str(&format.normalize_decimal(&self.fee)).unwrap_or_default();
+        let mut txn = Transaction::new();
+        txn.paid_currency = normalize_asset(&self.asset);
+        txn.paid_amount = amount - fee;
+        txn.paid_fee = -fee;
+        txn.date = Timestamp::parse(&self.time)?.to_string();
+        if self.is_earn() {
+            txn.r#type = TransactionType::Income;
+            return Some(txn);
+        }
+        txn.r#type = TransactionType::Margin;
+        txn.exchanged_currency = txn.paid_currency.clone();
+        txn.exchanged_amount = txn.paid_amount;
+        Some(txn)
+    }
+}
+
+/// Reads the Kraken ledger from the reader into a `Vec<Transaction>` with the profits and losses of
+/// margin positions in the target currency, the rewards of staking and Kraken Earn, and the spot
+/// trades of the legs of the same `refid`, see `legs::to_transaction`.
+/// The transactions are sorted by date, see `Transaction::cmp_by_date`.
+pub(crate) async fn read_transactions<R: Read>(rdr: R, format: &CsvFormat, currency: &Currency) -> Result<Vec<Transaction>> {
+    let mut rdr = ReaderBuilder::new()
+        .has_headers(true)
+        .delimiter(format.delimiter)
+        .trim(Trim::All)
+        .from_reader(rdr);
+
+    let (legs, rows): (Vec<_>, Vec<_>) =
+        reader::read_records::<LedgerRow, _>(&mut rdr)?
+            .into_iter()
+            .partition(|(row, _)| TRADE_TYPES.contains(&row.r#type.as_str()));
+    let events = legs::group(legs.into_iter().flat_map(|(row, origin)| row.to_legs(format, origin)));
+    let mut txns: Vec<Transaction> =
+        rows.into_iter()
+            .filter(|(row, _)| MARGIN_TYPES.contains(&row.r#type.as_str()) || row.is_earn())
+            .filter(|(row, _)| normalize_asset(&row.asset).eq(currency))
+            .filter_map(|(row, origin)| row.to_transaction(format).map(|txn| Transaction{ origin, ..txn }))
+            .chain(events.iter().filter_map(|event| legs::to_transaction(event, currency)))
+            .collect();
+    txns.sort_by(Transaction::cmp_by_date);
+
+    Ok(txns)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::reader::kraken::*;
+    use futures::executor::block_on;
+    use rust_decimal_macros::dec;
+    use std::error::Error;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn should_read_margin_pnl() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let mut file = NamedTempFile::new()?;
+        writeln!(file, "\"txid\",\"refid\",\"time\",\"type\",\"subtype\",\"aclass\",\"asset\",\"amount\",\"fee\",\"balance\"
+                        \"L1\",\"T1\",\"2022-01-01 10:00:00.1234\",\"trade\",\"\",\"currency\",\"XXBT\",0.0100000000,0.0000000000,0.0100000000
+                        \"L2\",\"T2\",\"2022-01-02 10:00:00.1234\",\"rollover\",\"\",\"currency\",\"ZEUR\",0.0000,0.0200,999.98
+                        \"L3\",\"T3\",\"2022-01-03 10:00:00.1234\",\"margin\",\"\",\"currency\",\"ZEUR\",12.5000,0.0300,1012.45
+                        \"L4\",\"T4\",\"2022-01-04 10:00:00.1234\",\"earn\",\"allocation\",\"currency\",\"EUR.M\",100.0000,0.0000,100.0000
+                        \"L5\",\"T5\",\"2022-01-05 10:00:00.1234\",\"earn\",\"reward\",\"currency\",\"EUR.M\",0.0500,0.0000,100.0500")?;
+
+        /*
+         * When
+         */
+        let txns = block_on(read_transactions(reader::open(file.path())?, &CsvFormat::default(), &"EUR".to_string()))?;
+
+        /*
+         * Then
+         */
+        let amounts: Vec<(&str, TransactionType, Decimal)> = txns.iter().map(|t| (t.date.as_str(), t.r#type.clone(), t.paid_amount)).collect();
+        assert_eq!(amounts, vec![
+            ("2022-01-02 10:00:00", TransactionType::Margin, dec!(-0.02)),
+            ("2022-01-03 10:00:00", TransactionType::Margin, dec!(12.47)),
+            ("2022-01-05 10:00:00", TransactionType::Income, dec!(0.05)),
+        ]);
+        assert_eq!(normalize_asset("XXBT"), "BTC");
+        assert_eq!(normalize_asset("ZEUR"), "EUR");
+        assert_eq!(normalize_asset("DOT"), "DOT");
+        assert_eq!(normalize_asset("DOT.S"), "DOT");
+        Ok(())
+    }
+
+    #[test]
+    fn should_read_trades_of_the_same_refid() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let mut file = NamedTempFile::new()?;
+        writeln!(file, "\"txid\",\"refid\",\"time\",\"type\",\"subtype\",\"aclass\",\"asset\",\"amount\",\"fee\",\"balance\"
+                        \"L1\",\"T1\",\"2022-01-01 10:00:00.1234\",\"trade\",\"\",\"currency\",\"ZEUR\",-400.0000,0.6400,599.36
+                        \"L2\",\"T1\",\"2022-01-01 10:00:00.1234\",\"trade\",\"\",\"currency\",\"XXBT\",0.0100000000,0.0000000000,0.0100000000
+                        \"L3\",\"T2\",\"2022-02-01 10:00:00.1234\",\"trade\",\"\",\"currency\",\"XXBT\",-0.0050000000,0.0000000000,0.0050000000
+                        \"L4\",\"T2\",\"2022-02-01 10:00:00.1234\",\"trade\",\"\",\"currency\",\"ZEUR\",250.0000,0.4000,848.96")?;
+
+        /*
+         * When
+         */
+        let txns = block_on(read_transactions(reader::open(file.path())?, &CsvFormat::default(), &"BTC".to_string()))?;
+
+        /*
+         * Then
+         */
+        let rows: Vec<_> = txns.iter()
+            .map(|t| (t.r#type.clone(), t.paid_amount, t.exchanged_currency.as_str(), t.exchanged_amount, t.exchanged_fee))
+            .collect();
+        assert_eq!(rows, vec![
+            (TransactionType::Buy, dec!(0.01), "EUR", dec!(-400.64), dec!(-0.64)),
+            (TransactionType::Sell, dec!(-0.005), "EUR", dec!(249.6), dec!(-0.4)),
+        ]);
+        Ok(())
+    }
+}
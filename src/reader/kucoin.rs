@@ -0,0 +1,137 @@
+use crate::error::Result;
+use crate::locale::CsvFormat;
+use crate::reader;
+use crate::timestamp::Timestamp;
+use crate::transaction::{Currency, Transaction, TransactionType};
+use csv::{ReaderBuilder, Trim};
+use rust_decimal::prelude::*;
+use serde::Deserialize;
+use std::io::Read;
+
+/// A row in the KuCoin trade history, e.g.
+/// `2022-01-02 10:00:00,61d17b1f,BTC-USDT,buy,40000,0.01,400,0.4,taker,USDT,limit`.
+#[derive(Debug, Deserialize, PartialEq)]
+struct TradeRow {
+    #[serde(rename = "tradeCreatedAt")]
+    trade_created_at: String,
+
+    #[serde(rename = "symbol")]
+    symbol: String,             // e.g. "BTC-USDT"
+
+    #[serde(rename = "side")]
+    side: String,
+
+    #[serde(rename = "size")]
+    size: String,               // In the base currency of the symbol
+
+    #[serde(rename = "funds")]
+    funds: String,              // In the quote currency, without the fee
+
+    #[serde(rename = "fee")]
+    fee: String,
+
+    #[serde(rename = "feeCurrency")]
+    fee_currency: Currency,
+}
+
+impl TradeRow {
+    fn pair(&self) -> Option<(Currency, Currency)> {
+        let (base, quote) = self.symbol.split_once('-')?;
+        Some((base.to_string(), quote.to_string()))
+    }
+
+    /// Converts the trade from the side of the target currency, which can be the base or the
+    /// quote currency of the symbol: selling BTC-USDT is buying USDT.
+    fn to_transaction(&self, format: &CsvFormat, currency: &Currency) -> Option<Transaction> {
+        let decimal = |field: &str| Decimal::from_str(&format.normalize_decimal(field)).ok();
+        let (base, quote) = self.pair()?;
+        let (size, funds) = (decimal(&self.size)?.abs(), decimal(&self.funds)?.abs());
+        let fee = decimal(&self.fee).unwrap_or_default().abs();
+        // Signed from the side of the base currency: bought size for funds, or sold size for funds.
+        let (base_amount, quote_amount) = match self.side.as_str() {
+            "buy" => (size, -funds),
+            "sell" => (-size, funds),
+            _ => return None,
+        };
+        let mut txn = Transaction::new();
+        txn.date = Timestamp::parse(&self.trade_created_at)?.to_string();
+        let (amount, other, other_amount) = match currency {
+            c if c.eq(&base) => (base_amount, quote, quote_amount),
+            c if c.eq(&quote) => (quote_amount, base, base_amount),
+            _ => return None,
+        };
+        txn.r#type = if amount.is_sign_positive() { TransactionType::Buy } else { TransactionType::Sell };
+        txn.paid_currency = currency.clone();
+        txn.paid_amount = amount;
+        txn.exchanged_currency = other;
+        txn.exchanged_amount = other_amount;
+        if self.fee_currency.eq(currency) {
+            txn.paid_amount -= fee;
+            txn.paid_fee = -fee;
+        } else if self.fee_currency.eq(&txn.exchanged_currency) {
+            txn.exchanged_amount -= fee;
+            txn.exchanged_fee = -fee;
+        }
+        Some(txn)
+    }
+}
+
+/// Reads the KuCoin trade history from the reader into a `Vec<Transaction>` with the trades of the
+/// target currency.
+/// The transactions are sorted by date, see `Transaction::cmp_by_date`.
+pub(crate) async fn read_transactions<R: Read>(rdr: R, format: &CsvFormat, currency: &Currency) -> Result<Vec<Transaction>> {
+    let mut rdr = ReaderBuilder::new()
+        .has_headers(true)
+        .delimiter(format.delimiter)
+        .trim(Trim::All)
+        .from_reader(rdr);
+
+    let mut txns: Vec<Transaction> =
+        reader::read_records::<TradeRow, _>(&mut rdr)?
+            .into_iter()
+            .filter_map(|(row, origin)| row.to_transaction(format, currency).map(|txn| Transaction{ origin, ..txn }))
+            .collect();
+    txns.sort_by(Transaction::cmp_by_date);
+
+    Ok(txns)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::reader::kucoin::*;
+    use futures::executor::block_on;
+    use rust_decimal_macros::dec;
+    use std::error::Error;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn should_read_kucoin_trade_history() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let mut file = NamedTempFile::new()?;
+        writeln!(file, "tradeCreatedAt,orderId,symbol,side,price,size,funds,fee,liquidity,feeCurrency,orderType
+                        2022-02-01 10:00:00,61f9041a,BTC-USDT,sell,45000,0.005,225,0.225,taker,USDT,market
+                        2022-01-15 10:00:00,61e29c1b,ETH-BTC,buy,0.075,1,0.075,0.000075,maker,BTC,limit
+                        2022-01-02 10:00:00,61d17b1f,BTC-USDT,buy,40000,0.01,400,0.4,taker,USDT,limit")?;
+
+        /*
+         * When
+         */
+        let txns = block_on(read_transactions(reader::open(file.path())?, &CsvFormat::default(), &"BTC".to_string()))?;
+
+        /*
+         * Then
+         */
+        let rows: Vec<_> = txns.iter()
+            .map(|t| (t.r#type.clone(), t.date.as_str(), t.paid_amount, t.paid_fee, t.exchanged_currency.as_str(), t.exchanged_amount, t.exchanged_fee))
+            .collect();
+        assert_eq!(rows, vec![
+            (TransactionType::Buy, "2022-01-02 10:00:00", dec!(0.01), dec!(0), "USDT", dec!(-400.4), dec!(-0.4)),
+            (TransactionType::Sell, "2022-01-15 10:00:00", dec!(-0.075075), dec!(-0.000075), "ETH", dec!(1), dec!(0)),
+            (TransactionType::Sell, "2022-02-01 10:00:00", dec!(-0.005), dec!(0), "USDT", dec!(224.775), dec!(-0.225)),
+        ]);
+        Ok(())
+    }
+}
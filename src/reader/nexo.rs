@@ -0,0 +1,143 @@
+use crate::error::Result;
+use crate::locale::CsvFormat;
+use crate::reader;
+use crate::timestamp::Timestamp;
+use crate::transaction::{Currency, Transaction, TransactionType};
+use csv::{ReaderBuilder, Trim};
+use rust_decimal::prelude::*;
+use serde::Deserialize;
+use std::io::Read;
+
+/// A row in the Nexo transactions export, e.g.
+/// `NXT1,Interest,BTC,0.0001,BTC,0.0001,$4.00,approved / BTC Interest Earned,2022-01-02 10:00:00`.
+#[derive(Debug, Deserialize, PartialEq)]
+struct NexoRow {
+    #[serde(rename = "Type")]
+    r#type: String,
+
+    #[serde(rename = "Input Currency")]
+    input_currency: Currency,
+
+    #[serde(rename = "Input Amount")]
+    input_amount: String,
+
+    #[serde(rename = "Output Currency")]
+    output_currency: Currency,
+
+    #[serde(rename = "Output Amount")]
+    output_amount: String,
+
+    #[serde(rename = "USD Equivalent")]
+    usd_equivalent: String,     // e.g. "$4.00"
+
+    #[serde(rename = "Date / Time (UTC)")]
+    date: String,
+}
+
+/// Nexo types of interest and rewards.
+const INCOME_TYPES: [&str; 7] = [
+    "Interest", "Fixed Term Interest", "Dividend", "Cashback", "Exchange Cashback", "Referral Bonus", "Bonus",
+];
+
+/// Nexo types of crypto moved in or out of the account.
+const TRANSFER_TYPES: [&str; 5] = ["Deposit", "Withdrawal", "Top up Crypto", "Transfer In", "Transfer Out"];
+
+impl NexoRow {
+    /// Converts the row from the side of the target currency, valued at its USD equivalent. A
+    /// liquidation sells collateral to repay a loan, so it is a disposal.
+    fn to_transaction(&self, format: &CsvFormat, currency: &Currency) -> Option<Transaction> {
+        let decimal = |field: &str| Decimal::from_str(&format.normalize_decimal(field.trim_start_matches('$'))).ok();
+        let value = decimal(&self.usd_equivalent).unwrap_or_default().abs();
+        let (input, output) = (decimal(&self.input_amount)?, decimal(&self.output_amount)?);
+        let mut txn = Transaction::new();
+        txn.date = Timestamp::parse(&self.date)?.to_string();
+        txn.paid_currency = currency.clone();
+        let r#type = self.r#type.as_str();
+        txn.paid_amount = match r#type {
+            _ if INCOME_TYPES.contains(&r#type) && self.output_currency.eq(currency) => {
+                txn.r#type = TransactionType::Income;
+                output.abs()
+            }
+            _ if TRANSFER_TYPES.contains(&r#type) && self.output_currency.eq(currency) => {
+                txn.r#type = TransactionType::Transfer;
+                return Some(Transaction{ paid_amount: output, ..txn });
+            }
+            "Exchange" | "Liquidation" | "Manual Sell Order" if self.input_currency.eq(currency) => {
+                txn.r#type = TransactionType::Sell;
+                -input.abs()
+            }
+            "Exchange" if self.output_currency.eq(currency) => {
+                txn.r#type = TransactionType::Buy;
+                output.abs()
+            }
+            _ => return None,
+        };
+        txn.exchanged_currency = "USD".to_string();
+        txn.exchanged_amount = if txn.paid_amount.is_sign_positive() { -value } else { value };
+        Some(txn)
+    }
+}
+
+/// Reads the Nexo transactions export from the reader into a `Vec<Transaction>` in the target currency:
+/// the exchanges and liquidations of collateral as buys and sells, the interest and rewards as
+/// income, and the deposits and withdrawals as transfers, all valued in USD.
+/// The transactions are sorted by date, see `Transaction::cmp_by_date`.
+pub(crate) async fn read_transactions<R: Read>(rdr: R, format: &CsvFormat, currency: &Currency) -> Result<Vec<Transaction>> {
+    let mut rdr = ReaderBuilder::new()
+        .has_headers(true)
+        .delimiter(format.delimiter)
+        .trim(Trim::All)
+        .from_reader(rdr);
+
+    let mut txns: Vec<Transaction> =
+        reader::read_records::<NexoRow, _>(&mut rdr)?
+            .into_iter()
+            .filter_map(|(row, origin)| row.to_transaction(format, currency).map(|txn| Transaction{ origin, ..txn }))
+            .collect();
+    txns.sort_by(Transaction::cmp_by_date);
+
+    Ok(txns)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::reader::nexo::*;
+    use futures::executor::block_on;
+    use rust_decimal_macros::dec;
+    use std::error::Error;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn should_read_nexo_transactions() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let mut file = NamedTempFile::new()?;
+        writeln!(file, "Transaction,Type,Input Currency,Input Amount,Output Currency,Output Amount,USD Equivalent,Details,Date / Time (UTC)
+                        NXT4,Liquidation,BTC,-0.005,USDX,150.00,$150.00,approved / Loan repayment,2022-06-13 02:00:00
+                        NXT3,Locking Term Deposit,BTC,-0.01,BTC,0.01,$300.00,approved / Term deposit,2022-03-01 10:00:00
+                        NXT2,Interest,BTC,0.0001,BTC,0.0001,$4.00,approved / BTC Interest Earned,2022-02-01 06:00:00
+                        NXT1,Exchange,EURX,-400.00,BTC,0.01,$450.00,approved / Exchange EURX to BTC,2022-01-02 10:00:00
+                        NXT0,Deposit,BTC,0.002,BTC,0.002,$90.00,approved / BTC Top up,2022-01-01 10:00:00")?;
+
+        /*
+         * When
+         */
+        let txns = block_on(read_transactions(reader::open(file.path())?, &CsvFormat::default(), &"BTC".to_string()))?;
+
+        /*
+         * Then
+         */
+        let rows: Vec<_> = txns.iter()
+            .map(|t| (t.r#type.clone(), t.date.as_str(), t.paid_amount, t.exchanged_currency.as_str(), t.exchanged_amount))
+            .collect();
+        assert_eq!(rows, vec![
+            (TransactionType::Transfer, "2022-01-01 10:00:00", dec!(0.002), "", dec!(0)),
+            (TransactionType::Buy, "2022-01-02 10:00:00", dec!(0.01), "USD", dec!(-450.00)),
+            (TransactionType::Income, "2022-02-01 06:00:00", dec!(0.0001), "USD", dec!(-4.00)),
+            (TransactionType::Sell, "2022-06-13 02:00:00", dec!(-0.005), "USD", dec!(150.00)),
+        ]);
+        Ok(())
+    }
+}
@@ -0,0 +1,289 @@
+use crate::error::Result;
+use crate::locale::CsvFormat;
+use crate::reader;
+use crate::timestamp::Timestamp;
+use crate::transaction::{Currency, Transaction, TransactionType};
+use csv::{ReaderBuilder, Trim};
+use rust_decimal::prelude::*;
+use serde::Deserialize;
+use std::io::Read;
+
+/// A row in the Koinly universal csv, e.g.
+/// `2022-01-02 10:00 UTC,400,EUR,0.01,BTC,1,EUR,,,,,`.
+#[derive(Debug, Deserialize, PartialEq)]
+struct KoinlyRow {
+    #[serde(rename = "Date")]
+    date: String,
+
+    #[serde(rename = "Sent Amount")]
+    sent_amount: String,
+
+    #[serde(rename = "Sent Currency")]
+    sent_currency: Currency,
+
+    #[serde(rename = "Received Amount")]
+    received_amount: String,
+
+    #[serde(rename = "Received Currency")]
+    received_currency: Currency,
+
+    #[serde(rename = "Fee Amount", default)]
+    fee_amount: String,
+
+    #[serde(rename = "Fee Currency", default)]
+    fee_currency: Currency,
+
+    #[serde(rename = "Net Worth Amount", default)]
+    net_worth_amount: String,
+
+    #[serde(rename = "Net Worth Currency", default)]
+    net_worth_currency: Currency,
+
+    #[serde(rename = "Label", default)]
+    label: String,
+}
+
+/// A row in the CoinTracking csv import format, e.g.
+/// `Trade,0.01,BTC,400,EUR,1,EUR,Kraken,,,2022-01-02 10:00:00`.
+#[derive(Debug, Deserialize, PartialEq)]
+struct CoinTrackingRow {
+    #[serde(rename = "Type")]
+    r#type: String,
+
+    #[serde(rename = "Buy Amount")]
+    buy_amount: String,
+
+    #[serde(rename = "Buy Currency")]
+    buy_currency: Currency,
+
+    #[serde(rename = "Sell Amount")]
+    sell_amount: String,
+
+    #[serde(rename = "Sell Currency")]
+    sell_currency: Currency,
+
+    #[serde(rename = "Fee", default)]
+    fee: String,
+
+    #[serde(rename = "Fee Currency", default)]
+    fee_currency: Currency,
+
+    #[serde(rename = "Date")]
+    date: String,
+}
+
+/// Parses a date such as `2022-01-02 10:00 UTC` or `02.01.2022 10:00:00` into
+/// `2022-01-02 10:00:00`.
+fn parse_date(field: &str) -> Option<String> {
+    Timestamp::parse(field).map(|t| t.to_string())
+}
+
+/// What a row without the other side of a trade is, told by the Koinly label or the CoinTracking
+/// type, e.g. `reward` or `Staking`.
+fn type_of(kind: &str) -> Option<TransactionType> {
+    let kind = kind.to_lowercase();
+    let is = |names: &[&str]| names.iter().any(|n| kind.contains(n));
+    match () {
+        _ if is(&["airdrop", "fork", "mining", "reward", "income", "interest", "staking", "bonus", "cashback"]) => Some(TransactionType::Income),
+        _ if is(&["donation"]) => Some(TransactionType::Donation),
+        _ if is(&["gift", "tip"]) => Some(TransactionType::Gift),
+        _ if is(&["stolen"]) => Some(TransactionType::Stolen),
+        _ if is(&["lost"]) => Some(TransactionType::Lost),
+        _ if is(&["cost", "spend"]) => Some(TransactionType::Sell),
+        _ if is(&["repay"]) => Some(TransactionType::Repay),
+        _ if is(&["loan", "borrow"]) => Some(TransactionType::Borrow),
+        _ => None,
+    }
+}
+
+/// The amounts of a row of either tool, from the side of the target currency.
+struct Legs<'a> {
+    date: &'a str,
+    sent: Option<(Decimal, &'a Currency)>,
+    received: Option<(Decimal, &'a Currency)>,
+    fee: Option<(Decimal, &'a Currency)>,
+    value: Option<(Decimal, &'a Currency)>,     // The Koinly net worth, of an income or a spending
+    kind: &'a str,
+}
+
+impl Legs<'_> {
+    /// Converts the row. A row with both sides is a trade, a buy of the target currency if it was
+    /// received and a sale if it was sent. A row with one side is an income, a gift, a loss or a
+    /// spending if its label or type tells so, valued at its net worth if given, and otherwise a
+    /// transfer between the wallets of the tool. The fee is taken from the side in its currency,
+    /// or left out if it is in another.
+    fn to_transaction(&self, currency: &Currency) -> Option<Transaction> {
+        let mut txn = Transaction::new();
+        txn.date = parse_date(self.date)?;
+        txn.paid_currency = currency.clone();
+        let fee = self.fee.filter(|(_, c)| *c == currency).map(|(f, _)| f.abs()).unwrap_or_default();
+        match (self.sent, self.received) {
+            (Some((sent, other)), Some((received, c))) if c == currency && other != currency => {
+                txn.r#type = TransactionType::Buy;
+                txn.paid_amount = received.abs() - fee;
+                txn.exchanged_currency = other.clone();
+                txn.exchanged_amount = -sent.abs();
+            }
+            (Some((sent, c)), Some((received, other))) if c == currency && other != currency => {
+                txn.r#type = TransactionType::Sell;
+                txn.paid_amount = -(sent.abs() + fee);
+                txn.exchanged_currency = other.clone();
+                txn.exchanged_amount = received.abs();
+            }
+            (None, Some((received, c))) if c == currency => {
+                txn.r#type = type_of(self.kind).filter(|t| *t == TransactionType::Income).unwrap_or(TransactionType::Transfer);
+                txn.paid_amount = received.abs() - fee;
+            }
+            (Some((sent, c)), None) if c == currency => {
+                txn.r#type = type_of(self.kind).filter(|t| *t != TransactionType::Income).unwrap_or(TransactionType::Transfer);
+                txn.paid_amount = -(sent.abs() + fee);
+            }
+            _ => return None,
+        }
+        txn.paid_fee = -fee;
+        if let Some((fee, _)) = self.fee.filter(|(_, c)| !c.is_empty() && **c == txn.exchanged_currency) {
+            txn.exchanged_amount -= fee.abs();
+            txn.exchanged_fee = -fee.abs();
+        }
+        if let (Some((value, c)), true) = (self.value, txn.exchanged_currency.is_empty() && txn.r#type != TransactionType::Transfer) {
+            txn.exchanged_currency = c.clone();
+            txn.exchanged_amount = if txn.paid_amount.is_sign_positive() { -value.abs() } else { value.abs() };
+        }
+        Some(txn)
+    }
+}
+
+/// Parses an amount with its currency, `None` if the currency is blank.
+fn leg<'a>(format: &CsvFormat, amount: &str, currency: &'a Currency) -> Option<(Decimal, &'a Currency)> {
+    match currency.is_empty() {
+        true => None,
+        false => Decimal::from_str(&format.normalize_decimal(amount)).ok().map(|a| (a, currency)),
+    }
+}
+
+impl KoinlyRow {
+    fn to_transaction(&self, format: &CsvFormat, currency: &Currency) -> Option<Transaction> {
+        let legs = Legs{
+            date: &self.date,
+            sent: leg(format, &self.sent_amount, &self.sent_currency),
+            received: leg(format, &self.received_amount, &self.received_currency),
+            fee: leg(format, &self.fee_amount, &self.fee_currency),
+            value: leg(format, &self.net_worth_amount, &self.net_worth_currency),
+            kind: &self.label,
+        };
+        legs.to_transaction(currency)
+    }
+}
+
+impl CoinTrackingRow {
+    fn to_transaction(&self, format: &CsvFormat, currency: &Currency) -> Option<Transaction> {
+        let legs = Legs{
+            date: &self.date,
+            sent: leg(format, &self.sell_amount, &self.sell_currency),
+            received: leg(format, &self.buy_amount, &self.buy_currency),
+            fee: leg(format, &self.fee, &self.fee_currency),
+            value: None,
+            kind: &self.r#type,
+        };
+        legs.to_transaction(currency)
+    }
+}
+
+/// Reads the Koinly universal csv from the reader into a `Vec<Transaction>` in the target currency,
+/// e.g. the consolidated history of many exchanges and wallets of a user migrating from Koinly.
+/// The transactions are sorted by date, see `Transaction::cmp_by_date`.
+pub(crate) async fn read_koinly<R: Read>(rdr: R, format: &CsvFormat, currency: &Currency) -> Result<Vec<Transaction>> {
+    let mut rdr = ReaderBuilder::new()
+        .has_headers(true)
+        .delimiter(format.delimiter)
+        .trim(Trim::All)
+        .from_reader(rdr);
+
+    let mut txns: Vec<Transaction> =
+        reader::read_records::<KoinlyRow, _>(&mut rdr)?
+            .into_iter()
+            .filter_map(|(row, origin)| row.to_transaction(format, currency).map(|txn| Transaction{ origin, ..txn }))
+            .collect();
+    txns.sort_by(Transaction::cmp_by_date);
+
+    Ok(txns)
+}
+
+/// Reads the CoinTracking csv import format from the reader into a `Vec<Transaction>` in the target
+/// currency, like `read_koinly`. The income of CoinTracking has no value, so it is valued at its
+/// market price with `--prices`.
+/// The transactions are sorted by date, see `Transaction::cmp_by_date`.
+pub(crate) async fn read_cointracking<R: Read>(rdr: R, format: &CsvFormat, currency: &Currency) -> Result<Vec<Transaction>> {
+    let mut rdr = ReaderBuilder::new()
+        .has_headers(true)
+        .delimiter(format.delimiter)
+        .trim(Trim::All)
+        .from_reader(rdr);
+
+    let mut txns: Vec<Transaction> =
+        reader::read_records::<CoinTrackingRow, _>(&mut rdr)?
+            .into_iter()
+            .filter_map(|(row, origin)| row.to_transaction(format, currency).map(|txn| Transaction{ origin, ..txn }))
+            .collect();
+    txns.sort_by(Transaction::cmp_by_date);
+
+    Ok(txns)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::reader::universal::*;
+    use futures::executor::block_on;
+    use rust_decimal_macros::dec;
+    use std::error::Error;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn should_read_koinly_and_cointracking_universal_csv() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let mut koinly = NamedTempFile::new()?;
+        writeln!(koinly, "Date,Sent Amount,Sent Currency,Received Amount,Received Currency,Fee Amount,Fee Currency,Net Worth Amount,Net Worth Currency,Label,Description,TxHash
+                          2022-01-02 10:00 UTC,400,EUR,0.01,BTC,1,EUR,,,,,
+                          2022-02-01 06:00 UTC,,,0.0001,BTC,,,4.00,EUR,reward,,
+                          2022-03-01 10:00 UTC,0.005,BTC,250,EUR,0.0001,BTC,,,,,
+                          2022-04-01 10:00 UTC,0.002,BTC,,,,,80.00,EUR,gift,,
+                          2022-05-01 10:00 UTC,0.001,BTC,,,,,,,,,
+                          2022-05-01 10:00 UTC,1,ETH,,,,,,,,,")?;
+        let mut cointracking = NamedTempFile::new()?;
+        writeln!(cointracking, "Type,Buy Amount,Buy Currency,Sell Amount,Sell Currency,Fee,Fee Currency,Exchange,Trade-Group,Comment,Date
+                                Trade,0.01,BTC,400,EUR,1,EUR,Kraken,,,02.01.2022 10:00:00
+                                Staking,0.0001,BTC,,,,,Kraken,,,01.02.2022 06:00:00
+                                Trade,250,EUR,0.005,BTC,0.0001,BTC,Kraken,,,01.03.2022 10:00:00")?;
+
+        /*
+         * When
+         */
+        let koinly = block_on(read_koinly(reader::open(koinly.path())?, &CsvFormat::default(), &"BTC".to_string()))?;
+        let cointracking = block_on(read_cointracking(reader::open(cointracking.path())?, &CsvFormat::default(), &"BTC".to_string()))?;
+
+        /*
+         * Then
+         */
+        let rows = |txns: &[Transaction]| -> Vec<_> {
+            txns.iter()
+                .map(|t| (t.r#type.clone(), t.date.clone(), t.paid_amount, t.exchanged_currency.clone(), t.exchanged_amount))
+                .collect()
+        };
+        assert_eq!(rows(&koinly), vec![
+            (TransactionType::Buy, "2022-01-02 10:00:00".to_string(), dec!(0.01), "EUR".to_string(), dec!(-401)),
+            (TransactionType::Income, "2022-02-01 06:00:00".to_string(), dec!(0.0001), "EUR".to_string(), dec!(-4.00)),
+            (TransactionType::Sell, "2022-03-01 10:00:00".to_string(), dec!(-0.0051), "EUR".to_string(), dec!(250)),
+            (TransactionType::Gift, "2022-04-01 10:00:00".to_string(), dec!(-0.002), "EUR".to_string(), dec!(80.00)),
+            (TransactionType::Transfer, "2022-05-01 10:00:00".to_string(), dec!(-0.001), "".to_string(), dec!(0)),
+        ]);
+        assert_eq!(rows(&cointracking), vec![
+            (TransactionType::Buy, "2022-01-02 10:00:00".to_string(), dec!(0.01), "EUR".to_string(), dec!(-401)),
+            (TransactionType::Income, "2022-02-01 06:00:00".to_string(), dec!(0.0001), "".to_string(), dec!(0)),
+            (TransactionType::Sell, "2022-03-01 10:00:00".to_string(), dec!(-0.0051), "EUR".to_string(), dec!(250)),
+        ]);
+        Ok(())
+    }
+}
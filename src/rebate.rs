@@ -0,0 +1,83 @@
+use crate::jurisdiction::Rebates;
+use crate::transaction::{Currency, Transaction, TransactionType};
+use rust_decimal_macros::dec;
+use tracing::debug;
+
+/// Words of the rows of fee rebates and referral bonuses, in lower case, e.g. the Binance
+/// `Commission Rebate` and `Referral Kickback`, the Crypto.com `referral_bonus`, the Nexo
+/// `Referral Bonus` and the Celsius `referrer award`.
+const REBATE_WORDS: [&str; 5] = ["rebate", "kickback", "referral", "referred award", "referrer award"];
+
+/// The price source of a rebate that is acquired at no cost, see `Rebates::Discount`.
+pub(crate) const DISCOUNT: &str = "Rebate";
+
+/// Whether the income is a fee rebate or a referral bonus, by the rows it was read from.
+pub(crate) fn is_rebate(t: &Transaction) -> bool {
+    t.r#type == TransactionType::Income
+        && t.origin.descriptions.iter().any(|d| {
+            let d = d.to_lowercase();
+            REBATE_WORDS.iter().any(|w| d.contains(w))
+        })
+}
+
+/// Treats the fee rebates and referral bonuses as `rebates`. As `Rebates::Income` they stay
+/// income at their market value. As `Rebates::Discount` they are discounts on the fees that were
+/// paid, so they are no income, and are acquired at no cost in the base currency. This runs after
+/// `price::value`, so that the discounts are not valued at the market.
+pub(crate) fn apply(txns: &mut [Transaction], base: &Currency, rebates: Rebates) {
+    if rebates == Rebates::Income {
+        return;
+    }
+    for t in txns.iter_mut().filter(|t| is_rebate(t)) {
+        debug!(date = %t.date, amount = %t.paid_amount, currency = %t.paid_currency, "Rebate as a discount on fees");
+        t.r#type = TransactionType::Buy;
+        t.exchanged_currency = base.clone();
+        t.exchanged_amount = dec!(0);
+        t.exchanged_fee = dec!(0);
+        t.price_source = Some(DISCOUNT.to_string());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::rebate::*;
+    use crate::transaction::Origin;
+    use rust_decimal::Decimal;
+    use std::error::Error;
+
+    #[test]
+    fn should_treat_rebates_as_discounts_on_fees() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let income = |description: &str| -> Result<Transaction, Box<dyn Error>> {
+            let t = Transaction::income().currency("BNB").amount(dec!(0.01)).exchanged_currency("USD").exchanged_amount(dec!(-4)).date("2022-01-01 10:00:00").build()?;
+            Ok(Transaction{ origin: Origin::new(2, description.to_string()), ..t })
+        };
+        let txns = vec![
+            income("1,2022-01-01 10:00:00,Spot,Commission Rebate,BNB,0.01,")?,
+            income("1,2022-01-01 10:00:00,Spot,Simple Earn Flexible Interest,BNB,0.01,")?,
+        ];
+
+        /*
+         * When
+         */
+        let mut as_income = txns.clone();
+        apply(&mut as_income, &"USD".to_string(), Rebates::Income);
+        let mut as_discount = txns.clone();
+        apply(&mut as_discount, &"USD".to_string(), Rebates::Discount);
+
+        /*
+         * Then
+         */
+        assert_eq!(as_income, txns);
+        let rows: Vec<(TransactionType, Decimal, Option<String>)> = as_discount.into_iter()
+            .map(|t| (t.r#type, t.exchanged_amount, t.price_source))
+            .collect();
+        assert_eq!(rows, vec![
+            (TransactionType::Buy, dec!(0), Some("Rebate".to_string())),
+            (TransactionType::Income, dec!(-4), None),
+        ]);
+        Ok(())
+    }
+}
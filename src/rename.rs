@@ -0,0 +1,174 @@
+use crate::error::{self, Result};
+use crate::transaction::{Currency, Transaction, TransactionType};
+use csv::{ReaderBuilder, Trim};
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use std::path::PathBuf;
+use tracing::debug;
+
+/// A line in the renames file, which renames the currency `from` to `to` at `date`, e.g. a ticker
+/// change, or redenominates it, e.g. 1000 old units for 1 new:
+///
+/// ```csv
+/// Date,From,To,Ratio
+/// 2022-05-28,LUNA,LUNC,
+/// 2023-01-01,XYZ,XYZ,0.001
+/// ```
+///
+/// The optional `Ratio` is the number of units of `to` for one unit of `from`, by default 1.
+#[derive(Debug, Deserialize, PartialEq)]
+pub(crate) struct Rename {
+    #[serde(rename = "Date")]
+    date: String,
+
+    #[serde(rename = "From")]
+    from: Currency,
+
+    #[serde(rename = "To")]
+    to: Currency,
+
+    #[serde(rename = "Ratio")]
+    ratio: Option<Decimal>,
+}
+
+impl Rename {
+    fn ratio(&self) -> Decimal {
+        self.ratio.unwrap_or(Decimal::ONE)
+    }
+
+    /// Renames and redenominates the `from` side of the transaction.
+    fn apply(&self, t: &mut Transaction) {
+        if t.paid_currency == self.from {
+            t.paid_currency = self.to.clone();
+            t.paid_amount *= self.ratio();
+            t.paid_fee *= self.ratio();
+        }
+        if t.exchanged_currency == self.from {
+            t.exchanged_currency = self.to.clone();
+            t.exchanged_amount *= self.ratio();
+            t.exchanged_fee *= self.ratio();
+        }
+    }
+}
+
+/// Reads the renames file from path into a `Vec<Rename>`, in order of date.
+pub(crate) async fn read_renames(path: &PathBuf) -> Result<Vec<Rename>> {
+    let mut rdr = ReaderBuilder::new()
+        .has_headers(true)
+        .trim(Trim::All)
+        .from_path(path)?;
+    let mut renames =
+        rdr.deserialize::<Rename>()
+            .collect::<Result<Vec<Rename>, csv::Error>>()?;
+    if let Some(r) = renames.iter().find(|r| r.ratio().is_sign_negative() || r.ratio().is_zero()) {
+        let msg = format!("Ratio of renaming `{}` to `{}` must be positive", r.from, r.to);
+        return Err(error::invalid(path, msg));
+    }
+    renames.sort_by(|a, b| a.date.cmp(&b.date));
+    Ok(renames)
+}
+
+/// The currencies that were renamed to `currency`, also through other renames, whose transactions
+/// before the rename are in `currency`.
+pub(crate) fn sources_of<'a>(currency: &Currency, renames: &'a [Rename]) -> Vec<&'a Currency> {
+    let mut sources: Vec<&Currency> = vec![];
+    let mut targets = vec![currency];
+    while let Some(target) = targets.pop() {
+        for r in renames.iter().filter(|r| r.to.eq(target) && r.from.ne(currency)) {
+            if !sources.contains(&&r.from) {
+                sources.push(&r.from);
+                targets.push(&r.from);
+            }
+        }
+    }
+    sources
+}
+
+/// Applies the renames in order of date, so that the holding of the old currency carries over to
+/// the new one with its cost, instead of being disposed of. The transactions in the old currency
+/// before the rename are in the new currency, with the amounts redenominated. A trade of the old
+/// currency for the new one, i.e. the conversion booked by an exchange, is a transfer. Later
+/// transactions in the old currency are left as they are, as the ticker may have been reused.
+pub(crate) fn apply(txns: &mut [Transaction], renames: &[Rename]) {
+    for r in renames {
+        let mut count = 0;
+        for t in txns.iter_mut() {
+            let is_conversion = matches!(t.r#type, TransactionType::Buy | TransactionType::Sell)
+                && r.from != r.to
+                && (t.paid_currency == r.from && t.exchanged_currency == r.to
+                    || t.paid_currency == r.to && t.exchanged_currency == r.from);
+            if is_conversion {
+                t.r#type = TransactionType::Transfer;
+                r.apply(t);
+                t.exchanged_currency = "".to_string();
+                t.exchanged_amount = Default::default();
+                t.exchanged_fee = Default::default();
+                count += 1;
+            } else if t.date.as_str() < r.date.as_str() && (t.paid_currency == r.from || t.exchanged_currency == r.from) {
+                r.apply(t);
+                count += 1;
+            }
+        }
+        debug!(from = %r.from, to = %r.to, date = %r.date, count, "Renamed");
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::rename::*;
+    use futures::executor::block_on;
+    use rust_decimal_macros::dec;
+    use std::error::Error;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn should_carry_over_renamed_currencies() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let mut file = NamedTempFile::new()?;
+        writeln!(file, "Date,From,To,Ratio
+                        2022-05-28,LUNA,LUNC,
+                        2023-01-01,LUNC,LUNC,0.001")?;
+        let txn = |r#type: TransactionType, paid: &str, amount: Decimal, exchanged: &str, exchanged_amount: Decimal, date: &str| {
+            let mut txn = Transaction::new();
+            txn.r#type = r#type;
+            txn.paid_currency = paid.to_string();
+            txn.paid_amount = amount;
+            txn.exchanged_currency = exchanged.to_string();
+            txn.exchanged_amount = exchanged_amount;
+            txn.date = date.to_string();
+            txn
+        };
+        let mut txns = vec![
+            txn(TransactionType::Buy, "LUNA", dec!(1000), "SEK", dec!(-5000), "2022-01-01 10:00:00"),
+            txn(TransactionType::Sell, "LUNA", dec!(-1000), "LUNC", dec!(1000), "2022-05-28 12:00:00"),  // Converted
+            txn(TransactionType::Buy, "LUNC", dec!(1000), "LUNA", dec!(-1000), "2022-05-28 12:00:00"),
+            txn(TransactionType::Buy, "LUNA", dec!(10), "SEK", dec!(-500), "2022-06-01 10:00:00"),       // The new LUNA
+            txn(TransactionType::Sell, "LUNC", dec!(-1), "SEK", dec!(50), "2023-02-01 10:00:00"),
+        ];
+
+        /*
+         * When
+         */
+        let renames = block_on(read_renames(&file.path().to_path_buf()))?;
+        apply(&mut txns, &renames);
+
+        /*
+         * Then
+         */
+        let rows: Vec<_> = txns.iter().map(|t| (t.r#type.clone(), t.paid_currency.as_str(), t.paid_amount)).collect();
+        assert_eq!(rows, vec![
+            (TransactionType::Buy, "LUNC", dec!(1)),
+            (TransactionType::Transfer, "LUNC", dec!(-1)),
+            (TransactionType::Transfer, "LUNC", dec!(1)),
+            (TransactionType::Buy, "LUNA", dec!(10)),
+            (TransactionType::Sell, "LUNC", dec!(-1)),
+        ]);
+        assert_eq!(txns[0].exchanged_amount, dec!(-5000));
+        assert_eq!(sources_of(&"LUNC".to_string(), &renames), vec!["LUNA"]);
+        assert!(sources_of(&"LUNA".to_string(), &renames).is_empty());
+        Ok(())
+    }
+}
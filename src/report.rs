@@ -0,0 +1,145 @@
+use crate::calculator::TaxableTransaction;
+use crate::error::Result;
+use crate::jurisdiction::Jurisdiction;
+use crate::transaction::Currency;
+use rust_decimal::Decimal;
+use std::fmt;
+use std::io;
+
+/// A disposal or an income of a `Report`, with the amounts in the base currency rounded like the
+/// csv report.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Gain {
+    pub date: String,
+    pub currency: Currency,
+    /// The amount disposed of as a negative number, or received as a positive number.
+    pub amount: Decimal,
+    /// The proceeds, including the disposal fee.
+    pub income: Decimal,
+    /// The cost basis, including the acquisition fee, as a negative number.
+    pub costs: Decimal,
+    /// The gain or the loss, or `None` if the proceeds or the costs are not in the base currency.
+    pub net_income: Option<Decimal>,
+}
+
+impl From<&TaxableTransaction> for Gain {
+    fn from(t: &TaxableTransaction) -> Gain {
+        Gain{
+            date: t.date.clone(),
+            currency: t.currency.clone(),
+            amount: t.amount,
+            income: t.income.amount(),
+            costs: t.costs.iter().map(|c| c.amount()).sum(),
+            net_income: t.net_income,
+        }
+    }
+}
+
+/// What a `ReportWriter` writes, the tax calculation of one currency.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Report {
+    pub currency: Currency,
+    pub base: Currency,
+    pub jurisdiction: Jurisdiction,
+    /// The taxable disposals, in order of date.
+    pub disposals: Vec<Gain>,
+    /// The staking rewards, airdrops and other income, in order of date.
+    pub income: Vec<Gain>,
+}
+
+/// A report format that is not maintained in this crate, e.g. a national tax form, chosen with
+/// `ReportFormat::Custom` once it is registered in `Options::reports`.
+pub trait ReportWriter {
+    /// The name of the format, matched regardless of case.
+    fn name(&self) -> &str;
+
+    /// Writes the report to `out`.
+    fn write(&self, report: &Report, out: &mut dyn io::Write) -> Result<()>;
+}
+
+/// The custom report formats, by name.
+#[derive(Default)]
+pub struct ReportRegistry {
+    writers: Vec<Box<dyn ReportWriter>>,
+}
+
+impl ReportRegistry {
+    pub fn new() -> ReportRegistry {
+        ReportRegistry::default()
+    }
+
+    /// Adds a format, replacing a format with the same name.
+    pub fn register(&mut self, writer: Box<dyn ReportWriter>) {
+        self.writers.retain(|w| !w.name().eq_ignore_ascii_case(writer.name()));
+        self.writers.push(writer);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn ReportWriter> {
+        self.writers.iter().find(|w| w.name().eq_ignore_ascii_case(name)).map(|w| w.as_ref())
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.writers.iter().map(|w| w.name()).collect()
+    }
+}
+
+impl fmt::Debug for ReportRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.names()).finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cryptotax::{self, Options, ReportFormat};
+    use crate::report::*;
+    use std::error::Error;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    struct Gains;
+
+    impl ReportWriter for Gains {
+        fn name(&self) -> &str {
+            "gains"
+        }
+
+        fn write(&self, report: &Report, out: &mut dyn io::Write) -> Result<()> {
+            for d in &report.disposals {
+                writeln!(out, "{} {} {} {:?}", report.jurisdiction, d.date, d.currency, d.net_income)?;
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn should_write_a_registered_report_format() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let mut file = NamedTempFile::new()?;
+        writeln!(file, "Type;Started Date;Completed Date;Description;Amount;Fee;Currency;Original Amount;Original Currency;Settled Amount;Settled Currency;State;Balance
+                        Exchange;2021-12-31 17:54:48;2021-12-31 17:54:48;Exchanged to BTC;-1000;0;SEK;-1000;SEK;;;Completed;0
+                        Exchange;2021-12-31 17:54:48;2021-12-31 17:54:48;Exchanged from SEK;1;0;BTC;1;BTC;;;Completed;1
+                        Exchange;2022-03-01 16:21:49;2022-03-01 16:21:49;Exchanged to SEK;-1;0;BTC;-1;BTC;;;Completed;0
+                        Exchange;2022-03-01 16:21:49;2022-03-01 16:21:49;Exchanged from BTC;1500;0;SEK;1500;SEK;;;Completed;1500")?;
+        let path = file.path().to_path_buf();
+        let mut options = Options{ format: ReportFormat::Custom("GAINS".to_string()), ..Default::default() };
+        options.reports.register(Box::new(Gains));
+
+        /*
+         * When
+         */
+        let mut out = vec![];
+        cryptotax::write_tax(&path, &"BTC".to_string(), &"SEK".to_string(), &options, &mut out)?;
+        options.format = ReportFormat::Custom("k4".to_string());
+        let unknown = cryptotax::write_tax(&path, &"BTC".to_string(), &"SEK".to_string(), &options, &mut vec![]);
+
+        /*
+         * Then
+         */
+        assert_eq!(String::from_utf8(out)?, "SE 2022-03-01 17:21:49 BTC Some(500)\n");    // In Stockholm
+        assert_eq!(unknown.map_err(|e| e.to_string()), Err("Unknown report format `k4`. Supported: CSV, TABLE, gains".to_string()));
+        Ok(())
+    }
+}
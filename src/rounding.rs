@@ -0,0 +1,95 @@
+use rust_decimal::{Decimal, RoundingStrategy};
+use std::fmt;
+use std::str::FromStr;
+
+/// How the amounts in the base currency are rounded in a report. The calculations always keep
+/// full precision, and only the printed amounts are rounded, so that the rounding of one report
+/// never drifts into another. Amounts of the traded currency are never rounded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Rounding {
+    /// No rounding.
+    #[default]
+    Full,
+    /// To the number of decimals, half away from zero, e.g. 2 for cents.
+    Decimals(u32),
+    /// To whole units in the taxpayer's favor, e.g. whole SEK on the K4 form: income and gains are
+    /// rounded down, costs and losses up.
+    Favorable,
+}
+
+impl Rounding {
+    /// Rounds an amount that adds to the taxable gain, e.g. an income, or a cost or a loss that
+    /// is given as a negative amount.
+    pub(crate) fn gain(&self, amount: Decimal) -> Decimal {
+        match self {
+            Rounding::Full => amount,
+            Rounding::Decimals(dp) => amount.round_dp_with_strategy(*dp, RoundingStrategy::MidpointAwayFromZero),
+            Rounding::Favorable => amount.round_dp_with_strategy(0, RoundingStrategy::ToNegativeInfinity),
+        }
+    }
+
+    /// Rounds an amount that reduces the taxable gain, e.g. a cost basis or a loss that is given
+    /// as a positive amount.
+    pub(crate) fn cost(&self, amount: Decimal) -> Decimal {
+        match self {
+            Rounding::Favorable => amount.round_dp_with_strategy(0, RoundingStrategy::ToPositiveInfinity),
+            _ => self.gain(amount),
+        }
+    }
+
+    /// Rounds an estimated tax, whatever its sign. A favorable one is rounded down, so that less
+    /// tax is owed, or more is credited for a loss.
+    pub(crate) fn tax(&self, amount: Decimal) -> Decimal {
+        match self {
+            Rounding::Full => amount,
+            Rounding::Decimals(_) => self.gain(amount),
+            Rounding::Favorable => amount.round_dp_with_strategy(0, RoundingStrategy::ToNegativeInfinity),
+        }
+    }
+}
+
+impl FromStr for Rounding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_uppercase().as_str() {
+            "FULL" => Ok(Rounding::Full),
+            "FAVORABLE" | "K4" => Ok(Rounding::Favorable),
+            dp => dp.parse().map(Rounding::Decimals).map_err(|_| {
+                format!("Unknown rounding `{}`. Supported: FULL, FAVORABLE, or a number of decimals", s)
+            }),
+        }
+    }
+}
+
+impl fmt::Display for Rounding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Rounding::Full => write!(f, "FULL"),
+            Rounding::Decimals(dp) => write!(f, "{}", dp),
+            Rounding::Favorable => write!(f, "FAVORABLE"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::rounding::*;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn should_round_in_taxpayers_favor() {
+        assert_eq!(Rounding::Favorable.gain(dec!(1234.99)), dec!(1234));
+        assert_eq!(Rounding::Favorable.gain(dec!(-1234.01)), dec!(-1235));
+        assert_eq!(Rounding::Favorable.cost(dec!(1234.01)), dec!(1235));
+        assert_eq!(Rounding::Decimals(2).gain(dec!(1234.005)), dec!(1234.01));
+        assert_eq!(Rounding::Decimals(2).cost(dec!(-1234.005)), dec!(-1234.01));
+        assert_eq!(Rounding::Full.gain(dec!(1234.005)), dec!(1234.005));
+        assert_eq!(Rounding::Full.tax(dec!(-5883.676575)), dec!(-5883.676575));
+        assert_eq!(Rounding::Decimals(2).tax(dec!(-5883.676575)), dec!(-5883.68));
+        assert_eq!(Rounding::Favorable.tax(dec!(-2.101575)), dec!(-3));
+        assert_eq!(Rounding::Favorable.tax(dec!(450.9)), dec!(450));
+        assert_eq!("k4".parse::<Rounding>(), Ok(Rounding::Favorable));
+        assert_eq!("2".parse::<Rounding>(), Ok(Rounding::Decimals(2)));
+    }
+}
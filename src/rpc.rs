@@ -0,0 +1,162 @@
+use crate::cryptotax::{Jurisdiction, Options, ReportFormat};
+use crate::error::{CryptotaxError, Result};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::io::{self, BufRead};
+use std::path::PathBuf;
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_PARAMS: i64 = -32602;
+const METHOD_NOT_FOUND: i64 = -32601;
+const SERVER_ERROR: i64 = -32000;
+
+/// A JSON-RPC 2.0 request. A request without an id is a notification, which gets no response.
+#[derive(Debug, Deserialize)]
+struct Request {
+    #[serde(default)]
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+/// The params of the `import`, `calculate` and `report` methods. Only the options that make sense
+/// without a terminal can be given.
+#[derive(Debug, Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct Params {
+    pub(crate) path: PathBuf,
+    pub(crate) currency: String,
+    pub(crate) base: Option<String>,
+    pub(crate) store: Option<PathBuf>,
+    pub(crate) mapping: Option<PathBuf>,
+    jurisdiction: Option<String>,
+    overrides: Option<PathBuf>,
+    assume_zero_cost: bool,
+    format: Option<String>,
+}
+
+impl Params {
+    pub(crate) fn to_options(&self) -> Result<Options> {
+        let invalid = CryptotaxError::Config;
+        Ok(Options{
+            jurisdiction: self.jurisdiction.as_deref().map(str::parse::<Jurisdiction>).transpose().map_err(invalid)?.unwrap_or_default(),
+            store: self.store.clone(),
+            mapping: self.mapping.clone(),
+            overrides: self.overrides.clone(),
+            assume_zero_cost: self.assume_zero_cost,
+            format: self.format.as_deref().map(str::parse::<ReportFormat>).transpose().map_err(invalid)?.unwrap_or_default(),
+            ..Default::default()
+        })
+    }
+}
+
+/// Reads the params of a request, or fails with a `Config` error, which is answered with
+/// `INVALID_PARAMS`.
+pub(crate) fn params(params: Value) -> Result<Params> {
+    serde_json::from_value(params).map_err(|e| CryptotaxError::Config(format!("Invalid params: {}", e)))
+}
+
+/// Serves JSON-RPC 2.0 requests, one per line of `input`, until it ends, and writes a response per
+/// line to `output`, e.g. stdin and stdout of a child process of another tool. `handle` answers a
+/// request by its method and params, and can send progress on the way, which is written as a
+/// `progress` notification with the id of the request. A method that `handle` answers with
+/// `Ok(None)` is not found.
+pub(crate) fn serve<R, W, F>(input: R, mut output: W, mut handle: F) -> Result<()>
+    where R: BufRead,
+          W: io::Write,
+          F: FnMut(&str, Value, &mut dyn FnMut(&str)) -> Result<Option<Value>>,
+{
+    for line in input.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let request: Request = match serde_json::from_str(&line) {
+            Ok(request) => request,
+            Err(e) => {
+                respond(&mut output, &Value::Null, Err((PARSE_ERROR, e.to_string())))?;
+                continue;
+            }
+        };
+        let id = request.id.clone().unwrap_or(Value::Null);
+        let mut progress_error = None;
+        let result = {
+            let mut progress = |message: &str| {
+                let notification = json!({ "jsonrpc": "2.0", "method": "progress", "params": { "id": id, "message": message } });
+                if let Err(e) = writeln!(output, "{}", notification).and_then(|_| output.flush()) {
+                    progress_error.get_or_insert(e);
+                }
+            };
+            handle(&request.method, request.params, &mut progress)
+        };
+        if let Some(e) = progress_error {
+            return Err(e.into());
+        }
+        if request.id.is_none() {
+            continue;
+        }
+        let result = match result {
+            Ok(Some(result)) => Ok(result),
+            Ok(None) => Err((METHOD_NOT_FOUND, format!("Unknown method `{}`", request.method))),
+            Err(e @ CryptotaxError::Config(_)) => Err((INVALID_PARAMS, e.to_string())),
+            Err(e) => Err((SERVER_ERROR, e.to_string())),
+        };
+        respond(&mut output, &id, result)?;
+    }
+    Ok(())
+}
+
+fn respond<W: io::Write>(output: &mut W, id: &Value, result: Result<Value, (i64, String)>) -> Result<()> {
+    let response = match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err((code, message)) => json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } }),
+    };
+    writeln!(output, "{}", response)?;
+    Ok(output.flush()?)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::rpc::*;
+    use std::error::Error;
+
+    #[test]
+    fn should_answer_each_request_with_its_progress() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let input = r#"{"jsonrpc": "2.0", "id": 1, "method": "echo", "params": {"currency": "BTC"}}
+                       {"jsonrpc": "2.0", "method": "echo"}
+                       not json
+                       {"jsonrpc": "2.0", "id": "2", "method": "calculate", "params": {"currency": 1}}
+                       {"jsonrpc": "2.0", "id": 3, "method": "fly"}"#;
+
+        /*
+         * When
+         */
+        let mut output = vec![];
+        serve(input.as_bytes(), &mut output, |method, p, progress| match method {
+            "echo" => {
+                progress("echoing");
+                Ok(Some(p))
+            }
+            "calculate" => params(p).map(|p| Some(Value::String(p.currency))),
+            _ => Ok(None),
+        })?;
+
+        /*
+         * Then
+         */
+        let lines = String::from_utf8(output)?.lines().map(serde_json::from_str).collect::<Result<Vec<Value>, _>>()?;
+        assert_eq!(lines.len(), 6);
+        assert_eq!(lines[0], json!({ "jsonrpc": "2.0", "method": "progress", "params": { "id": 1, "message": "echoing" } }));
+        assert_eq!(lines[1], json!({ "jsonrpc": "2.0", "id": 1, "result": { "currency": "BTC" } }));
+        assert_eq!(lines[2]["params"]["id"], Value::Null);
+        assert_eq!(lines[3]["error"]["code"], PARSE_ERROR);
+        assert_eq!(lines[4]["id"], "2");
+        assert_eq!(lines[4]["error"]["code"], INVALID_PARAMS);
+        assert_eq!(lines[5]["error"], json!({ "code": METHOD_NOT_FOUND, "message": "Unknown method `fly`" }));
+        Ok(())
+    }
+}
@@ -0,0 +1,167 @@
+use crate::error::{self, Result};
+use crate::transaction::{Annotation, Currency, Transaction, TransactionType};
+use regex::Regex;
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use std::fs;
+use std::path::PathBuf;
+use tracing::debug;
+
+/// The rules file, which classifies, tags or names the counterparty of the transactions that
+/// match, e.g. the rows that recur in every export and would otherwise need an override each:
+///
+/// ```toml
+/// [[rule]]
+/// description = "(?i)^payment from acme"
+/// currency = "BTC"
+/// type = "Transfer"
+/// min_amount = "0"
+/// classify = "Income"
+/// tags = ["business"]
+/// counterparty = "Acme Ltd"
+/// ```
+///
+/// A rule matches on all of its conditions that are given: a regex that any of the descriptions
+/// of the rows must match, the type, the currency, and the range of the amount, both included.
+/// The first rule that matches a transaction applies.
+#[derive(Debug, Deserialize)]
+struct RulesFile {
+    #[serde(default)]
+    rule: Vec<RuleLine>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RuleLine {
+    description: Option<String>,
+    #[serde(rename = "type")]
+    r#type: Option<TransactionType>,
+    currency: Option<Currency>,
+    min_amount: Option<Decimal>,
+    max_amount: Option<Decimal>,
+    classify: Option<TransactionType>,
+    note: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    counterparty: Option<String>,
+}
+
+/// A rule of the rules file, with its regex compiled.
+#[derive(Debug)]
+pub(crate) struct Rule {
+    description: Option<Regex>,
+    r#type: Option<TransactionType>,
+    currency: Option<Currency>,
+    min_amount: Option<Decimal>,
+    max_amount: Option<Decimal>,
+    pub(crate) annotation: Annotation,
+}
+
+impl Rule {
+    pub(crate) fn matches(&self, t: &Transaction) -> bool {
+        self.description.as_ref().is_none_or(|re| t.origin.descriptions.iter().any(|d| re.is_match(d)))
+            && self.r#type.as_ref().is_none_or(|r#type| t.r#type.eq(r#type))
+            && self.currency.as_ref().is_none_or(|currency| t.paid_currency.eq(currency))
+            && self.min_amount.is_none_or(|min| t.paid_amount >= min)
+            && self.max_amount.is_none_or(|max| t.paid_amount <= max)
+    }
+}
+
+/// Reads the rules file from path.
+pub(crate) fn read_rules(path: &PathBuf) -> Result<Vec<Rule>> {
+    let invalid = |e: String| error::invalid(path, format!("Invalid rules file: {}", e));
+    let file: RulesFile = toml::from_str(&fs::read_to_string(path)?).map_err(|e| invalid(e.to_string()))?;
+    file.rule.into_iter()
+        .map(|line| {
+            let description = line.description.as_deref().map(Regex::new).transpose().map_err(|e| invalid(e.to_string()))?;
+            let annotation = Annotation{ r#type: line.classify, note: line.note, tags: line.tags, counterparty: line.counterparty };
+            Ok(Rule{
+                description,
+                r#type: line.r#type,
+                currency: line.currency,
+                min_amount: line.min_amount,
+                max_amount: line.max_amount,
+                annotation,
+            })
+        })
+        .collect()
+}
+
+/// The annotation of the first rule that matches the transaction, if any.
+pub(crate) fn annotation_of<'a>(t: &Transaction, rules: &'a [Rule]) -> Option<&'a Annotation> {
+    rules.iter().find(|r| r.matches(t)).map(|r| &r.annotation)
+}
+
+/// Applies the first rule that matches to each of the transactions.
+pub(crate) fn apply(txns: &mut [Transaction], rules: &[Rule]) {
+    for t in txns.iter_mut() {
+        if let Some(annotation) = annotation_of(t, rules) {
+            debug!(date = %t.date, currency = %t.paid_currency, ?annotation, "Applying rule");
+            annotation.apply(t);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::rules::*;
+    use crate::transaction::Origin;
+    use rust_decimal_macros::dec;
+    use std::error::Error;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn should_apply_first_matching_rule() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let mut file = NamedTempFile::new()?;
+        writeln!(file, r#"
+            [[rule]]
+            description = "(?i)^payment from acme"
+            currency = "BTC"
+            min_amount = "0"
+            classify = "Income"
+            tags = ["business"]
+            counterparty = "Acme Ltd"
+
+            [[rule]]
+            description = "(?i)acme"
+            note = "Sent to Acme"
+            tags = ["acme"]
+        "#)?;
+        let rules = read_rules(&file.path().to_path_buf())?;
+        let transfer = |amount: Decimal, description: &str| {
+            let mut t = Transaction::new();
+            t.r#type = TransactionType::Transfer;
+            t.paid_currency = "BTC".to_string();
+            t.paid_amount = amount;
+            t.date = "2022-01-01 10:00:00".to_string();
+            t.origin = Origin::new(2, description.to_string());
+            t
+        };
+        let mut txns = vec![
+            transfer(dec!(0.1), "Payment from ACME"),
+            transfer(dec!(-0.1), "Payment from Acme"),
+            transfer(dec!(0.1), "Payment from Beta"),
+        ];
+
+        /*
+         * When
+         */
+        apply(&mut txns, &rules);
+
+        /*
+         * Then
+         */
+        assert_eq!(txns[0].r#type, TransactionType::Income);
+        assert_eq!(txns[0].origin.tags, vec!["business".to_string()]);
+        assert_eq!(txns[0].origin.counterparty, Some("Acme Ltd".to_string()));
+        assert_eq!(txns[1].r#type, TransactionType::Transfer);
+        assert_eq!(txns[1].origin.note, Some("Sent to Acme".to_string()));
+        assert!(!txns[2].origin.has_notes());
+        assert_eq!(txns[2].origin.counterparty, None);
+        Ok(())
+    }
+}
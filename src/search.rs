@@ -0,0 +1,88 @@
+use crate::error::{CryptotaxError, Result};
+use crate::transaction::{Transaction, TransactionType};
+use rust_decimal::Decimal;
+use serde::de::{value, IntoDeserializer};
+use serde::Deserialize;
+
+/// Which transactions are listed by `tx list`. Every filter that is set must match.
+#[derive(Debug, Clone, Default)]
+pub struct Search {
+    /// The types, e.g. `buy`, `sell`, `income` or `add-liquidity`, in any case.
+    pub types: Vec<String>,
+    /// The first day, e.g. `2022-01-01`.
+    pub from: Option<String>,
+    /// The last day, e.g. `2022-12-31`.
+    pub to: Option<String>,
+    /// The smallest amount of the currency, bought or sold.
+    pub min_amount: Option<Decimal>,
+}
+
+/// The type of a name such as `buy` or `add-liquidity`.
+fn type_of(name: &str) -> Result<TransactionType> {
+    let camel: String = name.split(['-', '_', ' '])
+        .map(|word| {
+            let mut chars = word.chars();
+            chars.next().map(|c| c.to_uppercase().chain(chars.flat_map(char::to_lowercase)).collect()).unwrap_or_default()
+        })
+        .collect::<Vec<String>>()
+        .concat();
+    TransactionType::deserialize(camel.as_str().into_deserializer()).map_err(|_: value::Error| {
+        let msg = format!("Unknown type `{}`. Supported: buy, sell, income, transfer, add-liquidity, remove-liquidity, \
+                           gift, donation, lost, stolen, margin, futures, short", name);
+        CryptotaxError::Config(msg)
+    })
+}
+
+impl Search {
+    /// Keeps the transactions that match, in their order.
+    pub(crate) fn filter(&self, txns: Vec<Transaction>) -> Result<Vec<Transaction>> {
+        let types = self.types.iter().map(|t| type_of(t)).collect::<Result<Vec<TransactionType>>>()?;
+        let day = |t: &Transaction| t.date.get(..10).unwrap_or(&t.date).to_string();
+        Ok(txns.into_iter()
+            .filter(|t| types.is_empty() || types.contains(&t.r#type))
+            .filter(|t| self.from.as_ref().is_none_or(|from| day(t) >= *from))
+            .filter(|t| self.to.as_ref().is_none_or(|to| day(t) <= *to))
+            .filter(|t| self.min_amount.is_none_or(|min| t.paid_amount.abs() >= min))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::search::*;
+    use rust_decimal_macros::dec;
+    use std::error::Error;
+
+    #[test]
+    fn should_filter_transactions() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let txns = vec![
+            Transaction::buy().currency("BTC").amount(dec!(0.5)).exchanged_currency("SEK").exchanged_amount(dec!(-50000)).date("2021-12-31 23:00:00").build()?,
+            Transaction::buy().currency("BTC").amount(dec!(0.01)).exchanged_currency("SEK").exchanged_amount(dec!(-1000)).date("2022-01-01 10:00:00").build()?,
+            Transaction::sell().currency("BTC").amount(dec!(-0.2)).exchanged_currency("SEK").exchanged_amount(dec!(30000)).date("2022-06-01 10:00:00").build()?,
+            Transaction::income().currency("BTC").amount(dec!(0.1)).exchanged_currency("SEK").exchanged_amount(dec!(-10000)).date("2023-01-01 10:00:00").build()?,
+        ];
+        let search = Search{
+            types: vec!["buy".to_string(), "SELL".to_string()],
+            from: Some("2022-01-01".to_string()),
+            to: Some("2022-12-31".to_string()),
+            min_amount: Some(dec!(0.1)),
+        };
+
+        /*
+         * When
+         */
+        let found = search.filter(txns)?;
+
+        /*
+         * Then
+         */
+        let dates: Vec<&str> = found.iter().map(|t| t.date.as_str()).collect();
+        assert_eq!(dates, vec!["2022-06-01 10:00:00"]);
+        assert_eq!(type_of("add-liquidity")?, TransactionType::AddLiquidity);
+        assert!(type_of("swap").is_err());
+        Ok(())
+    }
+}
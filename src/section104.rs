@@ -0,0 +1,538 @@
+use crate::calculator::{Consumed, TaxableTransaction};
+use crate::cryptotax::Options;
+use crate::decimal;
+use crate::error::{CryptotaxError, Result};
+use crate::jurisdiction::Jurisdiction;
+use crate::lots::{Event, LotRow};
+use crate::rounding::Rounding;
+use crate::transaction::{Currency, Money, Origins, Transaction, TransactionType};
+use chrono::{Duration, NaiveDate};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use tracing::instrument;
+
+/// An amount of the traded currency with its costs, e.g. what is left of a buy, or the
+/// Section 104 pool.
+#[derive(Debug, Clone, Default)]
+struct Part {
+    amount: Decimal,
+    costs: Vec<Money>,      // At most one cash cost, in the base currency
+    fee: Decimal,           // Acquisition fees in the base currency, included in `costs`
+    origins: Origins,       // The buys that the part is made of
+    date: Option<String>,   // Of the buy, None if pooled
+}
+
+impl Part {
+    fn new(transaction: &Transaction, base: &Currency) -> Part {
+        let cost = transaction.to_money(base);
+        let fee = match cost.is_cash() { true => transaction.exchanged_fee, false => dec!(0) };
+        let date = Some(transaction.date.clone());
+        Part{ amount: transaction.paid_amount, costs: vec![cost], fee, origins: vec![transaction.origin.clone()].into(), date }
+    }
+
+    /// Takes `amount` out of the part, with the costs in proportion.
+    fn take(&mut self, amount: Decimal) -> Result<Part> {
+        let costs = self.costs.iter_mut()
+            .map(|c| Ok(c.deduct(decimal::share(c.amount(), amount, self.amount)?)))
+            .collect::<Result<_>>()?;
+        let fee = decimal::share(self.fee, amount, self.amount)?;
+        self.amount = decimal::sub(self.amount, amount)?;
+        self.fee = decimal::sub(self.fee, fee)?;
+        Ok(Part{ amount, costs, fee, origins: self.origins.clone(), date: self.date.clone() })
+    }
+
+    /// Adds `other` to the part, e.g. a buy to the pool. A part that is empty, as the same day or
+    /// the following 30 days consumed all of it, is left out with its origins.
+    fn merge(&mut self, other: Part) -> Result<()> {
+        if other.amount.is_zero() {
+            return Ok(());
+        }
+        self.amount = decimal::add(self.amount, other.amount)?;
+        self.fee = decimal::add(self.fee, other.fee)?;
+        self.origins.extend(&other.origins);
+        for cost in other.costs {
+            match (cost, self.costs.iter_mut().find(|c| c.is_cash())) {
+                (Money::Cash(cash), Some(Money::Cash(pooled))) => pooled.amount = decimal::add(pooled.amount, cash.amount)?,
+                (cost, _) => self.costs.push(cost),
+            }
+        }
+        Ok(())
+    }
+
+    /// A part of `amount` at zero cost, pooled, for what a disposal is missing in the pool, see
+    /// `Options::assume_zero_cost`.
+    fn zero_cost(amount: Decimal, disposal: &Transaction, base: &Currency) -> Part {
+        Part{ amount, costs: vec![Money::new_cash(base.clone(), dec!(0))], fee: dec!(0), origins: vec![disposal.origin.clone()].into(), date: None }
+    }
+
+    fn to_consumed(&self) -> Consumed {
+        Consumed{ amount: self.amount, costs: self.costs.clone(), origins: self.origins.clone(), acquired: self.date.clone() }
+    }
+}
+
+/// A disposal, with what is left of its amount to match, the part of the acquisitions matched to
+/// it so far, and the parts it consumed, one per acquisition or the pool.
+type Disposal<'a> = (NaiveDate, &'a Transaction, Decimal, Part, Vec<Consumed>);
+
+/// Matches every disposal with the acquisitions by the UK share identification rules, in order:
+///
+/// 1. the acquisitions on the same day,
+/// 2. the acquisitions in the following 30 days ("bed and breakfasting"), earliest first,
+/// 3. the Section 104 pool of all other acquisitions, at its average cost.
+///
+/// Returns the disposals in the order of the transactions, and the pool of what is still held
+/// after the last transaction. A disposal of more than is in the pool fails, unless
+/// `assume_zero_cost`, which matches what is missing at zero cost.
+fn identify<'a>(txns: &'a [Transaction], base: &Currency, assume_zero_cost: bool) -> Result<(Vec<Disposal<'a>>, Part)> {
+    let day_of = |t: &Transaction| {
+        NaiveDate::parse_from_str(t.date.get(..10).unwrap_or_default(), "%Y-%m-%d")
+            .map_err(|e| CryptotaxError::Parse{ file: t.origin.file.clone(), line: t.origin.lines.first().copied(), column: None, message: e.to_string() })
+    };
+    let mut buys: Vec<(NaiveDate, Part)> = vec![];
+    let mut disposals: Vec<Disposal> = vec![];
+    for t in txns {
+        if is_acquisition(t) {
+            buys.push((day_of(t)?, Part::new(t, base)));
+        } else if !is_ignored(t) {
+            disposals.push((day_of(t)?, t, t.paid_amount.abs(), Part::default(), vec![]));
+        }
+    }
+
+    // 1. Same day
+    for (day, _, remaining, matched, consumed) in disposals.iter_mut() {
+        for (_, buy) in buys.iter_mut().filter(|(d, _)| d == day) {
+            take_from(buy, remaining, matched, consumed)?;
+        }
+    }
+    // 2. Bed and breakfasting
+    for (day, _, remaining, matched, consumed) in disposals.iter_mut() {
+        let window = *day + Duration::days(30);
+        for (_, buy) in buys.iter_mut().filter(|(d, _)| d > day && *d <= window) {
+            take_from(buy, remaining, matched, consumed)?;
+        }
+    }
+    // 3. Section 104 pool, by walking through the days in order
+    let mut pool = Part::default();
+    let mut buys = buys.into_iter().peekable();
+    for (day, t, remaining, matched, consumed) in disposals.iter_mut() {
+        while let Some((_, buy)) = buys.next_if(|(d, _)| d <= day) {
+            pool.merge(buy)?;
+        }
+        if remaining.is_zero() {
+            continue;
+        }
+        if pool.amount < *remaining && assume_zero_cost {
+            pool.merge(Part::zero_cost(decimal::sub(*remaining, pool.amount)?, t, base))?;
+        } else if pool.amount < *remaining {
+            let msg = format!(
+                "Disposed {} {} at {} with only {} in the Section 104 pool after matching the acquisitions of the \
+                 same day and the following 30 days. Import the earlier history of {}",
+                t.paid_amount.abs(), t.paid_currency, t.date, pool.amount, t.paid_currency
+            );
+            return Err(CryptotaxError::Pairing(msg));
+        }
+        take_from(&mut pool, remaining, matched, consumed)?;
+    }
+    buys.try_for_each(|(_, buy)| pool.merge(buy))?;
+    Ok((disposals, pool))
+}
+
+fn is_acquisition(t: &Transaction) -> bool {
+    match t.r#type {
+        TransactionType::Buy | TransactionType::Income => true,
+        TransactionType::AddLiquidity | TransactionType::RemoveLiquidity => t.paid_amount.is_sign_positive(),
+        _ => false,
+    }
+}
+
+fn is_ignored(t: &Transaction) -> bool {
+    matches!(t.r#type, TransactionType::Transfer | TransactionType::Margin | TransactionType::Futures | TransactionType::Short)
+}
+
+/// Matches the disposals with the acquisitions, see `identify`, and returns a
+/// `TaxableTransaction` for every disposal where a gain or loss is recognized.
+#[instrument(name = "calculate", skip_all, fields(matching = "section104"))]
+pub(crate) async fn calculate(
+    txns: &[Transaction],
+    base: &Currency,
+    options: &Options,
+) -> Result<Vec<TaxableTransaction>> {
+    let jurisdiction = &options.jurisdiction;
+    let (disposals, _) = identify(txns, base, options.assume_zero_cost)?;
+    let taxables = disposals.into_iter()
+        .filter_map(|(_, t, _, matched, consumed)| {
+            let write_off = matches!(t.r#type, TransactionType::Lost | TransactionType::Stolen)
+                && options.claim_write_offs;
+            match jurisdiction.recognizes_gain(&t.r#type) || write_off {
+                true => Some(to_taxable(t, matched, consumed, base, write_off)),
+                false => None,
+            }
+        })
+        .collect::<Result<_>>()?;
+    Ok(taxables)
+}
+
+/// The acquisitions and the pool that is still held, like `lots::explore`. With `history`, they
+/// come after a row for every acquisition and for every part that a disposal consumed, of the
+/// acquisition it was matched with or of the pool, in the order they happened.
+pub(crate) fn explore(txns: &[Transaction], base: &Currency, history: bool, assume_zero_cost: bool) -> Result<Vec<LotRow>> {
+    let (disposals, pool) = identify(txns, base, assume_zero_cost)?;
+    let mut rows = vec![];
+    if history {
+        let mut disposals = disposals.iter().peekable();
+        for t in txns {
+            if is_acquisition(t) {
+                rows.push(LotRow::of(&t.date, Event::Acquired, &Part::new(t, base).to_consumed())?);
+            }
+            if let Some((_, _, _, _, consumed)) = disposals.next_if(|(_, d, _, _, _)| std::ptr::eq(*d, t)) {
+                for c in consumed {
+                    rows.push(LotRow::of(&t.date, Event::Disposed, c)?);
+                }
+            }
+        }
+    }
+    if !pool.amount.is_zero() {
+        let date = txns.last().map_or("", |t| t.date.as_str());
+        rows.push(LotRow::of(date, Event::Open, &pool.to_consumed())?);
+    }
+    Ok(rows)
+}
+
+fn take_from(part: &mut Part, remaining: &mut Decimal, matched: &mut Part, consumed: &mut Vec<Consumed>) -> Result<()> {
+    let amount = part.amount.min(*remaining);
+    if amount > dec!(0) {
+        let taken = part.take(amount)?;
+        consumed.push(taken.to_consumed());
+        matched.merge(taken)?;
+        *remaining = decimal::sub(*remaining, amount)?;
+    }
+    Ok(())
+}
+
+fn to_taxable(transaction: &Transaction, matched: Part, consumed: Vec<Consumed>, base: &Currency, write_off: bool) -> Result<TaxableTransaction> {
+    let income = match write_off {
+        true => Money::new_cash(base.clone(), dec!(0)),
+        false => transaction.to_money(base),
+    };
+    let net_income = income.to_net_income(&matched.costs)?;
+    let disposal_fee = match income.is_cash() && !write_off {
+        true => transaction.exchanged_fee,
+        false => dec!(0),
+    };
+    Ok(TaxableTransaction{
+        r#type: transaction.r#type.clone(),
+        date: transaction.date.clone(),
+        currency: transaction.paid_currency.clone(),
+        amount: transaction.paid_amount,
+        income,
+        costs: matched.costs,
+        net_income,
+        acquisition_fee: matched.fee,
+        disposal_fee,
+        origin: transaction.origin.clone(),
+        consumed
+    })
+}
+
+/// The numbers of the capital gains summary of the HMRC Self Assessment, per UK tax year.
+#[derive(Debug, PartialEq, Serialize)]
+pub(crate) struct HmrcRow {
+    #[serde(rename = "Tax Year")]
+    tax_year: String,               // e.g. "2021/22", from 6 April 2021 to 5 April 2022
+
+    #[serde(rename = "Disposals")]
+    disposals: usize,
+
+    #[serde(rename = "Disposal Proceeds")]
+    proceeds: Decimal,
+
+    #[serde(rename = "Allowable Costs")]
+    costs: Decimal,
+
+    #[serde(rename = "Gains")]
+    gains: Decimal,
+
+    #[serde(rename = "Losses")]
+    losses: Decimal,
+
+    #[serde(rename = "Annual Exempt Amount")]
+    exempt_amount: Option<Decimal>, // Of the tax year, see `Jurisdiction::threshold`
+
+    #[serde(rename = "Taxable Gains")]
+    taxable_gains: Decimal,         // The gains less the losses and the annual exempt amount
+
+    #[serde(rename = "Unpriced")]
+    unpriced: usize,                // Disposals not in the base currency, not included above
+}
+
+impl HmrcRow {
+    /// Rounds the amounts for a report. The gains and the losses are rounded separately, as they
+    /// are entered separately on the return.
+    pub(crate) fn round(&mut self, rounding: &Rounding) {
+        self.proceeds = rounding.gain(self.proceeds);
+        self.costs = rounding.cost(self.costs);
+        self.gains = rounding.gain(self.gains);
+        self.losses = rounding.cost(self.losses);
+        self.taxable_gains = rounding.gain(self.taxable_gains);
+    }
+}
+
+/// Sums up the taxable transactions per UK tax year, and deducts the annual exempt amount of the
+/// tax year in the jurisdiction of `thresholds`, if any, from the net gains.
+pub(crate) fn summarize(txns: &[TaxableTransaction], thresholds: Option<Jurisdiction>) -> Result<Vec<HmrcRow>> {
+    txns.iter()
+        .try_fold(BTreeMap::new(), |mut years, t| -> Result<_> {
+            let tax_year = tax_year_of(&t.date);
+            let row = years.entry(tax_year.clone()).or_insert_with(|| HmrcRow{
+                tax_year,
+                disposals: 0,
+                proceeds: dec!(0),
+                costs: dec!(0),
+                gains: dec!(0),
+                losses: dec!(0),
+                exempt_amount: None,
+                taxable_gains: dec!(0),
+                unpriced: 0,
+            });
+            row.disposals += 1;
+            match t.net_income {
+                Some(net_income) => {
+                    row.proceeds = decimal::add(row.proceeds, t.income.amount())?;
+                    row.costs = decimal::sub(row.costs, decimal::sum(t.costs.iter().map(|c| c.amount()))?)?;
+                    match net_income.is_sign_negative() {
+                        true => row.losses = decimal::sub(row.losses, net_income)?,
+                        false => row.gains = decimal::add(row.gains, net_income)?,
+                    }
+                }
+                None => row.unpriced += 1,
+            }
+            Ok(years)
+        })?
+        .into_values()
+        .map(|row| {
+            let threshold = thresholds.and_then(|j| j.threshold(&row.tax_year));
+            let exempt_amount = threshold.map(|t| t.amount());
+            let net = decimal::sub(row.gains, row.losses)?;
+            let taxable_gains = threshold.map_or(net, |t| t.taxable(net)).max(dec!(0));
+            Ok(HmrcRow{ exempt_amount, taxable_gains, ..row })
+        })
+        .collect()
+}
+
+/// The UK tax year of the date, which starts on 6 April.
+fn tax_year_of(date: &str) -> String {
+    let year: i32 = date.get(..4).and_then(|y| y.parse().ok()).unwrap_or_default();
+    let start = match date.get(5..10).unwrap_or_default() >= "04-06" {
+        true => year,
+        false => year - 1,
+    };
+    format!("{}/{:02}", start, (start + 1) % 100)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cryptotax::{Jurisdiction, Options};
+    use crate::section104::*;
+    use futures::executor::block_on;
+    use rust_decimal_macros::dec;
+    use std::error::Error;
+
+    #[test]
+    fn should_match_same_day_then_30_days_then_pool() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let txn = |r#type, paid_amount, exchanged_amount, date: &str| Transaction{
+            r#type,
+            paid_currency: "BTC".to_string(),
+            paid_amount,
+            exchanged_currency: "GBP".to_string(),
+            exchanged_amount,
+            date: date.to_string(),
+            is_vault: false,
+            paid_fee: dec!(0),
+            exchanged_fee: dec!(0),
+            price_source: None,
+            unit_price: None,
+            fiat_value: None,
+            other_fee: None,
+            origin: Default::default()
+        };
+        let txns = vec![
+            txn(TransactionType::Buy, dec!(2), dec!(-20000), "2021-01-01 10:00:00"),
+            txn(TransactionType::Buy, dec!(1), dec!(-40000), "2021-05-01 09:00:00"),
+            txn(TransactionType::Sell, dec!(-2), dec!(90000), "2021-05-01 15:00:00"),
+            txn(TransactionType::Buy, dec!(0.5), dec!(-25000), "2021-05-20 10:00:00"),
+            txn(TransactionType::Buy, dec!(1), dec!(-60000), "2021-07-01 10:00:00"),
+        ];
+        let options = Options{ jurisdiction: Jurisdiction::UnitedKingdom, ..Default::default() };
+
+        /*
+         * When
+         */
+        let taxables = block_on(calculate(&txns, &"GBP".to_string(), &options))?;
+
+        /*
+         * Then
+         */
+        // 1 BTC on the same day for 40000, 0.5 BTC in 30 days for 25000, 0.5 BTC from the pool for 5000
+        assert_eq!(taxables.len(), 1);
+        assert_eq!(taxables[0].costs, vec![Money::new_cash("GBP".to_string(), dec!(-70000))]);
+        assert_eq!(taxables[0].net_income, Some(dec!(20000)));
+        assert_eq!(summarize(&taxables, Some(Jurisdiction::UnitedKingdom))?, vec![HmrcRow{
+            tax_year: "2021/22".to_string(),
+            disposals: 1,
+            proceeds: dec!(90000),
+            costs: dec!(70000),
+            gains: dec!(20000),
+            losses: dec!(0),
+            exempt_amount: Some(dec!(12300)),
+            taxable_gains: dec!(7700),
+            unpriced: 0,
+        }]);
+        Ok(())
+    }
+
+    #[test]
+    fn should_explore_parts_of_same_day_30_days_and_pool() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let txn = |r#type, paid_amount, exchanged_amount, date: &str| {
+            let mut txn = Transaction::new();
+            txn.r#type = r#type;
+            txn.paid_currency = "BTC".to_string();
+            txn.paid_amount = paid_amount;
+            txn.exchanged_currency = "GBP".to_string();
+            txn.exchanged_amount = exchanged_amount;
+            txn.date = date.to_string();
+            txn
+        };
+        let txns = vec![
+            txn(TransactionType::Buy, dec!(2), dec!(-20000), "2021-01-01 10:00:00"),
+            txn(TransactionType::Buy, dec!(1), dec!(-40000), "2021-05-01 09:00:00"),
+            txn(TransactionType::Sell, dec!(-2), dec!(90000), "2021-05-01 15:00:00"),
+            txn(TransactionType::Buy, dec!(0.5), dec!(-25000), "2021-05-20 10:00:00"),
+            txn(TransactionType::Buy, dec!(1), dec!(-60000), "2021-07-01 10:00:00"),
+        ];
+        let part = |amount, cost, acquired: Option<&str>| Consumed{
+            amount,
+            costs: vec![Money::new_cash("GBP".to_string(), cost)],
+            origins: vec![].into(),
+            acquired: acquired.map(str::to_string),
+        };
+
+        /*
+         * When
+         */
+        let history = explore(&txns, &"GBP".to_string(), true, false)?;
+        let open = explore(&txns, &"GBP".to_string(), false, false)?;
+
+        /*
+         * Then
+         */
+        let disposed: Vec<&LotRow> = history.iter().skip(2).take(3).collect();
+        assert_eq!(history.len(), 8);
+        assert_eq!(disposed, vec![
+            &LotRow::of("2021-05-01 15:00:00", Event::Disposed, &part(dec!(1), dec!(-40000), Some("2021-05-01 09:00:00")))?,
+            &LotRow::of("2021-05-01 15:00:00", Event::Disposed, &part(dec!(0.5), dec!(-25000.0), Some("2021-05-20 10:00:00")))?,
+            &LotRow::of("2021-05-01 15:00:00", Event::Disposed, &part(dec!(0.5), dec!(-5000.0), None))?,
+        ]);
+        assert_eq!(open, vec![LotRow::of("2021-07-01 10:00:00", Event::Open, &part(dec!(2.5), dec!(-75000.0), None))?]);
+        Ok(())
+    }
+
+    #[test]
+    fn should_take_exact_costs_without_empty_parts() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let txn = |r#type, paid_amount, exchanged_amount, date: &str| {
+            let mut txn = Transaction::new();
+            txn.r#type = r#type;
+            txn.paid_currency = "BTC".to_string();
+            txn.paid_amount = paid_amount;
+            txn.exchanged_currency = "GBP".to_string();
+            txn.exchanged_amount = exchanged_amount;
+            txn.date = date.to_string();
+            txn
+        };
+        let txns = vec![
+            txn(TransactionType::Buy, dec!(0.35), dec!(-10500), "2022-03-01 12:00:00"),
+            txn(TransactionType::Buy, dec!(0.05), dec!(-1100), "2023-05-02 09:00:00"),
+            txn(TransactionType::Sell, dec!(-0.2), dec!(4600), "2023-05-02 10:00:00"),
+            txn(TransactionType::Buy, dec!(0.1), dec!(-2100), "2023-05-20 11:00:00"),
+            txn(TransactionType::Sell, dec!(-1), dec!(30000), "2023-08-01 10:00:00"),
+        ];
+        let options = Options{ jurisdiction: Jurisdiction::UnitedKingdom, ..Default::default() };
+
+        /*
+         * When
+         */
+        let taxables = block_on(calculate(&txns[..4], &"GBP".to_string(), &options))?;
+        let error = block_on(calculate(&txns, &"GBP".to_string(), &options)).expect_err("1 BTC is more than is held");
+
+        /*
+         * Then
+         */
+        let costs: Vec<Vec<Money>> = taxables[0].consumed.iter().map(|c| c.costs.clone()).collect();
+        assert_eq!(costs, vec![
+            vec![Money::new_cash("GBP".to_string(), dec!(-1100))],
+            vec![Money::new_cash("GBP".to_string(), dec!(-2100))],
+            vec![Money::new_cash("GBP".to_string(), dec!(-1500))],      // Of the pool of the first buy only
+        ]);
+        assert_eq!(taxables[0].consumed[2].origins.to_vec().len(), 1);
+        assert!(error.to_string().starts_with("Disposed 1 BTC at 2023-08-01 10:00:00 with only 0.30 in the Section 104 pool"), "{}", error);
+        Ok(())
+    }
+
+    #[test]
+    fn should_match_sell_before_any_buy_at_zero_cost() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let txn = |r#type, paid_amount, exchanged_amount, date: &str| {
+            let mut txn = Transaction::new();
+            txn.r#type = r#type;
+            txn.paid_currency = "BTC".to_string();
+            txn.paid_amount = paid_amount;
+            txn.exchanged_currency = "GBP".to_string();
+            txn.exchanged_amount = exchanged_amount;
+            txn.date = date.to_string();
+            txn
+        };
+        let txns = vec![
+            txn(TransactionType::Sell, dec!(-0.5), dec!(15000), "2022-03-01 12:00:00"),
+            txn(TransactionType::Buy, dec!(1), dec!(-20000), "2022-06-01 09:00:00"),
+        ];
+        let options = Options{ jurisdiction: Jurisdiction::UnitedKingdom, ..Default::default() };
+        let zero_cost = Options{ jurisdiction: Jurisdiction::UnitedKingdom, assume_zero_cost: true, ..Default::default() };
+
+        /*
+         * When
+         */
+        let error = block_on(calculate(&txns, &"GBP".to_string(), &options)).expect_err("Nothing is held before the buy");
+        let taxables = block_on(calculate(&txns, &"GBP".to_string(), &zero_cost))?;
+        let open = explore(&txns, &"GBP".to_string(), false, true)?;
+
+        /*
+         * Then
+         */
+        assert!(error.to_string().starts_with("Disposed 0.5 BTC at 2022-03-01 12:00:00 with only 0 in the Section 104 pool"), "{}", error);
+        assert_eq!(taxables.len(), 1);
+        assert_eq!(taxables[0].costs, vec![Money::new_cash("GBP".to_string(), dec!(0))]);
+        assert_eq!(taxables[0].net_income, Some(dec!(15000)));
+        let held = Consumed{ amount: dec!(1), costs: vec![Money::new_cash("GBP".to_string(), dec!(-20000))], origins: vec![].into(), acquired: None };
+        assert_eq!(open, vec![LotRow::of("2022-06-01 09:00:00", Event::Open, &held)?]);
+        Ok(())
+    }
+
+    #[test]
+    fn should_tell_tax_year() {
+        assert_eq!(tax_year_of("2022-04-05 23:59:59"), "2021/22");
+        assert_eq!(tax_year_of("2022-04-06 00:00:00"), "2022/23");
+        assert_eq!(tax_year_of("1999-12-31 00:00:00"), "1999/00");
+    }
+}
@@ -0,0 +1,205 @@
+use crate::decimal;
+use crate::error::Result;
+use crate::transaction::{Currency, Origin, Transaction, TransactionType};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use std::collections::{HashMap, VecDeque};
+use tracing::debug;
+
+/// A sale of borrowed currency that is not bought back yet, or the part of it that is left.
+#[derive(Debug)]
+struct Position {
+    amount: Decimal,                // Still open, positive
+    proceeds: Decimal,              // Of the open amount, in the exchanged currency
+    fee: Decimal,                   // Of the open amount, negative
+    currency: Currency,
+    date: String,
+    origin: Origin,
+}
+
+/// What is held, borrowed and sold short of a currency.
+#[derive(Debug, Default)]
+struct Book {
+    held: Decimal,
+    borrowed: Decimal,
+    open: VecDeque<Position>,
+}
+
+impl Book {
+    fn open_amount(&self) -> Decimal {
+        self.open.iter().map(|p| p.amount).sum()
+    }
+
+    /// The amount of a sale that is sold short: what is sold of more than is held, as long as it
+    /// is borrowed and not sold short already.
+    fn short_of(&self, sold: Decimal) -> Decimal {
+        let shortage = sold - self.held.max(dec!(0));
+        let available = self.borrowed - self.open_amount();
+        shortage.min(available).max(dec!(0))
+    }
+
+    /// Closes the open positions in the exchanged currency of the buy, the first opened first,
+    /// with `bought` of the buy at a cost of `cost`. Returns the `Short` transactions of the
+    /// closed positions, with their profits or losses.
+    fn cover(&mut self, buy: &Transaction, bought: Decimal, cost: Decimal, fee: Decimal) -> Result<Vec<Transaction>> {
+        let mut closed = vec![];
+        let mut left = bought;
+        while left > dec!(0) {
+            let Some(position) = self.open.iter_mut().find(|p| p.currency == buy.exchanged_currency) else {
+                break;
+            };
+            let amount = left.min(position.amount);
+            let proceeds = decimal::share(position.proceeds, amount, position.amount)?;
+            let open_fee = decimal::share(position.fee, amount, position.amount)?;
+            let mut origin = position.origin.clone();
+            buy.origin.lines.iter().zip(&buy.origin.descriptions).for_each(|(l, d)| origin.add(*l, d.clone()));
+            debug!(opened = %position.date, closed = %buy.date, %amount, currency = %buy.paid_currency, "Short position closed");
+            closed.push(Transaction{
+                r#type: TransactionType::Short,
+                paid_currency: buy.paid_currency.clone(),
+                paid_amount: -amount,
+                exchanged_currency: position.currency.clone(),
+                exchanged_amount: proceeds + decimal::share(cost, amount, bought)?,
+                date: buy.date.clone(),
+                is_vault: false,
+                paid_fee: dec!(0),
+                exchanged_fee: open_fee + decimal::share(fee, amount, bought)?,
+                price_source: None,
+                unit_price: None,
+                fiat_value: None,
+                other_fee: None,
+                origin,
+            });
+            position.amount -= amount;
+            position.proceeds -= proceeds;
+            position.fee -= open_fee;
+            left -= amount;
+        }
+        self.open.retain(|p| !p.amount.is_zero());
+        Ok(closed)
+    }
+}
+
+/// The part of a transaction of `part` of its `whole` amount, to split it into parts.
+fn scaled(t: &Transaction, part: Decimal, whole: Decimal) -> Result<Transaction> {
+    Ok(Transaction{
+        paid_amount: decimal::share(t.paid_amount, part, whole)?,
+        paid_fee: decimal::share(t.paid_fee, part, whole)?,
+        exchanged_amount: decimal::share(t.exchanged_amount, part, whole)?,
+        exchanged_fee: decimal::share(t.exchanged_fee, part, whole)?,
+        ..t.clone()
+    })
+}
+
+/// Separates the short positions from the spot holdings, so that they don't take from the costs
+/// of what is held. A `Sell` of more than is held, of a currency that is borrowed, is sold short
+/// for the part that is not held, and a later `Buy` in the same exchanged currency buys it back,
+/// the first opened first. Each closed position is a `Short` transaction with the proceeds less
+/// the cost of buying back as its profit or loss, which is reported like those of the margin
+/// positions, and not in the `CostBook`. The `Borrow` and `Repay` transactions are left out.
+/// Positions that are still open are left out too, and taxed when they are closed.
+pub(crate) fn separate(txns: &mut Vec<Transaction>) -> Result<()> {
+    if !txns.iter().any(|t| t.r#type == TransactionType::Borrow) {
+        return Ok(());
+    }
+    let mut books: HashMap<Currency, Book> = HashMap::new();
+    let mut separated = vec![];
+    for t in std::mem::take(txns) {
+        let book = books.entry(t.paid_currency.clone()).or_default();
+        match t.r#type {
+            TransactionType::Borrow => book.borrowed += t.paid_amount.abs(),
+            TransactionType::Repay => book.borrowed = (book.borrowed - t.paid_amount.abs()).max(dec!(0)),
+            TransactionType::Transfer | TransactionType::Margin | TransactionType::Futures | TransactionType::Short => separated.push(t),
+            TransactionType::Sell if t.paid_amount.is_sign_negative() && !book.short_of(-t.paid_amount).is_zero() => {
+                let sold = -t.paid_amount;
+                let short = book.short_of(sold);
+                let position = scaled(&t, short, sold)?;
+                debug!(date = %t.date, amount = %short, currency = %t.paid_currency, "Short position opened");
+                book.open.push_back(Position{
+                    amount: short,
+                    proceeds: position.exchanged_amount,
+                    fee: position.exchanged_fee,
+                    currency: t.exchanged_currency.clone(),
+                    date: t.date.clone(),
+                    origin: t.origin.clone(),
+                });
+                if short < sold {
+                    book.held += t.paid_amount + short;
+                    separated.push(scaled(&t, sold - short, sold)?);
+                }
+            }
+            TransactionType::Buy if t.paid_amount.is_sign_positive() => {
+                let open: Decimal = book.open.iter().filter(|p| p.currency == t.exchanged_currency).map(|p| p.amount).sum();
+                let bought = open.min(t.paid_amount);
+                if bought.is_zero() {
+                    book.held += t.paid_amount;
+                    separated.push(t);
+                    continue;
+                }
+                let part = scaled(&t, bought, t.paid_amount)?;
+                separated.extend(book.cover(&t, bought, part.exchanged_amount, part.exchanged_fee)?);
+                if bought < t.paid_amount {
+                    book.held += t.paid_amount - bought;
+                    separated.push(scaled(&t, t.paid_amount - bought, t.paid_amount)?);
+                }
+            }
+            _ => {
+                book.held += t.paid_amount;
+                separated.push(t);
+            }
+        }
+    }
+    for (currency, book) in &books {
+        book.open.iter().for_each(|p| debug!(opened = %p.date, amount = %p.amount, %currency, "Short position still open"));
+    }
+    *txns = separated;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::short::*;
+    use std::error::Error;
+
+    #[test]
+    fn should_separate_short_positions_from_spot() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let loan = |r#type, amount, date: &str| {
+            let mut txn = Transaction::new();
+            txn.r#type = r#type;
+            txn.paid_currency = "BTC".to_string();
+            txn.paid_amount = amount;
+            txn.date = date.to_string();
+            txn
+        };
+        let mut txns = vec![
+            Transaction::buy().currency("BTC").amount(dec!(1)).exchanged_currency("SEK").exchanged_amount(dec!(-100)).date("2022-01-01 10:00:00").build()?,
+            loan(TransactionType::Borrow, dec!(2), "2022-01-02 10:00:00"),
+            Transaction::sell().currency("BTC").amount(dec!(-3)).exchanged_currency("SEK").exchanged_amount(dec!(600)).date("2022-01-02 10:00:01").build()?,
+            Transaction::buy().currency("BTC").amount(dec!(2)).exchanged_currency("SEK").exchanged_amount(dec!(-300)).date("2022-01-03 10:00:00").build()?,
+            loan(TransactionType::Repay, dec!(-2), "2022-01-03 10:00:01"),
+            Transaction::buy().currency("BTC").amount(dec!(1)).exchanged_currency("SEK").exchanged_amount(dec!(-160)).date("2022-01-04 10:00:00").build()?,
+        ];
+
+        /*
+         * When
+         */
+        separate(&mut txns)?;
+
+        /*
+         * Then
+         */
+        let rows: Vec<(TransactionType, Decimal, Decimal)> = txns.into_iter()
+            .map(|t| (t.r#type, t.paid_amount, t.exchanged_amount))
+            .collect();
+        assert_eq!(rows, vec![
+            (TransactionType::Buy, dec!(1), dec!(-100)),
+            (TransactionType::Sell, dec!(-1), dec!(200)),       // The part that was held
+            (TransactionType::Short, dec!(-2), dec!(100)),      // Sold for 400, bought back for 300
+            (TransactionType::Buy, dec!(1), dec!(-160)),        // Nothing left to buy back
+        ]);
+        Ok(())
+    }
+}
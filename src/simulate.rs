@@ -0,0 +1,160 @@
+use crate::calculator::{Calculation, TaxableTransaction};
+use crate::decimal;
+use crate::error::Result;
+use crate::rounding::Rounding;
+use crate::summary::TaxRate;
+use crate::transaction::{Currency, Money, Origin, Transaction, TransactionType};
+use chrono::DateTime;
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde::Serialize;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The file of the origin of the simulated sale, which tells its disposals from the others.
+const SIMULATION: &str = "simulation";
+
+/// What selling an amount of the holding would realize, and the tax it would add to its year.
+#[derive(Debug, PartialEq, Serialize)]
+pub(crate) struct SimulationRow {
+    #[serde(rename = "Date")]
+    date: String,
+
+    #[serde(rename = "Currency")]
+    currency: Currency,
+
+    #[serde(rename = "Amount")]
+    amount: Decimal,
+
+    #[serde(rename = "Price")]
+    price: Decimal,
+
+    #[serde(rename = "Income")]
+    income: Decimal,
+
+    #[serde(rename = "Cost")]
+    cost: Option<Decimal>,              // None if part of it is not valued in the base currency
+
+    #[serde(rename = "Net Income")]
+    net_income: Option<Decimal>,        // Of the taxable part
+
+    #[serde(rename = "Exempt")]
+    exempt: Option<Decimal>,            // The net income that is tax free, see `Jurisdiction::exempts_long_term_gains`
+
+    #[serde(rename = "Estimated Tax")]
+    estimated_tax: Option<Decimal>,     // Added to the year of the sale, see `TaxRate::tax_of`
+}
+
+impl SimulationRow {
+    /// Rounds the amounts in the base currency for a report.
+    pub(crate) fn round(&mut self, rounding: &Rounding) {
+        self.income = rounding.gain(self.income);
+        self.cost = self.cost.map(|c| rounding.gain(c));
+        self.net_income = self.net_income.map(|n| rounding.gain(n));
+        self.exempt = self.exempt.map(|e| rounding.gain(e));
+        self.estimated_tax = self.estimated_tax.map(|t| rounding.tax(t));
+    }
+}
+
+/// A sale of `amount` of the currency for the base currency at `price`, dated now, or right at
+/// the last transaction if that is later.
+pub(crate) fn sale(txns: &[Transaction], currency: &Currency, amount: Decimal, price: Decimal, base: &Currency) -> Result<Transaction> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or_default();
+    let now = DateTime::from_timestamp(now, 0).map(|d| d.format("%Y-%m-%d %H:%M:%S").to_string()).unwrap_or_default();
+    let date = txns.iter().map(|t| t.date.clone()).max().filter(|last| *last > now).unwrap_or(now);
+    let mut origin = Origin::new(0, "Simulated sale".to_string());
+    origin.file = SIMULATION.to_string();
+    let value = decimal::mul(amount.abs(), price)?;
+    Ok(Transaction{
+        r#type: TransactionType::Sell,
+        paid_currency: currency.clone(),
+        paid_amount: -amount.abs(),
+        exchanged_currency: base.clone(),
+        exchanged_amount: value,
+        date,
+        unit_price: Some(price),
+        fiat_value: Some(value),
+        origin,
+        ..Transaction::new()
+    })
+}
+
+/// What the simulated `sale` realizes in the calculation of the holdings with it, and the tax it
+/// adds to the realized gains of its year, so that the rules for losses apply to the year as a
+/// whole.
+pub(crate) fn report(sale: &Transaction, calculation: &Calculation, tax_rate: Option<TaxRate>) -> Result<SimulationRow> {
+    let is_simulated = |t: &&TaxableTransaction| t.origin.file == SIMULATION;
+    let taxables: Vec<&TaxableTransaction> = calculation.taxables.iter().filter(is_simulated).collect();
+    let exempt: Vec<&TaxableTransaction> = calculation.exempt.iter().filter(is_simulated).collect();
+    let costs: Vec<&Money> = taxables.iter().chain(&exempt).flat_map(|t| &t.costs).collect();
+    let cost = match costs.iter().all(|c| c.is_cash()) {
+        true => Some(costs.iter().fold(dec!(0), |acc, c| acc + c.amount())),
+        false => None,
+    };
+    let net_income = sum_net_income(&taxables);
+    let year = &sale.date[..4];
+    let before = calculation.taxables.iter()
+        .filter(|t| t.date.starts_with(year) && t.origin.file != SIMULATION)
+        .fold(dec!(0), |acc, t| acc + t.net_income.unwrap_or_default());
+    let estimated_tax = match tax_rate.zip(net_income) {
+        Some((rate, net_income)) => Some(rate.tax_of(before + net_income)? - rate.tax_of(before)?),
+        None => None,
+    };
+    Ok(SimulationRow{
+        date: sale.date.clone(),
+        currency: sale.paid_currency.clone(),
+        amount: sale.paid_amount,
+        price: sale.unit_price.unwrap_or_default(),
+        income: sale.exchanged_amount,
+        cost,
+        net_income,
+        exempt: match exempt.is_empty() {
+            true => None,
+            false => sum_net_income(&exempt),
+        },
+        estimated_tax,
+    })
+}
+
+/// The net income of the disposals, `None` if any is not valued in the base currency.
+fn sum_net_income(txns: &[&TaxableTransaction]) -> Option<Decimal> {
+    txns.iter().try_fold(dec!(0), |acc, t| t.net_income.map(|n| acc + n))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::calculator;
+    use crate::cryptotax::Options;
+    use crate::simulate::*;
+    use futures::executor::block_on;
+    use std::error::Error;
+
+    #[test]
+    fn should_simulate_a_sale_of_the_holding() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let buy = |amount, cost, date: &str| {
+            Transaction::buy().currency("BTC").amount(amount).exchanged_currency("SEK").exchanged_amount(cost).date(date).build()
+        };
+        let mut txns = vec![
+            buy(dec!(1), dec!(-1000), "2021-01-01 10:00:00")?,
+            buy(dec!(1), dec!(-3000), "2021-02-01 10:00:00")?,
+        ];
+        let sale = sale(&txns, &"BTC".to_string(), dec!(0.5), dec!(5000), &"SEK".to_string())?;
+        txns.push(sale.clone());
+        let calculation = block_on(calculator::calculate(&txns, &"BTC".to_string(), &"SEK".to_string(), &Options::default()))?;
+
+        /*
+         * When
+         */
+        let row = report(&sale, &calculation, Some(TaxRate{ rate: dec!(30), deductible_losses: dec!(70) }))?;
+
+        /*
+         * Then
+         */
+        assert!(sale.date.as_str() > "2021-02-01 10:00:00");
+        assert_eq!((row.amount, row.income, row.cost), (dec!(-0.5), dec!(2500), Some(dec!(-1000))));
+        assert_eq!((row.net_income, row.exempt, row.estimated_tax), (Some(dec!(1500)), None, Some(dec!(450))));
+        Ok(())
+    }
+}
@@ -0,0 +1,51 @@
+use crate::cryptotax::Options;
+use crate::jurisdiction::StablecoinGains;
+use crate::transaction::Currency;
+
+/// The stablecoins whose gains can be treated as negligible, unless `Options::stablecoins` is set.
+pub(crate) const DEFAULTS: [&str; 3] = ["USDC", "USDT", "EURS"];
+
+/// Whether the gains of disposing of the currency are negligible: it is one of the stablecoins
+/// of `Options::stablecoins`, or of `DEFAULTS`, and their gains are `StablecoinGains::Negligible`
+/// with `Options::stablecoin_gains` or in the jurisdiction.
+pub(crate) fn is_negligible(currency: &Currency, options: &Options) -> bool {
+    let gains = options.stablecoin_gains.unwrap_or(options.jurisdiction.stablecoin_gains());
+    let is_stablecoin = match options.stablecoins.is_empty() {
+        true => DEFAULTS.contains(&currency.as_str()),
+        false => options.stablecoins.contains(currency),
+    };
+    gains == StablecoinGains::Negligible && is_stablecoin
+}
+
+#[cfg(test)]
+mod test {
+    use crate::stablecoin::*;
+    use crate::jurisdiction::Jurisdiction;
+    use std::error::Error;
+
+    #[test]
+    fn should_treat_stablecoin_gains_as_negligible_if_configured() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let precise = Options{ jurisdiction: Jurisdiction::Sweden, ..Default::default() };
+        let negligible = Options{ stablecoin_gains: Some(StablecoinGains::Negligible), ..Default::default() };
+        let custom = Options{ stablecoin_gains: Some(StablecoinGains::Negligible), stablecoins: vec!["DAI".to_string()], ..Default::default() };
+
+        /*
+         * When
+         */
+        let usdc = |options: &Options| is_negligible(&"USDC".to_string(), options);
+        let dai = |options: &Options| is_negligible(&"DAI".to_string(), options);
+
+        /*
+         * Then
+         */
+        assert!(!usdc(&precise));
+        assert!(usdc(&negligible));
+        assert!(!dai(&negligible));
+        assert!(!usdc(&custom));
+        assert!(dai(&custom));
+        Ok(())
+    }
+}
@@ -0,0 +1,697 @@
+use crate::cryptotax::StoreAccess;
+use crate::error::{CryptotaxError, Result};
+use crate::transaction::{self, Annotation, Currency, Transaction, TransactionType};
+use rusqlite::{params, Connection, OptionalExtension};
+use rust_decimal::Decimal;
+use serde::de::IntoDeserializer;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+use std::str::FromStr;
+use tracing::debug;
+
+/// The first bytes of every SQLite database file.
+const MAGIC: &str = "SQLite format 3\0";
+
+/// A local SQLite database of imported transactions. Each transaction is identified by the
+/// hash of its content, so that importing overlapping export files adds every transaction once.
+///
+/// The store also keeps snapshots of the costs at the end of a year, as the buys that carry them
+/// over to the next year, see `Calculation::carryover`, and caches the fetched reference rates of
+/// fiat currencies, see `fx::FxRates`.
+pub(crate) struct Store {
+    conn: Connection,
+    portfolio: String,
+}
+
+/// How many transactions were added to the store, and how many were already stored.
+#[derive(Debug, PartialEq, Serialize)]
+pub(crate) struct Imported {
+    #[serde(rename = "Currency")]
+    pub(crate) currency: Currency,
+
+    #[serde(rename = "Added")]
+    pub(crate) added: usize,
+
+    #[serde(rename = "Skipped")]
+    pub(crate) skipped: usize,
+}
+
+/// A saved snapshot of the costs of a currency at the end of a year.
+#[derive(Debug, PartialEq, Serialize)]
+pub(crate) struct Snapshot {
+    #[serde(rename = "Currency")]
+    pub(crate) currency: Currency,
+
+    #[serde(rename = "Year")]
+    pub(crate) year: String,
+
+    #[serde(rename = "Amount")]
+    pub(crate) amount: Decimal,
+
+    #[serde(rename = "Costs")]
+    pub(crate) costs: usize,
+}
+
+/// Whether the sample of a file is a store, either a SQLite file, or an encrypted one, whose
+/// bytes are not text, if `access` has a passphrase.
+pub(crate) fn is_store(sample: &str, access: &StoreAccess) -> bool {
+    let is_unlocked = access.passphrase.is_some();
+    sample.starts_with(MAGIC) || (is_unlocked && sample.chars().take(16).any(|c| c == char::REPLACEMENT_CHARACTER))
+}
+
+/// Whether the file is there, and is not a plain SQLite file.
+fn is_encrypted(path: &Path) -> Result<bool> {
+    let mut head = vec![];
+    match File::open(path) {
+        Ok(file) => file.take(MAGIC.len() as u64).read_to_end(&mut head)?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(e.into()),
+    };
+    Ok(!head.is_empty() && !head.starts_with(MAGIC.as_bytes()))
+}
+
+#[cfg(feature = "encryption")]
+fn set_key(conn: &Connection, passphrase: &str) -> Result<()> {
+    conn.pragma_update(None, "key", passphrase).map_err(io::Error::other)?;
+    conn.query_row("SELECT count(*) FROM sqlite_master", [], |row| row.get::<_, i64>(0)).map_err(|_| {
+        CryptotaxError::Config("Wrong passphrase, or the store is not encrypted".to_string())
+    })?;
+    Ok(())
+}
+
+#[cfg(not(feature = "encryption"))]
+fn set_key(_: &Connection, _: &str) -> Result<()> {
+    let msg = "Encrypted stores need cryptotax built with `--features encryption`";
+    Err(CryptotaxError::Config(msg.to_string()))
+}
+
+/// The columns of a `Transaction`, in the order of its fields.
+const COLUMNS: &str = "type, paid_currency, paid_amount, exchanged_currency, exchanged_amount, date, is_vault, paid_fee, exchanged_fee";
+
+impl Store {
+    /// Opens the store at path, creating it if it doesn't exist. The store is encrypted with the
+    /// passphrase of `access`, if it has one, and its transactions and snapshots are the ones of
+    /// the portfolio of `access`.
+    pub(crate) fn open(path: &Path, access: &StoreAccess) -> Result<Store> {
+        let passphrase = &access.passphrase;
+        if passphrase.is_none() && is_encrypted(path)? {
+            let msg = format!("The store `{}` is encrypted. Give its passphrase with --store-key-file or --store-passphrase", path.display());
+            return Err(CryptotaxError::Config(msg));
+        }
+        let conn = Connection::open(path).map_err(io::Error::other)?;
+        if let Some(passphrase) = passphrase {
+            set_key(&conn, passphrase)?;
+        }
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS transactions (
+                hash                TEXT PRIMARY KEY,
+                type                TEXT NOT NULL,
+                paid_currency       TEXT NOT NULL,
+                paid_amount         TEXT NOT NULL,
+                exchanged_currency  TEXT NOT NULL,
+                exchanged_amount    TEXT NOT NULL,
+                date                TEXT NOT NULL,
+                is_vault            INTEGER NOT NULL,
+                paid_fee            TEXT NOT NULL,
+                exchanged_fee       TEXT NOT NULL,
+                portfolio           TEXT NOT NULL DEFAULT ''
+            );
+            CREATE INDEX IF NOT EXISTS transactions_paid_currency ON transactions (paid_currency);
+            CREATE TABLE IF NOT EXISTS snapshots (
+                currency            TEXT NOT NULL,
+                year                TEXT NOT NULL,
+                portfolio           TEXT NOT NULL DEFAULT '',
+                PRIMARY KEY (portfolio, currency, year)
+            );
+            CREATE TABLE IF NOT EXISTS snapshot_costs (
+                currency            TEXT NOT NULL,
+                year                TEXT NOT NULL,
+                type                TEXT NOT NULL,
+                paid_currency       TEXT NOT NULL,
+                paid_amount         TEXT NOT NULL,
+                exchanged_currency  TEXT NOT NULL,
+                exchanged_amount    TEXT NOT NULL,
+                date                TEXT NOT NULL,
+                is_vault            INTEGER NOT NULL,
+                paid_fee            TEXT NOT NULL,
+                exchanged_fee       TEXT NOT NULL,
+                portfolio           TEXT NOT NULL DEFAULT ''
+            );
+            CREATE TABLE IF NOT EXISTS annotations (
+                currency            TEXT NOT NULL,
+                date                TEXT NOT NULL,
+                type                TEXT,
+                note                TEXT,
+                tags                TEXT NOT NULL DEFAULT '',
+                counterparty        TEXT,
+                portfolio           TEXT NOT NULL DEFAULT '',
+                PRIMARY KEY (portfolio, currency, date)
+            );
+            CREATE TABLE IF NOT EXISTS fx_rates (
+                source              TEXT NOT NULL,
+                currency            TEXT NOT NULL,
+                date                TEXT NOT NULL,
+                rate                TEXT NOT NULL,
+                PRIMARY KEY (source, currency, date)
+            );"
+        ).map_err(io::Error::other)?;
+        migrate_portfolios(&conn)?;
+        Ok(Store{ conn, portfolio: access.portfolio.clone() })
+    }
+
+    /// Adds the transactions that are not stored yet. Identical transactions in `txns`, e.g. two
+    /// buys of the same amount in the same second, are told apart by their order.
+    pub(crate) fn add(&mut self, currency: &Currency, txns: &[Transaction]) -> Result<Imported> {
+        let tx = self.conn.transaction().map_err(io::Error::other)?;
+        let mut seen: HashMap<String, usize> = HashMap::new();
+        let mut added = 0;
+        {
+            let mut stmt = tx.prepare(
+                &format!("INSERT OR IGNORE INTO transactions (hash, {}, portfolio) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)", COLUMNS)
+            ).map_err(io::Error::other)?;
+            for t in txns {
+                // The default portfolio keeps the hashes of the stores from before portfolios
+                let content = match self.portfolio.is_empty() {
+                    true => content_of(t),
+                    false => format!("{};{}", self.portfolio, content_of(t)),
+                };
+                let occurrence = seen.entry(content.clone()).or_insert(0);
+                *occurrence += 1;
+                let hash = hash_of(&content, *occurrence);
+                added += stmt.execute(params![
+                    hash,
+                    format!("{:?}", t.r#type),
+                    t.paid_currency,
+                    t.paid_amount.to_string(),
+                    t.exchanged_currency,
+                    t.exchanged_amount.to_string(),
+                    t.date,
+                    t.is_vault,
+                    t.paid_fee.to_string(),
+                    t.exchanged_fee.to_string(),
+                    self.portfolio,
+                ]).map_err(io::Error::other)?;
+            }
+        }
+        tx.commit().map_err(io::Error::other)?;
+        debug!(added, total = txns.len(), %currency, "Added transactions");
+        Ok(Imported{ currency: currency.clone(), added, skipped: txns.len() - added })
+    }
+
+    /// Reads the stored transactions in the target currency, or of every NFT for `NFT`, sorted by
+    /// date, with the notes and tags they were annotated with.
+    pub(crate) fn transactions(&self, currency: &Currency) -> Result<Vec<Transaction>> {
+        let mut txns = match currency == transaction::NFT {
+            true => {
+                let sql = format!("SELECT {} FROM transactions WHERE paid_currency LIKE '0x%:%' AND portfolio = ?1", COLUMNS);
+                self.query_transactions(&sql, params![self.portfolio])?
+            }
+            false => {
+                let sql = format!("SELECT {} FROM transactions WHERE paid_currency = ?1 AND portfolio = ?2", COLUMNS);
+                self.query_transactions(&sql, params![currency, self.portfolio])?
+            }
+        };
+        let mut stmt = self.conn
+            .prepare("SELECT currency, date, type, note, tags, counterparty FROM annotations WHERE portfolio = ?1")
+            .map_err(io::Error::other)?;
+        let annotations = stmt
+            .query_map(params![self.portfolio], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, annotation_of(row, 2)?))
+            })
+            .and_then(|rows| rows.collect::<rusqlite::Result<Vec<_>>>())
+            .map_err(io::Error::other)?;
+        for (currency, date, annotation) in annotations {
+            txns.iter_mut()
+                .filter(|t| t.paid_currency == currency && t.date == date)
+                .for_each(|t| annotation.apply(t));
+        }
+        Ok(txns)
+    }
+
+    /// Annotates the stored transactions in the currency at the date, in addition to what they
+    /// were annotated with before, see `Annotation::then`.
+    pub(crate) fn annotate(&mut self, currency: &Currency, date: &str, annotation: &Annotation) -> Result<()> {
+        let exists = self.conn
+            .prepare("SELECT 1 FROM transactions WHERE paid_currency = ?1 AND date = ?2 AND portfolio = ?3")
+            .and_then(|mut stmt| stmt.exists(params![currency, date, self.portfolio]))
+            .map_err(io::Error::other)?;
+        if !exists {
+            let msg = format!("No transaction in {} at {} is stored", currency, date);
+            return Err(CryptotaxError::Config(msg));
+        }
+        let stored = self.conn
+            .query_row("SELECT type, note, tags, counterparty FROM annotations WHERE portfolio = ?1 AND currency = ?2 AND date = ?3",
+                       params![self.portfolio, currency, date],
+                       |row| annotation_of(row, 0))
+            .optional()
+            .map_err(io::Error::other)?
+            .unwrap_or_default();
+        let annotation = stored.then(annotation);
+        self.conn
+            .execute("INSERT OR REPLACE INTO annotations (currency, date, type, note, tags, counterparty, portfolio) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                     params![
+                         currency,
+                         date,
+                         annotation.r#type.as_ref().map(|t| format!("{:?}", t)),
+                         annotation.note,
+                         annotation.tags.join(" "),
+                         annotation.counterparty,
+                         self.portfolio,
+                     ])
+            .map_err(io::Error::other)?;
+        debug!(%currency, date, "Annotated transactions");
+        Ok(())
+    }
+
+    /// Saves the costs of the currency at the end of the year, replacing any earlier snapshot of
+    /// the same year.
+    pub(crate) fn save_snapshot(&mut self, currency: &Currency, year: &str, carryover: &[Transaction]) -> Result<()> {
+        let tx = self.conn.transaction().map_err(io::Error::other)?;
+        tx.execute("DELETE FROM snapshot_costs WHERE currency = ?1 AND year = ?2 AND portfolio = ?3", params![currency, year, self.portfolio])
+            .map_err(io::Error::other)?;
+        tx.execute("INSERT OR IGNORE INTO snapshots (currency, year, portfolio) VALUES (?1, ?2, ?3)", params![currency, year, self.portfolio])
+            .map_err(io::Error::other)?;
+        {
+            let sql = format!("INSERT INTO snapshot_costs (currency, year, {}, portfolio) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)", COLUMNS);
+            let mut stmt = tx.prepare(&sql).map_err(io::Error::other)?;
+            for t in carryover {
+                stmt.execute(params![
+                    currency,
+                    year,
+                    format!("{:?}", t.r#type),
+                    t.paid_currency,
+                    t.paid_amount.to_string(),
+                    t.exchanged_currency,
+                    t.exchanged_amount.to_string(),
+                    t.date,
+                    t.is_vault,
+                    t.paid_fee.to_string(),
+                    t.exchanged_fee.to_string(),
+                    self.portfolio,
+                ]).map_err(io::Error::other)?;
+            }
+        }
+        tx.commit().map_err(io::Error::other)?;
+        Ok(())
+    }
+
+    /// Reads the costs saved in the snapshot of the currency at the end of the year, as the buys
+    /// that carry them over. Returns `None` if there is no such snapshot.
+    pub(crate) fn snapshot(&self, currency: &Currency, year: &str) -> Result<Option<Vec<Transaction>>> {
+        let exists = self.conn
+            .prepare("SELECT 1 FROM snapshots WHERE currency = ?1 AND year = ?2 AND portfolio = ?3")
+            .and_then(|mut stmt| stmt.exists(params![currency, year, self.portfolio]))
+            .map_err(io::Error::other)?;
+        if !exists {
+            return Ok(None);
+        }
+        let sql = format!("SELECT {} FROM snapshot_costs WHERE currency = ?1 AND year = ?2 AND portfolio = ?3", COLUMNS);
+        self.query_transactions(&sql, params![currency, year, self.portfolio]).map(Some)
+    }
+
+    /// Lists the saved snapshots, sorted by currency and year.
+    pub(crate) fn snapshots(&self) -> Result<Vec<Snapshot>> {
+        let mut stmt = self.conn
+            .prepare("SELECT currency, year FROM snapshots WHERE portfolio = ?1 ORDER BY currency, year")
+            .map_err(io::Error::other)?;
+        let keys = stmt
+            .query_map(params![self.portfolio], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))
+            .and_then(|rows| rows.collect::<Result<Vec<(String, String)>, _>>())
+            .map_err(io::Error::other)?;
+        keys.into_iter()
+            .map(|(currency, year)| {
+                let carryover = self.snapshot(&currency, &year)?.unwrap_or_default();
+                let amount = carryover.iter().fold(Decimal::ZERO, |acc, t| acc + t.paid_amount);
+                Ok(Snapshot{ currency, year, amount, costs: carryover.len() })
+            })
+            .collect()
+    }
+
+    /// Saves the rates of the currency per date, replacing the ones already saved.
+    pub(crate) fn save_fx_rates(&mut self, source: &str, currency: &Currency, rates: &[(String, Decimal)]) -> Result<()> {
+        let tx = self.conn.transaction().map_err(io::Error::other)?;
+        {
+            let mut stmt = tx.prepare("INSERT OR REPLACE INTO fx_rates VALUES (?1, ?2, ?3, ?4)")
+                .map_err(io::Error::other)?;
+            for (date, rate) in rates {
+                stmt.execute(params![source, currency, date, rate.to_string()]).map_err(io::Error::other)?;
+            }
+        }
+        tx.commit().map_err(io::Error::other)?;
+        debug!(rates = rates.len(), %source, %currency, "Saved rates");
+        Ok(())
+    }
+
+    /// Reads the latest saved rate of the currency on or before the date.
+    pub(crate) fn fx_rate(&self, source: &str, currency: &Currency, date: &str) -> Result<Option<Decimal>> {
+        let rate = self.conn
+            .query_row(
+                "SELECT rate FROM fx_rates WHERE source = ?1 AND currency = ?2 AND date <= ?3 ORDER BY date DESC LIMIT 1",
+                params![source, currency, date],
+                |row| row.get::<_, String>(0),
+            )
+            .optional()
+            .map_err(io::Error::other)?;
+        rate.as_deref().map(to_decimal).transpose()
+    }
+
+    /// Whether a rate of the currency after the date is saved.
+    pub(crate) fn has_fx_rate_after(&self, source: &str, currency: &Currency, date: &str) -> Result<bool> {
+        self.conn
+            .prepare("SELECT 1 FROM fx_rates WHERE source = ?1 AND currency = ?2 AND date > ?3")
+            .and_then(|mut stmt| stmt.exists(params![source, currency, date]))
+            .map_err(|e| io::Error::other(e).into())
+    }
+
+    fn query_transactions<P: rusqlite::Params>(&self, sql: &str, params: P) -> Result<Vec<Transaction>> {
+        let mut stmt = self.conn.prepare(sql).map_err(io::Error::other)?;
+        let rows = stmt.query_map(params, |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?,
+                row.get::<_, String>(4)?,
+                row.get::<_, String>(5)?,
+                row.get::<_, bool>(6)?,
+                row.get::<_, String>(7)?,
+                row.get::<_, String>(8)?,
+            ))
+        }).map_err(io::Error::other)?;
+
+        let mut txns = vec![];
+        for row in rows {
+            let (r#type, paid_currency, paid_amount, exchanged_currency, exchanged_amount, date, is_vault, paid_fee, exchanged_fee) =
+                row.map_err(io::Error::other)?;
+            txns.push(Transaction{
+                r#type: TransactionType::deserialize(r#type.as_str().into_deserializer())
+                    .map_err(|e: serde::de::value::Error| io::Error::new(io::ErrorKind::InvalidData, e))?,
+                paid_currency,
+                paid_amount: to_decimal(&paid_amount)?,
+                exchanged_currency,
+                exchanged_amount: to_decimal(&exchanged_amount)?,
+                date,
+                is_vault,
+                paid_fee: to_decimal(&paid_fee)?,
+                exchanged_fee: to_decimal(&exchanged_fee)?,
+                price_source: None,
+                unit_price: None,
+                fiat_value: None,
+                other_fee: None,
+                origin: Default::default(),
+            });
+        }
+        txns.sort_by(Transaction::cmp_by_date);
+        Ok(txns)
+    }
+}
+
+/// Adds the portfolio to the tables of a store from before portfolios, whose transactions and
+/// snapshots are then in the default portfolio.
+fn migrate_portfolios(conn: &Connection) -> Result<()> {
+    let has_portfolio = |table: &str| -> rusqlite::Result<bool> {
+        conn.prepare(&format!("SELECT 1 FROM pragma_table_info('{}') WHERE name = 'portfolio'", table))?.exists([])
+    };
+    if !has_portfolio("transactions").map_err(io::Error::other)? {
+        conn.execute_batch("ALTER TABLE transactions ADD COLUMN portfolio TEXT NOT NULL DEFAULT ''")
+            .map_err(io::Error::other)?;
+    }
+    if !has_portfolio("snapshot_costs").map_err(io::Error::other)? {
+        conn.execute_batch("ALTER TABLE snapshot_costs ADD COLUMN portfolio TEXT NOT NULL DEFAULT ''")
+            .map_err(io::Error::other)?;
+    }
+    if !has_portfolio("snapshots").map_err(io::Error::other)? {
+        // The primary key can't be altered, so the table is copied
+        conn.execute_batch(
+            "BEGIN;
+            CREATE TABLE snapshots_with_portfolio (
+                currency            TEXT NOT NULL,
+                year                TEXT NOT NULL,
+                portfolio           TEXT NOT NULL DEFAULT '',
+                PRIMARY KEY (portfolio, currency, year)
+            );
+            INSERT INTO snapshots_with_portfolio (currency, year) SELECT currency, year FROM snapshots;
+            DROP TABLE snapshots;
+            ALTER TABLE snapshots_with_portfolio RENAME TO snapshots;
+            COMMIT;"
+        ).map_err(io::Error::other)?;
+    }
+    Ok(())
+}
+
+/// The annotation in the columns `type, note, tags, counterparty` of the row, from `first` on.
+fn annotation_of(row: &rusqlite::Row, first: usize) -> rusqlite::Result<Annotation> {
+    let r#type = row.get::<_, Option<String>>(first)?
+        .map(|t| TransactionType::deserialize(t.as_str().into_deserializer()))
+        .transpose()
+        .map_err(|e: serde::de::value::Error| rusqlite::Error::FromSqlConversionFailure(first, rusqlite::types::Type::Text, Box::new(e)))?;
+    Ok(Annotation{
+        r#type,
+        note: row.get(first + 1)?,
+        tags: row.get::<_, String>(first + 2)?.split_whitespace().map(String::from).collect(),
+        counterparty: row.get(first + 3)?,
+    })
+}
+
+fn to_decimal(s: &str) -> Result<Decimal> {
+    Decimal::from_str(s).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e).into())
+}
+
+fn content_of(t: &Transaction) -> String {
+    format!("{:?};{};{};{};{};{};{};{};{}",
+            t.r#type, t.paid_currency, t.paid_amount, t.exchanged_currency, t.exchanged_amount,
+            t.date, t.is_vault, t.paid_fee, t.exchanged_fee)
+}
+
+fn hash_of(content: &str, occurrence: usize) -> String {
+    let digest = Sha256::new()
+        .chain_update(content)
+        .chain_update(format!(";{}", occurrence))
+        .finalize();
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::store::*;
+    use rust_decimal_macros::dec;
+    use std::error::Error;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn should_skip_stored_transactions() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let file = NamedTempFile::new()?;
+        let mut store = Store::open(file.path(), &StoreAccess::default())?;
+        let buy = |date: &str| Transaction{
+            r#type: TransactionType::Buy,
+            paid_currency: "BTC".to_string(),
+            paid_amount: dec!(0.01),
+            exchanged_currency: "SEK".to_string(),
+            exchanged_amount: dec!(-3010.5),
+            date: date.to_string(),
+            is_vault: false,
+            paid_fee: dec!(0),
+            exchanged_fee: dec!(-10.5),
+            price_source: None,
+            unit_price: None,
+            fiat_value: None,
+            other_fee: None,
+            origin: Default::default()
+        };
+        let january = vec![buy("2022-01-01 10:00:00"), buy("2022-01-01 10:00:00")];
+        let february = vec![buy("2022-01-01 10:00:00"), buy("2022-01-01 10:00:00"), buy("2022-02-01 10:00:00")];
+
+        /*
+         * When
+         */
+        let first = store.add(&"BTC".to_string(), &january)?;
+        let second = store.add(&"BTC".to_string(), &february)?;
+
+        /*
+         * Then
+         */
+        assert_eq!(first, Imported{ currency: "BTC".to_string(), added: 2, skipped: 0 });
+        assert_eq!(second, Imported{ currency: "BTC".to_string(), added: 1, skipped: 2 });
+        assert_eq!(store.transactions(&"BTC".to_string())?, february);
+        assert_eq!(store.transactions(&"ETH".to_string())?, vec![]);
+        Ok(())
+    }
+
+    #[test]
+    fn should_save_snapshot() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let file = NamedTempFile::new()?;
+        let mut store = Store::open(file.path(), &StoreAccess::default())?;
+        let carryover = vec![
+            Transaction{
+                r#type: TransactionType::Buy,
+                paid_currency: "BTC".to_string(),
+                paid_amount: dec!(0.5),
+                exchanged_currency: "SEK".to_string(),
+                exchanged_amount: dec!(-15000),
+                date: "2021-12-01 10:00:00".to_string(),
+                is_vault: false,
+                paid_fee: dec!(0),
+                exchanged_fee: dec!(-150),
+                price_source: None,
+                unit_price: None,
+                fiat_value: None,
+                other_fee: None,
+                origin: Default::default()
+            },
+        ];
+
+        /*
+         * When
+         */
+        store.save_snapshot(&"BTC".to_string(), "2021", &carryover)?;
+        store.save_snapshot(&"BTC".to_string(), "2021", &carryover)?;
+        store.save_snapshot(&"ETH".to_string(), "2021", &[])?;
+
+        /*
+         * Then
+         */
+        assert_eq!(store.snapshot(&"BTC".to_string(), "2021")?, Some(carryover));
+        assert_eq!(store.snapshot(&"BTC".to_string(), "2020")?, None);
+        assert_eq!(store.snapshot(&"ETH".to_string(), "2021")?, Some(vec![]));
+        assert_eq!(store.snapshots()?, vec![
+            Snapshot{ currency: "BTC".to_string(), year: "2021".to_string(), amount: dec!(0.5), costs: 1 },
+            Snapshot{ currency: "ETH".to_string(), year: "2021".to_string(), amount: dec!(0), costs: 0 },
+        ]);
+        Ok(())
+    }
+
+    #[test]
+    fn should_open_an_encrypted_store_only_with_its_passphrase() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("cryptotax.db");
+        let buy = Transaction::buy().currency("BTC").amount(dec!(1)).exchanged_currency("SEK").exchanged_amount(dec!(-1000)).date("2021-01-01 10:00:00").build()?;
+        let locked = NamedTempFile::new()?;
+        std::fs::write(locked.path(), [0xe1, 0xe1, 0x6f, 0xce, 0x98, 0x9d, 0x13, 0xe6])?;
+
+        /*
+         * When
+         */
+        let secret = StoreAccess{ passphrase: Some("secret".to_string()), ..Default::default() };
+        let without_passphrase = Store::open(locked.path(), &StoreAccess::default());
+        let stored = Store::open(&path, &secret).and_then(|mut store| {
+            store.add(&"BTC".to_string(), std::slice::from_ref(&buy))?;
+            store.transactions(&"BTC".to_string())
+        });
+
+        /*
+         * Then
+         */
+        assert!(matches!(without_passphrase, Err(CryptotaxError::Config(_))));
+        match cfg!(feature = "encryption") {
+            true => {
+                assert_eq!(stored?, vec![buy]);
+                assert!(is_encrypted(&path)?);
+                let wrong = StoreAccess{ passphrase: Some("wrong".to_string()), ..Default::default() };
+                assert!(Store::open(&path, &wrong).is_err());
+            }
+            false => assert!(stored.is_err()),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn should_keep_portfolios_apart() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let file = NamedTempFile::new()?;
+        let path = file.path().to_path_buf();
+        let buy = Transaction::buy().currency("BTC").amount(dec!(1)).exchanged_currency("SEK").exchanged_amount(dec!(-1000)).date("2021-01-01 10:00:00").build()?;
+        Store::open(&path, &StoreAccess::default())?.add(&"BTC".to_string(), std::slice::from_ref(&buy))?;
+
+        /*
+         * When
+         */
+        let mut spouse = Store::open(&path, &StoreAccess{ portfolio: "spouse".to_string(), ..Default::default() })?;
+        let imported = spouse.add(&"BTC".to_string(), &[buy.clone(), buy.clone()])?;
+        spouse.save_snapshot(&"BTC".to_string(), "2021", std::slice::from_ref(&buy))?;
+        let default = Store::open(&path, &StoreAccess::default())?;
+
+        /*
+         * Then
+         */
+        assert_eq!(imported.added, 2);
+        assert_eq!(spouse.transactions(&"BTC".to_string())?.len(), 2);
+        assert_eq!(default.transactions(&"BTC".to_string())?, vec![buy]);
+        assert_eq!(spouse.snapshots()?.len(), 1);
+        assert_eq!(default.snapshots()?, vec![]);
+        Ok(())
+    }
+
+    #[test]
+    fn should_annotate_stored_transactions() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let file = NamedTempFile::new()?;
+        let mut store = Store::open(file.path(), &StoreAccess::default())?;
+        let sell = Transaction::sell().currency("BTC").amount(dec!(-0.1)).exchanged_currency("SEK").exchanged_amount(dec!(5000)).date("2022-03-01 16:21:49").build()?;
+        store.add(&"BTC".to_string(), &[sell])?;
+
+        /*
+         * When
+         */
+        let business = Annotation{ note: Some("Paid a supplier".to_string()), tags: vec!["business".to_string()], ..Default::default() };
+        let invoice = Annotation{ r#type: Some(TransactionType::Gift), tags: vec!["invoice-17".to_string()], ..Default::default() };
+        store.annotate(&"BTC".to_string(), "2022-03-01 16:21:49", &business)?;
+        store.annotate(&"BTC".to_string(), "2022-03-01 16:21:49", &invoice)?;
+        let missing = store.annotate(&"BTC".to_string(), "2022-03-02 10:00:00", &business);
+
+        /*
+         * Then
+         */
+        let txns = store.transactions(&"BTC".to_string())?;
+        assert_eq!(txns[0].r#type, TransactionType::Gift);
+        assert_eq!(txns[0].origin.note, Some("Paid a supplier".to_string()));
+        assert_eq!(txns[0].origin.tags, vec!["business".to_string(), "invoice-17".to_string()]);
+        assert!(matches!(missing, Err(CryptotaxError::Config(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn should_migrate_a_store_from_before_portfolios() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let file = NamedTempFile::new()?;
+        let path = file.path().to_path_buf();
+        Connection::open(&path)?.execute_batch(&format!(
+            "CREATE TABLE transactions (hash TEXT PRIMARY KEY, {columns});
+            CREATE TABLE snapshots (currency TEXT NOT NULL, year TEXT NOT NULL, PRIMARY KEY (currency, year));
+            CREATE TABLE snapshot_costs (currency TEXT NOT NULL, year TEXT NOT NULL, {columns});
+            INSERT INTO transactions VALUES ('a', 'Buy', 'BTC', '1', 'SEK', '-1000', '2021-01-01 10:00:00', 0, '0', '0');
+            INSERT INTO snapshots VALUES ('BTC', '2021');",
+            columns = COLUMNS,
+        ))?;
+
+        /*
+         * When
+         */
+        let store = Store::open(&path, &StoreAccess::default())?;
+
+        /*
+         * Then
+         */
+        assert_eq!(store.transactions(&"BTC".to_string())?.len(), 1);
+        assert_eq!(store.snapshot(&"BTC".to_string(), "2021")?, Some(vec![]));
+        Ok(())
+    }
+}
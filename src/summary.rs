@@ -0,0 +1,723 @@
+use crate::calculator::{Calculation, Holding, TaxableTransaction};
+use crate::decimal;
+use crate::earn;
+use crate::error::Result;
+use crate::jurisdiction::{Jurisdiction, Threshold};
+use crate::price::PriceProvider;
+use crate::rounding::Rounding;
+use crate::transaction::{self, Currency, Money, TransactionType, NFT};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub(crate) struct SummaryRow {
+    #[serde(rename = "Year")]
+    year: Option<String>,
+
+    #[serde(rename = "Kind")]
+    kind: GainKind,
+
+    #[serde(rename = "Currency")]
+    currency: Currency,
+
+    #[serde(rename = "Amount")]
+    amount: Decimal,
+
+    #[serde(rename = "Income")]
+    income: Option<Decimal>,        // Market value of the holdings if unrealized
+
+    #[serde(rename = "Cost")]
+    cost: Option<Decimal>,
+
+    #[serde(rename = "Net Income")]
+    net_income: Option<Decimal>,
+
+    #[serde(rename = "Unpriced")]
+    unpriced: usize,                // Sales or costs not in the base currency, not included above
+
+    #[serde(rename = "Price Source")]
+    price_source: Option<String>,   // Of the market value of the holdings, see `PriceProvider`
+}
+
+impl SummaryRow {
+    /// Rounds the amounts in the base currency for a report, recalculating the net income from
+    /// the rounded income and cost. The net income of an estimated tax is the tax, see
+    /// `Rounding::tax`.
+    pub(crate) fn round(&mut self, rounding: &Rounding) -> Result<()> {
+        if self.kind == GainKind::EstimatedTax {
+            self.net_income = self.net_income.map(|t| rounding.tax(t));
+            return Ok(());
+        }
+        self.income = self.income.map(|i| rounding.gain(i));
+        self.cost = self.cost.map(|c| rounding.gain(c));
+        self.net_income = match (self.income, self.cost) {
+            (Some(income), Some(cost)) if self.net_income.is_some() => Some(decimal::add(income, cost)?),
+            _ => self.net_income.map(|n| rounding.gain(n)),
+        };
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Serialize)]
+enum GainKind {
+    Realized,
+    Purchase,       // Realized on paying for goods or services, e.g. by card, see `TaxableTransaction::is_purchase`
+    Exempt,         // Realized, but tax free, e.g. held for more than a year in Germany
+    Unrealized,
+    Vault,          // The part of the unrealized that is held in vaults, e.g. of Revolut round-ups
+    Gift,           // The cost is carried over to the recipient
+    Donation,
+    Lost,           // The cost is written off without being claimed as a loss
+    Stolen,
+    Margin,         // Profits and losses of margin and futures positions
+    Futures,
+    Short,          // Profits and losses of short positions, see `short::separate`
+    Income,         // Rewards and cashback, at their market value when received
+    Interest,       // Income of savings and earn accounts, see `earn::is_interest`
+    Nft,            // Realized on NFTs, each at its own cost
+    Taxable,        // The realized gains of the year that are taxed, see `Jurisdiction::threshold`
+    EstimatedTax,   // Of the taxable gains of the year, see `TaxRate`
+}
+
+/// The rate that the realized gains are taxed at, and the share of a net loss of a year that is
+/// deducted, both in percent, for the estimated tax of the summary.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub(crate) struct TaxRate {
+    pub(crate) rate: Decimal,
+    pub(crate) deductible_losses: Decimal,
+}
+
+impl TaxRate {
+    /// The tax of the net gain of a year at the rate, or the deductible share of a net loss at the
+    /// rate, as a negative tax that reduces the tax on other income.
+    pub(crate) fn tax_of(&self, net_income: Decimal) -> Result<Decimal> {
+        let taxed = match net_income.is_sign_negative() {
+            true => decimal::share(net_income, self.deductible_losses, dec!(100))?,
+            false => net_income,
+        };
+        decimal::share(taxed, self.rate, dec!(100))
+    }
+}
+
+/// How finely the realized gains are broken down within a year, e.g. for the preliminary tax
+/// payments that some jurisdictions require during the year.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum Breakdown {
+    /// A row per year only.
+    #[default]
+    Year,
+    /// A row per quarter after the row of its year, e.g. `2022-Q1`.
+    Quarter,
+    /// A row per month after the row of its year, e.g. `2022-03`.
+    Month,
+}
+
+impl Breakdown {
+    /// The period of the breakdown that a date is in, or `None` for `Breakdown::Year`.
+    fn period_of(&self, date: &str) -> Option<String> {
+        let (year, month) = (date.get(..4)?, date.get(5..7)?);
+        match self {
+            Breakdown::Year => None,
+            Breakdown::Quarter => Some(format!("{}-Q{}", year, month.parse::<u32>().ok()?.div_ceil(3))),
+            Breakdown::Month => Some(format!("{}-{}", year, month)),
+        }
+    }
+}
+
+impl FromStr for Breakdown {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_uppercase().as_str() {
+            "YEAR" => Ok(Breakdown::Year),
+            "QUARTER" => Ok(Breakdown::Quarter),
+            "MONTH" => Ok(Breakdown::Month),
+            _ => Err(format!("Unknown breakdown `{}`. Supported: YEAR, QUARTER, MONTH", s)),
+        }
+    }
+}
+
+impl fmt::Display for Breakdown {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Breakdown::Year => write!(f, "YEAR"),
+            Breakdown::Quarter => write!(f, "QUARTER"),
+            Breakdown::Month => write!(f, "MONTH"),
+        }
+    }
+}
+
+/// Sums up the realized gains of the taxable transactions per year, and per quarter or month of
+/// the `breakdown`, and the costs of the non-taxable disposals such as gifts per year. Applies the
+/// tax free threshold of the jurisdiction of `thresholds` to the realized gains per year, if any,
+/// and estimates the tax of what is taxed at the `tax_rate`, if any. Calculates the unrealized
+/// gains of the holding using the latest price from `prices`.
+pub(crate) async fn summarize(
+    calculation: &Calculation,
+    base: &Currency,
+    prices: &dyn PriceProvider,
+    breakdown: Breakdown,
+    tax_rate: Option<TaxRate>,
+    thresholds: Option<Jurisdiction>,
+) -> Result<Vec<SummaryRow>> {
+    let mut rows = realized(&calculation.taxables, GainKind::Realized, breakdown)?;
+    let taxable = taxable(&realized(&calculation.taxables, GainKind::Realized, Breakdown::Year)?, thresholds)?;
+    let estimated = tax_rate.map(|tax_rate| estimated_tax(&taxable, &tax_rate)).transpose()?.unwrap_or_default();
+    rows.extend(taxable.into_iter().filter(|row| threshold_of(row, thresholds).is_some()));
+    rows.extend(estimated);
+    rows.extend(realized(&calculation.exempt, GainKind::Exempt, breakdown)?);
+    rows.extend(non_taxable(&calculation.non_taxables)?);
+    rows.extend(received(&calculation.derivatives)?);
+    rows.extend(received(&calculation.income)?);
+    let holding = &calculation.holding;
+    rows.push(unrealized(GainKind::Unrealized, holding, holding.amount, &holding.costs, base, prices)?);
+    if !holding.vault.is_zero() {
+        rows.push(unrealized(GainKind::Vault, holding, holding.vault, &holding.vault_costs, base, prices)?);
+    }
+    Ok(rows)
+}
+
+/// Sums up the disposals per year, and per period of the `breakdown`. A period sorts right after
+/// its year, e.g. `2022`, `2022-Q1`, `2022-Q2`.
+fn realized(txns: &[TaxableTransaction], kind: GainKind, breakdown: Breakdown) -> Result<Vec<SummaryRow>> {
+    let periods = txns.iter()
+        .try_fold(BTreeMap::new(), |mut periods, t| -> Result<_> {
+            let kind = match kind {
+                GainKind::Realized if transaction::is_nft(&t.currency) => GainKind::Nft,
+                GainKind::Realized if t.is_purchase() => GainKind::Purchase,
+                kind => kind,
+            };
+            for period in std::iter::once(year_of(t)).chain(breakdown.period_of(&t.date)) {
+                let row = periods.entry((period.clone(), kind)).or_insert_with(|| SummaryRow{
+                    year: Some(period),
+                    kind,
+                    currency: currency_of(t),
+                    amount: dec!(0),
+                    income: Some(dec!(0)),
+                    cost: Some(dec!(0)),
+                    net_income: Some(dec!(0)),
+                    unpriced: 0,
+                    price_source: None,
+                });
+                row.amount = decimal::add(row.amount, t.amount)?;
+                match t.net_income {
+                    Some(net_income) => {
+                        row.income = add_to(row.income, t.income.amount())?;
+                        row.cost = add_to(row.cost, sum(&t.costs)?)?;
+                        row.net_income = add_to(row.net_income, net_income)?;
+                    }
+                    None => row.unpriced += 1,
+                }
+            }
+            Ok(periods)
+        })?;
+    Ok(periods.into_values().collect())
+}
+
+/// Sums up the realized gains of each year, of the trades and the purchases together, and applies
+/// the threshold of the year in the jurisdiction of `thresholds` to the net income, see
+/// `Threshold::taxable`. The years without a threshold are taxed in full. The disposals that are
+/// not valued in the base currency are left out, and counted in `unpriced`.
+fn taxable(realized: &[SummaryRow], thresholds: Option<Jurisdiction>) -> Result<Vec<SummaryRow>> {
+    let years = realized.iter()
+        .try_fold(BTreeMap::new(), |mut years, row| -> Result<_> {
+            let year = years.entry(row.year.clone()).or_insert_with(|| SummaryRow{
+                year: row.year.clone(),
+                kind: GainKind::Taxable,
+                currency: row.currency.clone(),
+                amount: dec!(0),
+                income: None,
+                cost: None,
+                net_income: Some(dec!(0)),
+                unpriced: 0,
+                price_source: None,
+            });
+            year.amount = decimal::add(year.amount, row.amount)?;
+            year.net_income = add_to(year.net_income, row.net_income.unwrap_or_default())?;
+            year.unpriced += row.unpriced;
+            Ok(years)
+        })?;
+    Ok(years.into_values()
+        .map(|year| {
+            let net_income = match threshold_of(&year, thresholds) {
+                Some(threshold) => year.net_income.map(|n| threshold.taxable(n)),
+                None => year.net_income,
+            };
+            SummaryRow{ net_income, ..year }
+        })
+        .collect())
+}
+
+fn threshold_of(row: &SummaryRow, thresholds: Option<Jurisdiction>) -> Option<Threshold> {
+    thresholds.zip(row.year.as_deref()).and_then(|(jurisdiction, year)| jurisdiction.threshold(year))
+}
+
+/// Estimates the tax of what is taxed of the realized gains of each year, see `taxable` and
+/// `TaxRate::tax_of`.
+fn estimated_tax(taxable: &[SummaryRow], tax_rate: &TaxRate) -> Result<Vec<SummaryRow>> {
+    taxable.iter()
+        .map(|year| Ok(SummaryRow{
+            kind: GainKind::EstimatedTax,
+            net_income: Some(tax_rate.tax_of(year.net_income.unwrap_or_default())?),
+            ..year.clone()
+        }))
+        .collect()
+}
+
+fn non_taxable(txns: &[TaxableTransaction]) -> Result<Vec<SummaryRow>> {
+    let years = txns.iter()
+        .try_fold(BTreeMap::new(), |mut years, t| -> Result<_> {
+            let kind = match t.r#type {
+                TransactionType::Donation => GainKind::Donation,
+                TransactionType::Lost => GainKind::Lost,
+                TransactionType::Stolen => GainKind::Stolen,
+                _ => GainKind::Gift,
+            };
+            let year = year_of(t);
+            let row = years.entry((year.clone(), kind)).or_insert_with(|| SummaryRow{
+                year: Some(year),
+                kind,
+                currency: currency_of(t),
+                amount: dec!(0),
+                income: None,
+                cost: Some(dec!(0)),
+                net_income: None,
+                unpriced: 0,
+                price_source: None,
+            });
+            row.amount = decimal::add(row.amount, t.amount)?;
+            match t.costs.iter().all(|c| c.is_cash()) {
+                true => row.cost = add_to(row.cost, sum(&t.costs)?)?,
+                false => row.unpriced += 1,
+            }
+            Ok(years)
+        })?;
+    Ok(years.into_values().collect())
+}
+
+/// Sums up what was received without a cost per year and kind: the profits and losses of
+/// margin, futures and short positions, income, and the interest of earn accounts.
+fn received(txns: &[TaxableTransaction]) -> Result<Vec<SummaryRow>> {
+    let years = txns.iter()
+        .try_fold(BTreeMap::new(), |mut years, t| -> Result<_> {
+            let kind = match t.r#type {
+                TransactionType::Futures => GainKind::Futures,
+                TransactionType::Short => GainKind::Short,
+                TransactionType::Income if earn::is_interest(&t.r#type, &t.origin) => GainKind::Interest,
+                TransactionType::Income => GainKind::Income,
+                _ => GainKind::Margin,
+            };
+            let year = year_of(t);
+            let row = years.entry((year.clone(), kind)).or_insert_with(|| SummaryRow{
+                year: Some(year),
+                kind,
+                currency: currency_of(t),
+                amount: dec!(0),
+                income: Some(dec!(0)),
+                cost: None,
+                net_income: Some(dec!(0)),
+                unpriced: 0,
+                price_source: None,
+            });
+            row.amount = decimal::add(row.amount, t.amount)?;
+            match t.net_income {
+                Some(net_income) => {
+                    row.income = add_to(row.income, t.income.amount())?;
+                    row.net_income = add_to(row.net_income, net_income)?;
+                }
+                None => row.unpriced += 1,
+            }
+            Ok(years)
+        })?;
+    Ok(years.into_values().collect())
+}
+
+/// The currency of a row, `NFT` for the NFTs that are summed up together.
+fn currency_of(t: &TaxableTransaction) -> Currency {
+    match transaction::is_nft(&t.currency) {
+        true => NFT.to_string(),
+        false => t.currency.clone(),
+    }
+}
+
+fn year_of(t: &TaxableTransaction) -> String {
+    t.date.chars().take(4).collect()
+}
+
+/// The unrealized gains of `amount` of the holding with its `costs`: of all of it, or of the part
+/// in vaults.
+fn unrealized(
+    kind: GainKind,
+    holding: &Holding,
+    amount: Decimal,
+    costs: &[Money],
+    base: &Currency,
+    prices: &dyn PriceProvider,
+) -> Result<SummaryRow> {
+    let price = prices.latest_price(&holding.currency, base);
+    let market_value = price.as_ref().map(|(price, _)| decimal::mul(*price, amount)).transpose()?;
+    let (cash, coupons): (Vec<&Money>, Vec<&Money>) =
+        costs.iter().partition(|c| c.is_cash());
+    let cost = match coupons.is_empty() {
+        true => Some(decimal::sum(cash.iter().map(|c| c.amount()))?),
+        false => None,
+    };
+    let net_income = market_value.zip(cost).map(|(value, cost)| decimal::add(value, cost)).transpose()?;
+
+    Ok(SummaryRow{
+        year: None,
+        kind,
+        currency: holding.currency.clone(),
+        amount,
+        income: market_value,
+        cost,
+        net_income,
+        unpriced: coupons.len(),
+        price_source: price.map(|(_, source)| source),
+    })
+}
+
+fn sum(moneys: &[Money]) -> Result<Decimal> {
+    decimal::sum(moneys.iter().map(|m| m.amount()))
+}
+
+/// Adds `amount` to a `total` that is summed up, if any.
+fn add_to(total: Option<Decimal>, amount: Decimal) -> Result<Option<Decimal>> {
+    total.map(|t| decimal::add(t, amount)).transpose()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::calculator::{Calculation, Holding, TaxableTransaction};
+    use crate::price::FixedPrice;
+    use crate::summary::*;
+    use crate::transaction::Origin;
+    use futures::executor::block_on;
+    use rust_decimal_macros::dec;
+    use std::error::Error;
+
+    #[test]
+    fn should_summarize_gains() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let txns = vec![
+            TaxableTransaction{
+                r#type: TransactionType::Sell,
+                date: "2021-03-01 10:00:00".to_string(),
+                currency: "BTC".to_string(),
+                amount: dec!(-0.1),
+                income: Money::new_cash("SEK".to_string(), dec!(5000)),
+                costs: vec![Money::new_cash("SEK".to_string(), dec!(-3000))],
+                net_income: Some(dec!(2000)),
+                acquisition_fee: dec!(0),
+                disposal_fee: dec!(0),
+                origin: Default::default(),
+                consumed: vec![],
+            },
+            TaxableTransaction{
+                r#type: TransactionType::Sell,
+                date: "2021-06-01 10:00:00".to_string(),
+                currency: "BTC".to_string(),
+                amount: dec!(-0.1),
+                income: Money::new_coupon("ETH".to_string(), dec!(2), "2021-06-01 10:00:00".to_string()),
+                costs: vec![Money::new_cash("SEK".to_string(), dec!(-3000))],
+                net_income: None,
+                acquisition_fee: dec!(0),
+                disposal_fee: dec!(0),
+                origin: Default::default(),
+                consumed: vec![],
+            },
+            TaxableTransaction{
+                r#type: TransactionType::Sell,
+                date: "2022-01-01 10:00:00".to_string(),
+                currency: "BTC".to_string(),
+                amount: dec!(-0.2),
+                income: Money::new_cash("SEK".to_string(), dec!(4000)),
+                costs: vec![Money::new_cash("SEK".to_string(), dec!(-6000))],
+                net_income: Some(dec!(-2000)),
+                acquisition_fee: dec!(0),
+                disposal_fee: dec!(0),
+                origin: Default::default(),
+                consumed: vec![],
+            },
+        ];
+        let holding = Holding{
+            currency: "BTC".to_string(),
+            amount: dec!(0.5),
+            costs: vec![Money::new_cash("SEK".to_string(), dec!(-15000))],
+            ..Default::default()
+        };
+        let non_taxables = vec![
+            TaxableTransaction{
+                r#type: TransactionType::Gift,
+                date: "2022-02-01 10:00:00".to_string(),
+                currency: "BTC".to_string(),
+                amount: dec!(-0.1),
+                income: Money::new_cash("SEK".to_string(), dec!(0)),
+                costs: vec![Money::new_cash("SEK".to_string(), dec!(-3000))],
+                net_income: None,
+                acquisition_fee: dec!(0),
+                disposal_fee: dec!(0),
+                origin: Default::default(),
+                consumed: vec![],
+            },
+        ];
+        let calculation = Calculation{ taxables: txns, non_taxables, derivatives: vec![], income: vec![], exempt: vec![], holding, carryover: vec![] };
+        let prices = FixedPrice::new("BTC".to_string(), "SEK".to_string(), dec!(40000));
+
+        /*
+         * When
+         */
+        let rows = block_on(summarize(&calculation, &"SEK".to_string(), &prices, Breakdown::Year, None, None))?;
+
+        /*
+         * Then
+         */
+        let mut iter = rows.into_iter();
+        assert_eq!(iter.next(), Some(SummaryRow{
+            year: Some("2021".to_string()),
+            kind: GainKind::Realized,
+            currency: "BTC".to_string(),
+            amount: dec!(-0.2),
+            income: Some(dec!(5000)),
+            cost: Some(dec!(-3000)),
+            net_income: Some(dec!(2000)),
+            unpriced: 1,
+            price_source: None
+        }));
+        assert_eq!(iter.next(), Some(SummaryRow{
+            year: Some("2022".to_string()),
+            kind: GainKind::Realized,
+            currency: "BTC".to_string(),
+            amount: dec!(-0.2),
+            income: Some(dec!(4000)),
+            cost: Some(dec!(-6000)),
+            net_income: Some(dec!(-2000)),
+            unpriced: 0,
+            price_source: None
+        }));
+        assert_eq!(iter.next(), Some(SummaryRow{
+            year: Some("2022".to_string()),
+            kind: GainKind::Gift,
+            currency: "BTC".to_string(),
+            amount: dec!(-0.1),
+            income: None,
+            cost: Some(dec!(-3000)),
+            net_income: None,
+            unpriced: 0,
+            price_source: None
+        }));
+        assert_eq!(iter.next(), Some(SummaryRow{
+            year: None,
+            kind: GainKind::Unrealized,
+            currency: "BTC".to_string(),
+            amount: dec!(0.5),
+            income: Some(dec!(20000)),
+            cost: Some(dec!(-15000)),
+            net_income: Some(dec!(5000)),
+            unpriced: 0,
+            price_source: Some("FIXED".to_string())
+        }));
+        assert_eq!(iter.next(), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_break_down_gains_by_quarter() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let sell = |date: &str, income, cost| TaxableTransaction{
+            r#type: TransactionType::Sell,
+            date: date.to_string(),
+            currency: "BTC".to_string(),
+            amount: dec!(-0.1),
+            income: Money::new_cash("SEK".to_string(), income),
+            costs: vec![Money::new_cash("SEK".to_string(), cost)],
+            net_income: Some(income + cost),
+            acquisition_fee: dec!(0),
+            disposal_fee: dec!(0),
+            origin: Default::default(),
+            consumed: vec![],
+        };
+        let txns = vec![
+            sell("2022-02-01 10:00:00", dec!(5000), dec!(-3000)),
+            sell("2022-03-31 23:00:00", dec!(1000), dec!(-3000)),
+            sell("2022-11-15 10:00:00", dec!(4000), dec!(-3000)),
+        ];
+        let calculation = Calculation{ taxables: txns, non_taxables: vec![], derivatives: vec![], income: vec![], exempt: vec![], holding: Default::default(), carryover: vec![] };
+        let prices = FixedPrice::new("BTC".to_string(), "SEK".to_string(), dec!(40000));
+
+        /*
+         * When
+         */
+        let rows = block_on(summarize(&calculation, &"SEK".to_string(), &prices, Breakdown::Quarter, None, None))?;
+
+        /*
+         * Then
+         */
+        let realized: Vec<(Option<&str>, Option<Decimal>)> = rows.iter()
+            .filter(|r| r.kind == GainKind::Realized)
+            .map(|r| (r.year.as_deref(), r.net_income))
+            .collect();
+        assert_eq!(realized, vec![
+            (Some("2022"), Some(dec!(1000))),
+            (Some("2022-Q1"), Some(dec!(0))),
+            (Some("2022-Q4"), Some(dec!(1000))),
+        ]);
+        assert_eq!(Breakdown::Month.period_of("2022-03-31 23:00:00"), Some("2022-03".to_string()));
+        Ok(())
+    }
+
+    #[test]
+    fn should_estimate_tax_with_deductible_losses() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let sell = |date: &str, net_income| TaxableTransaction{
+            r#type: TransactionType::Sell,
+            date: date.to_string(),
+            currency: "BTC".to_string(),
+            amount: dec!(-0.1),
+            income: Money::new_cash("SEK".to_string(), dec!(3000) + net_income),
+            costs: vec![Money::new_cash("SEK".to_string(), dec!(-3000))],
+            net_income: Some(net_income),
+            acquisition_fee: dec!(0),
+            disposal_fee: dec!(0),
+            origin: Default::default(),
+            consumed: vec![],
+        };
+        let mut purchase = sell("2021-05-01 10:00:00", dec!(1000));
+        purchase.origin.counterparty = Some("Klarna".to_string());
+        let txns = vec![sell("2021-02-01 10:00:00", dec!(2000)), purchase, sell("2022-03-01 10:00:00", dec!(-1000))];
+        let calculation = Calculation{ taxables: txns, non_taxables: vec![], derivatives: vec![], income: vec![], exempt: vec![], holding: Default::default(), carryover: vec![] };
+        let prices = FixedPrice::new("BTC".to_string(), "SEK".to_string(), dec!(40000));
+        let tax_rate = TaxRate{ rate: dec!(30), deductible_losses: dec!(70) };
+
+        /*
+         * When
+         */
+        let rows = block_on(summarize(&calculation, &"SEK".to_string(), &prices, Breakdown::Year, Some(tax_rate), None))?;
+
+        /*
+         * Then
+         */
+        let taxes: Vec<(Option<&str>, Option<Decimal>)> = rows.iter()
+            .filter(|r| r.kind == GainKind::EstimatedTax)
+            .map(|r| (r.year.as_deref(), r.net_income))
+            .collect();
+        let purchases: Vec<(Option<&str>, Option<Decimal>)> = rows.iter()
+            .filter(|r| r.kind == GainKind::Purchase)
+            .map(|r| (r.year.as_deref(), r.net_income))
+            .collect();
+        assert_eq!(purchases, vec![(Some("2021"), Some(dec!(1000)))]);
+        assert_eq!(taxes, vec![(Some("2021"), Some(dec!(900))), (Some("2022"), Some(dec!(-210)))]);
+        Ok(())
+    }
+
+    #[test]
+    fn should_apply_threshold_of_jurisdiction() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let sell = |date: &str, net_income| TaxableTransaction{
+            r#type: TransactionType::Sell,
+            date: date.to_string(),
+            currency: "BTC".to_string(),
+            amount: dec!(-0.1),
+            income: Money::new_cash("EUR".to_string(), dec!(3000) + net_income),
+            costs: vec![Money::new_cash("EUR".to_string(), dec!(-3000))],
+            net_income: Some(net_income),
+            acquisition_fee: dec!(0),
+            disposal_fee: dec!(0),
+            origin: Default::default(),
+            consumed: vec![],
+        };
+        let txns = vec![
+            sell("2022-02-01 10:00:00", dec!(300)),
+            sell("2022-03-01 10:00:00", dec!(200)),
+            sell("2023-03-01 10:00:00", dec!(700)),
+            sell("2024-03-01 10:00:00", dec!(700)),
+        ];
+        let calculation = Calculation{ taxables: txns, non_taxables: vec![], derivatives: vec![], income: vec![], exempt: vec![], holding: Default::default(), carryover: vec![] };
+        let prices = FixedPrice::new("BTC".to_string(), "EUR".to_string(), dec!(40000));
+        let tax_rate = TaxRate{ rate: dec!(25), deductible_losses: dec!(100) };
+
+        /*
+         * When
+         */
+        let rows = block_on(summarize(&calculation, &"EUR".to_string(), &prices, Breakdown::Year, Some(tax_rate), Some(Jurisdiction::Germany)))?;
+
+        /*
+         * Then
+         */
+        let net_incomes = |kind| rows.iter()
+            .filter(|r| r.kind == kind)
+            .map(|r| (r.year.as_deref(), r.net_income))
+            .collect::<Vec<_>>();
+        // Below the limit of 600 in 2022 and of 1000 in 2024, above it in 2023 and taxed in full
+        assert_eq!(net_incomes(GainKind::Taxable), vec![
+            (Some("2022"), Some(dec!(0))),
+            (Some("2023"), Some(dec!(700))),
+            (Some("2024"), Some(dec!(0))),
+        ]);
+        assert_eq!(net_incomes(GainKind::EstimatedTax), vec![
+            (Some("2022"), Some(dec!(0))),
+            (Some("2023"), Some(dec!(175))),
+            (Some("2024"), Some(dec!(0))),
+        ]);
+        assert_eq!(Jurisdiction::UnitedKingdom.threshold("2023/24").map(|t| t.taxable(dec!(10000))), Some(dec!(4000)));
+        Ok(())
+    }
+
+    #[test]
+    fn should_sum_up_interest_apart_from_income() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let income = |date: &str, amount, description: &str| TaxableTransaction{
+            r#type: TransactionType::Income,
+            date: date.to_string(),
+            currency: "BTC".to_string(),
+            amount,
+            income: Money::new_cash("EUR".to_string(), amount * dec!(40000)),
+            costs: vec![],
+            net_income: Some(amount * dec!(40000)),
+            acquisition_fee: dec!(0),
+            disposal_fee: dec!(0),
+            origin: Origin::new(2, description.to_string()),
+            consumed: vec![],
+        };
+        let txns = vec![
+            income("2022-02-01 10:00:00", dec!(0.001), "2022-02-01 10:00:00,Card Cashback,BTC,0.001,,,EUR,40,45,referral_card_cashback,"),
+            income("2022-03-01 10:00:00", dec!(0.0001), "2022-03-01 10:00:00,Crypto Earn,BTC,0.0001,,,EUR,4,4.5,crypto_earn_interest_paid,"),
+            income("2022-04-01 10:00:00", dec!(0.0002), "2022-04-01 10:00:00,Crypto Earn,BTC,0.0002,,,EUR,8,9,crypto_earn_interest_paid,"),
+        ];
+        let calculation = Calculation{ taxables: vec![], non_taxables: vec![], derivatives: vec![], income: txns, exempt: vec![], holding: Default::default(), carryover: vec![] };
+        let prices = FixedPrice::new("BTC".to_string(), "EUR".to_string(), dec!(40000));
+
+        /*
+         * When
+         */
+        let rows = block_on(summarize(&calculation, &"EUR".to_string(), &prices, Breakdown::Year, None, None))?;
+
+        /*
+         * Then
+         */
+        let received: Vec<(GainKind, Decimal, Option<Decimal>)> = rows.iter()
+            .filter(|r| matches!(r.kind, GainKind::Income | GainKind::Interest))
+            .map(|r| (r.kind, r.amount, r.net_income))
+            .collect();
+        assert_eq!(received, vec![
+            (GainKind::Income, dec!(0.001), Some(dec!(40))),
+            (GainKind::Interest, dec!(0.0003), Some(dec!(12))),
+        ]);
+        Ok(())
+    }
+}
@@ -0,0 +1,122 @@
+use crate::calculator::TaxableTransaction;
+use crate::decimal;
+use crate::error::Result;
+use crate::rounding::Rounding;
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde::Serialize;
+
+/// Whether a lot was held for longer than the `HoldingPeriod` of the jurisdiction when it was
+/// disposed, e.g. Part I (short-term) or Part II (long-term) of Form 8949.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Serialize)]
+pub(crate) enum Term {
+    Short,
+    Long,
+}
+
+/// How long a lot is held before its gains are long-term, in whole years, e.g. one year in the US
+/// and Germany, see `Jurisdiction::holding_period`.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub(crate) struct HoldingPeriod {
+    pub(crate) years: i32,
+}
+
+pub(crate) const ONE_YEAR: HoldingPeriod = HoldingPeriod{ years: 1 };
+
+impl HoldingPeriod {
+    /// Whether a disposal at `disposed` of a lot acquired at `acquired` happens after the
+    /// anniversary of the acquisition at the end of the period. Both dates start with
+    /// `YYYY-MM-DD`.
+    pub(crate) fn term_of(&self, acquired: &str, disposed: &str) -> Term {
+        let year: i32 = acquired.get(..4).and_then(|y| y.parse().ok()).unwrap_or_default();
+        let anniversary = format!("{}{}", year + self.years, acquired.get(4..10).unwrap_or_default());
+        match disposed.get(..10).unwrap_or_default() > anniversary.as_str() {
+            true => Term::Long,
+            false => Term::Short,
+        }
+    }
+}
+
+/// The net income of a disposal split into the short-term and the long-term part, by the holding
+/// periods of the lots it consumed.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub(crate) struct Split {
+    pub(crate) short: Option<Decimal>,
+    pub(crate) long: Option<Decimal>,
+}
+
+/// Splits the net income of a disposal by the terms of the lots it consumed, with the income in
+/// proportion to their amounts. A lot without an acquisition date, e.g. of a pooled cost, is
+/// short-term. The long-term part is rounded, and the short-term part is the rest, so that both
+/// add up to the net income. Both are `None` if the disposal is not valued in the base currency.
+pub(crate) fn split(t: &TaxableTransaction, period: &HoldingPeriod, rounding: &Rounding) -> Result<Split> {
+    let Some(net_income) = t.net_income else {
+        return Ok(Split::default());
+    };
+    let total = t.consumed.iter().fold(dec!(0), |acc, c| acc + c.amount);
+    let mut long = dec!(0);
+    for c in t.consumed.iter().filter(|c| c.acquired.as_ref().is_some_and(|acquired| period.term_of(acquired, &t.date) == Term::Long)) {
+        let income = decimal::share(t.income.amount(), c.amount, total)?;
+        long += income + c.costs.iter().fold(dec!(0), |acc, cost| acc + cost.amount());
+    }
+    let long = rounding.gain(long);
+    Ok(Split{ short: Some(net_income - long), long: Some(long) })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::calculator::Consumed;
+    use crate::term::*;
+    use crate::transaction::{Money, TransactionType};
+    use std::error::Error;
+
+    #[test]
+    fn should_tell_holding_period() {
+        assert_eq!(ONE_YEAR.term_of("2020-03-01 10:00:00", "2021-03-01 12:00:00"), Term::Short);
+        assert_eq!(ONE_YEAR.term_of("2020-03-01 10:00:00", "2021-03-02 09:00:00"), Term::Long);
+        assert_eq!(ONE_YEAR.term_of("2020-02-29 10:00:00", "2021-02-28 10:00:00"), Term::Short);
+        assert_eq!(ONE_YEAR.term_of("2020-02-29 10:00:00", "2021-03-01 10:00:00"), Term::Long);
+        assert_eq!(HoldingPeriod{ years: 3 }.term_of("2020-03-01 10:00:00", "2022-03-02 10:00:00"), Term::Short);
+    }
+
+    #[test]
+    fn should_split_net_income_by_term_of_lots() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let lot = |amount, cost, acquired: Option<&str>| Consumed{
+            amount,
+            costs: vec![Money::new_cash("USD".to_string(), cost)],
+            origins: vec![].into(),
+            acquired: acquired.map(str::to_string),
+        };
+        let t = TaxableTransaction{
+            r#type: TransactionType::Sell,
+            date: "2022-06-01 10:00:00".to_string(),
+            currency: "BTC".to_string(),
+            amount: dec!(-3),
+            income: Money::new_cash("USD".to_string(), dec!(100)),
+            costs: vec![Money::new_cash("USD".to_string(), dec!(-60))],
+            net_income: Some(dec!(40)),
+            acquisition_fee: dec!(0),
+            disposal_fee: dec!(0),
+            origin: Default::default(),
+            consumed: vec![
+                lot(dec!(1), dec!(-10), Some("2021-01-01 10:00:00")),
+                lot(dec!(1), dec!(-20), Some("2022-01-01 10:00:00")),
+                lot(dec!(1), dec!(-30), None),
+            ],
+        };
+
+        /*
+         * When
+         */
+        let split = split(&t, &ONE_YEAR, &Rounding::Decimals(2))?;
+
+        /*
+         * Then
+         */
+        assert_eq!(split, Split{ short: Some(dec!(16.67)), long: Some(dec!(23.33)) });
+        Ok(())
+    }
+}
@@ -0,0 +1,140 @@
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Timelike};
+use std::fmt;
+
+/// The format of the dates of the transactions, e.g. `2022-01-02 10:00:00`, the format of the
+/// Revolut account statements.
+pub(crate) const FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// The formats of the dates of the exports without an offset, tried in order after ISO 8601 with
+/// an offset. A date with slashes is month first, as in the exports of US exchanges.
+pub(crate) const FORMATS: [&str; 12] = [
+    "%Y-%m-%d %H:%M:%S%.f",
+    "%Y-%m-%dT%H:%M:%S%.f",
+    "%Y-%m-%d %H:%M",
+    "%Y-%m-%dT%H:%M",
+    "%d.%m.%Y %H:%M:%S",
+    "%d.%m.%Y %H:%M",
+    "%m/%d/%Y %H:%M:%S",
+    "%m/%d/%Y %H:%M",
+    "%m/%d/%Y %I:%M:%S %p",
+    "%m/%d/%Y %I:%M %p",
+    "%b %d, %Y, %I:%M %p",      // Jan 02, 2022, 10:00 AM
+    "%B %d, %Y %I:%M %p",       // January 2, 2022 10:00 AM
+];
+
+/// The formats of ISO 8601 dates with an offset that RFC 3339 does not cover, e.g.
+/// `2022-01-02 10:00:00 +01:00` or `2022-01-02T10:00:00+0100`.
+const OFFSET_FORMATS: [&str; 2] = [
+    "%Y-%m-%d %H:%M:%S%.f %z",
+    "%Y-%m-%dT%H:%M:%S%.f%z",
+];
+
+/// A point in time of a transaction in UTC, to the second, whatever format the export gave it in.
+/// Its `Display` is the date of a `Transaction`, see `FORMAT`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) struct Timestamp(NaiveDateTime);
+
+impl Timestamp {
+    /// Parses a date of an export, see `FORMATS`. A date with an offset, e.g.
+    /// `2022-01-02T10:00:00+01:00`, is converted to UTC, and one without, or with a trailing `Z`
+    /// or ` UTC`, is taken as UTC. A date without a time is at midnight. The fractions of a
+    /// second are dropped.
+    pub(crate) fn parse(field: &str) -> Option<Timestamp> {
+        Timestamp::parse_with(field, &FORMATS)
+    }
+
+    /// Parses a date like `parse`, but with the given formats of the dates without an offset,
+    /// e.g. of an export whose dates with slashes are day first.
+    pub(crate) fn parse_with(field: &str, formats: &[&str]) -> Option<Timestamp> {
+        let field = field.trim();
+        if let Some(timestamp) = Timestamp::parse_plain(field) {
+            return Some(timestamp);
+        }
+        let field = field.strip_suffix(" UTC").unwrap_or(field);
+        let naive = field.strip_suffix('Z').unwrap_or(field);
+        DateTime::parse_from_rfc3339(field).ok()
+            .or_else(|| OFFSET_FORMATS.iter().find_map(|format| DateTime::parse_from_str(field, format).ok()))
+            .map(|d| d.naive_utc())
+            .or_else(|| formats.iter().find_map(|format| NaiveDateTime::parse_from_str(naive, format).ok()))
+            .or_else(|| NaiveDate::parse_from_str(naive, "%Y-%m-%d").ok().and_then(|d| d.and_hms_opt(0, 0, 0)))
+            .and_then(|d| d.with_nanosecond(0))
+            .map(Timestamp)
+    }
+
+    /// Whether the date is already in `FORMAT`, and needs no parsing to be the date of a
+    /// `Transaction`.
+    pub(crate) fn is_plain(field: &str) -> bool {
+        Timestamp::parse_plain(field).is_some()
+    }
+
+    /// Parses a date in `FORMAT` without going through the formats one by one, as most dates of
+    /// the exports, and all of the transactions, are in it.
+    fn parse_plain(field: &str) -> Option<Timestamp> {
+        let bytes = field.as_bytes();
+        if bytes.len() != 19 || bytes[4] != b'-' || bytes[7] != b'-' || bytes[10] != b' ' || bytes[13] != b':' || bytes[16] != b':' {
+            return None;
+        }
+        let number = |from: usize, to: usize| bytes[from..to].iter().try_fold(0u32, |n, b| b.is_ascii_digit().then(|| n * 10 + (b - b'0') as u32));
+        let date = NaiveDate::from_ymd_opt(number(0, 4)? as i32, number(5, 7)?, number(8, 10)?)?;
+        date.and_hms_opt(number(11, 13)?, number(14, 16)?, number(17, 19)?).map(Timestamp)
+    }
+
+    /// The time of a unix timestamp, e.g. of a trade fetched from an exchange or of a block.
+    #[cfg(feature = "native")]
+    pub(crate) fn from_unix(seconds: i64) -> Option<Timestamp> {
+        DateTime::from_timestamp(seconds, 0).map(|d| Timestamp(d.naive_utc()))
+    }
+
+    /// The unix timestamp.
+    pub(crate) fn seconds(&self) -> i64 {
+        self.0.and_utc().timestamp()
+    }
+}
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0.format(FORMAT))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::timestamp::*;
+
+    #[test]
+    fn should_parse_dates_of_exports() {
+        let parsed = |field: &str| Timestamp::parse(field).map(|t| t.to_string());
+        let date = Some("2022-01-02 10:00:00".to_string());
+        // Revolut, Kraken and Koinly
+        assert_eq!(parsed("2022-01-02 10:00:00"), date);
+        assert_eq!(parsed("2022-01-02 10:00:00.1234"), date);
+        assert_eq!(parsed("2022-01-02 10:00 UTC"), date);
+        assert_eq!(parsed("02.01.2022 10:00"), date);
+        // ISO 8601
+        assert_eq!(parsed("2022-01-02T10:00:00Z"), date);
+        assert_eq!(parsed("2022-01-02T10:00:00.500Z"), date);
+        assert_eq!(parsed("2022-01-02T11:00:00+01:00"), date);
+        assert_eq!(parsed("2022-01-02T05:00:00-0500"), date);
+        assert_eq!(parsed("2022-01-02 11:00:00 +01:00"), date);
+        assert_eq!(parsed("2022-01-02T10:00"), date);
+        // US exchanges
+        assert_eq!(parsed("01/02/2022 10:00:00"), date);
+        assert_eq!(parsed("01/02/2022 10:00 AM"), date);
+        assert_eq!(parsed("1/2/2022 10:00:00 AM"), date);
+        assert_eq!(parsed("Jan 02, 2022, 10:00 AM"), date);
+        assert_eq!(parsed("January 2, 2022 10:00 AM"), date);
+        assert_eq!(parsed("01/02/2022 10:00 PM"), Some("2022-01-02 22:00:00".to_string()));
+        assert_eq!(parsed("2022-01-02"), Some("2022-01-02 00:00:00".to_string()));
+        assert_eq!(parsed("02/01/2022 10:00"), Some("2022-02-01 10:00:00".to_string()));
+        assert_eq!(Timestamp::parse_with("02/01/2022 10:00", &["%d/%m/%Y %H:%M"]).map(|t| t.to_string()), date);
+        assert_eq!(parsed("yesterday"), None);
+        assert_eq!(parsed("2022-13-02 10:00:00"), None);
+        assert_eq!(Timestamp::parse("2022-01-02 10:00:00").map(|t| t.seconds()), Some(1641117600));
+    }
+
+    #[test]
+    #[cfg(feature = "native")]
+    fn should_convert_unix_timestamps() {
+        assert_eq!(Timestamp::from_unix(1641117600).map(|t| t.to_string()), Some("2022-01-02 10:00:00".to_string()));
+    }
+}
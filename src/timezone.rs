@@ -0,0 +1,63 @@
+use crate::timestamp::FORMAT;
+use crate::transaction::Transaction;
+use chrono::{NaiveDateTime, TimeZone};
+use chrono_tz::Tz;
+use tracing::debug;
+
+/// Converts the dates of the transactions, which the exports give in UTC, to the local time of
+/// `timezone`, so that a trade is in the tax year and on the day of the taxpayer, e.g. a trade at
+/// 23:30 on 31 December UTC is in the next year in Stockholm. A date without a time is left as it
+/// is.
+pub(crate) fn to_local(txns: &mut [Transaction], timezone: Tz) {
+    if timezone == Tz::UTC {
+        return;
+    }
+    for t in txns.iter_mut() {
+        if let Some(utc) = t.date.get(..19).and_then(|d| NaiveDateTime::parse_from_str(d, FORMAT).ok()) {
+            let local = timezone.from_utc_datetime(&utc).naive_local().format(FORMAT).to_string();
+            debug!(from = %t.date, to = %local, %timezone, "Converted to local time");
+            t.date = local;
+        }
+    }
+}
+
+/// The UTC time of a local date of `timezone`, the earlier one if the clocks are turned back. A
+/// date in the hour that is skipped when the clocks are turned forward is taken as UTC.
+pub(crate) fn to_utc(local: &NaiveDateTime, timezone: Tz) -> NaiveDateTime {
+    timezone.from_local_datetime(local)
+        .earliest()
+        .map(|d| d.naive_utc())
+        .unwrap_or(*local)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::timezone::*;
+    use rust_decimal_macros::dec;
+    use std::error::Error;
+
+    #[test]
+    fn should_move_trades_into_local_tax_year() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let mut txns = vec![
+            Transaction::buy().currency("BTC").amount(dec!(1)).exchanged_currency("SEK").exchanged_amount(dec!(-100000)).date("2021-12-31 23:30:00").build()?,
+            Transaction::sell().currency("BTC").amount(dec!(-1)).exchanged_currency("SEK").exchanged_amount(dec!(110000)).date("2022-07-01 10:00:00").build()?,
+        ];
+
+        /*
+         * When
+         */
+        to_local(&mut txns, chrono_tz::Europe::Stockholm);
+
+        /*
+         * Then
+         */
+        assert_eq!(txns[0].date, "2022-01-01 00:30:00");
+        assert_eq!(txns[1].date, "2022-07-01 12:00:00");   // Summer time
+        let local = NaiveDateTime::parse_from_str(&txns[1].date, FORMAT)?;
+        assert_eq!(to_utc(&local, chrono_tz::Europe::Stockholm).format(FORMAT).to_string(), "2022-07-01 10:00:00");
+        Ok(())
+    }
+}
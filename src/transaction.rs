@@ -1,10 +1,17 @@
+use crate::decimal;
+use crate::error::{CryptotaxError, Result};
+use crate::price;
+use crate::timestamp::Timestamp;
 use rust_decimal::Decimal;
-use rust_decimal_macros::dec;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
 use std::fmt;
+use std::sync::{Arc, RwLock, RwLockReadGuard};
 
-#[derive(Debug, PartialEq, Serialize)]
-pub(crate) struct Transaction {
+/// A trade, transfer or other movement of a currency, as read from an export file. Outside the
+/// crate it is built with `TransactionBuilder`, e.g. `Transaction::buy()`.
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct Transaction {
     #[serde(rename = "Type")]
     pub(crate) r#type: TransactionType,
 
@@ -25,6 +32,168 @@ pub(crate) struct Transaction {
 
     #[serde(rename = "Vault")]
     pub(crate) is_vault: bool,
+
+    #[serde(rename = "Paid Fee")]
+    pub(crate) paid_fee: Decimal,       // Included in `paid_amount`, negative if paid
+
+    #[serde(rename = "Exchanged Fee")]
+    pub(crate) exchanged_fee: Decimal,  // Included in `exchanged_amount`, negative if paid
+
+    #[serde(rename = "Price Source")]
+    pub(crate) price_source: Option<String>,    // Where the exchanged amount was priced from, if not traded
+
+    #[serde(rename = "Unit Price", serialize_with = "price::serialize_unit_price")]
+    pub(crate) unit_price: Option<Decimal>,     // Of one unit of the paid currency in the base currency, see `price::annotate`
+
+    #[serde(rename = "Fiat Value")]
+    pub(crate) fiat_value: Option<Decimal>,     // Of the paid amount in the base currency
+
+    #[serde(skip)]
+    pub(crate) other_fee: Option<(Currency, Decimal)>,    // In neither currency of the trade, e.g. BNB, negative if paid, see `legs::value_fees`
+
+    #[serde(skip)]
+    pub(crate) origin: Origin,
+}
+
+/// Where a transaction was read from, for the audit report: the file, and the lines and the raw
+/// descriptions of the rows it was made of, e.g. both rows of an exchange, and how far the price
+/// they imply is from the market. Also the note, the tags and the counterparty it was annotated
+/// with, e.g. why it was reclassified, see `Annotation`.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub(crate) struct Origin {
+    pub(crate) file: String,
+    pub(crate) lines: Vec<u64>,
+    pub(crate) descriptions: Vec<String>,
+    pub(crate) deviation: Option<Decimal>,      // Of the price the rows imply from the market price, in percent, see `price::check_deviations`
+    pub(crate) note: Option<String>,
+    pub(crate) tags: Vec<String>,
+    pub(crate) counterparty: Option<String>,    // Who was paid or paid, e.g. a merchant
+}
+
+impl Origin {
+    pub(crate) fn new(line: u64, description: String) -> Origin {
+        Origin{ file: "".to_string(), lines: vec![line], descriptions: vec![description], deviation: None, note: None, tags: vec![], counterparty: None }
+    }
+
+    /// Sets the note, if one is given, and adds the tags that are not there yet.
+    pub(crate) fn annotate(&mut self, note: Option<&String>, tags: &[String]) {
+        if let Some(note) = note {
+            self.note = Some(note.clone());
+        }
+        for tag in tags {
+            if !self.tags.contains(tag) {
+                self.tags.push(tag.clone());
+            }
+        }
+    }
+
+    pub(crate) fn has_notes(&self) -> bool {
+        self.note.is_some() || !self.tags.is_empty()
+    }
+
+    /// Adds a row to the origin, keeping the lines in the order of the file.
+    pub(crate) fn add(&mut self, line: u64, description: String) {
+        let i = self.lines.partition_point(|l| *l < line);
+        self.lines.insert(i, line);
+        self.descriptions.insert(i, description);
+    }
+}
+
+/// What a transaction is annotated with, by `tx annotate` or a rule of the rules file, see
+/// `rules::Rule`: a type it is reclassified as, a note, tags and a counterparty.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub(crate) struct Annotation {
+    pub(crate) r#type: Option<TransactionType>,
+    pub(crate) note: Option<String>,
+    pub(crate) tags: Vec<String>,
+    pub(crate) counterparty: Option<String>,
+}
+
+impl Annotation {
+    /// Reclassifies the transaction and annotates its origin. What is not given is left as it is,
+    /// and the tags are added to the earlier ones.
+    pub(crate) fn apply(&self, t: &mut Transaction) {
+        if let Some(r#type) = &self.r#type {
+            t.r#type = r#type.clone();
+        }
+        t.origin.annotate(self.note.as_ref(), &self.tags);
+        if let Some(counterparty) = &self.counterparty {
+            t.origin.counterparty = Some(counterparty.clone());
+        }
+    }
+
+    /// This annotation, followed by a later one, which wins where both give something.
+    #[cfg(feature = "native")]
+    pub(crate) fn then(&self, later: &Annotation) -> Annotation {
+        let mut tags = self.tags.clone();
+        tags.extend(later.tags.iter().filter(|t| !self.tags.contains(t)).cloned());
+        Annotation{
+            r#type: later.r#type.clone().or_else(|| self.r#type.clone()),
+            note: later.note.clone().or_else(|| self.note.clone()),
+            tags,
+            counterparty: later.counterparty.clone().or_else(|| self.counterparty.clone()),
+        }
+    }
+}
+
+/// The origins of a pooled cost, shared with the disposals that consumed part of it. Each sees the
+/// origins that were added until then, without copying them, so that a pool of many buys that is
+/// sold from many times doesn't copy its origins at every sale. The log is behind an `Arc`, so that
+/// the disposals can be sent to other threads.
+#[derive(Clone, Default)]
+pub(crate) struct Origins {
+    log: Arc<RwLock<Vec<Origin>>>,
+    len: usize,
+}
+
+impl Origins {
+    /// Adds an origin. The log is copied first if others were added to it after this view.
+    pub(crate) fn push(&mut self, origin: Origin) {
+        if self.read().len() != self.len {
+            self.log = Arc::new(RwLock::new(self.to_vec()));
+        }
+        self.log.write().unwrap_or_else(|e| e.into_inner()).push(origin);
+        self.len += 1;
+    }
+
+    pub(crate) fn extend(&mut self, other: &Origins) {
+        other.to_vec().into_iter().for_each(|o| self.push(o));
+    }
+
+    pub(crate) fn to_vec(&self) -> Vec<Origin> {
+        self.with(<[Origin]>::to_vec)
+    }
+
+    /// Calls `f` with the origins of this view, without copying them.
+    pub(crate) fn with<R>(&self, f: impl FnOnce(&[Origin]) -> R) -> R {
+        f(&self.read()[..self.len])
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, Vec<Origin>> {
+        self.log.read().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl From<Vec<Origin>> for Origins {
+    fn from(origins: Vec<Origin>) -> Origins {
+        let len = origins.len();
+        Origins{ log: Arc::new(RwLock::new(origins)), len }
+    }
+}
+
+impl PartialEq for Origins {
+    fn eq(&self, other: &Origins) -> bool {
+        match Arc::ptr_eq(&self.log, &other.log) {
+            true => self.len == other.len,
+            false => self.len == other.len && self.with(|origins| other.with(|others| origins == others)),
+        }
+    }
+}
+
+impl fmt::Debug for Origins {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.with(|origins| f.debug_list().entries(origins).finish())
+    }
 }
 
 impl Transaction {
@@ -36,7 +205,14 @@ impl Transaction {
             exchanged_currency: "".to_string(),
             exchanged_amount: Default::default(),
             date: "".to_string(),
-            is_vault: false
+            is_vault: false,
+            paid_fee: Default::default(),
+            exchanged_fee: Default::default(),
+            price_source: None,
+            unit_price: None,
+            fiat_value: None,
+            other_fee: None,
+            origin: Default::default()
         }
     }
 
@@ -47,16 +223,193 @@ impl Transaction {
             Money::new_coupon(self.exchanged_currency.clone(), self.exchanged_amount, self.date.clone())
         }
     }
+
+    /// Builds a `Buy`: a positive amount of the currency, bought for a negative amount of the
+    /// exchanged currency.
+    pub fn buy() -> TransactionBuilder {
+        TransactionBuilder::new(TransactionType::Buy)
+    }
+
+    /// Builds a `Sell`: a negative amount of the currency, sold for a positive amount of the
+    /// exchanged currency.
+    pub fn sell() -> TransactionBuilder {
+        TransactionBuilder::new(TransactionType::Sell)
+    }
+
+    /// Builds an `Income`: a positive amount of the currency received as a reward, with its market
+    /// value as a negative amount of the exchanged currency, if known.
+    pub fn income() -> TransactionBuilder {
+        TransactionBuilder::new(TransactionType::Income)
+    }
+
+    /// Builds a `Transfer`: an amount of the currency sent, if negative, or received, without an
+    /// exchanged currency.
+    pub fn transfer() -> TransactionBuilder {
+        TransactionBuilder::new(TransactionType::Transfer)
+    }
+
+    /// Orders transactions by date. Transactions with the same date are ordered with buys before
+    /// sells, then by currencies and amounts, so that the order never depends on the input order.
+    pub(crate) fn cmp_by_date(&self, other: &Transaction) -> Ordering {
+        self.date.cmp(&other.date)
+            .then_with(|| self.r#type.cmp(&other.r#type))
+            .then_with(|| self.paid_currency.cmp(&other.paid_currency))
+            .then_with(|| self.exchanged_currency.cmp(&other.exchanged_currency))
+            .then_with(|| self.paid_amount.cmp(&other.paid_amount))
+            .then_with(|| self.exchanged_amount.cmp(&other.exchanged_amount))
+            .then_with(|| self.is_vault.cmp(&other.is_vault))
+    }
+}
+
+/// Sets the fields of a `Transaction` and checks them when it is built. The amounts are signed as
+/// in the transaction, and include the fees.
+#[derive(Debug, Clone)]
+pub struct TransactionBuilder {
+    txn: Transaction,
+}
+
+impl TransactionBuilder {
+    fn new(r#type: TransactionType) -> TransactionBuilder {
+        let mut txn = Transaction::new();
+        txn.r#type = r#type;
+        TransactionBuilder{ txn }
+    }
+
+    /// The currency bought, sold, received or sent, e.g. `BTC`.
+    pub fn currency(mut self, currency: &str) -> TransactionBuilder {
+        self.txn.paid_currency = currency.to_string();
+        self
+    }
+
+    /// The amount of `currency`, including `fee`.
+    pub fn amount(mut self, amount: Decimal) -> TransactionBuilder {
+        self.txn.paid_amount = amount;
+        self
+    }
+
+    /// The fee paid in `currency`, negative if paid.
+    pub fn fee(mut self, fee: Decimal) -> TransactionBuilder {
+        self.txn.paid_fee = fee;
+        self
+    }
+
+    /// The currency that `currency` was exchanged for or with, e.g. `SEK`.
+    pub fn exchanged_currency(mut self, currency: &str) -> TransactionBuilder {
+        self.txn.exchanged_currency = currency.to_string();
+        self
+    }
+
+    /// The amount of `exchanged_currency`, including `exchanged_fee`.
+    pub fn exchanged_amount(mut self, amount: Decimal) -> TransactionBuilder {
+        self.txn.exchanged_amount = amount;
+        self
+    }
+
+    /// The fee paid in `exchanged_currency`, negative if paid.
+    pub fn exchanged_fee(mut self, fee: Decimal) -> TransactionBuilder {
+        self.txn.exchanged_fee = fee;
+        self
+    }
+
+    /// The date and time, e.g. `2022-05-02 08:00:00`, or in any format of the supported exports,
+    /// see `Timestamp::parse`.
+    pub fn date(mut self, date: &str) -> TransactionBuilder {
+        self.txn.date = date.to_string();
+        self
+    }
+
+    /// Whether the currency was bought into a Revolut vault.
+    pub fn vault(mut self, is_vault: bool) -> TransactionBuilder {
+        self.txn.is_vault = is_vault;
+        self
+    }
+
+    /// Checks the fields and builds the transaction, with the date in the format of the reports.
+    /// Fails if the currency or the date is missing, an amount has the wrong sign for the type, a trade has no exchanged currency, a
+    /// transfer has one, or a fee is positive.
+    pub fn build(self) -> Result<Transaction> {
+        let mut t = self.txn;
+        let invalid = |msg: String| Err(CryptotaxError::Config(msg));
+        if t.paid_currency.is_empty() {
+            return invalid(format!("{:?} without a currency", t.r#type));
+        }
+        match Timestamp::parse(&t.date) {
+            Some(timestamp) => t.date = timestamp.to_string(),
+            None => return invalid(format!("{:?} of {} at `{}`, expected e.g. 2022-05-02 08:00:00", t.r#type, t.paid_currency, t.date)),
+        }
+        let is_signed = match t.r#type {
+            TransactionType::Sell => t.paid_amount.is_sign_negative() && !t.paid_amount.is_zero(),
+            TransactionType::Transfer => !t.paid_amount.is_zero(),
+            _ => t.paid_amount.is_sign_positive() && !t.paid_amount.is_zero(),
+        };
+        if !is_signed {
+            return invalid(format!("{:?} of {} {}, the amount has the wrong sign", t.r#type, t.paid_amount, t.paid_currency));
+        }
+        let is_exchanged = !t.exchanged_currency.is_empty();
+        match t.r#type {
+            TransactionType::Buy | TransactionType::Sell if !is_exchanged => {
+                return invalid(format!("{:?} of {} {} without an exchanged currency", t.r#type, t.paid_amount, t.paid_currency));
+            }
+            TransactionType::Transfer if is_exchanged => {
+                return invalid(format!("Transfer of {} {} with an exchanged currency", t.paid_amount, t.paid_currency));
+            }
+            _ => {}
+        }
+        if is_exchanged && t.exchanged_amount.is_sign_positive() == t.paid_amount.is_sign_positive() && !t.exchanged_amount.is_zero() {
+            return invalid(format!("{:?} of {} {}, the exchanged amount has the wrong sign", t.r#type, t.paid_amount, t.paid_currency));
+        }
+        if t.paid_fee.is_sign_positive() && !t.paid_fee.is_zero() || t.exchanged_fee.is_sign_positive() && !t.exchanged_fee.is_zero() {
+            return invalid(format!("{:?} of {} {}, a fee is positive", t.r#type, t.paid_amount, t.paid_currency));
+        }
+        Ok(t)
+    }
 }
 
-#[derive(Debug, PartialEq, Serialize)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Serialize, Deserialize)]
 pub(crate) enum TransactionType {
     Buy,
-    Sell
+    Income,     // Received as a reward, interest or cashback, at a cost of its market value
+    Sell,
+    Transfer,   // Sent or received without being exchanged. Not a disposal unless overridden
+    AddLiquidity,       // A currency deposited into a liquidity pool for LP tokens, see `liquidity::prepare`
+    RemoveLiquidity,    // A currency withdrawn from a liquidity pool by burning LP tokens
+    Gift,
+    Donation,
+    Lost,
+    Stolen,
+    Margin,     // Profit or loss of a margin position, already in the currency it was settled in
+    Futures,    // Profit or loss of a futures position, including its funding fees
+    Short,      // Profit or loss of a short position, closed by buying back what was sold, see `short::separate`
+    Borrow,     // Borrowed on margin, e.g. to be sold short. Not an acquisition
+    Repay,      // A margin loan paid back. Not a disposal
 }
 
 pub(crate) type Currency = String;
 
+/// The fiat currencies that can be held in a Revolut account, in alphabetical order for `is_fiat`.
+const FIAT_CURRENCIES: [&str; 29] = [
+    "AED", "AUD", "BGN", "CAD", "CHF", "CNY", "CZK", "DKK", "EUR", "GBP", "HKD", "HUF", "ILS", "ISK", "JPY",
+    "MXN", "NOK", "NZD", "PLN", "QAR", "RON", "RSD", "SAR", "SEK", "SGD", "THB", "TRY", "USD", "ZAR",
+];
+
+pub(crate) fn is_fiat(currency: &str) -> bool {
+    FIAT_CURRENCIES.binary_search(&currency).is_ok()
+}
+
+/// The target currency that selects all NFTs. Each NFT is a currency of its own, see `nft_currency`.
+pub(crate) const NFT: &str = "NFT";
+
+/// The currency of an NFT, its contract and token id, e.g.
+/// `0xbc4ca0eda7647a8ab7c2061c2e118a18a936f13d:1234`.
+#[cfg(feature = "native")]
+pub(crate) fn nft_currency(contract: &str, token_id: &str) -> Currency {
+    format!("{}:{}", contract.to_lowercase(), token_id)
+}
+
+pub(crate) fn is_nft(currency: &str) -> bool {
+    currency.starts_with("0x") && currency.contains(':')
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub(crate) enum Money {
     Cash(Cash),
@@ -98,14 +451,16 @@ impl Money {
         }
     }
 
-    pub(crate) fn to_net_income(&self, costs: &Vec<Money>) -> Option<Decimal> {
+    /// The income less the costs, if all are in the base currency. Fails if it overflows, see
+    /// `decimal::add`.
+    pub(crate) fn to_net_income(&self, costs: &[Money]) -> Result<Option<Decimal>> {
         let all_cash = costs.iter().all(|c| c.is_cash());
         match (self, all_cash) {
             (Money::Cash(cash), true) => {
-                let cost = costs.iter().fold(dec!(0), |acc, c| acc + c.amount());
-                Some(cash.amount + cost)
+                let cost = decimal::sum(costs.iter().map(|c| c.amount()))?;
+                Ok(Some(decimal::add(cash.amount, cost)?))
             }
-            _ => None
+            _ => Ok(None)
         }
     }
 }
@@ -130,4 +485,111 @@ pub(crate) struct Coupon {
     pub(crate) currency: Currency,
     pub(crate) amount: Decimal,
     pub(crate) date: String
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cryptotax::{calculate_transactions, Options};
+    use crate::transaction::*;
+    use rust_decimal_macros::dec;
+    use std::error::Error;
+
+    #[test]
+    fn should_build_valid_transactions() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let buy = || Transaction::buy().currency("BTC").amount(dec!(0.1)).exchanged_currency("SEK").exchanged_amount(dec!(-30000));
+
+        /*
+         * When
+         */
+        let txn = buy().fee(dec!(-0.001)).date("2022-05-02 08:00:00").build()?;
+
+        /*
+         * Then
+         */
+        assert_eq!(txn.r#type, TransactionType::Buy);
+        assert_eq!((txn.paid_currency.as_str(), txn.paid_amount, txn.paid_fee), ("BTC", dec!(0.1), dec!(-0.001)));
+        assert_eq!((txn.exchanged_currency.as_str(), txn.exchanged_amount), ("SEK", dec!(-30000)));
+        assert_eq!(buy().date("2022-05-02T08:00:00Z").build()?.date, "2022-05-02 08:00:00");
+        assert!(buy().build().is_err());                                                  // No date
+        assert!(buy().date("2022-05-32 08:00:00").build().is_err());
+        assert!(buy().amount(dec!(-0.1)).date("2022-05-02 08:00:00").build().is_err());   // A negative buy
+        assert!(Transaction::sell().currency("BTC").amount(dec!(-0.1)).date("2022-05-02 08:00:00").build().is_err());
+        assert!(Transaction::transfer().currency("BTC").amount(dec!(-0.1)).date("2022-05-02 08:00:00").build().is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn should_calculate_built_transactions() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let txns = vec![
+            Transaction::buy().currency("BTC").amount(dec!(0.1)).exchanged_currency("SEK").exchanged_amount(dec!(-30000)).date("2022-05-02 08:00:00").build()?,
+            Transaction::sell().currency("BTC").amount(dec!(-0.05)).exchanged_currency("SEK").exchanged_amount(dec!(20000)).date("2022-06-01 10:00:00").build()?,
+            Transaction::buy().currency("ETH").amount(dec!(1)).exchanged_currency("SEK").exchanged_amount(dec!(-20000)).date("2022-06-01 11:00:00").build()?,
+        ];
+
+        /*
+         * When
+         */
+        let report = calculate_transactions(&txns, &"BTC".to_string(), &"SEK".to_string(), &Options::default())?;
+
+        /*
+         * Then
+         */
+        assert_eq!(report.disposals.len(), 1);
+        assert_eq!((report.disposals[0].amount, report.disposals[0].costs), (dec!(-0.05), dec!(-15000)));
+        assert_eq!(report.disposals[0].net_income, Some(dec!(5000)));
+        Ok(())
+    }
+
+    #[test]
+    fn should_share_origins_until_a_view_adds_to_them() {
+        /*
+         * Given
+         */
+        let origin = |line: u64| Origin::new(line, format!("Exchanged from SEK {}", line));
+        let mut pool = Origins::from(vec![origin(2), origin(3)]);
+        let sold = pool.clone();
+
+        /*
+         * When
+         */
+        pool.push(origin(4));
+        let mut branch = sold.clone();
+        branch.push(origin(5));
+        pool.push(origin(6));
+
+        /*
+         * Then
+         */
+        let lines = |origins: &Origins| origins.with(|o| o.iter().flat_map(|o| o.lines.clone()).collect::<Vec<u64>>());
+        assert_eq!(lines(&sold), vec![2, 3]);
+        assert_eq!(lines(&branch), vec![2, 3, 5]);
+        assert_eq!(lines(&pool), vec![2, 3, 4, 6]);
+        assert_eq!(sold, Origins::from(vec![origin(2), origin(3)]));
+        assert_ne!(branch, pool);
+    }
+
+    #[test]
+    fn should_send_origins_to_other_threads() {
+        /*
+         * Given
+         */
+        let origin = Origin::new(2, "Exchanged to BTC".to_string());
+        let origins = Origins::from(vec![origin.clone()]);
+
+        /*
+         * When
+         */
+        let sent = std::thread::spawn(move || origins.to_vec()).join();
+
+        /*
+         * Then
+         */
+        assert_eq!(sent.ok(), Some(vec![origin]));
+    }
+}
@@ -0,0 +1,216 @@
+use crate::audit;
+use crate::calculator::TaxableTransaction;
+use crate::error::Result;
+use crate::lots::LotRow;
+use crate::summary::SummaryRow;
+use crate::transaction::Transaction;
+use crate::warnings::Warning;
+use crate::writer;
+use csv::StringRecord;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style};
+use ratatui::widgets::{Block, Paragraph, Row, Table, TableState, Tabs};
+use ratatui::Frame;
+use serde::Serialize;
+
+/// How many of the latest transactions are shown.
+const RECENT: usize = 100;
+
+/// The pane of the disposals, which can be drilled down into.
+const DISPOSALS: usize = 3;
+
+const HELP: &str = "←/→ pane  ↑/↓ select  enter lots of a disposal  esc back  q quit";
+
+/// A table of the dashboard, with its selected row.
+#[derive(Debug)]
+struct Pane {
+    title: String,
+    header: StringRecord,
+    rows: Vec<StringRecord>,
+    selected: usize,
+}
+
+impl Pane {
+    fn new(title: &str, header: StringRecord, rows: Vec<StringRecord>) -> Pane {
+        Pane{ title: title.to_string(), header, rows, selected: 0 }
+    }
+
+    /// A pane of the rows of a report, with the columns of its csv, see `writer::records`.
+    fn of<S: Serialize>(title: &str, rows: &[S]) -> Result<Pane> {
+        let mut records = writer::records(rows)?.into_iter();
+        let header = records.next().unwrap_or_default();
+        Ok(Pane::new(title, header, records.collect()))
+    }
+
+    fn select(&mut self, step: isize) {
+        let last = self.rows.len().saturating_sub(1);
+        self.selected = self.selected.saturating_add_signed(step).min(last);
+    }
+
+    fn draw(&self, frame: &mut Frame, area: ratatui::layout::Rect) {
+        let block = Block::bordered().title(format!(" {} ", self.title));
+        if self.rows.is_empty() {
+            frame.render_widget(Paragraph::new("Nothing to show").block(block), area);
+            return;
+        }
+        let widths: Vec<Constraint> = (0..self.header.len())
+            .map(|i| {
+                let width = self.rows.iter().chain([&self.header]).map(|r| r.get(i).unwrap_or_default().chars().count()).max();
+                Constraint::Length(width.unwrap_or_default() as u16)
+            })
+            .collect();
+        let header = Row::new(self.header.iter().map(str::to_string)).style(Style::new().add_modifier(Modifier::BOLD));
+        let rows = self.rows.iter().map(|r| Row::new(r.iter().map(str::to_string)));
+        let table = Table::new(rows, widths)
+            .header(header)
+            .block(block)
+            .row_highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+        let mut state = TableState::default().with_selected(Some(self.selected));
+        frame.render_stateful_widget(table, area, &mut state);
+    }
+}
+
+/// The state of the dashboard: the holdings, the latest transactions, the realized gains per
+/// year, the disposals and the warnings, each in a pane of its own, and a disposal that is
+/// drilled down into with the lots it consumed, see `audit::report`.
+#[derive(Debug)]
+pub(crate) struct Dashboard {
+    panes: Vec<Pane>,
+    tab: usize,
+    lots: Vec<Pane>,                // Of each of the disposals, in their order
+    detail: Option<usize>,
+}
+
+impl Dashboard {
+    pub(crate) fn new(holdings: &[LotRow], txns: &[Transaction], gains: &[SummaryRow], disposals: &[TaxableTransaction],
+                      warnings: &[Warning]) -> Result<Dashboard> {
+        let recent: Vec<&Transaction> = txns.iter().rev().take(RECENT).collect();
+        let warning_rows = warnings.iter()
+            .map(|w| {
+                let lines = w.lines.iter().map(|l| l.to_string()).collect::<Vec<_>>().join(" ");
+                StringRecord::from(vec![
+                    w.code.to_string(), w.severity.to_string(), w.message.clone(),
+                    w.file.clone().unwrap_or_default(), lines, w.date.clone().unwrap_or_default(),
+                ])
+            })
+            .collect();
+        let warnings_header = StringRecord::from(vec!["Code", "Severity", "Message", "File", "Lines", "Date"]);
+        let lots = disposals.iter()
+            .map(|t| Pane::of(&format!("{:?} of {} {} on {}", t.r#type, t.amount, t.currency, t.date), &audit::report(std::slice::from_ref(t))))
+            .collect::<Result<Vec<Pane>>>()?;
+        Ok(Dashboard{
+            panes: vec![
+                Pane::of("Holdings", holdings)?,
+                Pane::of("Transactions", &recent)?,
+                Pane::of("Gains", gains)?,
+                Pane::of("Disposals", disposals)?,
+                Pane::new("Warnings", warnings_header, warning_rows),
+            ],
+            tab: 0,
+            lots,
+            detail: None,
+        })
+    }
+
+    /// Handles a key that was pressed. Returns false when the dashboard is quit.
+    fn handle(&mut self, key: KeyCode) -> bool {
+        match key {
+            KeyCode::Char('q') => return false,
+            KeyCode::Esc if self.detail.is_none() => return false,
+            KeyCode::Esc | KeyCode::Backspace => self.detail = None,
+            KeyCode::Enter if self.tab == DISPOSALS && !self.lots.is_empty() => self.detail = Some(self.panes[DISPOSALS].selected),
+            KeyCode::Right | KeyCode::Tab if self.detail.is_none() => self.tab = (self.tab + 1) % self.panes.len(),
+            KeyCode::Left | KeyCode::BackTab if self.detail.is_none() => self.tab = (self.tab + self.panes.len() - 1) % self.panes.len(),
+            KeyCode::Down | KeyCode::Char('j') => self.pane_mut().select(1),
+            KeyCode::Up | KeyCode::Char('k') => self.pane_mut().select(-1),
+            KeyCode::PageDown => self.pane_mut().select(20),
+            KeyCode::PageUp => self.pane_mut().select(-20),
+            _ => {}
+        }
+        true
+    }
+
+    fn pane_mut(&mut self) -> &mut Pane {
+        match self.detail {
+            Some(i) => &mut self.lots[i],
+            None => &mut self.panes[self.tab],
+        }
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let [tabs, body, help] = Layout::vertical([Constraint::Length(3), Constraint::Min(0), Constraint::Length(1)]).areas(frame.area());
+        let titles = self.panes.iter().map(|p| format!("{} ({})", p.title, p.rows.len()));
+        frame.render_widget(
+            Tabs::new(titles)
+                .select(self.tab)
+                .block(Block::bordered().title(" cryptotax "))
+                .highlight_style(Style::new().add_modifier(Modifier::REVERSED)),
+            tabs,
+        );
+        match self.detail {
+            Some(i) => self.lots[i].draw(frame, body),
+            None => self.panes[self.tab].draw(frame, body),
+        }
+        frame.render_widget(Paragraph::new(HELP), help);
+    }
+}
+
+/// Shows the dashboard in the terminal until it is quit, and restores the terminal then.
+pub(crate) fn run(mut dashboard: Dashboard) -> Result<()> {
+    let mut terminal = ratatui::init();
+    let result = (|| loop {
+        terminal.draw(|frame| dashboard.draw(frame))?;
+        if let Event::Key(key) = event::read()? {
+            if key.kind == KeyEventKind::Press && !dashboard.handle(key.code) {
+                return Ok(());
+            }
+        }
+    })();
+    ratatui::restore();
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use crate::calculator;
+    use crate::cryptotax::Options;
+    use crate::tui::*;
+    use futures::executor::block_on;
+    use ratatui::backend::TestBackend;
+    use ratatui::Terminal;
+    use rust_decimal_macros::dec;
+    use std::error::Error;
+
+    #[test]
+    fn should_drill_down_into_lots_of_disposal() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let txns = vec![
+            Transaction::buy().currency("BTC").amount(dec!(1)).exchanged_currency("SEK").exchanged_amount(dec!(-100000)).date("2022-01-01 10:00:00").build()?,
+            Transaction::sell().currency("BTC").amount(dec!(-0.5)).exchanged_currency("SEK").exchanged_amount(dec!(70000)).date("2022-02-01 10:00:00").build()?,
+        ];
+        let calculation = block_on(calculator::calculate(&txns, &"BTC".to_string(), &"SEK".to_string(), &Options::default()))?;
+        let mut dashboard = Dashboard::new(&[], &txns, &[], &calculation.taxables, &[])?;
+        let mut terminal = Terminal::new(TestBackend::new(120, 12))?;
+
+        /*
+         * When
+         */
+        [KeyCode::Right, KeyCode::Right, KeyCode::Right, KeyCode::Enter].into_iter().for_each(|key| { dashboard.handle(key); });
+        terminal.draw(|frame| dashboard.draw(frame))?;
+        let quit = !dashboard.handle(KeyCode::Char('q'));
+
+        /*
+         * Then
+         */
+        let screen: String = terminal.backend().buffer().content().iter().map(|c| c.symbol()).collect();
+        assert!(screen.contains("Sell of -0.5 BTC on 2022-02-01 10:00:00"));
+        assert!(screen.contains("Disposal"));
+        assert!(screen.contains("Lot"));
+        assert!(screen.contains("Disposals (1)"));
+        assert!(quit);
+        Ok(())
+    }
+}
@@ -0,0 +1,303 @@
+use crate::error::Result;
+use crate::transaction::Origin;
+use serde::Serialize;
+use std::cell::RefCell;
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+use tracing::debug;
+
+/// What a warning is about. The codes are stable, so that tools can match on them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub(crate) enum Code {
+    /// An exchange row whose description is not "Exchanged to" or "Exchanged from" in any of the
+    /// known languages.
+    #[serde(rename = "W001")]
+    UnknownDescription,
+    /// One side of an exchange without the other side, or with another side at another time.
+    #[serde(rename = "W002")]
+    UnmatchedRow,
+    /// A trade that is not valued in the base currency, so that its gain is left out of the sums.
+    #[serde(rename = "W003")]
+    MissingPrice,
+    /// A disposal of more than is held, e.g. because earlier buys are missing from the export.
+    #[serde(rename = "W004")]
+    NegativeHolding,
+    /// An on-chain transfer that is not the other side of a withdrawal from or a deposit to an
+    /// exchange, e.g. a payment, which is not a disposal unless overridden.
+    #[serde(rename = "W005")]
+    #[cfg_attr(not(feature = "native"), allow(dead_code))]   // Only the wallets report it
+    UnmatchedTransfer,
+    /// Transactions in a wrapped or bridged currency that are pooled with the currency it is
+    /// equivalent to, see `equivalence::apply`.
+    #[serde(rename = "W006")]
+    EquivalentCurrency,
+    /// Rows of a `Type` that is not in the registry of the account statement, which are left out,
+    /// see `reader::TYPES`.
+    #[serde(rename = "W007")]
+    UnknownType,
+    /// A trade whose price, implied by its rows, deviates from the market price by more than
+    /// `price::MAX_DEVIATION` percent, e.g. because rows of different exchanges were paired.
+    #[serde(rename = "W008")]
+    PriceDeviation,
+    /// A row of the account statement that is the same as another, down to the balance after it,
+    /// so it was exported twice and is collapsed, see `reader::collapse_duplicates`.
+    #[serde(rename = "W009")]
+    DuplicateRow,
+    /// A buy, sell or income of a zero amount that is left out, or of dust that is aggregated or
+    /// ignored, see `dust::apply`.
+    #[serde(rename = "W010")]
+    Dust,
+    /// A transaction dated after the time of the run, e.g. because of a wrong date format or
+    /// clock, see `reader::check_future_dates`.
+    #[serde(rename = "W011")]
+    FutureDate,
+    /// A row of the account statement that was completed before it was started.
+    #[serde(rename = "W012")]
+    CompletedBeforeStarted,
+    /// Exchange rows that are in neither date order, e.g. of statements that were concatenated,
+    /// which are sorted before they are paired, see `reader::sort_for_pairing`.
+    #[serde(rename = "W013")]
+    OutOfOrder,
+}
+
+impl Code {
+    fn severity(&self) -> Severity {
+        match self {
+            Code::UnknownDescription | Code::UnmatchedRow | Code::UnknownType | Code::PriceDeviation | Code::DuplicateRow => Severity::Warning,
+            Code::FutureDate | Code::CompletedBeforeStarted => Severity::Warning,
+            Code::MissingPrice | Code::UnmatchedTransfer | Code::EquivalentCurrency | Code::Dust | Code::OutOfOrder => Severity::Info,
+            Code::NegativeHolding => Severity::Error,
+        }
+    }
+}
+
+impl fmt::Display for Code {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Code::UnknownDescription => write!(f, "W001"),
+            Code::UnmatchedRow => write!(f, "W002"),
+            Code::MissingPrice => write!(f, "W003"),
+            Code::NegativeHolding => write!(f, "W004"),
+            Code::UnmatchedTransfer => write!(f, "W005"),
+            Code::EquivalentCurrency => write!(f, "W006"),
+            Code::UnknownType => write!(f, "W007"),
+            Code::PriceDeviation => write!(f, "W008"),
+            Code::DuplicateRow => write!(f, "W009"),
+            Code::Dust => write!(f, "W010"),
+            Code::FutureDate => write!(f, "W011"),
+            Code::CompletedBeforeStarted => write!(f, "W012"),
+            Code::OutOfOrder => write!(f, "W013"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Severity {
+    /// The report is complete, but may need attention.
+    Info,
+    /// Some rows may be missing from the report.
+    Warning,
+    /// The report is wrong unless the input is fixed.
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Info => write!(f, "info"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// Something in the input that the run could not handle as expected, with where it was found.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(crate) struct Warning {
+    pub(crate) code: Code,
+    pub(crate) severity: Severity,
+    pub(crate) message: String,
+    pub(crate) file: Option<String>,
+    pub(crate) lines: Vec<u64>,
+    pub(crate) date: Option<String>,
+}
+
+impl Warning {
+    pub(crate) fn new(code: Code, message: String) -> Warning {
+        Warning{ code, severity: code.severity(), message, file: None, lines: vec![], date: None }
+    }
+
+    pub(crate) fn at_line(mut self, line: u64) -> Warning {
+        self.lines.push(line);
+        self
+    }
+
+    pub(crate) fn at_date(mut self, date: &str) -> Warning {
+        self.date = Some(date.to_string());
+        self
+    }
+
+    /// Locates the warning at the rows that a transaction was read from.
+    pub(crate) fn at(mut self, origin: &Origin) -> Warning {
+        if !origin.file.is_empty() {
+            self.file = Some(origin.file.clone());
+        }
+        self.lines = origin.lines.clone();
+        self
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}[{}]: {}", self.severity, self.code, self.message)?;
+        let lines = self.lines.iter().map(|l| l.to_string()).collect::<Vec<String>>().join(",");
+        let location = match (&self.file, lines.is_empty()) {
+            (Some(file), false) => Some(format!("{}:{}", file, lines)),
+            (Some(file), true) => Some(file.clone()),
+            (None, false) => Some(format!("line {}", lines)),
+            (None, true) => None,
+        };
+        let location = [location, self.date.clone()].into_iter().flatten().collect::<Vec<String>>();
+        match location.is_empty() {
+            true => Ok(()),
+            false => write!(f, " ({})", location.join(", ")),
+        }
+    }
+}
+
+thread_local! {
+    // A run reads and calculates on one thread, see `futures::executor::block_on`
+    static WARNINGS: RefCell<Vec<Warning>> = const { RefCell::new(vec![]) };
+    static FILE: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Collects the warning, to be printed at the end of the run. A warning without a file is
+/// located in the file that is being read, see `in_file`.
+pub(crate) fn report(mut warning: Warning) {
+    if warning.file.is_none() {
+        warning.file = FILE.with(|f| f.borrow().clone());
+    }
+    debug!(code = %warning.code, message = %warning.message, "Warning");
+    WARNINGS.with(|w| {
+        // The same file is read once for every currency of a run
+        let mut warnings = w.borrow_mut();
+        if !warnings.contains(&warning) {
+            warnings.push(warning);
+        }
+    });
+}
+
+/// Returns the warnings collected so far, and clears them.
+pub(crate) fn take() -> Vec<Warning> {
+    WARNINGS.with(|w| w.take())
+}
+
+/// Whether a warning that rows may be missing or wrong was collected so far, that is of a
+/// severity above `Severity::Info`.
+pub(crate) fn need_attention() -> bool {
+    WARNINGS.with(|w| w.borrow().iter().any(|w| w.severity != Severity::Info))
+}
+
+/// Locates the warnings reported without a file in `file`, until the returned guard is dropped.
+pub(crate) fn in_file(file: &str) -> FileGuard {
+    FILE.with(|f| *f.borrow_mut() = Some(file.to_string()));
+    FileGuard
+}
+
+pub(crate) struct FileGuard;
+
+impl Drop for FileGuard {
+    fn drop(&mut self) {
+        FILE.with(|f| *f.borrow_mut() = None);
+    }
+}
+
+/// How the warnings are printed at the end of a run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WarningFormat {
+    /// One line per warning, for people.
+    #[default]
+    Text,
+    /// A JSON array of the warnings, for tools. Printed even if there are no warnings.
+    Json,
+}
+
+impl FromStr for WarningFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_uppercase().as_str() {
+            "TEXT" => Ok(WarningFormat::Text),
+            "JSON" => Ok(WarningFormat::Json),
+            _ => Err(format!("Unknown warning format `{}`. Supported: TEXT, JSON", s)),
+        }
+    }
+}
+
+impl fmt::Display for WarningFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WarningFormat::Text => write!(f, "TEXT"),
+            WarningFormat::Json => write!(f, "JSON"),
+        }
+    }
+}
+
+/// Writes the warnings to `out`, e.g. `std::io::stderr()` so that they don't mix with the report.
+pub(crate) fn print<W: io::Write>(warnings: &[Warning], format: &WarningFormat, mut out: W) -> Result<()> {
+    match format {
+        WarningFormat::Text => {
+            for warning in warnings {
+                writeln!(out, "{}", warning)?;
+            }
+        }
+        WarningFormat::Json => {
+            serde_json::to_writer_pretty(&mut out, warnings)?;
+            writeln!(out)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::warnings::*;
+    use std::error::Error;
+
+    #[test]
+    fn should_collect_warnings_in_file() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let guard = in_file("transactions_history.csv");
+        report(Warning::new(Code::UnmatchedRow, "Exchange without its other side".to_string())
+            .at_line(12)
+            .at_date("2022-01-01 10:00:00"));
+        drop(guard);
+        report(Warning::new(Code::MissingPrice, "Not valued".to_string()));
+
+        /*
+         * When
+         */
+        let warnings = take();
+        let mut text = vec![];
+        print(&warnings, &WarningFormat::Text, &mut text)?;
+        let mut json = vec![];
+        print(&warnings[..1], &WarningFormat::Json, &mut json)?;
+
+        /*
+         * Then
+         */
+        assert_eq!(take(), vec![]);
+        assert_eq!(String::from_utf8(text)?,
+                   "warning[W002]: Exchange without its other side (transactions_history.csv:12, 2022-01-01 10:00:00)\n\
+                    info[W003]: Not valued\n");
+        let json: serde_json::Value = serde_json::from_slice(&json)?;
+        assert_eq!(json[0]["code"], "W002");
+        assert_eq!(json[0]["severity"], "warning");
+        assert_eq!(json[0]["lines"][0], 12);
+        Ok(())
+    }
+}
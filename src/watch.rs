@@ -0,0 +1,113 @@
+use crate::error::Result;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use tracing::debug;
+
+/// The size and the modification time of a file, which change when the file is written to.
+type Stamp = (u64, Option<SystemTime>);
+
+/// A drop folder for export files. A file is ready to be imported once it is unchanged since the
+/// previous scan, so that a file that is still being copied or downloaded is not read half way.
+pub(crate) struct Folder {
+    dir: PathBuf,
+    ignored: Vec<PathBuf>,
+    pending: HashMap<PathBuf, Stamp>,
+    imported: HashMap<PathBuf, Stamp>,
+}
+
+impl Folder {
+    /// Watches the csv files in `dir`, also gzipped or zipped, except `ignored`, e.g. the report
+    /// that is written there.
+    pub(crate) fn new(dir: &Path, ignored: &[&Path]) -> Folder {
+        Folder{
+            dir: dir.to_path_buf(),
+            ignored: ignored.iter().map(|p| p.to_path_buf()).collect(),
+            pending: HashMap::new(),
+            imported: HashMap::new(),
+        }
+    }
+
+    /// Scans the folder and returns the files that are new or changed, and that have been
+    /// unchanged since the previous scan, sorted by name.
+    pub(crate) fn ready(&mut self) -> Result<Vec<PathBuf>> {
+        let mut ready = vec![];
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if !self.is_export(&path) {
+                continue;
+            }
+            let metadata = fs::metadata(&path)?;
+            let stamp = (metadata.len(), metadata.modified().ok());
+            if self.imported.get(&path) == Some(&stamp) {
+                continue;
+            }
+            match self.pending.insert(path.clone(), stamp) {
+                Some(previous) if previous == stamp => {
+                    self.pending.remove(&path);
+                    self.imported.insert(path.clone(), stamp);
+                    ready.push(path);
+                }
+                _ => debug!(path = %path.display(), "Waiting for file to settle"),
+            }
+        }
+        ready.sort();
+        Ok(ready)
+    }
+
+    fn is_export(&self, path: &Path) -> bool {
+        let name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+        let is_export = path.extension().and_then(|e| e.to_str()).is_some_and(|e| ["csv", "zip", "gz"].iter().any(|x| e.eq_ignore_ascii_case(x)));
+        let is_ignored = self.ignored.iter().any(|i| same_file(i, path));
+        path.is_file() && is_export && !name.starts_with('.') && !is_ignored
+    }
+}
+
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::watch::*;
+    use std::error::Error;
+    use std::io::Write;
+
+    #[test]
+    fn should_import_files_once_settled() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let dir = tempfile::tempdir()?;
+        let export = dir.path().join("transactions_history.csv");
+        let report = dir.path().join("report.csv");
+        fs::write(&export, "Type,Started Date\n")?;
+        fs::write(&report, "Date;Currency\n")?;
+        fs::write(dir.path().join("notes.txt"), "not an export")?;
+        let mut folder = Folder::new(dir.path(), &[&report]);
+
+        /*
+         * When
+         */
+        let first = folder.ready()?;
+        let second = folder.ready()?;
+        let third = folder.ready()?;
+        fs::OpenOptions::new().append(true).open(&export)?.write_all(b"Exchange,2022-01-01\n")?;
+        let fourth = folder.ready()?;
+        let fifth = folder.ready()?;
+
+        /*
+         * Then
+         */
+        assert_eq!(first, Vec::<PathBuf>::new());
+        assert_eq!(second, vec![export.clone()]);
+        assert_eq!(third, Vec::<PathBuf>::new());
+        assert_eq!(fourth, Vec::<PathBuf>::new());
+        assert_eq!(fifth, vec![export]);
+        Ok(())
+    }
+}
@@ -1,18 +1,76 @@
-use csv::WriterBuilder;
+use crate::error::Result;
+use crate::locale::Locale;
+use comfy_table::presets::UTF8_FULL_CONDENSED;
+#[cfg(feature = "native")]
+use comfy_table::Color;
+use comfy_table::{Cell, CellAlignment, Table};
+use csv::{ReaderBuilder, StringRecord, WriterBuilder};
+use rust_decimal::Decimal;
 use serde::Serialize;
+use std::fmt;
+use std::fs::File;
 use std::io;
+use std::path::Path;
+use std::str::FromStr;
+#[cfg(feature = "native")]
+use tempfile::NamedTempFile;
+use tracing::instrument;
+
+/// How the reports are written.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub enum ReportFormat {
+    /// A csv file separated by `;`, for spreadsheets and tools.
+    #[default]
+    Csv,
+    /// A table with aligned columns, for people to read in a terminal.
+    Table,
+    /// A JSON array of the rows, with the columns as keys, for tools and plotting.
+    Json,
+    /// A format of `Options::reports`, by name, for the tax report.
+    Custom(String),
+}
+
+impl FromStr for ReportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_uppercase().as_str() {
+            "CSV" => Ok(ReportFormat::Csv),
+            "TABLE" => Ok(ReportFormat::Table),
+            "JSON" => Ok(ReportFormat::Json),
+            "" => Err("No report format".to_string()),
+            _ => Ok(ReportFormat::Custom(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for ReportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReportFormat::Csv => write!(f, "CSV"),
+            ReportFormat::Table => write!(f, "TABLE"),
+            ReportFormat::Json => write!(f, "JSON"),
+            ReportFormat::Custom(name) => write!(f, "{}", name),
+        }
+    }
+}
 
 /// Wraps the `stdout.lock()` in a `csv::Writer` and writes the rows.
 /// The `csv::Writer` is already buffered so there is no need to wrap
 /// `stdout.lock()` in a `io::BufWriter`.
-pub(crate) async fn print<S: Serialize>(rows: &Vec<S>) -> io::Result<()>{
+pub(crate) async fn print<S: Serialize>(rows: &[S]) -> Result<()>{
     let stdout = io::stdout();
-    let lock = stdout.lock();
+    write(rows, stdout.lock()).await
+}
+
+/// Writes the rows to `out` like `print`, e.g. to a report file.
+#[instrument(name = "report", skip_all, fields(rows = rows.len()))]
+pub(crate) async fn write<S: Serialize, W: io::Write>(rows: &[S], out: W) -> Result<()>{
     let mut wtr =
         WriterBuilder::new()
             .has_headers(true)
             .delimiter(b';')
-            .from_writer(lock);
+            .from_writer(out);
 
     let mut err = None;
     rows.iter().for_each(|row|
@@ -23,5 +81,188 @@ pub(crate) async fn print<S: Serialize>(rows: &Vec<S>) -> io::Result<()>{
             })
     );
     err.map_or(Ok(()), Err)?;
+    wtr.flush()?;
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Writes the rows to `out` like `write`, as a JSON array instead.
+pub(crate) fn write_json<S: Serialize, W: io::Write>(rows: &[S], mut out: W) -> Result<()> {
+    serde_json::to_writer_pretty(&mut out, rows)?;
+    writeln!(out)?;
+    Ok(out.flush()?)
+}
+
+/// Columns of numbers that are not amounts, which are neither grouped nor aligned.
+const TEXT_COLUMNS: [&str; 3] = ["Year", "Tax Year", "Lines"];
+
+/// Serializes the rows to the records of a csv file, the header first, e.g. to write them in
+/// another format with the same columns.
+pub(crate) fn records<S: Serialize>(rows: &[S]) -> Result<Vec<StringRecord>> {
+    let mut csv = vec![];
+    let mut wtr = WriterBuilder::new().has_headers(true).from_writer(&mut csv);
+    rows.iter().try_for_each(|row| wtr.serialize(row))?;
+    wtr.flush()?;
+    drop(wtr);
+    let mut rdr = ReaderBuilder::new().has_headers(false).from_reader(csv.as_slice());
+    Ok(rdr.records().collect::<Result<Vec<_>, _>>()?)
+}
+
+/// Writes the rows to `out` like `write`, as a table for the terminal instead. The amounts are
+/// aligned to the right, and the amounts and the dates are written in the way of the `locale`. If
+/// `styled`, and `std::io::stdout()` is a terminal, gains are green and losses red. Without the
+/// `native` feature the table is never styled.
+#[instrument(name = "report", skip_all, fields(rows = rows.len()))]
+pub(crate) async fn write_table<S: Serialize, W: io::Write>(rows: &[S], locale: &Locale, styled: bool, mut out: W) -> Result<()> {
+    let mut records = records(rows)?.into_iter();
+    let header = records.next().unwrap_or_default();
+    let mut table = Table::new();
+    table.load_preset(UTF8_FULL_CONDENSED);
+    #[cfg(feature = "native")]
+    if !styled {
+        table.force_no_tty();
+    }
+    #[cfg(not(feature = "native"))]
+    let _ = styled;
+    table.set_header(header.iter());
+    for record in records {
+        table.add_row(record.iter().zip(header.iter()).map(|(field, column)| cell(field, column, locale)));
+    }
+    writeln!(out, "{}", table)?;
+    Ok(out.flush()?)
+}
+
+fn cell(field: &str, column: &str, locale: &Locale) -> Cell {
+    if column.starts_with("Date") {
+        return Cell::new(locale.date(field));
+    }
+    let amount = match TEXT_COLUMNS.contains(&column) {
+        true => None,
+        false => Decimal::from_str(field).ok(),
+    };
+    let amount = match amount {
+        Some(amount) => amount,
+        None => return Cell::new(field),
+    };
+    let cell = Cell::new(locale.number(&amount.to_string())).set_alignment(CellAlignment::Right);
+    let is_gain = match column {
+        "Net Income" | "Old Net Income" | "New Net Income" | "Gain or Loss" | "Delta" if !amount.is_zero() => Some(amount.is_sign_positive()),
+        "Gains" if !amount.is_zero() => Some(true),
+        "Losses" if !amount.is_zero() => Some(false),
+        _ => None,
+    };
+    style(cell, is_gain)
+}
+
+/// Colors a gain green and a loss red.
+#[cfg(feature = "native")]
+fn style(cell: Cell, is_gain: Option<bool>) -> Cell {
+    match is_gain {
+        Some(true) => cell.fg(Color::Green),
+        Some(false) => cell.fg(Color::Red),
+        None => cell,
+    }
+}
+
+/// Leaves the cell as it is, as tables are not styled without the `native` feature.
+#[cfg(not(feature = "native"))]
+fn style(cell: Cell, _is_gain: Option<bool>) -> Cell {
+    cell
+}
+
+/// Writes a file at `path` with `write` through a temporary file in the same directory, which
+/// is renamed to `path` once it is written. A report that fails half-way leaves the file that
+/// was at `path` as it was.
+#[cfg(feature = "native")]
+pub(crate) fn atomically<F: FnOnce(&mut File) -> Result<()>>(path: &Path, write: F) -> Result<()> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let mut tmp = NamedTempFile::new_in(dir)?;
+    write(tmp.as_file_mut())?;
+    tmp.as_file().sync_all()?;
+    tmp.persist(path).map_err(|e| e.error)?;
+    Ok(())
+}
+
+/// Writes a file at `path` with `write` like `atomically`, straight to the file, as there are no
+/// temporary files without the `native` feature.
+#[cfg(not(feature = "native"))]
+pub(crate) fn atomically<F: FnOnce(&mut File) -> Result<()>>(path: &Path, write: F) -> Result<()> {
+    write(&mut File::create(path)?)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::writer::*;
+    use futures::executor::block_on;
+    use rust_decimal_macros::dec;
+    use std::error::Error;
+    #[cfg(feature = "native")]
+    use std::fs;
+    #[cfg(feature = "native")]
+    use tempfile::TempDir;
+
+    #[test]
+    fn should_write_a_table_with_aligned_amounts() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        #[derive(Serialize)]
+        struct Row {
+            #[serde(rename = "Date")]
+            date: &'static str,
+
+            #[serde(rename = "Net Income")]
+            net_income: Decimal,
+        }
+        let rows = [
+            Row{ date: "2021-12-31", net_income: dec!(-1234.5) },
+            Row{ date: "2022", net_income: dec!(12) },
+        ];
+        let locale: Locale = "de-DE".parse()?;
+
+        /*
+         * When
+         */
+        let mut out = vec![];
+        block_on(write_table(&rows, &locale, false, &mut out))?;
+
+        /*
+         * Then
+         */
+        let table = String::from_utf8(out)?;
+        let lines: Vec<_> = table.lines().collect();
+        assert_eq!(lines[1], "│ Date       ┆ Net Income │");
+        assert_eq!(lines[3], "│ 31.12.2021 ┆   -1.234,5 │");
+        assert_eq!(lines[4], "│ 2022       ┆         12 │");
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "native")]
+    fn should_keep_the_old_report_when_writing_fails() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let dir = TempDir::new()?;
+        let path = dir.path().join("report.csv");
+        atomically(&path, |file| block_on(write(&[("BTC", 1)], file)))?;
+
+        /*
+         * When
+         */
+        let failed = atomically(&path, |file| {
+            block_on(write(&[("BTC", 2)], &mut *file))?;
+            Err(io::Error::new(io::ErrorKind::Interrupted, "Interrupted").into())
+        });
+
+        /*
+         * Then
+         */
+        assert!(failed.is_err());
+        assert_eq!(fs::read_to_string(&path)?, "BTC;1\n");
+        assert_eq!(fs::read_dir(dir.path())?.count(), 1);
+        Ok(())
+    }
+}
@@ -0,0 +1,98 @@
+use crate::error::Result;
+use crate::writer;
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use rust_xlsxwriter::{Format, Worksheet, XlsxError};
+use serde::Serialize;
+use std::io;
+use std::str::FromStr;
+
+/// An Excel workbook of reports, one per sheet, with the same columns as the printed csv. The
+/// amounts are written as numbers, and everything else as text, so that the sheets can be summed
+/// up and annotated in a spreadsheet.
+pub(crate) struct Workbook {
+    workbook: rust_xlsxwriter::Workbook,
+}
+
+fn to_io(e: XlsxError) -> io::Error {
+    io::Error::other(e)
+}
+
+impl Workbook {
+    pub(crate) fn new() -> Workbook {
+        Workbook{ workbook: rust_xlsxwriter::Workbook::new() }
+    }
+
+    /// Adds a sheet called `name` with the rows, under a bold header.
+    pub(crate) fn add_sheet<S: Serialize>(&mut self, name: &str, rows: &[S]) -> Result<()> {
+        let mut sheet = Worksheet::new();
+        sheet.set_name(name).map_err(to_io)?;
+        let bold = Format::new().set_bold();
+        for (row, record) in writer::records(rows)?.iter().enumerate() {
+            let row = row as u32;
+            for (col, field) in record.iter().enumerate() {
+                let col = col as u16;
+                match Decimal::from_str(field).ok().and_then(|d| d.to_f64()) {
+                    _ if row == 0 => sheet.write_string_with_format(row, col, field, &bold),
+                    _ if field.is_empty() => continue,
+                    Some(number) => sheet.write_number(row, col, number),
+                    None => sheet.write_string(row, col, field),
+                }.map_err(to_io)?;
+            }
+        }
+        sheet.set_freeze_panes(1, 0).map_err(to_io)?;
+        sheet.autofit();
+        self.workbook.push_worksheet(sheet);
+        Ok(())
+    }
+
+    /// Writes the workbook as an .xlsx file to `out`.
+    pub(crate) fn write<W: io::Write>(mut self, mut out: W) -> Result<()> {
+        let bytes = self.workbook.save_to_buffer().map_err(to_io)?;
+        out.write_all(&bytes)?;
+        Ok(out.flush()?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::xlsx::*;
+    use std::error::Error;
+
+    #[derive(Serialize)]
+    struct Row {
+        #[serde(rename = "Date")]
+        date: &'static str,
+
+        #[serde(rename = "Amount")]
+        amount: Option<Decimal>,
+    }
+
+    #[test]
+    fn should_write_a_sheet_per_report() -> Result<(), Box<dyn Error>> {
+        /*
+         * Given
+         */
+        let rows = [
+            Row{ date: "2022-01-01 10:00:00", amount: Some(Decimal::new(15, 1)) },
+            Row{ date: "2022-02-01 10:00:00", amount: None },
+        ];
+        let mut workbook = Workbook::new();
+
+        /*
+         * When
+         */
+        workbook.add_sheet("Transactions", &rows)?;
+        workbook.add_sheet("Summary", &rows[..0])?;
+        let invalid = workbook.add_sheet("Summary [2022]", &rows);
+        let mut out = vec![];
+        workbook.write(&mut out)?;
+
+        /*
+         * Then
+         */
+        assert!(invalid.is_err());
+        assert!(out.starts_with(b"PK"));    // A zip archive
+        Ok(())
+    }
+}
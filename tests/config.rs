@@ -0,0 +1,49 @@
+//! Runs the binary with settings that can't be applied, to check that it fails with a config error
+//! and exit code 3 rather than panicking.
+
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+/// Runs cryptotax with `args` on a Revolut statement, with the failure summary on stderr.
+fn run(args: &[&str]) -> Output {
+    let statement = PathBuf::from("tests").join("fixtures").join("revolut_crypto_trades.csv");
+    Command::new(env!("CARGO_BIN_EXE_cryptotax"))
+        .arg(&statement)
+        .args(["-c", "BTC", "--json-errors"])
+        .args(args)
+        .output()
+        .expect("Could not run cryptotax")
+}
+
+fn assert_config(output: &Output, message: &str) {
+    let error = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(3), "{}", error);
+    assert!(error.contains(r#""kind": "config""#), "{}", error);
+    assert!(error.contains(message), "{}", error);
+}
+
+#[test]
+fn should_fail_with_config_error_of_log_file() {
+    /*
+     * When
+     */
+    let output = run(&["--log-file", "missing/cryptotax.log"]);
+
+    /*
+     * Then
+     */
+    assert_config(&output, "Could not create log file");
+}
+
+#[test]
+fn should_fail_with_config_error_of_store_key_file() {
+    /*
+     * When
+     */
+    let output = run(&["--store-key-file", "missing.key"]);
+
+    /*
+     * Then
+     */
+    assert_config(&output, "Could not read the passphrase from file");
+}
@@ -0,0 +1,77 @@
+//! Runs the inputs in `tests/fixtures/fuzz` that once made the fuzz targets in `fuzz/` panic,
+//! since the corpus and the artifacts of `cargo fuzz` are not checked in. Whatever the input, the
+//! tax report must be written or fail with an error, which is a `Parse` error if the input can't
+//! be read.
+
+use ::cryptotax::cryptotax::{write_statement_tax, CryptotaxError, Options};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+fn crash(name: &str) -> Vec<u8> {
+    fs::read(PathBuf::from("tests").join("fixtures").join("fuzz").join(name)).expect("Could not read the crash input")
+}
+
+#[test]
+fn should_fail_on_disposal_of_more_than_is_held() {
+    /*
+     * Given
+     */
+    let (statement, btc, sek) = (crash("oversell.csv"), "BTC".to_string(), "SEK".to_string());
+
+    /*
+     * When
+     */
+    let checked = write_statement_tax(&statement[..], &btc, &sek, &Options::default(), io::sink());
+    let assumed = write_statement_tax(&statement[..], &btc, &sek, &Options{ assume_zero_cost: true, ..Default::default() }, io::sink());
+
+    /*
+     * Then
+     */
+    let error = checked.expect_err("The disposal was calculated").to_string();
+    assert!(error.contains("Disposed 0.2 BTC with only 0.1 held"), "{}", error);
+    assert!(assumed.is_ok(), "{:?}", assumed);
+}
+
+#[test]
+fn should_fail_to_parse_what_is_not_a_statement() {
+    /*
+     * Given
+     */
+    let (btc, sek) = ("BTC".to_string(), "SEK".to_string());
+    let statements: [&[u8]; 3] = [b"", b"\xff\xfe\x00\x01", b"date;what\n2023-01-02;nothing\n"];
+
+    /*
+     * When
+     */
+    let results: Vec<_> = statements.iter()
+        .map(|statement| write_statement_tax(statement, &btc, &sek, &Options::default(), io::sink()))
+        .collect();
+
+    /*
+     * Then
+     */
+    for result in results {
+        assert!(matches!(result, Err(CryptotaxError::Parse{ line: Some(1), .. })), "{:?}", result);
+    }
+}
+
+#[test]
+fn should_fail_to_parse_a_row_that_cant_be_read() {
+    /*
+     * Given
+     */
+    let statement = "Type,Started Date,Completed Date,Description,Amount,Fee,Currency,Original Amount,Original Currency,Settled Amount,Settled Currency,State,Balance
+EXCHANGE,2023-01-02 10:00:00,2023-01-02 10:00:00,Exchanged to BTC,0.1,0,BTC,0.1,BTC,,,COMPLETED,0.1
+EXCHANGE,2023-01-02 10:00:00,2023-01-02 10:00:00,Exchanged to BTC,-3000x,0,SEK,-3000,SEK,,,COMPLETED,7000";
+
+    /*
+     * When
+     */
+    let result = write_statement_tax(statement.as_bytes(), &"BTC".to_string(), &"SEK".to_string(), &Options::default(), io::sink());
+
+    /*
+     * Then
+     */
+    assert!(matches!(result, Err(CryptotaxError::Parse{ line: Some(3), .. })), "{:?}", result);
+}
@@ -0,0 +1,37 @@
+//! Runs the binary with `--offline`, to check that the flag reaches the price sources from the
+//! command line.
+
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+use tempfile::NamedTempFile;
+
+#[test]
+fn should_fail_with_missing_prices_without_network() {
+    /*
+     * Given
+     */
+    let statement = PathBuf::from("tests").join("fixtures").join("revolut_crypto_trades.csv");
+    let log = NamedTempFile::new().expect("Could not create the log file");
+
+    /*
+     * When
+     */
+    let output = Command::new(env!("CARGO_BIN_EXE_cryptotax"))
+        .arg(&statement)
+        .args(["-c", "BTC", "--prices", "COINGECKO", "--offline", "--log-file"])
+        .arg(log.path())
+        .output()
+        .expect("Could not run cryptotax");
+
+    /*
+     * Then
+     */
+    let error = String::from_utf8_lossy(&output.stderr);
+    let log = fs::read_to_string(log.path()).expect("Could not read the log file");
+    assert!(!output.status.success());
+    assert!(error.contains("No price of `ETH` in `SEK` at 2022-01-10"), "{}", error);
+    assert!(error.contains("No price of `ETH` in `SEK` at 2022-06-01"), "{}", error);
+    assert!(error.contains("as --offline leaves out the price sources"), "{}", error);
+    assert_eq!(log.matches("from coingecko, as --offline forbids network access").count(), 2, "{}", log);
+}
@@ -0,0 +1,143 @@
+//! Runs the binary on a holding of 10^21 units of a token at a price of 10^8, whose value doesn't
+//! fit the 28 digits of a decimal, and on two buys of 5 × 10^28 units, whose sum doesn't, to check
+//! that every command fails with an overflow error rather than panicking.
+
+use std::env;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+/// Runs cryptotax with `args` on the SHIB fixture, with the failure summary on stderr.
+fn run(args: &[&str]) -> Output {
+    run_on("shib.csv", args)
+}
+
+/// Runs cryptotax with `args` on the fixture, with the failure summary on stderr.
+fn run_on(fixture: &str, args: &[&str]) -> Output {
+    let statement = PathBuf::from("tests").join("fixtures").join(fixture);
+    Command::new(env!("CARGO_BIN_EXE_cryptotax"))
+        .args(args)
+        .arg(&statement)
+        .args(["--json-errors"])
+        .output()
+        .expect("Could not run cryptotax")
+}
+
+fn assert_overflow(output: &Output) {
+    let error = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(2), "{}", error);
+    assert!(error.contains(r#""kind": "overflow""#), "{}", error);
+    assert!(error.contains("doesn't fit the 28 digits of a decimal"), "{}", error);
+}
+
+#[test]
+fn should_fail_with_overflow_of_summary() {
+    /*
+     * When
+     */
+    let output = run(&["summary", "-c", "SHIB", "--price", "100000000"]);
+
+    /*
+     * Then
+     */
+    assert_overflow(&output);
+}
+
+#[test]
+fn should_fail_with_overflow_of_simulated_sale() {
+    /*
+     * When
+     */
+    let output = run(&["simulate", "sell", "1000000000000000000000", "SHIB", "--price", "100000000"]);
+
+    /*
+     * Then
+     */
+    assert_overflow(&output);
+}
+
+#[test]
+fn should_read_amounts_above_u64() {
+    /*
+     * When
+     */
+    let output = run(&["summary", "-c", "SHIB", "--price", "1"]);
+
+    /*
+     * Then
+     */
+    let summary = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(summary.contains(";Unrealized;SHIB;1000000000000000000000;1000000000000000000000;-1000;"), "{}", summary);
+}
+
+#[test]
+fn should_fail_with_overflow_of_sum_in_every_command() {
+    /*
+     * Given
+     */
+    let fixture = PathBuf::from("tests").join("fixtures").join("shib_sum.csv");
+    let workbook = env::temp_dir().join("cryptotax-overflow.xlsx");
+    let commands: Vec<Vec<&str>> = vec![
+        vec!["-c", "SHIB"],
+        vec!["-c", "SHIB", "-j", "US"],
+        vec!["-c", "SHIB", "-j", "UK"],
+        vec!["-c", "SHIB", "-j", "DE"],
+        vec!["summary", "-c", "SHIB", "--price", "1"],
+        vec!["chart", "-c", "SHIB"],
+        vec!["audit", "-c", "SHIB"],
+        vec!["lots", "SHIB", "-j", "US"],
+        vec!["lots", "SHIB", "-j", "UK"],
+        vec!["form8949", "-c", "SHIB"],
+        vec!["hmrc", "-c", "SHIB"],
+        vec!["xlsx", "-c", "SHIB", "--output", workbook.to_str().unwrap()],
+        vec!["journal", "-c", "SHIB"],
+        vec!["simulate", "sell", "1", "SHIB", "--price", "1"],
+        vec!["diff", "-c", "SHIB", fixture.to_str().unwrap()],
+    ];
+
+    for args in commands {
+        /*
+         * When
+         */
+        let output = run_on("shib_sum.csv", &args);
+
+        /*
+         * Then
+         */
+        assert_overflow(&output);
+    }
+}
+
+#[test]
+fn should_list_overflowing_sum() {
+    /*
+     * When
+     */
+    let output = run_on("shib_sum.csv", &["tx", "list", "-c", "SHIB"]);
+
+    /*
+     * Then
+     */
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+}
+
+#[test]
+fn should_bundle_overflowing_sum_or_fail_with_overflow() {
+    /*
+     * Given
+     */
+    let bundle = env::temp_dir().join("cryptotax-overflow.zip");
+
+    /*
+     * When
+     */
+    let output = run_on("shib_sum.csv", &["debug-bundle", "-c", "SHIB", "--output", bundle.to_str().unwrap()]);
+
+    /*
+     * Then
+     */
+    // The amounts are scaled by a random factor, which the sum may or may not fit
+    if !output.status.success() {
+        assert_overflow(&output);
+    }
+}
@@ -0,0 +1,110 @@
+//! Runs the whole pipeline, from reading and pairing the rows of the statements in
+//! `tests/fixtures` to writing the reports, and compares the reports with the golden files in
+//! `tests/snapshots`. A change of the pairing or the tax engines that changes a report fails here.
+//! If the change is intended, review and accept the new reports with `cargo insta review`, or
+//! write them over the golden files with `INSTA_UPDATE=always cargo test --test reports`.
+
+use ::cryptotax::cryptotax::{self, Breakdown, Jurisdiction, Options, Rounding};
+use std::fs;
+use std::path::PathBuf;
+use tempfile::NamedTempFile;
+
+/// The path of a fixture relative to the root of the crate, where the tests are run, so that the
+/// paths in the reports are the same on every machine.
+fn fixture(name: &str) -> PathBuf {
+    PathBuf::from("tests").join("fixtures").join(name)
+}
+
+/// Runs a report with `Options::output` set to a temporary file, and returns what was written to
+/// it.
+fn report<F>(options: Options, run: F) -> String
+where
+    F: FnOnce(&Options) -> cryptotax::Result<()>,
+{
+    let file = NamedTempFile::new().expect("Could not create the report file");
+    let options = Options{ output: Some(file.path().to_path_buf()), ..options };
+    run(&options).expect("The report failed");
+    fs::read_to_string(file.path()).expect("Could not read the report")
+}
+
+fn sweden() -> Options {
+    Options{ jurisdiction: Jurisdiction::Sweden, rounding: Rounding::Decimals(2), ..Default::default() }
+}
+
+fn united_states() -> Options {
+    Options{ jurisdiction: Jurisdiction::UnitedStates, rounding: Rounding::Decimals(2), ..Default::default() }
+}
+
+fn united_kingdom() -> Options {
+    Options{ jurisdiction: Jurisdiction::UnitedKingdom, rounding: Rounding::Decimals(2), ..Default::default() }
+}
+
+#[test]
+fn tax_report_of_sweden() {
+    let (path, currency, base) = (fixture("revolut_sek.csv"), "BTC".to_string(), "SEK".to_string());
+    insta::assert_snapshot!(report(sweden(), |o| cryptotax::calculate_tax(&path, &currency, &base, o)));
+}
+
+#[test]
+fn tax_report_of_sweden_in_utc() {
+    let (path, currency, base) = (fixture("revolut_sek.csv"), "BTC".to_string(), "SEK".to_string());
+    let options = Options{ timezone: Some(cryptotax::Tz::UTC), ..sweden() };
+    insta::assert_snapshot!(report(options, |o| cryptotax::calculate_tax(&path, &currency, &base, o)));
+}
+
+#[test]
+fn summary_of_sweden() {
+    let (path, currency, base) = (fixture("revolut_sek.csv"), "BTC".to_string(), "SEK".to_string());
+    insta::assert_snapshot!(report(sweden(), |o| cryptotax::print_summary(&path, &currency, &base, None, Breakdown::Year, o)));
+}
+
+#[test]
+fn sale_at_new_year_in_utc_is_in_the_next_swedish_tax_year() {
+    let (path, currency, base) = (fixture("revolut_sek.csv"), "BTC".to_string(), "SEK".to_string());
+    let summary = |options| report(options, |o| cryptotax::print_summary(&path, &currency, &base, None, Breakdown::Year, o));
+
+    let local = summary(sweden());
+    let utc = summary(Options{ timezone: Some(cryptotax::Tz::UTC), ..sweden() });
+
+    // The sale at 2022-12-31 23:30:00 UTC, which is 2023-01-01 00:30:00 in Stockholm
+    assert!(local.contains("\n2023;Realized;BTC;-0.3;77000;"), "{}", local);
+    assert!(!local.contains("\n2022;Realized;"), "{}", local);
+    assert!(utc.contains("\n2022;Realized;BTC;-0.1;17000;"), "{}", utc);
+}
+
+#[test]
+fn audit_of_sweden() {
+    let (path, currency, base) = (fixture("revolut_sek.csv"), "BTC".to_string(), "SEK".to_string());
+    insta::assert_snapshot!(report(sweden(), |o| cryptotax::print_audit(&path, &currency, &base, o)));
+}
+
+#[test]
+fn tax_report_of_united_states() {
+    let (path, currency, base) = (fixture("revolut_usd.csv"), "BTC".to_string(), "USD".to_string());
+    insta::assert_snapshot!(report(united_states(), |o| cryptotax::calculate_tax(&path, &currency, &base, o)));
+}
+
+#[test]
+fn form8949_of_united_states() {
+    let (path, currency, base) = (fixture("revolut_usd.csv"), "BTC".to_string(), "USD".to_string());
+    insta::assert_snapshot!(report(united_states(), |o| cryptotax::print_form8949(&path, &currency, &base, o)));
+}
+
+#[test]
+fn hmrc_summary_of_united_kingdom() {
+    let (path, currency, base) = (fixture("revolut_gbp.csv"), "BTC".to_string(), "GBP".to_string());
+    insta::assert_snapshot!(report(united_kingdom(), |o| cryptotax::print_hmrc_summary(&path, &currency, &base, o)));
+}
+
+#[test]
+fn lots_of_united_kingdom() {
+    let (path, currency, base) = (fixture("revolut_gbp.csv"), "BTC".to_string(), "GBP".to_string());
+    insta::assert_snapshot!(report(united_kingdom(), |o| cryptotax::print_lots(&path, &currency, &base, true, o)));
+}
+
+#[test]
+fn tax_report_of_futures_in_sweden() {
+    let (path, currency, base) = (fixture("binance_futures.csv"), "USDT".to_string(), "SEK".to_string());
+    let options = Options{ price_table: Some(fixture("usdt_sek.csv")), ..sweden() };
+    insta::assert_snapshot!(report(options, |o| cryptotax::calculate_tax(&path, &currency, &base, o)));
+}
@@ -0,0 +1,91 @@
+//! Runs the binary with the statement piped to stdin, `cryptotax -`, as in
+//! `gunzip -c statement.csv.gz | cryptotax -`, which no path of a fixture goes through.
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+fn fixture(name: &str) -> Vec<u8> {
+    fs::read(PathBuf::from("tests").join("fixtures").join(name)).expect("Could not read the fixture")
+}
+
+/// Runs `cryptotax - -c BTC` with `input` on stdin.
+fn run(input: &[u8]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_cryptotax"))
+        .args(["-", "-c", "BTC"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Could not run cryptotax");
+    child.stdin.take().expect("No stdin").write_all(input).expect("Could not write to stdin");
+    child.wait_with_output().expect("Could not wait for cryptotax")
+}
+
+#[test]
+fn should_read_csv_from_stdin() {
+    /*
+     * Given
+     */
+    let statement = fixture("revolut_sek.csv");
+
+    /*
+     * When
+     */
+    let output = run(&statement);
+
+    /*
+     * Then
+     */
+    let report = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(report.starts_with("Date;Currency;Amount;Income;Cost;Net Income"), "{}", report);
+    assert_eq!(report.lines().count(), 5);
+}
+
+#[test]
+fn should_read_gzip_from_stdin() {
+    /*
+     * Given
+     */
+    let statement = fixture("revolut_sek.csv");
+    let mut encoder = GzEncoder::new(vec![], Compression::default());
+    encoder.write_all(&statement).expect("Could not gzip the statement");
+    let gzipped = encoder.finish().expect("Could not gzip the statement");
+
+    /*
+     * When
+     */
+    let plain = run(&statement);
+    let output = run(&gzipped);
+
+    /*
+     * Then
+     */
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(output.stdout, plain.stdout);
+}
+
+#[test]
+fn should_reject_store_on_stdin() {
+    /*
+     * Given
+     */
+    let store = b"SQLite format 3\0";
+
+    /*
+     * When
+     */
+    let output = run(store);
+
+    /*
+     * Then
+     */
+    let error = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(error.contains("A store can't be read from stdin"), "{}", error);
+    assert!(!PathBuf::from("-").exists());
+}